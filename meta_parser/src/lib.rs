@@ -13,6 +13,9 @@ pub struct Meta {
     pub title: Option<String>,
     pub description: Option<String>,
     pub image: Option<String>,
+    /// The canonical URL of the page, as given by `<link rel="canonical">`
+    /// or, if there is no such link, by `og:url`. The URL may be relative.
+    pub canonical: Option<String>,
 
     buffer: Vec<u8>,
 }
@@ -26,6 +29,7 @@ impl Meta {
             title: None,
             description: None,
             image: None,
+            canonical: None,
             buffer: Vec::new(),
         }
     }
@@ -73,8 +77,23 @@ impl Meta {
                                 b"twitter:image" | b"twitter:image:src" | b"og:image" => {
                                     self.image = decode_str_bytes(meta_tag.content);
                                 }
+                                b"og:url" => {
+                                    // prefer the canonical link if available
+                                    if self.canonical.is_none() {
+                                        self.canonical = decode_str_bytes(meta_tag.content);
+                                    }
+                                }
                                 _ => {}
                             },
+                            Tag::Link(link_tag) => {
+                                let is_canonical = link_tag
+                                    .rel
+                                    .split(u8::is_ascii_whitespace)
+                                    .any(|rel| rel.eq_ignore_ascii_case(b"canonical"));
+                                if is_canonical {
+                                    self.canonical = decode_str_bytes(link_tag.href);
+                                }
+                            }
                             Tag::Title(title_tag) => {
                                 if self.title.is_none() {
                                     self.title = decode_str_bytes(title_tag.title);
@@ -206,4 +225,39 @@ mod tests {
 
         assert_eq!(meta.title, None);
     }
+
+    #[test]
+    fn test_canonical() {
+        let og_example = r#"
+            <meta property="og:url" content="https://example.com/og" />
+        "#;
+        let mut meta = Meta::new();
+        meta.parse(og_example.as_bytes());
+        assert_eq!(meta.canonical, Some("https://example.com/og".into()));
+
+        // the canonical link is preferred, regardless of order
+        for link_example in [
+            r#"
+                <link rel="canonical" href="/article?id=1&amp;page=2">
+                <meta property="og:url" content="https://example.com/og" />
+            "#,
+            r#"
+                <meta property="og:url" content="https://example.com/og" />
+                <LINK href='/article?id=1&amp;page=2' type="text/html" REL="Canonical" />
+            "#,
+        ] {
+            let mut meta = Meta::new();
+            meta.parse(link_example.as_bytes());
+            assert_eq!(meta.canonical, Some("/article?id=1&page=2".into()));
+        }
+
+        // other links are ignored
+        let other_example = r#"
+            <link rel="stylesheet" href="/style.css">
+            <link rel="alternate" type="application/rss+xml" href="/feed.xml">
+        "#;
+        let mut meta = Meta::new();
+        meta.parse(other_example.as_bytes());
+        assert_eq!(meta.canonical, None);
+    }
 }
@@ -16,10 +16,17 @@ pub struct TitleTag<'a> {
     pub(super) title: &'a [u8],
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct LinkTag<'a> {
+    pub(super) rel: &'a [u8],
+    pub(super) href: &'a [u8],
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Tag<'a> {
     Meta(MetaTag<'a>),
     Title(TitleTag<'a>),
+    Link(LinkTag<'a>),
 }
 
 /// matches `<`
@@ -88,11 +95,34 @@ fn keyword_title<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8],
     Ok((rest, ()))
 }
 
+/// matches `link` (case insensitive)
+fn keyword_link<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8], (), E> {
+    let (rest, _) = tag_no_case("link")(input)?;
+    Ok((rest, ()))
+}
+
+/// matches `rel` (case insensitive)
+fn keyword_rel<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8], (), E> {
+    let (rest, _) = tag_no_case("rel")(input)?;
+    Ok((rest, ()))
+}
+
+/// matches `href` (case insensitive)
+fn keyword_href<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8], (), E> {
+    let (rest, _) = tag_no_case("href")(input)?;
+    Ok((rest, ()))
+}
+
 /// matches `< *{meta}`
 fn open_meta_tag<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8], (), E> {
     preceded(preceded(open_bracket, multispace0), keyword_meta)(input)
 }
 
+/// matches `< *{link}`
+fn open_link_tag<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8], (), E> {
+    preceded(preceded(open_bracket, multispace0), keyword_link)(input)
+}
+
 /// matches `/>` or `>`
 fn close_tag<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8], (), E> {
     alt((preceded(slash, close_bracket), close_bracket))(input)
@@ -130,8 +160,16 @@ fn uninteresting_kw_pair<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResul
     let not_name = not(keyword_name);
     let not_property = not(keyword_property);
     let not_content = not(keyword_content);
+    let not_rel = not(keyword_rel);
+    let not_href = not(keyword_href);
     let key = take_till(|i| i == b'=' || i == b'>');
-    let uninteresting_key = preceded(not_name, preceded(not_property, preceded(not_content, key)));
+    let uninteresting_key = preceded(
+        not_name,
+        preceded(
+            not_property,
+            preceded(not_content, preceded(not_rel, preceded(not_href, key))),
+        ),
+    );
     delimited(uninteresting_key, equals, text)(input)
 }
 
@@ -174,6 +212,23 @@ fn meta_tag<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8], Meta
     Ok((rest, MetaTag { name, content }))
 }
 
+/// Parses a single `<link ... />` tag.
+fn link_tag<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8], LinkTag<'a>, E> {
+    let rel = preceded(preceded(keyword_rel, equals), text);
+    let href = preceded(preceded(keyword_href, equals), text);
+
+    let rel_ws = preceded(tag_white_space, rel);
+    let href_ws = preceded(tag_white_space, href);
+
+    let rel_href = permutation((rel_ws, href_ws));
+
+    let open = preceded(open_link_tag, multispace1);
+    let close = preceded(tag_white_space, close_tag);
+
+    let (rest, (rel, href)) = delimited(open, rel_href, close)(input)?;
+    Ok((rest, LinkTag { rel, href }))
+}
+
 /// Parses a single `<title>...</title>` tag.
 fn title_tag<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8], TitleTag<'a>, E> {
     let title_open_ws = preceded(keyword_title, tag_white_space);
@@ -188,9 +243,10 @@ fn title_tag<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8], Tit
     Ok((rest, TitleTag { title }))
 }
 
-/// Parses either a meta, or a title tag.
+/// Parses either a meta, a link, or a title tag.
 pub fn run<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&[u8], Tag<'a>, E> {
     meta_tag(input)
         .map(|(rest, tag)| (rest, Tag::Meta(tag)))
+        .or_else(|_: Err<E>| link_tag(input).map(|(rest, tag)| (rest, Tag::Link(tag))))
         .or_else(|_: Err<E>| title_tag(input).map(|(rest, tag)| (rest, Tag::Title(tag))))
 }
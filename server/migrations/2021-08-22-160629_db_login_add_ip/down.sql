@@ -1 +1,24 @@
-ALTER TABLE logins DROP COLUMN last_remote_ip;
+CREATE TABLE logins_no_last_remote_ip (
+  id              VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at      TIMESTAMP NOT NULL,
+  updated_at      TIMESTAMP NOT NULL,
+
+  user_id         VARCHAR(21) NOT NULL REFERENCES users(id),
+  email_token     TEXT NOT NULL,
+  claim_until     TIMESTAMP NOT NULL,
+  claimed         BOOLEAN NOT NULL,
+  session_token   TEXT UNIQUE,
+  last_used       TIMESTAMP NOT NULL,
+  last_user_agent TEXT,
+  revoked         BOOLEAN NOT NULL
+);
+
+INSERT INTO logins_no_last_remote_ip
+SELECT id, created_at, updated_at, user_id, email_token, claim_until, claimed,
+  session_token, last_used, last_user_agent, revoked
+FROM logins;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE logins;
+ALTER TABLE logins_no_last_remote_ip RENAME TO logins;
+PRAGMA foreign_keys = ON;
@@ -0,0 +1,18 @@
+CREATE TABLE user_preferences (
+  user_id     VARCHAR(21) PRIMARY KEY NOT NULL REFERENCES users(id),
+  created_at  TIMESTAMP NOT NULL,
+  updated_at  TIMESTAMP NOT NULL,
+
+  notify_new_login BOOLEAN NOT NULL
+);
+
+CREATE TABLE known_devices (
+  id          VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at  TIMESTAMP NOT NULL,
+  updated_at  TIMESTAMP NOT NULL,
+
+  user_id     VARCHAR(21) NOT NULL REFERENCES users(id),
+  fingerprint TEXT        NOT NULL,
+  last_seen   TIMESTAMP   NOT NULL,
+  UNIQUE (user_id, fingerprint)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE security_events (
+  id          VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at  TIMESTAMP NOT NULL,
+
+  user_id     VARCHAR(21) NOT NULL REFERENCES users(id),
+  kind        TEXT NOT NULL,
+  remote_ip   TEXT,
+  user_agent  TEXT
+);
+
+CREATE INDEX security_events_user_id_created_at ON security_events(user_id, created_at);
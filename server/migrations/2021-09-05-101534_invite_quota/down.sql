@@ -0,0 +1,36 @@
+CREATE TABLE invites_no_revoked_at (
+  id         VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP NOT NULL,
+
+  token      TEXT UNIQUE NOT NULL,
+  created_by VARCHAR(21) NOT NULL REFERENCES users(id),
+  claimed_by VARCHAR(21) UNIQUE REFERENCES users(id)
+);
+
+INSERT INTO invites_no_revoked_at
+SELECT id, created_at, updated_at, token, created_by, claimed_by
+FROM invites;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE invites;
+ALTER TABLE invites_no_revoked_at RENAME TO invites;
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE users_no_invite_quota (
+  id         VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP NOT NULL,
+
+  name       TEXT NOT NULL,
+  email      TEXT UNIQUE NOT NULL
+);
+
+INSERT INTO users_no_invite_quota
+SELECT id, created_at, updated_at, name, email
+FROM users;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE users;
+ALTER TABLE users_no_invite_quota RENAME TO users;
+PRAGMA foreign_keys = ON;
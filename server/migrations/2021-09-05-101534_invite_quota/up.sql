@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN invite_quota INTEGER;
+ALTER TABLE invites ADD COLUMN revoked_at TIMESTAMP;
@@ -0,0 +1,18 @@
+CREATE TABLE users_no_last_accrual_at (
+  id           VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at   TIMESTAMP NOT NULL,
+  updated_at   TIMESTAMP NOT NULL,
+
+  name         TEXT NOT NULL,
+  email        TEXT UNIQUE NOT NULL,
+  invite_quota INTEGER
+);
+
+INSERT INTO users_no_last_accrual_at
+SELECT id, created_at, updated_at, name, email, invite_quota
+FROM users;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE users;
+ALTER TABLE users_no_last_accrual_at RENAME TO users;
+PRAGMA foreign_keys = ON;
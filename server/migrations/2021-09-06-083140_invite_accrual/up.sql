@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN invite_credits INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN last_accrual_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+
+UPDATE users SET last_accrual_at = created_at;
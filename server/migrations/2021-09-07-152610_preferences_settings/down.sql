@@ -0,0 +1,15 @@
+CREATE TABLE user_preferences_no_default_sort (
+  user_id          VARCHAR(21) PRIMARY KEY NOT NULL REFERENCES users(id),
+  created_at       TIMESTAMP NOT NULL,
+  updated_at       TIMESTAMP NOT NULL,
+  notify_new_login BOOLEAN NOT NULL
+);
+
+INSERT INTO user_preferences_no_default_sort
+SELECT user_id, created_at, updated_at, notify_new_login
+FROM user_preferences;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE user_preferences;
+ALTER TABLE user_preferences_no_default_sort RENAME TO user_preferences;
+PRAGMA foreign_keys = ON;
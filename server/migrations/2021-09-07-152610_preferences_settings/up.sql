@@ -0,0 +1,3 @@
+ALTER TABLE user_preferences ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
+ALTER TABLE user_preferences ADD COLUMN locale TEXT NOT NULL DEFAULT 'en';
+ALTER TABLE user_preferences ADD COLUMN default_sort TEXT NOT NULL DEFAULT 'ranked';
@@ -0,0 +1,21 @@
+CREATE TABLE users_no_verification_sent_at (
+  id              VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at      TIMESTAMP NOT NULL,
+  updated_at      TIMESTAMP NOT NULL,
+
+  name            TEXT NOT NULL,
+  email           TEXT UNIQUE NOT NULL,
+  invite_quota    INTEGER,
+  invite_credits  INTEGER NOT NULL DEFAULT 0,
+  last_accrual_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00'
+);
+
+INSERT INTO users_no_verification_sent_at
+SELECT id, created_at, updated_at, name, email, invite_quota, invite_credits,
+  last_accrual_at
+FROM users;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE users;
+ALTER TABLE users_no_verification_sent_at RENAME TO users;
+PRAGMA foreign_keys = ON;
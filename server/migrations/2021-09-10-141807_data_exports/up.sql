@@ -0,0 +1,12 @@
+CREATE TABLE data_exports (
+  id            VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at    TIMESTAMP NOT NULL,
+  updated_at    TIMESTAMP NOT NULL,
+
+  user_id       VARCHAR(21) NOT NULL REFERENCES users(id),
+  status        TEXT NOT NULL,
+  completed_at  TIMESTAMP,
+  storage_key   TEXT
+);
+
+CREATE INDEX data_exports_user_id_status ON data_exports(user_id, status);
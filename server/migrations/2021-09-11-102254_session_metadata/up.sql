@@ -0,0 +1,8 @@
+ALTER TABLE logins ADD COLUMN claimed_at TIMESTAMP;
+ALTER TABLE logins ADD COLUMN user_agent TEXT;
+ALTER TABLE logins ADD COLUMN remote_ip TEXT;
+
+-- the best approximation available for existing sessions
+UPDATE logins
+SET claimed_at = created_at, user_agent = last_user_agent, remote_ip = last_remote_ip
+WHERE claimed;
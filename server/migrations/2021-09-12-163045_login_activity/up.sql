@@ -0,0 +1,12 @@
+ALTER TABLE users ADD COLUMN last_login_at TIMESTAMP;
+ALTER TABLE users ADD COLUMN login_count INTEGER NOT NULL DEFAULT 0;
+
+UPDATE users SET
+  last_login_at = (
+    SELECT MAX(COALESCE(claimed_at, created_at)) FROM logins
+    WHERE logins.user_id = users.id AND claimed
+  ),
+  login_count = (
+    SELECT COUNT(*) FROM logins
+    WHERE logins.user_id = users.id AND claimed
+  );
@@ -0,0 +1,7 @@
+ALTER TABLE users ADD COLUMN invited_by VARCHAR(21) REFERENCES users(id);
+
+UPDATE users SET invited_by = (
+  SELECT created_by FROM invites WHERE invites.claimed_by = users.id
+);
+
+CREATE INDEX users_invited_by ON users(invited_by);
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN feed_token TEXT;
@@ -0,0 +1,8 @@
+CREATE TABLE blocks (
+  user_id          VARCHAR(21) NOT NULL REFERENCES users(id),
+  blocked_user_id  VARCHAR(21) NOT NULL REFERENCES users(id),
+  created_at       TIMESTAMP NOT NULL,
+  PRIMARY KEY (user_id, blocked_user_id)
+);
+
+CREATE INDEX blocks_blocked_user_id ON blocks(blocked_user_id);
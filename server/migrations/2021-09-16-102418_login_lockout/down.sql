@@ -0,0 +1,60 @@
+CREATE TABLE users_no_login_locked_until (
+  id                   VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at           TIMESTAMP NOT NULL,
+  updated_at           TIMESTAMP NOT NULL,
+
+  name                 TEXT NOT NULL,
+  email                TEXT UNIQUE NOT NULL,
+  invite_quota         INTEGER,
+  invite_credits       INTEGER NOT NULL DEFAULT 0,
+  last_accrual_at      TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+  email_verified_at    TIMESTAMP,
+  verification_sent_at TIMESTAMP,
+  last_login_at        TIMESTAMP,
+  login_count          INTEGER NOT NULL DEFAULT 0,
+  invited_by           VARCHAR(21) REFERENCES users(id),
+  feed_token           TEXT
+);
+
+INSERT INTO users_no_login_locked_until
+SELECT id, created_at, updated_at, name, email, invite_quota, invite_credits,
+  last_accrual_at, email_verified_at, verification_sent_at, last_login_at,
+  login_count, invited_by, feed_token
+FROM users;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE users;
+ALTER TABLE users_no_login_locked_until RENAME TO users;
+PRAGMA foreign_keys = ON;
+
+CREATE INDEX users_invited_by ON users(invited_by);
+
+CREATE TABLE logins_no_failed_attempts (
+  id              VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at      TIMESTAMP NOT NULL,
+  updated_at      TIMESTAMP NOT NULL,
+
+  user_id         VARCHAR(21) NOT NULL REFERENCES users(id),
+  email_token     TEXT NOT NULL,
+  claim_until     TIMESTAMP NOT NULL,
+  claimed         BOOLEAN NOT NULL,
+  session_token   TEXT UNIQUE,
+  last_used       TIMESTAMP NOT NULL,
+  last_user_agent TEXT,
+  revoked         BOOLEAN NOT NULL,
+  last_remote_ip  TEXT,
+  claimed_at      TIMESTAMP,
+  user_agent      TEXT,
+  remote_ip       TEXT
+);
+
+INSERT INTO logins_no_failed_attempts
+SELECT id, created_at, updated_at, user_id, email_token, claim_until, claimed,
+  session_token, last_used, last_user_agent, revoked, last_remote_ip,
+  claimed_at, user_agent, remote_ip
+FROM logins;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE logins;
+ALTER TABLE logins_no_failed_attempts RENAME TO logins;
+PRAGMA foreign_keys = ON;
@@ -0,0 +1,4 @@
+ALTER TABLE logins ADD COLUMN failed_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN failed_login_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN failed_login_since TIMESTAMP;
+ALTER TABLE users ADD COLUMN login_locked_until TIMESTAMP;
@@ -0,0 +1,34 @@
+CREATE TABLE users_no_banned_at (
+  id                   VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at           TIMESTAMP NOT NULL,
+  updated_at           TIMESTAMP NOT NULL,
+
+  name                 TEXT NOT NULL,
+  email                TEXT UNIQUE NOT NULL,
+  invite_quota         INTEGER,
+  invite_credits       INTEGER NOT NULL DEFAULT 0,
+  last_accrual_at      TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+  email_verified_at    TIMESTAMP,
+  verification_sent_at TIMESTAMP,
+  last_login_at        TIMESTAMP,
+  login_count          INTEGER NOT NULL DEFAULT 0,
+  invited_by           VARCHAR(21) REFERENCES users(id),
+  feed_token           TEXT,
+  failed_login_count   INTEGER NOT NULL DEFAULT 0,
+  failed_login_since   TIMESTAMP,
+  login_locked_until   TIMESTAMP
+);
+
+INSERT INTO users_no_banned_at
+SELECT id, created_at, updated_at, name, email, invite_quota, invite_credits,
+  last_accrual_at, email_verified_at, verification_sent_at, last_login_at,
+  login_count, invited_by, feed_token, failed_login_count, failed_login_since,
+  login_locked_until
+FROM users;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE users;
+ALTER TABLE users_no_banned_at RENAME TO users;
+PRAGMA foreign_keys = ON;
+
+CREATE INDEX users_invited_by ON users(invited_by);
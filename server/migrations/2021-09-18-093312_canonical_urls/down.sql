@@ -0,0 +1,24 @@
+DROP INDEX urls_canonical_url;
+
+CREATE TABLE urls_no_canonical_url (
+  id          VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at  TIMESTAMP NOT NULL,
+  updated_at  TIMESTAMP NOT NULL,
+
+  url         TEXT UNIQUE NOT NULL,
+  status_code INTEGER NOT NULL,
+  title       TEXT,
+  description TEXT,
+  image       TEXT,
+  created_by  VARCHAR(21) NOT NULL REFERENCES users(id)
+);
+
+INSERT INTO urls_no_canonical_url
+SELECT id, created_at, updated_at, url, status_code, title, description, image,
+  created_by
+FROM urls;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE urls;
+ALTER TABLE urls_no_canonical_url RENAME TO urls;
+PRAGMA foreign_keys = ON;
@@ -0,0 +1,3 @@
+ALTER TABLE urls ADD COLUMN canonical_url TEXT NOT NULL DEFAULT '';
+UPDATE urls SET canonical_url = url;
+CREATE UNIQUE INDEX urls_canonical_url ON urls(canonical_url);
@@ -0,0 +1,4 @@
+ALTER TABLE urls ADD COLUMN fetched_title TEXT;
+ALTER TABLE urls ADD COLUMN fetched_description TEXT;
+ALTER TABLE urls ADD COLUMN metadata_status TEXT NOT NULL DEFAULT 'ok';
+UPDATE urls SET fetched_title = title, fetched_description = description;
@@ -0,0 +1,12 @@
+CREATE TABLE tags (
+  name        TEXT PRIMARY KEY NOT NULL,
+  created_at  TIMESTAMP NOT NULL
+);
+
+CREATE TABLE url_tags (
+  url_id    VARCHAR(21) NOT NULL REFERENCES urls(id),
+  tag_name  TEXT NOT NULL REFERENCES tags(name),
+  PRIMARY KEY (url_id, tag_name)
+);
+
+CREATE INDEX url_tags_tag_name ON url_tags(tag_name);
@@ -0,0 +1,16 @@
+DROP INDEX tags_created_at;
+DROP INDEX tags_url_count;
+
+CREATE TABLE tags_no_url_count (
+  name       TEXT PRIMARY KEY NOT NULL,
+  created_at TIMESTAMP NOT NULL
+);
+
+INSERT INTO tags_no_url_count
+SELECT name, created_at
+FROM tags;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE tags;
+ALTER TABLE tags_no_url_count RENAME TO tags;
+PRAGMA foreign_keys = ON;
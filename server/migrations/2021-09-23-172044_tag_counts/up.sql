@@ -0,0 +1,8 @@
+ALTER TABLE tags ADD COLUMN url_count BIGINT NOT NULL DEFAULT 0;
+
+UPDATE tags SET url_count = (
+  SELECT COUNT(*) FROM url_tags WHERE url_tags.tag_name = tags.name
+);
+
+CREATE INDEX tags_url_count ON tags(url_count DESC, name);
+CREATE INDEX tags_created_at ON tags(created_at DESC, name);
@@ -0,0 +1,31 @@
+CREATE TABLE urls_no_comment_count (
+  id                  VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at          TIMESTAMP NOT NULL,
+  updated_at          TIMESTAMP NOT NULL,
+
+  url                 TEXT UNIQUE NOT NULL,
+  status_code         INTEGER NOT NULL,
+  title               TEXT,
+  description         TEXT,
+  image               TEXT,
+  created_by          VARCHAR(21) NOT NULL REFERENCES users(id),
+  canonical_url       TEXT NOT NULL DEFAULT '',
+  fetched_title       TEXT,
+  fetched_description TEXT,
+  metadata_status     TEXT NOT NULL DEFAULT 'ok',
+  edited_at           TIMESTAMP,
+  deleted_at          TIMESTAMP
+);
+
+INSERT INTO urls_no_comment_count
+SELECT id, created_at, updated_at, url, status_code, title, description, image,
+  created_by, canonical_url, fetched_title, fetched_description,
+  metadata_status, edited_at, deleted_at
+FROM urls;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE urls;
+ALTER TABLE urls_no_comment_count RENAME TO urls;
+PRAGMA foreign_keys = ON;
+
+CREATE UNIQUE INDEX urls_canonical_url ON urls(canonical_url);
@@ -0,0 +1,5 @@
+ALTER TABLE urls ADD COLUMN comment_count BIGINT NOT NULL DEFAULT 0;
+
+UPDATE urls SET comment_count = (
+  SELECT COUNT(*) FROM comments WHERE comments.url_id = urls.id
+);
@@ -0,0 +1,21 @@
+DROP INDEX comments_replies_to;
+
+CREATE TABLE comments_no_depth (
+  id         VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP NOT NULL,
+
+  comment    TEXT NOT NULL,
+  url_id     VARCHAR(21) NOT NULL REFERENCES urls(id),
+  created_by VARCHAR(21) NOT NULL REFERENCES users(id),
+  replies_to VARCHAR(21) REFERENCES comments(id)
+);
+
+INSERT INTO comments_no_depth
+SELECT id, created_at, updated_at, comment, url_id, created_by, replies_to
+FROM comments;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE comments;
+ALTER TABLE comments_no_depth RENAME TO comments;
+PRAGMA foreign_keys = ON;
@@ -0,0 +1,13 @@
+ALTER TABLE comments ADD COLUMN depth INTEGER NOT NULL DEFAULT 0;
+
+WITH RECURSIVE thread(id, depth) AS (
+  SELECT id, 0 FROM comments WHERE replies_to IS NULL
+  UNION ALL
+  SELECT comments.id, thread.depth + 1
+    FROM comments JOIN thread ON comments.replies_to = thread.id
+)
+UPDATE comments SET depth = (
+  SELECT thread.depth FROM thread WHERE thread.id = comments.id
+);
+
+CREATE INDEX comments_replies_to ON comments(replies_to);
@@ -0,0 +1,11 @@
+ALTER TABLE comments ADD COLUMN edited_at TIMESTAMP;
+ALTER TABLE comments ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE comments ADD COLUMN deletion_reason TEXT;
+ALTER TABLE comments ADD COLUMN reply_count INTEGER NOT NULL DEFAULT 0;
+
+UPDATE comments SET deleted_at = updated_at, comment = '[deleted]'
+  WHERE comment = '[DELETED]';
+
+UPDATE comments SET reply_count = (
+  SELECT COUNT(*) FROM comments AS replies WHERE replies.replies_to = comments.id
+);
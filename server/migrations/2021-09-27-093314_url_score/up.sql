@@ -0,0 +1,5 @@
+ALTER TABLE urls ADD COLUMN score BIGINT NOT NULL DEFAULT 0;
+
+UPDATE urls SET score = (
+  SELECT COUNT(*) FROM url_upvotes WHERE url_upvotes.url_id = urls.id
+);
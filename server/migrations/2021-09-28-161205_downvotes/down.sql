@@ -0,0 +1,52 @@
+DELETE FROM url_upvotes WHERE direction = 'down';
+UPDATE urls SET score = upvotes;
+
+CREATE TABLE urls_no_downvotes (
+  id                  VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at          TIMESTAMP NOT NULL,
+  updated_at          TIMESTAMP NOT NULL,
+
+  url                 TEXT UNIQUE NOT NULL,
+  status_code         INTEGER NOT NULL,
+  title               TEXT,
+  description         TEXT,
+  image               TEXT,
+  created_by          VARCHAR(21) NOT NULL REFERENCES users(id),
+  canonical_url       TEXT NOT NULL DEFAULT '',
+  fetched_title       TEXT,
+  fetched_description TEXT,
+  metadata_status     TEXT NOT NULL DEFAULT 'ok',
+  edited_at           TIMESTAMP,
+  deleted_at          TIMESTAMP,
+  comment_count       BIGINT NOT NULL DEFAULT 0,
+  score               BIGINT NOT NULL DEFAULT 0
+);
+
+INSERT INTO urls_no_downvotes
+SELECT id, created_at, updated_at, url, status_code, title, description, image,
+  created_by, canonical_url, fetched_title, fetched_description,
+  metadata_status, edited_at, deleted_at, comment_count, score
+FROM urls;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE urls;
+ALTER TABLE urls_no_downvotes RENAME TO urls;
+PRAGMA foreign_keys = ON;
+
+CREATE UNIQUE INDEX urls_canonical_url ON urls(canonical_url);
+
+CREATE TABLE url_upvotes_no_direction (
+  url_id     VARCHAR(21) NOT NULL REFERENCES urls(id),
+  user_id    VARCHAR(21) NOT NULL REFERENCES users(id),
+  created_at TIMESTAMP NOT NULL,
+  PRIMARY KEY (url_id, user_id)
+);
+
+INSERT INTO url_upvotes_no_direction
+SELECT url_id, user_id, created_at
+FROM url_upvotes;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE url_upvotes;
+ALTER TABLE url_upvotes_no_direction RENAME TO url_upvotes;
+PRAGMA foreign_keys = ON;
@@ -0,0 +1,5 @@
+ALTER TABLE url_upvotes ADD COLUMN direction TEXT NOT NULL DEFAULT 'up';
+ALTER TABLE urls ADD COLUMN upvotes BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE urls ADD COLUMN downvotes BIGINT NOT NULL DEFAULT 0;
+
+UPDATE urls SET upvotes = score;
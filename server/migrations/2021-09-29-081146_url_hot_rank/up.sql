@@ -0,0 +1,3 @@
+ALTER TABLE urls ADD COLUMN hot_rank DOUBLE NOT NULL DEFAULT 0;
+
+CREATE INDEX urls_hot_rank ON urls(hot_rank DESC, created_at DESC, id DESC);
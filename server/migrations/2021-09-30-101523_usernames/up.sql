@@ -0,0 +1,5 @@
+-- existing users get a placeholder username, which
+-- they can change in their account settings
+ALTER TABLE users ADD COLUMN username TEXT NOT NULL DEFAULT '';
+UPDATE users SET username = 'user-' || lower(id);
+CREATE UNIQUE INDEX users_username ON users(username);
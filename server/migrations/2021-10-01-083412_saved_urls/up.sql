@@ -0,0 +1,9 @@
+CREATE TABLE saved_urls (
+  id        VARCHAR(21) NOT NULL PRIMARY KEY,
+  user_id   VARCHAR(21) NOT NULL REFERENCES users(id),
+  url_id    VARCHAR(21) NOT NULL REFERENCES urls(id),
+  saved_at  TIMESTAMP NOT NULL,
+  UNIQUE (user_id, url_id)
+);
+
+CREATE INDEX saved_urls_user_id_saved_at ON saved_urls(user_id, saved_at DESC, id DESC);
@@ -0,0 +1,18 @@
+CREATE TABLE saved_urls_no_read_at (
+  id       VARCHAR(21) NOT NULL PRIMARY KEY,
+  user_id  VARCHAR(21) NOT NULL REFERENCES users(id),
+  url_id   VARCHAR(21) NOT NULL REFERENCES urls(id),
+  saved_at TIMESTAMP NOT NULL,
+  UNIQUE (user_id, url_id)
+);
+
+INSERT INTO saved_urls_no_read_at
+SELECT id, user_id, url_id, saved_at
+FROM saved_urls;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE saved_urls;
+ALTER TABLE saved_urls_no_read_at RENAME TO saved_urls;
+PRAGMA foreign_keys = ON;
+
+CREATE INDEX saved_urls_user_id_saved_at ON saved_urls(user_id, saved_at DESC, id DESC);
@@ -0,0 +1 @@
+ALTER TABLE saved_urls ADD COLUMN read_at TIMESTAMP;
@@ -0,0 +1,2 @@
+ALTER TABLE urls ADD COLUMN pinned_at TIMESTAMP;
+CREATE INDEX urls_created_by_pinned_at ON urls(created_by, pinned_at) WHERE pinned_at IS NOT NULL;
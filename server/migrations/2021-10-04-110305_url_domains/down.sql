@@ -0,0 +1,42 @@
+DROP INDEX urls_domain_created_at;
+
+CREATE TABLE urls_no_domain (
+  id                  VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at          TIMESTAMP NOT NULL,
+  updated_at          TIMESTAMP NOT NULL,
+
+  url                 TEXT UNIQUE NOT NULL,
+  status_code         INTEGER NOT NULL,
+  title               TEXT,
+  description         TEXT,
+  image               TEXT,
+  created_by          VARCHAR(21) NOT NULL REFERENCES users(id),
+  canonical_url       TEXT NOT NULL DEFAULT '',
+  fetched_title       TEXT,
+  fetched_description TEXT,
+  metadata_status     TEXT NOT NULL DEFAULT 'ok',
+  edited_at           TIMESTAMP,
+  deleted_at          TIMESTAMP,
+  comment_count       BIGINT NOT NULL DEFAULT 0,
+  score               BIGINT NOT NULL DEFAULT 0,
+  upvotes             BIGINT NOT NULL DEFAULT 0,
+  downvotes           BIGINT NOT NULL DEFAULT 0,
+  hot_rank            DOUBLE NOT NULL DEFAULT 0,
+  pinned_at           TIMESTAMP
+);
+
+INSERT INTO urls_no_domain
+SELECT id, created_at, updated_at, url, status_code, title, description, image,
+  created_by, canonical_url, fetched_title, fetched_description,
+  metadata_status, edited_at, deleted_at, comment_count, score, upvotes,
+  downvotes, hot_rank, pinned_at
+FROM urls;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE urls;
+ALTER TABLE urls_no_domain RENAME TO urls;
+PRAGMA foreign_keys = ON;
+
+CREATE UNIQUE INDEX urls_canonical_url ON urls(canonical_url);
+CREATE INDEX urls_hot_rank ON urls(hot_rank DESC, created_at DESC, id DESC);
+CREATE INDEX urls_created_by_pinned_at ON urls(created_by, pinned_at) WHERE pinned_at IS NOT NULL;
@@ -0,0 +1,4 @@
+-- existing submissions are assigned their domain
+-- once the server starts
+ALTER TABLE urls ADD COLUMN domain TEXT NOT NULL DEFAULT '';
+CREATE INDEX urls_domain_created_at ON urls(domain, created_at);
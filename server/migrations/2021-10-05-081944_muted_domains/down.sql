@@ -0,0 +1 @@
+DROP TABLE muted_domains;
@@ -0,0 +1,6 @@
+CREATE TABLE muted_domains (
+  user_id     VARCHAR(21) NOT NULL REFERENCES users(id),
+  domain      TEXT NOT NULL,
+  created_at  TIMESTAMP NOT NULL,
+  PRIMARY KEY (user_id, domain)
+);
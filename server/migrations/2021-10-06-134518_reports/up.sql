@@ -0,0 +1,16 @@
+CREATE TABLE reports (
+  id           VARCHAR(21) NOT NULL PRIMARY KEY,
+  url_id       VARCHAR(21) NOT NULL REFERENCES urls(id),
+  created_by   VARCHAR(21) NOT NULL REFERENCES users(id),
+  reason       TEXT NOT NULL,
+  note         TEXT,
+  created_at   TIMESTAMP NOT NULL,
+  updated_at   TIMESTAMP NOT NULL,
+  resolved_at  TIMESTAMP,
+  resolved_by  VARCHAR(21) REFERENCES users(id),
+  action       TEXT
+);
+
+-- each user has at most one open report per submission
+CREATE UNIQUE INDEX reports_open ON reports(url_id, created_by) WHERE resolved_at IS NULL;
+CREATE INDEX reports_resolved_at_created_at ON reports(resolved_at, created_at);
@@ -0,0 +1,14 @@
+ALTER TABLE urls ADD COLUMN removed_at TIMESTAMP;
+ALTER TABLE urls ADD COLUMN shadow_removed BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE moderation_log (
+  id            VARCHAR(21) NOT NULL PRIMARY KEY,
+  created_at    TIMESTAMP NOT NULL,
+  moderator_id  VARCHAR(21) NOT NULL REFERENCES users(id),
+  url_id        VARCHAR(21) NOT NULL REFERENCES urls(id),
+  shadow        BOOLEAN NOT NULL,
+  reason        TEXT NOT NULL
+);
+
+CREATE INDEX urls_removed_at ON urls(removed_at) WHERE removed_at IS NOT NULL;
+CREATE INDEX moderation_log_created_at ON moderation_log(created_at, id);
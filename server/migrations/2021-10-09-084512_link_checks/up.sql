@@ -0,0 +1,5 @@
+ALTER TABLE urls ADD COLUMN last_checked_at TIMESTAMP;
+ALTER TABLE urls ADD COLUMN http_status INTEGER;
+ALTER TABLE urls ADD COLUMN link_status TEXT NOT NULL DEFAULT 'unknown';
+
+CREATE INDEX urls_last_checked_at ON urls(last_checked_at);
@@ -0,0 +1,6 @@
+ALTER TABLE urls ADD COLUMN archived_url TEXT;
+ALTER TABLE urls ADD COLUMN archive_status TEXT;
+ALTER TABLE urls ADD COLUMN archive_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE urls ADD COLUMN next_archive_at TIMESTAMP;
+
+CREATE INDEX urls_next_archive_at ON urls(next_archive_at) WHERE archive_status = 'pending';
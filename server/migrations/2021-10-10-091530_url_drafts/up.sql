@@ -0,0 +1 @@
+ALTER TABLE urls ADD COLUMN draft BOOLEAN NOT NULL DEFAULT 0;
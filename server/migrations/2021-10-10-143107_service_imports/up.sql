@@ -0,0 +1,21 @@
+CREATE TABLE service_imports (
+  id                  VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at          TIMESTAMP NOT NULL,
+  updated_at          TIMESTAMP NOT NULL,
+
+  user_id             VARCHAR(21) NOT NULL REFERENCES users(id),
+  service             TEXT NOT NULL,
+  credentials         TEXT,
+  status              TEXT NOT NULL,
+  cursor              TEXT,
+  imported            INTEGER NOT NULL DEFAULT 0,
+  skipped_duplicates  INTEGER NOT NULL DEFAULT 0,
+  error_count         INTEGER NOT NULL DEFAULT 0,
+  last_error          TEXT,
+  attempts            INTEGER NOT NULL DEFAULT 0,
+  next_attempt_at     TIMESTAMP NOT NULL,
+  completed_at        TIMESTAMP
+);
+
+CREATE INDEX service_imports_user_id_status ON service_imports(user_id, status);
+CREATE INDEX service_imports_status_next_attempt_at ON service_imports(status, next_attempt_at);
@@ -0,0 +1,13 @@
+CREATE TABLE bookmark_exports (
+  id            VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at    TIMESTAMP NOT NULL,
+  updated_at    TIMESTAMP NOT NULL,
+
+  user_id       VARCHAR(21) NOT NULL REFERENCES users(id),
+  format        TEXT NOT NULL,
+  status        TEXT NOT NULL,
+  completed_at  TIMESTAMP,
+  storage_key   TEXT
+);
+
+CREATE INDEX bookmark_exports_user_id_status ON bookmark_exports(user_id, status);
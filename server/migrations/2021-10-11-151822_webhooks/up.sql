@@ -0,0 +1,32 @@
+CREATE TABLE webhooks (
+  id          VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at  TIMESTAMP NOT NULL,
+  updated_at  TIMESTAMP NOT NULL,
+
+  user_id     VARCHAR(21) NOT NULL REFERENCES users(id),
+  site_wide   BOOLEAN NOT NULL DEFAULT FALSE,
+  url         TEXT NOT NULL,
+  events      TEXT NOT NULL,
+  secret      TEXT NOT NULL
+);
+
+CREATE INDEX webhooks_user_id ON webhooks(user_id);
+
+CREATE TABLE webhook_deliveries (
+  id               VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at       TIMESTAMP NOT NULL,
+  updated_at       TIMESTAMP NOT NULL,
+
+  webhook_id       VARCHAR(21) NOT NULL REFERENCES webhooks(id),
+  event            TEXT NOT NULL,
+  payload          TEXT NOT NULL,
+  status           TEXT NOT NULL,
+  attempts         INTEGER NOT NULL DEFAULT 0,
+  next_attempt_at  TIMESTAMP NOT NULL,
+  response_status  INTEGER,
+  last_error       TEXT,
+  delivered_at     TIMESTAMP
+);
+
+CREATE INDEX webhook_deliveries_webhook_id_created_at ON webhook_deliveries(webhook_id, created_at);
+CREATE INDEX webhook_deliveries_status_next_attempt_at ON webhook_deliveries(status, next_attempt_at);
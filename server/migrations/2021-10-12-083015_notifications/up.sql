@@ -0,0 +1,14 @@
+CREATE TABLE notifications (
+  id          VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at  TIMESTAMP NOT NULL,
+
+  user_id     VARCHAR(21) NOT NULL REFERENCES users(id),
+  kind        TEXT NOT NULL,
+  actor_id    VARCHAR(21) NOT NULL REFERENCES users(id),
+  url_id      VARCHAR(21) NOT NULL REFERENCES urls(id),
+  comment_id  VARCHAR(21) NOT NULL REFERENCES comments(id),
+  read_at     TIMESTAMP
+);
+
+CREATE UNIQUE INDEX notifications_user_id_comment_id ON notifications(user_id, comment_id);
+CREATE INDEX notifications_user_id_created_at ON notifications(user_id, created_at);
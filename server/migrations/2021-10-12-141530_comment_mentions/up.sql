@@ -0,0 +1,8 @@
+CREATE TABLE comment_mentions (
+  comment_id  VARCHAR(21) NOT NULL REFERENCES comments(id),
+  user_id     VARCHAR(21) NOT NULL REFERENCES users(id),
+  created_at  TIMESTAMP NOT NULL,
+  PRIMARY KEY (comment_id, user_id)
+);
+
+CREATE INDEX comment_mentions_user_id ON comment_mentions(user_id);
@@ -0,0 +1,22 @@
+DROP TABLE digest_items;
+DROP TABLE digests;
+
+CREATE TABLE user_preferences_no_digest (
+  user_id          VARCHAR(21) PRIMARY KEY NOT NULL REFERENCES users(id),
+  created_at       TIMESTAMP NOT NULL,
+  updated_at       TIMESTAMP NOT NULL,
+  notify_new_login BOOLEAN NOT NULL,
+  timezone         TEXT NOT NULL DEFAULT 'UTC',
+  locale           TEXT NOT NULL DEFAULT 'en',
+  default_sort     TEXT NOT NULL DEFAULT 'ranked'
+);
+
+INSERT INTO user_preferences_no_digest
+SELECT user_id, created_at, updated_at, notify_new_login, timezone, locale,
+  default_sort
+FROM user_preferences;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE user_preferences;
+ALTER TABLE user_preferences_no_digest RENAME TO user_preferences;
+PRAGMA foreign_keys = ON;
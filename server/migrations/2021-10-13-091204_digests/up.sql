@@ -0,0 +1,19 @@
+ALTER TABLE user_preferences ADD COLUMN digest TEXT NOT NULL DEFAULT 'off';
+
+CREATE TABLE digests (
+  id          VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at  TIMESTAMP NOT NULL,
+
+  user_id     VARCHAR(21) NOT NULL REFERENCES users(id),
+  frequency   TEXT NOT NULL,
+  url_count   INTEGER NOT NULL
+);
+
+CREATE INDEX digests_user_id_created_at ON digests(user_id, created_at);
+
+CREATE TABLE digest_items (
+  user_id     VARCHAR(21) NOT NULL REFERENCES users(id),
+  url_id      VARCHAR(21) NOT NULL REFERENCES urls(id),
+  digest_id   VARCHAR(21) NOT NULL REFERENCES digests(id),
+  PRIMARY KEY (user_id, url_id)
+);
@@ -0,0 +1,10 @@
+ALTER TABLE urls ADD COLUMN views BIGINT NOT NULL DEFAULT 0;
+
+CREATE TABLE url_views (
+  url_id     VARCHAR(21) NOT NULL REFERENCES urls(id),
+  viewer     TEXT NOT NULL,
+  viewed_on  DATE NOT NULL,
+  PRIMARY KEY (url_id, viewer, viewed_on)
+);
+
+CREATE INDEX url_views_viewed_on ON url_views(viewed_on);
@@ -0,0 +1,8 @@
+CREATE TABLE url_embeds (
+  url_id      VARCHAR(21) NOT NULL PRIMARY KEY REFERENCES urls(id),
+  updated_at  TIMESTAMP NOT NULL,
+  provider    TEXT NOT NULL,
+  html        TEXT NOT NULL,
+  width       INTEGER,
+  height      INTEGER
+);
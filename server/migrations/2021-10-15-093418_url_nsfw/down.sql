@@ -0,0 +1,81 @@
+CREATE TABLE user_preferences_no_show_nsfw (
+  user_id          VARCHAR(21) PRIMARY KEY NOT NULL REFERENCES users(id),
+  created_at       TIMESTAMP NOT NULL,
+  updated_at       TIMESTAMP NOT NULL,
+  notify_new_login BOOLEAN NOT NULL,
+  timezone         TEXT NOT NULL DEFAULT 'UTC',
+  locale           TEXT NOT NULL DEFAULT 'en',
+  default_sort     TEXT NOT NULL DEFAULT 'ranked',
+  digest           TEXT NOT NULL DEFAULT 'off'
+);
+
+INSERT INTO user_preferences_no_show_nsfw
+SELECT user_id, created_at, updated_at, notify_new_login, timezone, locale,
+  default_sort, digest
+FROM user_preferences;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE user_preferences;
+ALTER TABLE user_preferences_no_show_nsfw RENAME TO user_preferences;
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE urls_no_nsfw_locked (
+  id                  VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at          TIMESTAMP NOT NULL,
+  updated_at          TIMESTAMP NOT NULL,
+
+  url                 TEXT UNIQUE NOT NULL,
+  status_code         INTEGER NOT NULL,
+  title               TEXT,
+  description         TEXT,
+  image               TEXT,
+  created_by          VARCHAR(21) NOT NULL REFERENCES users(id),
+  canonical_url       TEXT NOT NULL DEFAULT '',
+  fetched_title       TEXT,
+  fetched_description TEXT,
+  metadata_status     TEXT NOT NULL DEFAULT 'ok',
+  edited_at           TIMESTAMP,
+  deleted_at          TIMESTAMP,
+  comment_count       BIGINT NOT NULL DEFAULT 0,
+  score               BIGINT NOT NULL DEFAULT 0,
+  upvotes             BIGINT NOT NULL DEFAULT 0,
+  downvotes           BIGINT NOT NULL DEFAULT 0,
+  hot_rank            DOUBLE NOT NULL DEFAULT 0,
+  pinned_at           TIMESTAMP,
+  domain              TEXT NOT NULL DEFAULT '',
+  removed_at          TIMESTAMP,
+  shadow_removed      BOOLEAN NOT NULL DEFAULT FALSE,
+  preview_image       TEXT,
+  last_checked_at     TIMESTAMP,
+  http_status         INTEGER,
+  link_status         TEXT NOT NULL DEFAULT 'unknown',
+  archived_url        TEXT,
+  archive_status      TEXT,
+  archive_attempts    INTEGER NOT NULL DEFAULT 0,
+  next_archive_at     TIMESTAMP,
+  draft               BOOLEAN NOT NULL DEFAULT 0,
+  clicks              BIGINT NOT NULL DEFAULT 0,
+  views               BIGINT NOT NULL DEFAULT 0
+);
+
+INSERT INTO urls_no_nsfw_locked
+SELECT id, created_at, updated_at, url, status_code, title, description, image,
+  created_by, canonical_url, fetched_title, fetched_description,
+  metadata_status, edited_at, deleted_at, comment_count, score, upvotes,
+  downvotes, hot_rank, pinned_at, domain, removed_at, shadow_removed,
+  preview_image, last_checked_at, http_status, link_status, archived_url,
+  archive_status, archive_attempts, next_archive_at, draft, clicks, views
+FROM urls;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE urls;
+ALTER TABLE urls_no_nsfw_locked RENAME TO urls;
+PRAGMA foreign_keys = ON;
+
+CREATE UNIQUE INDEX urls_canonical_url ON urls(canonical_url);
+CREATE INDEX urls_hot_rank ON urls(hot_rank DESC, created_at DESC, id DESC);
+CREATE INDEX urls_created_by_pinned_at ON urls(created_by, pinned_at) WHERE pinned_at IS NOT NULL;
+CREATE INDEX urls_domain_created_at ON urls(domain, created_at);
+CREATE INDEX urls_removed_at ON urls(removed_at) WHERE removed_at IS NOT NULL;
+CREATE INDEX urls_last_checked_at ON urls(last_checked_at);
+CREATE INDEX urls_next_archive_at ON urls(next_archive_at) WHERE archive_status = 'pending';
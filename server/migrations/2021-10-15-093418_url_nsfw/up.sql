@@ -0,0 +1,3 @@
+ALTER TABLE urls ADD COLUMN nsfw BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE urls ADD COLUMN nsfw_locked BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE user_preferences ADD COLUMN show_nsfw TEXT NOT NULL DEFAULT 'hide';
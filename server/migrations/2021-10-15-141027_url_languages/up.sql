@@ -0,0 +1,3 @@
+ALTER TABLE urls ADD COLUMN language TEXT;
+CREATE INDEX urls_language ON urls(language);
+ALTER TABLE user_preferences ADD COLUMN languages TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,23 @@
+CREATE TABLE collections (
+  id          VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at  TIMESTAMP NOT NULL,
+  updated_at  TIMESTAMP NOT NULL,
+
+  user_id     VARCHAR(21) NOT NULL REFERENCES users(id),
+  title       TEXT NOT NULL,
+  description TEXT,
+  visibility  TEXT NOT NULL
+);
+
+CREATE INDEX collections_user_id_created_at ON collections(user_id, created_at);
+
+CREATE TABLE collection_items (
+  collection_id VARCHAR(21) NOT NULL REFERENCES collections(id),
+  url_id        VARCHAR(21) NOT NULL REFERENCES urls(id),
+  position      INTEGER NOT NULL,
+  note          TEXT,
+  added_at      TIMESTAMP NOT NULL,
+  PRIMARY KEY (collection_id, url_id)
+);
+
+CREATE INDEX collection_items_collection_id_position ON collection_items(collection_id, position);
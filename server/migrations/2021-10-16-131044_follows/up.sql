@@ -0,0 +1,8 @@
+CREATE TABLE follows (
+  user_id           VARCHAR(21) NOT NULL REFERENCES users(id),
+  followed_user_id  VARCHAR(21) NOT NULL REFERENCES users(id),
+  created_at        TIMESTAMP NOT NULL,
+  PRIMARY KEY (user_id, followed_user_id)
+);
+
+CREATE INDEX follows_followed_user_id ON follows(followed_user_id);
@@ -0,0 +1,6 @@
+CREATE TABLE tag_follows (
+  user_id     VARCHAR(21) NOT NULL REFERENCES users(id),
+  tag_name    TEXT NOT NULL REFERENCES tags(name),
+  created_at  TIMESTAMP NOT NULL,
+  PRIMARY KEY (user_id, tag_name)
+);
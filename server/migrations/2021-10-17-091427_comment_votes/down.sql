@@ -0,0 +1,29 @@
+CREATE TABLE comments_no_score (
+  id              VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at      TIMESTAMP NOT NULL,
+  updated_at      TIMESTAMP NOT NULL,
+
+  comment         TEXT NOT NULL,
+  url_id          VARCHAR(21) NOT NULL REFERENCES urls(id),
+  created_by      VARCHAR(21) NOT NULL REFERENCES users(id),
+  replies_to      VARCHAR(21) REFERENCES comments(id),
+  depth           INTEGER NOT NULL DEFAULT 0,
+  edited_at       TIMESTAMP,
+  deleted_at      TIMESTAMP,
+  deletion_reason TEXT,
+  reply_count     INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT INTO comments_no_score
+SELECT id, created_at, updated_at, comment, url_id, created_by, replies_to,
+  depth, edited_at, deleted_at, deletion_reason, reply_count
+FROM comments;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE comments;
+ALTER TABLE comments_no_score RENAME TO comments;
+PRAGMA foreign_keys = ON;
+
+CREATE INDEX comments_replies_to ON comments(replies_to);
+
+DROP TABLE comment_votes;
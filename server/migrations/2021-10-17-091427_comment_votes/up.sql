@@ -0,0 +1,9 @@
+CREATE TABLE comment_votes (
+  comment_id  VARCHAR(21) NOT NULL REFERENCES comments(id),
+  user_id     VARCHAR(21) NOT NULL REFERENCES users(id),
+  created_at  TIMESTAMP NOT NULL,
+  PRIMARY KEY (comment_id, user_id)
+);
+CREATE INDEX comment_votes_user_id ON comment_votes(user_id);
+
+ALTER TABLE comments ADD COLUMN score BIGINT NOT NULL DEFAULT 0;
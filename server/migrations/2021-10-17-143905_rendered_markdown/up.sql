@@ -0,0 +1,3 @@
+-- existing rows are rendered on startup
+ALTER TABLE comments ADD COLUMN html TEXT;
+ALTER TABLE urls ADD COLUMN description_html TEXT;
@@ -0,0 +1,19 @@
+CREATE TABLE revisions (
+  id          VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at  TIMESTAMP NOT NULL,
+  editor_id   VARCHAR(21) NOT NULL REFERENCES users(id),
+
+  -- exactly one of url_id and comment_id is set
+  url_id      VARCHAR(21) REFERENCES urls(id),
+  comment_id  VARCHAR(21) REFERENCES comments(id),
+  title       TEXT,
+  description TEXT,
+  body        TEXT,
+  CHECK ((url_id IS NULL) <> (comment_id IS NULL))
+);
+
+CREATE INDEX revisions_url_id_created_at ON revisions(url_id, created_at);
+CREATE INDEX revisions_comment_id_created_at ON revisions(comment_id, created_at);
+
+ALTER TABLE urls ADD COLUMN revision_count BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE comments ADD COLUMN revision_count BIGINT NOT NULL DEFAULT 0;
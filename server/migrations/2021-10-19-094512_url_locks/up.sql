@@ -0,0 +1,2 @@
+ALTER TABLE urls ADD COLUMN locked_at TIMESTAMP;
+ALTER TABLE urls ADD COLUMN lock_reason TEXT;
@@ -0,0 +1,6 @@
+ALTER TABLE urls ADD COLUMN previous_id VARCHAR(21) REFERENCES urls(id);
+
+-- a URL may be submitted again, but each submission can only be
+-- followed by one resubmission, such that they form a single chain
+DROP INDEX urls_canonical_url;
+CREATE UNIQUE INDEX urls_canonical_url ON urls(canonical_url, COALESCE(previous_id, ''));
@@ -0,0 +1 @@
+ALTER TABLE user_preferences ADD COLUMN hide_votes BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE urls ADD COLUMN anonymous BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,107 @@
+CREATE TABLE comments_no_held_at (
+  id              VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at      TIMESTAMP NOT NULL,
+  updated_at      TIMESTAMP NOT NULL,
+
+  comment         TEXT NOT NULL,
+  url_id          VARCHAR(21) NOT NULL REFERENCES urls(id),
+  created_by      VARCHAR(21) NOT NULL REFERENCES users(id),
+  replies_to      VARCHAR(21) REFERENCES comments(id),
+  depth           INTEGER NOT NULL DEFAULT 0,
+  edited_at       TIMESTAMP,
+  deleted_at      TIMESTAMP,
+  deletion_reason TEXT,
+  reply_count     INTEGER NOT NULL DEFAULT 0,
+  score           BIGINT NOT NULL DEFAULT 0,
+  html            TEXT,
+  revision_count  BIGINT NOT NULL DEFAULT 0
+);
+
+INSERT INTO comments_no_held_at
+SELECT id, created_at, updated_at, comment, url_id, created_by, replies_to,
+  depth, edited_at, deleted_at, deletion_reason, reply_count, score, html,
+  revision_count
+FROM comments;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE comments;
+ALTER TABLE comments_no_held_at RENAME TO comments;
+PRAGMA foreign_keys = ON;
+
+CREATE INDEX comments_replies_to ON comments(replies_to);
+
+CREATE TABLE urls_no_held_at (
+  id                  VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at          TIMESTAMP NOT NULL,
+  updated_at          TIMESTAMP NOT NULL,
+
+  url                 TEXT,
+  status_code         INTEGER NOT NULL,
+  title               TEXT,
+  description         TEXT,
+  image               TEXT,
+  created_by          VARCHAR(21) NOT NULL REFERENCES users(id),
+  canonical_url       TEXT,
+  fetched_title       TEXT,
+  fetched_description TEXT,
+  metadata_status     TEXT NOT NULL DEFAULT 'ok',
+  edited_at           TIMESTAMP,
+  deleted_at          TIMESTAMP,
+  comment_count       BIGINT NOT NULL DEFAULT 0,
+  score               BIGINT NOT NULL DEFAULT 0,
+  upvotes             BIGINT NOT NULL DEFAULT 0,
+  downvotes           BIGINT NOT NULL DEFAULT 0,
+  hot_rank            DOUBLE NOT NULL DEFAULT 0,
+  pinned_at           TIMESTAMP,
+  domain              TEXT,
+  removed_at          TIMESTAMP,
+  shadow_removed      BOOLEAN NOT NULL DEFAULT FALSE,
+  preview_image       TEXT,
+  last_checked_at     TIMESTAMP,
+  http_status         INTEGER,
+  link_status         TEXT NOT NULL DEFAULT 'unknown',
+  archived_url        TEXT,
+  archive_status      TEXT,
+  archive_attempts    INTEGER NOT NULL DEFAULT 0,
+  next_archive_at     TIMESTAMP,
+  draft               BOOLEAN NOT NULL DEFAULT 0,
+  clicks              BIGINT NOT NULL DEFAULT 0,
+  views               BIGINT NOT NULL DEFAULT 0,
+  nsfw                BOOLEAN NOT NULL DEFAULT 0,
+  nsfw_locked         BOOLEAN NOT NULL DEFAULT 0,
+  language            TEXT,
+  description_html    TEXT,
+  revision_count      BIGINT NOT NULL DEFAULT 0,
+  locked_at           TIMESTAMP,
+  lock_reason         TEXT,
+  previous_id         VARCHAR(21) REFERENCES urls(id),
+  anonymous           BOOLEAN NOT NULL DEFAULT 0,
+  kind                TEXT NOT NULL DEFAULT 'link',
+  text                TEXT,
+  text_html           TEXT
+);
+
+INSERT INTO urls_no_held_at
+SELECT id, created_at, updated_at, url, status_code, title, description, image,
+  created_by, canonical_url, fetched_title, fetched_description,
+  metadata_status, edited_at, deleted_at, comment_count, score, upvotes,
+  downvotes, hot_rank, pinned_at, domain, removed_at, shadow_removed,
+  preview_image, last_checked_at, http_status, link_status, archived_url,
+  archive_status, archive_attempts, next_archive_at, draft, clicks, views, nsfw,
+  nsfw_locked, language, description_html, revision_count, locked_at,
+  lock_reason, previous_id, anonymous, kind, text, text_html
+FROM urls;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE urls;
+ALTER TABLE urls_no_held_at RENAME TO urls;
+PRAGMA foreign_keys = ON;
+
+CREATE UNIQUE INDEX urls_canonical_url ON urls(canonical_url, COALESCE(previous_id, ''));
+CREATE INDEX urls_hot_rank ON urls(hot_rank DESC, created_at DESC, id DESC);
+CREATE INDEX urls_created_by_pinned_at ON urls(created_by, pinned_at) WHERE pinned_at IS NOT NULL;
+CREATE INDEX urls_domain_created_at ON urls(domain, created_at);
+CREATE INDEX urls_removed_at ON urls(removed_at) WHERE removed_at IS NOT NULL;
+CREATE INDEX urls_last_checked_at ON urls(last_checked_at);
+CREATE INDEX urls_next_archive_at ON urls(next_archive_at) WHERE archive_status = 'pending';
+CREATE INDEX urls_language ON urls(language);
@@ -0,0 +1,2 @@
+ALTER TABLE urls ADD COLUMN held_at TIMESTAMP;
+ALTER TABLE comments ADD COLUMN held_at TIMESTAMP;
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN karma BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,23 @@
+CREATE TABLE user_preferences_no_hide_from_leaderboard (
+  user_id          VARCHAR(21) PRIMARY KEY NOT NULL REFERENCES users(id),
+  created_at       TIMESTAMP NOT NULL,
+  updated_at       TIMESTAMP NOT NULL,
+  notify_new_login BOOLEAN NOT NULL,
+  timezone         TEXT NOT NULL DEFAULT 'UTC',
+  locale           TEXT NOT NULL DEFAULT 'en',
+  default_sort     TEXT NOT NULL DEFAULT 'ranked',
+  digest           TEXT NOT NULL DEFAULT 'off',
+  show_nsfw        TEXT NOT NULL DEFAULT 'hide',
+  languages        TEXT NOT NULL DEFAULT '',
+  hide_votes       BOOLEAN NOT NULL DEFAULT 0
+);
+
+INSERT INTO user_preferences_no_hide_from_leaderboard
+SELECT user_id, created_at, updated_at, notify_new_login, timezone, locale,
+  default_sort, digest, show_nsfw, languages, hide_votes
+FROM user_preferences;
+
+PRAGMA foreign_keys = OFF;
+DROP TABLE user_preferences;
+ALTER TABLE user_preferences_no_hide_from_leaderboard RENAME TO user_preferences;
+PRAGMA foreign_keys = ON;
@@ -0,0 +1 @@
+ALTER TABLE user_preferences ADD COLUMN hide_from_leaderboard BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+DROP TABLE tag_log;
+DROP TABLE tag_redirects;
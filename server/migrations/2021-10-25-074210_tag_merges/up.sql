@@ -0,0 +1,17 @@
+CREATE TABLE tag_redirects (
+  name        TEXT NOT NULL PRIMARY KEY,
+  target      TEXT NOT NULL REFERENCES tags(name),
+  created_at  TIMESTAMP NOT NULL
+);
+
+CREATE TABLE tag_log (
+  id          VARCHAR(21) NOT NULL PRIMARY KEY,
+  created_at  TIMESTAMP NOT NULL,
+  admin_id    VARCHAR(21) NOT NULL REFERENCES users(id),
+  sources     TEXT NOT NULL,
+  target      TEXT NOT NULL,
+  url_count   BIGINT NOT NULL
+);
+
+CREATE INDEX tag_redirects_target ON tag_redirects(target);
+CREATE INDEX tag_log_created_at ON tag_log(created_at, id);
@@ -0,0 +1,6 @@
+ALTER TABLE urls ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public';
+ALTER TABLE urls ADD COLUMN published_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+
+UPDATE urls SET published_at = created_at;
+
+CREATE INDEX urls_published_at ON urls(published_at);
@@ -0,0 +1,33 @@
+CREATE TABLE groups (
+  id           VARCHAR(21) PRIMARY KEY NOT NULL,
+  created_at   TIMESTAMP NOT NULL,
+  updated_at   TIMESTAMP NOT NULL,
+
+  slug         TEXT NOT NULL UNIQUE,
+  title        TEXT NOT NULL,
+  description  TEXT,
+  created_by   VARCHAR(21) NOT NULL REFERENCES users(id),
+  visibility   TEXT NOT NULL,
+  syndicated   BOOLEAN NOT NULL DEFAULT FALSE,
+  member_count BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE TABLE group_members (
+  group_id   VARCHAR(21) NOT NULL REFERENCES groups(id),
+  user_id    VARCHAR(21) NOT NULL REFERENCES users(id),
+  created_at TIMESTAMP NOT NULL,
+  PRIMARY KEY (group_id, user_id)
+);
+
+CREATE INDEX group_members_user_id ON group_members(user_id);
+
+CREATE TABLE group_moderators (
+  group_id   VARCHAR(21) NOT NULL REFERENCES groups(id),
+  user_id    VARCHAR(21) NOT NULL REFERENCES users(id),
+  created_at TIMESTAMP NOT NULL,
+  PRIMARY KEY (group_id, user_id)
+);
+
+ALTER TABLE urls ADD COLUMN group_id VARCHAR(21) REFERENCES groups(id);
+
+CREATE INDEX urls_group_id ON urls(group_id);
@@ -0,0 +1,3 @@
+ALTER TABLE urls ADD COLUMN publish_at TIMESTAMP;
+
+CREATE INDEX urls_publish_at ON urls(publish_at);
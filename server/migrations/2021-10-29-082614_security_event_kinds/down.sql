@@ -0,0 +1,2 @@
+DELETE FROM security_events WHERE kind IN ('email_change_confirmed', 'token_created', 'token_revoked');
+UPDATE security_events SET kind = 'email_changed' WHERE kind = 'email_change_requested';
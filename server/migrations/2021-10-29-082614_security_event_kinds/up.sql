@@ -0,0 +1 @@
+UPDATE security_events SET kind = 'email_change_requested' WHERE kind = 'email_changed';
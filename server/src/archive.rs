@@ -0,0 +1,69 @@
+//! Archiving of submitted pages, such that discussions remain
+//! useful when the linked page disappears. Archiving is done by an
+//! external service behind the [`Archiver`] trait, which is chosen
+//! by the configuration, see [`Config::archive_url`].
+
+use crate::{Config, Context};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use reqwest::header::CONTENT_LOCATION;
+
+/// A service which takes snapshots of web pages.
+#[async_trait]
+pub trait Archiver: Send + Sync {
+    /// Request a snapshot of the page at the given URL, returning
+    /// the URL at which the snapshot can be viewed.
+    async fn archive(&self, ctx: &Context, url: &str) -> Result<String>;
+}
+
+/// Archives pages using the save API of the Wayback Machine, or
+/// of a compatible service.
+#[derive(Debug, Clone)]
+pub struct Wayback {
+    base_url: String,
+}
+
+impl Wayback {
+    /// Use the service at the given URL, e.g.
+    /// `https://web.archive.org`.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Archiver for Wayback {
+    async fn archive(&self, ctx: &Context, url: &str) -> Result<String> {
+        let resp = ctx
+            .http_client()
+            .get(format!("{}/save/{}", self.base_url, url))
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("Saving snapshot failed with {}", resp.status()));
+        }
+        // the snapshot is either given in the header, or
+        // the request was redirected to it
+        let location = resp
+            .headers()
+            .get(CONTENT_LOCATION)
+            .and_then(|location| location.to_str().ok());
+        match location {
+            Some(location) if location.starts_with("/web/") => {
+                Ok(format!("{}{}", self.base_url, location))
+            }
+            _ if resp.url().path().starts_with("/web/") => Ok(resp.url().to_string()),
+            _ => Err(anyhow!("The archive did not return a snapshot")),
+        }
+    }
+}
+
+/// The archiver described by the given configuration, or `None`
+/// if archiving is disabled.
+pub fn from_config(config: &Config) -> Option<Box<dyn Archiver>> {
+    config
+        .archive_url()
+        .map(|url| Box::new(Wayback::new(url)) as Box<dyn Archiver>)
+}
@@ -0,0 +1,326 @@
+//! Remote bookmarking services bookmarks can be imported from. Each
+//! service is accessed through the [`BookmarkSource`] trait, which
+//! hands out bookmarks one page at a time, such that imports can be
+//! resumed after the last page which was imported, see
+//! [`ServiceImport`](crate::db::models::ServiceImport).
+
+use crate::db::models::ImportService;
+use crate::error::RateLimited;
+use crate::{Config, Context};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const PINBOARD_API_URL: &str = "https://api.pinboard.in/v1";
+const POCKET_API_URL: &str = "https://getpocket.com/v3";
+
+/// Pocket returns at most this many items per request.
+const POCKET_PAGE_SIZE: usize = 500;
+
+/// A bookmark read from a remote service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceBookmark {
+    pub url: String,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    /// Whether the bookmark was not read yet.
+    pub unread: bool,
+    /// When the bookmark was created, if known.
+    pub added_at: Option<DateTime<Utc>>,
+}
+
+/// A page of bookmarks, and the cursor of the next
+/// page, if there is one.
+#[derive(Debug, Clone, Default)]
+pub struct SourcePage {
+    pub bookmarks: Vec<SourceBookmark>,
+    pub next: Option<String>,
+}
+
+/// A service which bookmarks can be read from.
+#[async_trait]
+pub trait BookmarkSource: Send + Sync {
+    /// Fetch the page of bookmarks at the given cursor, or the first
+    /// page. Sources fail with [`RateLimited`] if the service asks to
+    /// slow down.
+    async fn fetch(
+        &self,
+        ctx: &Context,
+        credentials: &str,
+        cursor: Option<&str>,
+    ) -> Result<SourcePage>;
+
+    /// The time to wait between two requests for
+    /// the same user, as required by the service.
+    fn request_interval(&self) -> Duration;
+}
+
+/// The time to wait before retrying a request the service
+/// rejected due to its rate limits.
+fn retry_after(resp: &Response, default: Duration) -> Duration {
+    resp.headers()
+        .get("Retry-After")
+        .or_else(|| resp.headers().get("X-Limit-User-Reset"))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::seconds)
+        .unwrap_or(default)
+}
+
+/// Parse a UNIX timestamp written as a string.
+fn timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let secs: i64 = value.trim().parse().ok().filter(|&secs| secs > 0)?;
+    NaiveDateTime::from_timestamp_opt(secs, 0).map(|time| DateTime::from_utc(time, Utc))
+}
+
+/// Reads all bookmarks from the Pinboard v1 API at once, since the
+/// API allows listing all bookmarks only once every five minutes.
+#[derive(Debug, Clone)]
+pub struct Pinboard {
+    base_url: String,
+}
+
+#[derive(Deserialize)]
+struct PinboardPost {
+    href: String,
+    description: Option<String>,
+    time: Option<String>,
+    #[serde(default)]
+    toread: String,
+    #[serde(default)]
+    tags: String,
+}
+
+impl Pinboard {
+    /// Use the API at the given URL, e.g.
+    /// `https://api.pinboard.in/v1`.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl BookmarkSource for Pinboard {
+    async fn fetch(
+        &self,
+        ctx: &Context,
+        credentials: &str,
+        _cursor: Option<&str>,
+    ) -> Result<SourcePage> {
+        let resp = ctx
+            .http_client()
+            .get(format!("{}/posts/all", self.base_url))
+            .query(&[("auth_token", credentials), ("format", "json")])
+            .send()
+            .await?;
+        match resp.status() {
+            StatusCode::TOO_MANY_REQUESTS => {
+                let retry_after = retry_after(&resp, self.request_interval());
+                return Err(RateLimited::new("pinboard", retry_after).into());
+            }
+            StatusCode::UNAUTHORIZED => return Err(anyhow!("Invalid Pinboard API token")),
+            status if !status.is_success() => {
+                return Err(anyhow!("Pinboard responded with {}", status));
+            }
+            _ => {}
+        }
+        let posts: Vec<PinboardPost> = resp.json().await?;
+        let bookmarks = posts
+            .into_iter()
+            .map(|post| SourceBookmark {
+                url: post.href,
+                title: post.description.filter(|title| !title.trim().is_empty()),
+                tags: post.tags.split_whitespace().map(str::to_string).collect(),
+                unread: post.toread == "yes",
+                added_at: post
+                    .time
+                    .and_then(|time| DateTime::parse_from_rfc3339(&time).ok())
+                    .map(|time| time.with_timezone(&Utc)),
+            })
+            .collect();
+        Ok(SourcePage {
+            bookmarks,
+            next: None,
+        })
+    }
+
+    fn request_interval(&self) -> Duration {
+        Duration::minutes(5)
+    }
+}
+
+/// Reads bookmarks from the Pocket v3 API, oldest first, using the
+/// offset into the list as the cursor.
+#[derive(Debug, Clone)]
+pub struct Pocket {
+    base_url: String,
+    consumer_key: String,
+}
+
+impl Pocket {
+    /// Use the API at the given URL, e.g. `https://getpocket.com/v3`,
+    /// with the consumer key of the application.
+    pub fn new(base_url: &str, consumer_key: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            consumer_key: consumer_key.to_string(),
+        }
+    }
+
+    /// Read an item of the list returned by Pocket. Items
+    /// with status `2` were deleted, and are skipped.
+    fn bookmark(item: &Value) -> Option<SourceBookmark> {
+        let field = |name: &str| {
+            item[name]
+                .as_str()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        if field("status") == Some("2") {
+            return None;
+        }
+        let url = field("given_url").or_else(|| field("resolved_url"))?;
+        let mut tags: Vec<String> = item["tags"]
+            .as_object()
+            .map(|tags| tags.keys().cloned().collect())
+            .unwrap_or_default();
+        tags.sort();
+        Some(SourceBookmark {
+            url: url.to_string(),
+            title: field("given_title")
+                .or_else(|| field("resolved_title"))
+                .map(str::to_string),
+            tags,
+            unread: field("status") != Some("1"),
+            added_at: field("time_added").and_then(timestamp),
+        })
+    }
+}
+
+#[async_trait]
+impl BookmarkSource for Pocket {
+    async fn fetch(
+        &self,
+        ctx: &Context,
+        credentials: &str,
+        cursor: Option<&str>,
+    ) -> Result<SourcePage> {
+        let offset: usize = cursor.unwrap_or("0").parse()?;
+        let resp = ctx
+            .http_client()
+            .post(format!("{}/get", self.base_url))
+            .json(&json!({
+                "consumer_key": self.consumer_key,
+                "access_token": credentials,
+                "state": "all",
+                "sort": "oldest",
+                "detailType": "complete",
+                "count": POCKET_PAGE_SIZE,
+                "offset": offset,
+            }))
+            .send()
+            .await?;
+        let rate_limited = resp
+            .headers()
+            .get("X-Limit-User-Remaining")
+            .map_or(false, |remaining| remaining.as_bytes() == b"0");
+        match resp.status() {
+            StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS if rate_limited => {
+                let retry_after = retry_after(&resp, Duration::hours(1));
+                return Err(RateLimited::new("pocket", retry_after).into());
+            }
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                return Err(anyhow!("Invalid Pocket access token"));
+            }
+            status if !status.is_success() => {
+                return Err(anyhow!("Pocket responded with {}", status));
+            }
+            _ => {}
+        }
+        let body: Value = resp.json().await?;
+        // an empty list is returned as an array
+        let items: Vec<&Value> = match &body["list"] {
+            Value::Object(items) => items.values().collect(),
+            _ => vec![],
+        };
+        let mut items: Vec<(i64, SourceBookmark)> = items
+            .into_iter()
+            .filter_map(|item| {
+                let position = item["sort_id"].as_i64().unwrap_or_default();
+                Self::bookmark(item).map(|bookmark| (position, bookmark))
+            })
+            .collect();
+        items.sort_by_key(|(position, _)| *position);
+        let count = body["list"].as_object().map_or(0, |items| items.len());
+        Ok(SourcePage {
+            bookmarks: items.into_iter().map(|(_, bookmark)| bookmark).collect(),
+            next: if count < POCKET_PAGE_SIZE {
+                None
+            } else {
+                Some((offset + count).to_string())
+            },
+        })
+    }
+
+    fn request_interval(&self) -> Duration {
+        // Pocket allows 320 requests per hour and user
+        Duration::seconds(12)
+    }
+}
+
+/// The source used to import bookmarks from the given service,
+/// or `None` if the service is not configured.
+pub fn for_service(config: &Config, service: ImportService) -> Option<Box<dyn BookmarkSource>> {
+    match service {
+        ImportService::Pinboard => Some(Box::new(Pinboard::new(PINBOARD_API_URL))),
+        ImportService::Pocket => config
+            .pocket_consumer_key()
+            .map(|key| Box::new(Pocket::new(POCKET_API_URL, key)) as Box<dyn BookmarkSource>),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pocket_bookmark() {
+        let item = json!({
+            "item_id": "229279689",
+            "given_url": "https://example.com/article",
+            "resolved_url": "https://example.com/article/",
+            "given_title": "",
+            "resolved_title": "An Article",
+            "status": "1",
+            "time_added": "1633100000",
+            "sort_id": 0,
+            "tags": {
+                "rust": { "item_id": "229279689", "tag": "rust" },
+                "later": { "item_id": "229279689", "tag": "later" }
+            }
+        });
+        assert_eq!(
+            Pocket::bookmark(&item),
+            Some(SourceBookmark {
+                url: "https://example.com/article".into(),
+                title: Some("An Article".into()),
+                tags: vec!["later".into(), "rust".into()],
+                unread: false,
+                added_at: timestamp("1633100000"),
+            })
+        );
+
+        let deleted = json!({ "given_url": "https://example.com", "status": "2" });
+        assert_eq!(Pocket::bookmark(&deleted), None);
+        let unread = json!({ "resolved_url": "https://example.com", "status": "0" });
+        let unread = Pocket::bookmark(&unread).unwrap();
+        assert!(unread.unread);
+        assert_eq!(unread.title, None);
+        assert_eq!(unread.added_at, None);
+    }
+}
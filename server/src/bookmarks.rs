@@ -0,0 +1,368 @@
+//! Parser for bookmarks exported by browsers in the Netscape bookmark
+//! file format. The format is HTML in name only: elements like `<DT>`
+//! and `<p>` are never closed, and exports are not expected to be
+//! well formed. The parser therefore only looks at the few elements
+//! which carry information, and ignores everything else:
+//!
+//! - `<H3>` starts a folder, whose contents is the next `<DL>` list
+//! - `</DL>` ends the current folder
+//! - `<A HREF=".." ADD_DATE=".." TAGS="..">` is a bookmark, whose text
+//!   is its title
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+/// Attributes which mark the folders browsers create on their
+/// own, like the bookmarks toolbar. These are not used as tags.
+const ROOT_FOLDER_ATTRIBUTES: &[&str] = &["personal_toolbar_folder", "unfiled_bookmarks_folder"];
+
+/// A bookmark read from an export.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    pub url: String,
+    pub title: Option<String>,
+    /// When the bookmark was created, if known.
+    pub added_at: Option<DateTime<Utc>>,
+    /// Names of the folders containing the bookmark,
+    /// outermost first.
+    pub folders: Vec<String>,
+    /// Tags listed in the `TAGS` attribute, which is
+    /// written by Firefox, Pinboard, and ourselves.
+    pub tags: Vec<String>,
+}
+
+/// An element of the export which is relevant to the parser.
+#[derive(Debug)]
+enum Element {
+    Folder {
+        root: bool,
+    },
+    List,
+    ListEnd,
+    Link {
+        href: String,
+        added_at: Option<String>,
+        tags: Option<String>,
+    },
+    Other,
+}
+
+/// Decode the character references which show up in exports.
+/// Unknown references are kept as they are.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) if end <= 10 => end,
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let entity = &rest[1..end];
+        let character = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(character) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Parse the attributes of a tag, with lowercase names. Values
+/// may be quoted with either kind of quotes, or not at all.
+fn attributes(mut rest: &str) -> Vec<(String, String)> {
+    let mut attributes = vec![];
+    loop {
+        rest = rest.trim_start();
+        let name_len = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        if name_len == 0 {
+            if rest.is_empty() {
+                return attributes;
+            }
+            rest = &rest[1..];
+            continue;
+        }
+        let name = rest[..name_len].to_ascii_lowercase();
+        rest = rest[name_len..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (value, remaining) = match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => match value[1..].find(quote) {
+                        Some(end) => (&value[1..end + 1], &value[end + 2..]),
+                        None => (&value[1..], ""),
+                    },
+                    _ => {
+                        let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                        (&value[..end], &value[end..])
+                    }
+                };
+                rest = remaining;
+                decode_entities(value)
+            }
+            None => String::new(),
+        };
+        attributes.push((name, value));
+    }
+}
+
+/// Classify the tag with the given source, i.e. the
+/// text between `<` and `>`.
+fn element(tag: &str) -> Element {
+    let name_len = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let (name, rest) = tag.split_at(name_len);
+    match name.trim_end_matches('/').to_ascii_lowercase().as_str() {
+        "h3" => Element::Folder {
+            root: attributes(rest)
+                .iter()
+                .any(|(name, _)| ROOT_FOLDER_ATTRIBUTES.contains(&name.as_str())),
+        },
+        "dl" => Element::List,
+        "/dl" => Element::ListEnd,
+        "a" => {
+            let mut href = None;
+            let mut added_at = None;
+            let mut tags = None;
+            for (name, value) in attributes(rest) {
+                match name.as_str() {
+                    "href" => href = Some(value),
+                    "add_date" => added_at = Some(value),
+                    "tags" => tags = Some(value),
+                    _ => {}
+                }
+            }
+            match href {
+                Some(href) => Element::Link {
+                    href,
+                    added_at,
+                    tags,
+                },
+                None => Element::Other,
+            }
+        }
+        _ => Element::Other,
+    }
+}
+
+/// Parse an `ADD_DATE`, which is a UNIX timestamp. Some browsers
+/// write microseconds rather than seconds.
+fn timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let mut value: i64 = value.trim().parse().ok()?;
+    if value <= 0 {
+        return None;
+    }
+    while value > 100_000_000_000 {
+        value /= 1000;
+    }
+    let time = NaiveDateTime::from_timestamp_opt(value, 0)?;
+    Some(DateTime::from_utc(time, Utc))
+}
+
+/// Collapse the whitespace in a title, and
+/// drop it if nothing is left.
+fn clean_text(text: &str) -> Option<String> {
+    let text = decode_entities(text);
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// What the text since the last relevant element belongs to.
+enum Text {
+    None,
+    Folder { root: bool, start: usize },
+    Link { bookmark: Bookmark, start: usize },
+}
+
+/// Parse all bookmarks in the given export, in the order they are
+/// listed. Anything which can not be made sense of is skipped.
+pub fn parse(html: &str) -> Vec<Bookmark> {
+    let mut bookmarks = vec![];
+    // the folders containing the current position, and the
+    // folder whose list is expected next, if any
+    let mut folders: Vec<Option<String>> = vec![];
+    let mut next_folder: Option<Option<String>> = None;
+    let mut text = Text::None;
+
+    let mut pos = 0;
+    while let Some(offset) = html[pos..].find('<') {
+        let start = pos + offset;
+        let end = match html[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = &html[start + 1..end];
+        pos = end + 1;
+        if tag.starts_with('!') {
+            continue;
+        }
+
+        // any tag ends the text of a folder or link, which
+        // copes with closing tags that are missing
+        match std::mem::replace(&mut text, Text::None) {
+            Text::None => {}
+            Text::Folder { root, start: from } => {
+                let name = clean_text(&html[from..start]);
+                next_folder = Some(if root { None } else { name });
+            }
+            Text::Link {
+                mut bookmark,
+                start: from,
+            } => {
+                bookmark.title = clean_text(&html[from..start]);
+                bookmarks.push(bookmark);
+            }
+        }
+
+        match element(tag) {
+            Element::Folder { root } => text = Text::Folder { root, start: pos },
+            Element::List => folders.push(next_folder.take().flatten()),
+            Element::ListEnd => {
+                folders.pop();
+            }
+            Element::Link {
+                href,
+                added_at,
+                tags,
+            } => {
+                let bookmark = Bookmark {
+                    url: href.trim().to_string(),
+                    title: None,
+                    added_at: added_at.as_deref().and_then(timestamp),
+                    folders: folders.iter().flatten().cloned().collect(),
+                    tags: tags
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|tag| !tag.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                };
+                text = Text::Link {
+                    bookmark,
+                    start: pos,
+                };
+            }
+            Element::Other => {}
+        }
+    }
+
+    if let Text::Link {
+        mut bookmark,
+        start,
+    } = text
+    {
+        bookmark.title = clean_text(&html[start..]);
+        bookmarks.push(bookmark);
+    }
+    bookmarks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_entities() {
+        let cases = [
+            ("Fish &amp; Chips", "Fish & Chips"),
+            ("&lt;b&gt; &quot;x&quot; &#39;y&#39;", "<b> \"x\" 'y'"),
+            ("&#x1F600;", "😀"),
+            ("AT&T", "AT&T"),
+            ("a & b; c", "a & b; c"),
+            ("&unknown;", "&unknown;"),
+        ];
+        for (text, decoded) in cases {
+            assert_eq!(decode_entities(text), decoded, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp("1633791600").unwrap().timestamp(), 1633791600);
+        assert_eq!(
+            timestamp("1633791600123456").unwrap().timestamp(),
+            1633791600
+        );
+        assert_eq!(timestamp("0"), None);
+        assert_eq!(timestamp("soon"), None);
+    }
+
+    #[test]
+    fn test_parse_unclosed_tags() {
+        let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+            <DL><p>
+                <DT><H3 ADD_DATE="1" PERSONAL_TOOLBAR_FOLDER="true">Toolbar</H3>
+                <DL><p>
+                    <DT><A HREF="https://example.com/a?x=1&amp;y=2" ADD_DATE="1633791600">First
+                    link</A>
+                    <DT><H3>Rust Lang
+                    <DL><p>
+                        <DT><a href='https://example.com/b' add_date=1633791601 tags="web, Rust,">Second
+                        <DD>A description
+                        <DT><A HREF=https://example.com/c>
+                    </DL><p>
+                    <DT><A HREF="https://example.com/d">Fourth</A>
+                    <DT><A>Nothing</A>
+                </DL><p>
+                <DT><A HREF="https://example.com/e">Last"#;
+        let bookmarks = parse(html);
+        let urls: Vec<&str> = bookmarks.iter().map(|b| b.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/a?x=1&y=2",
+                "https://example.com/b",
+                "https://example.com/c",
+                "https://example.com/d",
+                "https://example.com/e",
+            ]
+        );
+
+        assert_eq!(bookmarks[0].title.as_deref(), Some("First link"));
+        assert_eq!(bookmarks[0].added_at.unwrap().timestamp(), 1633791600);
+        assert!(bookmarks[0].folders.is_empty());
+        assert!(bookmarks[0].tags.is_empty());
+
+        // the title ends at the next tag, even if the
+        // link is never closed
+        assert_eq!(bookmarks[1].title.as_deref(), Some("Second"));
+        assert_eq!(bookmarks[1].folders, vec!["Rust Lang"]);
+        assert_eq!(bookmarks[1].tags, vec!["web", "Rust"]);
+        assert_eq!(bookmarks[2].title, None);
+        assert_eq!(bookmarks[2].added_at, None);
+        assert_eq!(bookmarks[2].folders, vec!["Rust Lang"]);
+
+        assert!(bookmarks[3].folders.is_empty());
+        assert_eq!(bookmarks[4].title.as_deref(), Some("Last"));
+    }
+}
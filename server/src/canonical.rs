@@ -0,0 +1,282 @@
+//! Canonical forms of submitted URLs. Submissions are stored with
+//! their original and canonical form, and two URLs are considered
+//! the same link if their canonical forms are equal.
+//!
+//! Canonicalization is deliberately conservative, it only applies
+//! changes which (almost) never alter the linked resource:
+//!
+//! - the scheme and host are lowercased, and international host
+//!   names are converted to punycode
+//! - default ports and fragments are removed (except for `#!` style
+//!   fragments, which historically identify distinct pages)
+//! - tracking query parameters are removed, and the remaining
+//!   parameters are sorted
+//! - percent encodings in the path are normalized, and a single
+//!   trailing slash is removed from paths other than the root
+//!
+//! When a URL is submitted, it is additionally [resolved](resolve) by
+//! fetching the page, such that links through URL shorteners and
+//! alternate versions of a page are stored under the page they lead to:
+//!
+//! - redirects are followed, within the limits of [`fetch`], and the
+//!   final URL is used if it responds successfully
+//! - the canonical URL declared by the page (`<link rel="canonical">`
+//!   or `og:url`) is used, but only if it is on the same registrable
+//!   domain as the page, and doesn't point to the front page of a site
+//!   from any other page
+//!
+//! If the page can not be fetched, e.g. because its redirects loop,
+//! the URL as submitted is used.
+
+use crate::{domain, fetch, Context};
+use anyhow::{anyhow, Result};
+use form_urlencoded::Serializer;
+use reqwest::Url;
+
+/// Query parameters which are removed from URLs unless configured
+/// otherwise. Entries ending in `*` match any parameter starting with
+/// the given prefix.
+pub static DEFAULT_TRACKING_PARAMS: &[&str] = &[
+    "utm_*", "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "mc_cid", "mc_eid",
+    "igshid", "yclid", "_hsenc", "_hsmi", "mkt_tok",
+];
+
+/// Determine if the given query parameter should be discarded.
+fn is_discarded(host: &str, name: &str, tracking_params: &[String]) -> bool {
+    let name = name.to_ascii_lowercase();
+    let tracking = tracking_params
+        .iter()
+        .any(|param| match param.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == *param,
+        });
+    tracking
+        || match (host, name.as_str()) {
+            // discard youtube time stamps
+            ("youtu.be" | "youtube.com" | "www.youtube.com", "t") => true,
+            // discard twitter share method tracking
+            ("twitter.com", "s") => true,
+            // keep everything else
+            (_, _) => false,
+        }
+}
+
+/// Decode percent encoded unreserved characters and use upper case
+/// hex digits for all other encoded characters.
+fn normalize_percent_encoding(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut normalized = String::with_capacity(path.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], escape) {
+            (b'%', Some(byte)) => {
+                if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                    normalized.push(byte as char);
+                } else {
+                    normalized.push_str(&format!("%{:02X}", byte));
+                }
+                i += 3;
+            }
+            (byte, _) => {
+                normalized.push(byte as char);
+                i += 1;
+            }
+        }
+    }
+    normalized
+}
+
+/// Compute the canonical form of the given URL, removing the given
+/// tracking parameters. Only absolute `http` and `https` URLs are
+/// accepted.
+pub fn canonicalize(raw: &str, tracking_params: &[String]) -> Result<String> {
+    let mut url = Url::parse(raw.trim())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!("Only http and https URLs can be submitted"));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("Malformed URL"))?
+        .to_string();
+
+    let mut params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !is_discarded(&host, name, tracking_params))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    params.sort();
+    if params.is_empty() {
+        url.set_query(None);
+    } else {
+        let query = params
+            .iter()
+            .fold(
+                Serializer::new(String::new()),
+                |mut builder, (name, value)| {
+                    if value.is_empty() {
+                        builder.append_key_only(name);
+                    } else {
+                        builder.append_pair(name, value);
+                    }
+                    builder
+                },
+            )
+            .finish();
+        url.set_query(Some(&query));
+    }
+
+    if !url.fragment().unwrap_or("").starts_with('!') {
+        url.set_fragment(None);
+    }
+
+    let mut path = normalize_percent_encoding(url.path());
+    if path.len() > 1 && path.ends_with('/') {
+        path.pop();
+    }
+    url.set_path(&path);
+
+    Ok(url.into())
+}
+
+/// Determine whether the canonical URL declared by a page at the given
+/// (canonical) URL is trustworthy. Pages can only claim URLs on their
+/// own registrable domain, and many sites wrongly declare their front
+/// page as the canonical URL of every page.
+fn is_trusted_hint(page: &str, hint: &str) -> bool {
+    let is_front_page = |url: &str| Url::parse(url).map_or(false, |url| url.path() == "/");
+    domain::of_url(page) == domain::of_url(hint) && (is_front_page(page) || !is_front_page(hint))
+}
+
+/// Resolve the canonical form of the given URL, following redirects and
+/// the canonical URL declared by the page, see the [module
+/// documentation](self). This only fails if the URL itself can not be
+/// canonicalized.
+pub async fn resolve(ctx: &Context, raw: &str) -> Result<String> {
+    let tracking_params = ctx.config().tracking_params();
+    let canonical = canonicalize(raw, tracking_params)?;
+    let page = match fetch::fetch_page(ctx, &canonical).await {
+        Ok(page) if page.status.is_success() => page,
+        Ok(page) => {
+            log::info!("Not resolving {}: {}", canonical, page.status);
+            return Ok(canonical);
+        }
+        Err(err) => {
+            log::info!("Failed to resolve {}: {}", canonical, err);
+            return Ok(canonical);
+        }
+    };
+    let resolved = match canonicalize(page.url.as_str(), tracking_params) {
+        Ok(resolved) => resolved,
+        Err(_) => return Ok(canonical),
+    };
+    let hint = page
+        .meta
+        .canonical
+        .as_ref()
+        .and_then(|hint| page.url.join(hint).ok())
+        .and_then(|hint| canonicalize(hint.as_str(), tracking_params).ok())
+        .filter(|hint| is_trusted_hint(&resolved, hint));
+    Ok(hint.unwrap_or(resolved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize() {
+        let tracking: Vec<String> = DEFAULT_TRACKING_PARAMS
+            .iter()
+            .map(|param| param.to_string())
+            .collect();
+        let cases = [
+            // tracking parameters
+            ("https://urls.fyi/?utm_source=google&utm_campaign=test&allowed&other=test", "https://urls.fyi/?allowed&other=test"),
+            ("https://urls.fyi/no-other-params?utm_medium=cpc&utm_content=textlink&utm_term=running+shoes", "https://urls.fyi/no-other-params"),
+            ("https://urls.fyi/no-proto?other_test=&utm_medium=cpc&utm_content=text", "https://urls.fyi/no-proto?other_test"),
+            ("https://example.com/post?utm_source=x#frag", "https://example.com/post"),
+            ("https://example.com/post?fbclid=IwAR0abc", "https://example.com/post"),
+            ("https://example.com/post?gclid=abc&id=1", "https://example.com/post?id=1"),
+            ("https://example.com/post?UTM_Source=x", "https://example.com/post"),
+            ("https://example.com/post?msclkid=1&mc_cid=2&mc_eid=3", "https://example.com/post"),
+            ("https://example.com/post?utm=kept", "https://example.com/post?utm=kept"),
+            ("https://www.youtube.com/watch?v=XXX&t=200s", "https://www.youtube.com/watch?v=XXX"),
+            ("https://youtu.be/YYY?t=200", "https://youtu.be/YYY"),
+            ("https://twitter.com/user/status/1?s=20", "https://twitter.com/user/status/1"),
+            ("https://example.com/?s=search", "https://example.com/?s=search"),
+            // query ordering
+            ("https://example.com/search?q=rust&a=1", "https://example.com/search?a=1&q=rust"),
+            ("https://example.com/search?b=2&a=2&a=1", "https://example.com/search?a=1&a=2&b=2"),
+            ("https://example.com/search?q=hello+world", "https://example.com/search?q=hello+world"),
+            ("https://example.com/search?q=hello%20world", "https://example.com/search?q=hello+world"),
+            ("https://example.com/post?", "https://example.com/post"),
+            // scheme, host, and port
+            ("https://EXAMPLE.com/post", "https://example.com/post"),
+            ("HTTPS://Example.COM/post", "https://example.com/post"),
+            ("https://example.com:443/post", "https://example.com/post"),
+            ("http://example.com:80/post", "http://example.com/post"),
+            ("https://example.com:8443/post", "https://example.com:8443/post"),
+            ("http://example.com/post", "http://example.com/post"),
+            ("  https://example.com/post  ", "https://example.com/post"),
+            // punycode hosts
+            ("https://bücher.de/katalog", "https://xn--bcher-kva.de/katalog"),
+            ("https://BÜCHER.de/katalog", "https://xn--bcher-kva.de/katalog"),
+            ("https://xn--bcher-kva.de/katalog", "https://xn--bcher-kva.de/katalog"),
+            ("https://例え.jp/", "https://xn--r8jz45g.jp/"),
+            // fragments
+            ("https://example.com/post#comments", "https://example.com/post"),
+            ("https://example.com/#!/app/page", "https://example.com/#!/app/page"),
+            // paths and percent encoding
+            ("https://example.com", "https://example.com/"),
+            ("https://example.com/", "https://example.com/"),
+            ("https://example.com/post/", "https://example.com/post"),
+            ("https://example.com/a//", "https://example.com/a/"),
+            ("https://example.com/%7Euser/", "https://example.com/~user"),
+            ("https://example.com/caf%c3%a9", "https://example.com/caf%C3%A9"),
+            ("https://example.com/café", "https://example.com/caf%C3%A9"),
+            ("https://example.com/a%2fb", "https://example.com/a%2Fb"),
+            ("https://example.com/%41%42c", "https://example.com/ABc"),
+            ("https://example.com/with space", "https://example.com/with%20space"),
+        ];
+        for (raw, canonical) in cases {
+            assert_eq!(canonicalize(raw, &tracking).unwrap(), canonical, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_canonicalize_rejects_other_schemes() {
+        assert!(canonicalize("ftp://example.com/file", &[]).is_err());
+        assert!(canonicalize("mailto:test@urls.fyi", &[]).is_err());
+        assert!(canonicalize("not a url", &[]).is_err());
+    }
+
+    #[test]
+    fn test_is_trusted_hint() {
+        let cases = [
+            (
+                "https://example.com/amp/post",
+                "https://example.com/post",
+                true,
+            ),
+            (
+                "https://amp.example.com/post",
+                "https://www.example.com/post",
+                true,
+            ),
+            ("https://example.com/", "https://www.example.com/", true),
+            (
+                "https://example.com/post",
+                "https://other.example/post",
+                false,
+            ),
+            ("https://example.com/post", "https://example.com/", false),
+        ];
+        for (page, hint, trusted) in cases {
+            assert_eq!(is_trusted_hint(page, hint), trusted, "{} {}", page, hint);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Counting of outbound clicks on submitted links. Clicks are buffered
+//! in memory and periodically added to the stored counts in batches, see
+//! [`Url::flush_clicks`](crate::db::models::Url::flush_clicks), such that
+//! clicks on popular links don't all contend for the same row. Buffered
+//! clicks are kept per server process, and lost if the server stops
+//! before they are flushed.
+
+use crate::db::id::UrlID;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Case insensitive fragments of the user agents of crawlers, link
+/// previewers, and command line clients, whose requests are not
+/// counted as clicks.
+const BOT_USER_AGENTS: &[&str] = &[
+    "bot",
+    "crawl",
+    "spider",
+    "slurp",
+    "facebookexternalhit",
+    "embedly",
+    "preview",
+    "headless",
+    "curl/",
+    "wget/",
+    "python-requests",
+    "go-http-client",
+];
+
+/// Whether a request with the given user agent is likely not made
+/// by a person following a link. Requests without a user agent are
+/// assumed to be by bots.
+pub fn is_bot(user_agent: Option<&str>) -> bool {
+    let user_agent = match user_agent {
+        Some(user_agent) if !user_agent.trim().is_empty() => user_agent.to_ascii_lowercase(),
+        _ => return true,
+    };
+    BOT_USER_AGENTS
+        .iter()
+        .any(|fragment| user_agent.contains(fragment))
+}
+
+/// Buffer of clicks which were not yet added to the
+/// stored counts, shared by all requests.
+#[derive(Clone, Default)]
+pub struct ClickCounter {
+    pending: Arc<Mutex<HashMap<UrlID, i64>>>,
+}
+
+impl ClickCounter {
+    /// Record a click on the link of the given submission.
+    pub fn record(&self, url: UrlID) {
+        *self.pending.lock().unwrap().entry(url).or_default() += 1;
+    }
+
+    /// The number of buffered clicks on the link of the given
+    /// submission.
+    pub fn pending(&self, url: UrlID) -> i64 {
+        self.pending.lock().unwrap().get(&url).copied().unwrap_or(0)
+    }
+
+    /// Take all buffered clicks, leaving the buffer empty.
+    pub(crate) fn take(&self) -> HashMap<UrlID, i64> {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+
+    /// Return clicks which were taken, but could not
+    /// be stored, to the buffer.
+    pub(crate) fn restore(&self, clicks: HashMap<UrlID, i64>) {
+        let mut pending = self.pending.lock().unwrap();
+        for (url, count) in clicks {
+            *pending.entry(url).or_default() += count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_bot() {
+        let browser = "Mozilla/5.0 (X11; Linux x86_64; rv:93.0) Gecko/20100101 Firefox/93.0";
+        assert!(!is_bot(Some(browser)));
+        for user_agent in [
+            "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+            "Mozilla/5.0 (compatible; Urlsbot/0.1.0; +https://urls.fyi/bot.html)",
+            "facebookexternalhit/1.1",
+            "curl/7.79.1",
+            "Mozilla/5.0 (X11; Linux x86_64) HeadlessChrome/94.0.4606.71",
+            " ",
+        ] {
+            assert!(is_bot(Some(user_agent)), "{}", user_agent);
+        }
+        assert!(is_bot(None));
+    }
+
+    #[test]
+    fn test_counter() {
+        let counter = ClickCounter::default();
+        let (a, b) = (UrlID::new(), UrlID::new());
+        counter.record(a);
+        counter.record(a);
+        counter.record(b);
+        assert_eq!(counter.pending(a), 2);
+
+        let taken = counter.take();
+        assert_eq!(taken.get(&a), Some(&2));
+        assert_eq!(taken.get(&b), Some(&1));
+        assert_eq!(counter.pending(a), 0);
+
+        counter.record(a);
+        counter.restore(taken);
+        assert_eq!(counter.pending(a), 3);
+        assert_eq!(counter.pending(b), 1);
+    }
+}
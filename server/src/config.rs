@@ -1,28 +1,146 @@
-use anyhow::Result;
-use dotenv::var;
+use crate::embed::{self, Provider};
+use crate::spam::SpamFilter;
+use crate::{canonical, signing};
+use chrono::Duration;
+use juniper::GraphQLEnum;
 use nanoid::nanoid;
-use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
+use reqwest::Url;
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 
+// defaults of optional configuration variables, see `load`
 static DEFAULT_WWW: &str = "www/static";
 static DEFAULT_SMTP_PORT: u16 = 587;
 static DEFAULT_INDEX: &str = "index";
+static DEFAULT_STORAGE: &str = "storage";
+static DEFAULT_INVITE_QUOTA: i64 = 3;
+static DEFAULT_INVITE_ACCRUAL_DAYS: i64 = 7;
+static DEFAULT_INVITE_BANK: i64 = 5;
+static DEFAULT_FETCH_TIMEOUT_SECS: u64 = 10;
+static DEFAULT_ALLOW_PRIVATE_URLS: bool = false;
+static DEFAULT_URL_EDIT_WINDOW_MINUTES: i64 = 120;
+static DEFAULT_MAX_COMMENT_DEPTH: i32 = 6;
+static DEFAULT_COMMENT_EDIT_WINDOW_MINUTES: i64 = 15;
+static DEFAULT_RESUBMIT_AFTER_DAYS: i64 = 365;
+static DEFAULT_ALLOW_SELF_VOTES: bool = true;
+static DEFAULT_DOWNVOTES_ENABLED: bool = false;
+static DEFAULT_DOWNVOTE_MIN_KARMA: i64 = 10;
+static DEFAULT_KARMA_SUBMISSION_WEIGHT: i64 = 1;
+static DEFAULT_KARMA_COMMENT_WEIGHT: i64 = 1;
+static DEFAULT_PUBLIC_REVISIONS: bool = false;
+static DEFAULT_PUBLIC_VOTERS: bool = false;
+static DEFAULT_ANONYMOUS_SUBMISSIONS: bool = false;
+static DEFAULT_TRENDING_GRAVITY: f64 = 1.6;
+static DEFAULT_ARCHIVE_URL: &str = "https://web.archive.org";
+static DEFAULT_SPAM_MAX_LINKS: usize = 5;
+static DEFAULT_SPAM_NEW_ACCOUNT_HOURS: i64 = 24;
+static DEFAULT_SPAM_NEW_ACCOUNT_MAX_LINKS: usize = 1;
+static DEFAULT_AKISMET_URL: &str = "https://rest.akismet.com";
+static DEFAULT_SUBMISSIONS_PER_HOUR: usize = 5;
+static DEFAULT_COMMENTS_PER_HOUR: usize = 30;
+static DEFAULT_NEW_ACCOUNT_SUBMISSIONS_PER_HOUR: usize = 2;
+static DEFAULT_NEW_ACCOUNT_COMMENTS_PER_HOUR: usize = 10;
+static DEFAULT_RATE_LIMIT_NEW_ACCOUNT_HOURS: i64 = 24;
+/// Limits of the test configuration, high enough to not get
+/// in the way of tests which aren't about rate limits.
+static TEST_POSTS_PER_HOUR: usize = 1_000;
+/// The sitemap protocol allows at most this many URLs per sitemap.
+static DEFAULT_SITEMAP_SIZE: i64 = 50_000;
+static DEFAULT_MAX_QUERY_DEPTH: usize = 15;
+static DEFAULT_MAX_QUERY_COMPLEXITY: u64 = 10_000;
+static DEFAULT_REGISTER_PERSISTED_QUERIES: bool = false;
+static DEFAULT_PERSISTED_QUERIES_ONLY: bool = false;
 
-static ENV: Lazy<Config> = Lazy::new(|| match load_from_env() {
-    Ok(conf) => conf,
-    Err(msg) => {
-        log::error!("Failed to load configuration: {}", msg);
-        panic!("Failed to load configuration: {}", msg);
-    }
-});
+/// Session keys must have at least this many bytes.
+static MIN_SESSION_KEY_LEN: usize = 32;
+
+static ENV: OnceCell<Config> = OnceCell::new();
 
 #[derive(Debug, Clone)]
 pub struct Config {
     database_url: String,
     search_idx: Option<PathBuf>,
+    storage_dir: PathBuf,
     www_dir: PathBuf,
     hostname: String,
     smtp: Option<SmtpConfig>,
+    geoip_api: Option<String>,
+    invite_quota: i64,
+    invite_accrual: Option<Duration>,
+    invite_bank: i64,
+    email_blocklist: Option<PathBuf>,
+    registration_mode: RegistrationMode,
+    session_keys: Vec<String>,
+    ip_privacy: IpPrivacy,
+    tracking_params: Vec<String>,
+    fetch_timeout: std::time::Duration,
+    fetch_private_addresses: bool,
+    allow_private_urls: bool,
+    url_edit_window: Duration,
+    max_comment_depth: i32,
+    comment_edit_window: Duration,
+    resubmit_after: Duration,
+    allow_self_votes: bool,
+    downvotes_enabled: bool,
+    downvote_min_karma: i64,
+    karma_submission_weight: i64,
+    karma_comment_weight: i64,
+    public_revisions: bool,
+    public_voters: bool,
+    anonymous_submissions: bool,
+    trending_gravity: f64,
+    archive_url: Option<String>,
+    spam_max_links: usize,
+    spam_new_account_age: Duration,
+    spam_new_account_max_links: usize,
+    spam_filter: Option<Arc<dyn SpamFilter>>,
+    akismet_url: String,
+    akismet_key: Option<String>,
+    posting_limits: PostingLimits,
+    new_account_posting_limits: PostingLimits,
+    rate_limit_new_account_age: Duration,
+    pocket_consumer_key: Option<String>,
+    oembed_providers: Vec<Provider>,
+    sitemap_size: i64,
+    max_query_depth: usize,
+    max_query_complexity: u64,
+    persisted_queries: Option<PathBuf>,
+    register_persisted_queries: bool,
+    persisted_queries_only: bool,
+}
+
+/// Determines who may register a new account.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationMode {
+    /// Registering requires an invitation.
+    InviteOnly,
+    /// Anyone may register, invitations are not required.
+    Open,
+    /// No new accounts may be registered.
+    Closed,
+}
+
+/// Determines how client IP addresses are stored, e.g. as
+/// part of login sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpPrivacy {
+    /// Addresses are stored in full.
+    Full,
+    /// Addresses are truncated to their network prefix (/24
+    /// for IPv4 and /48 for IPv6) before being stored.
+    Truncate,
+    /// Only a keyed hash of each address is stored.
+    Hash,
+}
+
+/// How many submissions and comments a user may post per hour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PostingLimits {
+    pub submissions: usize,
+    pub comments: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -34,26 +152,121 @@ pub struct SmtpConfig {
 }
 
 impl Config {
-    /// Configuration loaded from the
-    /// environment.
+    /// Load and validate the configuration from the environment,
+    /// including the `.env` file, once. This is meant to be called
+    /// on startup, such that the server fails with all missing or
+    /// invalid variables right away.
+    pub fn init() -> Result<&'static Self, ConfigError> {
+        ENV.get_or_try_init(|| {
+            dotenv::dotenv().ok();
+            load_from_env()
+        })
+    }
+
+    /// Configuration loaded from the environment by
+    /// [`Config::init`], which must have been called.
     pub fn env() -> &'static Self {
-        &ENV
+        ENV.get().expect("Config::init not called")
+    }
+
+    /// Load the configuration from the variables of the process
+    /// environment, without the `.env` file or caching the result.
+    pub fn load() -> Result<Self, ConfigError> {
+        load_from_env()
     }
 
     /// Configuration suitable for unit
     /// or integration tests. Database
     /// connections are in-memory, and no
-    /// smtp config is provided.
+    /// smtp config is provided. Stored files
+    /// are written to a fresh temporary directory,
+    /// pages are not archived, and links are not
+    /// unfurled.
     pub fn test() -> Self {
         Self {
             database_url: format!("file:{}?mode=memory&cache=shared", nanoid!(16)),
             search_idx: None,
+            storage_dir: std::env::temp_dir().join(format!("urls-storage-{}", nanoid!(16))),
             www_dir: DEFAULT_WWW.into(),
             hostname: "localhost".into(),
             smtp: None,
+            geoip_api: None,
+            invite_quota: DEFAULT_INVITE_QUOTA,
+            invite_accrual: Some(Duration::days(DEFAULT_INVITE_ACCRUAL_DAYS)),
+            invite_bank: DEFAULT_INVITE_BANK,
+            email_blocklist: None,
+            registration_mode: RegistrationMode::InviteOnly,
+            session_keys: vec![nanoid!(32)],
+            ip_privacy: IpPrivacy::Full,
+            tracking_params: default_tracking_params(),
+            fetch_timeout: std::time::Duration::from_secs(DEFAULT_FETCH_TIMEOUT_SECS),
+            fetch_private_addresses: true,
+            // test pages are served locally
+            allow_private_urls: true,
+            url_edit_window: Duration::minutes(DEFAULT_URL_EDIT_WINDOW_MINUTES),
+            max_comment_depth: DEFAULT_MAX_COMMENT_DEPTH,
+            comment_edit_window: Duration::minutes(DEFAULT_COMMENT_EDIT_WINDOW_MINUTES),
+            resubmit_after: Duration::days(DEFAULT_RESUBMIT_AFTER_DAYS),
+            allow_self_votes: DEFAULT_ALLOW_SELF_VOTES,
+            downvotes_enabled: DEFAULT_DOWNVOTES_ENABLED,
+            // the test users have no karma to begin with
+            downvote_min_karma: 0,
+            karma_submission_weight: DEFAULT_KARMA_SUBMISSION_WEIGHT,
+            karma_comment_weight: DEFAULT_KARMA_COMMENT_WEIGHT,
+            public_revisions: DEFAULT_PUBLIC_REVISIONS,
+            public_voters: DEFAULT_PUBLIC_VOTERS,
+            anonymous_submissions: DEFAULT_ANONYMOUS_SUBMISSIONS,
+            trending_gravity: DEFAULT_TRENDING_GRAVITY,
+            archive_url: None,
+            spam_max_links: DEFAULT_SPAM_MAX_LINKS,
+            spam_new_account_age: Duration::hours(DEFAULT_SPAM_NEW_ACCOUNT_HOURS),
+            spam_new_account_max_links: DEFAULT_SPAM_NEW_ACCOUNT_MAX_LINKS,
+            spam_filter: None,
+            akismet_url: DEFAULT_AKISMET_URL.into(),
+            akismet_key: None,
+            posting_limits: PostingLimits {
+                submissions: TEST_POSTS_PER_HOUR,
+                comments: TEST_POSTS_PER_HOUR,
+            },
+            new_account_posting_limits: PostingLimits {
+                submissions: TEST_POSTS_PER_HOUR,
+                comments: TEST_POSTS_PER_HOUR,
+            },
+            rate_limit_new_account_age: Duration::hours(DEFAULT_RATE_LIMIT_NEW_ACCOUNT_HOURS),
+            pocket_consumer_key: None,
+            oembed_providers: vec![],
+            sitemap_size: DEFAULT_SITEMAP_SIZE,
+            max_query_depth: DEFAULT_MAX_QUERY_DEPTH,
+            max_query_complexity: DEFAULT_MAX_QUERY_COMPLEXITY,
+            persisted_queries: None,
+            register_persisted_queries: DEFAULT_REGISTER_PERSISTED_QUERIES,
+            persisted_queries_only: DEFAULT_PERSISTED_QUERIES_ONLY,
         }
     }
 
+    /// Use the given file as a blocklist of email
+    /// domains. This is useful to customize the
+    /// test configuration.
+    pub fn with_email_blocklist(mut self, path: impl Into<PathBuf>) -> Self {
+        self.email_blocklist = Some(path.into());
+        self
+    }
+
+    /// Use the given registration mode. This is useful
+    /// to customize the test configuration.
+    pub fn with_registration_mode(mut self, mode: RegistrationMode) -> Self {
+        self.registration_mode = mode;
+        self
+    }
+
+    /// Store client IP addresses as described by the given
+    /// privacy mode. This is useful to customize the test
+    /// configuration.
+    pub fn with_ip_privacy(mut self, privacy: IpPrivacy) -> Self {
+        self.ip_privacy = privacy;
+        self
+    }
+
     /// SQLite database URI.
     pub fn database(&self) -> &str {
         self.database_url.as_str()
@@ -77,6 +290,12 @@ impl Config {
         self.search_idx.as_ref().map(|p| p.as_ref())
     }
 
+    /// Directory in which files generated by the
+    /// server (e.g. data exports) are stored.
+    pub fn storage_dir(&self) -> &Path {
+        self.storage_dir.as_path()
+    }
+
     /// Directory to serve static files
     /// from.
     pub fn www(&self) -> &Path {
@@ -93,6 +312,515 @@ impl Config {
     pub fn hostname(&self) -> &str {
         &self.hostname
     }
+
+    /// Geo IP lookup service used to approximate the
+    /// location of login sessions, if any. The service
+    /// is queried with an `ip` query parameter. Client
+    /// addresses are only sent to a service which is
+    /// configured explicitly.
+    pub fn geoip_api(&self) -> Option<&str> {
+        self.geoip_api.as_deref()
+    }
+
+    /// Number of invitations a user may have issued at
+    /// any time, unless overridden for the specific user.
+    /// Revoked invitations do not count towards the quota.
+    pub fn invite_quota(&self) -> i64 {
+        self.invite_quota
+    }
+
+    /// Interval in which users earn an additional invitation,
+    /// or `None` if invitations are not replenished.
+    pub fn invite_accrual(&self) -> Option<Duration> {
+        self.invite_accrual
+    }
+
+    /// Maximum number of available invitations up to which
+    /// additional invitations are earned.
+    pub fn invite_bank(&self) -> i64 {
+        self.invite_bank
+    }
+
+    /// File listing additional (e.g. disposable) email domains
+    /// which may not be used to register, one domain per line.
+    pub fn email_blocklist(&self) -> Option<&Path> {
+        self.email_blocklist.as_deref()
+    }
+
+    /// Use the given timeout when fetching submitted pages.
+    /// This is useful to customize the test configuration.
+    pub fn with_fetch_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.fetch_timeout = timeout;
+        self
+    }
+
+    /// Allow or refuse fetching submitted pages from private
+    /// network addresses. The test configuration allows this,
+    /// such that pages can be served locally.
+    pub fn with_fetch_private_addresses(mut self, allow: bool) -> Self {
+        self.fetch_private_addresses = allow;
+        self
+    }
+
+    /// Allow or refuse submitting URLs on private networks or
+    /// with IP addresses. The test configuration allows this,
+    /// such that pages can be served locally.
+    pub fn with_allow_private_urls(mut self, allow: bool) -> Self {
+        self.allow_private_urls = allow;
+        self
+    }
+
+    /// Allow submitters to edit their submissions for the given
+    /// time. This is useful to customize the test configuration.
+    pub fn with_url_edit_window(mut self, window: Duration) -> Self {
+        self.url_edit_window = window;
+        self
+    }
+
+    /// Allow replies to be nested at most `depth` levels deep.
+    /// This is useful to customize the test configuration.
+    pub fn with_max_comment_depth(mut self, depth: i32) -> Self {
+        self.max_comment_depth = depth;
+        self
+    }
+
+    /// Allow authors to edit their comments for the given
+    /// time. This is useful to customize the test configuration.
+    pub fn with_comment_edit_window(mut self, window: Duration) -> Self {
+        self.comment_edit_window = window;
+        self
+    }
+
+    /// Allow URLs to be submitted again once their previous
+    /// submission is older than the given time. This is useful
+    /// to customize the test configuration.
+    pub fn with_resubmit_after(mut self, window: Duration) -> Self {
+        self.resubmit_after = window;
+        self
+    }
+
+    /// Allow or disallow users to vote on their own
+    /// submissions and comments. This is useful to
+    /// customize the test configuration.
+    pub fn with_allow_self_votes(mut self, allow: bool) -> Self {
+        self.allow_self_votes = allow;
+        self
+    }
+
+    /// Enable or disable downvotes. This is useful to customize
+    /// the test configuration.
+    pub fn with_downvotes_enabled(mut self, enabled: bool) -> Self {
+        self.downvotes_enabled = enabled;
+        self
+    }
+
+    /// Only allow users with at least the given karma to
+    /// downvote. This is useful to customize the test
+    /// configuration.
+    pub fn with_downvote_min_karma(mut self, karma: i64) -> Self {
+        self.downvote_min_karma = karma;
+        self
+    }
+
+    /// Weigh the scores of submissions and comments with the
+    /// given factors when computing karma. This is useful to
+    /// customize the test configuration.
+    pub fn with_karma_weights(mut self, submission: i64, comment: i64) -> Self {
+        self.karma_submission_weight = submission;
+        self.karma_comment_weight = comment;
+        self
+    }
+
+    /// Show or hide the edit history of submissions and comments
+    /// from users other than the author and administrators. This
+    /// is useful to customize the test configuration.
+    pub fn with_public_revisions(mut self, public: bool) -> Self {
+        self.public_revisions = public;
+        self
+    }
+
+    /// Show or hide who voted for submissions from users other
+    /// than the submitter and administrators. This is useful to
+    /// customize the test configuration.
+    pub fn with_public_voters(mut self, public: bool) -> Self {
+        self.public_voters = public;
+        self
+    }
+
+    /// Allow or refuse submitting URLs without showing the
+    /// submitter. This is useful to customize the test
+    /// configuration.
+    pub fn with_anonymous_submissions(mut self, allow: bool) -> Self {
+        self.anonymous_submissions = allow;
+        self
+    }
+
+    /// Use the given exponent for the age of submissions when
+    /// ranking trending submissions. This is useful to customize
+    /// the test configuration.
+    pub fn with_trending_gravity(mut self, gravity: f64) -> Self {
+        self.trending_gravity = gravity;
+        self
+    }
+
+    /// Archive submitted pages with the service at the given URL,
+    /// or disable archiving. This is useful to customize the test
+    /// configuration.
+    pub fn with_archive_url(mut self, url: Option<String>) -> Self {
+        self.archive_url = url;
+        self
+    }
+
+    /// Check new submissions and comments with the given spam
+    /// filter instead of the configured one. This is useful to
+    /// customize the test configuration.
+    pub fn with_spam_filter(mut self, filter: Arc<dyn SpamFilter>) -> Self {
+        self.spam_filter = Some(filter);
+        self
+    }
+
+    /// Treat content by accounts younger than the given age as
+    /// posted by a new account. This is useful to customize the
+    /// test configuration.
+    pub fn with_spam_new_account_age(mut self, age: Duration) -> Self {
+        self.spam_new_account_age = age;
+        self
+    }
+
+    /// Limit how many submissions and comments established
+    /// accounts may post per hour. This is useful to customize
+    /// the test configuration.
+    pub fn with_posting_limits(mut self, limits: PostingLimits) -> Self {
+        self.posting_limits = limits;
+        self
+    }
+
+    /// Limit how many submissions and comments new accounts may
+    /// post per hour. This is useful to customize the test
+    /// configuration.
+    pub fn with_new_account_posting_limits(mut self, limits: PostingLimits) -> Self {
+        self.new_account_posting_limits = limits;
+        self
+    }
+
+    /// Treat accounts younger than the given age as new accounts
+    /// for rate limits. This is useful to customize the test
+    /// configuration.
+    pub fn with_rate_limit_new_account_age(mut self, age: Duration) -> Self {
+        self.rate_limit_new_account_age = age;
+        self
+    }
+
+    /// Use the given keys to sign and verify tokens, where
+    /// the first key is primary. This is useful to customize
+    /// the test configuration.
+    pub fn with_session_keys(mut self, keys: &[&str]) -> Self {
+        assert!(!keys.is_empty(), "At least one session key is required");
+        self.session_keys = keys.iter().map(|key| key.to_string()).collect();
+        self
+    }
+
+    /// Unfurl links using the given oEmbed providers. This
+    /// is useful to customize the test configuration.
+    pub fn with_oembed_providers(mut self, providers: Vec<Provider>) -> Self {
+        self.oembed_providers = providers;
+        self
+    }
+
+    /// List at most the given number of submissions per
+    /// sitemap. This is useful to customize the test
+    /// configuration.
+    pub fn with_sitemap_size(mut self, size: i64) -> Self {
+        assert!(
+            (1..=DEFAULT_SITEMAP_SIZE).contains(&size),
+            "Sitemaps list between 1 and {} URLs",
+            DEFAULT_SITEMAP_SIZE
+        );
+        self.sitemap_size = size;
+        self
+    }
+
+    /// Reject GraphQL queries nested deeper than `depth` fields, or
+    /// with a complexity above `complexity`. This is useful to
+    /// customize the test configuration.
+    pub fn with_query_limits(mut self, depth: usize, complexity: u64) -> Self {
+        self.max_query_depth = depth;
+        self.max_query_complexity = complexity;
+        self
+    }
+
+    /// Load persisted GraphQL queries from the given manifest. This
+    /// is useful to customize the test configuration.
+    pub fn with_persisted_queries(mut self, path: impl Into<PathBuf>) -> Self {
+        self.persisted_queries = Some(path.into());
+        self
+    }
+
+    /// Persist GraphQL queries which clients register on first use.
+    /// This is useful to customize the test configuration.
+    pub fn with_register_persisted_queries(mut self, register: bool) -> Self {
+        self.register_persisted_queries = register;
+        self
+    }
+
+    /// Only allow persisted GraphQL queries, except for administrators.
+    /// This is useful to customize the test configuration.
+    pub fn with_persisted_queries_only(mut self, only: bool) -> Self {
+        self.persisted_queries_only = only;
+        self
+    }
+
+    /// Who may register new accounts.
+    pub fn registration_mode(&self) -> RegistrationMode {
+        self.registration_mode
+    }
+
+    /// Secret key used to sign tokens handed out
+    /// to users, e.g. in emails.
+    pub fn session_key(&self) -> &[u8] {
+        self.session_keys[0].as_bytes()
+    }
+
+    /// All keys which are accepted when verifying signed
+    /// tokens, starting with the primary
+    /// [`session_key`](Config::session_key). Older keys remain
+    /// valid during a key rotation.
+    pub fn session_keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.session_keys.iter().map(String::as_bytes)
+    }
+
+    /// How client IP addresses are stored.
+    pub fn ip_privacy(&self) -> IpPrivacy {
+        self.ip_privacy
+    }
+
+    /// Maximum time spent fetching a submitted page,
+    /// including any redirects.
+    pub fn fetch_timeout(&self) -> std::time::Duration {
+        self.fetch_timeout
+    }
+
+    /// Whether submitted pages may be fetched from private
+    /// network addresses. This is never enabled outside of
+    /// tests, to avoid server side request forgery.
+    pub fn fetch_private_addresses(&self) -> bool {
+        self.fetch_private_addresses
+    }
+
+    /// Whether URLs on private networks, like `localhost`, and
+    /// URLs with IP addresses may be submitted, e.g. on instances
+    /// which are only reachable on an intranet.
+    pub fn allow_private_urls(&self) -> bool {
+        self.allow_private_urls
+    }
+
+    /// Time after submitting a URL during which the submitter
+    /// may edit its title and description.
+    pub fn url_edit_window(&self) -> Duration {
+        self.url_edit_window
+    }
+
+    /// How many levels deep replies to comments may be
+    /// nested, where comments on a submission are at
+    /// depth zero.
+    pub fn max_comment_depth(&self) -> i32 {
+        self.max_comment_depth
+    }
+
+    /// Time after commenting during which the author may
+    /// edit the comment.
+    pub fn comment_edit_window(&self) -> Duration {
+        self.comment_edit_window
+    }
+
+    /// Time after which a URL may be submitted again, starting
+    /// a new discussion. Deleted and removed submissions don't
+    /// prevent submitting their URL again at all.
+    pub fn resubmit_after(&self) -> Duration {
+        self.resubmit_after
+    }
+
+    /// Whether users may vote on their own submissions and comments.
+    pub fn allow_self_votes(&self) -> bool {
+        self.allow_self_votes
+    }
+
+    /// Whether users may downvote submissions, such that
+    /// the score of a submission can decrease.
+    pub fn downvotes_enabled(&self) -> bool {
+        self.downvotes_enabled
+    }
+
+    /// Minimum karma users need to downvote, if downvotes
+    /// are enabled.
+    pub fn downvote_min_karma(&self) -> i64 {
+        self.downvote_min_karma
+    }
+
+    /// Factor the score of each submission of a user is
+    /// weighed with in their karma.
+    pub fn karma_submission_weight(&self) -> i64 {
+        self.karma_submission_weight
+    }
+
+    /// Factor the score of each comment of a user is
+    /// weighed with in their karma.
+    pub fn karma_comment_weight(&self) -> i64 {
+        self.karma_comment_weight
+    }
+
+    /// Whether everyone may see the edit history of submissions
+    /// and comments, instead of only their authors and
+    /// administrators.
+    pub fn public_revisions(&self) -> bool {
+        self.public_revisions
+    }
+
+    /// Whether everyone may see who voted for a submission,
+    /// instead of only the submitter and administrators.
+    pub fn public_voters(&self) -> bool {
+        self.public_voters
+    }
+
+    /// Whether users may submit URLs anonymously, hiding the
+    /// submitter from everyone but themselves and administrators.
+    pub fn anonymous_submissions(&self) -> bool {
+        self.anonymous_submissions
+    }
+
+    /// Exponent applied to the age of submissions when ranking
+    /// trending submissions. Larger values let submissions fall
+    /// off the front page faster.
+    pub fn trending_gravity(&self) -> f64 {
+        self.trending_gravity
+    }
+
+    /// Base URL of the service implementing the save API of the
+    /// Wayback Machine, which is used to archive submitted pages,
+    /// or `None` if pages are not archived.
+    pub fn archive_url(&self) -> Option<&str> {
+        self.archive_url.as_deref()
+    }
+
+    /// Maximum number of links to other sites in a comment or in
+    /// the text of a submission, before the built-in spam filter
+    /// considers it suspect, see [`Heuristic`](crate::spam::Heuristic).
+    pub fn spam_max_links(&self) -> usize {
+        self.spam_max_links
+    }
+
+    /// Accounts younger than this are considered new by the
+    /// built-in spam filter, and may post fewer links, see
+    /// [`spam_new_account_max_links`](Config::spam_new_account_max_links).
+    pub fn spam_new_account_age(&self) -> Duration {
+        self.spam_new_account_age
+    }
+
+    /// Maximum number of links to other sites new accounts
+    /// may post in a comment or in the text of a submission,
+    /// before it is considered suspect.
+    pub fn spam_new_account_max_links(&self) -> usize {
+        self.spam_new_account_max_links
+    }
+
+    /// Spam filter which replaces the configured one, see
+    /// [`with_spam_filter`](Config::with_spam_filter).
+    pub fn spam_filter(&self) -> Option<&Arc<dyn SpamFilter>> {
+        self.spam_filter.as_ref()
+    }
+
+    /// Base URL of the service implementing the Akismet API,
+    /// which is used to check for spam with the `akismet`
+    /// feature.
+    pub fn akismet_url(&self) -> &str {
+        &self.akismet_url
+    }
+
+    /// API key for the Akismet service, or `None` if content
+    /// is checked by the built-in spam filter.
+    pub fn akismet_key(&self) -> Option<&str> {
+        self.akismet_key.as_deref()
+    }
+
+    /// How many submissions and comments users may post per
+    /// hour. Administrators are not limited.
+    pub fn posting_limits(&self) -> PostingLimits {
+        self.posting_limits
+    }
+
+    /// How many submissions and comments accounts younger than
+    /// [`rate_limit_new_account_age`](Config::rate_limit_new_account_age)
+    /// may post per hour.
+    pub fn new_account_posting_limits(&self) -> PostingLimits {
+        self.new_account_posting_limits
+    }
+
+    /// Accounts younger than this are subject to the
+    /// [`new_account_posting_limits`](Config::new_account_posting_limits).
+    pub fn rate_limit_new_account_age(&self) -> Duration {
+        self.rate_limit_new_account_age
+    }
+
+    /// Consumer key of the Pocket application used to import
+    /// bookmarks from Pocket, or `None` if importing from Pocket
+    /// is not available.
+    pub fn pocket_consumer_key(&self) -> Option<&str> {
+        self.pocket_consumer_key.as_deref()
+    }
+
+    /// Providers used to unfurl links into embeds, see
+    /// [`embed`](crate::embed). Configured providers take
+    /// precedence over the built-in ones.
+    pub fn oembed_providers(&self) -> &[Provider] {
+        &self.oembed_providers
+    }
+
+    /// Maximum number of submissions listed by a single
+    /// sitemap. Once there are more submissions, they are
+    /// split across several sitemaps, see
+    /// [`sitemap`](crate::pages::sitemap).
+    pub fn sitemap_size(&self) -> i64 {
+        self.sitemap_size
+    }
+
+    /// How many levels deep the fields of a GraphQL query may
+    /// be nested.
+    pub fn max_query_depth(&self) -> usize {
+        self.max_query_depth
+    }
+
+    /// Maximum estimated number of values a GraphQL query may
+    /// resolve, where connections count their selections once
+    /// per requested item.
+    pub fn max_query_complexity(&self) -> u64 {
+        self.max_query_complexity
+    }
+
+    /// Manifest of persisted GraphQL queries, loaded on startup,
+    /// see [`persisted`](crate::persisted).
+    pub fn persisted_queries(&self) -> Option<&Path> {
+        self.persisted_queries.as_deref()
+    }
+
+    /// Whether queries sent along with their hash are persisted,
+    /// such that clients can refer to them by their hash in later
+    /// requests.
+    pub fn register_persisted_queries(&self) -> bool {
+        self.register_persisted_queries
+    }
+
+    /// Whether only persisted queries may be executed, except
+    /// for clients logged in as administrators.
+    pub fn persisted_queries_only(&self) -> bool {
+        self.persisted_queries_only
+    }
+
+    /// Query parameters which are removed when computing the
+    /// canonical form of a submitted URL. Entries ending in `*`
+    /// match any parameter with the given prefix.
+    pub fn tracking_params(&self) -> &[String] {
+        &self.tracking_params
+    }
 }
 
 impl SmtpConfig {
@@ -113,11 +841,224 @@ impl SmtpConfig {
     }
 }
 
-fn load_from_env() -> Result<Config> {
-    let database_url = var("DATABASE_URL")?;
+fn default_tracking_params() -> Vec<String> {
+    canonical::DEFAULT_TRACKING_PARAMS
+        .iter()
+        .map(|param| param.to_string())
+        .collect()
+}
+
+/// A variable of the environment which could not be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VarError {
+    /// A required variable is not set.
+    Missing(&'static str),
+    /// The value of the variable can't be used.
+    Invalid { name: &'static str, reason: String },
+}
+
+impl VarError {
+    /// The name of the variable.
+    pub fn name(&self) -> &'static str {
+        match self {
+            VarError::Missing(name) | VarError::Invalid { name, .. } => name,
+        }
+    }
+}
+
+impl fmt::Display for VarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VarError::Missing(name) => write!(f, "{} is not set", name),
+            VarError::Invalid { name, reason } => write!(f, "{} is invalid: {}", name, reason),
+        }
+    }
+}
+
+/// Error returned when the configuration of the environment
+/// is incomplete or invalid, listing every offending variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    errors: Vec<VarError>,
+}
+
+impl ConfigError {
+    /// The offending variables, in the order they are read.
+    pub fn errors(&self) -> &[VarError] {
+        &self.errors
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} configuration variable(s) missing or invalid",
+            self.errors.len()
+        )?;
+        for error in &self.errors {
+            write!(f, "\n  {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Reads configuration variables, recording every missing or
+/// invalid variable instead of stopping at the first one.
+struct Vars<F> {
+    lookup: F,
+    errors: Vec<VarError>,
+}
+
+impl<F: Fn(&str) -> Option<String>> Vars<F> {
+    fn get(&self, name: &str) -> Option<String> {
+        (self.lookup)(name)
+    }
+
+    fn missing(&mut self, name: &'static str) {
+        self.errors.push(VarError::Missing(name));
+    }
+
+    fn invalid(&mut self, name: &'static str, reason: impl Into<String>) {
+        self.errors.push(VarError::Invalid {
+            name,
+            reason: reason.into(),
+        });
+    }
+
+    /// The value of a variable which must be set.
+    fn required(&mut self, name: &'static str) -> String {
+        match self.get(name) {
+            Some(value) if !value.trim().is_empty() => value,
+            _ => {
+                self.missing(name);
+                String::new()
+            }
+        }
+    }
+
+    /// The parsed value of the given variable, if it is set.
+    fn parse_optional<T>(&mut self, name: &'static str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = self.get(name)?;
+        match value.trim().parse() {
+            Ok(parsed) => Some(parsed),
+            Err(err) => {
+                self.invalid(name, format!("'{}' ({})", value, err));
+                None
+            }
+        }
+    }
 
-    let search_idx: PathBuf = var("INDEX_DIR")
-        .unwrap_or_else(|_| {
+    /// The parsed value of the given variable, or
+    /// the default if it is not set.
+    fn parse<T>(&mut self, name: &'static str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.parse_optional(name).unwrap_or(default)
+    }
+
+    /// A non-negative number of the given unit, e.g. `Duration::minutes`,
+    /// or the default if the variable is not set.
+    fn duration(
+        &mut self,
+        name: &'static str,
+        default: i64,
+        unit: fn(i64) -> Duration,
+    ) -> Duration {
+        match self.parse_optional::<i64>(name) {
+            Some(value) if value < 0 => {
+                self.invalid(name, format!("'{}' is negative", value));
+                unit(default)
+            }
+            value => unit(value.unwrap_or(default)),
+        }
+    }
+
+    /// A non-negative number, or the
+    /// default if the variable is not set.
+    fn non_negative<T>(&mut self, name: &'static str, default: T) -> T
+    where
+        T: FromStr + PartialOrd + Default + fmt::Display,
+        T::Err: fmt::Display,
+    {
+        match self.parse_optional::<T>(name) {
+            Some(value) if value < T::default() => {
+                self.invalid(name, format!("'{}' is negative", value));
+                default
+            }
+            value => value.unwrap_or(default),
+        }
+    }
+
+    /// An http(s) URL, if the given variable is set.
+    fn url(&mut self, name: &'static str) -> Option<String> {
+        let value = self.get(name)?;
+        match Url::parse(&value) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Some(value),
+            _ => {
+                self.invalid(name, format!("'{}' is not an http(s) URL", value));
+                None
+            }
+        }
+    }
+
+    /// One of the given named choices, or the
+    /// default if the variable is not set.
+    fn choice<T: Copy>(&mut self, name: &'static str, default: T, choices: &[(&str, T)]) -> T {
+        let value = match self.get(name) {
+            Some(value) => value,
+            None => return default,
+        };
+        match choices.iter().find(|(choice, _)| *choice == value) {
+            Some((_, choice)) => *choice,
+            None => {
+                let names: Vec<&str> = choices.iter().map(|(choice, _)| *choice).collect();
+                self.invalid(
+                    name,
+                    format!("'{}' is not one of {}", value, names.join(", ")),
+                );
+                default
+            }
+        }
+    }
+
+    fn finish(self, config: Config) -> Result<Config, ConfigError> {
+        if self.errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError {
+                errors: self.errors,
+            })
+        }
+    }
+}
+
+fn load_from_env() -> Result<Config, ConfigError> {
+    load(|name| std::env::var(name).ok())
+}
+
+/// Load the configuration from the given variables. Only `DATABASE_URL`,
+/// `HOSTNAME`, and `SESSION_KEY` are required, all other variables
+/// have the defaults declared above.
+fn load(lookup: impl Fn(&str) -> Option<String>) -> Result<Config, ConfigError> {
+    let mut vars = Vars {
+        lookup,
+        errors: vec![],
+    };
+
+    let database_url = vars.required("DATABASE_URL");
+
+    let search_idx: PathBuf = vars
+        .get("INDEX_DIR")
+        .unwrap_or_else(|| {
             log::info!(
                 "INDEX_DIR configuration not set, using default '{}'",
                 DEFAULT_INDEX
@@ -126,8 +1067,20 @@ fn load_from_env() -> Result<Config> {
         })
         .into();
 
-    let www_dir = var("WWW_DIR")
-        .unwrap_or_else(|_| {
+    let storage_dir = vars
+        .get("STORAGE_DIR")
+        .unwrap_or_else(|| {
+            log::info!(
+                "STORAGE_DIR configuration not set, using default '{}'",
+                DEFAULT_STORAGE
+            );
+            DEFAULT_STORAGE.to_string()
+        })
+        .into();
+
+    let www_dir = vars
+        .get("WWW_DIR")
+        .unwrap_or_else(|| {
             log::info!(
                 "WWW_DIR configuration not set, using default '{}'",
                 DEFAULT_WWW
@@ -136,32 +1089,276 @@ fn load_from_env() -> Result<Config> {
         })
         .into();
 
-    let smtp = match (var("SMTP_HOST"), var("SMTP_USER"), var("SMTP_PASS")) {
-        (Ok(host), Ok(user), Ok(password)) => Some(SmtpConfig {
+    let smtp = match (
+        vars.get("SMTP_HOST"),
+        vars.get("SMTP_USER"),
+        vars.get("SMTP_PASS"),
+    ) {
+        (Some(host), Some(user), Some(password)) => Some(SmtpConfig {
             host,
-            port: var("SMTP_PORT").ok().and_then(|port| {
-                port.parse()
-                    .map_err(|_| {
-                        log::warn!("Invalid SMTP_PORT set, using default {}", DEFAULT_SMTP_PORT);
-                    })
-                    .ok()
-            }),
+            port: vars.parse_optional("SMTP_PORT"),
             user,
             password,
         }),
+        (None, None, None) => {
+            log::info!("SMTP_HOST, SMTP_USER, or SMTP_PASS not set");
+            None
+        }
+        // a partial smtp config is most likely a mistake
         _ => {
-            log::info!("SMTP_HOST, SMTP_PORT, SMTP_USER, or SMTP_PASS not set");
+            for &name in &["SMTP_HOST", "SMTP_USER", "SMTP_PASS"] {
+                if vars.get(name).is_none() {
+                    vars.missing(name);
+                }
+            }
+            None
+        }
+    };
+
+    let hostname = vars.required("HOSTNAME");
+    if hostname.contains('/') {
+        vars.invalid(
+            "HOSTNAME",
+            format!("'{}' is not a host name, optionally with a port", hostname),
+        );
+    }
+
+    let geoip_api = vars.url("GEOIP_API");
+    if geoip_api.is_none() {
+        log::info!("GEOIP_API not set, login sessions are not located");
+    }
+
+    let invite_quota = vars.non_negative("INVITE_QUOTA", DEFAULT_INVITE_QUOTA);
+
+    // non-positive values disable accruing invites
+    let invite_accrual = match vars.parse("INVITE_ACCRUAL_DAYS", DEFAULT_INVITE_ACCRUAL_DAYS) {
+        days if days > 0 => Some(Duration::days(days)),
+        _ => None,
+    };
+
+    let invite_bank = vars.parse("INVITE_BANK", DEFAULT_INVITE_BANK);
+
+    let email_blocklist = vars.get("EMAIL_BLOCKLIST").map(PathBuf::from);
+    if email_blocklist.is_none() {
+        log::info!("EMAIL_BLOCKLIST not set, only using built-in blocklist");
+    }
+
+    let registration_mode = vars.choice(
+        "REGISTRATION_MODE",
+        RegistrationMode::InviteOnly,
+        &[
+            ("invite_only", RegistrationMode::InviteOnly),
+            ("open", RegistrationMode::Open),
+            ("closed", RegistrationMode::Closed),
+        ],
+    );
+
+    // signed tokens, e.g. sessions, don't survive restarts
+    // without a key, which is why it is required
+    let session_keys: Vec<String> = vars
+        .required("SESSION_KEY")
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .collect();
+    if session_keys
+        .iter()
+        .any(|key| key.len() < MIN_SESSION_KEY_LEN)
+    {
+        vars.invalid(
+            "SESSION_KEY",
+            format!("keys must have at least {} bytes", MIN_SESSION_KEY_LEN),
+        );
+    } else if !session_keys.is_empty() {
+        let fingerprints: Vec<String> = session_keys
+            .iter()
+            .map(|key| signing::fingerprint(key.as_bytes()))
+            .collect();
+        log::info!(
+            "Using session keys with fingerprints {} (primary first)",
+            fingerprints.join(", ")
+        );
+    }
+
+    let ip_privacy = vars.choice(
+        "IP_PRIVACY",
+        IpPrivacy::Full,
+        &[
+            ("full", IpPrivacy::Full),
+            ("truncate", IpPrivacy::Truncate),
+            ("hash", IpPrivacy::Hash),
+        ],
+    );
+
+    let tracking_params = vars
+        .get("TRACKING_PARAMS")
+        .map(|params| {
+            params
+                .split(',')
+                .map(|param| param.trim().to_ascii_lowercase())
+                .filter(|param| !param.is_empty())
+                .collect()
+        })
+        .unwrap_or_else(default_tracking_params);
+
+    let fetch_timeout = std::time::Duration::from_secs(
+        vars.parse("FETCH_TIMEOUT_SECS", DEFAULT_FETCH_TIMEOUT_SECS),
+    );
+    let allow_private_urls = vars.parse("ALLOW_PRIVATE_URLS", DEFAULT_ALLOW_PRIVATE_URLS);
+    let url_edit_window = vars.duration(
+        "URL_EDIT_WINDOW_MINUTES",
+        DEFAULT_URL_EDIT_WINDOW_MINUTES,
+        Duration::minutes,
+    );
+    let max_comment_depth = vars.non_negative("MAX_COMMENT_DEPTH", DEFAULT_MAX_COMMENT_DEPTH);
+    let comment_edit_window = vars.duration(
+        "COMMENT_EDIT_WINDOW_MINUTES",
+        DEFAULT_COMMENT_EDIT_WINDOW_MINUTES,
+        Duration::minutes,
+    );
+    let resubmit_after = vars.duration(
+        "RESUBMIT_AFTER_DAYS",
+        DEFAULT_RESUBMIT_AFTER_DAYS,
+        Duration::days,
+    );
+    let allow_self_votes = vars.parse("ALLOW_SELF_VOTES", DEFAULT_ALLOW_SELF_VOTES);
+    let downvotes_enabled = vars.parse("DOWNVOTES_ENABLED", DEFAULT_DOWNVOTES_ENABLED);
+    let downvote_min_karma = vars.parse("DOWNVOTE_MIN_KARMA", DEFAULT_DOWNVOTE_MIN_KARMA);
+    let karma_submission_weight =
+        vars.parse("KARMA_SUBMISSION_WEIGHT", DEFAULT_KARMA_SUBMISSION_WEIGHT);
+    let karma_comment_weight = vars.parse("KARMA_COMMENT_WEIGHT", DEFAULT_KARMA_COMMENT_WEIGHT);
+    let public_revisions = vars.parse("PUBLIC_REVISIONS", DEFAULT_PUBLIC_REVISIONS);
+    let public_voters = vars.parse("PUBLIC_VOTERS", DEFAULT_PUBLIC_VOTERS);
+    let anonymous_submissions = vars.parse("ANONYMOUS_SUBMISSIONS", DEFAULT_ANONYMOUS_SUBMISSIONS);
+    let trending_gravity = vars.parse("TRENDING_GRAVITY", DEFAULT_TRENDING_GRAVITY);
+
+    let archive_url = match vars.get("ARCHIVE_URL").as_deref() {
+        None => Some(DEFAULT_ARCHIVE_URL.to_string()),
+        Some("") | Some("none") => {
+            log::info!("ARCHIVE_URL set to 'none', submitted pages will not be archived");
             None
         }
+        Some(_) => vars.url("ARCHIVE_URL"),
+    };
+
+    let spam_max_links = vars.parse("SPAM_MAX_LINKS", DEFAULT_SPAM_MAX_LINKS);
+    let spam_new_account_age = vars.duration(
+        "SPAM_NEW_ACCOUNT_HOURS",
+        DEFAULT_SPAM_NEW_ACCOUNT_HOURS,
+        Duration::hours,
+    );
+    let spam_new_account_max_links = vars.parse(
+        "SPAM_NEW_ACCOUNT_MAX_LINKS",
+        DEFAULT_SPAM_NEW_ACCOUNT_MAX_LINKS,
+    );
+
+    let akismet_url = vars
+        .url("AKISMET_URL")
+        .unwrap_or_else(|| DEFAULT_AKISMET_URL.to_string());
+    let akismet_key = vars.get("AKISMET_KEY");
+    if akismet_key.is_some() && cfg!(not(feature = "akismet")) {
+        log::warn!("AKISMET_KEY set, but the server was built without the akismet feature");
+    }
+
+    let posting_limits = PostingLimits {
+        submissions: vars.parse("SUBMISSIONS_PER_HOUR", DEFAULT_SUBMISSIONS_PER_HOUR),
+        comments: vars.parse("COMMENTS_PER_HOUR", DEFAULT_COMMENTS_PER_HOUR),
+    };
+    let new_account_posting_limits = PostingLimits {
+        submissions: vars.parse(
+            "NEW_ACCOUNT_SUBMISSIONS_PER_HOUR",
+            DEFAULT_NEW_ACCOUNT_SUBMISSIONS_PER_HOUR,
+        ),
+        comments: vars.parse(
+            "NEW_ACCOUNT_COMMENTS_PER_HOUR",
+            DEFAULT_NEW_ACCOUNT_COMMENTS_PER_HOUR,
+        ),
     };
+    let rate_limit_new_account_age = vars.duration(
+        "RATE_LIMIT_NEW_ACCOUNT_HOURS",
+        DEFAULT_RATE_LIMIT_NEW_ACCOUNT_HOURS,
+        Duration::hours,
+    );
 
-    let hostname = var("HOSTNAME")?;
+    let pocket_consumer_key = vars.get("POCKET_CONSUMER_KEY");
+    if pocket_consumer_key.is_none() {
+        log::info!("POCKET_CONSUMER_KEY not set, importing from Pocket is disabled");
+    }
+
+    let max_query_depth = vars.parse("MAX_QUERY_DEPTH", DEFAULT_MAX_QUERY_DEPTH);
+    let max_query_complexity = vars.parse("MAX_QUERY_COMPLEXITY", DEFAULT_MAX_QUERY_COMPLEXITY);
+
+    let persisted_queries = vars.get("PERSISTED_QUERIES").map(PathBuf::from);
+    if persisted_queries.is_none() {
+        log::info!("PERSISTED_QUERIES not set, no queries are persisted on startup");
+    }
+    let register_persisted_queries = vars.parse(
+        "REGISTER_PERSISTED_QUERIES",
+        DEFAULT_REGISTER_PERSISTED_QUERIES,
+    );
+    let persisted_queries_only =
+        vars.parse("PERSISTED_QUERIES_ONLY", DEFAULT_PERSISTED_QUERIES_ONLY);
 
-    Ok(Config {
+    let mut oembed_providers = match vars.get("OEMBED_PROVIDERS") {
+        Some(path) => Provider::load_all(path.as_ref()).unwrap_or_else(|err| {
+            vars.invalid("OEMBED_PROVIDERS", format!("'{}' ({})", path, err));
+            vec![]
+        }),
+        None => vec![],
+    };
+    oembed_providers.extend(embed::default_providers());
+
+    let config = Config {
         database_url,
         search_idx: Some(search_idx),
+        storage_dir,
         www_dir,
         smtp,
         hostname,
-    })
+        geoip_api,
+        invite_quota,
+        invite_accrual,
+        invite_bank,
+        email_blocklist,
+        registration_mode,
+        session_keys,
+        ip_privacy,
+        tracking_params,
+        fetch_timeout,
+        fetch_private_addresses: false,
+        allow_private_urls,
+        url_edit_window,
+        max_comment_depth,
+        comment_edit_window,
+        resubmit_after,
+        allow_self_votes,
+        downvotes_enabled,
+        downvote_min_karma,
+        karma_submission_weight,
+        karma_comment_weight,
+        public_revisions,
+        public_voters,
+        anonymous_submissions,
+        trending_gravity,
+        archive_url,
+        spam_max_links,
+        spam_new_account_age,
+        spam_new_account_max_links,
+        spam_filter: None,
+        akismet_url,
+        akismet_key,
+        posting_limits,
+        new_account_posting_limits,
+        rate_limit_new_account_age,
+        pocket_consumer_key,
+        oembed_providers,
+        sitemap_size: DEFAULT_SITEMAP_SIZE,
+        max_query_depth,
+        max_query_complexity,
+        persisted_queries,
+        register_persisted_queries,
+        persisted_queries_only,
+    };
+    vars.finish(config)
 }
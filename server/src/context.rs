@@ -1,13 +1,26 @@
-use crate::db::id::UserID;
-use crate::db::models::User;
+use crate::archive::{self, Archiver};
+use crate::clicks::ClickCounter;
+use crate::db::id::{CommentID, UrlID, UserID};
+use crate::db::models::{User, VoteDirection};
 use crate::db::{Pool, PooledConnection, SearchIndex};
 use crate::email::Mailer;
+use crate::error::AppError;
+use crate::events::EventBus;
+use crate::loader::Loader;
+use crate::persisted::PersistedQueries;
+use crate::rate_limit::RateLimiter;
 use crate::schema::users;
-use anyhow::{anyhow, Result};
+use crate::spam::{self, SpamFilter};
+use crate::storage::Storage;
+use crate::{signing, Config, IpPrivacy};
+use anyhow::Result;
 use chrono::{DateTime, Utc};
 use diesel::{query_dsl::methods::FindDsl, RunQueryDsl};
 use once_cell::sync::Lazy;
-use std::net::IpAddr;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
 
 const SERVER_XSRF_TOKEN: &str = "server_xsrt_token";
 static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
@@ -25,6 +38,28 @@ static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
         .unwrap()
 });
 
+/// How the session of a request was presented. Sessions
+/// sent with the `Authorization` header can not be forged
+/// by other sites, while browsers attach cookies to any
+/// request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionSource {
+    Cookie,
+    Header,
+}
+
+/// Batched lookups of a request, which resolvers of list items use
+/// instead of querying each item, see [`loader`](crate::loader).
+#[derive(Default)]
+pub(crate) struct Loaders {
+    /// Users by their ID.
+    pub users: Loader<UserID, User>,
+    /// How users voted on URLs.
+    pub votes: Loader<(UserID, UrlID), VoteDirection>,
+    /// Number of comments on URLs by users the viewer blocked.
+    pub hidden_comments: Loader<UrlID, i64>,
+}
+
 /// Application request context. The context holds information
 /// about the current request, and also can provide access to
 /// application level resources such as database handles.
@@ -34,18 +69,26 @@ static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
 /// resolvers.
 #[derive(Clone)]
 pub struct Context {
+    config: Arc<Config>,
     pool: Pool,
     mailer: Mailer,
     xsrf_token: String,
+    xsrf_verified: bool,
     login_session: Option<(UserID, String)>,
+    session_source: Option<SessionSource>,
+    session_cookie: Arc<Mutex<Option<String>>>,
     request_time: DateTime<Utc>,
     user_agent: Option<String>,
     remote_ip: Option<IpAddr>,
+    loaders: Arc<Loaders>,
+    viewer_comment_votes: Arc<Mutex<Option<HashSet<CommentID>>>>,
+    uploads: Arc<HashMap<String, Vec<u8>>>,
 }
 
 impl Context {
     /// Create a new request context.
     pub fn for_request(
+        config: &Arc<Config>,
         pool: &Pool,
         mailer: &Mailer,
         xsrf_token: String,
@@ -53,10 +96,17 @@ impl Context {
         remote_ip: Option<IpAddr>,
     ) -> Self {
         Self {
+            config: config.clone(),
             pool: pool.clone(),
             mailer: mailer.clone(),
             xsrf_token,
+            xsrf_verified: false,
             login_session: None,
+            session_source: None,
+            session_cookie: Arc::new(Mutex::new(None)),
+            loaders: Arc::new(Loaders::default()),
+            viewer_comment_votes: Arc::new(Mutex::new(None)),
+            uploads: Arc::new(HashMap::new()),
             request_time: Utc::now(),
             user_agent,
             remote_ip,
@@ -65,12 +115,19 @@ impl Context {
 
     /// Create a new context for operations
     /// initiated by the server.
-    pub fn for_server(pool: &Pool, mailer: &Mailer) -> Self {
+    pub fn for_server(config: &Arc<Config>, pool: &Pool, mailer: &Mailer) -> Self {
         Self {
+            config: config.clone(),
             pool: pool.clone(),
             mailer: mailer.clone(),
             xsrf_token: SERVER_XSRF_TOKEN.to_string(),
+            xsrf_verified: false,
             login_session: None,
+            session_source: None,
+            session_cookie: Arc::new(Mutex::new(None)),
+            loaders: Arc::new(Loaders::default()),
+            viewer_comment_votes: Arc::new(Mutex::new(None)),
+            uploads: Arc::new(HashMap::new()),
             request_time: Utc::now(),
             user_agent: None,
             remote_ip: None,
@@ -84,17 +141,149 @@ impl Context {
         self.login_session = Some((user, session_token));
     }
 
+    /// Returns a copy of this context acting on behalf of the given
+    /// user, for requests which are authenticated by other means than
+    /// a session, such as private feeds. The copy carries no session
+    /// token, and never sets a session cookie.
+    pub fn acting_as(&self, user: UserID) -> Self {
+        Self {
+            login_session: Some((user, String::new())),
+            session_source: None,
+            session_cookie: Arc::new(Mutex::new(None)),
+            loaders: Arc::new(Loaders::default()),
+            viewer_comment_votes: Arc::new(Mutex::new(None)),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of this context without the session of the
+    /// request, for connections which authenticate by other means
+    /// than the session cookie, such as GraphQL subscriptions.
+    pub fn logged_out(&self) -> Self {
+        Self {
+            login_session: None,
+            session_source: None,
+            session_cookie: Arc::new(Mutex::new(None)),
+            loaders: Arc::new(Loaders::default()),
+            viewer_comment_votes: Arc::new(Mutex::new(None)),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of this context for another operation on the
+    /// same long lived connection, such as a GraphQL subscription,
+    /// at the current time and without the cached data of earlier
+    /// operations.
+    pub fn for_operation(&self) -> Self {
+        Self {
+            request_time: Utc::now(),
+            loaders: Arc::new(Loaders::default()),
+            viewer_comment_votes: Arc::new(Mutex::new(None)),
+            ..self.clone()
+        }
+    }
+
+    /// Stores the files uploaded with the request, by the name of
+    /// their part. This exists to be used when constructing the context.
+    pub fn set_uploads(&mut self, uploads: HashMap<String, Vec<u8>>) {
+        self.uploads = Arc::new(uploads);
+    }
+
+    /// The contents of the file uploaded as the part
+    /// with the given name, if any.
+    pub fn upload(&self, key: &str) -> Option<&[u8]> {
+        self.uploads.get(key).map(Vec::as_slice)
+    }
+
+    /// Records how the session of this context was presented.
+    /// This exists to be used when constructing the context.
+    pub fn set_session_source(&mut self, source: SessionSource) {
+        self.session_source = Some(source);
+    }
+
+    /// Marks the XSRF token of this context as verified, i.e.
+    /// the request carried a matching token in a header.
+    pub fn set_xsrf_verified(&mut self) {
+        self.xsrf_verified = true;
+    }
+
+    /// Updates the time of the request associated with
+    /// this context. This exists to simulate the passing
+    /// of time in tests, and is probably not what you want.
+    pub fn set_request_time(&mut self, time: DateTime<Utc>) {
+        self.request_time = time;
+    }
+
+    /// Retrieve the configuration this context
+    /// was created with. Prefer this over the
+    /// global `Config::env()`.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     /// Retrieve a database connection from the
     /// connection pool.
     pub async fn conn(&self) -> Result<PooledConnection<'_>> {
+        self.pool.checkouts.fetch_add(1, Ordering::Relaxed);
         Ok(self.pool.db.get().await?)
     }
 
+    /// The number of database connections retrieved with
+    /// [`conn`](Context::conn) so far, across all contexts
+    /// sharing the connection pool. Most operations run one
+    /// query per connection, which makes this a way for tests
+    /// to check how many queries a request makes.
+    pub fn connection_count(&self) -> usize {
+        self.pool.checkouts.load(Ordering::Relaxed)
+    }
+
     /// Retrieve a handle to the search index.
     pub fn search(&self) -> &SearchIndex {
         &self.pool.search
     }
 
+    /// Retrieve the rate limiter shared by all requests,
+    /// for limiting actions by anonymous clients.
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.pool.limiter
+    }
+
+    /// Retrieve the buffer of outbound clicks
+    /// shared by all requests.
+    pub fn click_counter(&self) -> &ClickCounter {
+        &self.pool.clicks
+    }
+
+    /// Retrieve the bus of events about new content,
+    /// shared by all requests.
+    pub fn events(&self) -> &EventBus {
+        &self.pool.events
+    }
+
+    /// Retrieve the persisted GraphQL queries
+    /// shared by all requests.
+    pub fn persisted_queries(&self) -> &PersistedQueries {
+        &self.pool.persisted
+    }
+
+    /// Retrieve the storage backend in which
+    /// generated files are kept.
+    pub fn storage(&self) -> Storage {
+        Storage::from_config(&self.config)
+    }
+
+    /// Retrieve the service used to archive submitted
+    /// pages, if archiving is enabled.
+    pub fn archiver(&self) -> Option<Box<dyn Archiver>> {
+        archive::from_config(&self.config)
+    }
+
+    /// Retrieve the filter new submissions and
+    /// comments are checked for spam with.
+    pub fn spam_filter(&self) -> Arc<dyn SpamFilter> {
+        spam::from_config(&self.config)
+    }
+
     /// Retrieve the mailer to send an email
     /// message. Note that sending emails costs
     /// money.
@@ -140,8 +329,9 @@ impl Context {
     ///     Ok(())
     /// }
     /// ```
-    pub fn user_id(&self) -> Result<UserID> {
-        self.maybe_user_id().ok_or_else(|| anyhow!("Not logged in"))
+    pub fn user_id(&self) -> Result<UserID, AppError> {
+        self.maybe_user_id()
+            .ok_or_else(|| AppError::Unauthenticated("Not logged in".into()))
     }
 
     /// Retrieve the logged in `User`. This requires
@@ -161,10 +351,26 @@ impl Context {
     /// context. This is similar to [`user_id`](user_id),
     /// and is meant to force a logged in user.
     /// Also see [`maybe_user`](maybe_user).
-    pub async fn user(&self) -> Result<User> {
+    pub async fn user(&self) -> Result<User, AppError> {
         self.maybe_user()
             .await?
-            .ok_or_else(|| anyhow!("Not logged in"))
+            .ok_or_else(|| AppError::Unauthenticated("Not logged in".into()))
+    }
+
+    /// Retrieve the logged in user, requiring the user
+    /// to have verified their email address and not to
+    /// be banned. Use this to guard actions which publish
+    /// content.
+    pub async fn verified_user(&self) -> Result<User, AppError> {
+        let user = self.user().await?;
+        if user.is_banned() {
+            Err(AppError::PermissionDenied("Your account was banned".into()))
+        } else if user.is_email_verified() {
+            Ok(user)
+        } else {
+            let message = "Please verify your email address first";
+            Err(AppError::PermissionDenied(message.into()))
+        }
     }
 
     /// Prefer this over `Utc::now()`, since it
@@ -191,6 +397,63 @@ impl Context {
         self.xsrf_token == token
     }
 
+    /// Whether the request carried a valid XSRF token.
+    pub fn is_xsrf_verified(&self) -> bool {
+        self.xsrf_verified
+    }
+
+    /// Return how the session of the current request
+    /// was presented, if the request is logged in.
+    pub fn session_source(&self) -> Option<SessionSource> {
+        self.login_session.as_ref().and(self.session_source)
+    }
+
+    /// Request a session cookie to be set on the response
+    /// to the current request.
+    pub fn set_session_cookie(&self, session_token: String) {
+        *self.session_cookie.lock().unwrap() = Some(session_token);
+    }
+
+    /// Take the session token which should be set as a
+    /// cookie on the response to the current request.
+    pub fn take_session_cookie(&self) -> Option<String> {
+        self.session_cookie.lock().unwrap().take()
+    }
+
+    /// The batched lookups of the current request, see
+    /// [`loader`](crate::loader).
+    pub(crate) fn loaders(&self) -> &Loaders {
+        &self.loaders
+    }
+
+    /// Whether the viewer voted on the given comment, if the comment
+    /// votes of the viewer were already loaded during the current request.
+    pub(crate) fn cached_comment_vote(&self, comment: CommentID) -> Option<bool> {
+        self.viewer_comment_votes
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|votes| votes.contains(&comment))
+    }
+
+    /// Remember all comment votes of the viewer for the remainder
+    /// of the current request.
+    pub(crate) fn cache_comment_votes(&self, votes: impl IntoIterator<Item = CommentID>) {
+        *self.viewer_comment_votes.lock().unwrap() = Some(votes.into_iter().collect());
+    }
+
+    /// Update the remembered comment votes of the viewer after voting
+    /// or rescinding a vote on the given comment.
+    pub(crate) fn update_cached_comment_vote(&self, comment: CommentID, voted: bool) {
+        if let Some(votes) = self.viewer_comment_votes.lock().unwrap().as_mut() {
+            if voted {
+                votes.insert(comment);
+            } else {
+                votes.remove(&comment);
+            }
+        }
+    }
+
     /// Return the user-agent of the request
     /// which created this context.
     pub fn user_agent(&self) -> Option<&str> {
@@ -202,6 +465,29 @@ impl Context {
     pub fn remote_ip_address(&self) -> Option<IpAddr> {
         self.remote_ip
     }
+
+    /// Return the IP address of the remote request in the
+    /// form it should be stored, which depends on the configured
+    /// IP privacy. Hashed addresses can not be parsed back into
+    /// an IP address.
+    pub fn stored_remote_ip(&self) -> Option<String> {
+        let ip = self.remote_ip?;
+        let stored = match self.config.ip_privacy() {
+            IpPrivacy::Full => ip.to_string(),
+            IpPrivacy::Truncate => match ip {
+                IpAddr::V4(ip) => {
+                    let [a, b, c, _] = ip.octets();
+                    Ipv4Addr::new(a, b, c, 0).to_string()
+                }
+                IpAddr::V6(ip) => {
+                    let [a, b, c, ..] = ip.segments();
+                    Ipv6Addr::new(a, b, c, 0, 0, 0, 0, 0).to_string()
+                }
+            },
+            IpPrivacy::Hash => signing::digest(self, "remote_ip", &ip.to_string()),
+        };
+        Some(stored)
+    }
 }
 
 impl juniper::Context for Context {}
@@ -6,3 +6,19 @@ pub type RoleID = ID<2>;
 pub type InviteID = ID<3>;
 pub type UrlID = ID<4>;
 pub type CommentID = ID<5>;
+pub type KnownDeviceID = ID<6>;
+pub type SecurityEventID = ID<7>;
+pub type DataExportID = ID<8>;
+pub type SavedUrlID = ID<9>;
+pub type ReportID = ID<10>;
+pub type ModerationLogID = ID<11>;
+pub type ServiceImportID = ID<12>;
+pub type BookmarkExportID = ID<13>;
+pub type WebhookID = ID<14>;
+pub type WebhookDeliveryID = ID<15>;
+pub type NotificationID = ID<16>;
+pub type DigestID = ID<17>;
+pub type CollectionID = ID<18>;
+pub type RevisionID = ID<19>;
+pub type TagLogID = ID<20>;
+pub type GroupID = ID<21>;
@@ -1,10 +1,19 @@
-use crate::db::models::Url;
+use crate::clicks::ClickCounter;
+use crate::db::models::{Comment, Url, User};
+use crate::events::EventBus;
+use crate::persisted::PersistedQueries;
+use crate::rate_limit::RateLimiter;
 use crate::schema::urls;
 use crate::Config;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use bb8_diesel::{bb8, DieselConnection, DieselConnectionManager};
-use diesel::{sqlite::SqliteConnection, RunQueryDsl};
+use diesel::migration::MigrationConnection;
+use diesel::sql_types::BigInt;
+use diesel::sqlite::Sqlite;
+use diesel::{sqlite::SqliteConnection, Connection, RunQueryDsl};
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
 
 pub mod id;
 pub mod models;
@@ -13,12 +22,19 @@ pub mod search;
 type DBPool = bb8::Pool<DieselConnectionManager<SqliteConnection>>;
 pub type PooledConnection<'a> =
     bb8::PooledConnection<'a, DieselConnectionManager<SqliteConnection>>;
-pub use search::SearchIndex;
+pub use search::{SearchCursor, SearchIndex};
 
 #[derive(Clone)]
 pub struct Pool {
     pub db: DBPool,
     pub search: SearchIndex,
+    pub limiter: RateLimiter,
+    pub clicks: ClickCounter,
+    pub persisted: PersistedQueries,
+    pub events: EventBus,
+    /// Number of database connections checked out
+    /// through [`Context::conn`](crate::Context::conn).
+    pub checkouts: Arc<AtomicUsize>,
 }
 
 diesel_migrations::embed_migrations!();
@@ -34,15 +50,44 @@ impl bb8::CustomizeConnection<DieselConnection<SqliteConnection>, diesel::r2d2::
         &self,
         conn: &mut DieselConnection<SqliteConnection>,
     ) -> Result<(), diesel::r2d2::Error> {
-        let query = diesel::sql_query("PRAGMA foreign_keys = ON");
-        query.execute(&*conn).map_err(|err| {
-            log::error!("Failed to customize connection: {}", err);
-            diesel::r2d2::Error::QueryError(err)
-        })?;
+        // wait for concurrent writers, rather than failing
+        // right away when the database is locked
+        for pragma in ["PRAGMA foreign_keys = ON", "PRAGMA busy_timeout = 5000"] {
+            diesel::sql_query(pragma).execute(&*conn).map_err(|err| {
+                log::error!("Failed to customize connection: {}", err);
+                diesel::r2d2::Error::QueryError(err)
+            })?;
+        }
         Ok(())
     }
 }
 
+/// Run all pending migrations. Foreign keys are not enforced while
+/// migrations run, such that they can rebuild tables which other tables
+/// refer to. SQLite ignores changes to this within a transaction, like
+/// the one each migration runs in, so it is changed around all of them
+/// instead, and the foreign keys are checked once they are done.
+fn run_migrations<C>(conn: &C) -> Result<()>
+where
+    C: MigrationConnection + Connection<Backend = Sqlite>,
+{
+    diesel::sql_query("PRAGMA foreign_keys = OFF").execute(conn)?;
+    let result = embedded_migrations::run(conn);
+    diesel::sql_query("PRAGMA foreign_keys = ON").execute(conn)?;
+    result?;
+    let violations: i64 = diesel::select(diesel::dsl::sql::<BigInt>(
+        "(SELECT COUNT(*) FROM pragma_foreign_key_check)",
+    ))
+    .get_result(conn)?;
+    if violations > 0 {
+        return Err(anyhow!(
+            "Migrations left {} rows with broken foreign keys",
+            violations
+        ));
+    }
+    Ok(())
+}
+
 pub async fn connect(config: &Config) -> Result<Pool> {
     let manager = DieselConnectionManager::new(config.database());
     let db = bb8::Pool::builder()
@@ -52,18 +97,34 @@ pub async fn connect(config: &Config) -> Result<Pool> {
         .await?;
 
     let search = SearchIndex::new(config).await?;
+    let persisted = match config.persisted_queries() {
+        Some(path) => PersistedQueries::load(path)?,
+        None => PersistedQueries::default(),
+    };
 
     {
         // Run migrations
         let conn = db.get().await?;
-        embedded_migrations::run(&*conn)?;
+        run_migrations(&*conn)?;
+        Url::backfill_domains(&*conn)?;
+        Url::backfill_description_html(&*conn)?;
+        Comment::backfill_html(&*conn)?;
+        User::backfill_karma(&*conn, config)?;
 
         // Set up search index on startup
         log::info!("Building search index ...");
         let urls: Vec<Url> = urls::table.load(&*conn)?;
-        search.index_urls(urls.iter())?;
+        search.rebuild(urls.iter())?;
         log::info!("Search index build completed");
     }
 
-    Ok(Pool { db, search })
+    Ok(Pool {
+        db,
+        search,
+        limiter: RateLimiter::default(),
+        clicks: ClickCounter::default(),
+        persisted,
+        events: EventBus::default(),
+        checkouts: Arc::new(AtomicUsize::new(0)),
+    })
 }
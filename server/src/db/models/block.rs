@@ -0,0 +1,92 @@
+use crate::db::id::UserID;
+use crate::db::models::User;
+use crate::schema::{blocks, follows, users};
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use diesel::sqlite::Sqlite;
+
+/// A user which was blocked by another user. Content created by
+/// the blocked user is hidden from the blocking user, and the blocked
+/// user can not reply to the content of the blocking user.
+#[derive(Debug, Clone, Queryable, Insertable)]
+pub struct Block {
+    user_id: UserID,
+    blocked_user_id: UserID,
+    created_at: NaiveDateTime,
+}
+
+impl Block {
+    /// Block the given user for the currently logged in user.
+    /// Blocking an already blocked user has no effect. Blocking
+    /// removes the follows between both users.
+    pub async fn create(ctx: &Context, blocked_user_id: UserID) -> Result<()> {
+        let user_id = ctx.user_id()?;
+        if user_id == blocked_user_id {
+            return Err(anyhow!("You can not block yourself"));
+        }
+        let blocked = User::find(ctx, blocked_user_id).await?;
+        let block = Block {
+            user_id,
+            blocked_user_id: blocked.id(),
+            created_at: ctx.now().naive_utc(),
+        };
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            diesel::insert_or_ignore_into(blocks::table)
+                .values(&block)
+                .execute(&*conn)?;
+            let follows = follows::table.filter(
+                follows::dsl::user_id
+                    .eq(block.user_id)
+                    .and(follows::dsl::followed_user_id.eq(block.blocked_user_id))
+                    .or(follows::dsl::user_id
+                        .eq(block.blocked_user_id)
+                        .and(follows::dsl::followed_user_id.eq(block.user_id))),
+            );
+            diesel::delete(follows).execute(&*conn)?;
+            Ok(())
+        })
+    }
+
+    /// Unblock the given user for the currently logged in user.
+    pub async fn delete(ctx: &Context, blocked_user_id: UserID) -> Result<()> {
+        let block = blocks::table
+            .filter(blocks::dsl::user_id.eq(ctx.user_id()?))
+            .filter(blocks::dsl::blocked_user_id.eq(blocked_user_id));
+        diesel::delete(block).execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Check if the user `user_id` blocked the user `blocked_user_id`.
+    pub async fn exists(ctx: &Context, user_id: UserID, blocked_user_id: UserID) -> Result<bool> {
+        let count: i64 = blocks::table
+            .filter(blocks::dsl::user_id.eq(user_id))
+            .filter(blocks::dsl::blocked_user_id.eq(blocked_user_id))
+            .select(diesel::dsl::count_star())
+            .get_result(&*ctx.conn().await?)?;
+        Ok(count > 0)
+    }
+
+    /// IDs of all users blocked by the given user, whose content
+    /// should be excluded from their listings, as a subquery.
+    pub fn hidden_authors(user_id: UserID) -> blocks::BoxedQuery<'static, Sqlite, Text> {
+        blocks::table
+            .filter(blocks::dsl::user_id.eq(user_id))
+            .select(blocks::dsl::blocked_user_id)
+            .into_boxed()
+    }
+
+    /// Users blocked by the given user, most
+    /// recently blocked first.
+    pub async fn blocked_users(ctx: &Context, user_id: UserID) -> Result<Vec<User>> {
+        Ok(users::table
+            .inner_join(blocks::table.on(blocks::dsl::blocked_user_id.eq(users::dsl::id)))
+            .filter(blocks::dsl::user_id.eq(user_id))
+            .order_by(blocks::dsl::created_at.desc())
+            .select(users::all_columns)
+            .load(&*ctx.conn().await?)?)
+    }
+}
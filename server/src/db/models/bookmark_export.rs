@@ -0,0 +1,486 @@
+use super::data_export::{maybe_time, Table};
+use crate::db::id::{BookmarkExportID, UserID};
+use crate::db::models::{DataExportStatus, User};
+use crate::error::AppError;
+use crate::schema::{bookmark_exports, saved_urls, url_tags, urls};
+use crate::{signing, Context};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::{Text, Timestamp};
+use juniper::GraphQLEnum;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Write;
+
+const DOWNLOAD_PURPOSE: &str = "bookmark_export";
+const DOWNLOAD_VALID_DAYS: i64 = 7;
+
+/// The file format of a bookmark export.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum ExportFormat {
+    /// A JSON document, with a list of all bookmarks.
+    Json,
+    /// A CSV file, with a row for each bookmark.
+    Csv,
+    /// A bookmarks file in the Netscape bookmark file format,
+    /// which browsers and most bookmarking services can import.
+    Html,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Html => "html",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+/// An export of the submissions and saves of a user, requested by
+/// that user. Exports are generated in the background, and can be
+/// downloaded for a limited time once they are ready.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset, Associations)]
+#[belongs_to(User)]
+pub struct BookmarkExport {
+    id: BookmarkExportID,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+
+    user_id: UserID,
+    format: ExportFormat,
+    status: DataExportStatus,
+    completed_at: Option<NaiveDateTime>,
+    storage_key: Option<String>,
+}
+
+/// A link which was submitted or saved, or both,
+/// by the exporting user.
+struct Entry {
+    url_id: String,
+    url: String,
+    title: Option<String>,
+    tags: Vec<String>,
+    score: i64,
+    /// When the link was first submitted
+    /// or saved by the user.
+    added_at: NaiveDateTime,
+    submitted_at: Option<NaiveDateTime>,
+    saved_at: Option<NaiveDateTime>,
+    read_at: Option<NaiveDateTime>,
+}
+
+/// Escape text for use in HTML text and attributes.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl BookmarkExport {
+    pub fn id(&self) -> BookmarkExportID {
+        self.id
+    }
+
+    pub fn user_id(&self) -> UserID {
+        self.user_id
+    }
+
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    pub fn status(&self) -> DataExportStatus {
+        self.status
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    pub fn completed_at(&self) -> Option<DateTime<Utc>> {
+        self.completed_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    /// The time after which the export can no longer
+    /// be downloaded, if it completed.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.completed_at()
+            .map(|at| at + Duration::days(DOWNLOAD_VALID_DAYS))
+    }
+
+    /// The signed link the export can be downloaded from, if
+    /// it is ready and did not expire yet. The link stops working
+    /// once the export expires.
+    pub fn download_url(&self, ctx: &Context) -> Option<String> {
+        let expires_at = self.expires_at()?;
+        if self.status != DataExportStatus::Ready || expires_at < ctx.now() {
+            return None;
+        }
+        let payload = format!("{}:{}", self.id, expires_at.timestamp());
+        Some(format!(
+            "https://{}/bookmark-export/{}",
+            ctx.config().hostname(),
+            signing::sign(ctx, DOWNLOAD_PURPOSE, &payload)
+        ))
+    }
+}
+
+impl BookmarkExport {
+    pub async fn find(ctx: &Context, id: BookmarkExportID) -> Result<Self> {
+        let export = bookmark_exports::table
+            .find(id)
+            .get_result(&*ctx.conn().await?)?;
+        Ok(export)
+    }
+
+    /// Find the export referenced by a download token. Tampered,
+    /// malformed, or expired tokens are rejected with the same
+    /// error.
+    pub async fn find_by_download_token(ctx: &Context, token: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid or expired download token");
+
+        let payload = signing::verify(ctx, DOWNLOAD_PURPOSE, token).ok_or_else(invalid)?;
+        let (id, expires_at) = payload.split_once(':').ok_or_else(invalid)?;
+        let expires_at =
+            NaiveDateTime::from_timestamp_opt(expires_at.parse().map_err(|_| invalid())?, 0)
+                .ok_or_else(invalid)?;
+        if DateTime::<Utc>::from_utc(expires_at, Utc) < ctx.now() {
+            return Err(invalid());
+        }
+
+        let id: BookmarkExportID = id.parse().map_err(|_| invalid())?;
+        let export = Self::find(ctx, id).await.map_err(|_| invalid())?;
+        if export.status != DataExportStatus::Ready {
+            return Err(invalid());
+        }
+        Ok(export)
+    }
+
+    /// Exports requested by the given user, newest first.
+    pub async fn for_user(ctx: &Context, user_id: UserID) -> Result<Vec<Self>> {
+        let exports = bookmark_exports::table
+            .filter(bookmark_exports::dsl::user_id.eq(user_id))
+            .order_by(bookmark_exports::dsl::created_at.desc())
+            .load(&*ctx.conn().await?)?;
+        Ok(exports)
+    }
+
+    /// Request a new export of the submissions and saves of the
+    /// given user. Each user may only have one export in flight
+    /// at any time.
+    pub async fn request(ctx: &Context, user: &User, format: ExportFormat) -> Result<Self> {
+        let export = BookmarkExport {
+            id: BookmarkExportID::new(),
+            created_at: ctx.now().naive_utc(),
+            updated_at: ctx.now().naive_utc(),
+
+            user_id: user.id(),
+            format,
+            status: DataExportStatus::Pending,
+            completed_at: None,
+            storage_key: None,
+        };
+
+        // The check is part of the insert, such that
+        // concurrent requests can not both succeed.
+        let inserted = diesel::sql_query(
+            "INSERT INTO bookmark_exports (id, created_at, updated_at, user_id, format, status) \
+            SELECT ?, ?, ?, ?, ?, 'pending' \
+            WHERE NOT EXISTS (SELECT 1 FROM bookmark_exports \
+                WHERE user_id = ? AND status IN ('pending', 'processing'))",
+        )
+        .bind::<Text, _>(export.id.as_str())
+        .bind::<Timestamp, _>(export.created_at)
+        .bind::<Timestamp, _>(export.updated_at)
+        .bind::<Text, _>(export.user_id.as_str())
+        .bind::<Text, _>(export.format.extension())
+        .bind::<Text, _>(export.user_id.as_str())
+        .execute(&*ctx.conn().await?)?;
+        if inserted != 1 {
+            let message =
+                "A bookmark export is already in progress, please wait for it to complete";
+            return Err(AppError::Conflict(message.into()).into());
+        }
+        Ok(export)
+    }
+
+    /// Generate all pending exports.
+    pub async fn process_pending(ctx: &Context) -> Result<()> {
+        let pending: Vec<BookmarkExport> = bookmark_exports::table
+            .filter(bookmark_exports::dsl::status.eq(DataExportStatus::Pending))
+            .order_by(bookmark_exports::dsl::created_at.asc())
+            .load(&*ctx.conn().await?)?;
+
+        for mut export in pending {
+            if let Err(err) = export.process(ctx).await {
+                log::error!("Failed to process bookmark export {}: {}", export.id, err);
+                export.status = DataExportStatus::Failed;
+                export.updated_at = ctx.now().naive_utc();
+                export.save_changes::<BookmarkExport>(&*ctx.conn().await?)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn process(&mut self, ctx: &Context) -> Result<()> {
+        // claim the export, so concurrent job runs
+        // do not process the same export twice
+        let claimed = diesel::update(&*self)
+            .filter(bookmark_exports::dsl::status.eq(DataExportStatus::Pending))
+            .set((
+                bookmark_exports::dsl::status.eq(DataExportStatus::Processing),
+                bookmark_exports::dsl::updated_at.eq(ctx.now().naive_utc()),
+            ))
+            .execute(&*ctx.conn().await?)?;
+        if claimed != 1 {
+            return Ok(());
+        }
+        self.status = DataExportStatus::Processing;
+
+        let entries = Self::entries(ctx, self.user_id).await?;
+        let file = match self.format {
+            ExportFormat::Json => Self::json(ctx, &entries)?,
+            ExportFormat::Csv => Self::table(&entries).to_csv().into_bytes(),
+            ExportFormat::Html => Self::html(&entries).into_bytes(),
+        };
+        let key = format!(
+            "bookmark-exports/{}/{}.{}",
+            self.user_id,
+            self.id,
+            self.format.extension()
+        );
+        ctx.storage().put(&key, &file).await?;
+
+        self.status = DataExportStatus::Ready;
+        self.completed_at = Some(ctx.now().naive_utc());
+        self.storage_key = Some(key);
+        self.updated_at = ctx.now().naive_utc();
+        *self = self.save_changes(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Read the stored file of a completed export.
+    pub async fn read_file(&self, ctx: &Context) -> Result<Vec<u8>> {
+        let key = self
+            .storage_key
+            .as_deref()
+            .ok_or_else(|| anyhow!("Bookmark export is not completed"))?;
+        ctx.storage().get(key).await
+    }
+
+    /// Load all links the user submitted or saved, oldest first.
+    /// Links which were deleted are left out, as are text posts,
+    /// which have no link.
+    async fn entries(ctx: &Context, user_id: UserID) -> Result<Vec<Entry>> {
+        let conn = ctx.conn().await?;
+
+        let submissions = urls::table
+            .filter(urls::dsl::created_by.eq(user_id))
+            .filter(urls::dsl::deleted_at.is_null())
+            .filter(urls::dsl::url.is_not_null())
+            .order_by(urls::dsl::created_at.asc())
+            .select((
+                urls::dsl::id,
+                urls::dsl::url,
+                urls::dsl::title,
+                urls::dsl::fetched_title,
+                urls::dsl::score,
+                urls::dsl::created_at,
+            ))
+            .load::<(
+                String,
+                Option<String>,
+                Option<String>,
+                Option<String>,
+                i64,
+                NaiveDateTime,
+            )>(&*conn)?;
+        let saves = saved_urls::table
+            .inner_join(urls::table)
+            .filter(saved_urls::dsl::user_id.eq(user_id))
+            .filter(urls::dsl::deleted_at.is_null())
+            .filter(urls::dsl::url.is_not_null())
+            .order_by(saved_urls::dsl::saved_at.asc())
+            .select((
+                urls::dsl::id,
+                urls::dsl::url,
+                urls::dsl::title,
+                urls::dsl::fetched_title,
+                urls::dsl::score,
+                saved_urls::dsl::saved_at,
+                saved_urls::dsl::read_at,
+            ))
+            .load::<(
+                String,
+                Option<String>,
+                Option<String>,
+                Option<String>,
+                i64,
+                NaiveDateTime,
+                Option<NaiveDateTime>,
+            )>(&*conn)?;
+
+        let mut entries: Vec<Entry> = vec![];
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for (url_id, url, title, fetched_title, score, created_at) in submissions {
+            positions.insert(url_id.clone(), entries.len());
+            entries.push(Entry {
+                url_id,
+                url: url.unwrap_or_default(),
+                title: title.or(fetched_title),
+                tags: vec![],
+                score,
+                added_at: created_at,
+                submitted_at: Some(created_at),
+                saved_at: None,
+                read_at: None,
+            });
+        }
+        for (url_id, url, title, fetched_title, score, saved_at, read_at) in saves {
+            if let Some(&position) = positions.get(&url_id) {
+                let entry = &mut entries[position];
+                entry.added_at = entry.added_at.min(saved_at);
+                entry.saved_at = Some(saved_at);
+                entry.read_at = read_at;
+                continue;
+            }
+            positions.insert(url_id.clone(), entries.len());
+            entries.push(Entry {
+                url_id,
+                url: url.unwrap_or_default(),
+                title: title.or(fetched_title),
+                tags: vec![],
+                score,
+                added_at: saved_at,
+                submitted_at: None,
+                saved_at: Some(saved_at),
+                read_at,
+            });
+        }
+
+        let ids: Vec<&str> = entries.iter().map(|entry| entry.url_id.as_str()).collect();
+        let tags = url_tags::table
+            .filter(url_tags::dsl::url_id.eq_any(ids))
+            .order_by(url_tags::dsl::tag_name.asc())
+            .select((url_tags::dsl::url_id, url_tags::dsl::tag_name))
+            .load::<(String, String)>(&*conn)?;
+        for (url_id, tag) in tags {
+            if let Some(&position) = positions.get(&url_id) {
+                entries[position].tags.push(tag);
+            }
+        }
+
+        entries.sort_by_key(|entry| entry.added_at);
+        Ok(entries)
+    }
+
+    fn table(entries: &[Entry]) -> Table {
+        Table {
+            name: "bookmarks",
+            columns: &[
+                "url",
+                "title",
+                "tags",
+                "score",
+                "submitted_at",
+                "saved_at",
+                "read_at",
+            ],
+            rows: entries
+                .iter()
+                .map(|entry| {
+                    vec![
+                        json!(entry.url),
+                        json!(entry.title),
+                        json!(entry.tags),
+                        json!(entry.score),
+                        maybe_time(entry.submitted_at),
+                        maybe_time(entry.saved_at),
+                        maybe_time(entry.read_at),
+                    ]
+                })
+                .collect(),
+        }
+    }
+
+    fn json(ctx: &Context, entries: &[Entry]) -> Result<Vec<u8>> {
+        let export: Value = json!({
+            "exported_at": ctx.now().to_rfc3339(),
+            "bookmarks": Self::table(entries).to_json(),
+        });
+        Ok(serde_json::to_vec_pretty(&export)?)
+    }
+
+    /// Write the entries as a Netscape bookmarks file. Tags are written
+    /// to the `TAGS` attribute, rather than as folders, such that links
+    /// keep all their tags, and links saved but not read yet are marked
+    /// with `TOREAD`, as Pinboard does.
+    fn html(entries: &[Entry]) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+            <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+            <TITLE>Bookmarks</TITLE>\n\
+            <H1>Bookmarks</H1>\n\
+            <DL><p>\n",
+        );
+        for entry in entries {
+            html.push_str(&format!(
+                "    <DT><A HREF=\"{}\" ADD_DATE=\"{}\"",
+                escape(&entry.url),
+                entry.added_at.timestamp()
+            ));
+            if !entry.tags.is_empty() {
+                html.push_str(&format!(" TAGS=\"{}\"", escape(&entry.tags.join(","))));
+            }
+            if entry.saved_at.is_some() && entry.read_at.is_none() {
+                html.push_str(" TOREAD=\"1\"");
+            }
+            html.push_str(&format!(
+                ">{}</A>\n",
+                escape(entry.title.as_deref().unwrap_or_default())
+            ));
+        }
+        html.push_str("</DL><p>\n");
+        html
+    }
+}
+
+impl<DB> ToSql<Text, DB> for ExportFormat
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        self.extension().to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for ExportFormat
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "csv" => Ok(ExportFormat::Csv),
+            "html" => Ok(ExportFormat::Html),
+            _ => Err("Unrecognized export format".into()),
+        }
+    }
+}
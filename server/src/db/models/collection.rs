@@ -0,0 +1,554 @@
+use crate::db::id::{CollectionID, UrlID, UserID};
+use crate::db::models::{Listed, Url, User};
+use crate::schema::{collection_items, collections, urls};
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::Text;
+use juniper::{GraphQLEnum, GraphQLInputObject};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use validator::Validate;
+
+/// Maximum number of collections a single user may create.
+pub const MAX_COLLECTIONS_PER_USER: i64 = 100;
+/// Maximum number of submissions in a single collection.
+pub const MAX_COLLECTION_ITEMS: i64 = 1000;
+
+/// Who can see a collection.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum CollectionVisibility {
+    /// Anyone can see the collection, and it is
+    /// listed on the profile of its owner.
+    Public,
+    /// Anyone who knows the ID of the collection
+    /// can see it, but it is not listed anywhere.
+    Unlisted,
+    /// Only the owner can see the collection.
+    Private,
+}
+
+/// A list of submissions curated by a user, e.g. a reading list
+/// on a topic. Submissions are kept in the order chosen by the
+/// owner, and may carry a note explaining why they were added.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset, Associations)]
+#[belongs_to(User)]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct Collection {
+    id: CollectionID,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+
+    user_id: UserID,
+    title: String,
+    description: Option<String>,
+    visibility: CollectionVisibility,
+}
+
+/// A submission in a collection. Positions start at zero and
+/// have no gaps, such that moving an item only renumbers the
+/// items between its old and new position.
+#[derive(Debug, Clone, Queryable, Insertable)]
+pub struct CollectionItem {
+    collection_id: CollectionID,
+    url_id: UrlID,
+    position: i32,
+    note: Option<String>,
+    added_at: NaiveDateTime,
+}
+
+/// Position of an item in a collection, used to page through
+/// the items in their manual order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollectionItemCursor {
+    position: i32,
+}
+
+impl fmt::Display for CollectionItemCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = format!("position:{}", self.position);
+        write!(f, "{}", base64::encode_config(raw, base64::URL_SAFE_NO_PAD))
+    }
+}
+
+impl FromStr for CollectionItemCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid collection item cursor";
+        let raw = base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|_| ERR)?;
+        let raw = String::from_utf8(raw).map_err(|_| ERR)?;
+        let position = raw
+            .strip_prefix("position:")
+            .and_then(|position| position.parse().ok())
+            .ok_or(ERR)?;
+        Ok(Self { position })
+    }
+}
+
+#[derive(Debug, Clone, Validate, GraphQLInputObject)]
+pub struct NewCollectionInput {
+    #[validate(length(min = 1, max = 256, message = "The title is too long"))]
+    title: String,
+    #[validate(length(min = 1, max = 2048, message = "The description is too long"))]
+    description: Option<String>,
+    /// Who can see the collection, `PUBLIC` by default.
+    visibility: Option<CollectionVisibility>,
+}
+
+#[derive(Debug, Clone, Validate, GraphQLInputObject)]
+pub struct UpdateCollectionInput {
+    #[validate(length(min = 1, max = 256, message = "The title is too long"))]
+    title: Option<String>,
+    /// Replaces the description of the collection. An
+    /// empty description removes it.
+    #[validate(length(max = 2048, message = "The description is too long"))]
+    description: Option<String>,
+    visibility: Option<CollectionVisibility>,
+}
+
+/// Trim the given note, treating empty notes as absent.
+fn normalize_note(note: Option<String>) -> Result<Option<String>> {
+    let note = note
+        .map(|note| note.trim().to_string())
+        .filter(|note| !note.is_empty());
+    match note {
+        Some(note) if note.len() > 2048 => Err(anyhow!("The note is too long")),
+        note => Ok(note),
+    }
+}
+
+impl Collection {
+    pub fn id(&self) -> CollectionID {
+        self.id
+    }
+
+    pub fn user_id(&self) -> UserID {
+        self.user_id
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn visibility(&self) -> CollectionVisibility {
+        self.visibility
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.updated_at, Utc)
+    }
+
+    pub async fn owner(&self, ctx: &Context) -> Result<User> {
+        User::find(ctx, self.user_id).await
+    }
+}
+
+impl Collection {
+    /// Find the given collection, failing if it is private and
+    /// not owned by the viewer, such that private collections
+    /// are indistinguishable from missing ones.
+    pub async fn find(ctx: &Context, id: CollectionID) -> Result<Self> {
+        let collection: Self = collections::table
+            .find(id)
+            .get_result(&*ctx.conn().await?)
+            .optional()?
+            .ok_or_else(|| anyhow!("Collection not found"))?;
+        if collection.visibility == CollectionVisibility::Private
+            && ctx.maybe_user_id() != Some(collection.user_id)
+        {
+            return Err(anyhow!("Collection not found"));
+        }
+        Ok(collection)
+    }
+
+    /// Find the given collection, failing unless it is
+    /// owned by the viewer.
+    async fn find_owned(ctx: &Context, id: CollectionID) -> Result<Self> {
+        let collection = Self::find(ctx, id).await?;
+        if collection.user_id != ctx.user_id()? {
+            return Err(anyhow!("Only the owner of a collection can change it"));
+        }
+        Ok(collection)
+    }
+
+    /// Collections of the given user, newest first. Unlisted and
+    /// private collections are only included if the viewer is the
+    /// given user, and not only public collections are requested.
+    pub async fn for_user(
+        ctx: &Context,
+        user_id: UserID,
+        public_only: bool,
+        after: Option<CollectionID>,
+        before: Option<CollectionID>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        use collections::dsl;
+
+        let conn = ctx.conn().await?;
+        let mut query = collections::table
+            .filter(dsl::user_id.eq(user_id))
+            .order_by(dsl::created_at.desc())
+            .then_order_by(dsl::id.desc())
+            .into_boxed();
+
+        if public_only || ctx.maybe_user_id() != Some(user_id) {
+            query = query.filter(dsl::visibility.eq(CollectionVisibility::Public));
+        }
+
+        if let Some(after) = after {
+            let after: Self = collections::table.find(after).get_result(&*conn)?;
+            query = query.filter(
+                dsl::created_at.lt(after.created_at).or(dsl::created_at
+                    .eq(after.created_at)
+                    .and(dsl::id.lt(after.id))),
+            );
+        }
+        if let Some(before) = before {
+            let before: Self = collections::table.find(before).get_result(&*conn)?;
+            query = query.filter(
+                dsl::created_at.gt(before.created_at).or(dsl::created_at
+                    .eq(before.created_at)
+                    .and(dsl::id.gt(before.id))),
+            );
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        Ok(query.load(&*conn)?)
+    }
+
+    /// Create a collection owned by the currently logged in user.
+    pub async fn create(ctx: &Context, input: NewCollectionInput) -> Result<Self> {
+        let user = ctx.verified_user().await?;
+        let input = NewCollectionInput {
+            title: input.title.trim().into(),
+            description: input.description.map(|desc| desc.trim().into()),
+            visibility: input.visibility,
+        };
+        input.validate()?;
+
+        let count: i64 = collections::table
+            .filter(collections::dsl::user_id.eq(user.id()))
+            .select(diesel::dsl::count_star())
+            .get_result(&*ctx.conn().await?)?;
+        if count >= MAX_COLLECTIONS_PER_USER {
+            return Err(anyhow!(
+                "You can create at most {} collections",
+                MAX_COLLECTIONS_PER_USER
+            ));
+        }
+
+        let collection = Collection {
+            id: CollectionID::new(),
+            created_at: ctx.now().naive_utc(),
+            updated_at: ctx.now().naive_utc(),
+
+            user_id: user.id(),
+            title: input.title,
+            description: input.description,
+            visibility: input.visibility.unwrap_or(CollectionVisibility::Public),
+        };
+        diesel::insert_into(collections::table)
+            .values(&collection)
+            .execute(&*ctx.conn().await?)?;
+        Ok(collection)
+    }
+
+    /// Update the given collection of the currently logged in user.
+    pub async fn update(
+        ctx: &Context,
+        id: CollectionID,
+        input: UpdateCollectionInput,
+    ) -> Result<Self> {
+        let mut collection = Self::find_owned(ctx, id).await?;
+        let input = UpdateCollectionInput {
+            title: input.title.map(|title| title.trim().into()),
+            description: input.description.map(|desc| desc.trim().into()),
+            visibility: input.visibility,
+        };
+        input.validate()?;
+
+        if let Some(title) = input.title {
+            collection.title = title;
+        }
+        if let Some(description) = input.description {
+            collection.description = Some(description).filter(|desc| !desc.is_empty());
+        }
+        if let Some(visibility) = input.visibility {
+            collection.visibility = visibility;
+        }
+        collection.updated_at = ctx.now().naive_utc();
+        Ok(collection.save_changes(&*ctx.conn().await?)?)
+    }
+
+    /// Delete the given collection of the currently logged in
+    /// user. The submissions in it are not affected.
+    pub async fn delete(ctx: &Context, id: CollectionID) -> Result<()> {
+        let collection = Self::find_owned(ctx, id).await?;
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            diesel::delete(
+                collection_items::table
+                    .filter(collection_items::dsl::collection_id.eq(collection.id)),
+            )
+            .execute(&*conn)?;
+            diesel::delete(&collection).execute(&*conn)?;
+            Ok(())
+        })
+    }
+
+    /// Add the given submission to the end of the given collection
+    /// of the currently logged in user. Adding a submission which is
+    /// in the collection already replaces its note, but keeps its
+    /// position.
+    pub async fn add_item(
+        ctx: &Context,
+        id: CollectionID,
+        url: &Url,
+        note: Option<String>,
+    ) -> Result<Self> {
+        let mut collection = Self::find_owned(ctx, id).await?;
+        if url.is_deleted() || (url.is_draft() && url.created_by_id() != collection.user_id) {
+            return Err(anyhow!("This submission can not be added to a collection"));
+        }
+        let note = normalize_note(note)?;
+        let now = ctx.now().naive_utc();
+
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            let items = collection_items::table
+                .filter(collection_items::dsl::collection_id.eq(collection.id));
+            let updated = diesel::update(items.filter(collection_items::dsl::url_id.eq(url.id())))
+                .set(collection_items::dsl::note.eq(&note))
+                .execute(&*conn)?;
+            if updated == 0 {
+                let count: i64 = items.select(diesel::dsl::count_star()).get_result(&*conn)?;
+                if count >= MAX_COLLECTION_ITEMS {
+                    return Err(anyhow!(
+                        "Collections can hold at most {} submissions",
+                        MAX_COLLECTION_ITEMS
+                    ));
+                }
+                let item = CollectionItem {
+                    collection_id: collection.id,
+                    url_id: url.id(),
+                    position: count as i32,
+                    note,
+                    added_at: now,
+                };
+                diesel::insert_into(collection_items::table)
+                    .values(&item)
+                    .execute(&*conn)?;
+            }
+            collection.updated_at = now;
+            collection = collection.save_changes(&*conn)?;
+            Ok(())
+        })?;
+        Ok(collection)
+    }
+
+    /// Remove the given submission from the given collection of the
+    /// currently logged in user, closing the gap it leaves. Removing
+    /// a submission which is not in the collection has no effect.
+    pub async fn remove_item(ctx: &Context, id: CollectionID, url_id: UrlID) -> Result<Self> {
+        use collection_items::dsl;
+
+        let mut collection = Self::find_owned(ctx, id).await?;
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            let items = collection_items::table.filter(dsl::collection_id.eq(collection.id));
+            let position: Option<i32> = items
+                .filter(dsl::url_id.eq(url_id))
+                .select(dsl::position)
+                .get_result(&*conn)
+                .optional()?;
+            if let Some(position) = position {
+                diesel::delete(items.filter(dsl::url_id.eq(url_id))).execute(&*conn)?;
+                diesel::update(items.filter(dsl::position.gt(position)))
+                    .set(dsl::position.eq(dsl::position - 1))
+                    .execute(&*conn)?;
+                collection.updated_at = ctx.now().naive_utc();
+                collection = collection.save_changes(&*conn)?;
+            }
+            Ok(())
+        })?;
+        Ok(collection)
+    }
+
+    /// Move the given submission to the given position in the given
+    /// collection of the currently logged in user, shifting the items
+    /// in between. Positions past the end move the item to the end.
+    pub async fn move_item(
+        ctx: &Context,
+        id: CollectionID,
+        url_id: UrlID,
+        position: i32,
+    ) -> Result<Self> {
+        use collection_items::dsl;
+
+        let mut collection = Self::find_owned(ctx, id).await?;
+        if position < 0 {
+            return Err(anyhow!("Positions can not be negative"));
+        }
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            let items = collection_items::table.filter(dsl::collection_id.eq(collection.id));
+            let from: i32 = items
+                .filter(dsl::url_id.eq(url_id))
+                .select(dsl::position)
+                .get_result(&*conn)
+                .optional()?
+                .ok_or_else(|| anyhow!("This submission is not in the collection"))?;
+            let count: i64 = items.select(diesel::dsl::count_star()).get_result(&*conn)?;
+            let to = position.min(count as i32 - 1);
+            if from < to {
+                diesel::update(
+                    items
+                        .filter(dsl::position.gt(from))
+                        .filter(dsl::position.le(to)),
+                )
+                .set(dsl::position.eq(dsl::position - 1))
+                .execute(&*conn)?;
+            } else if to < from {
+                diesel::update(
+                    items
+                        .filter(dsl::position.ge(to))
+                        .filter(dsl::position.lt(from)),
+                )
+                .set(dsl::position.eq(dsl::position + 1))
+                .execute(&*conn)?;
+            }
+            diesel::update(items.filter(dsl::url_id.eq(url_id)))
+                .set(dsl::position.eq(to))
+                .execute(&*conn)?;
+            collection.updated_at = ctx.now().naive_utc();
+            collection = collection.save_changes(&*conn)?;
+            Ok(())
+        })?;
+        Ok(collection)
+    }
+
+    /// The number of submissions in this collection, including
+    /// those which are not listed for the viewer.
+    pub async fn item_count(&self, ctx: &Context) -> Result<i64> {
+        let count = collection_items::table
+            .filter(collection_items::dsl::collection_id.eq(self.id))
+            .select(diesel::dsl::count_star())
+            .get_result(&*ctx.conn().await?)?;
+        Ok(count)
+    }
+
+    /// The submissions in this collection in their manual order, in
+    /// a way that's suitable for use with a Relay connection. Deleted
+    /// submissions, removed submissions hidden from the viewer, NSFW
+    /// submissions hidden from the viewer, and drafts of other users
+    /// are excluded.
+    pub async fn items(
+        &self,
+        ctx: &Context,
+        after: Option<CollectionItemCursor>,
+        before: Option<CollectionItemCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<(CollectionItem, Url)>> {
+        use collection_items::dsl::{collection_id, position};
+
+        let listed = Listed::reachable(ctx).await?;
+        let nsfw = Url::listed_nsfw(ctx).await?;
+        let conn = ctx.conn().await?;
+        let mut query = collection_items::table
+            .inner_join(urls::table)
+            .filter(collection_id.eq(self.id))
+            .filter(listed.filter())
+            .filter(urls::dsl::nsfw.eq_any(nsfw))
+            .order_by(position.asc())
+            .select((collection_items::all_columns, urls::all_columns))
+            .into_boxed();
+
+        if let Some(after) = after {
+            query = query.filter(position.gt(after.position));
+        }
+
+        if let Some(before) = before {
+            query = query.filter(position.lt(before.position));
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        Ok(query.load(&*conn)?)
+    }
+}
+
+impl CollectionItem {
+    pub fn url_id(&self) -> UrlID {
+        self.url_id
+    }
+
+    /// The position of this item in its collection,
+    /// starting at zero.
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    pub fn added_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.added_at, Utc)
+    }
+
+    pub fn cursor(&self) -> CollectionItemCursor {
+        CollectionItemCursor {
+            position: self.position,
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for CollectionVisibility
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            CollectionVisibility::Public => "public",
+            CollectionVisibility::Unlisted => "unlisted",
+            CollectionVisibility::Private => "private",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for CollectionVisibility
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "public" => Ok(CollectionVisibility::Public),
+            "unlisted" => Ok(CollectionVisibility::Unlisted),
+            "private" => Ok(CollectionVisibility::Private),
+            _ => Err("Unrecognized collection visibility".into()),
+        }
+    }
+}
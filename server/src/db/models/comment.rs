@@ -1,12 +1,24 @@
 use crate::db::id::{CommentID, UrlID, UserID};
-use crate::db::models::{Url, User};
-use crate::schema::comments;
-use crate::Context;
-use anyhow::Result;
+use crate::db::models::{
+    Block, Listed, Mention, Notification, PostingAction, RateLimit, Revision, Tag, Url, User,
+    Webhook,
+};
+use crate::error::{EditNotAllowed, EditNotAllowedReason};
+use crate::events::Event;
+use crate::schema::{
+    comment_mentions, comment_votes, comments, notifications, revisions, urls, users,
+};
+use crate::spam::{SpamCheckInput, SpamContentKind, SpamVerdict};
+use crate::{markdown, mentions, Context};
+use anyhow::{anyhow, Result};
 use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::expression::BoxableExpression;
 use diesel::prelude::*;
-use juniper::GraphQLInputObject;
-use pulldown_cmark::{html, Options, Parser};
+use diesel::sql_types::Bool;
+use diesel::sqlite::Sqlite;
+use juniper::{GraphQLEnum, GraphQLInputObject};
+use std::fmt;
+use std::str::FromStr;
 use validator::Validate;
 
 #[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset, Associations)]
@@ -22,16 +34,152 @@ pub struct Comment {
     url_id: UrlID,
     created_by: UserID,
     replies_to: Option<CommentID>,
+    depth: i32,
+    edited_at: Option<NaiveDateTime>,
+    deleted_at: Option<NaiveDateTime>,
+    deletion_reason: Option<String>,
+    reply_count: i32,
+    score: i64,
+    html: Option<String>,
+    revision_count: i64,
+    held_at: Option<NaiveDateTime>,
+}
+
+/// Determines how comments are ordered when listing
+/// the comments of a submission.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentSort {
+    /// Highest scoring first, ties are listed in the
+    /// order they were written.
+    Top,
+    /// Most recently written first.
+    New,
+    /// Least recently written first.
+    Old,
+}
+
+impl CommentSort {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CommentSort::Top => "top",
+            CommentSort::New => "new",
+            CommentSort::Old => "old",
+        }
+    }
+}
+
+/// Position of a comment in a list of comments. Like
+/// [`UrlCursor`](crate::db::models::UrlCursor), this holds the order
+/// of the list and the score of the comment at the time the cursor
+/// was handed out, such that later pages don't shift when the comment
+/// the cursor points to gains or loses votes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CommentCursor {
+    sort: CommentSort,
+    score: i64,
+    created_at: NaiveDateTime,
+    id: CommentID,
+}
+
+impl fmt::Display for CommentCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = format!(
+            "{}:{}:{}:{}",
+            self.sort.as_str(),
+            self.score,
+            self.created_at.timestamp_nanos(),
+            self.id
+        );
+        write!(f, "{}", base64::encode_config(raw, base64::URL_SAFE_NO_PAD))
+    }
+}
+
+impl FromStr for CommentCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid comment cursor";
+        let raw = base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|_| ERR)?;
+        let raw = String::from_utf8(raw).map_err(|_| ERR)?;
+        let mut parts = raw.splitn(4, ':');
+        let sort = match parts.next() {
+            Some("top") => CommentSort::Top,
+            Some("new") => CommentSort::New,
+            Some("old") => CommentSort::Old,
+            _ => return Err(ERR),
+        };
+        let score = parts.next().and_then(|s| s.parse().ok()).ok_or(ERR)?;
+        let nanos: i64 = parts.next().and_then(|t| t.parse().ok()).ok_or(ERR)?;
+        let id = parts.next().and_then(|id| id.parse().ok()).ok_or(ERR)?;
+        let created_at = NaiveDateTime::from_timestamp_opt(
+            nanos.div_euclid(1_000_000_000),
+            nanos.rem_euclid(1_000_000_000) as u32,
+        )
+        .ok_or(ERR)?;
+        Ok(Self {
+            sort,
+            score,
+            created_at,
+            id,
+        })
+    }
+}
+
+/// Text which replaces the content of deleted comments.
+const DELETED_TEXT: &str = "[deleted]";
+
+/// Render the markdown text of a comment, linking mentions of the
+/// users in `mentioned`, and hashtags of existing tags.
+fn render<C>(conn: &C, text: &str, mentioned: &[User]) -> Result<String>
+where
+    C: Connection<Backend = Sqlite>,
+{
+    let profiles: Vec<(String, String)> = mentioned
+        .iter()
+        .map(|user| (user.username().to_string(), format!("/user/{}", user.id())))
+        .collect();
+    let tags = Tag::existing(conn, &markdown::hashtags(text))?;
+    let links = markdown::Links {
+        profiles: &profiles,
+        tags: &tags,
+    };
+    Ok(markdown::render(text, links))
 }
 
 #[derive(Debug, Clone, Validate, GraphQLInputObject)]
 pub struct NewCommentInput {
-    #[validate(length(min = 1, message = "The comment can not be empty"))]
+    #[validate(length(
+        min = 1,
+        max = 5000,
+        message = "Comments must have between 1 and 5000 characters"
+    ))]
     comment: String,
     url: UrlID,
     replies_to: Option<CommentID>,
 }
 
+impl NewCommentInput {
+    /// A comment on the given URL, which does not
+    /// reply to any other comment.
+    pub fn new(url: UrlID, comment: String) -> Self {
+        Self {
+            comment,
+            url,
+            replies_to: None,
+        }
+    }
+
+    /// A comment on the given URL, which replies to the
+    /// given comment.
+    pub fn reply(url: UrlID, replies_to: CommentID, comment: String) -> Self {
+        Self {
+            comment,
+            url,
+            replies_to: Some(replies_to),
+        }
+    }
+}
+
 impl Comment {
     pub fn id(&self) -> CommentID {
         self.id
@@ -41,34 +189,136 @@ impl Comment {
         &self.comment
     }
 
-    /// Render the given markdown `text` as
-    /// html. This safely escapes and html present
-    /// on the input.
-    pub fn html(&self) -> String {
-        let mut out = String::new();
-        let mut opts = Options::empty();
-        opts.insert(Options::ENABLE_STRIKETHROUGH);
-        opts.insert(Options::ENABLE_TASKLISTS);
-        opts.insert(Options::ENABLE_SMART_PUNCTUATION);
-        let parser = Parser::new_ext(self.text(), opts);
-        html::push_html(&mut out, parser);
-        out
+    /// The text of this comment rendered as sanitized html, see
+    /// [`markdown::render`]. This is rendered when the comment is
+    /// written, and mentions link to the profiles of the users who
+    /// were mentioned at the time.
+    pub fn html(&self) -> &str {
+        self.html.as_deref().unwrap_or_default()
     }
 
     pub fn created_at(&self) -> DateTime<Utc> {
         DateTime::from_utc(self.created_at, Utc)
     }
 
+    /// How deeply this comment is nested, where comments
+    /// which don't reply to another comment are at depth
+    /// zero.
+    pub fn depth(&self) -> i32 {
+        self.depth
+    }
+
+    /// The last time the author edited this comment, if ever.
+    pub fn edited_at(&self) -> Option<DateTime<Utc>> {
+        self.edited_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    pub fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// The time the spam filter held this comment for review by
+    /// a moderator, if it did and the comment was not released yet.
+    /// Held comments are only visible to their author and moderators.
+    pub fn held_at(&self) -> Option<DateTime<Utc>> {
+        self.held_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.held_at.is_some()
+    }
+
+    /// The number of previous versions of this comment, see
+    /// [`Revision`].
+    pub fn revision_count(&self) -> i64 {
+        self.revision_count
+    }
+
+    /// Why a moderator deleted this comment, if given.
+    pub fn deletion_reason(&self) -> Option<&str> {
+        self.deletion_reason.as_deref()
+    }
+
+    /// Number of listed comments which directly reply to
+    /// this comment.
+    pub fn reply_count(&self) -> i64 {
+        self.reply_count.into()
+    }
+
     pub fn updated_at(&self) -> DateTime<Utc> {
         DateTime::from_utc(self.updated_at, Utc)
     }
 
+    /// Number of users who voted for this comment.
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
+    /// Cursor pointing to this comment in a list sorted
+    /// in the given order.
+    pub fn cursor(&self, sort: CommentSort) -> CommentCursor {
+        CommentCursor {
+            sort,
+            score: self.score,
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+
+    /// If the logged in user voted for this comment. The votes of
+    /// the viewer are loaded once, and remembered for the rest of
+    /// the request.
+    pub async fn viewer_has_voted(&self, ctx: &Context) -> Result<bool> {
+        let user_id = match ctx.maybe_user_id() {
+            Some(user_id) => user_id,
+            None => return Ok(false),
+        };
+        if let Some(voted) = ctx.cached_comment_vote(self.id) {
+            return Ok(voted);
+        }
+        let votes: Vec<CommentID> = comment_votes::table
+            .filter(comment_votes::dsl::user_id.eq(user_id))
+            .select(comment_votes::dsl::comment_id)
+            .load(&*ctx.conn().await?)?;
+        ctx.cache_comment_votes(votes);
+        Ok(ctx.cached_comment_vote(self.id).unwrap_or(false))
+    }
+
     pub async fn url(&self, ctx: &Context) -> Result<Url> {
         Ok(Url::find(ctx, self.url_id).await?)
     }
 
+    pub fn url_id(&self) -> UrlID {
+        self.url_id
+    }
+
+    /// The existing users mentioned in this comment, ordered
+    /// by username. Deleted comments mention nobody.
+    pub async fn mentions(&self, ctx: &Context) -> Result<Vec<User>> {
+        if self.is_deleted() {
+            return Ok(vec![]);
+        }
+        Mention::users(ctx, self.id).await
+    }
+
+    /// ID of the user who wrote this comment.
+    pub fn created_by_id(&self) -> UserID {
+        self.created_by
+    }
+
+    /// ID of the comment this comment replies to, if any.
+    pub fn replies_to_id(&self) -> Option<CommentID> {
+        self.replies_to
+    }
+
+    /// The author of this comment, loaded together with the authors
+    /// of the comments resolved alongside it, see [`User::load`].
     pub async fn created_by(&self, ctx: &Context) -> Result<User> {
-        Ok(User::find(ctx, self.created_by).await?)
+        User::load(ctx, self.created_by).await
     }
 
     pub async fn replies_to(&self, ctx: &Context) -> Result<Option<Self>> {
@@ -88,55 +338,572 @@ impl Comment {
         let comment = comments::table.find(id).get_result(&*ctx.conn().await?)?;
         Ok(comment)
     }
+
+    /// The comment with the given ID, if it is visible to the viewer.
+    /// Deleted comments, held comments hidden from the viewer, comments
+    /// by users they blocked, and comments on submissions hidden from
+    /// them are not visible.
+    pub async fn listed(ctx: &Context, id: CommentID) -> Result<Option<Self>> {
+        let comment = Self::find(ctx, id).await?;
+        if comment.is_deleted() {
+            return Ok(None);
+        }
+        let blocked = match ctx.maybe_user_id() {
+            Some(viewer) => Block::exists(ctx, viewer, comment.created_by).await?,
+            None => false,
+        };
+        if blocked || comment.check_not_held(ctx).await.is_err() {
+            return Ok(None);
+        }
+        let url = Url::find(ctx, comment.url_id).await?;
+        let reachable = Listed::reachable(ctx).await?;
+        let hidden =
+            url.check_visible(ctx).await.is_err() || !reachable.includes(ctx, url.id()).await?;
+        Ok(if hidden { None } else { Some(comment) })
+    }
+
+    /// Filter excluding the comments held by the spam filter which
+    /// are hidden from the currently logged in user, i.e. those of
+    /// other users.
+    pub fn not_held(
+        ctx: &Context,
+    ) -> Box<dyn BoxableExpression<comments::table, Sqlite, SqlType = Bool>> {
+        match ctx.maybe_user_id() {
+            Some(viewer) => Box::new(
+                comments::dsl::held_at
+                    .is_null()
+                    .or(comments::dsl::created_by.eq(viewer)),
+            ),
+            None => Box::new(comments::dsl::held_at.is_null()),
+        }
+    }
+
+    /// Fail if this comment is held by the spam filter and hidden
+    /// from the viewer, who is neither its author nor a moderator.
+    pub async fn check_not_held(&self, ctx: &Context) -> Result<()> {
+        if !self.is_held() || ctx.maybe_user_id() == Some(self.created_by) {
+            return Ok(());
+        }
+        let moderator = match ctx.maybe_user().await? {
+            Some(viewer) => viewer
+                .check_permissions(ctx, |perm| perm.review_reports())
+                .await
+                .is_ok(),
+            None => false,
+        };
+        if moderator {
+            Ok(())
+        } else {
+            Err(anyhow!("This comment is awaiting review by a moderator"))
+        }
+    }
+
+    /// Held comments on the given submission which were not deleted,
+    /// oldest first, as listed in the moderation queue.
+    pub(crate) async fn held_for(ctx: &Context, url_id: UrlID) -> Result<Vec<Self>> {
+        Ok(comments::table
+            .filter(comments::dsl::url_id.eq(url_id))
+            .filter(comments::dsl::held_at.is_not_null())
+            .filter(comments::dsl::deleted_at.is_null())
+            .order_by(comments::dsl::held_at.asc())
+            .then_order_by(comments::dsl::id.asc())
+            .load(&*ctx.conn().await?)?)
+    }
+
+    /// Render the text of comments which were written before their
+    /// html was stored. This is run on startup.
+    pub fn backfill_html<C>(conn: &C) -> Result<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let missing: Vec<Self> = comments::table
+            .filter(comments::dsl::html.is_null())
+            .load(conn)?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            for comment in missing {
+                let mentioned: Vec<User> = users::table
+                    .inner_join(comment_mentions::table)
+                    .filter(comment_mentions::dsl::comment_id.eq(comment.id))
+                    .select(users::all_columns)
+                    .load(conn)?;
+                let html = render(conn, &comment.comment, &mentioned)?;
+                diesel::update(comments::table.find(comment.id))
+                    .set(comments::dsl::html.eq(html))
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Order the comments selected by `query` in the given order,
+    /// in a way that's suitable for use with a Relay connection.
+    /// Cursors must have been issued for the same order.
+    pub(crate) fn paginate<C>(
+        conn: &C,
+        query: comments::BoxedQuery<'_, Sqlite>,
+        sort: CommentSort,
+        after: Option<CommentCursor>,
+        before: Option<CommentCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        use comments::dsl::{created_at, id, score};
+
+        for cursor in after.iter().chain(before.iter()) {
+            if cursor.sort != sort {
+                return Err(anyhow!(
+                    "This cursor was issued for the {:?} order, not {:?}",
+                    cursor.sort,
+                    sort
+                ));
+            }
+        }
+
+        let mut query = match sort {
+            CommentSort::Top => query
+                .order_by(score.desc())
+                .then_order_by(created_at.asc())
+                .then_order_by(id.asc()),
+            CommentSort::New => query.order_by(created_at.desc()).then_order_by(id.desc()),
+            CommentSort::Old => query.order_by(created_at.asc()).then_order_by(id.asc()),
+        };
+
+        if let Some(after) = after {
+            let older = created_at
+                .lt(after.created_at)
+                .or(created_at.eq(after.created_at).and(id.lt(after.id)));
+            let newer = created_at
+                .gt(after.created_at)
+                .or(created_at.eq(after.created_at).and(id.gt(after.id)));
+            query = match sort {
+                CommentSort::Top => {
+                    query.filter(score.lt(after.score).or(score.eq(after.score).and(newer)))
+                }
+                CommentSort::New => query.filter(older),
+                CommentSort::Old => query.filter(newer),
+            };
+        }
+
+        if let Some(before) = before {
+            let older = created_at
+                .lt(before.created_at)
+                .or(created_at.eq(before.created_at).and(id.lt(before.id)));
+            let newer = created_at
+                .gt(before.created_at)
+                .or(created_at.eq(before.created_at).and(id.gt(before.id)));
+            query = match sort {
+                CommentSort::Top => {
+                    query.filter(score.gt(before.score).or(score.eq(before.score).and(older)))
+                }
+                CommentSort::New => query.filter(newer),
+                CommentSort::Old => query.filter(older),
+            };
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        Ok(query.load(conn)?)
+    }
 }
 
 impl Comment {
-    /// Creates a new comment in the database.
+    /// Creates a new comment in the database. Users can not comment on
+    /// deleted or locked submissions, or on submissions or reply to
+    /// comments of users who blocked them. Replies must be to a comment
+    /// on the same submission which wasn't deleted, and may not be
+    /// nested deeper than
+    /// [`Config::max_comment_depth`](crate::Config::max_comment_depth).
+    /// The authors of the submission and the parent comment, as well as
+    /// mentioned users are notified. Comments are checked by the spam
+    /// filter, which rejects spam, and holds suspect comments until a
+    /// moderator releases them, see [`release`](Comment::release).
+    /// Held comments are not counted, and nobody is notified about
+    /// them until they are released. Commenting is rate limited per
+    /// author, see [`RateLimit`].
     pub async fn create(ctx: &Context, mut input: NewCommentInput) -> Result<Self> {
         input.comment = input.comment.trim().into();
         input.validate()?;
 
-        let comment = Comment {
+        let author = ctx.user_id()?;
+        let url = Url::find_visible(ctx, input.url).await?;
+        if url.is_deleted() {
+            return Err(anyhow!("This submission was deleted"));
+        }
+        url.check_not_locked(ctx).await?;
+        if Block::exists(ctx, url.created_by_id(), author).await? {
+            return Err(anyhow!("You can not comment on this submission"));
+        }
+        let parent = match input.replies_to {
+            Some(replies_to) => Some(Self::find(ctx, replies_to).await?),
+            None => None,
+        };
+        let mut depth = 0;
+        if let Some(parent) = &parent {
+            if parent.url_id != input.url {
+                return Err(anyhow!("The parent comment belongs to another submission"));
+            }
+            if parent.is_deleted() {
+                return Err(anyhow!("You can not reply to a deleted comment"));
+            }
+            if Block::exists(ctx, parent.created_by, author).await? {
+                return Err(anyhow!("You can not reply to this comment"));
+            }
+            depth = parent.depth + 1;
+            let max_depth = ctx.config().max_comment_depth();
+            if depth > max_depth {
+                return Err(anyhow!(
+                    "Replies can not be nested more than {} levels deep",
+                    max_depth
+                ));
+            }
+        }
+
+        let user = ctx.user().await?;
+        RateLimit::check(ctx, &user, PostingAction::AddComment).await?;
+        let mut content = SpamCheckInput::new(ctx, SpamContentKind::Comment, user);
+        content.text = Some(input.comment.clone());
+        let held_at = match ctx.spam_filter().check(ctx, content).await {
+            SpamVerdict::Ham => None,
+            SpamVerdict::Suspect => Some(ctx.now().naive_utc()),
+            SpamVerdict::Spam => return Err(anyhow!("This comment can not be accepted")),
+        };
+
+        let mut comment = Comment {
             id: CommentID::new(),
             created_at: ctx.now().naive_utc(),
             updated_at: ctx.now().naive_utc(),
 
             comment: input.comment,
             url_id: input.url,
-            created_by: ctx.user_id()?,
+            created_by: author,
             replies_to: input.replies_to,
+            depth,
+            edited_at: None,
+            deleted_at: None,
+            deletion_reason: None,
+            reply_count: 0,
+            score: 0,
+            html: None,
+            revision_count: 0,
+            held_at,
         };
-        diesel::insert_into(comments::table)
-            .values(&comment)
-            .execute(&*ctx.conn().await?)?;
+        let mentioned =
+            Mention::resolve(ctx, mentions::parse(markdown::parse(&comment.comment))).await?;
+        let conn = ctx.conn().await?;
+        comment.html = Some(render(&*conn, &comment.comment, &mentioned)?);
+        drop(conn);
+        if comment.is_held() {
+            diesel::insert_into(comments::table)
+                .values(&comment)
+                .execute(&*ctx.conn().await?)?;
+            return Ok(comment);
+        }
+
+        let mentioned_ids: Vec<UserID> = mentioned.iter().map(User::id).collect();
+        let notifications =
+            Notification::for_comment(ctx, &comment, &url, parent.as_ref(), &mentioned_ids).await?;
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            diesel::insert_into(comments::table)
+                .values(&comment)
+                .execute(&*conn)?;
+            comment.publish(&*conn, &mentioned, &notifications)
+        })?;
 
+        Webhook::comment_added(ctx, &comment).await;
+        ctx.events()
+            .publish(Event::CommentAdded(comment.id(), comment.url_id));
         Ok(comment)
     }
 
-    /// Deletes a given comment from the database. If the comment
-    /// has replies, the comment is censored instead. (This is done
-    /// to prevent loosing deletion of replies.)
-    pub async fn delete(&mut self, ctx: &Context) -> Result<()> {
-        if self.created_by != ctx.user_id()? {
-            ctx.user()
+    /// Record the mentions of this comment, count it as a comment
+    /// on its submission and as a reply to its parent, and store the
+    /// given notifications about it.
+    fn publish<C>(&self, conn: &C, mentioned: &[User], notifications: &[Notification]) -> Result<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        Mention::replace(conn, self.id, mentioned, self.created_at)?;
+        diesel::update(urls::table.find(self.url_id))
+            .set(urls::dsl::comment_count.eq(urls::dsl::comment_count + 1))
+            .execute(conn)?;
+        if let Some(replies_to) = self.replies_to {
+            diesel::update(comments::table.find(replies_to))
+                .set(comments::dsl::reply_count.eq(comments::dsl::reply_count + 1))
+                .execute(conn)?;
+        }
+        for notification in notifications {
+            diesel::insert_into(notifications::table)
+                .values(notification)
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Release this comment after the spam filter held it, publishing
+    /// it as if it was never held. This is only available to
+    /// administrators and moderators. Releasing a comment which is not
+    /// held does nothing.
+    pub async fn release(&mut self, ctx: &Context) -> Result<()> {
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.review_reports())
+            .await?;
+        if !self.is_held() {
+            return Ok(());
+        }
+        let url = self.url(ctx).await?;
+        let parent = self.replies_to(ctx).await?;
+        let mentioned =
+            Mention::resolve(ctx, mentions::parse(markdown::parse(&self.comment))).await?;
+        let mentioned_ids: Vec<UserID> = mentioned.iter().map(User::id).collect();
+        let notifications =
+            Notification::for_comment(ctx, self, &url, parent.as_ref(), &mentioned_ids).await?;
+        let conn = ctx.conn().await?;
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            diesel::update(comments::table.find(self.id))
+                .set(comments::dsl::held_at.eq(None::<NaiveDateTime>))
+                .execute(&*conn)?;
+            let comment: Comment = comments::table.find(self.id).get_result(&*conn)?;
+            if !comment.is_deleted() {
+                comment.publish(&*conn, &mentioned, &notifications)?;
+            }
+            Ok(comment)
+        })?;
+        drop(conn);
+
+        if !self.is_deleted() {
+            Webhook::comment_added(ctx, self).await;
+            ctx.events()
+                .publish(Event::CommentAdded(self.id(), self.url_id));
+        }
+        Ok(())
+    }
+
+    /// Check if the logged in user may edit this comment. Authors may
+    /// edit their own comments within the configured edit window,
+    /// administrators may edit any comment at any time. Comments in
+    /// locked discussions can only be edited by administrators.
+    async fn check_may_edit(&self, ctx: &Context) -> Result<()> {
+        let user = ctx.verified_user().await?;
+        if self.is_deleted() {
+            return Err(anyhow!("You can not edit a deleted comment"));
+        }
+        self.url(ctx).await?.check_not_locked(ctx).await?;
+        let may_edit_any = user
+            .check_permissions(ctx, |perm| perm.edit_any_comment())
+            .await
+            .is_ok();
+        if !may_edit_any {
+            if self.created_by != user.id() {
+                return Err(EditNotAllowed::new(EditNotAllowedReason::NotAuthor).into());
+            }
+            if self.created_at() + ctx.config().comment_edit_window() < ctx.now() {
+                return Err(EditNotAllowed::new(EditNotAllowedReason::WindowClosed).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the text of this comment, see
+    /// [`check_may_edit`](Comment::check_may_edit) for who may edit
+    /// a comment. Only users who were not mentioned before the
+    /// edit are notified about their mentions. The previous text
+    /// is kept as a [`Revision`] if it changed.
+    pub async fn update(&mut self, ctx: &Context, text: String) -> Result<()> {
+        self.check_may_edit(ctx).await?;
+
+        let input = NewCommentInput::new(self.url_id, text.trim().into());
+        input.validate()?;
+        let revision = if self.comment != input.comment {
+            self.revision_count += 1;
+            let previous = std::mem::replace(&mut self.comment, input.comment);
+            Some(Revision::of_comment(
+                self.id,
+                ctx.user_id()?,
+                previous,
+                ctx.now().naive_utc(),
+            ))
+        } else {
+            None
+        };
+        self.edited_at = Some(ctx.now().naive_utc());
+        self.updated_at = ctx.now().naive_utc();
+
+        let mentioned =
+            Mention::resolve(ctx, mentions::parse(markdown::parse(&self.comment))).await?;
+        let before = Mention::user_ids(ctx, self.id).await?;
+        let added: Vec<UserID> = mentioned
+            .iter()
+            .map(User::id)
+            .filter(|user_id| !before.contains(user_id))
+            .collect();
+        let notifications = Notification::for_mentions(ctx, self, &added).await?;
+        let conn = ctx.conn().await?;
+        self.html = Some(render(&*conn, &self.comment, &mentioned)?);
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            let comment: Comment = self.save_changes(&*conn)?;
+            if let Some(revision) = &revision {
+                diesel::insert_into(revisions::table)
+                    .values(revision)
+                    .execute(&*conn)?;
+            }
+            Mention::replace(&*conn, comment.id, &mentioned, comment.updated_at)?;
+            for notification in &notifications {
+                diesel::insert_or_ignore_into(notifications::table)
+                    .values(notification)
+                    .execute(&*conn)?;
+            }
+            Ok(comment)
+        })?;
+        Ok(())
+    }
+
+    /// Deletes the given comment. Only the original author, or a
+    /// moderator may delete comments, and moderators may give a reason.
+    /// Moderators of a group may delete comments on its submissions.
+    /// Deleted comments keep their place in the thread as a placeholder
+    /// while they have replies, and are removed from all listings otherwise.
+    /// Removing a comment may in turn remove its deleted parents. Deleting
+    /// a comment again does nothing. The deleted text is kept as a
+    /// [`Revision`].
+    pub async fn delete(&mut self, ctx: &Context, reason: Option<String>) -> Result<()> {
+        let is_author = self.created_by == ctx.user_id()?;
+        if !is_author {
+            self.url(ctx)
                 .await?
-                .check_permissions(ctx, |perm| perm.delete_any_comment())
+                .check_may_moderate(ctx, |perm| perm.delete_any_comment())
                 .await?;
         }
+        if self.is_deleted() {
+            return Ok(());
+        }
 
-        let replies_count: i64 = comments::table
-            .filter(comments::dsl::replies_to.eq(self.id()))
-            .select(diesel::dsl::count_star())
-            .get_result(&*ctx.conn().await?)?;
+        let revision = Revision::of_comment(
+            self.id,
+            ctx.user_id()?,
+            std::mem::replace(&mut self.comment, DELETED_TEXT.to_string()),
+            ctx.now().naive_utc(),
+        );
+        self.revision_count += 1;
+        self.html = Some(markdown::render(DELETED_TEXT, Default::default()));
+        self.deleted_at = Some(ctx.now().naive_utc());
+        self.updated_at = ctx.now().naive_utc();
+        if !is_author {
+            self.deletion_reason = reason
+                .map(|reason| reason.trim().to_string())
+                .filter(|reason| !reason.is_empty());
+        }
 
-        if replies_count > 0 {
-            self.updated_at = ctx.now().naive_utc();
-            self.comment = "[DELETED]".to_string();
-            *self = self.save_changes(&*ctx.conn().await?)?;
-        } else {
-            diesel::delete(&*self).execute(&*ctx.conn().await?)?;
+        let conn = ctx.conn().await?;
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            let comment: Comment = self.save_changes(&*conn)?;
+            diesel::insert_into(revisions::table)
+                .values(&revision)
+                .execute(&*conn)?;
+            // held comments were never counted
+            let mut removed = Some(comment.clone())
+                .filter(|comment| comment.reply_count == 0 && !comment.is_held());
+            while let Some(leaf) = removed.take() {
+                diesel::update(urls::table.find(leaf.url_id))
+                    .set(urls::dsl::comment_count.eq(urls::dsl::comment_count - 1))
+                    .execute(&*conn)?;
+                if let Some(replies_to) = leaf.replies_to {
+                    diesel::update(comments::table.find(replies_to))
+                        .set(comments::dsl::reply_count.eq(comments::dsl::reply_count - 1))
+                        .execute(&*conn)?;
+                    let parent: Comment = comments::table.find(replies_to).get_result(&*conn)?;
+                    if parent.is_deleted() && parent.reply_count == 0 {
+                        removed = Some(parent);
+                    }
+                }
+            }
+            Ok(comment)
+        })?;
+
+        Ok(())
+    }
+
+    /// Vote for this comment as the logged in user, and reload it
+    /// with the updated score. Voting again does nothing. Deleted
+    /// comments and comments in locked discussions can not be voted
+    /// on, and whether authors may vote on their own comments is
+    /// configured like for submissions. Votes change the karma of
+    /// the author.
+    pub async fn vote(&mut self, ctx: &Context) -> Result<()> {
+        let user_id = ctx.user_id()?;
+        if self.is_deleted() {
+            return Err(anyhow!("You can not vote on a deleted comment"));
         }
+        if self.created_by == user_id && !ctx.config().allow_self_votes() {
+            return Err(anyhow!("You can not vote on your own comment"));
+        }
+        self.url(ctx).await?.check_not_locked(ctx).await?;
+        let conn = ctx.conn().await?;
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            let inserted = diesel::insert_or_ignore_into(comment_votes::table)
+                .values((
+                    comment_votes::dsl::comment_id.eq(self.id),
+                    comment_votes::dsl::user_id.eq(user_id),
+                    comment_votes::dsl::created_at.eq(ctx.now().naive_utc()),
+                ))
+                .execute(&*conn)?;
+            if inserted > 0 {
+                diesel::update(comments::table.find(self.id))
+                    .set(comments::dsl::score.eq(comments::dsl::score + 1))
+                    .execute(&*conn)?;
+                let karma = ctx.config().karma_comment_weight();
+                User::add_karma(&*conn, self.created_by, karma)?;
+            }
+            Ok(comments::table.find(self.id).get_result(&*conn)?)
+        })?;
+        ctx.update_cached_comment_vote(self.id, true);
+        Ok(())
+    }
 
+    /// Rescind a vote for this comment as the logged in user, and
+    /// reload it with the updated score. Rescinding a missing vote
+    /// does nothing. Votes in locked discussions can not be rescinded.
+    pub async fn unvote(&mut self, ctx: &Context) -> Result<()> {
+        let user_id = ctx.user_id()?;
+        self.url(ctx).await?.check_not_locked(ctx).await?;
+        let conn = ctx.conn().await?;
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            let vote = comment_votes::table
+                .filter(comment_votes::dsl::comment_id.eq(self.id))
+                .filter(comment_votes::dsl::user_id.eq(user_id));
+            let deleted = diesel::delete(vote).execute(&*conn)?;
+            if deleted > 0 {
+                diesel::update(comments::table.find(self.id))
+                    .set(comments::dsl::score.eq(comments::dsl::score - 1))
+                    .execute(&*conn)?;
+                let karma = ctx.config().karma_comment_weight();
+                User::add_karma(&*conn, self.created_by, -karma)?;
+            }
+            Ok(comments::table.find(self.id).get_result(&*conn)?)
+        })?;
+        ctx.update_cached_comment_vote(self.id, false);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_cursor() {
+        let cursor = CommentCursor {
+            sort: CommentSort::Top,
+            score: -2,
+            created_at: NaiveDateTime::from_timestamp(1_632_000_000, 123_456_789),
+            id: CommentID::new(),
+        };
+        assert_eq!(cursor.to_string().parse::<CommentCursor>(), Ok(cursor));
+        assert!("not a cursor".parse::<CommentCursor>().is_err());
+    }
+}
@@ -0,0 +1,560 @@
+use crate::db::id::{DataExportID, UserID};
+use crate::db::models::User;
+use crate::error::AppError;
+use crate::schema::{comments, data_exports, invites, logins, saved_urls, url_upvotes, urls};
+use crate::{signing, Context};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::{Text, Timestamp};
+use juniper::GraphQLEnum;
+use lettre::message::{Mailbox, Message};
+use serde_json::{json, Map, Value};
+use std::io::Write;
+
+const DOWNLOAD_PURPOSE: &str = "data_export";
+const DOWNLOAD_VALID_DAYS: i64 = 7;
+
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum DataExportStatus {
+    /// The export was requested, but not started yet.
+    Pending,
+    /// The export archive is being assembled.
+    Processing,
+    /// The export archive is available for download.
+    Ready,
+    /// Assembling the export archive failed.
+    Failed,
+}
+
+/// A copy of all data associated with a user account,
+/// requested by that user. Exports are assembled in the
+/// background, and stored as a zip archive.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset, Associations)]
+#[belongs_to(User)]
+pub struct DataExport {
+    id: DataExportID,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+
+    user_id: UserID,
+    status: DataExportStatus,
+    completed_at: Option<NaiveDateTime>,
+    storage_key: Option<String>,
+}
+
+/// A table of exported data, which is written as a
+/// JSON array and a CSV file to the archive.
+pub(super) struct Table {
+    pub name: &'static str,
+    pub columns: &'static [&'static str],
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn to_json(&self) -> Value {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                let object: Map<String, Value> = self
+                    .columns
+                    .iter()
+                    .map(|column| column.to_string())
+                    .zip(row.iter().cloned())
+                    .collect();
+                Value::Object(object)
+            })
+            .collect();
+        Value::Array(rows)
+    }
+
+    /// Write the table as CSV. Lists, such as tags,
+    /// are written as space separated words.
+    pub fn to_csv(&self) -> String {
+        fn text(value: &Value) -> String {
+            match value {
+                Value::Null => String::new(),
+                Value::String(text) => text.clone(),
+                Value::Array(items) => items.iter().map(text).collect::<Vec<_>>().join(" "),
+                other => other.to_string(),
+            }
+        }
+
+        fn field(value: &Value) -> String {
+            let text = text(value);
+            if text.contains(&[',', '"', '\n', '\r'][..]) {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            } else {
+                text
+            }
+        }
+
+        let mut csv = self.columns.join(",");
+        csv.push_str("\r\n");
+        for row in &self.rows {
+            csv.push_str(&row.iter().map(field).collect::<Vec<_>>().join(","));
+            csv.push_str("\r\n");
+        }
+        csv
+    }
+}
+
+pub(super) fn time(time: NaiveDateTime) -> Value {
+    json!(DateTime::<Utc>::from_utc(time, Utc).to_rfc3339())
+}
+
+pub(super) fn maybe_time(time: Option<NaiveDateTime>) -> Value {
+    time.map(self::time).unwrap_or(Value::Null)
+}
+
+impl DataExport {
+    pub fn id(&self) -> DataExportID {
+        self.id
+    }
+
+    pub fn user_id(&self) -> UserID {
+        self.user_id
+    }
+
+    pub fn status(&self) -> DataExportStatus {
+        self.status
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    pub fn completed_at(&self) -> Option<DateTime<Utc>> {
+        self.completed_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    /// The time after which the export can no longer
+    /// be downloaded, if it completed.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.completed_at()
+            .map(|at| at + Duration::days(DOWNLOAD_VALID_DAYS))
+    }
+}
+
+impl DataExport {
+    pub async fn find(ctx: &Context, id: DataExportID) -> Result<Self> {
+        let export = data_exports::table
+            .find(id)
+            .get_result(&*ctx.conn().await?)?;
+        Ok(export)
+    }
+
+    /// Find the export referenced by a download token, which
+    /// was sent to the user once the export completed. Tampered,
+    /// malformed, or expired tokens are rejected with the same
+    /// error.
+    pub async fn find_by_download_token(ctx: &Context, token: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid or expired download token");
+
+        let payload = signing::verify(ctx, DOWNLOAD_PURPOSE, token).ok_or_else(invalid)?;
+        let (id, expires_at) = payload.split_once(':').ok_or_else(invalid)?;
+        let expires_at =
+            NaiveDateTime::from_timestamp_opt(expires_at.parse().map_err(|_| invalid())?, 0)
+                .ok_or_else(invalid)?;
+        if DateTime::<Utc>::from_utc(expires_at, Utc) < ctx.now() {
+            return Err(invalid());
+        }
+
+        let id: DataExportID = id.parse().map_err(|_| invalid())?;
+        let export = Self::find(ctx, id).await.map_err(|_| invalid())?;
+        if export.status != DataExportStatus::Ready {
+            return Err(invalid());
+        }
+        Ok(export)
+    }
+
+    /// Request a new export of all data associated with the
+    /// given user. Each user may only have one export in flight
+    /// at any time.
+    pub async fn request(ctx: &Context, user: &User) -> Result<Self> {
+        let export = DataExport {
+            id: DataExportID::new(),
+            created_at: ctx.now().naive_utc(),
+            updated_at: ctx.now().naive_utc(),
+
+            user_id: user.id(),
+            status: DataExportStatus::Pending,
+            completed_at: None,
+            storage_key: None,
+        };
+
+        // The check is part of the insert, such that
+        // concurrent requests can not both succeed.
+        let inserted = diesel::sql_query(
+            "INSERT INTO data_exports (id, created_at, updated_at, user_id, status) \
+            SELECT ?, ?, ?, ?, 'pending' \
+            WHERE NOT EXISTS (SELECT 1 FROM data_exports \
+                WHERE user_id = ? AND status IN ('pending', 'processing'))",
+        )
+        .bind::<Text, _>(export.id.as_str())
+        .bind::<Timestamp, _>(export.created_at)
+        .bind::<Timestamp, _>(export.updated_at)
+        .bind::<Text, _>(export.user_id.as_str())
+        .bind::<Text, _>(export.user_id.as_str())
+        .execute(&*ctx.conn().await?)?;
+        if inserted != 1 {
+            let message = "A data export is already in progress, please wait for it to complete";
+            return Err(AppError::Conflict(message.into()).into());
+        }
+        Ok(export)
+    }
+
+    /// Assemble all pending exports. Each completed export
+    /// is stored, and a download link is sent to the user.
+    pub async fn process_pending(ctx: &Context) -> Result<()> {
+        let pending: Vec<DataExport> = data_exports::table
+            .filter(data_exports::dsl::status.eq(DataExportStatus::Pending))
+            .order_by(data_exports::dsl::created_at.asc())
+            .load(&*ctx.conn().await?)?;
+
+        for mut export in pending {
+            if let Err(err) = export.process(ctx).await {
+                log::error!("Failed to process data export {}: {}", export.id, err);
+                export.status = DataExportStatus::Failed;
+                export.updated_at = ctx.now().naive_utc();
+                export.save_changes::<DataExport>(&*ctx.conn().await?)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn process(&mut self, ctx: &Context) -> Result<()> {
+        // claim the export, so concurrent job runs
+        // do not process the same export twice
+        let claimed = diesel::update(&*self)
+            .filter(data_exports::dsl::status.eq(DataExportStatus::Pending))
+            .set((
+                data_exports::dsl::status.eq(DataExportStatus::Processing),
+                data_exports::dsl::updated_at.eq(ctx.now().naive_utc()),
+            ))
+            .execute(&*ctx.conn().await?)?;
+        if claimed != 1 {
+            return Ok(());
+        }
+        self.status = DataExportStatus::Processing;
+
+        let user = User::find(ctx, self.user_id).await?;
+        let archive = Self::archive(ctx, &user).await?;
+        let key = format!("data-exports/{}/{}.zip", self.user_id, self.id);
+        ctx.storage().put(&key, &archive).await?;
+
+        self.status = DataExportStatus::Ready;
+        self.completed_at = Some(ctx.now().naive_utc());
+        self.storage_key = Some(key);
+        self.updated_at = ctx.now().naive_utc();
+        *self = self.save_changes(&*ctx.conn().await?)?;
+
+        // the export remains available from the account
+        // page, should sending the email fail
+        if let Err(err) = self.send_download_email(ctx, &user).await {
+            log::error!("Failed to send data export email: {}", err);
+        }
+        Ok(())
+    }
+
+    async fn send_download_email(&self, ctx: &Context, user: &User) -> Result<()> {
+        let expires_at = self
+            .expires_at()
+            .ok_or_else(|| anyhow!("Data export is not completed"))?;
+        let payload = format!("{}:{}", self.id, expires_at.timestamp());
+        let token = signing::sign(ctx, DOWNLOAD_PURPOSE, &payload);
+
+        let email = Message::builder()
+            .from("noreply@urls.fyi <noreply@urls.fyi>".parse().unwrap()) // TODO: Make configurable ...
+            .to(Mailbox::new(Some(user.name().to_string()), user.email()?))
+            .subject("Your data export is ready")
+            .body(format!(
+                "The export of your account data you requested is ready. You \
+                can download it from the following link:\n\n\
+                https://{host}/data-export/{token}\n\n\
+                The link is valid for {days} days.",
+                host = ctx.config().hostname(),
+                token = token,
+                days = DOWNLOAD_VALID_DAYS,
+            ))?;
+        ctx.mailer().send(email).await?;
+        Ok(())
+    }
+
+    /// Read the stored archive of a completed export.
+    pub async fn read_archive(&self, ctx: &Context) -> Result<Vec<u8>> {
+        let key = self
+            .storage_key
+            .as_deref()
+            .ok_or_else(|| anyhow!("Data export is not completed"))?;
+        ctx.storage().get(key).await
+    }
+
+    /// Assemble a zip archive of all data associated with the user. The
+    /// archive contains an `export.json` file with all data, and a CSV
+    /// file for each table. Secrets such as session tokens are omitted.
+    async fn archive(ctx: &Context, user: &User) -> Result<Vec<u8>> {
+        let conn = ctx.conn().await?;
+        let user_id = user.id();
+
+        let profile = Table {
+            name: "profile",
+            columns: &[
+                "id",
+                "name",
+                "username",
+                "email",
+                "created_at",
+                "email_verified_at",
+            ],
+            rows: vec![vec![
+                json!(user.id().to_string()),
+                json!(user.name()),
+                json!(user.username()),
+                json!(user.email()?.to_string()),
+                json!(user.created_at().to_rfc3339()),
+                json!(user.email_verified_at().map(|at| at.to_rfc3339())),
+            ]],
+        };
+
+        let submissions = Table {
+            name: "submissions",
+            columns: &["id", "created_at", "url", "title", "description", "text"],
+            rows: urls::table
+                .filter(urls::dsl::created_by.eq(user_id))
+                .order_by(urls::dsl::created_at.asc())
+                .select((
+                    urls::dsl::id,
+                    urls::dsl::created_at,
+                    urls::dsl::url,
+                    urls::dsl::title,
+                    urls::dsl::description,
+                    urls::dsl::text,
+                ))
+                .load::<(
+                    String,
+                    NaiveDateTime,
+                    Option<String>,
+                    Option<String>,
+                    Option<String>,
+                    Option<String>,
+                )>(&*conn)?
+                .into_iter()
+                .map(|(id, created_at, url, title, description, text)| {
+                    vec![
+                        json!(id),
+                        time(created_at),
+                        json!(url),
+                        json!(title),
+                        json!(description),
+                        json!(text),
+                    ]
+                })
+                .collect(),
+        };
+
+        let comments = Table {
+            name: "comments",
+            columns: &["id", "created_at", "url_id", "replies_to", "comment"],
+            rows: comments::table
+                .filter(comments::dsl::created_by.eq(user_id))
+                .order_by(comments::dsl::created_at.asc())
+                .select((
+                    comments::dsl::id,
+                    comments::dsl::created_at,
+                    comments::dsl::url_id,
+                    comments::dsl::replies_to,
+                    comments::dsl::comment,
+                ))
+                .load::<(String, NaiveDateTime, String, Option<String>, String)>(&*conn)?
+                .into_iter()
+                .map(|(id, created_at, url_id, replies_to, comment)| {
+                    vec![
+                        json!(id),
+                        time(created_at),
+                        json!(url_id),
+                        json!(replies_to),
+                        json!(comment),
+                    ]
+                })
+                .collect(),
+        };
+
+        let votes = Table {
+            name: "votes",
+            columns: &["url_id", "created_at", "direction"],
+            rows: url_upvotes::table
+                .filter(url_upvotes::dsl::user_id.eq(user_id))
+                .order_by(url_upvotes::dsl::created_at.asc())
+                .select((
+                    url_upvotes::dsl::url_id,
+                    url_upvotes::dsl::created_at,
+                    url_upvotes::dsl::direction,
+                ))
+                .load::<(String, NaiveDateTime, String)>(&*conn)?
+                .into_iter()
+                .map(|(url_id, created_at, direction)| {
+                    vec![json!(url_id), time(created_at), json!(direction)]
+                })
+                .collect(),
+        };
+
+        let saved = Table {
+            name: "saved",
+            columns: &["url_id", "saved_at", "read_at"],
+            rows: saved_urls::table
+                .filter(saved_urls::dsl::user_id.eq(user_id))
+                .order_by(saved_urls::dsl::saved_at.asc())
+                .select((
+                    saved_urls::dsl::url_id,
+                    saved_urls::dsl::saved_at,
+                    saved_urls::dsl::read_at,
+                ))
+                .load::<(String, NaiveDateTime, Option<NaiveDateTime>)>(&*conn)?
+                .into_iter()
+                .map(|(url_id, saved_at, read_at)| {
+                    vec![json!(url_id), time(saved_at), maybe_time(read_at)]
+                })
+                .collect(),
+        };
+
+        let sessions = Table {
+            name: "sessions",
+            columns: &[
+                "id",
+                "created_at",
+                "last_used",
+                "user_agent",
+                "ip_address",
+                "revoked",
+            ],
+            rows: logins::table
+                .filter(logins::dsl::user_id.eq(user_id))
+                .filter(logins::dsl::claimed.eq(true))
+                .order_by(logins::dsl::created_at.asc())
+                .select((
+                    logins::dsl::id,
+                    logins::dsl::created_at,
+                    logins::dsl::last_used,
+                    logins::dsl::last_user_agent,
+                    logins::dsl::last_remote_ip,
+                    logins::dsl::revoked,
+                ))
+                .load::<(
+                    String,
+                    NaiveDateTime,
+                    NaiveDateTime,
+                    Option<String>,
+                    Option<String>,
+                    bool,
+                )>(&*conn)?
+                .into_iter()
+                .map(
+                    |(id, created_at, last_used, user_agent, remote_ip, revoked)| {
+                        vec![
+                            json!(id),
+                            time(created_at),
+                            time(last_used),
+                            json!(user_agent),
+                            json!(remote_ip),
+                            json!(revoked),
+                        ]
+                    },
+                )
+                .collect(),
+        };
+
+        let invites = Table {
+            name: "invites",
+            columns: &["id", "created_at", "claimed_by", "revoked_at"],
+            rows: invites::table
+                .filter(invites::dsl::created_by.eq(user_id))
+                .order_by(invites::dsl::created_at.asc())
+                .select((
+                    invites::dsl::id,
+                    invites::dsl::created_at,
+                    invites::dsl::claimed_by,
+                    invites::dsl::revoked_at,
+                ))
+                .load::<(String, NaiveDateTime, Option<String>, Option<NaiveDateTime>)>(&*conn)?
+                .into_iter()
+                .map(|(id, created_at, claimed_by, revoked_at)| {
+                    vec![
+                        json!(id),
+                        time(created_at),
+                        json!(claimed_by),
+                        maybe_time(revoked_at),
+                    ]
+                })
+                .collect(),
+        };
+
+        let tables = [
+            profile,
+            submissions,
+            comments,
+            votes,
+            saved,
+            sessions,
+            invites,
+        ];
+
+        let mut export = Map::new();
+        export.insert("exported_at".into(), json!(ctx.now().to_rfc3339()));
+        for table in &tables {
+            let rows = table.to_json();
+            let value = match table.name {
+                "profile" => rows[0].clone(),
+                _ => rows,
+            };
+            export.insert(table.name.into(), value);
+        }
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default();
+        zip.start_file("export.json", options)?;
+        zip.write_all(serde_json::to_string_pretty(&export)?.as_bytes())?;
+        for table in &tables {
+            zip.start_file(format!("{}.csv", table.name), options)?;
+            zip.write_all(table.to_csv().as_bytes())?;
+        }
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+impl<DB> ToSql<Text, DB> for DataExportStatus
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            DataExportStatus::Pending => "pending",
+            DataExportStatus::Processing => "processing",
+            DataExportStatus::Ready => "ready",
+            DataExportStatus::Failed => "failed",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for DataExportStatus
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "pending" => Ok(DataExportStatus::Pending),
+            "processing" => Ok(DataExportStatus::Processing),
+            "ready" => Ok(DataExportStatus::Ready),
+            "failed" => Ok(DataExportStatus::Failed),
+            _ => Err("Unrecognized data export status".into()),
+        }
+    }
+}
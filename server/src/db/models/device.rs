@@ -0,0 +1,97 @@
+use crate::db::id::{KnownDeviceID, UserID};
+use crate::db::models::User;
+use crate::schema::known_devices;
+use crate::Context;
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use sha2::{Digest, Sha256};
+
+const FORGET_AFTER_UNUSED_DAYS: i64 = 90;
+
+/// A device (user agent and IP combination) from which a
+/// user previously logged in. Only a fingerprint of the
+/// device is stored.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset, Associations)]
+#[belongs_to(User)]
+pub struct KnownDevice {
+    id: KnownDeviceID,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+
+    user_id: UserID,
+    fingerprint: String,
+    last_seen: NaiveDateTime,
+}
+
+impl KnownDevice {
+    pub fn id(&self) -> KnownDeviceID {
+        self.id
+    }
+
+    pub fn last_seen(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.last_seen, Utc)
+    }
+
+    /// Compute the device fingerprint for the request which
+    /// created the given context. The user agent is reduced
+    /// to the browser and operating system, such that browser
+    /// updates are not considered a new device.
+    pub fn fingerprint(ctx: &Context) -> String {
+        let user_agent = ctx
+            .user_agent()
+            .map(|raw| {
+                use woothee::parser::Parser;
+                match Parser::new().parse(raw) {
+                    Some(res) => format!("{}/{}", res.name, res.os),
+                    None => raw.to_string(),
+                }
+            })
+            .unwrap_or_default();
+        let remote_ip = ctx
+            .remote_ip_address()
+            .map(|ip| ip.to_string())
+            .unwrap_or_default();
+        format!(
+            "{:x}",
+            Sha256::digest(format!("{}\n{}", user_agent, remote_ip).as_bytes())
+        )
+    }
+}
+
+impl KnownDevice {
+    /// Record that the given user logged in from the device which
+    /// made the current request. Returns `true` if the device was
+    /// not seen recently (and the user should be notified).
+    pub async fn remember(ctx: &Context, user_id: UserID) -> Result<bool> {
+        let conn = ctx.conn().await?;
+        let device = KnownDevice {
+            id: KnownDeviceID::new(),
+            created_at: ctx.now().naive_utc(),
+            updated_at: ctx.now().naive_utc(),
+
+            user_id,
+            fingerprint: Self::fingerprint(ctx),
+            last_seen: ctx.now().naive_utc(),
+        };
+
+        // inserting first makes concurrent logins from
+        // the same device only notify once
+        let inserted = diesel::insert_or_ignore_into(known_devices::table)
+            .values(&device)
+            .execute(&*conn)?;
+        if inserted > 0 {
+            return Ok(true);
+        }
+
+        let mut known: KnownDevice = known_devices::table
+            .filter(known_devices::dsl::user_id.eq(user_id))
+            .filter(known_devices::dsl::fingerprint.eq(&device.fingerprint))
+            .get_result(&*conn)?;
+        let is_stale = known.last_seen() + Duration::days(FORGET_AFTER_UNUSED_DAYS) < ctx.now();
+        known.last_seen = ctx.now().naive_utc();
+        known.updated_at = ctx.now().naive_utc();
+        known.save_changes::<KnownDevice>(&*conn)?;
+        Ok(is_stale)
+    }
+}
@@ -0,0 +1,214 @@
+use crate::db::id::{DigestID, UrlID, UserID};
+use crate::db::models::{
+    DigestFrequency, EmailCategory, UnsubscribeToken, Url, UrlFilter, UrlSort, User,
+};
+use crate::schema::{digest_items, digests, user_preferences, users};
+use crate::Context;
+use anyhow::{anyhow, Result};
+use askama::Template;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use lettre::message::Mailbox;
+use lettre::Message;
+
+/// Maximum number of submissions included in a digest.
+pub const DIGEST_SIZE: i64 = 10;
+
+/// A digest of the top submissions, which was assembled for a user.
+/// Digests are recorded along with the submissions they include, such
+/// that no submission is included in more than one digest for the same
+/// user, and are recorded even if they turned out empty.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, Associations)]
+#[belongs_to(User)]
+pub struct Digest {
+    id: DigestID,
+    created_at: NaiveDateTime,
+
+    user_id: UserID,
+    frequency: DigestFrequency,
+    url_count: i32,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "digest_items"]
+struct DigestItem {
+    user_id: UserID,
+    url_id: UrlID,
+    digest_id: DigestID,
+}
+
+struct DigestEntry {
+    title: String,
+    link: String,
+    discussion: String,
+    score: i64,
+}
+
+#[derive(Template)]
+#[template(path = "emails/digest.txt")]
+struct DigestEmail<'a> {
+    name: &'a str,
+    period: &'a str,
+    entries: &'a [DigestEntry],
+    host: &'a str,
+    footer: String,
+}
+
+impl Digest {
+    pub fn id(&self) -> DigestID {
+        self.id
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    pub fn frequency(&self) -> DigestFrequency {
+        self.frequency
+    }
+
+    /// Number of submissions included in this digest.
+    pub fn url_count(&self) -> i64 {
+        self.url_count.into()
+    }
+}
+
+impl Digest {
+    /// Send digests to all verified users who chose to receive
+    /// them, and did not receive one within the chosen period yet.
+    /// Running this again before the period passed has no effect.
+    pub async fn send_due(ctx: &Context) -> Result<()> {
+        let subscribed: Vec<(User, DigestFrequency)> = users::table
+            .inner_join(user_preferences::table)
+            .filter(user_preferences::dsl::digest.ne(DigestFrequency::Off))
+            .filter(users::dsl::email_verified_at.is_not_null())
+            .filter(users::dsl::banned_at.is_null())
+            .select((users::all_columns, user_preferences::dsl::digest))
+            .load(&*ctx.conn().await?)?;
+
+        for (user, frequency) in subscribed {
+            let duration = match frequency.range().and_then(|range| range.duration()) {
+                Some(duration) => duration,
+                None => continue,
+            };
+            let last_sent: Option<NaiveDateTime> = digests::table
+                .filter(digests::dsl::user_id.eq(user.id()))
+                .select(diesel::dsl::max(digests::dsl::created_at))
+                .get_result(&*ctx.conn().await?)?;
+            let due = (ctx.now() - duration).naive_utc();
+            if last_sent.map_or(true, |sent| sent <= due) {
+                if let Err(err) = Self::send(ctx, &user, frequency).await {
+                    log::error!("Failed to send digest to user {}: {}", user.id(), err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Assemble, record, and email a digest of the top submissions
+    /// for the period of the given frequency to the given user. The
+    /// muted domains and blocks of the user apply, and submissions
+    /// included in earlier digests are skipped. Empty digests are
+    /// recorded but not sent.
+    pub async fn send(ctx: &Context, user: &User, frequency: DigestFrequency) -> Result<Self> {
+        let range = frequency
+            .range()
+            .ok_or_else(|| anyhow!("The user does not receive digests"))?;
+
+        let sent: Vec<UrlID> = digest_items::table
+            .filter(digest_items::dsl::user_id.eq(user.id()))
+            .select(digest_items::dsl::url_id)
+            .load(&*ctx.conn().await?)?;
+        let filter = UrlFilter {
+            range: Some(range),
+            ..Default::default()
+        };
+        let limit = DIGEST_SIZE + sent.len() as i64;
+        let urls: Vec<Url> = Url::all_submissions(
+            &ctx.acting_as(user.id()),
+            filter,
+            UrlSort::Top,
+            None,
+            None,
+            Some(limit),
+        )
+        .await?
+        .into_iter()
+        .filter(|url| !sent.contains(&url.id()))
+        .take(DIGEST_SIZE as usize)
+        .collect();
+
+        let digest = Digest {
+            id: DigestID::new(),
+            created_at: ctx.now().naive_utc(),
+
+            user_id: user.id(),
+            frequency,
+            url_count: urls.len() as i32,
+        };
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            diesel::insert_into(digests::table)
+                .values(&digest)
+                .execute(&*conn)?;
+            for url in &urls {
+                let item = DigestItem {
+                    user_id: user.id(),
+                    url_id: url.id(),
+                    digest_id: digest.id,
+                };
+                diesel::insert_or_ignore_into(digest_items::table)
+                    .values(&item)
+                    .execute(&*conn)?;
+            }
+            Ok(())
+        })?;
+        drop(conn);
+
+        if !urls.is_empty() {
+            digest.send_email(ctx, user, &urls).await?;
+        }
+        Ok(digest)
+    }
+
+    async fn send_email(&self, ctx: &Context, user: &User, urls: &[Url]) -> Result<()> {
+        let host = ctx.config().hostname();
+        let entries: Vec<DigestEntry> = urls
+            .iter()
+            .map(|url| {
+                let discussion = format!("https://{}/comments/{}", host, url.id());
+                DigestEntry {
+                    title: url
+                        .title()
+                        .or_else(|| url.url_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    // text posts link their discussion
+                    link: url.url_str().unwrap_or(&discussion).to_string(),
+                    discussion,
+                    score: url.score(),
+                }
+            })
+            .collect();
+        let (subject, period) = match self.frequency {
+            DigestFrequency::Daily => ("Your daily digest", "day"),
+            _ => ("Your weekly digest", "week"),
+        };
+        let body = DigestEmail {
+            name: user.name(),
+            period,
+            entries: &entries,
+            host,
+            footer: UnsubscribeToken::email_footer(ctx, user.id(), EmailCategory::Digest),
+        }
+        .render()?;
+
+        let email = Message::builder()
+            .from("noreply@urls.fyi <noreply@urls.fyi>".parse().unwrap()) // TODO: Make configurable ...
+            .to(Mailbox::new(Some(user.name().to_string()), user.email()?))
+            .subject(subject)
+            .body(body)?;
+        ctx.mailer().send(email).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+use crate::db::id::{UrlID, UserID};
+use crate::db::models::{Block, Url, User};
+use crate::schema::{follows, tag_follows, url_tags, users};
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use juniper::GraphQLObject;
+
+/// A user followed by another user. Submissions of followed users
+/// are listed in the following feed of the follower. Follows and
+/// blocks are mutually exclusive, blocking a user removes the follows
+/// between both users.
+#[derive(Debug, Clone, Queryable, Insertable)]
+pub struct Follow {
+    user_id: UserID,
+    followed_user_id: UserID,
+    created_at: NaiveDateTime,
+}
+
+/// Why a submission appears in the following feed of a user. A
+/// submission can appear for several reasons, but is only listed
+/// once.
+#[derive(Debug, Clone, PartialEq, Eq, GraphQLObject)]
+pub struct FeedSource {
+    /// Whether the submitter is followed.
+    pub followed_user: bool,
+    /// Followed tags of the submission, ordered by name.
+    pub followed_tags: Vec<String>,
+}
+
+impl Follow {
+    /// Follow the given user as the currently logged in user.
+    /// Following an already followed user has no effect. Banned
+    /// users can not be followed, and neither can users who blocked
+    /// the currently logged in user or were blocked by them.
+    pub async fn create(ctx: &Context, followed_user_id: UserID) -> Result<()> {
+        let user_id = ctx.user_id()?;
+        if user_id == followed_user_id {
+            return Err(anyhow!("You can not follow yourself"));
+        }
+        let followed = User::find(ctx, followed_user_id).await?;
+        if followed.is_banned()
+            || Block::exists(ctx, user_id, followed.id()).await?
+            || Block::exists(ctx, followed.id(), user_id).await?
+        {
+            return Err(anyhow!("You can not follow this user"));
+        }
+        let follow = Follow {
+            user_id,
+            followed_user_id: followed.id(),
+            created_at: ctx.now().naive_utc(),
+        };
+        diesel::insert_or_ignore_into(follows::table)
+            .values(&follow)
+            .execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Unfollow the given user for the currently logged in user.
+    pub async fn delete(ctx: &Context, followed_user_id: UserID) -> Result<()> {
+        let follow = follows::table
+            .filter(follows::dsl::user_id.eq(ctx.user_id()?))
+            .filter(follows::dsl::followed_user_id.eq(followed_user_id));
+        diesel::delete(follow).execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Check if the user `user_id` follows the user `followed_user_id`.
+    pub async fn exists(ctx: &Context, user_id: UserID, followed_user_id: UserID) -> Result<bool> {
+        let count: i64 = follows::table
+            .filter(follows::dsl::user_id.eq(user_id))
+            .filter(follows::dsl::followed_user_id.eq(followed_user_id))
+            .select(diesel::dsl::count_star())
+            .get_result(&*ctx.conn().await?)?;
+        Ok(count > 0)
+    }
+
+    /// The number of users following the given user.
+    pub async fn follower_count(ctx: &Context, user_id: UserID) -> Result<i64> {
+        Ok(follows::table
+            .filter(follows::dsl::followed_user_id.eq(user_id))
+            .select(diesel::dsl::count_star())
+            .get_result(&*ctx.conn().await?)?)
+    }
+
+    /// The number of users the given user follows.
+    pub async fn following_count(ctx: &Context, user_id: UserID) -> Result<i64> {
+        Ok(follows::table
+            .filter(follows::dsl::user_id.eq(user_id))
+            .select(diesel::dsl::count_star())
+            .get_result(&*ctx.conn().await?)?)
+    }
+
+    /// Users following the given user, most recently followed
+    /// first, in a way that's suitable for use with a Relay
+    /// connection.
+    pub async fn followers(
+        ctx: &Context,
+        user_id: UserID,
+        after: Option<UserID>,
+        before: Option<UserID>,
+        limit: Option<i64>,
+    ) -> Result<Vec<User>> {
+        use follows::dsl::{created_at, followed_user_id};
+
+        let conn = ctx.conn().await?;
+        let mut query = users::table
+            .inner_join(follows::table.on(follows::dsl::user_id.eq(users::dsl::id)))
+            .filter(followed_user_id.eq(user_id))
+            .order_by(created_at.desc())
+            .then_order_by(users::dsl::id.desc())
+            .select(users::all_columns)
+            .into_boxed();
+
+        if let Some(after) = after {
+            let after: Self = follows::table.find((after, user_id)).get_result(&*conn)?;
+            query = query.filter(
+                created_at.lt(after.created_at).or(created_at
+                    .eq(after.created_at)
+                    .and(users::dsl::id.lt(after.user_id))),
+            );
+        }
+        if let Some(before) = before {
+            let before: Self = follows::table.find((before, user_id)).get_result(&*conn)?;
+            query = query.filter(
+                created_at.gt(before.created_at).or(created_at
+                    .eq(before.created_at)
+                    .and(users::dsl::id.gt(before.user_id))),
+            );
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        Ok(query.load(&*conn)?)
+    }
+
+    /// Users followed by the given user, most recently followed
+    /// first, in a way that's suitable for use with a Relay
+    /// connection.
+    pub async fn following(
+        ctx: &Context,
+        user_id: UserID,
+        after: Option<UserID>,
+        before: Option<UserID>,
+        limit: Option<i64>,
+    ) -> Result<Vec<User>> {
+        use follows::dsl::{created_at, followed_user_id};
+
+        let conn = ctx.conn().await?;
+        let mut query = users::table
+            .inner_join(follows::table.on(followed_user_id.eq(users::dsl::id)))
+            .filter(follows::dsl::user_id.eq(user_id))
+            .order_by(created_at.desc())
+            .then_order_by(users::dsl::id.desc())
+            .select(users::all_columns)
+            .into_boxed();
+
+        if let Some(after) = after {
+            let after: Self = follows::table.find((user_id, after)).get_result(&*conn)?;
+            query = query.filter(
+                created_at.lt(after.created_at).or(created_at
+                    .eq(after.created_at)
+                    .and(users::dsl::id.lt(after.followed_user_id))),
+            );
+        }
+        if let Some(before) = before {
+            let before: Self = follows::table.find((user_id, before)).get_result(&*conn)?;
+            query = query.filter(
+                created_at.gt(before.created_at).or(created_at
+                    .eq(before.created_at)
+                    .and(users::dsl::id.gt(before.followed_user_id))),
+            );
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        Ok(query.load(&*conn)?)
+    }
+
+    /// Why each of the given submissions appears in the following
+    /// feed of the given user, in the same order.
+    pub async fn feed_sources(
+        ctx: &Context,
+        user_id: UserID,
+        urls: &[Url],
+    ) -> Result<Vec<FeedSource>> {
+        let conn = ctx.conn().await?;
+        let followed: Vec<UserID> = follows::table
+            .filter(follows::dsl::user_id.eq(user_id))
+            .select(follows::dsl::followed_user_id)
+            .load(&*conn)?;
+        let url_ids: Vec<UrlID> = urls.iter().map(|url| url.id()).collect();
+        let tags = tag_follows::table
+            .filter(tag_follows::dsl::user_id.eq(user_id))
+            .select(tag_follows::dsl::tag_name);
+        let tagged: Vec<(UrlID, String)> = url_tags::table
+            .filter(url_tags::dsl::url_id.eq_any(url_ids))
+            .filter(url_tags::dsl::tag_name.eq_any(tags))
+            .order_by(url_tags::dsl::tag_name.asc())
+            .select((url_tags::dsl::url_id, url_tags::dsl::tag_name))
+            .load(&*conn)?;
+        Ok(urls
+            .iter()
+            .map(|url| FeedSource {
+                followed_user: !url.is_anonymous() && followed.contains(&url.created_by_id()),
+                followed_tags: tagged
+                    .iter()
+                    .filter(|(url_id, _)| *url_id == url.id())
+                    .map(|(_, tag)| tag.clone())
+                    .collect(),
+            })
+            .collect())
+    }
+}
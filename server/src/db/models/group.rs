@@ -0,0 +1,481 @@
+use crate::db::id::{GroupID, UserID};
+use crate::db::models::User;
+use crate::schema::{group_members, group_moderators, groups, users};
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::{Nullable, Text};
+use diesel::sqlite::Sqlite;
+use juniper::{GraphQLEnum, GraphQLInputObject};
+use std::io::Write;
+use validator::{Validate, ValidationError};
+
+const MIN_SLUG_LEN: usize = 2;
+const MAX_SLUG_LEN: usize = 30;
+
+/// Who can find a group.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum GroupVisibility {
+    /// The group is listed in the directory of groups.
+    Public,
+    /// Anyone who knows the slug of the group can find
+    /// it, but it is not listed anywhere, and its
+    /// submissions are never listed on the front page.
+    Unlisted,
+}
+
+/// A space for submissions on a topic, which users can join and
+/// submit to. Groups are moderated by their creator and the members
+/// they appoint, who may moderate the submissions to the group like
+/// site moderators. Submissions to a group are only listed on the
+/// front page if the group is public and syndicated.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset)]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct Group {
+    id: GroupID,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+
+    slug: String,
+    title: String,
+    description: Option<String>,
+    created_by: UserID,
+    visibility: GroupVisibility,
+    syndicated: bool,
+    member_count: i64,
+}
+
+/// A member of a group, who may submit to it.
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "group_members"]
+struct GroupMember {
+    group_id: GroupID,
+    user_id: UserID,
+    created_at: NaiveDateTime,
+}
+
+/// A member appointed to moderate a group. The creator
+/// of a group moderates it without being appointed.
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "group_moderators"]
+struct GroupModerator {
+    group_id: GroupID,
+    user_id: UserID,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Validate, GraphQLInputObject)]
+pub struct NewGroupInput {
+    /// The unique slug used to link to the group. Slugs are case
+    /// insensitive and can contain letters, digits, and dashes.
+    #[validate(custom(
+        function = "valid_slug",
+        message = "Slugs must have between 2 and 30 letters, digits, or dashes"
+    ))]
+    slug: String,
+    #[validate(length(min = 1, max = 256, message = "The title is too long"))]
+    title: String,
+    #[validate(length(min = 1, max = 2048, message = "The description is too long"))]
+    description: Option<String>,
+    /// Who can find the group, `PUBLIC` by default.
+    visibility: Option<GroupVisibility>,
+    /// Whether submissions to the group are listed on the
+    /// front page, which requires the group to be public.
+    syndicated: Option<bool>,
+}
+
+#[derive(Debug, Clone, Validate, GraphQLInputObject)]
+pub struct UpdateGroupInput {
+    #[validate(length(min = 1, max = 256, message = "The title is too long"))]
+    title: Option<String>,
+    /// Replaces the description of the group. An
+    /// empty description removes it.
+    #[validate(length(max = 2048, message = "The description is too long"))]
+    description: Option<String>,
+    visibility: Option<GroupVisibility>,
+    syndicated: Option<bool>,
+}
+
+/// Normalize a slug, such that slugs are case insensitive.
+fn normalize_slug(slug: &str) -> String {
+    slug.trim().to_ascii_lowercase()
+}
+
+fn valid_slug(slug: &str) -> Result<(), ValidationError> {
+    let valid_len = (MIN_SLUG_LEN..=MAX_SLUG_LEN).contains(&slug.len());
+    let valid_chars = slug
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid_len && valid_chars {
+        Ok(())
+    } else {
+        Err(ValidationError::new("slug"))
+    }
+}
+
+impl Group {
+    pub fn id(&self) -> GroupID {
+        self.id
+    }
+
+    pub fn slug(&self) -> &str {
+        &self.slug
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn visibility(&self) -> GroupVisibility {
+        self.visibility
+    }
+
+    /// Whether submissions to this group are listed
+    /// on the front page.
+    pub fn is_syndicated(&self) -> bool {
+        self.syndicated
+    }
+
+    pub fn member_count(&self) -> i64 {
+        self.member_count
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.updated_at, Utc)
+    }
+
+    pub async fn creator(&self, ctx: &Context) -> Result<User> {
+        User::find(ctx, self.created_by).await
+    }
+}
+
+impl Group {
+    pub async fn find(ctx: &Context, id: GroupID) -> Result<Self> {
+        Ok(groups::table
+            .find(id)
+            .get_result(&*ctx.conn().await?)
+            .optional()?
+            .ok_or_else(|| anyhow!("Group not found"))?)
+    }
+
+    /// Retrieve a group by its slug, if there is one. Slugs
+    /// are case insensitive.
+    pub async fn find_by_slug(ctx: &Context, slug: &str) -> Result<Option<Self>> {
+        Ok(groups::table
+            .filter(groups::dsl::slug.eq(normalize_slug(slug)))
+            .get_result(&*ctx.conn().await?)
+            .optional()?)
+    }
+
+    /// Public groups, newest first, in a way that's suitable
+    /// for use with a Relay connection.
+    pub async fn all(
+        ctx: &Context,
+        after: Option<GroupID>,
+        before: Option<GroupID>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        use groups::dsl;
+
+        let conn = ctx.conn().await?;
+        let mut query = groups::table
+            .filter(dsl::visibility.eq(GroupVisibility::Public))
+            .order_by(dsl::created_at.desc())
+            .then_order_by(dsl::id.desc())
+            .into_boxed();
+
+        if let Some(after) = after {
+            let after: Self = groups::table.find(after).get_result(&*conn)?;
+            query = query.filter(
+                dsl::created_at.lt(after.created_at).or(dsl::created_at
+                    .eq(after.created_at)
+                    .and(dsl::id.lt(after.id))),
+            );
+        }
+        if let Some(before) = before {
+            let before: Self = groups::table.find(before).get_result(&*conn)?;
+            query = query.filter(
+                dsl::created_at.gt(before.created_at).or(dsl::created_at
+                    .eq(before.created_at)
+                    .and(dsl::id.gt(before.id))),
+            );
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        Ok(query.load(&*conn)?)
+    }
+
+    /// IDs of the groups whose submissions are listed on the front
+    /// page, i.e. the public groups which are syndicated, as a subquery
+    /// on the group of submissions.
+    pub(crate) fn syndicated_ids() -> groups::BoxedQuery<'static, Sqlite, Nullable<Text>> {
+        groups::table
+            .filter(groups::dsl::visibility.eq(GroupVisibility::Public))
+            .filter(groups::dsl::syndicated.eq(true))
+            .select(groups::dsl::id.nullable())
+            .into_boxed()
+    }
+
+    /// Create a group as the currently logged in user, who
+    /// becomes its first member and moderates it.
+    pub async fn create(ctx: &Context, input: NewGroupInput) -> Result<Self> {
+        let user = ctx.verified_user().await?;
+        let input = NewGroupInput {
+            slug: normalize_slug(&input.slug),
+            title: input.title.trim().into(),
+            description: input.description.map(|desc| desc.trim().into()),
+            visibility: input.visibility,
+            syndicated: input.syndicated,
+        };
+        input.validate()?;
+        let visibility = input.visibility.unwrap_or(GroupVisibility::Public);
+        let syndicated = input.syndicated.unwrap_or(false);
+        if syndicated && visibility != GroupVisibility::Public {
+            return Err(anyhow!("Only public groups can be syndicated"));
+        }
+
+        let group = Group {
+            id: GroupID::new(),
+            created_at: ctx.now().naive_utc(),
+            updated_at: ctx.now().naive_utc(),
+
+            slug: input.slug,
+            title: input.title,
+            description: input.description,
+            created_by: user.id(),
+            visibility,
+            syndicated,
+            member_count: 1,
+        };
+        let member = GroupMember {
+            group_id: group.id,
+            user_id: user.id(),
+            created_at: ctx.now().naive_utc(),
+        };
+
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            let taken: i64 = groups::table
+                .filter(groups::dsl::slug.eq(&group.slug))
+                .count()
+                .get_result(&*conn)?;
+            if taken > 0 {
+                return Err(anyhow!("This slug is already taken"));
+            }
+            diesel::insert_into(groups::table)
+                .values(&group)
+                .execute(&*conn)?;
+            diesel::insert_into(group_members::table)
+                .values(&member)
+                .execute(&*conn)?;
+            Ok(())
+        })?;
+        Ok(group)
+    }
+
+    /// Update this group. This is only available to its creator,
+    /// and to administrators. Making a group unlisted stops
+    /// syndicating it.
+    pub async fn update(&mut self, ctx: &Context, input: UpdateGroupInput) -> Result<()> {
+        self.check_may_manage(ctx).await?;
+        let input = UpdateGroupInput {
+            title: input.title.map(|title| title.trim().into()),
+            description: input.description.map(|desc| desc.trim().into()),
+            visibility: input.visibility,
+            syndicated: input.syndicated,
+        };
+        input.validate()?;
+
+        if let Some(title) = input.title {
+            self.title = title;
+        }
+        if let Some(description) = input.description {
+            self.description = Some(description).filter(|desc| !desc.is_empty());
+        }
+        if let Some(visibility) = input.visibility {
+            self.visibility = visibility;
+            self.syndicated &= visibility == GroupVisibility::Public;
+        }
+        if let Some(syndicated) = input.syndicated {
+            if syndicated && self.visibility != GroupVisibility::Public {
+                return Err(anyhow!("Only public groups can be syndicated"));
+            }
+            self.syndicated = syndicated;
+        }
+        self.updated_at = ctx.now().naive_utc();
+        *self = self.save_changes(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Join this group as the currently logged in user. Joining
+    /// a group again has no effect.
+    pub async fn join(&mut self, ctx: &Context) -> Result<()> {
+        let user = ctx.verified_user().await?;
+        let member = GroupMember {
+            group_id: self.id,
+            user_id: user.id(),
+            created_at: ctx.now().naive_utc(),
+        };
+        let conn = ctx.conn().await?;
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            let joined = diesel::insert_or_ignore_into(group_members::table)
+                .values(&member)
+                .execute(&*conn)?;
+            diesel::update(&*self)
+                .set(groups::dsl::member_count.eq(groups::dsl::member_count + joined as i64))
+                .execute(&*conn)?;
+            Ok(groups::table.find(self.id).get_result(&*conn)?)
+        })?;
+        Ok(())
+    }
+
+    /// Leave this group as the currently logged in user, which also
+    /// ends their appointment as a moderator. The creator of a group
+    /// can not leave it. Leaving a group which the user is not a
+    /// member of has no effect.
+    pub async fn leave(&mut self, ctx: &Context) -> Result<()> {
+        let user_id = ctx.user_id()?;
+        if user_id == self.created_by {
+            return Err(anyhow!("The creator of a group can not leave it"));
+        }
+        let conn = ctx.conn().await?;
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            let left =
+                diesel::delete(group_members::table.find((self.id, user_id))).execute(&*conn)?;
+            diesel::delete(group_moderators::table.find((self.id, user_id))).execute(&*conn)?;
+            diesel::update(&*self)
+                .set(groups::dsl::member_count.eq(groups::dsl::member_count - left as i64))
+                .execute(&*conn)?;
+            Ok(groups::table.find(self.id).get_result(&*conn)?)
+        })?;
+        Ok(())
+    }
+
+    /// Check if the given user is a member of this group.
+    pub async fn is_member(&self, ctx: &Context, user_id: UserID) -> Result<bool> {
+        let count: i64 = group_members::table
+            .find((self.id, user_id))
+            .count()
+            .get_result(&*ctx.conn().await?)?;
+        Ok(count > 0)
+    }
+
+    /// Check if the given user moderates this group, i.e. created
+    /// it or was appointed as a moderator.
+    pub async fn is_moderator(&self, ctx: &Context, user_id: UserID) -> Result<bool> {
+        if user_id == self.created_by {
+            return Ok(true);
+        }
+        let count: i64 = group_moderators::table
+            .find((self.id, user_id))
+            .count()
+            .get_result(&*ctx.conn().await?)?;
+        Ok(count > 0)
+    }
+
+    /// The moderators of this group, its creator first, followed
+    /// by the appointed moderators in the order they were appointed.
+    pub async fn moderators(&self, ctx: &Context) -> Result<Vec<User>> {
+        let appointed: Vec<User> = users::table
+            .inner_join(group_moderators::table)
+            .filter(group_moderators::dsl::group_id.eq(self.id))
+            .order_by(group_moderators::dsl::created_at.asc())
+            .then_order_by(users::dsl::id.asc())
+            .select(users::all_columns)
+            .load(&*ctx.conn().await?)?;
+        let mut moderators = vec![self.creator(ctx).await?];
+        moderators.extend(appointed);
+        Ok(moderators)
+    }
+
+    /// Fail unless the currently logged in user created this
+    /// group, or may manage all groups.
+    async fn check_may_manage(&self, ctx: &Context) -> Result<()> {
+        let user = ctx.user().await?;
+        if user.id() == self.created_by {
+            return Ok(());
+        }
+        user.check_permissions(ctx, |perm| perm.manage_groups())
+            .await
+            .map_err(|_| anyhow!("Only the creator of a group can manage it"))
+    }
+
+    /// Appoint the given member as a moderator of this group. This
+    /// is only available to the creator of the group, and to
+    /// administrators. Appointing a moderator again has no effect.
+    pub async fn appoint_moderator(&self, ctx: &Context, user_id: UserID) -> Result<()> {
+        self.check_may_manage(ctx).await?;
+        if !self.is_member(ctx, user_id).await? {
+            return Err(anyhow!("Only members can moderate a group"));
+        }
+        if user_id == self.created_by {
+            return Ok(());
+        }
+        let moderator = GroupModerator {
+            group_id: self.id,
+            user_id,
+            created_at: ctx.now().naive_utc(),
+        };
+        diesel::insert_or_ignore_into(group_moderators::table)
+            .values(&moderator)
+            .execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Dismiss the given appointed moderator of this group, who stays
+    /// a member. This is only available to the creator of the group,
+    /// and to administrators. The creator can not be dismissed.
+    pub async fn dismiss_moderator(&self, ctx: &Context, user_id: UserID) -> Result<()> {
+        self.check_may_manage(ctx).await?;
+        if user_id == self.created_by {
+            return Err(anyhow!("The creator of a group can not be dismissed"));
+        }
+        diesel::delete(group_moderators::table.find((self.id, user_id)))
+            .execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+}
+
+impl<DB> ToSql<Text, DB> for GroupVisibility
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            GroupVisibility::Public => "public",
+            GroupVisibility::Unlisted => "unlisted",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for GroupVisibility
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "public" => Ok(GroupVisibility::Public),
+            "unlisted" => Ok(GroupVisibility::Unlisted),
+            _ => Err("Unrecognized group visibility".into()),
+        }
+    }
+}
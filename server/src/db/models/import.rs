@@ -0,0 +1,203 @@
+use crate::db::models::{tag, SavedUrl, Url};
+use crate::{bookmarks, canonical, link, Context};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use juniper::{GraphQLEnum, GraphQLObject};
+use std::collections::HashSet;
+
+/// Maximum size of an imported bookmarks file.
+pub const MAX_IMPORT_BYTES: usize = 5 * 1024 * 1024;
+/// Maximum number of bookmarks in a single import.
+pub const MAX_IMPORT_ITEMS: usize = 5000;
+/// Longest title kept for an imported bookmark.
+const MAX_TITLE_LEN: usize = 256;
+
+/// Determines what imported bookmarks become.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportVisibility {
+    /// Private saves in the reading list of the user. Links which
+    /// were not submitted yet are saved as drafts of the user.
+    Private,
+    /// Drafts of the user, which can be published later.
+    Draft,
+}
+
+/// The outcome of a bookmark import.
+#[derive(Debug, Clone, Default, GraphQLObject)]
+pub struct ImportReport {
+    /// Number of bookmarks which were saved, or
+    /// created as drafts.
+    pub imported: i32,
+    /// Number of bookmarks which were listed twice, or which
+    /// were already saved or submitted, and were skipped.
+    pub skipped_duplicates: i32,
+    /// Bookmarks which could not be imported, and why.
+    pub errors: Vec<String>,
+}
+
+/// A bookmark to import, from any source.
+pub(crate) struct ImportItem {
+    pub url: String,
+    pub title: Option<String>,
+    /// Tags as named by the source, which are
+    /// normalized when importing.
+    pub tags: Vec<String>,
+    pub read: bool,
+    pub added_at: Option<DateTime<Utc>>,
+}
+
+/// Derive the tags of a bookmark from the folders it is filed in,
+/// innermost folders first.
+fn folder_tags(folders: &[String]) -> Vec<String> {
+    valid_tags(&folders.iter().rev().cloned().collect::<Vec<_>>())
+}
+
+/// Normalize the given tag names. Names which are not valid tags
+/// are skipped, and at most [`tag::MAX_TAGS_PER_URL`] tags are kept.
+fn valid_tags(names: &[String]) -> Vec<String> {
+    let mut tags = vec![];
+    for name in names {
+        if let Ok(normalized) = tag::normalize(name) {
+            if !tags.contains(&normalized) && tags.len() < tag::MAX_TAGS_PER_URL {
+                tags.push(normalized);
+            }
+        }
+    }
+    tags
+}
+
+/// Import the bookmarks in the given export, in the Netscape bookmark
+/// file format, for the currently logged in user. Links are deduplicated
+/// by their canonical form, and tags, folder names, and creation dates of
+/// the bookmarks are kept as tags and save or creation times. Bookmarks which
+/// can not be imported are reported, and don't fail the import.
+pub async fn import_bookmarks(
+    ctx: &Context,
+    file: &[u8],
+    visibility: ImportVisibility,
+) -> Result<ImportReport> {
+    ctx.user_id()?;
+    if file.len() > MAX_IMPORT_BYTES {
+        return Err(anyhow!(
+            "Bookmark files can be at most {} MB",
+            MAX_IMPORT_BYTES / 1024 / 1024
+        ));
+    }
+    let bookmarks = bookmarks::parse(&String::from_utf8_lossy(file));
+    if bookmarks.len() > MAX_IMPORT_ITEMS {
+        return Err(anyhow!(
+            "Bookmark files can contain at most {} bookmarks",
+            MAX_IMPORT_ITEMS
+        ));
+    }
+
+    let mut report = ImportReport::default();
+    let mut seen = HashSet::new();
+    for bookmark in bookmarks {
+        let item = ImportItem {
+            tags: [bookmark.tags, folder_tags(&bookmark.folders)].concat(),
+            url: bookmark.url,
+            title: bookmark.title,
+            read: false,
+            added_at: bookmark.added_at,
+        };
+        import_item(ctx, item, visibility, &mut report, &mut seen).await?;
+    }
+    Ok(report)
+}
+
+/// Import a single bookmark for the currently logged in user, and
+/// record the outcome in the report. Bookmarks whose canonical form
+/// is in `seen` are skipped as duplicates. Only failures to access
+/// the database fail the import.
+pub(crate) async fn import_item(
+    ctx: &Context,
+    item: ImportItem,
+    visibility: ImportVisibility,
+    report: &mut ImportReport,
+    seen: &mut HashSet<String>,
+) -> Result<()> {
+    let user_id = ctx.user_id()?;
+    if let Err(err) = link::check(&item.url, ctx.config().allow_private_urls()) {
+        report.errors.push(format!("{}: {}", item.url, err));
+        return Ok(());
+    }
+    let canonical = match canonical::canonicalize(&item.url, ctx.config().tracking_params()) {
+        Ok(canonical) => canonical,
+        Err(err) => {
+            report.errors.push(format!("{}: {}", item.url, err));
+            return Ok(());
+        }
+    };
+    if !seen.insert(canonical) {
+        report.skipped_duplicates += 1;
+        return Ok(());
+    }
+
+    let added_at = item.added_at.unwrap_or_else(|| ctx.now());
+    let read_at = if item.read { Some(ctx.now()) } else { None };
+    let existing = Url::find_duplicate(ctx, &item.url).await?;
+    let imported = match (visibility, existing) {
+        (ImportVisibility::Private, Some(url)) if url.is_deleted() => {
+            report
+                .errors
+                .push(format!("{}: The submission was deleted", item.url));
+            return Ok(());
+        }
+        (ImportVisibility::Private, Some(url)) => {
+            SavedUrl::save_if_new(ctx, &url, added_at, read_at).await?
+        }
+        (ImportVisibility::Draft, Some(_)) => false,
+        (visibility, None) => {
+            let title = item
+                .title
+                .map(|title| title.chars().take(MAX_TITLE_LEN).collect());
+            let tags = valid_tags(&item.tags);
+            let url = match Url::create_draft(ctx, &item.url, title, tags, user_id, added_at).await
+            {
+                Ok(url) => url,
+                Err(err) => {
+                    report.errors.push(format!("{}: {}", item.url, err));
+                    return Ok(());
+                }
+            };
+            if visibility == ImportVisibility::Private {
+                SavedUrl::save_if_new(ctx, &url, added_at, read_at).await?;
+            }
+            true
+        }
+    };
+    if imported {
+        report.imported += 1;
+    } else {
+        report.skipped_duplicates += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folder_tags() {
+        let folders: Vec<String> = [
+            "Dev",
+            "Rust Lang",
+            "x",
+            "dev",
+            "Web",
+            "Tools",
+            "Reading",
+            "Later",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        assert_eq!(
+            folder_tags(&folders),
+            vec!["later", "reading", "tools", "web", "dev"]
+        );
+        assert_eq!(folder_tags(&folders[..3]), vec!["rust-lang", "dev"]);
+    }
+}
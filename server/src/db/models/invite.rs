@@ -1,13 +1,16 @@
 use crate::db::id::{InviteID, UserID};
 use crate::db::models::User;
+use crate::error::{AppError, RateLimited};
 use crate::schema::{invites, users};
 use crate::Context;
 use anyhow::{anyhow, Result};
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text, Timestamp};
+use juniper::GraphQLObject;
 use nanoid::nanoid;
 
-const MAX_INVITES_PER_USER: i64 = 3;
+const MAX_INVITES_PER_HOUR: i64 = 25;
 const TOKEN_ALPHABET: &[char] = &[
     '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i',
     'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B',
@@ -24,6 +27,24 @@ pub struct Invite {
     token: String,
     created_by: UserID,
     claimed_by: Option<UserID>,
+    revoked_at: Option<NaiveDateTime>,
+}
+
+/// The number of invitations a user may issue.
+#[derive(Debug, Clone, GraphQLObject)]
+pub struct InviteQuota {
+    /// Total number of invitations the user may have
+    /// issued at any time, or null if unlimited.
+    pub total: Option<i32>,
+    /// Number of issued invitations which were not
+    /// revoked.
+    pub used: i32,
+    /// Number of invitations the user may still issue,
+    /// or null if unlimited.
+    pub remaining: Option<i32>,
+    /// When the user will earn their next invitation, or
+    /// null if no more invitations are currently earned.
+    pub next_accrual_at: Option<DateTime<Utc>>,
 }
 
 impl Invite {
@@ -31,6 +52,11 @@ impl Invite {
         self.id
     }
 
+    /// ID of the user who issued this invite.
+    pub fn created_by_id(&self) -> UserID {
+        self.created_by
+    }
+
     pub fn token(&self) -> &str {
         &self.token
     }
@@ -43,6 +69,14 @@ impl Invite {
         DateTime::from_utc(self.updated_at, Utc)
     }
 
+    pub fn revoked_at(&self) -> Option<DateTime<Utc>> {
+        self.revoked_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
     /// Return the user who created this invitation.
     pub async fn created_by(&self, ctx: &Context) -> Result<User> {
         let user = users::table
@@ -65,21 +99,20 @@ impl Invite {
 impl Invite {
     /// Create a new invite issued by the given user.
     pub async fn create(ctx: &Context, created_by: &User) -> Result<Self> {
-        let total_invites_issued: i64 = invites::table
+        // Even users with unlimited invites can only issue a limited
+        // number of invites per hour, to slow down automated abuse.
+        let last_hour = ctx.now() - Duration::hours(1);
+        let invites_last_hour: Vec<NaiveDateTime> = invites::table
             .filter(invites::dsl::created_by.eq(created_by.id()))
-            .select(diesel::dsl::count_star())
-            .get_result(&*ctx.conn().await?)?;
-
-        if total_invites_issued >= MAX_INVITES_PER_USER {
-            created_by
-                .check_permissions(ctx, |perm| perm.unlimited_invites())
-                .await
-                .map_err(|_| {
-                    anyhow!(
-                        "This account is not allowed to issue more than {} invitations",
-                        MAX_INVITES_PER_USER
-                    )
-                })?;
+            .filter(invites::dsl::created_at.gt(last_hour.naive_utc()))
+            .order_by(invites::dsl::created_at.asc())
+            .select(invites::dsl::created_at)
+            .load(&*ctx.conn().await?)?;
+        if invites_last_hour.len() as i64 >= MAX_INVITES_PER_HOUR {
+            let oldest = invites_last_hour[invites_last_hour.len() - MAX_INVITES_PER_HOUR as usize];
+            let retry_after =
+                DateTime::<Utc>::from_utc(oldest, Utc) + Duration::hours(1) - ctx.now();
+            return Err(RateLimited::new("issue_invite", retry_after).into());
         }
 
         let invite = Invite {
@@ -90,10 +123,99 @@ impl Invite {
             token: nanoid!(32, TOKEN_ALPHABET),
             created_by: created_by.id(),
             claimed_by: None,
+            revoked_at: None,
         };
-        diesel::insert_into(invites::table)
-            .values(&invite)
-            .execute(&*ctx.conn().await?)?;
+
+        let conn = ctx.conn().await?;
+        match Self::quota(ctx, created_by).await?.total {
+            None => {
+                diesel::insert_into(invites::table)
+                    .values(&invite)
+                    .execute(&*conn)?;
+            }
+            Some(quota) => {
+                // The quota is checked as part of the insert, such
+                // that concurrent requests can not exceed it.
+                let inserted = diesel::sql_query(
+                    "INSERT INTO invites (id, created_at, updated_at, token, created_by) \
+                    SELECT ?, ?, ?, ?, ? \
+                    WHERE (SELECT COUNT(*) FROM invites \
+                        WHERE created_by = ? AND revoked_at IS NULL) < ?",
+                )
+                .bind::<Text, _>(invite.id.as_str())
+                .bind::<Timestamp, _>(invite.created_at)
+                .bind::<Timestamp, _>(invite.updated_at)
+                .bind::<Text, _>(&invite.token)
+                .bind::<Text, _>(invite.created_by.as_str())
+                .bind::<Text, _>(invite.created_by.as_str())
+                .bind::<BigInt, _>(i64::from(quota))
+                .execute(&*conn)?;
+                if inserted != 1 {
+                    let message = format!(
+                        "Invite quota exhausted, this account may not issue more than {} invitations",
+                        quota
+                    );
+                    return Err(AppError::PermissionDenied(message).into());
+                }
+            }
+        }
+        Ok(invite)
+    }
+
+    /// Compute the invitation quota of the given user. Invitations
+    /// earned since the last time the quota was computed are credited
+    /// to the user first.
+    pub async fn quota(ctx: &Context, user: &User) -> Result<InviteQuota> {
+        let used: i64 = invites::table
+            .filter(invites::dsl::created_by.eq(user.id()))
+            .filter(invites::dsl::revoked_at.is_null())
+            .select(diesel::dsl::count_star())
+            .get_result(&*ctx.conn().await?)?;
+        let mut total = match user.invite_quota(ctx).await? {
+            Some(total) => total,
+            None => {
+                return Ok(InviteQuota {
+                    total: None,
+                    used: used as i32,
+                    remaining: None,
+                    next_accrual_at: None,
+                })
+            }
+        };
+
+        let mut next_accrual_at = None;
+        if let Some(period) = ctx.config().invite_accrual() {
+            let bank = ctx.config().invite_bank();
+            let mut user = user.clone();
+            let (earned, accrued_at) = accrual(
+                total - used,
+                user.last_accrual_at(),
+                ctx.now(),
+                period,
+                bank,
+            );
+            if accrued_at != user.last_accrual_at() {
+                // if another request accrued concurrently, the
+                // reloaded user will reflect that accrual instead
+                user.accrue_invites(ctx, earned as i32, accrued_at).await?;
+                total = user.invite_quota(ctx).await?.unwrap_or(total + earned);
+            }
+            if total - used < bank {
+                next_accrual_at = Some(user.last_accrual_at() + period);
+            }
+        }
+
+        Ok(InviteQuota {
+            total: Some(total as i32),
+            used: used as i32,
+            remaining: Some((total - used).max(0) as i32),
+            next_accrual_at,
+        })
+    }
+
+    /// Load by ID.
+    pub async fn find(ctx: &Context, id: InviteID) -> Result<Self> {
+        let invite = invites::table.find(id).get_result(&*ctx.conn().await?)?;
         Ok(invite)
     }
 
@@ -101,14 +223,17 @@ impl Invite {
     pub async fn find_by_token(ctx: &Context, token: &str) -> Result<Self> {
         let invite = invites::table
             .filter(invites::dsl::token.eq(token))
-            .get_result(&*ctx.conn().await?)?;
-        Ok(invite)
+            .get_result(&*ctx.conn().await?)
+            .optional()?;
+        invite.ok_or_else(|| AppError::NotFound("This invitation does not exist".into()).into())
     }
 
     /// Claim this invite for the given user.
     pub async fn claim(&mut self, ctx: &Context, claimed_by: &User) -> Result<()> {
         if self.claimed_by.is_some() {
-            Err(anyhow!("This invitation is already claimed"))
+            Err(AppError::Conflict("This invitation is already claimed".into()).into())
+        } else if self.is_revoked() {
+            Err(AppError::Conflict("This invitation was revoked".into()).into())
         } else {
             self.claimed_by = Some(claimed_by.id());
             self.updated_at = ctx.now().naive_utc();
@@ -116,4 +241,86 @@ impl Invite {
             Ok(())
         }
     }
+
+    /// Revoke this invite, such that it can no longer be claimed.
+    /// Revoked invites do not count towards the invitation quota.
+    /// Only the user who issued an invite may revoke it.
+    pub async fn revoke(&mut self, ctx: &Context) -> Result<()> {
+        if self.created_by != ctx.user_id()? {
+            Err(anyhow!("Only the issuer may revoke an invitation"))
+        } else if self.claimed_by.is_some() {
+            Err(anyhow!("This invitation is already claimed"))
+        } else if self.is_revoked() {
+            Err(anyhow!("This invitation is already revoked"))
+        } else {
+            self.revoked_at = Some(ctx.now().naive_utc());
+            self.updated_at = ctx.now().naive_utc();
+            *self = self.save_changes(&*ctx.conn().await?)?;
+            Ok(())
+        }
+    }
+}
+
+/// Determine the number of invitations earned since `last_accrual_at`,
+/// given the number of currently `available` invitations. One invitation
+/// is earned per `period`, until `bank` invitations are available. Returns
+/// the number of earned invitations and the new accrual time.
+fn accrual(
+    available: i64,
+    last_accrual_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    period: Duration,
+    bank: i64,
+) -> (i64, DateTime<Utc>) {
+    let periods = (now - last_accrual_at).num_seconds() / period.num_seconds().max(1);
+    if available >= bank {
+        // Nothing is earned while the bank is full. The clock is reset
+        // (at most once per period), so the next invitation is earned
+        // about one period after the bank stops being full.
+        if periods > 0 {
+            (0, now)
+        } else {
+            (0, last_accrual_at)
+        }
+    } else {
+        let earned = periods.min(bank - available).max(0);
+        if available + earned >= bank {
+            (earned, now)
+        } else {
+            (earned, last_accrual_at + period * earned as i32)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_accrual() {
+        let start = Utc.ymd(2021, 9, 1).and_hms(12, 0, 0);
+        let week = Duration::days(7);
+
+        // nothing earned before a full period passed
+        let now = start + Duration::days(6);
+        assert_eq!(accrual(0, start, now, week, 5), (0, start));
+
+        // partial periods carry over
+        let now = start + Duration::days(15);
+        assert_eq!(
+            accrual(0, start, now, week, 5),
+            (2, start + Duration::days(14))
+        );
+
+        // earning stops once the bank is full
+        let now = start + Duration::days(70);
+        assert_eq!(accrual(3, start, now, week, 5), (2, now));
+
+        // a full bank resets the clock at most once per period
+        let now = start + Duration::days(3);
+        assert_eq!(accrual(5, start, now, week, 5), (0, start));
+        let now = start + Duration::days(8);
+        assert_eq!(accrual(6, start, now, week, 5), (0, now));
+    }
 }
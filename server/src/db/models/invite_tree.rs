@@ -0,0 +1,113 @@
+use crate::db::id::UserID;
+use crate::db::models::User;
+use crate::schema::users;
+use crate::Context;
+use anyhow::Result;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use std::collections::HashMap;
+
+/// Maximum number of levels below the root which
+/// are loaded for an invite tree.
+pub const MAX_INVITE_TREE_DEPTH: i32 = 10;
+
+/// A user together with the users who registered using
+/// one of their invitations, recursively. Since users are
+/// always invited by an existing user, the tree can not
+/// contain cycles.
+#[derive(Debug, Clone)]
+pub struct InviteTree {
+    user: User,
+    depth: i32,
+    invitee_count: i32,
+    invitees: Vec<InviteTree>,
+}
+
+impl InviteTree {
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    /// Number of levels this node is below the
+    /// root of the tree.
+    pub fn depth(&self) -> i32 {
+        self.depth
+    }
+
+    /// Number of users who were directly invited by this
+    /// user. This is accurate even if the invitees were
+    /// not loaded because of the depth limit.
+    pub fn invitee_count(&self) -> i32 {
+        self.invitee_count
+    }
+
+    pub fn invitees(&self) -> &[InviteTree] {
+        &self.invitees
+    }
+}
+
+impl InviteTree {
+    /// Load the invite tree rooted at the given user, up to
+    /// `depth` levels below the root. The depth is capped at
+    /// [`MAX_INVITE_TREE_DEPTH`](MAX_INVITE_TREE_DEPTH).
+    pub async fn load(ctx: &Context, root: UserID, depth: i32) -> Result<Self> {
+        let depth = depth.clamp(0, MAX_INVITE_TREE_DEPTH);
+        let conn = ctx.conn().await?;
+
+        let root: User = users::table.find(root).get_result(&*conn)?;
+        let mut levels: Vec<Vec<User>> = vec![vec![root]];
+        while levels.len() <= depth as usize {
+            let parents: Vec<UserID> = levels.last().unwrap().iter().map(User::id).collect();
+            let level: Vec<User> = users::table
+                .filter(users::dsl::invited_by.eq_any(parents))
+                .order_by(users::dsl::created_at.asc())
+                .load(&*conn)?;
+            if level.is_empty() {
+                break;
+            }
+            levels.push(level);
+        }
+
+        // invitees of the deepest loaded level are only counted
+        let frontier: Vec<UserID> = levels.last().unwrap().iter().map(User::id).collect();
+        let frontier_counts: HashMap<UserID, i32> = users::table
+            .filter(users::dsl::invited_by.eq_any(frontier))
+            .group_by(users::dsl::invited_by)
+            .select((users::dsl::invited_by, sql::<BigInt>("COUNT(*)")))
+            .load::<(Option<UserID>, i64)>(&*conn)?
+            .into_iter()
+            .filter_map(|(id, count)| id.map(|id| (id, count as i32)))
+            .collect();
+
+        // assemble the tree bottom up, grouping
+        // each level by the inviting user
+        let deepest = levels.len() - 1;
+        let mut invitees: HashMap<UserID, Vec<InviteTree>> = HashMap::new();
+        for (level, users) in levels.into_iter().enumerate().rev() {
+            let mut parents: HashMap<UserID, Vec<InviteTree>> = HashMap::new();
+            for user in users {
+                let children = invitees.remove(&user.id()).unwrap_or_default();
+                let invitee_count = if level == deepest {
+                    frontier_counts.get(&user.id()).copied().unwrap_or(0)
+                } else {
+                    children.len() as i32
+                };
+                let node = InviteTree {
+                    depth: level as i32,
+                    invitee_count,
+                    invitees: children,
+                    user,
+                };
+                if level == 0 {
+                    return Ok(node);
+                }
+                if let Some(parent) = node.user.invited_by_id() {
+                    parents.entry(parent).or_default().push(node);
+                }
+            }
+            invitees = parents;
+        }
+        unreachable!("The invite tree always contains the root")
+    }
+}
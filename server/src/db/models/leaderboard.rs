@@ -0,0 +1,225 @@
+use crate::db::models::User;
+use crate::schema::{user_preferences, users};
+use crate::Context;
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime};
+use diesel::expression::{AppearsOnTable, Expression, NonAggregate, SelectableExpression};
+use diesel::prelude::*;
+use diesel::query_builder::{AstPass, QueryFragment, QueryId};
+use diesel::sql_types::{BigInt, Timestamp};
+use diesel::sqlite::Sqlite;
+use juniper::GraphQLEnum;
+use std::fmt;
+use std::str::FromStr;
+
+/// The time range points on the leaderboard are counted in.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardPeriod {
+    /// Votes cast within the last day.
+    Day,
+    /// Votes cast within the last week.
+    Week,
+    /// Votes cast within the last 30 days.
+    Month,
+    /// All votes.
+    All,
+}
+
+impl LeaderboardPeriod {
+    /// Maximum age of counted votes, if any.
+    pub fn duration(&self) -> Option<Duration> {
+        match self {
+            LeaderboardPeriod::Day => Some(Duration::days(1)),
+            LeaderboardPeriod::Week => Some(Duration::weeks(1)),
+            LeaderboardPeriod::Month => Some(Duration::days(30)),
+            LeaderboardPeriod::All => None,
+        }
+    }
+}
+
+/// A user on the leaderboard, and the points they earned
+/// within the period of the leaderboard.
+#[derive(Debug, Clone)]
+pub struct LeaderboardEntry {
+    user: User,
+    points: i64,
+}
+
+/// Position of an entry on the leaderboard, which lists the
+/// users with the most points first, and users with the same
+/// number of points by their username.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaderboardCursor {
+    points: i64,
+    username: String,
+}
+
+impl fmt::Display for LeaderboardCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = format!("{}:{}", self.points, self.username);
+        write!(f, "{}", base64::encode_config(raw, base64::URL_SAFE_NO_PAD))
+    }
+}
+
+impl FromStr for LeaderboardCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid leaderboard cursor";
+        let raw = base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|_| ERR)?;
+        let raw = String::from_utf8(raw).map_err(|_| ERR)?;
+        let mut parts = raw.splitn(2, ':');
+        let points = parts.next().and_then(|p| p.parse().ok()).ok_or(ERR)?;
+        let username = parts.next().ok_or(ERR)?.to_string();
+        Ok(Self { points, username })
+    }
+}
+
+impl LeaderboardEntry {
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    /// Points the user earned within the period.
+    pub fn points(&self) -> i64 {
+        self.points
+    }
+
+    pub fn cursor(&self) -> LeaderboardCursor {
+        LeaderboardCursor {
+            points: self.points,
+            username: self.user.username().to_string(),
+        }
+    }
+}
+
+impl LeaderboardEntry {
+    /// The users who earned the most points within the given period,
+    /// in a way that's suitable for use with a Relay connection. Users
+    /// earn points for the votes other users cast on their submissions
+    /// and comments within the period, regardless of when the content
+    /// was posted. Like karma, upvotes and downvotes on submissions
+    /// count plus and minus the submission weight, votes on comments
+    /// count the comment weight, and anonymous submissions earn nothing.
+    /// Votes on their own content don't earn users points. Only users
+    /// with a positive number of points are listed, leaving out banned
+    /// users and those who opted out, see
+    /// [`UserPreferences::hide_from_leaderboard`](crate::db::models::UserPreferences::hide_from_leaderboard).
+    pub async fn list(
+        ctx: &Context,
+        period: LeaderboardPeriod,
+        after: Option<LeaderboardCursor>,
+        before: Option<LeaderboardCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        use users::dsl::{banned_at, id, username};
+
+        let points = Points {
+            since: period
+                .duration()
+                .map(|duration| (ctx.now() - duration).naive_utc()),
+            submission_weight: ctx.config().karma_submission_weight(),
+            comment_weight: ctx.config().karma_comment_weight(),
+        };
+        let hidden = user_preferences::table
+            .filter(user_preferences::dsl::hide_from_leaderboard.eq(true))
+            .select(user_preferences::dsl::user_id);
+        let conn = ctx.conn().await?;
+        let mut query = users::table
+            .filter(banned_at.is_null())
+            .filter(id.ne_all(hidden))
+            .filter(points.clone().gt(0))
+            .order_by(points.clone().desc())
+            .then_order_by(username.asc())
+            .select((users::all_columns, points.clone()))
+            .into_boxed();
+
+        if let Some(after) = after {
+            query = query.filter(
+                points.clone().lt(after.points).or(points
+                    .clone()
+                    .eq(after.points)
+                    .and(username.gt(after.username))),
+            );
+        }
+
+        if let Some(before) = before {
+            query = query.filter(
+                points.clone().gt(before.points).or(points
+                    .clone()
+                    .eq(before.points)
+                    .and(username.lt(before.username))),
+            );
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        let entries: Vec<(User, i64)> = query.load(&*conn)?;
+        Ok(entries
+            .into_iter()
+            .map(|(user, points)| Self { user, points })
+            .collect())
+    }
+}
+
+/// The points a user earned from votes cast since the given time,
+/// as described in [`LeaderboardEntry::list`], such that users can
+/// be ranked by their points as part of a single query.
+#[derive(Debug, Clone)]
+struct Points {
+    since: Option<NaiveDateTime>,
+    submission_weight: i64,
+    comment_weight: i64,
+}
+
+impl Expression for Points {
+    type SqlType = BigInt;
+}
+
+impl NonAggregate for Points {}
+
+impl AppearsOnTable<users::table> for Points {}
+
+impl SelectableExpression<users::table> for Points {}
+
+impl QueryId for Points {
+    type QueryId = ();
+
+    // the query depends on whether votes are limited to a period
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl Points {
+    /// Restrict the votes of the given table to those cast since
+    /// the start of the period, if there is one.
+    fn walk_since(&self, out: &mut AstPass<Sqlite>, table: &str) -> QueryResult<()> {
+        if let Some(since) = &self.since {
+            out.push_sql(&format!(" AND {}.created_at >= ", table));
+            out.push_bind_param::<Timestamp, _>(since)?;
+        }
+        Ok(())
+    }
+}
+
+impl QueryFragment<Sqlite> for Points {
+    fn walk_ast(&self, mut out: AstPass<Sqlite>) -> QueryResult<()> {
+        out.push_sql(
+            "(COALESCE((SELECT SUM(CASE url_upvotes.direction WHEN 'up' THEN 1 ELSE -1 END) \
+            FROM url_upvotes INNER JOIN urls ON urls.id = url_upvotes.url_id \
+            WHERE urls.created_by = users.id AND urls.anonymous = 0 \
+            AND url_upvotes.user_id != users.id",
+        );
+        self.walk_since(&mut out, "url_upvotes")?;
+        out.push_sql(&format!(
+            "), 0) * {} + (SELECT COUNT(*) \
+            FROM comment_votes INNER JOIN comments ON comments.id = comment_votes.comment_id \
+            WHERE comments.created_by = users.id AND comment_votes.user_id != users.id",
+            self.submission_weight
+        ));
+        self.walk_since(&mut out, "comment_votes")?;
+        out.push_sql(&format!(") * {})", self.comment_weight));
+        Ok(())
+    }
+}
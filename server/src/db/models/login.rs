@@ -1,16 +1,22 @@
 use crate::db::id::{LoginID, UserID};
-use crate::db::models::User;
+use crate::db::models::{SecurityEvent, SecurityEventKind, User};
+use crate::error::{AppError, RateLimited};
 use crate::schema::logins;
 use crate::Context;
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use diesel::prelude::*;
+use diesel::sqlite::Sqlite;
+use juniper::GraphQLObject;
 use nanoid::nanoid;
+use serde::Deserialize;
 use std::net::IpAddr;
 
 const LOGIN_LIMIT_PER_HOUR: i64 = 3;
 const LOGIN_VALID_MINUTES: i64 = 60;
 const WEB_SESSION_MAX_UNUSED_DAYS: i64 = 90;
+const LAST_USED_GRANULARITY_MINUTES: i64 = 5;
+const LOGIN_CODE_MAX_ATTEMPTS: i32 = 5;
 const EMAIL_TOKEN_ALPHABET: &[char] = &[
     '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i',
     'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B',
@@ -34,6 +40,52 @@ pub struct Login {
     last_user_agent: Option<String>,
     revoked: bool,
     last_remote_ip: Option<String>,
+    claimed_at: Option<NaiveDateTime>,
+    user_agent: Option<String>,
+    remote_ip: Option<String>,
+    failed_attempts: i32,
+}
+
+/// Approximate location of an IP address, as reported by the
+/// configured geo IP lookup service.
+#[derive(Debug, Clone, GraphQLObject, Deserialize)]
+pub struct LoginLocation {
+    ip_address: String,
+    country_code: Option<String>,
+    country_name: Option<String>,
+    city_name: Option<String>,
+}
+
+impl LoginLocation {
+    /// Look up the approximate location of the given IP address.
+    /// If no geo IP lookup service is configured, this returns
+    /// `None`.
+    pub async fn lookup(ctx: &Context, ip_addr: IpAddr) -> Result<Option<Self>> {
+        if let Some(api) = ctx.config().geoip_api() {
+            let location = ctx
+                .http_client()
+                .get(api)
+                .query(&[("ip", ip_addr.to_string())])
+                .send()
+                .await?
+                .json()
+                .await?;
+            Ok(Some(location))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// A human readable description of this location,
+    /// e.g. `London, United Kingdom`.
+    pub fn describe(&self) -> String {
+        match (&self.city_name, &self.country_name) {
+            (Some(city), Some(country)) => format!("{}, {}", city, country),
+            (None, Some(country)) => country.clone(),
+            (Some(city), None) => city.clone(),
+            (None, None) => "Unknown".to_string(),
+        }
+    }
 }
 
 impl Login {
@@ -57,6 +109,9 @@ impl Login {
         self.session_token.as_ref().map(|s| s.as_str())
     }
 
+    /// Last time this session was used. This is only
+    /// updated every few minutes, to avoid writing to
+    /// the database on every request.
     pub fn last_used(&self) -> DateTime<Utc> {
         DateTime::from_utc(self.last_used, Utc)
     }
@@ -65,10 +120,37 @@ impl Login {
         self.last_user_agent.as_ref().map(|s| s.as_str())
     }
 
+    /// The IP address which last used this session, if it
+    /// is known and was not hashed before being stored.
     pub fn last_remote_ip(&self) -> Option<IpAddr> {
         self.last_remote_ip.as_ref().and_then(|ip| ip.parse().ok())
     }
 
+    /// The stored form of the IP address which last used
+    /// this session. Depending on the configuration, this
+    /// may be truncated or hashed.
+    pub fn last_stored_remote_ip(&self) -> Option<&str> {
+        self.last_remote_ip.as_deref()
+    }
+
+    /// The time at which the login was claimed, and
+    /// the session created.
+    pub fn claimed_at(&self) -> Option<DateTime<Utc>> {
+        self.claimed_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    /// The user agent which created this session.
+    pub fn user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref()
+    }
+
+    /// The stored form of the IP address which created
+    /// this session. Depending on the configuration, this
+    /// may be truncated or hashed.
+    pub fn stored_remote_ip(&self) -> Option<&str> {
+        self.remote_ip.as_deref()
+    }
+
     pub fn is_valid(&self, now: DateTime<Utc>) -> bool {
         self.is_claimed()
             && !self.revoked
@@ -94,22 +176,25 @@ impl Login {
 impl Login {
     /// Creates a new login in the database. This will fail, if too
     /// many requests have been made within the last hour, this will fail.
+    /// The request is recorded as a security event together with the login.
     pub async fn create(ctx: &Context, user_id: UserID) -> Result<Self> {
         let conn = ctx.conn().await?;
 
         // Check there haven't been too many logins within the
         // last hour.
         let last_hour = ctx.now() - Duration::hours(1);
-        let num_logins_last_hour: i64 = logins::table
+        let logins_last_hour: Vec<NaiveDateTime> = logins::table
             .filter(logins::dsl::user_id.eq(user_id))
             .filter(logins::dsl::created_at.gt(last_hour.naive_utc()))
-            .count()
-            .get_result(&*conn)?;
-        if num_logins_last_hour >= LOGIN_LIMIT_PER_HOUR {
-            return Err(anyhow!(
-                "Exceeded login limit of {} per hour",
-                LOGIN_LIMIT_PER_HOUR,
-            ));
+            .order_by(logins::dsl::created_at.asc())
+            .select(logins::dsl::created_at)
+            .load(&*conn)?;
+        if logins_last_hour.len() as i64 >= LOGIN_LIMIT_PER_HOUR {
+            // the limit frees up once enough logins leave the window
+            let oldest = logins_last_hour[logins_last_hour.len() - LOGIN_LIMIT_PER_HOUR as usize];
+            let retry_after =
+                DateTime::<Utc>::from_utc(oldest, Utc) + Duration::hours(1) - ctx.now();
+            return Err(RateLimited::new("request_login", retry_after).into());
         }
 
         let login = Login {
@@ -123,14 +208,22 @@ impl Login {
             last_user_agent: None,
             revoked: false,
             last_remote_ip: None,
+            claimed_at: None,
+            user_agent: None,
+            remote_ip: None,
+            failed_attempts: 0,
 
             created_at: ctx.now().naive_utc(),
             updated_at: ctx.now().naive_utc(),
         };
 
-        diesel::insert_into(logins::table)
-            .values(&login)
-            .execute(&*conn)?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            diesel::insert_into(logins::table)
+                .values(&login)
+                .execute(&*conn)?;
+            SecurityEvent::record(ctx, &*conn, user_id, SecurityEventKind::LoginCodeRequested)?;
+            Ok(())
+        })?;
 
         Ok(login)
     }
@@ -143,28 +236,141 @@ impl Login {
         } else {
             self.revoked = true;
             self.updated_at = ctx.now().naive_utc();
-            *self = self.save_changes(&*ctx.conn().await?)?;
+            let conn = ctx.conn().await?;
+            *self = conn.transaction::<_, anyhow::Error, _>(|| {
+                let login = self.save_changes(&*conn)?;
+                SecurityEvent::record(
+                    ctx,
+                    &*conn,
+                    self.user_id,
+                    SecurityEventKind::SessionRevoked,
+                )?;
+                Ok(login)
+            })?;
             Ok(())
         }
     }
 
+    /// Revoke all active sessions and outstanding login codes of
+    /// the given user, except for the session of the current request.
+    /// Returns the number of revoked sessions.
+    pub async fn revoke_other_sessions(ctx: &Context, user_id: UserID) -> Result<i32> {
+        let conn = ctx.conn().await?;
+        let revoked = conn.transaction::<_, anyhow::Error, _>(|| {
+            Ok(Self::revoke_others(ctx, &*conn, user_id)?)
+        })?;
+        Ok(revoked as i32)
+    }
+
+    /// Like [`revoke_other_sessions`](Login::revoke_other_sessions), but
+    /// running on the given connection. This is meant to be called within
+    /// the transaction of the action which requires the revocation.
+    pub(crate) fn revoke_others<C>(ctx: &Context, conn: &C, user_id: UserID) -> QueryResult<usize>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let now = ctx.now().naive_utc();
+
+        let unused_since = ctx.now() - Duration::days(WEB_SESSION_MAX_UNUSED_DAYS);
+        let current = ctx.session_token().unwrap_or_default();
+        let revoked = diesel::update(logins::table)
+            .filter(logins::dsl::user_id.eq(user_id))
+            .filter(logins::dsl::claimed.eq(true))
+            .filter(logins::dsl::revoked.eq(false))
+            .filter(logins::dsl::last_used.ge(unused_since.naive_utc()))
+            .filter(logins::dsl::session_token.ne(current))
+            .set((
+                logins::dsl::revoked.eq(true),
+                logins::dsl::updated_at.eq(now),
+            ))
+            .execute(conn)?;
+
+        diesel::update(logins::table)
+            .filter(logins::dsl::user_id.eq(user_id))
+            .filter(logins::dsl::claimed.eq(false))
+            .filter(logins::dsl::revoked.eq(false))
+            .filter(logins::dsl::claim_until.ge(now))
+            .set((
+                logins::dsl::revoked.eq(true),
+                logins::dsl::updated_at.eq(now),
+            ))
+            .execute(conn)?;
+
+        if revoked > 0 {
+            SecurityEvent::record(ctx, conn, user_id, SecurityEventKind::SessionRevoked)?;
+        }
+        Ok(revoked)
+    }
+
+    /// Count a failed login attempt against all outstanding login codes of
+    /// the given user. Codes which reach the maximum number of failed
+    /// attempts are revoked, such that guessing a code is not feasible.
+    /// Both updates are done in the database, so concurrent attempts are
+    /// counted correctly. This is meant to be called within the transaction
+    /// recording the failed login.
+    pub(crate) fn record_failed_attempt<C>(
+        ctx: &Context,
+        conn: &C,
+        user_id: UserID,
+    ) -> QueryResult<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let now = ctx.now().naive_utc();
+        let outstanding = logins::table
+            .filter(logins::dsl::user_id.eq(user_id))
+            .filter(logins::dsl::claimed.eq(false))
+            .filter(logins::dsl::revoked.eq(false))
+            .filter(logins::dsl::claim_until.gt(now));
+
+        diesel::update(outstanding)
+            .set((
+                logins::dsl::failed_attempts.eq(logins::dsl::failed_attempts + 1),
+                logins::dsl::updated_at.eq(now),
+            ))
+            .execute(conn)?;
+        diesel::update(
+            outstanding.filter(logins::dsl::failed_attempts.ge(LOGIN_CODE_MAX_ATTEMPTS)),
+        )
+        .set(logins::dsl::revoked.eq(true))
+        .execute(conn)?;
+        Ok(())
+    }
+
     /// Claims the login token and returns a session token. The session can be
-    /// used to authenticate to the graphql API.
+    /// used to authenticate to the graphql API. The successful login is recorded
+    /// as a security event together with the new session.
     pub async fn claim(&mut self, ctx: &Context, email_token: &str) -> Result<String> {
         if self.is_claimed() {
-            Err(anyhow!("The login was already claimed"))
+            Err(AppError::Unauthenticated("The login was already claimed".into()).into())
+        } else if self.revoked {
+            Err(AppError::Unauthenticated("The login was revoked".into()).into())
         } else if self.claim_until() < ctx.now() {
-            Err(anyhow!("The login is expired"))
+            Err(AppError::Unauthenticated("The login is expired".into()).into())
         } else if self.email_token() != email_token {
-            Err(anyhow!("Invalid login token"))
+            Err(AppError::Unauthenticated("Invalid login token".into()).into())
         } else {
             let session_token = nanoid!(64);
             self.claimed = true;
             self.session_token = Some(session_token.clone());
             self.last_used = ctx.now().naive_utc();
+            self.last_user_agent = ctx.user_agent().map(str::to_string);
+            self.last_remote_ip = ctx.stored_remote_ip();
+            self.claimed_at = Some(ctx.now().naive_utc());
+            self.user_agent = ctx.user_agent().map(str::to_string);
+            self.remote_ip = ctx.stored_remote_ip();
             self.updated_at = ctx.now().naive_utc();
             let conn = ctx.conn().await?;
-            *self = self.save_changes(&*conn)?;
+            *self = conn.transaction::<_, anyhow::Error, _>(|| {
+                let login = self.save_changes(&*conn)?;
+                SecurityEvent::record(
+                    ctx,
+                    &*conn,
+                    self.user_id,
+                    SecurityEventKind::LoginSucceeded,
+                )?;
+                Ok(login)
+            })?;
             Ok(session_token)
         }
     }
@@ -173,6 +379,9 @@ impl Login {
     /// user session. This function would typically be called to construct
     /// a request context. If the session token is invalid, this returns an
     /// error.
+    ///
+    /// The last usage of the session is recorded at most once every
+    /// few minutes, to avoid writing to the database on every request.
     pub async fn use_session(ctx: &mut Context, session_token: &str) -> Result<()> {
         let conn = ctx.conn().await?;
         let mut login: Self = logins::table
@@ -181,11 +390,14 @@ impl Login {
         if !login.is_valid(ctx.now()) {
             Err(anyhow!("Invalid login session"))
         } else {
-            login.last_used = ctx.now().naive_utc();
-            login.last_user_agent = ctx.user_agent().map(str::to_string);
-            login.last_remote_ip = ctx.remote_ip_address().map(|ip| ip.to_string());
-            login.updated_at = ctx.now().naive_utc();
-            login.save_changes::<Login>(&*conn)?;
+            let touch_after = login.last_used() + Duration::minutes(LAST_USED_GRANULARITY_MINUTES);
+            if touch_after <= ctx.now() {
+                login.last_used = ctx.now().naive_utc();
+                login.last_user_agent = ctx.user_agent().map(str::to_string);
+                login.last_remote_ip = ctx.stored_remote_ip();
+                login.updated_at = ctx.now().naive_utc();
+                login.save_changes::<Login>(&*conn)?;
+            }
             drop(conn);
             ctx.set_logged_in_user(login.user_id, session_token.to_string());
             Ok(())
@@ -0,0 +1,79 @@
+use crate::db::id::{CommentID, UserID};
+use crate::db::models::User;
+use crate::schema::{comment_mentions, users};
+use crate::Context;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sqlite::Sqlite;
+
+/// A user mentioned as `@username` in the text of
+/// a comment, see [`mentions`](crate::mentions).
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "comment_mentions"]
+pub struct Mention {
+    comment_id: CommentID,
+    user_id: UserID,
+    created_at: NaiveDateTime,
+}
+
+impl Mention {
+    /// The existing users with the given lowercase usernames, as
+    /// returned by [`mentions::parse`](crate::mentions::parse).
+    pub(crate) async fn resolve(ctx: &Context, usernames: Vec<String>) -> Result<Vec<User>> {
+        if usernames.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(users::table
+            .filter(users::dsl::username.eq_any(usernames))
+            .load(&*ctx.conn().await?)?)
+    }
+
+    /// Users mentioned in the given comment, ordered by username.
+    pub async fn users(ctx: &Context, comment_id: CommentID) -> Result<Vec<User>> {
+        Ok(users::table
+            .inner_join(comment_mentions::table)
+            .filter(comment_mentions::dsl::comment_id.eq(comment_id))
+            .order_by(users::dsl::username.asc())
+            .select(users::all_columns)
+            .load(&*ctx.conn().await?)?)
+    }
+
+    /// IDs of the users mentioned in the given comment.
+    pub(crate) async fn user_ids(ctx: &Context, comment_id: CommentID) -> Result<Vec<UserID>> {
+        Ok(comment_mentions::table
+            .filter(comment_mentions::dsl::comment_id.eq(comment_id))
+            .select(comment_mentions::dsl::user_id)
+            .load(&*ctx.conn().await?)?)
+    }
+
+    /// Replace the stored mentions of the given comment with
+    /// the users in `mentioned`. Mentions which were already
+    /// stored keep their creation time.
+    pub(crate) fn replace<C>(
+        conn: &C,
+        comment_id: CommentID,
+        mentioned: &[User],
+        now: NaiveDateTime,
+    ) -> Result<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let ids: Vec<UserID> = mentioned.iter().map(User::id).collect();
+        let removed = comment_mentions::table
+            .filter(comment_mentions::dsl::comment_id.eq(comment_id))
+            .filter(comment_mentions::dsl::user_id.ne_all(ids.clone()));
+        diesel::delete(removed).execute(conn)?;
+        for user_id in ids {
+            let mention = Mention {
+                comment_id,
+                user_id,
+                created_at: now,
+            };
+            diesel::insert_or_ignore_into(comment_mentions::table)
+                .values(&mention)
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+}
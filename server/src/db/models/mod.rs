@@ -1,15 +1,78 @@
+mod block;
+mod bookmark_export;
+mod collection;
 mod comment;
+mod data_export;
+mod device;
+mod digest;
+mod follow;
+mod group;
+mod import;
 mod invite;
+mod invite_tree;
+mod leaderboard;
 mod login;
+mod mention;
+mod moderation_log;
+mod muted_domain;
+mod notification;
 mod permission;
+mod posting_limit;
+mod preferences;
+mod report;
+mod revision;
 mod role;
+mod saved_url;
+mod security_event;
+mod service_import;
+pub(crate) mod tag;
+mod tag_log;
+mod unsubscribe;
 mod url;
+mod url_embed;
+mod url_view;
 mod user;
+mod webhook;
 
-pub use comment::{Comment, NewCommentInput};
-pub use invite::Invite;
-pub use login::Login;
+pub use block::Block;
+pub use bookmark_export::{BookmarkExport, ExportFormat};
+pub use collection::{
+    Collection, CollectionItem, CollectionItemCursor, CollectionVisibility, NewCollectionInput,
+    UpdateCollectionInput,
+};
+pub use comment::{Comment, CommentCursor, CommentSort, NewCommentInput};
+pub use data_export::{DataExport, DataExportStatus};
+pub use device::KnownDevice;
+pub use digest::{Digest, DIGEST_SIZE};
+pub use follow::{FeedSource, Follow};
+pub use group::{Group, GroupVisibility, NewGroupInput, UpdateGroupInput};
+pub use import::{import_bookmarks, ImportReport, ImportVisibility};
+pub use invite::{Invite, InviteQuota};
+pub use invite_tree::InviteTree;
+pub use leaderboard::{LeaderboardCursor, LeaderboardEntry, LeaderboardPeriod};
+pub use login::{Login, LoginLocation};
+pub use mention::Mention;
+pub use moderation_log::{ModerationLog, ModerationLogCursor};
+pub use muted_domain::MutedDomain;
+pub use notification::{Notification, NotificationFilter, NotificationKind};
 pub use permission::Permission;
+pub use posting_limit::{PostingAction, RateLimit};
+pub use preferences::{DigestFrequency, FeedSort, PreferencesInput, ShowNsfw, UserPreferences};
+pub use report::{ModerationAction, Report, ReportCursor, ReportReason, ReportStatus, ReportedUrl};
+pub use revision::{Revision, RevisionCursor};
 pub use role::Role;
-pub use url::{NewUrlInput, Url, UrlOrdering};
-pub use user::{NewUserInput, UpdateUserInput, User};
+pub use saved_url::{SavedCounts, SavedStatus, SavedUrl, SavedUrlCursor};
+pub use security_event::{SecurityEvent, SecurityEventKind};
+pub use service_import::{ImportService, ServiceImport, ServiceImportStatus};
+pub use tag::{Tag, TagCursor, TagSort, TagSuggestion};
+pub use tag_log::{TagLog, TagLogCursor};
+pub use unsubscribe::{EmailCategory, UnsubscribeToken};
+pub use url::{
+    hot_rank, ArchiveStatus, LinkStatus, Listed, MetadataStatus, NewUrlInput, ProfileCursor,
+    SubmissionKind, SubmissionVisibility, SubmitUrlResult, TopRange, UpdateUrlInput, Url, UrlCheck,
+    UrlCursor, UrlFilter, UrlOrdering, UrlSort, VoteDirection,
+};
+pub use url_embed::UrlEmbed;
+pub use url_view::UrlView;
+pub use user::{FeedUrls, NewUserInput, UpdateUserInput, User};
+pub use webhook::{Webhook, WebhookDelivery, WebhookDeliveryStatus, WebhookEvent};
@@ -0,0 +1,147 @@
+use crate::db::id::{ModerationLogID, UrlID, UserID};
+use crate::db::models::{Url, User};
+use crate::schema::moderation_log;
+use crate::Context;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// An entry in the moderation log, recording which moderator removed
+/// a submission and why. Entries are only visible to administrators.
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "moderation_log"]
+pub struct ModerationLog {
+    id: ModerationLogID,
+    created_at: NaiveDateTime,
+    moderator_id: UserID,
+    url_id: UrlID,
+    shadow: bool,
+    reason: String,
+}
+
+/// Position of an entry in the moderation log, which
+/// lists the most recent entries first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModerationLogCursor {
+    created_at: NaiveDateTime,
+    id: ModerationLogID,
+}
+
+impl fmt::Display for ModerationLogCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = format!("{}:{}", self.created_at.timestamp_nanos(), self.id);
+        write!(f, "{}", base64::encode_config(raw, base64::URL_SAFE_NO_PAD))
+    }
+}
+
+impl FromStr for ModerationLogCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid moderation log cursor";
+        let raw = base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|_| ERR)?;
+        let raw = String::from_utf8(raw).map_err(|_| ERR)?;
+        let mut parts = raw.splitn(2, ':');
+        let nanos: i64 = parts.next().and_then(|t| t.parse().ok()).ok_or(ERR)?;
+        let id = parts.next().and_then(|id| id.parse().ok()).ok_or(ERR)?;
+        let created_at = NaiveDateTime::from_timestamp_opt(
+            nanos.div_euclid(1_000_000_000),
+            nanos.rem_euclid(1_000_000_000) as u32,
+        )
+        .ok_or(ERR)?;
+        Ok(Self { created_at, id })
+    }
+}
+
+impl ModerationLog {
+    pub(crate) fn new(
+        moderator_id: UserID,
+        url_id: UrlID,
+        shadow: bool,
+        reason: &str,
+        now: NaiveDateTime,
+    ) -> Self {
+        Self {
+            id: ModerationLogID::new(),
+            created_at: now,
+            moderator_id,
+            url_id,
+            shadow,
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    /// Whether the submission was shadow removed, i.e. only
+    /// hidden from users other than its author.
+    pub fn shadow(&self) -> bool {
+        self.shadow
+    }
+
+    /// The reason the moderator gave for the removal.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    pub async fn moderator(&self, ctx: &Context) -> Result<User> {
+        User::find(ctx, self.moderator_id).await
+    }
+
+    pub async fn url(&self, ctx: &Context) -> Result<Url> {
+        Url::find(ctx, self.url_id).await
+    }
+
+    pub fn cursor(&self) -> ModerationLogCursor {
+        ModerationLogCursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
+impl ModerationLog {
+    /// Returns the moderation log, most recent entries first, in a way
+    /// that's suitable for use with a Relay connection. This is only
+    /// available to administrators.
+    pub async fn all(
+        ctx: &Context,
+        after: Option<ModerationLogCursor>,
+        before: Option<ModerationLogCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        use moderation_log::dsl::{created_at, id};
+
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.view_moderation_log())
+            .await?;
+        let conn = ctx.conn().await?;
+        let mut query = moderation_log::table
+            .order_by(created_at.desc())
+            .then_order_by(id.desc())
+            .into_boxed();
+        if let Some(after) = after {
+            query = query.filter(
+                created_at
+                    .lt(after.created_at)
+                    .or(created_at.eq(after.created_at).and(id.lt(after.id))),
+            );
+        }
+        if let Some(before) = before {
+            query = query.filter(
+                created_at
+                    .gt(before.created_at)
+                    .or(created_at.eq(before.created_at).and(id.gt(before.id))),
+            );
+        }
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        Ok(query.load(&*conn)?)
+    }
+}
@@ -0,0 +1,75 @@
+use crate::db::id::UserID;
+use crate::domain;
+use crate::schema::muted_domains;
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{Nullable, Text};
+use diesel::sqlite::Sqlite;
+
+/// A domain muted by a user. Submissions from muted domains are
+/// hidden from the front page, tag listings, and search results of
+/// the muting user, but remain reachable by direct link.
+#[derive(Debug, Clone, Queryable, Insertable)]
+pub struct MutedDomain {
+    user_id: UserID,
+    domain: String,
+    created_at: NaiveDateTime,
+}
+
+/// Normalize the given domain, host name, or URL to the registrable
+/// domain it belongs to, the same way as [`Url::domain`](crate::db::models::Url::domain).
+fn normalize(input: &str) -> Result<String> {
+    domain::normalize(input).ok_or_else(|| anyhow!("Please enter a valid domain"))
+}
+
+impl MutedDomain {
+    /// Mute the registrable domain of the given host name or URL for
+    /// the currently logged in user, returning the muted domain.
+    /// Muting an already muted domain has no effect.
+    pub async fn create(ctx: &Context, input: &str) -> Result<String> {
+        let muted = MutedDomain {
+            user_id: ctx.user_id()?,
+            domain: normalize(input)?,
+            created_at: ctx.now().naive_utc(),
+        };
+        diesel::insert_or_ignore_into(muted_domains::table)
+            .values(&muted)
+            .execute(&*ctx.conn().await?)?;
+        Ok(muted.domain)
+    }
+
+    /// Unmute the registrable domain of the given host name
+    /// or URL for the currently logged in user.
+    pub async fn delete(ctx: &Context, input: &str) -> Result<()> {
+        let muted = muted_domains::table
+            .filter(muted_domains::dsl::user_id.eq(ctx.user_id()?))
+            .filter(muted_domains::dsl::domain.eq(normalize(input)?));
+        diesel::delete(muted).execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Domains muted by the given user, whose submissions should be
+    /// excluded from their listings, as a subquery on the domain of
+    /// submissions.
+    pub fn hidden_domains(
+        user_id: UserID,
+    ) -> muted_domains::BoxedQuery<'static, Sqlite, Nullable<Text>> {
+        muted_domains::table
+            .filter(muted_domains::dsl::user_id.eq(user_id))
+            .select(muted_domains::dsl::domain.nullable())
+            .into_boxed()
+    }
+
+    /// Domains muted by the given user in their ASCII
+    /// form, most recently muted first.
+    pub async fn muted_by(ctx: &Context, user_id: UserID) -> Result<Vec<String>> {
+        Ok(muted_domains::table
+            .filter(muted_domains::dsl::user_id.eq(user_id))
+            .order_by(muted_domains::dsl::created_at.desc())
+            .then_order_by(muted_domains::dsl::domain.asc())
+            .select(muted_domains::dsl::domain)
+            .load(&*ctx.conn().await?)?)
+    }
+}
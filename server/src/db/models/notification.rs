@@ -0,0 +1,289 @@
+use crate::db::id::{CommentID, NotificationID, UrlID, UserID};
+use crate::db::models::{Block, Comment, Url, User};
+use crate::schema::notifications;
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::Text;
+use juniper::GraphQLEnum;
+use std::io::Write;
+
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum NotificationKind {
+    /// Someone commented on a submission of the user.
+    SubmissionReply,
+    /// Someone replied to a comment of the user.
+    CommentReply,
+    /// Someone mentioned the user in a comment.
+    Mention,
+}
+
+/// Determines which notifications are listed.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationFilter {
+    /// Notifications which were not marked as read yet.
+    Unread,
+    /// All notifications, read or not.
+    All,
+}
+
+/// A notification informing a user about a comment by
+/// another user `actor`, which replied to one of their
+/// submissions or comments, or mentioned them.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, Associations)]
+#[belongs_to(User)]
+pub struct Notification {
+    id: NotificationID,
+    created_at: NaiveDateTime,
+
+    user_id: UserID,
+    kind: NotificationKind,
+    actor_id: UserID,
+    url_id: UrlID,
+    comment_id: CommentID,
+    read_at: Option<NaiveDateTime>,
+}
+
+impl Notification {
+    pub fn id(&self) -> NotificationID {
+        self.id
+    }
+
+    pub fn kind(&self) -> NotificationKind {
+        self.kind
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    pub fn read_at(&self) -> Option<DateTime<Utc>> {
+        self.read_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    pub fn is_read(&self) -> bool {
+        self.read_at.is_some()
+    }
+
+    /// The user whose comment caused this notification.
+    pub async fn actor(&self, ctx: &Context) -> Result<User> {
+        Ok(User::find(ctx, self.actor_id).await?)
+    }
+
+    /// The submission which was commented on.
+    pub async fn url(&self, ctx: &Context) -> Result<Url> {
+        Ok(Url::find(ctx, self.url_id).await?)
+    }
+
+    /// The comment which caused this notification.
+    pub async fn comment(&self, ctx: &Context) -> Result<Comment> {
+        Ok(Comment::find(ctx, self.comment_id).await?)
+    }
+}
+
+impl Notification {
+    /// Find a notification of the currently logged in user.
+    pub async fn find(ctx: &Context, id: NotificationID) -> Result<Self> {
+        notifications::table
+            .find(id)
+            .filter(notifications::dsl::user_id.eq(ctx.user_id()?))
+            .get_result(&*ctx.conn().await?)
+            .optional()?
+            .ok_or_else(|| anyhow!("Notification not found"))
+    }
+
+    /// Notifications of the given user, newest first. Notifications
+    /// caused by users which are blocked by the currently logged in
+    /// user are not listed.
+    pub async fn list(
+        ctx: &Context,
+        user_id: UserID,
+        filter: NotificationFilter,
+        after: Option<NotificationID>,
+        before: Option<NotificationID>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        use notifications::dsl;
+
+        let conn = ctx.conn().await?;
+        let mut query = notifications::table
+            .filter(dsl::user_id.eq(user_id))
+            .order_by(dsl::created_at.desc())
+            .then_order_by(dsl::id.desc())
+            .into_boxed();
+
+        if let Some(viewer) = ctx.maybe_user_id() {
+            query = query.filter(dsl::actor_id.ne_all(Block::hidden_authors(viewer)));
+        }
+
+        if filter == NotificationFilter::Unread {
+            query = query.filter(dsl::read_at.is_null());
+        }
+
+        if let Some(after) = after {
+            let after: Self = notifications::table.find(after).get_result(&*conn)?;
+            query = query.filter(
+                dsl::created_at.lt(after.created_at).or(dsl::created_at
+                    .eq(after.created_at)
+                    .and(dsl::id.lt(after.id))),
+            );
+        }
+        if let Some(before) = before {
+            let before: Self = notifications::table.find(before).get_result(&*conn)?;
+            query = query.filter(
+                dsl::created_at.gt(before.created_at).or(dsl::created_at
+                    .eq(before.created_at)
+                    .and(dsl::id.gt(before.id))),
+            );
+        }
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        Ok(query.load(&*conn)?)
+    }
+
+    /// The number of unread notifications listed for the given user.
+    pub async fn unread_count(ctx: &Context, user_id: UserID) -> Result<i64> {
+        let conn = ctx.conn().await?;
+        let mut query = notifications::table
+            .filter(notifications::dsl::user_id.eq(user_id))
+            .filter(notifications::dsl::read_at.is_null())
+            .select(diesel::dsl::count_star())
+            .into_boxed();
+        if let Some(viewer) = ctx.maybe_user_id() {
+            query =
+                query.filter(notifications::dsl::actor_id.ne_all(Block::hidden_authors(viewer)));
+        }
+        Ok(query.get_result(&*conn)?)
+    }
+
+    /// The notifications caused by the new `comment` on `url`, which
+    /// may reply to the comment `parent`, and mention the users in
+    /// `mentioned`. The author of the submission, the author of the
+    /// parent comment, and the mentioned users are notified once each,
+    /// where replying to a comment takes precedence over replying to a
+    /// submission, which takes precedence over mentions. Authors are
+    /// never notified about their own comments, or comments of users
+    /// they blocked.
+    pub(crate) async fn for_comment(
+        ctx: &Context,
+        comment: &Comment,
+        url: &Url,
+        parent: Option<&Comment>,
+        mentioned: &[UserID],
+    ) -> Result<Vec<Self>> {
+        let mut recipients = vec![];
+        if let Some(parent) = parent {
+            recipients.push((parent.created_by_id(), NotificationKind::CommentReply));
+        }
+        recipients.push((url.created_by_id(), NotificationKind::SubmissionReply));
+        for user_id in mentioned {
+            recipients.push((*user_id, NotificationKind::Mention));
+        }
+        Self::for_recipients(ctx, comment, recipients).await
+    }
+
+    /// The notifications for users newly mentioned in the edited
+    /// `comment`, with the same rules as for new comments.
+    pub(crate) async fn for_mentions(
+        ctx: &Context,
+        comment: &Comment,
+        mentioned: &[UserID],
+    ) -> Result<Vec<Self>> {
+        let recipients = mentioned
+            .iter()
+            .map(|user_id| (*user_id, NotificationKind::Mention))
+            .collect();
+        Self::for_recipients(ctx, comment, recipients).await
+    }
+
+    async fn for_recipients(
+        ctx: &Context,
+        comment: &Comment,
+        recipients: Vec<(UserID, NotificationKind)>,
+    ) -> Result<Vec<Self>> {
+        let actor_id = comment.created_by_id();
+        let mut notifications: Vec<Self> = vec![];
+        for (user_id, kind) in recipients {
+            if user_id == actor_id
+                || notifications.iter().any(|other| other.user_id == user_id)
+                || Block::exists(ctx, user_id, actor_id).await?
+            {
+                continue;
+            }
+            notifications.push(Notification {
+                id: NotificationID::new(),
+                created_at: ctx.now().naive_utc(),
+
+                user_id,
+                kind,
+                actor_id,
+                url_id: comment.url_id(),
+                comment_id: comment.id(),
+                read_at: None,
+            });
+        }
+        Ok(notifications)
+    }
+
+    /// Mark this notification as read. Notifications
+    /// which were already read keep their read time.
+    pub async fn mark_read(&mut self, ctx: &Context) -> Result<()> {
+        if self.read_at.is_none() {
+            let now = ctx.now().naive_utc();
+            diesel::update(&*self)
+                .set(notifications::dsl::read_at.eq(now))
+                .execute(&*ctx.conn().await?)?;
+            self.read_at = Some(now);
+        }
+        Ok(())
+    }
+
+    /// Mark all notifications of the currently
+    /// logged in user as read.
+    pub async fn mark_all_read(ctx: &Context) -> Result<()> {
+        let unread = notifications::table
+            .filter(notifications::dsl::user_id.eq(ctx.user_id()?))
+            .filter(notifications::dsl::read_at.is_null());
+        diesel::update(unread)
+            .set(notifications::dsl::read_at.eq(ctx.now().naive_utc()))
+            .execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+}
+
+impl<DB> ToSql<Text, DB> for NotificationKind
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            NotificationKind::SubmissionReply => "submission_reply",
+            NotificationKind::CommentReply => "comment_reply",
+            NotificationKind::Mention => "mention",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for NotificationKind
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "submission_reply" => Ok(NotificationKind::SubmissionReply),
+            "comment_reply" => Ok(NotificationKind::CommentReply),
+            "mention" => Ok(NotificationKind::Mention),
+            _ => Err("Unrecognized notification kind".into()),
+        }
+    }
+}
@@ -22,6 +22,16 @@ impl Permission {
         }
     }
 
+    /// Determine if this permission grants the ability to
+    /// change the invitation quota of other users, or grant
+    /// them additional invitations.
+    pub fn modify_invite_quotas(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
     /// Determine if this permission grants the ability to
     /// create or delete user roles.
     pub fn modify_user_roles(&self) -> bool {
@@ -49,6 +59,179 @@ impl Permission {
         }
     }
 
+    /// Determine if this permission grants the ability to
+    /// see when other users logged in.
+    pub fn view_login_activity(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// trace who invited whom beyond ones own invitees.
+    pub fn view_invite_tree(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => true,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// restore deleted URLs.
+    pub fn restore_urls(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// edit any submitted URL, at any time.
+    pub fn edit_any_url(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// edit any comment, at any time.
+    pub fn edit_any_comment(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// ban and unban users.
+    pub fn ban_users(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => true,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// review and resolve reported submissions.
+    pub fn review_reports(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => true,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// remove or shadow remove submissions of other users.
+    pub fn remove_urls(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => true,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// lock and unlock the discussions of submissions.
+    pub fn lock_urls(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => true,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// submit and comment without being rate limited.
+    pub fn unlimited_posting(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// comment, vote and edit in locked discussions.
+    pub fn bypass_url_locks(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// see which moderator removed which submissions.
+    pub fn view_moderation_log(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// rename and merge tags, and see the log of both.
+    pub fn manage_tags(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// see the edit history of submissions and comments by
+    /// other users.
+    pub fn view_revisions(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// see who voted for submissions by other users.
+    pub fn view_voters(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// execute GraphQL queries which are not persisted, when
+    /// only persisted queries are allowed.
+    pub fn ad_hoc_queries(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// see who submitted anonymous submissions.
+    pub fn view_anonymous_submitters(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// check the links of submissions on demand.
+    pub fn recheck_urls(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// lift login locks on other accounts.
+    pub fn unlock_accounts(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
     /// Determine if this permission grants the ability to
     /// access database backups.
     pub fn access_admin_backups(&self) -> bool {
@@ -57,6 +240,26 @@ impl Permission {
             Permission::Moderator => false,
         }
     }
+
+    /// Determine if this permission grants the ability to
+    /// manage webhooks which receive events from the whole
+    /// site.
+    pub fn manage_site_webhooks(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
+
+    /// Determine if this permission grants the ability to
+    /// change any group and appoint its moderators, as if
+    /// having created it.
+    pub fn manage_groups(&self) -> bool {
+        match *self {
+            Permission::Administrator => true,
+            Permission::Moderator => false,
+        }
+    }
 }
 
 impl<DB> ToSql<Text, DB> for Permission
@@ -0,0 +1,204 @@
+use crate::db::models::User;
+use crate::error::RateLimited;
+use crate::schema::{comments, urls};
+use crate::Context;
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use juniper::GraphQLObject;
+
+/// Length of the sliding window posts are counted in.
+const WINDOW_HOURS: i64 = 1;
+
+/// Rate limited ways of posting to the site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostingAction {
+    SubmitUrl,
+    AddComment,
+}
+
+impl PostingAction {
+    /// The scope of the [`RateLimited`] errors returned
+    /// when exceeding the limit of this action.
+    pub fn scope(&self) -> &'static str {
+        match self {
+            PostingAction::SubmitUrl => "submit_url",
+            PostingAction::AddComment => "add_comment",
+        }
+    }
+}
+
+/// How often a user may still post within the current window.
+#[derive(Debug, Clone, GraphQLObject)]
+pub struct RateLimit {
+    /// The limited action, matching the `scope` of the
+    /// `RATE_LIMITED` errors returned when exceeding the
+    /// limit, i.e. `submit_url` or `add_comment`.
+    pub scope: String,
+    /// Number of times the action may be taken within
+    /// the window.
+    pub limit: i32,
+    /// Length of the sliding window in seconds.
+    pub window: i32,
+    /// Number of times the action may still be taken
+    /// right now.
+    pub remaining: i32,
+    /// Number of seconds until the action may be taken
+    /// again, or null if it may be taken right now.
+    pub retry_after: Option<i32>,
+}
+
+impl RateLimit {
+    /// The limits on how many urls and comments the given user may
+    /// post. Accounts younger than
+    /// [`Config::rate_limit_new_account_age`](crate::Config::rate_limit_new_account_age)
+    /// have lower limits, and administrators are not limited at all,
+    /// in which case the list is empty.
+    pub async fn all(ctx: &Context, user: &User) -> Result<Vec<Self>> {
+        let mut limits = vec![];
+        for &action in &[PostingAction::SubmitUrl, PostingAction::AddComment] {
+            if let Some((limit, times)) = usage(ctx, user, action).await? {
+                let window = Duration::hours(WINDOW_HOURS);
+                let (remaining, retry_after) = tally(&times, limit, window, ctx.now());
+                limits.push(RateLimit {
+                    scope: action.scope().to_string(),
+                    limit: limit as i32,
+                    window: window.num_seconds() as i32,
+                    remaining: remaining as i32,
+                    retry_after: retry_after.map(|retry_after| {
+                        RateLimited::new(action.scope(), retry_after).retry_after_secs() as i32
+                    }),
+                });
+            }
+        }
+        Ok(limits)
+    }
+
+    /// Fail with [`RateLimited`] if the given user exceeded the limit
+    /// of the given action. Posts count against their author even when
+    /// they are posted anonymously, or were deleted since.
+    pub async fn check(ctx: &Context, user: &User, action: PostingAction) -> Result<()> {
+        if let Some((limit, times)) = usage(ctx, user, action).await? {
+            let window = Duration::hours(WINDOW_HOURS);
+            if let (_, Some(retry_after)) = tally(&times, limit, window, ctx.now()) {
+                return Err(RateLimited::new(action.scope(), retry_after).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The limit of the given action for the given user, and the times the
+/// user took it within the current window, oldest first. Returns `None`
+/// for users who are not limited.
+async fn usage(
+    ctx: &Context,
+    user: &User,
+    action: PostingAction,
+) -> Result<Option<(usize, Vec<DateTime<Utc>>)>> {
+    let exempt = user
+        .permissions(ctx)
+        .await?
+        .into_iter()
+        .any(|perm| perm.unlimited_posting());
+    if exempt {
+        return Ok(None);
+    }
+    let config = ctx.config();
+    let limits = if user.created_at() + config.rate_limit_new_account_age() > ctx.now() {
+        config.new_account_posting_limits()
+    } else {
+        config.posting_limits()
+    };
+
+    let since = (ctx.now() - Duration::hours(WINDOW_HOURS)).naive_utc();
+    let conn = ctx.conn().await?;
+    let (limit, times): (usize, Vec<NaiveDateTime>) = match action {
+        PostingAction::SubmitUrl => (
+            limits.submissions,
+            urls::table
+                .filter(urls::dsl::created_by.eq(user.id()))
+                .filter(urls::dsl::created_at.gt(since))
+                .order_by(urls::dsl::created_at.asc())
+                .select(urls::dsl::created_at)
+                .load(&*conn)?,
+        ),
+        PostingAction::AddComment => (
+            limits.comments,
+            comments::table
+                .filter(comments::dsl::created_by.eq(user.id()))
+                .filter(comments::dsl::created_at.gt(since))
+                .order_by(comments::dsl::created_at.asc())
+                .select(comments::dsl::created_at)
+                .load(&*conn)?,
+        ),
+    };
+    let times = times
+        .into_iter()
+        .map(|at| DateTime::from_utc(at, Utc))
+        .collect();
+    Ok(Some((limit, times)))
+}
+
+/// Count how often an action which may be taken `limit` times per
+/// `window` may still be taken at `now`, given the times it was taken
+/// before, oldest first. If it may not be taken, this also returns how
+/// long until it may be taken again.
+fn tally(
+    times: &[DateTime<Utc>],
+    limit: usize,
+    window: Duration,
+    now: DateTime<Utc>,
+) -> (usize, Option<Duration>) {
+    let recent: Vec<DateTime<Utc>> = times
+        .iter()
+        .copied()
+        .filter(|at| *at + window > now)
+        .collect();
+    if recent.len() < limit {
+        return (limit - recent.len(), None);
+    }
+    if limit == 0 {
+        return (0, Some(window));
+    }
+    // the limit frees up once the oldest counted post leaves the window
+    let retry_after = recent[recent.len() - limit] + window - now;
+    (0, Some(retry_after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_tally() {
+        let start = Utc.ymd(2021, 10, 1).and_hms(12, 0, 0);
+        let hour = Duration::hours(1);
+        let times: Vec<_> = (0..3).map(|i| start + Duration::minutes(i * 10)).collect();
+
+        // posts within the window count against the limit
+        let now = start + Duration::minutes(30);
+        assert_eq!(tally(&times, 5, hour, now), (2, None));
+        assert_eq!(tally(&[], 5, hour, now), (5, None));
+
+        // once exhausted, the oldest counted post frees up the limit
+        assert_eq!(
+            tally(&times, 3, hour, now),
+            (0, Some(Duration::minutes(30)))
+        );
+        assert_eq!(
+            tally(&times, 2, hour, now),
+            (0, Some(Duration::minutes(40)))
+        );
+
+        // posts leave the window after exactly one window
+        let now = start + hour;
+        assert_eq!(tally(&times, 3, hour, now), (1, None));
+        let now = start + Duration::minutes(80);
+        assert_eq!(tally(&times, 3, hour, now), (3, None));
+
+        // nothing may be posted with a limit of zero
+        assert_eq!(tally(&[], 0, hour, now), (0, Some(hour)));
+    }
+}
@@ -0,0 +1,423 @@
+use crate::db::id::UserID;
+use crate::db::models::{EmailCategory, TopRange, UrlOrdering, User};
+use crate::schema::user_preferences;
+use crate::validation::Validator;
+use crate::{language, Context};
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use chrono_tz::Tz;
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::Text;
+use juniper::{GraphQLEnum, GraphQLInputObject};
+use std::io::Write;
+use validator::{Validate, ValidationError};
+
+/// Locales the user interface may be displayed in.
+pub const SUPPORTED_LOCALES: &[&str] = &["en", "en-GB", "de", "fr", "es"];
+
+/// How the front page feed is sorted by default.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum FeedSort {
+    /// Default ranking used on the home page.
+    Ranked,
+    /// All time best submissions.
+    Best,
+    /// All submissions, ranked chronologically.
+    Recent,
+}
+
+impl From<FeedSort> for UrlOrdering {
+    fn from(sort: FeedSort) -> Self {
+        match sort {
+            FeedSort::Ranked => UrlOrdering::Ranked,
+            FeedSort::Best => UrlOrdering::Best,
+            FeedSort::Recent => UrlOrdering::Recent,
+        }
+    }
+}
+
+/// How often users receive an email digest of the
+/// top submissions.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum DigestFrequency {
+    /// No digest emails are sent.
+    Off,
+    /// The top submissions of the past week, once a week.
+    Weekly,
+    /// The top submissions of the past day, once a day.
+    Daily,
+}
+
+impl DigestFrequency {
+    /// The range of submissions included in a digest, which
+    /// is also the time between two digests, if any.
+    pub fn range(&self) -> Option<TopRange> {
+        match self {
+            DigestFrequency::Off => None,
+            DigestFrequency::Weekly => Some(TopRange::Week),
+            DigestFrequency::Daily => Some(TopRange::Day),
+        }
+    }
+}
+
+/// How submissions marked as not safe for work are shown
+/// to the user.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum ShowNsfw {
+    /// NSFW submissions are excluded from listings.
+    Hide,
+    /// NSFW submissions are listed, but blurred by clients.
+    Blur,
+    /// NSFW submissions are listed like any other.
+    Show,
+}
+
+impl ShowNsfw {
+    /// How NSFW submissions are shown to the viewer. Anonymous
+    /// visitors never see NSFW submissions in listings.
+    pub async fn of_viewer(ctx: &Context) -> Result<Self> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(UserPreferences::find(ctx, user_id).await?.show_nsfw),
+            None => Ok(ShowNsfw::Hide),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset, Associations)]
+#[belongs_to(User)]
+#[table_name = "user_preferences"]
+#[primary_key(user_id)]
+pub struct UserPreferences {
+    user_id: UserID,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+
+    notify_new_login: bool,
+    timezone: String,
+    locale: String,
+    default_sort: FeedSort,
+    digest: DigestFrequency,
+    show_nsfw: ShowNsfw,
+    /// Comma separated ISO 639-1 codes.
+    languages: String,
+    hide_votes: bool,
+    hide_from_leaderboard: bool,
+}
+
+#[derive(Debug, Clone, Validate, GraphQLInputObject)]
+pub struct PreferencesInput {
+    #[validate(custom(
+        function = "known_timezone",
+        message = "The timezone must be a valid tz database name"
+    ))]
+    timezone: Option<String>,
+    #[validate(custom(function = "supported_locale", message = "The locale is not supported"))]
+    locale: Option<String>,
+    default_sort: Option<FeedSort>,
+    notify_new_login: Option<bool>,
+    digest: Option<DigestFrequency>,
+    show_nsfw: Option<ShowNsfw>,
+    #[validate(custom(function = "known_languages", message = "The language is not known"))]
+    languages: Option<Vec<String>>,
+    hide_votes: Option<bool>,
+    hide_from_leaderboard: Option<bool>,
+}
+
+fn known_timezone(timezone: &str) -> Result<(), ValidationError> {
+    match timezone.parse::<Tz>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ValidationError::new("UNKNOWN_TIMEZONE")),
+    }
+}
+
+fn known_languages(languages: &[String]) -> Result<(), ValidationError> {
+    match language::normalize_all(languages) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ValidationError::new("UNKNOWN_LANGUAGE")),
+    }
+}
+
+fn supported_locale(locale: &str) -> Result<(), ValidationError> {
+    if SUPPORTED_LOCALES.contains(&locale) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("UNSUPPORTED_LOCALE"))
+    }
+}
+
+impl UserPreferences {
+    /// Default preferences for a user who never
+    /// changed any of their settings.
+    fn default_for(ctx: &Context, user_id: UserID) -> Self {
+        Self {
+            user_id,
+            created_at: ctx.now().naive_utc(),
+            updated_at: ctx.now().naive_utc(),
+
+            notify_new_login: true,
+            timezone: "UTC".into(),
+            locale: "en".into(),
+            default_sort: FeedSort::Ranked,
+            digest: DigestFrequency::Off,
+            show_nsfw: ShowNsfw::Hide,
+            languages: String::new(),
+            hide_votes: false,
+            hide_from_leaderboard: false,
+        }
+    }
+
+    /// Whether the user wants to receive an email when
+    /// their account is logged into from a new device.
+    pub fn notify_new_login(&self) -> bool {
+        self.notify_new_login
+    }
+
+    /// Whether the user wants to receive emails of
+    /// the given category.
+    pub fn is_subscribed(&self, category: EmailCategory) -> bool {
+        match category {
+            EmailCategory::NewLogin => self.notify_new_login,
+            EmailCategory::Digest => self.digest != DigestFrequency::Off,
+        }
+    }
+
+    /// Subscribe or unsubscribe the user from emails
+    /// of the given category. This does not save the
+    /// preferences. Subscribing to digests chooses the
+    /// weekly digest, unless one is already chosen.
+    pub fn set_subscribed(&mut self, category: EmailCategory, subscribed: bool) {
+        match (category, subscribed) {
+            (EmailCategory::NewLogin, _) => self.notify_new_login = subscribed,
+            (EmailCategory::Digest, false) => self.digest = DigestFrequency::Off,
+            (EmailCategory::Digest, true) => {
+                if self.digest == DigestFrequency::Off {
+                    self.digest = DigestFrequency::Weekly;
+                }
+            }
+        }
+    }
+
+    /// Name of the users timezone in the tz database,
+    /// e.g. `Europe/Berlin`.
+    pub fn timezone_name(&self) -> &str {
+        &self.timezone
+    }
+
+    /// The users timezone. This falls back to UTC, should
+    /// the stored timezone no longer be known.
+    pub fn timezone(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+
+    /// Preferred locale of the user interface.
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// How the users front page feed is sorted by
+    /// default.
+    pub fn default_sort(&self) -> FeedSort {
+        self.default_sort
+    }
+
+    /// How often the user receives an email digest
+    /// of the top submissions.
+    pub fn digest(&self) -> DigestFrequency {
+        self.digest
+    }
+
+    /// How submissions marked as not safe for work are
+    /// shown to the user.
+    pub fn show_nsfw(&self) -> ShowNsfw {
+        self.show_nsfw
+    }
+
+    /// ISO 639-1 codes of the languages listings are
+    /// restricted to by default. All languages are
+    /// listed if this is empty.
+    pub fn languages(&self) -> Vec<String> {
+        self.languages
+            .split(',')
+            .filter(|code| !code.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Whether the user opted out of being listed among the
+    /// voters of submissions. Their votes are still counted.
+    pub fn hide_votes(&self) -> bool {
+        self.hide_votes
+    }
+
+    /// Whether the user opted out of being listed on the
+    /// leaderboard. Their votes still earn others points.
+    pub fn hide_from_leaderboard(&self) -> bool {
+        self.hide_from_leaderboard
+    }
+}
+
+impl UserPreferences {
+    /// Load the preferences for the given user. Preferences
+    /// are only stored once they are first changed, so this
+    /// returns the defaults for users without a stored row.
+    pub async fn find(ctx: &Context, user_id: UserID) -> Result<Self> {
+        let prefs = user_preferences::table
+            .find(user_id)
+            .get_result(&*ctx.conn().await?)
+            .optional()?;
+        Ok(prefs.unwrap_or_else(|| Self::default_for(ctx, user_id)))
+    }
+
+    /// Persist these preferences, creating the row if it
+    /// does not exist yet.
+    pub async fn save(&mut self, ctx: &Context) -> Result<()> {
+        self.updated_at = ctx.now().naive_utc();
+        diesel::replace_into(user_preferences::table)
+            .values(&*self)
+            .execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Update these preferences using data given in an update
+    /// object. Only the given fields are changed. This is meant
+    /// to be exposed from the graphql API.
+    pub async fn update(&mut self, ctx: &Context, input: PreferencesInput) -> Result<()> {
+        Validator::new().check("input", &input).finish()?;
+        let PreferencesInput {
+            timezone,
+            locale,
+            default_sort,
+            notify_new_login,
+            digest,
+            show_nsfw,
+            languages,
+            hide_votes,
+            hide_from_leaderboard,
+        } = input;
+
+        if let Some(timezone) = timezone {
+            self.timezone = timezone;
+        }
+        if let Some(locale) = locale {
+            self.locale = locale;
+        }
+        if let Some(default_sort) = default_sort {
+            self.default_sort = default_sort;
+        }
+        if let Some(notify_new_login) = notify_new_login {
+            self.notify_new_login = notify_new_login;
+        }
+        if let Some(digest) = digest {
+            self.digest = digest;
+        }
+        if let Some(show_nsfw) = show_nsfw {
+            self.show_nsfw = show_nsfw;
+        }
+        if let Some(languages) = languages {
+            self.languages = language::normalize_all(&languages)?.join(",");
+        }
+        if let Some(hide_votes) = hide_votes {
+            self.hide_votes = hide_votes;
+        }
+        if let Some(hide_from_leaderboard) = hide_from_leaderboard {
+            self.hide_from_leaderboard = hide_from_leaderboard;
+        }
+        self.save(ctx).await
+    }
+}
+
+impl<DB> ToSql<Text, DB> for FeedSort
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            FeedSort::Ranked => "ranked",
+            FeedSort::Best => "best",
+            FeedSort::Recent => "recent",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for FeedSort
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "ranked" => Ok(FeedSort::Ranked),
+            "best" => Ok(FeedSort::Best),
+            "recent" => Ok(FeedSort::Recent),
+            _ => Err("Unrecognized feed sort".into()),
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for DigestFrequency
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            DigestFrequency::Off => "off",
+            DigestFrequency::Weekly => "weekly",
+            DigestFrequency::Daily => "daily",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for DigestFrequency
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "off" => Ok(DigestFrequency::Off),
+            "weekly" => Ok(DigestFrequency::Weekly),
+            "daily" => Ok(DigestFrequency::Daily),
+            _ => Err("Unrecognized digest frequency".into()),
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for ShowNsfw
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            ShowNsfw::Hide => "hide",
+            ShowNsfw::Blur => "blur",
+            ShowNsfw::Show => "show",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for ShowNsfw
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "hide" => Ok(ShowNsfw::Hide),
+            "blur" => Ok(ShowNsfw::Blur),
+            "show" => Ok(ShowNsfw::Show),
+            _ => Err("Unrecognized NSFW preference".into()),
+        }
+    }
+}
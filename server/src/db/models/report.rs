@@ -0,0 +1,485 @@
+use crate::db::id::{ReportID, UrlID, UserID};
+use crate::db::models::{Comment, Url, User};
+use crate::schema::{comments, reports, urls};
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::Text;
+use juniper::GraphQLEnum;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Maximum length of the note attached to a report.
+const MAX_NOTE_LEN: usize = 1000;
+
+/// A report of a submission by a user, asking moderators to review
+/// it. Reports are private, neither the reported user nor anyone but
+/// moderators learn who reported a submission, and reporters are not
+/// told how their report was resolved.
+#[derive(Debug, Clone, Queryable, Insertable)]
+pub struct Report {
+    id: ReportID,
+    url_id: UrlID,
+    created_by: UserID,
+    reason: ReportReason,
+    note: Option<String>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    resolved_at: Option<NaiveDateTime>,
+    resolved_by: Option<UserID>,
+    action: Option<ModerationAction>,
+}
+
+/// Why a submission was reported.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum ReportReason {
+    /// Advertising or otherwise unsolicited content.
+    Spam,
+    /// Abusive, hateful, or otherwise offensive content.
+    Offensive,
+    /// Content which does not belong on this site.
+    OffTopic,
+    /// A link which was already submitted.
+    Duplicate,
+    /// Any other reason, which should be explained in the note.
+    Other,
+}
+
+/// Whether reports still await review by a moderator.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportStatus {
+    /// Reports which were not reviewed yet.
+    Open,
+    /// Reports which were reviewed by a moderator.
+    Resolved,
+}
+
+/// How a moderator resolved the reports of a submission.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum ModerationAction {
+    /// Keep the submission, closing the reports.
+    Dismiss,
+    /// Delete the submission.
+    RemoveUrl,
+    /// Ban the user who submitted the submission.
+    BanUser,
+}
+
+/// The reports of a single submission, as listed in the moderation
+/// queue. Reports with the same status are grouped by submission,
+/// together with the comments on it which the spam filter held.
+#[derive(Debug, Clone)]
+pub struct ReportedUrl {
+    url: Url,
+    reports: Vec<Report>,
+    held_comments: Vec<Comment>,
+    first_reported_at: NaiveDateTime,
+}
+
+/// Position of a reported submission in the moderation queue, which
+/// lists the submissions reported first at the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportCursor {
+    first_reported_at: NaiveDateTime,
+    url_id: UrlID,
+}
+
+impl fmt::Display for ReportCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = format!(
+            "{}:{}",
+            self.first_reported_at.timestamp_nanos(),
+            self.url_id
+        );
+        write!(f, "{}", base64::encode_config(raw, base64::URL_SAFE_NO_PAD))
+    }
+}
+
+impl FromStr for ReportCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid report cursor";
+        let raw = base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|_| ERR)?;
+        let raw = String::from_utf8(raw).map_err(|_| ERR)?;
+        let mut parts = raw.splitn(2, ':');
+        let nanos: i64 = parts.next().and_then(|t| t.parse().ok()).ok_or(ERR)?;
+        let url_id = parts.next().and_then(|id| id.parse().ok()).ok_or(ERR)?;
+        let first_reported_at = NaiveDateTime::from_timestamp_opt(
+            nanos.div_euclid(1_000_000_000),
+            nanos.rem_euclid(1_000_000_000) as u32,
+        )
+        .ok_or(ERR)?;
+        Ok(Self {
+            first_reported_at,
+            url_id,
+        })
+    }
+}
+
+impl ReportCursor {
+    fn key(&self) -> (NaiveDateTime, &str) {
+        (self.first_reported_at, self.url_id.as_str())
+    }
+}
+
+impl Report {
+    pub fn reason(&self) -> ReportReason {
+        self.reason
+    }
+
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.updated_at, Utc)
+    }
+
+    pub fn resolved_at(&self) -> Option<DateTime<Utc>> {
+        self.resolved_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    /// How the report was resolved, if it was.
+    pub fn action(&self) -> Option<ModerationAction> {
+        self.action
+    }
+}
+
+impl ReportedUrl {
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The reports of the submission, oldest first.
+    pub fn reports(&self) -> &[Report] {
+        &self.reports
+    }
+
+    /// The comments on the submission held by the spam
+    /// filter, oldest first.
+    pub fn held_comments(&self) -> &[Comment] {
+        &self.held_comments
+    }
+
+    /// The distinct reasons the submission was reported for,
+    /// most common first.
+    pub fn reasons(&self) -> Vec<ReportReason> {
+        let mut counts: Vec<(ReportReason, usize)> = vec![];
+        for report in &self.reports {
+            match counts
+                .iter_mut()
+                .find(|(reason, _)| *reason == report.reason)
+            {
+                Some((_, count)) => *count += 1,
+                None => counts.push((report.reason, 1)),
+            }
+        }
+        // stable, such that ties keep the order of first report
+        counts.sort_by(|a, b| b.1.cmp(&a.1));
+        counts.into_iter().map(|(reason, _)| reason).collect()
+    }
+
+    pub fn cursor(&self) -> ReportCursor {
+        ReportCursor {
+            first_reported_at: self.first_reported_at,
+            url_id: self.url.id(),
+        }
+    }
+}
+
+impl Report {
+    /// Report the given submission as the currently logged in user.
+    /// Users have at most one open report per submission, reporting
+    /// the submission again replaces the reason and note of the open
+    /// report.
+    pub async fn create(
+        ctx: &Context,
+        url: &Url,
+        reason: ReportReason,
+        note: Option<String>,
+    ) -> Result<()> {
+        let user = ctx.verified_user().await?;
+        if url.is_deleted() {
+            return Err(anyhow!("This submission was deleted"));
+        }
+        if url.created_by_id() == user.id() {
+            return Err(anyhow!("You can not report your own submission"));
+        }
+        let note = note
+            .map(|note| note.trim().to_string())
+            .filter(|note| !note.is_empty());
+        if note
+            .as_ref()
+            .map_or(false, |note| note.chars().count() > MAX_NOTE_LEN)
+        {
+            return Err(anyhow!(
+                "Notes can have at most {} characters",
+                MAX_NOTE_LEN
+            ));
+        }
+
+        let now = ctx.now().naive_utc();
+        let report = Report {
+            id: ReportID::new(),
+            url_id: url.id(),
+            created_by: user.id(),
+            reason,
+            note,
+            created_at: now,
+            updated_at: now,
+            resolved_at: None,
+            resolved_by: None,
+            action: None,
+        };
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            let inserted = diesel::insert_or_ignore_into(reports::table)
+                .values(&report)
+                .execute(&*conn)?;
+            if inserted == 0 {
+                let open = reports::table
+                    .filter(reports::dsl::url_id.eq(report.url_id))
+                    .filter(reports::dsl::created_by.eq(report.created_by))
+                    .filter(reports::dsl::resolved_at.is_null());
+                diesel::update(open)
+                    .set((
+                        reports::dsl::reason.eq(report.reason),
+                        reports::dsl::note.eq(&report.note),
+                        reports::dsl::updated_at.eq(now),
+                    ))
+                    .execute(&*conn)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Resolve all open reports of the given submission by taking the
+    /// given action, where removing the submission and banning its
+    /// submitter go through [`Url::delete`] and [`User::ban`]. This is
+    /// only available to administrators and moderators.
+    pub async fn resolve(ctx: &Context, url_id: UrlID, action: ModerationAction) -> Result<()> {
+        let moderator = ctx.user().await?;
+        moderator
+            .check_permissions(ctx, |perm| perm.review_reports())
+            .await?;
+        let open = reports::table
+            .filter(reports::dsl::url_id.eq(url_id))
+            .filter(reports::dsl::resolved_at.is_null());
+        let count: i64 = open
+            .select(diesel::dsl::count_star())
+            .get_result(&*ctx.conn().await?)?;
+        if count == 0 {
+            return Err(anyhow!("There are no open reports for this submission"));
+        }
+
+        let mut url = Url::find(ctx, url_id).await?;
+        match action {
+            ModerationAction::Dismiss => {}
+            ModerationAction::RemoveUrl => url.delete(ctx).await?,
+            ModerationAction::BanUser => {
+                moderator
+                    .check_permissions(ctx, |perm| perm.ban_users())
+                    .await?;
+                let mut user = User::find(ctx, url.created_by_id()).await?;
+                user.ban(ctx).await?;
+            }
+        }
+
+        diesel::update(open)
+            .set((
+                reports::dsl::resolved_at.eq(ctx.now().naive_utc()),
+                reports::dsl::resolved_by.eq(moderator.id()),
+                reports::dsl::action.eq(action),
+            ))
+            .execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Returns the moderation queue of submissions with reports of the
+    /// given status, in a way that's suitable for use with a Relay
+    /// connection. The submissions reported first are listed at the top.
+    /// The open queue also lists submissions which the spam filter held,
+    /// or on which it held comments, as if they were reported when they
+    /// were held. This is only available to administrators and moderators.
+    pub async fn queue(
+        ctx: &Context,
+        status: ReportStatus,
+        after: Option<ReportCursor>,
+        before: Option<ReportCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<ReportedUrl>> {
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.review_reports())
+            .await?;
+        let reports: Vec<Report> = {
+            let conn = ctx.conn().await?;
+            let query = reports::table
+                .order_by(reports::dsl::created_at.asc())
+                .then_order_by(reports::dsl::id.asc())
+                .into_boxed();
+            let query = match status {
+                ReportStatus::Open => query.filter(reports::dsl::resolved_at.is_null()),
+                ReportStatus::Resolved => query.filter(reports::dsl::resolved_at.is_not_null()),
+            };
+            query.load(&*conn)?
+        };
+
+        // the queue is expected to stay small, so reports are grouped
+        // here rather than in the database
+        let mut groups: Vec<(ReportCursor, Vec<Report>)> = vec![];
+        for report in reports {
+            match groups
+                .iter_mut()
+                .find(|(cursor, _)| cursor.url_id == report.url_id)
+            {
+                Some((_, reports)) => reports.push(report),
+                None => {
+                    let cursor = ReportCursor {
+                        first_reported_at: report.created_at,
+                        url_id: report.url_id,
+                    };
+                    groups.push((cursor, vec![report]));
+                }
+            }
+        }
+        if status == ReportStatus::Open {
+            let held = Self::held(ctx).await?;
+            for (url_id, held_at) in held {
+                match groups
+                    .iter_mut()
+                    .find(|(cursor, _)| cursor.url_id == url_id)
+                {
+                    Some((cursor, _)) => {
+                        cursor.first_reported_at = cursor.first_reported_at.min(held_at)
+                    }
+                    None => {
+                        let cursor = ReportCursor {
+                            first_reported_at: held_at,
+                            url_id,
+                        };
+                        groups.push((cursor, vec![]));
+                    }
+                }
+            }
+        }
+        groups.sort_by(|(a, _), (b, _)| a.key().cmp(&b.key()));
+        groups.retain(|(cursor, _)| after.map(|a| cursor.key() > a.key()).unwrap_or(true));
+        groups.retain(|(cursor, _)| before.map(|b| cursor.key() < b.key()).unwrap_or(true));
+        if let Some(limit) = limit {
+            groups.truncate(limit as usize);
+        }
+
+        let mut queue = vec![];
+        for (cursor, reports) in groups {
+            let url = Url::find(ctx, cursor.url_id).await?;
+            let held_comments = match status {
+                ReportStatus::Open => Comment::held_for(ctx, cursor.url_id).await?,
+                ReportStatus::Resolved => vec![],
+            };
+            queue.push(ReportedUrl {
+                url,
+                reports,
+                held_comments,
+                first_reported_at: cursor.first_reported_at,
+            });
+        }
+        Ok(queue)
+    }
+
+    /// The submissions which the spam filter held, or on which it held
+    /// comments, with the time it held them, excluding deleted content.
+    async fn held(ctx: &Context) -> Result<Vec<(UrlID, NaiveDateTime)>> {
+        let urls: Vec<(UrlID, Option<NaiveDateTime>)> = urls::table
+            .filter(urls::dsl::held_at.is_not_null())
+            .filter(urls::dsl::deleted_at.is_null())
+            .select((urls::dsl::id, urls::dsl::held_at))
+            .load(&*ctx.conn().await?)?;
+        let comments: Vec<(UrlID, Option<NaiveDateTime>)> = comments::table
+            .filter(comments::dsl::held_at.is_not_null())
+            .filter(comments::dsl::deleted_at.is_null())
+            .select((comments::dsl::url_id, comments::dsl::held_at))
+            .load(&*ctx.conn().await?)?;
+        Ok(urls
+            .into_iter()
+            .chain(comments)
+            .filter_map(|(url_id, held_at)| Some((url_id, held_at?)))
+            .collect())
+    }
+}
+
+impl<DB> ToSql<Text, DB> for ReportReason
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            ReportReason::Spam => "spam",
+            ReportReason::Offensive => "offensive",
+            ReportReason::OffTopic => "off_topic",
+            ReportReason::Duplicate => "duplicate",
+            ReportReason::Other => "other",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for ReportReason
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "spam" => Ok(ReportReason::Spam),
+            "offensive" => Ok(ReportReason::Offensive),
+            "off_topic" => Ok(ReportReason::OffTopic),
+            "duplicate" => Ok(ReportReason::Duplicate),
+            "other" => Ok(ReportReason::Other),
+            _ => Err("Unrecognized report reason".into()),
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for ModerationAction
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            ModerationAction::Dismiss => "dismiss",
+            ModerationAction::RemoveUrl => "remove_url",
+            ModerationAction::BanUser => "ban_user",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for ModerationAction
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "dismiss" => Ok(ModerationAction::Dismiss),
+            "remove_url" => Ok(ModerationAction::RemoveUrl),
+            "ban_user" => Ok(ModerationAction::BanUser),
+            _ => Err("Unrecognized moderation action".into()),
+        }
+    }
+}
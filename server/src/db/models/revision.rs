@@ -0,0 +1,228 @@
+use crate::db::id::{CommentID, RevisionID, UrlID, UserID};
+use crate::db::models::{Comment, Url, User};
+use crate::schema::revisions;
+use crate::Context;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::sqlite::Sqlite;
+use std::fmt;
+use std::str::FromStr;
+
+/// A previous version of an edited submission or comment, recorded
+/// whenever the content changes. Revisions of submissions keep the
+/// title and description provided by the submitter, revisions of
+/// comments keep their text. Revisions are kept when the content is
+/// deleted, and are only visible to the author and administrators,
+/// unless the edit history is configured to be public.
+#[derive(Debug, Clone, Queryable, Insertable)]
+pub struct Revision {
+    id: RevisionID,
+    created_at: NaiveDateTime,
+    editor_id: UserID,
+    url_id: Option<UrlID>,
+    comment_id: Option<CommentID>,
+    title: Option<String>,
+    description: Option<String>,
+    body: Option<String>,
+}
+
+/// Position of a revision in the edit history of a submission or
+/// comment, which lists the most recent revisions first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RevisionCursor {
+    created_at: NaiveDateTime,
+    id: RevisionID,
+}
+
+impl fmt::Display for RevisionCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = format!("{}:{}", self.created_at.timestamp_nanos(), self.id);
+        write!(f, "{}", base64::encode_config(raw, base64::URL_SAFE_NO_PAD))
+    }
+}
+
+impl FromStr for RevisionCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid revision cursor";
+        let raw = base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|_| ERR)?;
+        let raw = String::from_utf8(raw).map_err(|_| ERR)?;
+        let mut parts = raw.splitn(2, ':');
+        let nanos: i64 = parts.next().and_then(|t| t.parse().ok()).ok_or(ERR)?;
+        let id = parts.next().and_then(|id| id.parse().ok()).ok_or(ERR)?;
+        let created_at = NaiveDateTime::from_timestamp_opt(
+            nanos.div_euclid(1_000_000_000),
+            nanos.rem_euclid(1_000_000_000) as u32,
+        )
+        .ok_or(ERR)?;
+        Ok(Self { created_at, id })
+    }
+}
+
+impl Revision {
+    /// The previous title and description of a submission, replaced
+    /// by the given editor.
+    pub(crate) fn of_url(
+        url_id: UrlID,
+        editor_id: UserID,
+        title: Option<String>,
+        description: Option<String>,
+        now: NaiveDateTime,
+    ) -> Self {
+        Self {
+            id: RevisionID::new(),
+            created_at: now,
+            editor_id,
+            url_id: Some(url_id),
+            comment_id: None,
+            title,
+            description,
+            body: None,
+        }
+    }
+
+    /// The previous text of a comment, replaced by the given editor.
+    pub(crate) fn of_comment(
+        comment_id: CommentID,
+        editor_id: UserID,
+        body: String,
+        now: NaiveDateTime,
+    ) -> Self {
+        Self {
+            id: RevisionID::new(),
+            created_at: now,
+            editor_id,
+            url_id: None,
+            comment_id: Some(comment_id),
+            title: None,
+            description: None,
+            body: Some(body),
+        }
+    }
+
+    pub fn id(&self) -> RevisionID {
+        self.id
+    }
+
+    /// When this version was replaced.
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    /// The title provided by the submitter of a submission.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// The description provided by the submitter of a submission.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// The markdown text of a comment.
+    pub fn body(&self) -> Option<&str> {
+        self.body.as_deref()
+    }
+
+    /// The user who replaced this version, which is either the
+    /// author or a moderator.
+    pub async fn editor(&self, ctx: &Context) -> Result<User> {
+        User::find(ctx, self.editor_id).await
+    }
+
+    pub fn cursor(&self) -> RevisionCursor {
+        RevisionCursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
+impl Revision {
+    /// Revisions of the given submission, most recent first, in a
+    /// way that's suitable for use with a Relay connection, see
+    /// [`check_may_view`](Revision::check_may_view).
+    pub async fn for_url(
+        ctx: &Context,
+        url: &Url,
+        after: Option<RevisionCursor>,
+        before: Option<RevisionCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        Self::check_may_view(ctx, url.created_by_id(), url.is_deleted()).await?;
+        let conn = ctx.conn().await?;
+        let query = revisions::table
+            .filter(revisions::dsl::url_id.eq(url.id()))
+            .into_boxed();
+        Self::paginate(&*conn, query, after, before, limit)
+    }
+
+    /// Revisions of the given comment, most recent first, in a
+    /// way that's suitable for use with a Relay connection, see
+    /// [`check_may_view`](Revision::check_may_view).
+    pub async fn for_comment(
+        ctx: &Context,
+        comment: &Comment,
+        after: Option<RevisionCursor>,
+        before: Option<RevisionCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        Self::check_may_view(ctx, comment.created_by_id(), comment.is_deleted()).await?;
+        let conn = ctx.conn().await?;
+        let query = revisions::table
+            .filter(revisions::dsl::comment_id.eq(comment.id()))
+            .into_boxed();
+        Self::paginate(&*conn, query, after, before, limit)
+    }
+
+    /// The edit history of content is visible to its author and
+    /// administrators, and to everyone if configured. The history
+    /// of deleted content is never public, as it would reveal the
+    /// deleted text.
+    async fn check_may_view(ctx: &Context, author_id: UserID, deleted: bool) -> Result<()> {
+        if ctx.config().public_revisions() && !deleted {
+            return Ok(());
+        }
+        let user = ctx.user().await?;
+        if user.id() == author_id {
+            return Ok(());
+        }
+        user.check_permissions(ctx, |perm| perm.view_revisions())
+            .await
+    }
+
+    fn paginate<C>(
+        conn: &C,
+        mut query: revisions::BoxedQuery<'_, Sqlite>,
+        after: Option<RevisionCursor>,
+        before: Option<RevisionCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        use revisions::dsl::{created_at, id};
+
+        query = query.order_by(created_at.desc()).then_order_by(id.desc());
+        if let Some(after) = after {
+            query = query.filter(
+                created_at
+                    .lt(after.created_at)
+                    .or(created_at.eq(after.created_at).and(id.lt(after.id))),
+            );
+        }
+        if let Some(before) = before {
+            query = query.filter(
+                created_at
+                    .gt(before.created_at)
+                    .or(created_at.eq(before.created_at).and(id.gt(before.id))),
+            );
+        }
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        Ok(query.load(conn)?)
+    }
+}
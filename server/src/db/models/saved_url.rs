@@ -0,0 +1,293 @@
+use crate::db::id::{SavedUrlID, UrlID, UserID};
+use crate::db::models::{Listed, Url};
+use crate::schema::{saved_urls, urls};
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use juniper::{GraphQLEnum, GraphQLObject};
+use std::fmt;
+use std::str::FromStr;
+
+/// A submission saved by a user to their reading list. Saves
+/// are private, and only visible to the user who saved the
+/// submission.
+#[derive(Debug, Clone, Queryable, Insertable)]
+pub struct SavedUrl {
+    id: SavedUrlID,
+    user_id: UserID,
+    url_id: UrlID,
+    saved_at: NaiveDateTime,
+    read_at: Option<NaiveDateTime>,
+}
+
+/// Determines which saves are listed in the reading
+/// list of a user.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SavedStatus {
+    /// Saves which were not marked as read yet.
+    Unread,
+    /// Saves which were marked as read.
+    Read,
+    /// All saves, read or not.
+    All,
+}
+
+/// The number of saves in the reading list of a user.
+#[derive(Debug, Clone, GraphQLObject)]
+pub struct SavedCounts {
+    /// Number of saves which were not marked as read yet.
+    pub unread: i32,
+    /// Number of saves which were marked as read.
+    pub read: i32,
+}
+
+/// Position of a save in the reading list of a user. This holds
+/// the values saves are sorted by at the time the cursor was handed
+/// out, such that pages stay stable when the save the cursor points
+/// to is removed, or bumped to the top by saving the submission again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavedUrlCursor {
+    saved_at: NaiveDateTime,
+    id: SavedUrlID,
+}
+
+impl fmt::Display for SavedUrlCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = format!("{}:{}", self.saved_at.timestamp_nanos(), self.id);
+        write!(f, "{}", base64::encode_config(raw, base64::URL_SAFE_NO_PAD))
+    }
+}
+
+impl FromStr for SavedUrlCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid saved submission cursor";
+        let raw = base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|_| ERR)?;
+        let raw = String::from_utf8(raw).map_err(|_| ERR)?;
+        let mut parts = raw.splitn(2, ':');
+        let nanos: i64 = parts.next().and_then(|t| t.parse().ok()).ok_or(ERR)?;
+        let id = parts.next().and_then(|id| id.parse().ok()).ok_or(ERR)?;
+        let saved_at = NaiveDateTime::from_timestamp_opt(
+            nanos.div_euclid(1_000_000_000),
+            nanos.rem_euclid(1_000_000_000) as u32,
+        )
+        .ok_or(ERR)?;
+        Ok(Self { saved_at, id })
+    }
+}
+
+impl SavedUrl {
+    pub fn url_id(&self) -> UrlID {
+        self.url_id
+    }
+
+    /// When the submission was last saved.
+    pub fn saved_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.saved_at, Utc)
+    }
+
+    pub fn cursor(&self) -> SavedUrlCursor {
+        SavedUrlCursor {
+            saved_at: self.saved_at,
+            id: self.id,
+        }
+    }
+}
+
+impl SavedUrl {
+    /// Save the given submission for the currently logged in user.
+    /// Saving an already saved submission moves it to the top of the
+    /// reading list, and marks it as unread again.
+    pub async fn save(ctx: &Context, url: &Url) -> Result<()> {
+        let now = ctx.now().naive_utc();
+        let save = Self::new(ctx, url, None)?;
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            let inserted = diesel::insert_or_ignore_into(saved_urls::table)
+                .values(&save)
+                .execute(&*conn)?;
+            if inserted == 0 {
+                let existing = saved_urls::table
+                    .filter(saved_urls::dsl::user_id.eq(save.user_id))
+                    .filter(saved_urls::dsl::url_id.eq(save.url_id));
+                diesel::update(existing)
+                    .set((
+                        saved_urls::dsl::saved_at.eq(now),
+                        saved_urls::dsl::read_at.eq(None::<NaiveDateTime>),
+                    ))
+                    .execute(&*conn)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Save the given submission for the currently logged in user as of
+    /// the given time, e.g. when importing bookmarks, unless it is saved
+    /// already. Returns whether the submission was saved.
+    pub(crate) async fn save_if_new(
+        ctx: &Context,
+        url: &Url,
+        saved_at: DateTime<Utc>,
+        read_at: Option<DateTime<Utc>>,
+    ) -> Result<bool> {
+        let save = SavedUrl {
+            saved_at: saved_at.naive_utc(),
+            ..Self::new(ctx, url, read_at.map(|at| at.naive_utc()))?
+        };
+        let inserted = diesel::insert_or_ignore_into(saved_urls::table)
+            .values(&save)
+            .execute(&*ctx.conn().await?)?;
+        Ok(inserted > 0)
+    }
+
+    /// Mark the given submission as read or unread in the reading list
+    /// of the currently logged in user. Submissions which are not saved
+    /// yet are saved, such that something can be marked to read later
+    /// from anywhere. Unlike saving, this does not move the submission
+    /// to the top of the reading list.
+    pub async fn mark(ctx: &Context, url: &Url, read: bool) -> Result<()> {
+        let read_at = if read {
+            Some(ctx.now().naive_utc())
+        } else {
+            None
+        };
+        let save = Self::new(ctx, url, read_at)?;
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            let inserted = diesel::insert_or_ignore_into(saved_urls::table)
+                .values(&save)
+                .execute(&*conn)?;
+            if inserted == 0 {
+                let existing = saved_urls::table
+                    .filter(saved_urls::dsl::user_id.eq(save.user_id))
+                    .filter(saved_urls::dsl::url_id.eq(save.url_id));
+                // marking a read submission as read again keeps the
+                // time it was first read
+                if read {
+                    diesel::update(existing.filter(saved_urls::dsl::read_at.is_null()))
+                        .set(saved_urls::dsl::read_at.eq(read_at))
+                        .execute(&*conn)?;
+                } else {
+                    diesel::update(existing)
+                        .set(saved_urls::dsl::read_at.eq(read_at))
+                        .execute(&*conn)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn new(ctx: &Context, url: &Url, read_at: Option<NaiveDateTime>) -> Result<Self> {
+        if url.is_deleted() {
+            return Err(anyhow!("You can not save a deleted submission"));
+        }
+        Ok(SavedUrl {
+            id: SavedUrlID::new(),
+            user_id: ctx.user_id()?,
+            url_id: url.id(),
+            saved_at: ctx.now().naive_utc(),
+            read_at,
+        })
+    }
+
+    /// Remove the given submission from the reading list of the
+    /// currently logged in user. Removing a submission which is
+    /// not saved has no effect.
+    pub async fn unsave(ctx: &Context, url_id: UrlID) -> Result<()> {
+        let save = saved_urls::table
+            .filter(saved_urls::dsl::user_id.eq(ctx.user_id()?))
+            .filter(saved_urls::dsl::url_id.eq(url_id));
+        diesel::delete(save).execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Check if the currently logged in user saved the given
+    /// submission. This is false if no user is logged in.
+    pub async fn exists(ctx: &Context, url_id: UrlID) -> Result<bool> {
+        let user_id = match ctx.maybe_user_id() {
+            Some(user_id) => user_id,
+            None => return Ok(false),
+        };
+        let count: i64 = saved_urls::table
+            .filter(saved_urls::dsl::user_id.eq(user_id))
+            .filter(saved_urls::dsl::url_id.eq(url_id))
+            .select(diesel::dsl::count_star())
+            .get_result(&*ctx.conn().await?)?;
+        Ok(count > 0)
+    }
+
+    /// The number of read and unread saves of the given user.
+    /// Deleted submissions, removed submissions hidden from the viewer,
+    /// and drafts of other users are not counted.
+    pub async fn counts(ctx: &Context, user_id: UserID) -> Result<SavedCounts> {
+        let listed = Listed::curated_by(ctx, Some(user_id)).await?;
+        let conn = ctx.conn().await?;
+        let read_at: Vec<Option<NaiveDateTime>> = saved_urls::table
+            .inner_join(urls::table)
+            .filter(saved_urls::dsl::user_id.eq(user_id))
+            .filter(listed.filter())
+            .select(saved_urls::dsl::read_at)
+            .load(&*conn)?;
+        let read = read_at.iter().filter(|read_at| read_at.is_some()).count();
+        Ok(SavedCounts {
+            unread: (read_at.len() - read) as i32,
+            read: read as i32,
+        })
+    }
+
+    /// Returns the reading list of the given user, most recently
+    /// saved first, in a way that's suitable for use with a Relay
+    /// connection. Deleted submissions, removed submissions hidden from
+    /// the viewer, and drafts of other users are excluded.
+    pub async fn reading_list(
+        ctx: &Context,
+        user_id: UserID,
+        status: SavedStatus,
+        after: Option<SavedUrlCursor>,
+        before: Option<SavedUrlCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<(SavedUrl, Url)>> {
+        use saved_urls::dsl::{id, saved_at};
+
+        let listed = Listed::curated_by(ctx, Some(user_id)).await?;
+        let conn = ctx.conn().await?;
+        let mut query = saved_urls::table
+            .inner_join(urls::table)
+            .filter(saved_urls::dsl::user_id.eq(user_id))
+            .filter(listed.filter())
+            .order_by(saved_at.desc())
+            .then_order_by(id.desc())
+            .select((saved_urls::all_columns, urls::all_columns))
+            .into_boxed();
+
+        query = match status {
+            SavedStatus::Unread => query.filter(saved_urls::dsl::read_at.is_null()),
+            SavedStatus::Read => query.filter(saved_urls::dsl::read_at.is_not_null()),
+            SavedStatus::All => query,
+        };
+
+        if let Some(after) = after {
+            query = query.filter(
+                saved_at
+                    .lt(after.saved_at)
+                    .or(saved_at.eq(after.saved_at).and(id.lt(after.id))),
+            );
+        }
+
+        if let Some(before) = before {
+            query = query.filter(
+                saved_at
+                    .gt(before.saved_at)
+                    .or(saved_at.eq(before.saved_at).and(id.gt(before.id))),
+            );
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        Ok(query.load(&*conn)?)
+    }
+}
@@ -0,0 +1,165 @@
+use crate::db::id::{SecurityEventID, UserID};
+use crate::db::models::User;
+use crate::schema::security_events;
+use crate::Context;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::sqlite::Sqlite;
+use juniper::GraphQLEnum;
+use std::io::Write;
+
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum SecurityEventKind {
+    /// A login code was requested by email.
+    LoginCodeRequested,
+    /// A login code was exchanged for a session.
+    LoginSucceeded,
+    /// An invalid or expired login code was used.
+    LoginFailed,
+    /// A login session was revoked.
+    SessionRevoked,
+    /// A change of the account email address was requested. The
+    /// new address is in use, but has not been verified yet.
+    EmailChangeRequested,
+    /// A changed account email address was verified.
+    EmailChangeConfirmed,
+    /// Logging in was locked after too many failed attempts.
+    LoginLocked,
+    /// A login lock was lifted by an administrator.
+    LoginUnlocked,
+    /// A new access token (e.g. for the private feeds) was created.
+    TokenCreated,
+    /// An access token was replaced, and stopped working.
+    TokenRevoked,
+}
+
+/// An entry in the append-only security audit log of
+/// a user account.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, Associations)]
+#[belongs_to(User)]
+pub struct SecurityEvent {
+    id: SecurityEventID,
+    created_at: NaiveDateTime,
+
+    user_id: UserID,
+    kind: SecurityEventKind,
+    remote_ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl SecurityEvent {
+    pub fn id(&self) -> SecurityEventID {
+        self.id
+    }
+
+    pub fn kind(&self) -> SecurityEventKind {
+        self.kind
+    }
+
+    /// The stored form of the remote IP address. Depending
+    /// on the configuration, this may be truncated or hashed.
+    pub fn remote_ip(&self) -> Option<&str> {
+        self.remote_ip.as_deref()
+    }
+
+    pub fn user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+}
+
+impl SecurityEvent {
+    /// Record a security event for the given user. The remote IP and
+    /// user agent are taken from the request context. This must be
+    /// called within the transaction performing the action, such that
+    /// the action and its event are committed (or rolled back) together.
+    pub fn record<C>(
+        ctx: &Context,
+        conn: &C,
+        user_id: UserID,
+        kind: SecurityEventKind,
+    ) -> QueryResult<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let event = SecurityEvent {
+            id: SecurityEventID::new(),
+            created_at: ctx.now().naive_utc(),
+
+            user_id,
+            kind,
+            remote_ip: ctx.stored_remote_ip(),
+            user_agent: ctx.user_agent().map(str::to_string),
+        };
+        diesel::insert_into(security_events::table)
+            .values(&event)
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Whether an event of the given kind was ever
+    /// recorded for the given user.
+    pub fn exists<C>(conn: &C, user_id: UserID, kind: SecurityEventKind) -> QueryResult<bool>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let count: i64 = security_events::table
+            .filter(security_events::dsl::user_id.eq(user_id))
+            .filter(security_events::dsl::kind.eq(kind))
+            .count()
+            .get_result(conn)?;
+        Ok(count > 0)
+    }
+}
+
+impl<DB> ToSql<Text, DB> for SecurityEventKind
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            SecurityEventKind::LoginCodeRequested => "login_code_requested",
+            SecurityEventKind::LoginSucceeded => "login_succeeded",
+            SecurityEventKind::LoginFailed => "login_failed",
+            SecurityEventKind::SessionRevoked => "session_revoked",
+            SecurityEventKind::EmailChangeRequested => "email_change_requested",
+            SecurityEventKind::EmailChangeConfirmed => "email_change_confirmed",
+            SecurityEventKind::LoginLocked => "login_locked",
+            SecurityEventKind::LoginUnlocked => "login_unlocked",
+            SecurityEventKind::TokenCreated => "token_created",
+            SecurityEventKind::TokenRevoked => "token_revoked",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for SecurityEventKind
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "login_code_requested" => Ok(SecurityEventKind::LoginCodeRequested),
+            "login_succeeded" => Ok(SecurityEventKind::LoginSucceeded),
+            "login_failed" => Ok(SecurityEventKind::LoginFailed),
+            "session_revoked" => Ok(SecurityEventKind::SessionRevoked),
+            "email_change_requested" => Ok(SecurityEventKind::EmailChangeRequested),
+            "email_change_confirmed" => Ok(SecurityEventKind::EmailChangeConfirmed),
+            "login_locked" => Ok(SecurityEventKind::LoginLocked),
+            "login_unlocked" => Ok(SecurityEventKind::LoginUnlocked),
+            "token_created" => Ok(SecurityEventKind::TokenCreated),
+            "token_revoked" => Ok(SecurityEventKind::TokenRevoked),
+            _ => Err("Unrecognized security event kind".into()),
+        }
+    }
+}
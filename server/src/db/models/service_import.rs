@@ -0,0 +1,423 @@
+use crate::bookmark_source::{self, BookmarkSource};
+use crate::db::id::{ServiceImportID, UserID};
+use crate::db::models::import::{import_item, ImportItem};
+use crate::db::models::{ImportReport, ImportVisibility, User};
+use crate::error::RateLimited;
+use crate::schema::service_imports;
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::{Text, Timestamp};
+use juniper::GraphQLEnum;
+use std::collections::HashSet;
+use std::io::Write;
+
+/// Number of failed attempts to fetch a page after
+/// which an import is given up.
+const MAX_IMPORT_ATTEMPTS: i32 = 5;
+/// Longest credentials accepted for an import.
+const MAX_CREDENTIALS_LEN: usize = 256;
+
+/// A service bookmarks can be imported from.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[sql_type = "Text"]
+pub enum ImportService {
+    /// Pinboard, authenticated with the API token from the
+    /// password settings, which looks like `user:0123456789ABCDEF`.
+    Pinboard,
+    /// Pocket, authenticated with an access token obtained
+    /// through its OAuth flow.
+    Pocket,
+}
+
+impl ImportService {
+    fn name(&self) -> &'static str {
+        match self {
+            ImportService::Pinboard => "Pinboard",
+            ImportService::Pocket => "Pocket",
+        }
+    }
+}
+
+/// The progress of an import from a remote service.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum ServiceImportStatus {
+    /// The import is waiting for its next page to be fetched.
+    Pending,
+    /// A page of bookmarks is being imported.
+    Processing,
+    /// All bookmarks were imported.
+    Completed,
+    /// The import was given up after repeated failures.
+    Failed,
+}
+
+/// An import of the bookmarks of a user from a remote service, which
+/// runs in the background. Bookmarks are fetched one page at a time,
+/// and the position of the next page is kept, such that imports resume
+/// where they left off after failures. The credentials are only kept
+/// until the import completed or failed.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset, Associations)]
+#[belongs_to(User)]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct ServiceImport {
+    id: ServiceImportID,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+
+    user_id: UserID,
+    service: ImportService,
+    credentials: Option<String>,
+    status: ServiceImportStatus,
+    cursor: Option<String>,
+    imported: i32,
+    skipped_duplicates: i32,
+    error_count: i32,
+    last_error: Option<String>,
+    attempts: i32,
+    next_attempt_at: NaiveDateTime,
+    completed_at: Option<NaiveDateTime>,
+}
+
+/// Time to wait before fetching a page again, after the given
+/// number of failed attempts. This starts at five minutes, and
+/// doubles with each attempt.
+fn import_backoff(attempts: i32) -> Duration {
+    Duration::minutes(5 * 2i64.pow(attempts.clamp(1, 16) as u32 - 1))
+}
+
+impl ServiceImport {
+    pub fn id(&self) -> ServiceImportID {
+        self.id
+    }
+
+    pub fn user_id(&self) -> UserID {
+        self.user_id
+    }
+
+    pub fn service(&self) -> ImportService {
+        self.service
+    }
+
+    pub fn status(&self) -> ServiceImportStatus {
+        self.status
+    }
+
+    /// Number of bookmarks which were saved so far.
+    pub fn imported(&self) -> i32 {
+        self.imported
+    }
+
+    /// Number of bookmarks which were already
+    /// saved, and were skipped so far.
+    pub fn skipped_duplicates(&self) -> i32 {
+        self.skipped_duplicates
+    }
+
+    /// Number of bookmarks which could not be imported.
+    pub fn error_count(&self) -> i32 {
+        self.error_count
+    }
+
+    /// Why the last bookmark which could not be imported
+    /// failed, or why the import was given up.
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    pub fn completed_at(&self) -> Option<DateTime<Utc>> {
+        self.completed_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+}
+
+impl ServiceImport {
+    pub async fn find(ctx: &Context, id: ServiceImportID) -> Result<Self> {
+        let import = service_imports::table
+            .find(id)
+            .get_result(&*ctx.conn().await?)?;
+        Ok(import)
+    }
+
+    /// All imports requested by the given user,
+    /// most recent first.
+    pub async fn for_user(ctx: &Context, user_id: UserID) -> Result<Vec<Self>> {
+        let imports = service_imports::table
+            .filter(service_imports::dsl::user_id.eq(user_id))
+            .order_by(service_imports::dsl::created_at.desc())
+            .load(&*ctx.conn().await?)?;
+        Ok(imports)
+    }
+
+    /// Request an import of the bookmarks the currently logged in user
+    /// has stored with the given service, using the given credentials.
+    /// The bookmarks are saved to the reading list of the user, see
+    /// [`ImportVisibility::Private`]. Each user may only have one import
+    /// from each service in flight at any time.
+    pub async fn request(ctx: &Context, service: ImportService, credentials: &str) -> Result<Self> {
+        let user = ctx.verified_user().await?;
+        let credentials = credentials.trim();
+        if credentials.is_empty() || credentials.len() > MAX_CREDENTIALS_LEN {
+            return Err(anyhow!("Invalid credentials for {}", service.name()));
+        }
+        if bookmark_source::for_service(ctx.config(), service).is_none() {
+            return Err(anyhow!(
+                "Importing from {} is not available",
+                service.name()
+            ));
+        }
+
+        let import = ServiceImport {
+            id: ServiceImportID::new(),
+            created_at: ctx.now().naive_utc(),
+            updated_at: ctx.now().naive_utc(),
+
+            user_id: user.id(),
+            service,
+            credentials: Some(credentials.to_string()),
+            status: ServiceImportStatus::Pending,
+            cursor: None,
+            imported: 0,
+            skipped_duplicates: 0,
+            error_count: 0,
+            last_error: None,
+            attempts: 0,
+            next_attempt_at: ctx.now().naive_utc(),
+            completed_at: None,
+        };
+
+        // The check is part of the insert, such that
+        // concurrent requests can not both succeed.
+        let inserted = diesel::sql_query(
+            "INSERT INTO service_imports \
+                (id, created_at, updated_at, user_id, service, credentials, status, next_attempt_at) \
+            SELECT ?, ?, ?, ?, ?, ?, 'pending', ? \
+            WHERE NOT EXISTS (SELECT 1 FROM service_imports \
+                WHERE user_id = ? AND service = ? AND status IN ('pending', 'processing'))",
+        )
+        .bind::<Text, _>(import.id.as_str())
+        .bind::<Timestamp, _>(import.created_at)
+        .bind::<Timestamp, _>(import.updated_at)
+        .bind::<Text, _>(import.user_id.as_str())
+        .bind::<Text, _>(service)
+        .bind::<Text, _>(credentials)
+        .bind::<Timestamp, _>(import.next_attempt_at)
+        .bind::<Text, _>(import.user_id.as_str())
+        .bind::<Text, _>(service)
+        .execute(&*ctx.conn().await?)?;
+        if inserted != 1 {
+            return Err(anyhow!(
+                "An import from {} is already in progress, please wait for it to complete",
+                service.name()
+            ));
+        }
+        Ok(import)
+    }
+
+    /// Import the next page of all pending imports which are due,
+    /// using the sources configured for their services.
+    pub async fn process_pending(ctx: &Context) -> Result<()> {
+        Self::process_pending_with(ctx, |service| {
+            bookmark_source::for_service(ctx.config(), service)
+        })
+        .await
+    }
+
+    /// Import the next page of all pending imports which are due,
+    /// using the given sources. Imports from services without a
+    /// source are left pending.
+    pub async fn process_pending_with<F>(ctx: &Context, sources: F) -> Result<()>
+    where
+        F: Fn(ImportService) -> Option<Box<dyn BookmarkSource>>,
+    {
+        let pending: Vec<ServiceImport> = service_imports::table
+            .filter(service_imports::dsl::status.eq(ServiceImportStatus::Pending))
+            .filter(service_imports::dsl::next_attempt_at.le(ctx.now().naive_utc()))
+            .order_by(service_imports::dsl::next_attempt_at.asc())
+            .load(&*ctx.conn().await?)?;
+
+        for mut import in pending {
+            let source = match sources(import.service) {
+                Some(source) => source,
+                None => continue,
+            };
+            if let Err(err) = import.process(ctx, &*source).await {
+                log::error!("Failed to process service import {}: {}", import.id, err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetch and import the next page of bookmarks. Pages are fetched
+    /// no more often than the service allows, and failed attempts are
+    /// retried with an exponential backoff, until the import is given
+    /// up after [`MAX_IMPORT_ATTEMPTS`] attempts. Attempts the service
+    /// rejected due to its rate limits are retried when the service
+    /// asks for, and don't count as failed.
+    async fn process(&mut self, ctx: &Context, source: &dyn BookmarkSource) -> Result<()> {
+        // claim the import, so concurrent job runs
+        // do not fetch the same page twice
+        let claimed = diesel::update(&*self)
+            .filter(service_imports::dsl::status.eq(ServiceImportStatus::Pending))
+            .set((
+                service_imports::dsl::status.eq(ServiceImportStatus::Processing),
+                service_imports::dsl::updated_at.eq(ctx.now().naive_utc()),
+            ))
+            .execute(&*ctx.conn().await?)?;
+        if claimed != 1 {
+            return Ok(());
+        }
+        self.status = ServiceImportStatus::Processing;
+
+        let credentials = self.credentials.clone().unwrap_or_default();
+        let result = source
+            .fetch(ctx, &credentials, self.cursor.as_deref())
+            .await;
+        match result {
+            Ok(page) => {
+                let report = self.import_page(ctx, page.bookmarks).await?;
+                self.imported += report.imported;
+                self.skipped_duplicates += report.skipped_duplicates;
+                self.error_count += report.errors.len() as i32;
+                if let Some(error) = report.errors.last() {
+                    self.last_error = Some(error.clone());
+                }
+                self.attempts = 0;
+                self.next_attempt_at = (ctx.now() + source.request_interval()).naive_utc();
+                match page.next {
+                    Some(cursor) => {
+                        self.cursor = Some(cursor);
+                        self.status = ServiceImportStatus::Pending;
+                    }
+                    None => {
+                        self.cursor = None;
+                        self.credentials = None;
+                        self.status = ServiceImportStatus::Completed;
+                        self.completed_at = Some(ctx.now().naive_utc());
+                    }
+                }
+            }
+            Err(err) => match err.downcast::<RateLimited>() {
+                Ok(rate_limited) => {
+                    self.status = ServiceImportStatus::Pending;
+                    self.next_attempt_at = (ctx.now() + rate_limited.retry_after).naive_utc();
+                }
+                Err(err) => {
+                    log::info!("Failed to fetch bookmarks for import {}: {}", self.id, err);
+                    self.attempts += 1;
+                    self.last_error = Some(err.to_string());
+                    if self.attempts >= MAX_IMPORT_ATTEMPTS {
+                        self.credentials = None;
+                        self.status = ServiceImportStatus::Failed;
+                        self.completed_at = Some(ctx.now().naive_utc());
+                    } else {
+                        self.status = ServiceImportStatus::Pending;
+                        self.next_attempt_at =
+                            (ctx.now() + import_backoff(self.attempts)).naive_utc();
+                    }
+                }
+            },
+        }
+        self.updated_at = ctx.now().naive_utc();
+        *self = self.save_changes(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Save the given bookmarks for the user who requested the import,
+    /// keeping their tags, read status, and creation time.
+    async fn import_page(
+        &self,
+        ctx: &Context,
+        bookmarks: Vec<bookmark_source::SourceBookmark>,
+    ) -> Result<ImportReport> {
+        let ctx = ctx.acting_as(self.user_id);
+        let mut report = ImportReport::default();
+        let mut seen = HashSet::new();
+        for bookmark in bookmarks {
+            let item = ImportItem {
+                url: bookmark.url,
+                title: bookmark.title,
+                tags: bookmark.tags,
+                read: !bookmark.unread,
+                added_at: bookmark.added_at,
+            };
+            import_item(
+                &ctx,
+                item,
+                ImportVisibility::Private,
+                &mut report,
+                &mut seen,
+            )
+            .await?;
+        }
+        Ok(report)
+    }
+}
+
+impl<DB> ToSql<Text, DB> for ImportService
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            ImportService::Pinboard => "pinboard",
+            ImportService::Pocket => "pocket",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for ImportService
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "pinboard" => Ok(ImportService::Pinboard),
+            "pocket" => Ok(ImportService::Pocket),
+            _ => Err("Unrecognized import service".into()),
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for ServiceImportStatus
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            ServiceImportStatus::Pending => "pending",
+            ServiceImportStatus::Processing => "processing",
+            ServiceImportStatus::Completed => "completed",
+            ServiceImportStatus::Failed => "failed",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for ServiceImportStatus
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "pending" => Ok(ServiceImportStatus::Pending),
+            "processing" => Ok(ServiceImportStatus::Processing),
+            "completed" => Ok(ServiceImportStatus::Completed),
+            "failed" => Ok(ServiceImportStatus::Failed),
+            _ => Err("Unrecognized service import status".into()),
+        }
+    }
+}
@@ -0,0 +1,641 @@
+use crate::db::id::{UrlID, UserID};
+use crate::db::models::{SubmissionVisibility, TagLog};
+use crate::schema::{tag_follows, tag_log, tag_redirects, tags, url_tags, urls};
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::sqlite::Sqlite;
+use juniper::{GraphQLEnum, GraphQLObject};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Maximum number of tags a single submission may have.
+pub const MAX_TAGS_PER_URL: usize = 5;
+const MIN_TAG_LEN: usize = 2;
+const MAX_TAG_LEN: usize = 30;
+/// Maximum number of suggestions returned for a prefix.
+pub const MAX_SUGGESTIONS: i64 = 25;
+
+/// A tag used to categorize submissions. Tags are identified
+/// by their normalized name, and are created the first time
+/// they are used.
+#[derive(Debug, Clone, Queryable, Insertable)]
+pub struct Tag {
+    name: String,
+    created_at: NaiveDateTime,
+    url_count: i64,
+}
+
+/// Determines how tags are ordered when listing
+/// all tags.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagSort {
+    /// Most used tags first.
+    Popular,
+    /// Most recently created tags first.
+    Newest,
+}
+
+/// Position of a tag in the list of all tags. This holds the values
+/// the tag is sorted by, such that pages stay stable when the tag
+/// the cursor points to changes or disappears.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagCursor {
+    url_count: i64,
+    created_at: NaiveDateTime,
+    name: String,
+}
+
+impl fmt::Display for TagCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = format!(
+            "{}:{}:{}",
+            self.url_count,
+            self.created_at.timestamp_nanos(),
+            self.name
+        );
+        write!(f, "{}", base64::encode_config(raw, base64::URL_SAFE_NO_PAD))
+    }
+}
+
+impl FromStr for TagCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid tag cursor";
+        let raw = base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|_| ERR)?;
+        let raw = String::from_utf8(raw).map_err(|_| ERR)?;
+        let mut parts = raw.splitn(3, ':');
+        let url_count = parts.next().and_then(|c| c.parse().ok()).ok_or(ERR)?;
+        let nanos: i64 = parts.next().and_then(|t| t.parse().ok()).ok_or(ERR)?;
+        let name = parts.next().ok_or(ERR)?.to_string();
+        let created_at = NaiveDateTime::from_timestamp_opt(
+            nanos.div_euclid(1_000_000_000),
+            nanos.rem_euclid(1_000_000_000) as u32,
+        )
+        .ok_or(ERR)?;
+        Ok(Self {
+            url_count,
+            created_at,
+            name,
+        })
+    }
+}
+
+impl Tag {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    /// Number of submissions using this tag.
+    pub fn url_count(&self) -> i64 {
+        self.url_count
+    }
+
+    pub fn cursor(&self) -> TagCursor {
+        TagCursor {
+            url_count: self.url_count,
+            created_at: self.created_at,
+            name: self.name.clone(),
+        }
+    }
+}
+
+/// A tag suggested for a prefix, and how often it is used.
+#[derive(Debug, Clone, GraphQLObject)]
+pub struct TagSuggestion {
+    /// The normalized name of the tag.
+    pub name: String,
+    /// Number of submissions using the tag.
+    pub count: i32,
+}
+
+/// Convert the given text to lower case kebab form.
+fn kebab_case(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Normalize the given tag to lower case kebab form, such that
+/// `Rust Lang` and `rust-lang` are the same tag. This fails if
+/// the normalized tag is too short or too long.
+pub fn normalize(tag: &str) -> Result<String> {
+    let normalized = kebab_case(tag);
+    let len = normalized.chars().count();
+    if !(MIN_TAG_LEN..=MAX_TAG_LEN).contains(&len) {
+        return Err(anyhow!(
+            "Invalid tag \"{}\", tags must have between {} and {} characters",
+            tag,
+            MIN_TAG_LEN,
+            MAX_TAG_LEN
+        ));
+    }
+    Ok(normalized)
+}
+
+/// Normalize a list of tags, removing duplicates. This fails if any
+/// tag is invalid, or if there are more than [`MAX_TAGS_PER_URL`]
+/// distinct tags.
+pub fn normalize_all(tags: &[String]) -> Result<Vec<String>> {
+    let mut normalized = vec![];
+    for tag in tags {
+        let tag = normalize(tag)?;
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TAGS_PER_URL {
+        return Err(anyhow!(
+            "Submissions can have at most {} tags",
+            MAX_TAGS_PER_URL
+        ));
+    }
+    Ok(normalized)
+}
+
+impl Tag {
+    /// The names of the existing tags among the given normalized
+    /// names, e.g. those returned by [`markdown::hashtags`](crate::markdown::hashtags).
+    pub(crate) fn existing<C>(conn: &C, names: &[String]) -> Result<Vec<String>>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(tags::table
+            .filter(tags::dsl::name.eq_any(names))
+            .select(tags::dsl::name)
+            .load(conn)?)
+    }
+
+    /// The name of the tag the given normalized name refers to. Tags
+    /// which were renamed or merged into another tag redirect to it,
+    /// such that old links and filters keep working.
+    pub(crate) fn resolve<C>(conn: &C, name: String) -> QueryResult<String>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let target = tag_redirects::table
+            .find(&name)
+            .select(tag_redirects::dsl::target)
+            .get_result(conn)
+            .optional()?;
+        Ok(target.unwrap_or(name))
+    }
+
+    /// Resolve each of the given normalized names, removing tags
+    /// which turn out to be the same, see [`resolve`](Tag::resolve).
+    fn resolve_all<C>(conn: &C, names: &[String]) -> QueryResult<Vec<String>>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let mut resolved = vec![];
+        for name in names {
+            let name = Self::resolve(conn, name.clone())?;
+            if !resolved.contains(&name) {
+                resolved.push(name);
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Follow the given tag as the currently logged in user, listing
+    /// its submissions in their following feed. Following an already
+    /// followed tag has no effect. Following a renamed or merged tag
+    /// follows the tag it redirects to.
+    pub async fn follow(ctx: &Context, name: &str) -> Result<Self> {
+        let user_id = ctx.user_id()?;
+        let name = Self::resolve(&*ctx.conn().await?, normalize(name)?)?;
+        let tag: Self = tags::table
+            .find(&name)
+            .get_result(&*ctx.conn().await?)
+            .optional()?
+            .ok_or_else(|| anyhow!("Unknown tag \"{}\"", name))?;
+        diesel::insert_or_ignore_into(tag_follows::table)
+            .values((
+                tag_follows::dsl::user_id.eq(user_id),
+                tag_follows::dsl::tag_name.eq(&tag.name),
+                tag_follows::dsl::created_at.eq(ctx.now().naive_utc()),
+            ))
+            .execute(&*ctx.conn().await?)?;
+        Ok(tag)
+    }
+
+    /// Unfollow the given tag for the currently logged in user.
+    pub async fn unfollow(ctx: &Context, name: &str) -> Result<()> {
+        let name = Self::resolve(&*ctx.conn().await?, normalize(name)?)?;
+        let follow = tag_follows::table
+            .filter(tag_follows::dsl::user_id.eq(ctx.user_id()?))
+            .filter(tag_follows::dsl::tag_name.eq(name));
+        diesel::delete(follow).execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Tags followed by the given user, ordered by name.
+    pub async fn followed_by(ctx: &Context, user_id: UserID) -> Result<Vec<Self>> {
+        Ok(tags::table
+            .inner_join(tag_follows::table)
+            .filter(tag_follows::dsl::user_id.eq(user_id))
+            .order_by(tags::dsl::name.asc())
+            .select(tags::all_columns)
+            .load(&*ctx.conn().await?)?)
+    }
+
+    /// Tags of the given URL, ordered by name.
+    pub async fn for_url(ctx: &Context, url_id: UrlID) -> Result<Vec<Self>> {
+        Ok(tags::table
+            .inner_join(url_tags::table)
+            .filter(url_tags::dsl::url_id.eq(url_id))
+            .order_by(tags::dsl::name.asc())
+            .select(tags::all_columns)
+            .load(&*ctx.conn().await?)?)
+    }
+
+    /// Suggest tags starting with the given prefix, most used first.
+    /// At most [`MAX_SUGGESTIONS`] tags are returned, and prefixes
+    /// shorter than two characters yield no suggestions.
+    pub async fn suggestions(
+        ctx: &Context,
+        prefix: &str,
+        limit: i64,
+    ) -> Result<Vec<TagSuggestion>> {
+        let prefix = kebab_case(prefix);
+        if prefix.chars().count() < MIN_TAG_LEN {
+            return Ok(vec![]);
+        }
+        // a range on the primary key, rather than `LIKE`, such
+        // that the lookup uses the index
+        let upper = format!("{}\u{10ffff}", prefix);
+        let suggestions: Vec<(String, i64)> = tags::table
+            .filter(tags::dsl::name.ge(&prefix))
+            .filter(tags::dsl::name.lt(&upper))
+            .filter(tags::dsl::url_count.gt(0))
+            .select((tags::dsl::name, tags::dsl::url_count))
+            .order_by(tags::dsl::url_count.desc())
+            .then_order_by(tags::dsl::name.asc())
+            .limit(limit.clamp(0, MAX_SUGGESTIONS))
+            .load(&*ctx.conn().await?)?;
+        Ok(suggestions
+            .into_iter()
+            .map(|(name, count)| TagSuggestion {
+                name,
+                count: count as i32,
+            })
+            .collect())
+    }
+
+    /// Returns all tags which are in use, in the given order, in a way
+    /// that's suitable for use with a Relay connection.
+    pub async fn all(
+        ctx: &Context,
+        sort: TagSort,
+        after: Option<TagCursor>,
+        before: Option<TagCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        use tags::dsl::{created_at, name, url_count};
+
+        let conn = ctx.conn().await?;
+        let mut query = tags::table.filter(url_count.gt(0)).into_boxed();
+        query = match sort {
+            TagSort::Popular => query.order_by(url_count.desc()),
+            TagSort::Newest => query.order_by(created_at.desc()),
+        }
+        .then_order_by(name.asc());
+
+        if let Some(after) = after {
+            query = match sort {
+                TagSort::Popular => query.filter(
+                    url_count
+                        .lt(after.url_count)
+                        .or(url_count.eq(after.url_count).and(name.gt(after.name))),
+                ),
+                TagSort::Newest => query.filter(
+                    created_at
+                        .lt(after.created_at)
+                        .or(created_at.eq(after.created_at).and(name.gt(after.name))),
+                ),
+            };
+        }
+
+        if let Some(before) = before {
+            query = match sort {
+                TagSort::Popular => query.filter(
+                    url_count
+                        .gt(before.url_count)
+                        .or(url_count.eq(before.url_count).and(name.lt(before.name))),
+                ),
+                TagSort::Newest => query.filter(
+                    created_at
+                        .gt(before.created_at)
+                        .or(created_at.eq(before.created_at).and(name.lt(before.name))),
+                ),
+            };
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        Ok(query.load(&*conn)?)
+    }
+
+    /// Replace the tags of the given URL. Tags which don't exist yet
+    /// are created, and the usage counts of added and removed tags are
+    /// updated. Drafts and unlisted URLs don't count towards the usage
+    /// of their tags, such that tags of private bookmarks aren't
+    /// revealed, see [`count_for_url`](Tag::count_for_url). The tags
+    /// must already be normalized, see [`normalize_all`]. Renamed and
+    /// merged tags are replaced by the tag they redirect to.
+    pub(crate) async fn set_for_url(ctx: &Context, url_id: UrlID, names: &[String]) -> Result<()> {
+        let now = ctx.now().naive_utc();
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            let names = &Self::resolve_all(&*conn, names)?;
+            let (draft, visibility): (bool, SubmissionVisibility) = urls::table
+                .find(url_id)
+                .select((urls::dsl::draft, urls::dsl::visibility))
+                .get_result(&*conn)?;
+            let counted = !draft && visibility == SubmissionVisibility::Public;
+            let new_tags: Vec<_> = names
+                .iter()
+                .map(|name| (tags::dsl::name.eq(name), tags::dsl::created_at.eq(now)))
+                .collect();
+            for tag in &new_tags {
+                diesel::insert_or_ignore_into(tags::table)
+                    .values(tag)
+                    .execute(&*conn)?;
+            }
+
+            let current: Vec<String> = url_tags::table
+                .filter(url_tags::dsl::url_id.eq(url_id))
+                .select(url_tags::dsl::tag_name)
+                .load(&*conn)?;
+            let removed: Vec<String> = current
+                .iter()
+                .filter(|name| !names.contains(name))
+                .cloned()
+                .collect();
+            let added: Vec<String> = names
+                .iter()
+                .filter(|name| !current.contains(name))
+                .cloned()
+                .collect();
+
+            let removed_url_tags = url_tags::table
+                .filter(url_tags::dsl::url_id.eq(url_id))
+                .filter(url_tags::dsl::tag_name.eq_any(&removed));
+            diesel::delete(removed_url_tags).execute(&*conn)?;
+            if counted {
+                diesel::update(tags::table.filter(tags::dsl::name.eq_any(&removed)))
+                    .set(tags::dsl::url_count.eq(tags::dsl::url_count - 1))
+                    .execute(&*conn)?;
+            }
+
+            let new_url_tags: Vec<_> = added
+                .iter()
+                .map(|name| {
+                    (
+                        url_tags::dsl::url_id.eq(url_id),
+                        url_tags::dsl::tag_name.eq(name),
+                    )
+                })
+                .collect();
+            for url_tag in &new_url_tags {
+                diesel::insert_into(url_tags::table)
+                    .values(url_tag)
+                    .execute(&*conn)?;
+            }
+            if counted {
+                diesel::update(tags::table.filter(tags::dsl::name.eq_any(&added)))
+                    .set(tags::dsl::url_count.eq(tags::dsl::url_count + 1))
+                    .execute(&*conn)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Count the tags of the given URL towards their usage, once
+    /// the URL is no longer a draft or is made public, with a `delta`
+    /// of one, or stop counting them once it is unlisted, with minus one.
+    pub(crate) async fn count_for_url(ctx: &Context, url_id: UrlID, delta: i64) -> Result<()> {
+        let names = url_tags::table
+            .filter(url_tags::dsl::url_id.eq(url_id))
+            .select(url_tags::dsl::tag_name);
+        diesel::update(tags::table.filter(tags::dsl::name.eq_any(names)))
+            .set(tags::dsl::url_count.eq(tags::dsl::url_count + delta))
+            .execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Rename a tag as an administrator, see [`merge`](Tag::merge).
+    /// This fails if a tag with the new name already exists, in which
+    /// case the tags should be merged instead.
+    pub async fn rename(ctx: &Context, from: &str, to: &str) -> Result<Self> {
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.manage_tags())
+            .await?;
+        let to = normalize(to)?;
+        let exists = tags::table
+            .find(&to)
+            .count()
+            .get_result::<i64>(&*ctx.conn().await?)?
+            > 0;
+        if exists {
+            return Err(anyhow!(
+                "The tag \"{}\" already exists, merge the tags instead",
+                to
+            ));
+        }
+        Self::merge(ctx, &[from.to_string()], &to).await
+    }
+
+    /// Merge the given tags into the target tag as an administrator,
+    /// creating the target tag if it doesn't exist yet. Submissions
+    /// and followers of the merged tags are moved to the target tag,
+    /// and the merged tags are replaced by redirects to it. As each
+    /// submission ends up with at most as many tags as it had, this
+    /// never exceeds [`MAX_TAGS_PER_URL`]; submissions which had more
+    /// than one of the tags get the target tag once. The merge is
+    /// recorded in the tag log.
+    pub async fn merge(ctx: &Context, sources: &[String], target: &str) -> Result<Self> {
+        let admin = ctx.user().await?;
+        admin
+            .check_permissions(ctx, |perm| perm.manage_tags())
+            .await?;
+        let target = normalize(target)?;
+        let mut names: Vec<String> = vec![];
+        for source in sources {
+            let source = normalize(source)?;
+            if source != target && !names.contains(&source) {
+                names.push(source);
+            }
+        }
+        if names.is_empty() {
+            return Err(anyhow!("There are no tags to merge"));
+        }
+
+        let now = ctx.now().naive_utc();
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            let known: Vec<String> = tags::table
+                .filter(tags::dsl::name.eq_any(&names))
+                .select(tags::dsl::name)
+                .load(&*conn)?;
+            if let Some(unknown) = names.iter().find(|name| !known.contains(name)) {
+                return Err(anyhow!("Unknown tag \"{}\"", unknown));
+            }
+            diesel::delete(tag_redirects::table.find(&target)).execute(&*conn)?;
+            diesel::insert_or_ignore_into(tags::table)
+                .values((tags::dsl::name.eq(&target), tags::dsl::created_at.eq(now)))
+                .execute(&*conn)?;
+
+            // move the submissions which don't have the target tag yet,
+            // counting those which aren't drafts or unlisted towards its usage
+            let tagged: Vec<(UrlID, bool, SubmissionVisibility)> = url_tags::table
+                .inner_join(urls::table)
+                .filter(url_tags::dsl::tag_name.eq_any(&names))
+                .select((
+                    url_tags::dsl::url_id,
+                    urls::dsl::draft,
+                    urls::dsl::visibility,
+                ))
+                .load(&*conn)?;
+            let mut has_target: HashSet<UrlID> = url_tags::table
+                .filter(url_tags::dsl::tag_name.eq(&target))
+                .select(url_tags::dsl::url_id)
+                .load::<UrlID>(&*conn)?
+                .into_iter()
+                .collect();
+            let mut moved = vec![];
+            let mut counted = 0;
+            for (url_id, draft, visibility) in tagged {
+                if has_target.insert(url_id) {
+                    moved.push((
+                        url_tags::dsl::url_id.eq(url_id),
+                        url_tags::dsl::tag_name.eq(&target),
+                    ));
+                    if !draft && visibility == SubmissionVisibility::Public {
+                        counted += 1;
+                    }
+                }
+            }
+            diesel::delete(url_tags::table.filter(url_tags::dsl::tag_name.eq_any(&names)))
+                .execute(&*conn)?;
+            for url_tag in &moved {
+                diesel::insert_into(url_tags::table)
+                    .values(url_tag)
+                    .execute(&*conn)?;
+            }
+            diesel::update(tags::table.find(&target))
+                .set(tags::dsl::url_count.eq(tags::dsl::url_count + counted))
+                .execute(&*conn)?;
+
+            let follows: Vec<(UserID, NaiveDateTime)> = tag_follows::table
+                .filter(tag_follows::dsl::tag_name.eq_any(&names))
+                .select((tag_follows::dsl::user_id, tag_follows::dsl::created_at))
+                .load(&*conn)?;
+            let follows: Vec<_> = follows
+                .into_iter()
+                .map(|(user_id, created_at)| {
+                    (
+                        tag_follows::dsl::user_id.eq(user_id),
+                        tag_follows::dsl::tag_name.eq(&target),
+                        tag_follows::dsl::created_at.eq(created_at),
+                    )
+                })
+                .collect();
+            diesel::delete(tag_follows::table.filter(tag_follows::dsl::tag_name.eq_any(&names)))
+                .execute(&*conn)?;
+            for follow in &follows {
+                diesel::insert_or_ignore_into(tag_follows::table)
+                    .values(follow)
+                    .execute(&*conn)?;
+            }
+
+            // redirects to the merged tags are pointed at the target,
+            // such that redirects never chain
+            diesel::update(tag_redirects::table.filter(tag_redirects::dsl::target.eq_any(&names)))
+                .set(tag_redirects::dsl::target.eq(&target))
+                .execute(&*conn)?;
+            let redirects: Vec<_> = names
+                .iter()
+                .map(|name| {
+                    (
+                        tag_redirects::dsl::name.eq(name),
+                        tag_redirects::dsl::target.eq(&target),
+                        tag_redirects::dsl::created_at.eq(now),
+                    )
+                })
+                .collect();
+            diesel::delete(tags::table.filter(tags::dsl::name.eq_any(&names))).execute(&*conn)?;
+            for redirect in &redirects {
+                diesel::replace_into(tag_redirects::table)
+                    .values(redirect)
+                    .execute(&*conn)?;
+            }
+
+            let entry = TagLog::new(admin.id(), &names, &target, moved.len() as i64, now);
+            diesel::insert_into(tag_log::table)
+                .values(&entry)
+                .execute(&*conn)?;
+            Ok(tags::table.find(&target).get_result(&*conn)?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let cases = [
+            ("rust", "rust"),
+            ("Rust", "rust"),
+            ("  rust  ", "rust"),
+            ("Rust Lang", "rust-lang"),
+            ("rust_lang", "rust-lang"),
+            ("--rust--lang--", "rust-lang"),
+            ("Machine  Learning!", "machine-learning"),
+            ("Node.js", "node-js"),
+            ("Über", "über"),
+        ];
+        for (tag, normalized) in cases {
+            assert_eq!(normalize(tag).unwrap(), normalized, "{}", tag);
+        }
+    }
+
+    #[test]
+    fn test_normalize_rejects_invalid() {
+        let long = "a".repeat(31);
+        for tag in ["", "a", "!!", "c#", long.as_str()] {
+            let err = normalize(tag).unwrap_err();
+            assert!(err.to_string().contains(&format!("\"{}\"", tag)), "{}", tag);
+        }
+    }
+
+    #[test]
+    fn test_normalize_all() {
+        let tags: Vec<String> = ["Rust", "rust", "Web Dev", "web-dev"]
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect();
+        assert_eq!(normalize_all(&tags).unwrap(), vec!["rust", "web-dev"]);
+
+        let tags: Vec<String> = (0..6).map(|i| format!("tag-{}", i)).collect();
+        assert!(normalize_all(&tags).is_err());
+        assert_eq!(normalize_all(&tags[..5]).unwrap().len(), 5);
+    }
+}
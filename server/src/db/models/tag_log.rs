@@ -0,0 +1,149 @@
+use crate::db::id::{TagLogID, UserID};
+use crate::db::models::User;
+use crate::schema::tag_log;
+use crate::Context;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// An entry in the tag log, recording which administrator renamed
+/// or merged which tags. Entries are only visible to administrators.
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "tag_log"]
+pub struct TagLog {
+    id: TagLogID,
+    created_at: NaiveDateTime,
+    admin_id: UserID,
+    /// Comma separated names of the renamed or merged tags.
+    sources: String,
+    target: String,
+    url_count: i64,
+}
+
+/// Position of an entry in the tag log, which
+/// lists the most recent entries first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagLogCursor {
+    created_at: NaiveDateTime,
+    id: TagLogID,
+}
+
+impl fmt::Display for TagLogCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = format!("{}:{}", self.created_at.timestamp_nanos(), self.id);
+        write!(f, "{}", base64::encode_config(raw, base64::URL_SAFE_NO_PAD))
+    }
+}
+
+impl FromStr for TagLogCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid tag log cursor";
+        let raw = base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|_| ERR)?;
+        let raw = String::from_utf8(raw).map_err(|_| ERR)?;
+        let mut parts = raw.splitn(2, ':');
+        let nanos: i64 = parts.next().and_then(|t| t.parse().ok()).ok_or(ERR)?;
+        let id = parts.next().and_then(|id| id.parse().ok()).ok_or(ERR)?;
+        let created_at = NaiveDateTime::from_timestamp_opt(
+            nanos.div_euclid(1_000_000_000),
+            nanos.rem_euclid(1_000_000_000) as u32,
+        )
+        .ok_or(ERR)?;
+        Ok(Self { created_at, id })
+    }
+}
+
+impl TagLog {
+    pub(crate) fn new(
+        admin_id: UserID,
+        sources: &[String],
+        target: &str,
+        url_count: i64,
+        now: NaiveDateTime,
+    ) -> Self {
+        Self {
+            id: TagLogID::new(),
+            created_at: now,
+            admin_id,
+            sources: sources.join(","),
+            target: target.to_string(),
+            url_count,
+        }
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    /// The names the tags had before they were renamed or merged.
+    pub fn sources(&self) -> Vec<String> {
+        self.sources.split(',').map(str::to_string).collect()
+    }
+
+    /// The name of the tag the tags were renamed or merged into.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Number of submissions which were given the target tag.
+    /// This leaves out submissions which already had it.
+    pub fn url_count(&self) -> i64 {
+        self.url_count
+    }
+
+    pub async fn admin(&self, ctx: &Context) -> Result<User> {
+        User::find(ctx, self.admin_id).await
+    }
+
+    pub fn cursor(&self) -> TagLogCursor {
+        TagLogCursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
+impl TagLog {
+    /// Returns the tag log, most recent entries first, in a way
+    /// that's suitable for use with a Relay connection. This is only
+    /// available to administrators.
+    pub async fn all(
+        ctx: &Context,
+        after: Option<TagLogCursor>,
+        before: Option<TagLogCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        use tag_log::dsl::{created_at, id};
+
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.manage_tags())
+            .await?;
+        let conn = ctx.conn().await?;
+        let mut query = tag_log::table
+            .order_by(created_at.desc())
+            .then_order_by(id.desc())
+            .into_boxed();
+        if let Some(after) = after {
+            query = query.filter(
+                created_at
+                    .lt(after.created_at)
+                    .or(created_at.eq(after.created_at).and(id.lt(after.id))),
+            );
+        }
+        if let Some(before) = before {
+            query = query.filter(
+                created_at
+                    .gt(before.created_at)
+                    .or(created_at.eq(before.created_at).and(id.gt(before.id))),
+            );
+        }
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        Ok(query.load(&*conn)?)
+    }
+}
@@ -0,0 +1,140 @@
+use crate::db::id::UserID;
+use crate::db::models::UserPreferences;
+use crate::{signing, Context};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use juniper::GraphQLEnum;
+use std::convert::TryFrom;
+
+const TOKEN_PURPOSE: &str = "unsubscribe";
+const TOKEN_VALID_DAYS: i64 = 180;
+
+/// Categories of emails users can unsubscribe from.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailCategory {
+    /// Notifications about logins from new devices.
+    NewLogin,
+    /// Digests of the top submissions.
+    Digest,
+}
+
+impl EmailCategory {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            EmailCategory::NewLogin => "new_login",
+            EmailCategory::Digest => "digest",
+        }
+    }
+
+    /// Human readable description of the emails
+    /// in this category.
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            EmailCategory::NewLogin => "new sign-in notifications",
+            EmailCategory::Digest => "digests of top submissions",
+        }
+    }
+}
+
+impl TryFrom<&str> for EmailCategory {
+    type Error = anyhow::Error;
+
+    fn try_from(category: &str) -> Result<Self> {
+        match category {
+            "new_login" => Ok(EmailCategory::NewLogin),
+            "digest" => Ok(EmailCategory::Digest),
+            _ => Err(anyhow!("Unrecognized email category")),
+        }
+    }
+}
+
+/// A signed token which allows unsubscribing a user from
+/// a category of emails, without requiring the user to be
+/// logged in. Tokens are included in outgoing emails.
+#[derive(Debug, Clone)]
+pub struct UnsubscribeToken {
+    user_id: UserID,
+    category: EmailCategory,
+    expires_at: NaiveDateTime,
+}
+
+impl UnsubscribeToken {
+    /// Create a new token for the given user and category.
+    pub fn new(ctx: &Context, user_id: UserID, category: EmailCategory) -> Self {
+        Self {
+            user_id,
+            category,
+            expires_at: (ctx.now() + Duration::days(TOKEN_VALID_DAYS)).naive_utc(),
+        }
+    }
+
+    pub fn user_id(&self) -> UserID {
+        self.user_id
+    }
+
+    pub fn category(&self) -> EmailCategory {
+        self.category
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.expires_at, Utc)
+    }
+
+    /// Encode and sign this token.
+    pub fn encode(&self, ctx: &Context) -> String {
+        let payload = format!(
+            "{}:{}:{}",
+            self.user_id,
+            self.category.as_str(),
+            self.expires_at.timestamp()
+        );
+        signing::sign(ctx, TOKEN_PURPOSE, &payload)
+    }
+
+    /// Decode a token, verifying it's signature and
+    /// expiry. Any tampered, malformed, or expired token
+    /// is rejected with the same error.
+    pub fn decode(ctx: &Context, token: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid or expired unsubscribe token");
+
+        let payload = signing::verify(ctx, TOKEN_PURPOSE, token).ok_or_else(invalid)?;
+        let mut parts = payload.split(':');
+        let (user_id, category, expires_at) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(user_id), Some(category), Some(expires_at)) => (user_id, category, expires_at),
+            _ => return Err(invalid()),
+        };
+        let token = Self {
+            user_id: user_id.parse().map_err(|_| invalid())?,
+            category: EmailCategory::try_from(category).map_err(|_| invalid())?,
+            expires_at: NaiveDateTime::from_timestamp_opt(
+                expires_at.parse().map_err(|_| invalid())?,
+                0,
+            )
+            .ok_or_else(invalid)?,
+        };
+        if token.expires_at() < ctx.now() {
+            return Err(invalid());
+        }
+        Ok(token)
+    }
+
+    /// Unsubscribe the user from the tokens email category.
+    /// Applying a token more than once has no further effect.
+    pub async fn apply(&self, ctx: &Context) -> Result<()> {
+        let mut preferences = UserPreferences::find(ctx, self.user_id).await?;
+        preferences.set_subscribed(self.category, false);
+        preferences.save(ctx).await
+    }
+
+    /// Text to append to outgoing emails of the given
+    /// category, containing a link to unsubscribe.
+    pub fn email_footer(ctx: &Context, user_id: UserID, category: EmailCategory) -> String {
+        let token = Self::new(ctx, user_id, category);
+        format!(
+            "To stop receiving {}, visit https://{}/unsubscribe/{}",
+            category.describe(),
+            ctx.config().hostname(),
+            token.encode(ctx),
+        )
+    }
+}
@@ -0,0 +1,117 @@
+use super::{ArchiveStatus, NewUrlInput, Url};
+use crate::db::id::UserID;
+use crate::db::models::{Tag, Webhook};
+use crate::events::Event;
+use crate::schema::urls;
+use crate::{canonical, Context};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+
+impl Url {
+    /// Creates a new draft, which is not listed anywhere and not
+    /// archived until it is published, see [`publish`](Url::publish).
+    /// The linked page is crawled for meta data in the background,
+    /// since drafts are created in bulk when importing bookmarks. For
+    /// the same reason, the URL is canonicalized without resolving it.
+    /// The tags must already be normalized, see
+    /// [`tag::normalize_all`](crate::db::models::tag::normalize_all).
+    pub(crate) async fn create_draft(
+        ctx: &Context,
+        url: &str,
+        title: Option<String>,
+        tags: Vec<String>,
+        created_by: UserID,
+        created_at: DateTime<Utc>,
+    ) -> Result<Self> {
+        let input = NewUrlInput {
+            url: Some(url.to_string()),
+            text: None,
+            title,
+            description: None,
+            tags: Some(tags),
+            nsfw: None,
+            anonymous: None,
+            visibility: None,
+            group_id: None,
+            publish_at: None,
+        };
+        let canonical_url = canonical::canonicalize(url, ctx.config().tracking_params())?;
+        Self::insert(
+            ctx,
+            input,
+            Some(canonical_url),
+            None,
+            created_by,
+            true,
+            created_at,
+        )
+        .await
+    }
+
+    /// Publishes this URL if it is a draft, such that it is listed like
+    /// any other submission from now on, dated to the time it was
+    /// published. Only the author may publish their drafts. Scheduled
+    /// drafts are published right away.
+    pub async fn publish(&mut self, ctx: &Context) -> Result<()> {
+        let user = ctx.verified_user().await?;
+        if self.created_by != user.id() {
+            return Err(anyhow!("Not authorized"));
+        }
+        if !self.draft || self.is_deleted() {
+            return Ok(());
+        }
+        self.publish_draft(ctx, user.id(), None).await
+    }
+
+    /// Turn this draft into a submission by the given user, with the
+    /// current title and description. If a time to publish it is given,
+    /// it stays a draft until then instead, see
+    /// [`publish_scheduled`](Url::publish_scheduled).
+    pub(super) async fn publish_draft(
+        &mut self,
+        ctx: &Context,
+        created_by: UserID,
+        publish_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let now = ctx.now().naive_utc();
+        let draft = publish_at.is_some();
+        let archive_status = ctx
+            .archiver()
+            .map(|_| ArchiveStatus::Pending)
+            .filter(|_| !draft);
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set((
+                urls::dsl::draft.eq(draft),
+                urls::dsl::publish_at.eq(publish_at.map(|at| at.naive_utc())),
+                urls::dsl::created_by.eq(created_by),
+                urls::dsl::title.eq(&self.title),
+                urls::dsl::description.eq(&self.description),
+                urls::dsl::description_html.eq(&self.description_html),
+                urls::dsl::nsfw.eq(self.nsfw),
+                urls::dsl::anonymous.eq(self.anonymous),
+                urls::dsl::held_at.eq(self.held_at),
+                urls::dsl::visibility.eq(self.visibility),
+                urls::dsl::group_id.eq(self.group_id),
+                urls::dsl::created_at.eq(now),
+                urls::dsl::published_at.eq(now),
+                urls::dsl::updated_at.eq(now),
+                urls::dsl::archive_status.eq(archive_status),
+                urls::dsl::next_archive_at.eq(archive_status.map(|_| now)),
+            ))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        drop(conn);
+        if self.draft || self.is_unlisted() {
+            return Ok(());
+        }
+        Tag::count_for_url(ctx, self.id, 1).await?;
+        ctx.search().index_url(self)?;
+        if !self.is_held() {
+            Webhook::url_submitted(ctx, self).await;
+            ctx.events().publish(Event::UrlSubmitted(self.id()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+use super::{SubmissionVisibility, Url};
+use crate::db::id::{UrlID, UserID};
+use crate::db::models::{Block, Group, MutedDomain};
+use crate::schema::urls;
+use crate::Context;
+use anyhow::Result;
+use diesel::expression::{BoxableExpression, SelectableExpression};
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use diesel::sqlite::Sqlite;
+
+/// Which submissions a listing includes for the viewer, as a single
+/// filter on the query of the listing, see [`Listed::filter`]. Deleted
+/// submissions are never listed, and neither are removed or held
+/// submissions hidden from the viewer: removed submissions are hidden
+/// from everyone, while shadow removed submissions stay visible to
+/// their author and to moderators, and held submissions to their author.
+#[derive(Debug, Clone)]
+pub struct Listed {
+    viewer: Option<UserID>,
+    moderator: bool,
+    /// Values of the NSFW mark of listed submissions, if NSFW
+    /// submissions hidden from the viewer are excluded.
+    nsfw: Option<Vec<bool>>,
+    /// Whether drafts and unlisted submissions are excluded.
+    public: bool,
+    /// The user whose drafts are listed, unless `public` is set.
+    drafts_of: Option<UserID>,
+    blocked: bool,
+    muted: bool,
+    syndicated: bool,
+}
+
+impl Listed {
+    /// The submissions listed to the viewer, which excludes drafts,
+    /// unlisted submissions, NSFW submissions hidden from the viewer,
+    /// and submissions by users blocked by the viewer.
+    pub(crate) async fn to_viewer(ctx: &Context) -> Result<Self> {
+        Ok(Self {
+            nsfw: Some(Url::listed_nsfw(ctx).await?),
+            public: true,
+            blocked: true,
+            ..Self::curated_by(ctx, None).await?
+        })
+    }
+
+    /// The submissions included in lists curated by the given user,
+    /// like their saved submissions, which includes their drafts and
+    /// unlisted submissions.
+    pub(crate) async fn curated_by(ctx: &Context, curator: Option<UserID>) -> Result<Self> {
+        let viewer = ctx.maybe_user().await?;
+        let moderator = match &viewer {
+            Some(viewer) => viewer
+                .check_permissions(ctx, |perm| perm.remove_urls())
+                .await
+                .is_ok(),
+            None => false,
+        };
+        Ok(Self {
+            viewer: viewer.map(|viewer| viewer.id()),
+            moderator,
+            nsfw: None,
+            public: false,
+            drafts_of: curator,
+            blocked: false,
+            muted: false,
+            syndicated: false,
+        })
+    }
+
+    /// The submissions reachable by the viewer through a direct link,
+    /// which includes unlisted submissions and their own drafts.
+    pub(crate) async fn reachable(ctx: &Context) -> Result<Self> {
+        Self::curated_by(ctx, ctx.maybe_user_id()).await
+    }
+
+    /// The submissions listed to anonymous viewers, e.g. in the
+    /// sitemap, which excludes all removed, held and NSFW submissions.
+    pub(crate) fn anonymous() -> Self {
+        Self {
+            viewer: None,
+            moderator: false,
+            nsfw: Some(vec![false]),
+            public: true,
+            drafts_of: None,
+            blocked: false,
+            muted: false,
+            syndicated: false,
+        }
+    }
+
+    /// Also exclude submissions from domains muted by the viewer.
+    pub(crate) fn without_muted(self) -> Self {
+        Self {
+            muted: true,
+            ..self
+        }
+    }
+
+    /// Also exclude submissions to groups which are not syndicated,
+    /// see [`Group::syndicated_ids`].
+    pub(crate) fn syndicated(self) -> Self {
+        Self {
+            syndicated: true,
+            ..self
+        }
+    }
+
+    /// The filter selecting the listed submissions, for queries of
+    /// the submissions table, or of joins including it.
+    pub(crate) fn filter<QS>(&self) -> Box<dyn BoxableExpression<QS, Sqlite, SqlType = Bool>>
+    where
+        QS: 'static,
+        urls::deleted_at: SelectableExpression<QS>,
+        urls::draft: SelectableExpression<QS>,
+        urls::visibility: SelectableExpression<QS>,
+        urls::nsfw: SelectableExpression<QS>,
+        urls::removed_at: SelectableExpression<QS>,
+        urls::shadow_removed: SelectableExpression<QS>,
+        urls::held_at: SelectableExpression<QS>,
+        urls::created_by: SelectableExpression<QS>,
+        urls::domain: SelectableExpression<QS>,
+        urls::group_id: SelectableExpression<QS>,
+    {
+        let removed = urls::removed_at.is_null();
+        let held = urls::held_at.is_null();
+        let mut filter: Box<dyn BoxableExpression<QS, Sqlite, SqlType = Bool>> =
+            match (self.viewer, self.moderator) {
+                (Some(_), true) => Box::new(
+                    urls::deleted_at
+                        .is_null()
+                        .and(removed.or(urls::shadow_removed.eq(true))),
+                ),
+                (Some(viewer), false) => Box::new(
+                    urls::deleted_at.is_null().and(
+                        removed.or(urls::shadow_removed
+                            .eq(true)
+                            .and(urls::created_by.eq(viewer))),
+                    ),
+                ),
+                (None, _) => Box::new(urls::deleted_at.is_null().and(removed)),
+            };
+        filter = match self.viewer {
+            Some(viewer) => Box::new(filter.and(held.or(urls::created_by.eq(viewer)))),
+            None => Box::new(filter.and(held)),
+        };
+        filter = match (self.public, self.drafts_of) {
+            (true, _) => Box::new(
+                filter
+                    .and(urls::draft.eq(false))
+                    .and(urls::visibility.eq(SubmissionVisibility::Public)),
+            ),
+            (false, Some(user_id)) => {
+                Box::new(filter.and(urls::draft.eq(false).or(urls::created_by.eq(user_id))))
+            }
+            (false, None) => Box::new(filter.and(urls::draft.eq(false))),
+        };
+        if let Some(nsfw) = &self.nsfw {
+            filter = Box::new(filter.and(urls::nsfw.eq_any(nsfw.clone())));
+        }
+        if let (true, Some(viewer)) = (self.blocked, self.viewer) {
+            filter = Box::new(filter.and(urls::created_by.ne_all(Block::hidden_authors(viewer))));
+        }
+        if let (true, Some(viewer)) = (self.muted, self.viewer) {
+            filter = Box::new(
+                filter.and(
+                    urls::domain
+                        .is_null()
+                        .or(urls::domain.ne_all(MutedDomain::hidden_domains(viewer))),
+                ),
+            );
+        }
+        if self.syndicated {
+            filter = Box::new(
+                filter.and(
+                    urls::group_id
+                        .is_null()
+                        .or(urls::group_id.eq_any(Group::syndicated_ids())),
+                ),
+            );
+        }
+        filter
+    }
+
+    /// Whether the submission with the given ID is listed.
+    pub(crate) async fn includes(&self, ctx: &Context, url_id: UrlID) -> Result<bool> {
+        let conn = ctx.conn().await?;
+        let count: i64 = urls::table
+            .filter(urls::id.eq(url_id))
+            .filter(self.filter())
+            .select(diesel::dsl::count_star())
+            .get_result(&*conn)?;
+        Ok(count > 0)
+    }
+}
@@ -0,0 +1,3323 @@
+use crate::db::id::{GroupID, UrlID, UserID};
+use crate::db::models::tag::{self, Tag};
+use crate::db::models::{
+    Block, Comment, Group, PostingAction, RateLimit, Revision, ShowNsfw, UrlEmbed, User, Webhook,
+};
+use crate::error::{AppError, EditNotAllowed, EditNotAllowedReason, RateLimited};
+use crate::events::Event;
+use crate::schema::{
+    comments, follows, revisions, tag_follows, url_tags, url_upvotes, urls, user_preferences, users,
+};
+use crate::spam::{SpamCheckInput, SpamContentKind, SpamVerdict};
+use crate::validation::Validator;
+use crate::{canonical, domain, fetch, language, link, markdown, preview, Context};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::expression::{AppearsOnTable, Expression, NonAggregate, SelectableExpression};
+use diesel::prelude::*;
+use diesel::query_builder::{AstPass, QueryFragment, QueryId};
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::{BigInt, Nullable, Text, Timestamp};
+use diesel::sqlite::Sqlite;
+use juniper::{GraphQLEnum, GraphQLInputObject};
+use pulldown_cmark::escape::escape_html;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use validator::Validate;
+use warp::http::{StatusCode, Uri};
+
+mod import;
+mod listed;
+mod moderation;
+mod scheduling;
+
+pub use listed::Listed;
+use scheduling::check_publish_at;
+
+const INCLUDE_DAYS_IN_RANKED: i64 = 7;
+/// Age in days after which the trending rank of a submission
+/// is no longer refreshed, and is reset to zero instead.
+const TRENDING_DAYS: i64 = 30;
+/// Maximum number of submissions a user may pin to their profile.
+pub const MAX_PINNED_URLS: i64 = 3;
+/// Maximum number of URL checks a single client may make per minute.
+const CHECK_LIMIT_PER_MINUTE: usize = 30;
+/// Age in days after which links are checked again.
+const DAYS_BETWEEN_LINK_CHECKS: i64 = 7;
+/// Maximum number of links checked by a single run of
+/// [`Url::check_due_links`].
+const LINK_CHECK_BATCH_SIZE: i64 = 100;
+/// Maximum number of links checked per minute for any
+/// registrable domain.
+const LINK_CHECKS_PER_DOMAIN_PER_MINUTE: usize = 10;
+/// Number of failed attempts after which archiving a
+/// page is given up.
+const MAX_ARCHIVE_ATTEMPTS: i32 = 5;
+/// Maximum number of pages archived by a single run of
+/// [`Url::archive_pending`].
+const ARCHIVE_BATCH_SIZE: i64 = 20;
+/// Granularity of the click counts shown to users
+/// other than the submitter.
+const CLICK_ROUNDING: i64 = 10;
+/// Maximum number of related submissions listed for a submission.
+const MAX_RELATED_URLS: i64 = 25;
+/// Maximum number of words of a title which are compared to the
+/// titles of other submissions when finding related submissions.
+const MAX_RELATED_TERMS: usize = 8;
+/// Words of titles with fewer characters are not compared.
+const MIN_RELATED_TERM_LEN: usize = 4;
+/// Weight of each tag two related submissions share.
+const RELATED_TAG_WEIGHT: i64 = 3;
+/// Weight of two related submissions linking the same domain.
+const RELATED_DOMAIN_WEIGHT: i64 = 2;
+
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset, Associations)]
+#[belongs_to(User, foreign_key = "created_by")]
+pub struct Url {
+    id: UrlID,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+
+    url: Option<String>,
+    status_code: i32,
+    title: Option<String>,
+    description: Option<String>,
+    image: Option<String>,
+    created_by: UserID,
+    canonical_url: Option<String>,
+    fetched_title: Option<String>,
+    fetched_description: Option<String>,
+    metadata_status: MetadataStatus,
+    edited_at: Option<NaiveDateTime>,
+    deleted_at: Option<NaiveDateTime>,
+    comment_count: i64,
+    score: i64,
+    upvotes: i64,
+    downvotes: i64,
+    hot_rank: f64,
+    pinned_at: Option<NaiveDateTime>,
+    domain: Option<String>,
+    removed_at: Option<NaiveDateTime>,
+    shadow_removed: bool,
+    preview_image: Option<String>,
+    last_checked_at: Option<NaiveDateTime>,
+    http_status: Option<i32>,
+    link_status: LinkStatus,
+    archived_url: Option<String>,
+    archive_status: Option<ArchiveStatus>,
+    archive_attempts: i32,
+    next_archive_at: Option<NaiveDateTime>,
+    draft: bool,
+    clicks: i64,
+    views: i64,
+    nsfw: bool,
+    nsfw_locked: bool,
+    language: Option<String>,
+    description_html: Option<String>,
+    revision_count: i64,
+    locked_at: Option<NaiveDateTime>,
+    lock_reason: Option<String>,
+    previous_id: Option<UrlID>,
+    anonymous: bool,
+    kind: SubmissionKind,
+    text: Option<String>,
+    text_html: Option<String>,
+    held_at: Option<NaiveDateTime>,
+    visibility: SubmissionVisibility,
+    published_at: NaiveDateTime,
+    group_id: Option<GroupID>,
+    publish_at: Option<NaiveDateTime>,
+}
+
+/// Whether the meta data of the linked page was
+/// fetched successfully.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum MetadataStatus {
+    /// The page was not fetched yet.
+    Pending,
+    /// The page was fetched successfully.
+    Ok,
+    /// The page could not be fetched.
+    Failed,
+}
+
+/// Whether the link of a submission still works, as found
+/// by the last periodic check.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum LinkStatus {
+    /// The link works.
+    Ok,
+    /// The link works, but redirects elsewhere.
+    Redirected,
+    /// The link responds with an error.
+    Broken,
+    /// The link was not checked yet, or the check didn't get
+    /// a conclusive response, e.g. because it timed out.
+    Unknown,
+}
+
+impl LinkStatus {
+    /// Derive the status of a link from the result of checking it.
+    /// Responses asking to slow down are inconclusive.
+    fn from_check(check: &Result<fetch::CheckedLink>) -> Self {
+        match check {
+            Ok(link) if link.status.is_success() && link.redirected => LinkStatus::Redirected,
+            Ok(link) if link.status.is_success() => LinkStatus::Ok,
+            Ok(link) if link.status == StatusCode::TOO_MANY_REQUESTS => LinkStatus::Unknown,
+            Ok(link) if link.status.is_client_error() || link.status.is_server_error() => {
+                LinkStatus::Broken
+            }
+            _ => LinkStatus::Unknown,
+        }
+    }
+}
+
+/// Whether a snapshot of the linked page was stored
+/// by the archive.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum ArchiveStatus {
+    /// Archiving the page was requested, but didn't
+    /// succeed yet.
+    Pending,
+    /// A snapshot of the page was stored.
+    Archived,
+    /// Archiving the page failed repeatedly, and was
+    /// given up.
+    Failed,
+}
+
+/// Whether a submission shares a link, or is a text post
+/// starting a discussion without one.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum SubmissionKind {
+    /// The submission links a page.
+    Link,
+    /// The submission has a text instead of a link.
+    Text,
+}
+
+/// Where a submission is listed. Unlisted submissions are only
+/// reachable by their link, but are otherwise like any other
+/// submission, e.g. they can be commented on and voted on.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum SubmissionVisibility {
+    /// The submission is listed everywhere.
+    Public,
+    /// The submission is left out of all feeds, search results,
+    /// tags, the sitemap, and the profile of its submitter.
+    Unlisted,
+}
+
+/// Time to wait before attempting to archive a page again,
+/// after the given number of failed attempts. This starts at
+/// ten minutes, and doubles with each attempt.
+fn archive_backoff(attempts: i32) -> Duration {
+    Duration::minutes(10 * 2i64.pow(attempts.clamp(1, 16) as u32 - 1))
+}
+
+/// Whether a vote raises or lowers the score of
+/// a submission.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum VoteDirection {
+    Up,
+    /// Only available if downvotes are enabled.
+    Down,
+}
+
+/// Determines how submissions are ordered when listing
+/// all submissions.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrlSort {
+    /// Most recently published first.
+    Newest,
+    /// Least recently published first.
+    Oldest,
+    /// Highest scoring first.
+    Top,
+    /// Highest scoring submissions relative to their age first.
+    /// Ranks are refreshed every few minutes.
+    Trending,
+}
+
+impl UrlSort {
+    fn as_str(&self) -> &'static str {
+        match self {
+            UrlSort::Newest => "newest",
+            UrlSort::Oldest => "oldest",
+            UrlSort::Top => "top",
+            UrlSort::Trending => "trending",
+        }
+    }
+}
+
+/// Limits the submissions listed in the top order to those
+/// submitted within the given time range.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopRange {
+    /// Submitted within the last day.
+    Day,
+    /// Submitted within the last week.
+    Week,
+    /// Submitted within the last 30 days.
+    Month,
+    /// Submitted within the last 365 days.
+    Year,
+    /// All submissions.
+    All,
+}
+
+impl TopRange {
+    /// Maximum age of included submissions, if any.
+    pub fn duration(&self) -> Option<Duration> {
+        match self {
+            TopRange::Day => Some(Duration::days(1)),
+            TopRange::Week => Some(Duration::weeks(1)),
+            TopRange::Month => Some(Duration::days(30)),
+            TopRange::Year => Some(Duration::days(365)),
+            TopRange::All => None,
+        }
+    }
+}
+
+/// Restricts which submissions are listed by
+/// [`Url::all_submissions`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UrlFilter<'a> {
+    /// Only list submissions with this tag.
+    pub tag: Option<&'a str>,
+    /// Only list submissions by this user.
+    pub created_by: Option<UserID>,
+    /// Only list submissions by users followed by this user, or
+    /// with tags followed by this user.
+    pub followed_by: Option<UserID>,
+    /// Only list submissions from this registrable
+    /// domain, in its ASCII form.
+    pub domain: Option<&'a str>,
+    /// Only list submissions from this range. This can
+    /// only be given for the top order.
+    pub range: Option<TopRange>,
+    /// Exclude submissions pinned to the profile of
+    /// their author.
+    pub unpinned: bool,
+    /// Only list submissions whose link has this status.
+    pub link_status: Option<LinkStatus>,
+    /// Only list submissions in one of these languages, given as
+    /// ISO 639-1 codes. Submissions of unknown language are always
+    /// listed, see [`language`].
+    pub languages: Option<&'a [String]>,
+    /// Only list submissions to this group. Without a group,
+    /// submissions to groups which are not syndicated are only
+    /// listed with the submissions of a single user.
+    pub group: Option<GroupID>,
+    /// Only list the submission with this ID, i.e. check
+    /// whether it is listed, see [`Url::listed`].
+    pub id: Option<UrlID>,
+}
+
+/// Position of a submission in a list of submissions. This holds the
+/// order the list was sorted in, and the values submissions are sorted
+/// by at the time the cursor was handed out, such that pages stay stable
+/// when the submission the cursor points to is re-ranked or disappears.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UrlCursor {
+    sort: UrlSort,
+    score: i64,
+    hot_rank: f64,
+    published_at: NaiveDateTime,
+    id: UrlID,
+}
+
+impl UrlCursor {
+    /// The order of the list this cursor points into.
+    pub fn sort(&self) -> UrlSort {
+        self.sort
+    }
+
+    pub fn id(&self) -> UrlID {
+        self.id
+    }
+
+    pub fn published_at(&self) -> NaiveDateTime {
+        self.published_at
+    }
+}
+
+impl fmt::Display for UrlCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = format!(
+            "{}:{}:{}:{}:{}",
+            self.sort.as_str(),
+            self.score,
+            self.hot_rank,
+            self.published_at.timestamp_nanos(),
+            self.id
+        );
+        write!(f, "{}", base64::encode_config(raw, base64::URL_SAFE_NO_PAD))
+    }
+}
+
+impl FromStr for UrlCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid submission cursor";
+        let raw = base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|_| ERR)?;
+        let raw = String::from_utf8(raw).map_err(|_| ERR)?;
+        let mut parts = raw.splitn(5, ':');
+        let sort = match parts.next() {
+            Some("newest") => UrlSort::Newest,
+            Some("oldest") => UrlSort::Oldest,
+            Some("top") => UrlSort::Top,
+            Some("trending") => UrlSort::Trending,
+            _ => return Err(ERR),
+        };
+        let score = parts.next().and_then(|s| s.parse().ok()).ok_or(ERR)?;
+        let hot_rank = parts.next().and_then(|r| r.parse().ok()).ok_or(ERR)?;
+        let nanos: i64 = parts.next().and_then(|t| t.parse().ok()).ok_or(ERR)?;
+        let id = parts.next().and_then(|id| id.parse().ok()).ok_or(ERR)?;
+        let published_at = NaiveDateTime::from_timestamp_opt(
+            nanos.div_euclid(1_000_000_000),
+            nanos.rem_euclid(1_000_000_000) as u32,
+        )
+        .ok_or(ERR)?;
+        Ok(Self {
+            sort,
+            score,
+            hot_rank,
+            published_at,
+            id,
+        })
+    }
+}
+
+/// Position of a submission on the profile of its author. Pinned
+/// submissions are listed before all others, in the order they were
+/// pinned, so a cursor either points into the pinned submissions, or
+/// into the remaining ones in the order of the list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProfileCursor {
+    Pinned { pinned_at: NaiveDateTime, id: UrlID },
+    Listed(UrlCursor),
+}
+
+impl fmt::Display for ProfileCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileCursor::Pinned { pinned_at, id } => {
+                let raw = format!("pinned:{}:{}", pinned_at.timestamp_nanos(), id);
+                write!(f, "{}", base64::encode_config(raw, base64::URL_SAFE_NO_PAD))
+            }
+            ProfileCursor::Listed(cursor) => write!(f, "{}", cursor),
+        }
+    }
+}
+
+impl FromStr for ProfileCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid submission cursor";
+        let raw = base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|_| ERR)?;
+        let raw = String::from_utf8(raw).map_err(|_| ERR)?;
+        let pinned = match raw.strip_prefix("pinned:") {
+            Some(pinned) => pinned,
+            None => return Ok(ProfileCursor::Listed(s.parse()?)),
+        };
+        let mut parts = pinned.splitn(2, ':');
+        let nanos: i64 = parts.next().and_then(|t| t.parse().ok()).ok_or(ERR)?;
+        let id = parts.next().and_then(|id| id.parse().ok()).ok_or(ERR)?;
+        let pinned_at = NaiveDateTime::from_timestamp_opt(
+            nanos.div_euclid(1_000_000_000),
+            nanos.rem_euclid(1_000_000_000) as u32,
+        )
+        .ok_or(ERR)?;
+        Ok(ProfileCursor::Pinned { pinned_at, id })
+    }
+}
+
+/// Trending rank of a submission with the given score and age. Ranks
+/// decay as submissions get older, faster for larger `gravity`, such
+/// that new submissions outrank older ones with the same score.
+pub fn hot_rank(score: i64, age: Duration, gravity: f64) -> f64 {
+    let hours = age.num_seconds().max(0) as f64 / 3600.0;
+    score as f64 / (hours + 2.0).powf(gravity)
+}
+
+#[derive(Debug, Clone, Validate, GraphQLInputObject)]
+pub struct NewUrlInput {
+    /// The URL to share. Text posts have a text instead.
+    #[validate(url(message = "Please submit a valid URL"))]
+    url: Option<String>,
+    /// Markdown text of a text post, which is submitted
+    /// instead of a URL.
+    #[validate(length(min = 1, max = 10000, message = "The text is too long"))]
+    text: Option<String>,
+    /// Title to use instead of the one provided by
+    /// the linked html document.
+    #[validate(length(min = 1, max = 256, message = "The title is too long"))]
+    title: Option<String>,
+    /// Description to use instead of the one provided
+    /// by the linked html document.
+    #[validate(length(min = 1, max = 2048, message = "The description is too long"))]
+    description: Option<String>,
+    /// Tags used to categorize the submission. Unknown
+    /// tags are created.
+    tags: Option<Vec<String>>,
+    /// Whether the linked page is not safe for work.
+    nsfw: Option<bool>,
+    /// Hide the submitter from everyone but themselves and
+    /// administrators. This is only possible if the server
+    /// enables anonymous submissions.
+    anonymous: Option<bool>,
+    /// Where the submission is listed, public by default.
+    visibility: Option<SubmissionVisibility>,
+    /// The group to submit to, which the submitter must be a
+    /// member of. Group submissions are only listed on the front
+    /// page if the group is syndicated.
+    group_id: Option<GroupID>,
+    /// Schedule the submission to be published at this time. Until
+    /// then, it is a draft only visible to the submitter.
+    publish_at: Option<DateTime<Utc>>,
+}
+
+impl NewUrlInput {
+    /// The kind of submission this input creates. Exactly one of
+    /// a URL and a text must be given, and text posts need a title,
+    /// since there is no linked page to take one from.
+    fn kind(&self) -> Result<SubmissionKind> {
+        match (&self.url, &self.text) {
+            (Some(_), None) => Ok(SubmissionKind::Link),
+            (None, Some(_)) if self.title.is_none() => Err(anyhow!("Text posts need a title")),
+            (None, Some(_)) => Ok(SubmissionKind::Text),
+            _ => Err(anyhow!("Please submit either a URL or a text")),
+        }
+    }
+
+    /// Check that the URL to share may be submitted, see
+    /// [`link::check`].
+    fn check_url(&self, ctx: &Context) -> Result<()> {
+        if let Some(url) = &self.url {
+            link::check(url, ctx.config().allow_private_urls())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Validate, GraphQLInputObject)]
+pub struct UpdateUrlInput {
+    #[validate(length(min = 1, max = 256, message = "The title is too long"))]
+    title: Option<String>,
+    #[validate(length(min = 1, max = 2048, message = "The description is too long"))]
+    description: Option<String>,
+    /// Replaces the tags of the submission, if given.
+    tags: Option<Vec<String>>,
+    /// Marks the submission as not safe for work, or
+    /// clears the mark.
+    nsfw: Option<bool>,
+    /// Lists or unlists the submission, see
+    /// [`Url::set_visibility`].
+    visibility: Option<SubmissionVisibility>,
+    /// Schedules a draft to be published at this time, or
+    /// reschedules a scheduled submission.
+    publish_at: Option<DateTime<Utc>>,
+    /// Cancels the scheduled publication of a submission,
+    /// which is kept as a draft.
+    unschedule: Option<bool>,
+}
+
+/// The outcome of submitting a URL. If the canonical form of the
+/// URL was submitted before, this holds the existing submission.
+#[derive(Debug, Clone)]
+pub struct SubmitUrlResult {
+    url: Url,
+    duplicate: bool,
+}
+
+impl SubmitUrlResult {
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Whether the URL was submitted before, in which
+    /// case no new submission was created.
+    pub fn is_duplicate(&self) -> bool {
+        self.duplicate
+    }
+}
+
+/// The outcome of checking whether a URL was submitted before,
+/// without submitting it. See [`Url::check`].
+#[derive(Debug, Clone)]
+pub struct UrlCheck {
+    url: Option<Url>,
+}
+
+impl UrlCheck {
+    /// The earlier submission of the URL, if there is one.
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
+    pub fn exists(&self) -> bool {
+        self.url.is_some()
+    }
+}
+
+impl Url {
+    pub fn id(&self) -> UrlID {
+        self.id
+    }
+
+    /// The URL being shared, or `None` for text posts. This
+    /// should usually succeed, unless the database was corrupted.
+    pub fn url(&self) -> Result<Option<Uri>> {
+        Ok(self.url.as_deref().map(str::parse).transpose()?)
+    }
+
+    /// Whether this submission links a page, or is a text post.
+    pub fn kind(&self) -> SubmissionKind {
+        self.kind
+    }
+
+    /// The markdown text of a text post.
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    /// The text of a text post rendered as sanitized html, see
+    /// [`markdown::render`].
+    pub fn text_html(&self) -> Option<&str> {
+        self.text_html.as_deref()
+    }
+
+    /// The status code which was returned when last
+    /// trying to fetch the given URL.
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status_code.try_into().unwrap_or(500))
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    /// The canonical form of the URL, which is used to detect
+    /// duplicate submissions. This may differ from the submitted
+    /// URL beyond canonicalization, if the link redirected or the
+    /// page declared its canonical URL when it was submitted. Text
+    /// posts have no canonical URL.
+    pub fn canonical_url(&self) -> Option<&str> {
+        self.canonical_url.as_deref()
+    }
+
+    /// Return the url as a `&str`, or `None` for text posts. This
+    /// might return an invalid Uri, since it simply returns the
+    /// value found in the database.
+    pub fn url_str(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// The title provided by the submitter, or by the
+    /// linked html document, if available.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref().or_else(|| self.fetched_title())
+    }
+
+    /// The description provided by the submitter, or by
+    /// the linked html document, if available.
+    pub fn description(&self) -> Option<&str> {
+        self.description
+            .as_deref()
+            .or_else(|| self.fetched_description())
+    }
+
+    /// The title provided by the linked html document, if
+    /// available.
+    pub fn fetched_title(&self) -> Option<&str> {
+        self.fetched_title.as_deref()
+    }
+
+    /// The description provided by the linked html
+    /// document, if available.
+    pub fn fetched_description(&self) -> Option<&str> {
+        self.fetched_description.as_deref()
+    }
+
+    /// The description rendered as sanitized html. Descriptions
+    /// provided by the submitter are markdown, and rendered when
+    /// they are written, see [`markdown::render`]. Descriptions
+    /// provided by the linked html document are plain text.
+    pub fn description_html(&self) -> Option<String> {
+        if self.description.is_some() {
+            return self.description_html.clone();
+        }
+        self.fetched_description().map(|description| {
+            let mut html = String::from("<p>");
+            escape_html(&mut html, description).unwrap();
+            html.push_str("</p>\n");
+            html
+        })
+    }
+
+    pub fn metadata_status(&self) -> MetadataStatus {
+        self.metadata_status
+    }
+
+    /// The last time the title or description were
+    /// edited, if ever.
+    pub fn edited_at(&self) -> Option<DateTime<Utc>> {
+        self.edited_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    /// The number of previous versions of the title and
+    /// description, see [`Revision`].
+    pub fn revision_count(&self) -> i64 {
+        self.revision_count
+    }
+
+    /// The time this URL was deleted, if it was. Deleted URLs
+    /// are kept for moderation, but excluded from all listings.
+    pub fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// The time a moderator removed this URL, if they did. This
+    /// includes shadow removals, which must not be revealed to
+    /// anyone but the author and moderators.
+    pub fn removed_at(&self) -> Option<DateTime<Utc>> {
+        self.removed_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    /// Whether a moderator removed this URL for everyone, in which
+    /// case its page shows that it was removed. This is `false` for
+    /// shadow removals.
+    pub fn is_removed(&self) -> bool {
+        self.removed_at.is_some() && !self.shadow_removed
+    }
+
+    /// Whether a moderator removed this URL from all listings except
+    /// those of its author and of moderators.
+    pub fn is_shadow_removed(&self) -> bool {
+        self.removed_at.is_some() && self.shadow_removed
+    }
+
+    /// The time the spam filter held this URL for review by a
+    /// moderator, if it did and the URL was not released yet. Held
+    /// URLs are only visible to their author and moderators.
+    pub fn held_at(&self) -> Option<DateTime<Utc>> {
+        self.held_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.held_at.is_some()
+    }
+
+    /// The time a moderator locked the discussion of this URL, if
+    /// they did. See [`lock`](Url::lock).
+    pub fn locked_at(&self) -> Option<DateTime<Utc>> {
+        self.locked_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked_at.is_some()
+    }
+
+    /// The reason the moderator gave for locking the discussion.
+    pub fn lock_reason(&self) -> Option<&str> {
+        self.lock_reason.as_deref()
+    }
+
+    /// Whether this URL is a draft, e.g. an imported bookmark, which
+    /// is not listed anywhere until its author publishes it.
+    pub fn is_draft(&self) -> bool {
+        self.draft
+    }
+
+    /// When this draft is scheduled to be published, if it is,
+    /// see [`publish_scheduled`](Url::publish_scheduled).
+    pub fn publish_at(&self) -> Option<DateTime<Utc>> {
+        self.publish_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    pub fn is_scheduled(&self) -> bool {
+        self.draft && self.publish_at.is_some()
+    }
+
+    /// Where this URL is listed, see [`SubmissionVisibility`].
+    pub fn visibility(&self) -> SubmissionVisibility {
+        self.visibility
+    }
+
+    pub fn is_unlisted(&self) -> bool {
+        self.visibility == SubmissionVisibility::Unlisted
+    }
+
+    /// Whether the linked page is not safe for work. Depending on
+    /// their preferences, such submissions are excluded from the
+    /// listings of viewers, see [`ShowNsfw`].
+    pub fn is_nsfw(&self) -> bool {
+        self.nsfw
+    }
+
+    /// Whether the NSFW mark was set by a moderator, in which case
+    /// the submitter can no longer clear it.
+    pub fn is_nsfw_locked(&self) -> bool {
+        self.nsfw_locked
+    }
+
+    /// ISO 639-1 code of the language of the linked page, if it
+    /// could be detected confidently, see [`language`].
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// The image uri provided by the linked html
+    /// document, if available.
+    pub fn image(&self) -> Result<Option<Uri>> {
+        let maybe_uri = self
+            .image
+            .as_ref()
+            .map(|img_uri| img_uri.parse())
+            .transpose()?;
+        Ok(maybe_uri)
+    }
+
+    /// Storage key of the stored copy of the image, if the
+    /// image could be fetched. See [`preview`](crate::preview).
+    pub fn preview_image(&self) -> Option<&str> {
+        self.preview_image.as_deref()
+    }
+
+    /// The last time the link was checked, if ever. See
+    /// [`check_link`](Url::check_link).
+    pub fn last_checked_at(&self) -> Option<DateTime<Utc>> {
+        self.last_checked_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    /// Status code of the final response found by the last
+    /// link check, if there was a response.
+    pub fn http_status(&self) -> Option<i32> {
+        self.http_status
+    }
+
+    pub fn link_status(&self) -> LinkStatus {
+        self.link_status
+    }
+
+    /// URL of a snapshot of the linked page, if the
+    /// page was archived.
+    pub fn archived_url(&self) -> Option<&str> {
+        self.archived_url.as_deref()
+    }
+
+    /// Whether the linked page was archived, or `None` if
+    /// archiving it was never requested.
+    pub fn archive_status(&self) -> Option<ArchiveStatus> {
+        self.archive_status
+    }
+
+    /// The link shown to readers. This is the snapshot of the
+    /// page if the link is broken and the page was archived, and
+    /// the submitted URL otherwise. Text posts have no link.
+    pub fn link_str(&self) -> Option<&str> {
+        match (self.link_status, &self.archived_url) {
+            (LinkStatus::Broken, Some(archived_url)) => Some(archived_url),
+            _ => self.url.as_deref(),
+        }
+    }
+
+    /// The link shown to readers, as returned by [`link_str`](Url::link_str),
+    /// if it is safe to redirect to, see [`link::check`]. The stored URLs of
+    /// old submissions were not always validated.
+    pub fn outbound_link(&self, ctx: &Context) -> Result<reqwest::Url> {
+        let url = self
+            .link_str()
+            .ok_or_else(|| anyhow!("Text posts do not link anywhere"))?;
+        Ok(link::check(url, ctx.config().allow_private_urls())?)
+    }
+
+    /// Return the image uri as a `&str`. This always succeeds
+    /// but might return an invalid Uri, since it simply
+    /// returns the value found in the database.
+    pub fn image_str(&self) -> Option<&str> {
+        self.image.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.updated_at, Utc)
+    }
+
+    /// When this URL was last made public, which is when it was
+    /// submitted unless it was unlisted at first. Submissions are
+    /// listed by the time they were published.
+    pub fn published_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.published_at, Utc)
+    }
+
+    /// ID of the group this URL was submitted to, if any.
+    pub fn group_id(&self) -> Option<GroupID> {
+        self.group_id
+    }
+
+    /// The group this URL was submitted to, if any.
+    pub async fn group(&self, ctx: &Context) -> Result<Option<Group>> {
+        match self.group_id {
+            Some(group_id) => Ok(Some(Group::find(ctx, group_id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// ID of the user who submitted this URL.
+    pub fn created_by_id(&self) -> UserID {
+        self.created_by
+    }
+
+    /// The registrable domain of the URL in its ASCII form, e.g.
+    /// `example.co.uk` for `https://blog.example.co.uk/`. Text
+    /// posts have no domain.
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    /// Number of submissions from the same domain as this URL,
+    /// including this one. Only submissions listed to the viewer are
+    /// counted, see [`Listed::to_viewer`], and submissions to groups
+    /// which are not syndicated are not.
+    pub async fn domain_submission_count(&self, ctx: &Context) -> Result<i64> {
+        if self.domain.is_none() {
+            return Ok(0);
+        }
+        let listed = Listed::to_viewer(ctx).await?.syndicated();
+        let conn = ctx.conn().await?;
+        Ok(urls::table
+            .filter(urls::dsl::domain.eq(&self.domain))
+            .filter(listed.filter())
+            .select(diesel::dsl::count_star())
+            .get_result(&*conn)?)
+    }
+
+    /// The user who submitted this URL, loaded together with the
+    /// submitters of the URLs resolved alongside it, see [`User::load`].
+    pub async fn created_by(&self, ctx: &Context) -> Result<User> {
+        User::load(ctx, self.created_by).await
+    }
+
+    /// Whether this URL was submitted anonymously.
+    pub fn is_anonymous(&self) -> bool {
+        self.anonymous
+    }
+
+    /// The user who submitted this URL, as shown to the viewer.
+    /// Submitters of anonymous submissions are only shown to
+    /// themselves and administrators.
+    pub async fn author(&self, ctx: &Context) -> Result<Option<User>> {
+        if self.anonymous && ctx.maybe_user_id() != Some(self.created_by) {
+            let may_view = match ctx.maybe_user().await? {
+                Some(viewer) => viewer
+                    .check_permissions(ctx, |perm| perm.view_anonymous_submitters())
+                    .await
+                    .is_ok(),
+                None => false,
+            };
+            if !may_view {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.created_by(ctx).await?))
+    }
+
+    /// The embed shown on the discussion page, if the link was
+    /// unfurled. Deleted and removed submissions have no embed.
+    pub async fn embed(&self, ctx: &Context) -> Result<Option<UrlEmbed>> {
+        if self.is_deleted() || self.is_removed() {
+            return Ok(None);
+        }
+        UrlEmbed::find(ctx, self.id).await
+    }
+
+    /// Number of upvotes minus the number of downvotes
+    /// this URL received.
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
+    pub fn upvotes(&self) -> i64 {
+        self.upvotes
+    }
+
+    /// Number of outbound clicks on the link of this submission,
+    /// excluding clicks which were not flushed yet.
+    pub fn clicks(&self) -> i64 {
+        self.clicks
+    }
+
+    /// Number of distinct daily viewers of the discussion of this
+    /// submission, see [`UrlView`](crate::db::models::UrlView).
+    pub fn views(&self) -> i64 {
+        self.views
+    }
+
+    /// Number of outbound clicks as shown to the viewer. The
+    /// submitter sees the exact count, while everyone else sees
+    /// the count rounded down to a multiple of ten, such that
+    /// single clicks can not be attributed.
+    pub fn viewer_clicks(&self, ctx: &Context) -> i64 {
+        if ctx.maybe_user_id() == Some(self.created_by) {
+            self.clicks
+        } else {
+            self.clicks - self.clicks % CLICK_ROUNDING
+        }
+    }
+
+    /// Trending rank of this URL as of the last refresh, see
+    /// [`refresh_hot_ranks`](Url::refresh_hot_ranks).
+    pub fn hot_rank(&self) -> f64 {
+        self.hot_rank
+    }
+
+    /// Cursor pointing to this URL in a list sorted in
+    /// the given order.
+    pub fn cursor(&self, sort: UrlSort) -> UrlCursor {
+        UrlCursor {
+            sort,
+            score: self.score,
+            hot_rank: self.hot_rank,
+            published_at: self.published_at,
+            id: self.id,
+        }
+    }
+
+    /// Cursor pointing to this URL on the profile of its
+    /// author, listed in the given order.
+    pub fn profile_cursor(&self, sort: UrlSort) -> ProfileCursor {
+        match self.pinned_at {
+            Some(pinned_at) => ProfileCursor::Pinned {
+                pinned_at,
+                id: self.id,
+            },
+            None => ProfileCursor::Listed(self.cursor(sort)),
+        }
+    }
+
+    pub fn downvotes(&self) -> i64 {
+        self.downvotes
+    }
+
+    /// Whether the submitter pinned this URL to their profile.
+    pub fn is_pinned(&self) -> bool {
+        self.pinned_at.is_some()
+    }
+
+    /// When the submitter pinned this URL to their profile,
+    /// if at all.
+    pub fn pinned_at(&self) -> Option<DateTime<Utc>> {
+        self.pinned_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    /// How the logged in user voted on this URL, if at all. The votes
+    /// on the URLs resolved alongside it are loaded at once, and are
+    /// remembered for the rest of the request, see [`loader`](crate::loader).
+    pub async fn viewer_vote(&self, ctx: &Context) -> Result<Option<VoteDirection>> {
+        let user_id = match ctx.maybe_user_id() {
+            Some(user_id) => user_id,
+            None => return Ok(None),
+        };
+        ctx.loaders()
+            .votes
+            .load((user_id, self.id), |keys| Self::load_votes(ctx, keys))
+            .await
+    }
+
+    /// The votes of the given users on the given URLs, for
+    /// [`viewer_vote`](Url::viewer_vote).
+    async fn load_votes(
+        ctx: &Context,
+        keys: Vec<(UserID, UrlID)>,
+    ) -> Result<Vec<((UserID, UrlID), VoteDirection)>> {
+        let user_ids: HashSet<UserID> = keys.iter().map(|(user_id, _)| *user_id).collect();
+        let user_ids: Vec<UserID> = user_ids.into_iter().collect();
+        let url_ids: Vec<UrlID> = keys.iter().map(|(_, url_id)| *url_id).collect();
+        let votes: Vec<(UserID, UrlID, VoteDirection)> = url_upvotes::table
+            .filter(url_upvotes::dsl::user_id.eq_any(user_ids))
+            .filter(url_upvotes::dsl::url_id.eq_any(url_ids))
+            .select((
+                url_upvotes::dsl::user_id,
+                url_upvotes::dsl::url_id,
+                url_upvotes::dsl::direction,
+            ))
+            .load(&*ctx.conn().await?)?;
+        Ok(votes
+            .into_iter()
+            .map(|(user_id, url_id, direction)| ((user_id, url_id), direction))
+            .collect())
+    }
+
+    /// If the logged in user voted on this URL in any direction.
+    pub async fn voted_by_viewer(&self, ctx: &Context) -> Result<bool> {
+        Ok(self.viewer_vote(ctx).await?.is_some())
+    }
+
+    /// Comments on this URL, excluding those by users
+    /// blocked by the viewer, held comments of other users,
+    /// and deleted comments without replies.
+    pub async fn comments(&self, ctx: &Context, limit: i64) -> Result<Vec<Comment>> {
+        let conn = ctx.conn().await?;
+        let mut query = comments::table
+            .filter(comments::dsl::url_id.eq(self.id))
+            .filter(Comment::not_held(ctx))
+            .filter(
+                comments::dsl::deleted_at
+                    .is_null()
+                    .or(comments::dsl::reply_count.gt(0)),
+            )
+            .order_by(comments::created_at.asc())
+            .then_order_by(comments::id.asc())
+            .limit(limit)
+            .select(comments::all_columns)
+            .into_boxed();
+        if let Some(viewer) = ctx.maybe_user_id() {
+            query = query.filter(comments::dsl::created_by.ne_all(Block::hidden_authors(viewer)));
+        }
+        Ok(query.load(&*conn)?)
+    }
+
+    /// Tags of this URL, ordered by name.
+    pub async fn tags(&self, ctx: &Context) -> Result<Vec<Tag>> {
+        Tag::for_url(ctx, self.id).await
+    }
+
+    /// Number of comments on this URL, excluding those by users
+    /// blocked by the viewer. This uses the maintained counter, and
+    /// only counts hidden comments if the viewer is logged in, for all
+    /// URLs resolved alongside this one at once.
+    pub async fn comment_count(&self, ctx: &Context) -> Result<i64> {
+        let hidden_count = ctx
+            .loaders()
+            .hidden_comments
+            .load(self.id, |url_ids| Self::count_hidden_comments(ctx, url_ids))
+            .await?;
+        Ok(self.comment_count - hidden_count.unwrap_or(0))
+    }
+
+    /// Number of comments on each of the given URLs by users blocked
+    /// by the viewer, for [`comment_count`](Url::comment_count). URLs
+    /// without such comments are left out.
+    async fn count_hidden_comments(
+        ctx: &Context,
+        url_ids: Vec<UrlID>,
+    ) -> Result<Vec<(UrlID, i64)>> {
+        let viewer = match ctx.maybe_user_id() {
+            Some(viewer) => viewer,
+            None => return Ok(vec![]),
+        };
+        let conn = ctx.conn().await?;
+        Ok(comments::table
+            .filter(comments::dsl::url_id.eq_any(url_ids))
+            .filter(comments::dsl::created_by.eq_any(Block::hidden_authors(viewer)))
+            .filter(comments::dsl::held_at.is_null())
+            .filter(
+                comments::dsl::deleted_at
+                    .is_null()
+                    .or(comments::dsl::reply_count.gt(0)),
+            )
+            .group_by(comments::dsl::url_id)
+            .select((
+                comments::dsl::url_id,
+                diesel::dsl::sql::<BigInt>("COUNT(*)"),
+            ))
+            .load(&*conn)?)
+    }
+
+    pub fn slug(&self) -> Option<String> {
+        let slugify = |text: &str| {
+            let words = text
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty());
+            let mut slug = String::new();
+            for word in words {
+                if slug.is_empty() {
+                    slug.push_str(word);
+                } else {
+                    slug.push('-');
+                    slug.push_str(word);
+                }
+            }
+            slug.make_ascii_lowercase();
+            slug
+        };
+        self.title().map(slugify).or_else(|| {
+            let url = self.url().ok()??;
+            let authority = url.authority().map(|authority| slugify(authority.as_str()));
+            let path = slugify(url.path());
+            match authority {
+                Some(authority) if !path.is_empty() => Some(format!("{authority}-{path}")),
+                Some(authority) => Some(authority),
+                None => {
+                    if path.is_empty() {
+                        None
+                    } else {
+                        Some(path)
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Determine how to order and filter the url
+/// pagination.
+#[derive(Debug, Clone, Copy)]
+pub enum UrlOrdering {
+    /// Default ranking used on the home page.
+    Ranked,
+    /// All time best submissions.
+    Best,
+    /// Submissions from the given user, ranked
+    /// chronologically.
+    User(UserID),
+    /// All submissions, ranked chronologically.
+    Recent,
+}
+
+impl Url {
+    pub async fn find(ctx: &Context, url_id: UrlID) -> Result<Self> {
+        let url = urls::table.find(url_id).get_result(&*ctx.conn().await?)?;
+        Ok(url)
+    }
+
+    /// Find the URL with the given ID, if the viewer may see it, see
+    /// [`check_visible`](Url::check_visible). Use this for URLs the
+    /// viewer asks for by their ID.
+    pub async fn find_visible(ctx: &Context, url_id: UrlID) -> Result<Self> {
+        let url = Self::find(ctx, url_id).await?;
+        url.check_visible(ctx).await?;
+        Ok(url)
+    }
+
+    /// Fail if this URL is hidden from the viewer, who is not its
+    /// author. Drafts, like imported bookmarks, are private to their
+    /// author and are reported as not found to everyone else. URLs
+    /// held by the spam filter are only visible to moderators.
+    pub async fn check_visible(&self, ctx: &Context) -> Result<()> {
+        if ctx.maybe_user_id() == Some(self.created_by) {
+            return Ok(());
+        }
+        if self.is_scheduled() {
+            return Err(anyhow!("This submission is not published yet"));
+        }
+        if self.is_draft() {
+            return Err(AppError::NotFound("Not found".into()).into());
+        }
+        if !self.is_held() {
+            return Ok(());
+        }
+        let moderator = match ctx.maybe_user().await? {
+            Some(viewer) => viewer
+                .check_permissions(ctx, |perm| perm.review_reports())
+                .await
+                .is_ok(),
+            None => false,
+        };
+        if moderator {
+            Ok(())
+        } else {
+            Err(anyhow!("This submission is awaiting review by a moderator"))
+        }
+    }
+
+    /// Values of the NSFW mark of submissions which are included in
+    /// the listings of the viewer, i.e. only `false` if the viewer
+    /// hides NSFW submissions, see [`ShowNsfw::of_viewer`].
+    pub async fn listed_nsfw(ctx: &Context) -> Result<Vec<bool>> {
+        match ShowNsfw::of_viewer(ctx).await? {
+            ShowNsfw::Hide => Ok(vec![false]),
+            ShowNsfw::Blur | ShowNsfw::Show => Ok(vec![false, true]),
+        }
+    }
+
+    /// Values of the anonymous mark of submissions which are included
+    /// when listing the submissions of the given user, i.e. anonymous
+    /// submissions are only listed to the user themselves.
+    pub fn listed_anonymous(ctx: &Context, user_id: UserID) -> Vec<bool> {
+        if ctx.maybe_user_id() == Some(user_id) {
+            vec![false, true]
+        } else {
+            vec![false]
+        }
+    }
+
+    /// Returns URLs ranked according to the given ordering, as well, as the total number of
+    /// available pages for the given ordering. Deleted and unlisted submissions, removed
+    /// submissions hidden from the viewer, NSFW submissions hidden from the viewer, and
+    /// submissions by users blocked by the viewer are excluded, as are submissions from domains
+    /// muted by the viewer unless listing the submissions of a single user. Anonymous submissions
+    /// are only listed with the submissions of their submitter to the submitter themselves.
+    /// Submissions to groups which are not syndicated are also only listed for a single user.
+    pub async fn paginate(
+        ctx: &Context,
+        order: UrlOrdering,
+        page: i64,
+        page_size: i64,
+    ) -> Result<(Vec<Self>, i64)> {
+        use UrlOrdering::*;
+
+        let listed = match order {
+            User(_) => Listed::to_viewer(ctx).await?,
+            Ranked | Best | Recent => Listed::to_viewer(ctx).await?.without_muted().syndicated(),
+        };
+        let conn = ctx.conn().await?;
+        let total_count_query = urls::table
+            .filter(listed.filter())
+            .select(diesel::dsl::count_star());
+        let total_count: i64 = match order {
+            Ranked | Best | Recent => total_count_query.get_result(&*conn)?,
+            User(creator_id) => total_count_query
+                .filter(urls::dsl::created_by.eq(creator_id))
+                .filter(urls::dsl::anonymous.eq_any(Self::listed_anonymous(ctx, creator_id)))
+                .get_result(&*conn)?,
+        };
+        let page_count = if total_count % page_size != 0 {
+            total_count / page_size + 1
+        } else {
+            total_count / page_size
+        };
+
+        let query = urls::table
+            .filter(listed.filter())
+            .order_by(urls::dsl::created_at.desc());
+        let page = match order {
+            Ranked => {
+                let count_vote_after = ctx.now() - Duration::days(INCLUDE_DAYS_IN_RANKED);
+                let join_on_recent = url_upvotes::dsl::url_id
+                    .eq(urls::dsl::id)
+                    .and(url_upvotes::dsl::direction.eq(VoteDirection::Up))
+                    .and(url_upvotes::dsl::created_at.ge(count_vote_after.naive_utc()));
+                urls::table
+                    .left_outer_join(url_upvotes::table.on(join_on_recent))
+                    .filter(listed.filter())
+                    .group_by(urls::all_columns)
+                    .order_by(diesel::dsl::count(urls::dsl::id).desc())
+                    .then_order_by(url_upvotes::dsl::created_at.is_null().asc()) // order 1 higher than none
+                    .then_order_by(urls::dsl::created_at.desc())
+                    .select(urls::all_columns)
+                    .offset(page * page_size)
+                    .limit(page_size)
+                    .load(&*conn)?
+            }
+            Best => query
+                .order_by(urls::dsl::score.desc())
+                .then_order_by(urls::dsl::created_at.desc())
+                .offset(page * page_size)
+                .limit(page_size)
+                .load(&*conn)?,
+            User(creator_id) => query
+                .filter(urls::dsl::created_by.eq(creator_id))
+                .filter(urls::dsl::anonymous.eq_any(Self::listed_anonymous(ctx, creator_id)))
+                .offset(page * page_size)
+                .limit(page_size)
+                .load(&*conn)?,
+            Recent => query
+                .offset(page * page_size)
+                .limit(page_size)
+                .load(&*conn)?,
+        };
+
+        Ok((page, page_count))
+    }
+
+    /// Returns all submissions, in the given order, in a way that's
+    /// suitable for use with a Relay connection. Deleted and unlisted submissions, removed
+    /// submissions hidden from the viewer, NSFW submissions hidden from the
+    /// viewer, and submissions by users blocked by the viewer are excluded, as are
+    /// submissions from domains muted by the viewer, unless the listing
+    /// is restricted to a single domain or user. Anonymous submissions
+    /// are only listed by their submitter to the submitter themselves,
+    /// and never for following their submitter. Cursors
+    /// remain valid if the submission they point to is deleted, but are
+    /// rejected if they were issued for a different order. If a tag is
+    /// given, only submissions with that tag are returned, and unknown
+    /// or invalid tags yield no submissions. Tags which were renamed or
+    /// merged list the submissions of the tag they redirect to. The top order only includes
+    /// submissions from the given range, the last day by default, and
+    /// giving a range for any other order is an error. Submissions are
+    /// ordered and ranged by the time they were published, see
+    /// [`published_at`](Url::published_at). Submissions to groups are
+    /// only listed for their group, for a single user, or if their
+    /// group is syndicated, see [`Group`].
+    pub async fn all_submissions(
+        ctx: &Context,
+        filter: UrlFilter<'_>,
+        sort: UrlSort,
+        after: Option<UrlCursor>,
+        before: Option<UrlCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        use urls::dsl::{hot_rank, id, published_at, score};
+
+        for cursor in after.iter().chain(before.iter()) {
+            if cursor.sort != sort {
+                return Err(anyhow!(
+                    "This cursor was issued for the {:?} order, not {:?}",
+                    cursor.sort,
+                    sort
+                ));
+            }
+        }
+        let range = match (sort, filter.range) {
+            (UrlSort::Top, range) => range.unwrap_or(TopRange::Day),
+            (_, None) => TopRange::All,
+            (_, Some(_)) => return Err(anyhow!("A range can only be given for the top order")),
+        };
+
+        let mut listed = Listed::to_viewer(ctx).await?;
+        if let (None, None) = (filter.domain, filter.created_by) {
+            listed = listed.without_muted();
+        }
+        if let (None, None) = (filter.group, filter.created_by) {
+            listed = listed.syndicated();
+        }
+        let conn = ctx.conn().await?;
+
+        let mut query = urls::table.filter(listed.filter()).into_boxed();
+        query = match sort {
+            UrlSort::Newest => query.order_by(published_at.desc()).then_order_by(id.desc()),
+            UrlSort::Oldest => query.order_by(published_at.asc()).then_order_by(id.asc()),
+            UrlSort::Top => query
+                .order_by(score.desc())
+                .then_order_by(published_at.desc())
+                .then_order_by(id.desc()),
+            UrlSort::Trending => query
+                .order_by(hot_rank.desc())
+                .then_order_by(published_at.desc())
+                .then_order_by(id.desc()),
+        };
+
+        if let Some(tag) = filter.tag {
+            let tag = match tag::normalize(tag) {
+                Ok(tag) => Tag::resolve(&*conn, tag)?,
+                Err(_) => return Ok(vec![]),
+            };
+            let tagged = url_tags::table
+                .filter(url_tags::dsl::tag_name.eq(tag))
+                .select(url_tags::dsl::url_id);
+            query = query.filter(id.eq_any(tagged));
+        }
+
+        if let Some(created_by) = filter.created_by {
+            query = query
+                .filter(urls::dsl::created_by.eq(created_by))
+                .filter(urls::dsl::anonymous.eq_any(Self::listed_anonymous(ctx, created_by)));
+        }
+
+        if let Some(followed_by) = filter.followed_by {
+            let users = follows::table
+                .filter(follows::dsl::user_id.eq(followed_by))
+                .select(follows::dsl::followed_user_id);
+            let tags = tag_follows::table
+                .filter(tag_follows::dsl::user_id.eq(followed_by))
+                .select(tag_follows::dsl::tag_name);
+            let tagged = url_tags::table
+                .filter(url_tags::dsl::tag_name.eq_any(tags))
+                .select(url_tags::dsl::url_id);
+            let by_users = urls::dsl::created_by
+                .eq_any(users)
+                .and(urls::dsl::anonymous.eq(false));
+            query = query.filter(by_users.or(id.eq_any(tagged)));
+        }
+
+        if let Some(domain) = filter.domain {
+            query = query.filter(urls::dsl::domain.eq(domain));
+        }
+
+        if let Some(group) = filter.group {
+            query = query.filter(urls::dsl::group_id.eq(group));
+        }
+
+        if let Some(url_id) = filter.id {
+            query = query.filter(id.eq(url_id));
+        }
+
+        if filter.unpinned {
+            query = query.filter(urls::dsl::pinned_at.is_null());
+        }
+
+        if let Some(link_status) = filter.link_status {
+            query = query.filter(urls::dsl::link_status.eq(link_status));
+        }
+
+        if let Some(languages) = filter.languages {
+            query = query.filter(
+                urls::dsl::language
+                    .is_null()
+                    .or(urls::dsl::language.eq_any(languages)),
+            );
+        }
+
+        if let Some(duration) = range.duration() {
+            query = query.filter(published_at.ge((ctx.now() - duration).naive_utc()));
+        }
+
+        if let Some(after) = after {
+            let older = published_at
+                .lt(after.published_at)
+                .or(published_at.eq(after.published_at).and(id.lt(after.id)));
+            let newer = published_at
+                .gt(after.published_at)
+                .or(published_at.eq(after.published_at).and(id.gt(after.id)));
+            query = match sort {
+                UrlSort::Newest => query.filter(older),
+                UrlSort::Oldest => query.filter(newer),
+                UrlSort::Top => {
+                    query.filter(score.lt(after.score).or(score.eq(after.score).and(older)))
+                }
+                UrlSort::Trending => query.filter(
+                    hot_rank
+                        .lt(after.hot_rank)
+                        .or(hot_rank.eq(after.hot_rank).and(older)),
+                ),
+            };
+        }
+
+        if let Some(before) = before {
+            let older = published_at
+                .lt(before.published_at)
+                .or(published_at.eq(before.published_at).and(id.lt(before.id)));
+            let newer = published_at
+                .gt(before.published_at)
+                .or(published_at.eq(before.published_at).and(id.gt(before.id)));
+            query = match sort {
+                UrlSort::Newest => query.filter(newer),
+                UrlSort::Oldest => query.filter(older),
+                UrlSort::Top => {
+                    query.filter(score.gt(before.score).or(score.eq(before.score).and(newer)))
+                }
+                UrlSort::Trending => query.filter(
+                    hot_rank
+                        .gt(before.hot_rank)
+                        .or(hot_rank.eq(before.hot_rank).and(newer)),
+                ),
+            };
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        Ok(query.load(&*conn)?)
+    }
+
+    /// The submission with the given ID, if it is included when listing
+    /// all submissions (with the given tag) to the viewer, see
+    /// [`all_submissions`](Url::all_submissions).
+    pub async fn listed(ctx: &Context, url_id: UrlID, tag: Option<&str>) -> Result<Option<Self>> {
+        let filter = UrlFilter {
+            id: Some(url_id),
+            tag,
+            ..Default::default()
+        };
+        let urls = Self::all_submissions(ctx, filter, UrlSort::Newest, None, None, Some(1)).await?;
+        Ok(urls.into_iter().next())
+    }
+
+    /// Submissions related to this one, most related first, see
+    /// [`Relatedness`]. Submissions which are not related at all
+    /// are not included, and neither are submissions which would
+    /// be excluded from listing all submissions. At most 25
+    /// submissions are returned.
+    pub async fn related(&self, ctx: &Context, limit: i64) -> Result<Vec<Self>> {
+        let listed = Listed::to_viewer(ctx).await?.without_muted().syndicated();
+        let relatedness = Relatedness {
+            url_id: self.id,
+            domain: self.domain.clone(),
+            terms: title_terms(self.title().unwrap_or_default()),
+        };
+        let conn = ctx.conn().await?;
+        Ok(urls::table
+            .filter(urls::dsl::id.ne(self.id))
+            .filter(listed.filter())
+            .filter(relatedness.clone().gt(0))
+            .order_by(relatedness.desc())
+            .then_order_by(urls::dsl::created_at.desc())
+            .then_order_by(urls::dsl::id.desc())
+            .limit(limit.max(0).min(MAX_RELATED_URLS))
+            .load(&*conn)?)
+    }
+
+    /// The number of submissions listed in the sitemap, see
+    /// [`sitemap`](Url::sitemap).
+    pub async fn sitemap_count(ctx: &Context) -> Result<i64> {
+        let conn = ctx.conn().await?;
+        Ok(urls::table
+            .filter(Listed::anonymous().syndicated().filter())
+            .select(diesel::dsl::count_star())
+            .get_result(&*conn)?)
+    }
+
+    /// IDs of the submissions listed in the sitemap, oldest first, with
+    /// the last time their discussion changed, i.e. the time of their
+    /// latest comment or edit. Only submissions listed for anonymous
+    /// viewers are included, which excludes deleted, removed, and shadow
+    /// removed submissions, drafts, unlisted, held and NSFW submissions,
+    /// and submissions to groups which are not syndicated.
+    pub async fn sitemap(
+        ctx: &Context,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<(UrlID, DateTime<Utc>)>> {
+        let conn = ctx.conn().await?;
+        let rows: Vec<(
+            UrlID,
+            NaiveDateTime,
+            Option<NaiveDateTime>,
+            Option<NaiveDateTime>,
+        )> = urls::table
+            .left_join(comments::table)
+            .filter(Listed::anonymous().syndicated().filter())
+            .group_by(urls::dsl::id)
+            .order_by(urls::dsl::created_at.asc())
+            .then_order_by(urls::dsl::id.asc())
+            .select((
+                urls::dsl::id,
+                urls::dsl::created_at,
+                urls::dsl::edited_at,
+                diesel::dsl::sql::<Nullable<Timestamp>>("MAX(comments.created_at)"),
+            ))
+            .offset(offset)
+            .limit(limit)
+            .load(&*conn)?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, created_at, edited_at, commented_at)| {
+                let changed_at = created_at.max(edited_at.unwrap_or(created_at));
+                let changed_at = changed_at.max(commented_at.unwrap_or(changed_at));
+                (id, DateTime::from_utc(changed_at, Utc))
+            })
+            .collect())
+    }
+
+    /// Recompute the trending rank of recent submissions. Ranks are
+    /// stored, rather than computed when listing submissions, such that
+    /// listing trending submissions uses an index, and such that ranks
+    /// only change when they are refreshed. Paging through trending
+    /// submissions thus doesn't skip or repeat submissions as votes come
+    /// in, at the cost of ranks lagging behind votes until the next
+    /// refresh. Submissions published more than thirty days ago rank zero.
+    pub async fn refresh_hot_ranks(ctx: &Context) -> Result<()> {
+        let gravity = ctx.config().trending_gravity();
+        let now = ctx.now().naive_utc();
+        let cutoff = (ctx.now() - Duration::days(TRENDING_DAYS)).naive_utc();
+        let conn = ctx.conn().await?;
+        let recent: Vec<(UrlID, i64, NaiveDateTime)> = urls::table
+            .filter(urls::dsl::published_at.ge(cutoff))
+            .select((urls::dsl::id, urls::dsl::score, urls::dsl::published_at))
+            .load(&*conn)?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            for (id, score, published_at) in recent {
+                let rank = hot_rank(score, now - published_at, gravity);
+                diesel::update(urls::table.find(id))
+                    .set(urls::dsl::hot_rank.eq(rank))
+                    .execute(&*conn)?;
+            }
+            let expired = urls::table
+                .filter(urls::dsl::published_at.lt(cutoff))
+                .filter(urls::dsl::hot_rank.ne(0.0));
+            diesel::update(expired)
+                .set(urls::dsl::hot_rank.eq(0.0))
+                .execute(&*conn)?;
+            Ok(())
+        })
+    }
+
+    /// Add the clicks buffered by the [`ClickCounter`](crate::clicks::ClickCounter)
+    /// to the stored counts, updating each submission once. Clicks are
+    /// kept in the buffer if storing them fails.
+    pub async fn flush_clicks(ctx: &Context) -> Result<usize> {
+        let clicks = ctx.click_counter().take();
+        if clicks.is_empty() {
+            return Ok(0);
+        }
+        let conn = match ctx.conn().await {
+            Ok(conn) => conn,
+            Err(err) => {
+                ctx.click_counter().restore(clicks);
+                return Err(err);
+            }
+        };
+        let flushed = conn.transaction::<_, anyhow::Error, _>(|| {
+            for (id, count) in &clicks {
+                diesel::update(urls::table.find(*id))
+                    .set(urls::dsl::clicks.eq(urls::dsl::clicks + *count))
+                    .execute(&*conn)?;
+            }
+            Ok(clicks.len())
+        });
+        if flushed.is_err() {
+            ctx.click_counter().restore(clicks);
+        }
+        flushed
+    }
+
+    /// Render the descriptions of submissions which were submitted
+    /// before rendered descriptions were stored. This is run on startup.
+    pub fn backfill_description_html<C>(conn: &C) -> Result<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let missing: Vec<(UrlID, Option<String>)> = urls::table
+            .filter(urls::dsl::description.is_not_null())
+            .filter(urls::dsl::description_html.is_null())
+            .select((urls::dsl::id, urls::dsl::description))
+            .load(conn)?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            for (id, description) in missing {
+                let html = render_description(conn, description.as_deref())?;
+                diesel::update(urls::table.find(id))
+                    .set(urls::dsl::description_html.eq(html))
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Store the registrable domain of submissions which don't have
+    /// one yet, i.e. those submitted before domains were stored. This
+    /// is run on startup.
+    pub fn backfill_domains<C>(conn: &C) -> Result<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let missing: Vec<(UrlID, Option<String>)> = urls::table
+            .filter(urls::dsl::domain.eq(""))
+            .filter(urls::dsl::canonical_url.is_not_null())
+            .select((urls::dsl::id, urls::dsl::canonical_url))
+            .load(conn)?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            for (id, canonical_url) in missing {
+                let domain = canonical_url.as_deref().map(domain::of_url);
+                diesel::update(urls::table.find(id))
+                    .set(urls::dsl::domain.eq(domain))
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Url {
+    /// Creates a new URL and crawls the linked html page for meta
+    /// data. The URL is stored even if the page can not be fetched,
+    /// in which case the meta data status is failed. If archiving is
+    /// enabled, the page is archived in the background, see
+    /// [`archive_pending`](Url::archive_pending). This fails if
+    /// the URL was already submitted, see [`submit`](Url::submit).
+    pub async fn create(ctx: &Context, input: NewUrlInput, created_by: UserID) -> Result<Self> {
+        input.check_url(ctx)?;
+        Validator::new().check("input", &input).finish()?;
+        input.kind()?;
+        let canonical_url = match &input.url {
+            Some(url) => Some(canonical::resolve(ctx, url).await?),
+            None => None,
+        };
+        Self::create_resolved(ctx, input, canonical_url, None, created_by, false).await
+    }
+
+    /// Creates a new URL like [`create`](Url::create), which was
+    /// already resolved to the given canonical URL, which text posts
+    /// don't have. If the URL is submitted again, `previous_id` is the
+    /// latest earlier submission. Held URLs are only announced once a
+    /// moderator releases them, see [`release`](Url::release), unlisted
+    /// URLs once they are made public, and scheduled URLs once they are
+    /// published.
+    async fn create_resolved(
+        ctx: &Context,
+        input: NewUrlInput,
+        canonical_url: Option<String>,
+        previous_id: Option<UrlID>,
+        created_by: UserID,
+        held: bool,
+    ) -> Result<Self> {
+        let mut url = Self::insert(
+            ctx,
+            input,
+            canonical_url,
+            previous_id,
+            created_by,
+            false,
+            ctx.now(),
+        )
+        .await?;
+        if held {
+            let conn = ctx.conn().await?;
+            diesel::update(&url)
+                .set(urls::dsl::held_at.eq(ctx.now().naive_utc()))
+                .execute(&*conn)?;
+            url = urls::table.find(url.id).get_result(&*conn)?;
+        }
+        url.fetch_metadata(ctx).await?;
+        if !held && !url.draft && !url.is_unlisted() {
+            Webhook::url_submitted(ctx, &url).await;
+            ctx.events().publish(Event::UrlSubmitted(url.id()));
+        }
+        Ok(url)
+    }
+
+    async fn insert(
+        ctx: &Context,
+        input: NewUrlInput,
+        canonical_url: Option<String>,
+        previous_id: Option<UrlID>,
+        created_by: UserID,
+        draft: bool,
+        created_at: DateTime<Utc>,
+    ) -> Result<Self> {
+        input.validate()?;
+        let kind = input.kind()?;
+        let NewUrlInput {
+            url,
+            text,
+            title,
+            description,
+            tags,
+            nsfw,
+            anonymous,
+            visibility,
+            group_id,
+            publish_at,
+        } = input;
+        let draft = draft || publish_at.is_some();
+        let anonymous = anonymous.unwrap_or(false);
+        if anonymous && !ctx.config().anonymous_submissions() {
+            return Err(anyhow!("Anonymous submissions are not enabled"));
+        }
+        let tags = tags.as_deref().map(tag::normalize_all).transpose()?;
+        let url = url
+            .map(|url| reqwest::Url::parse(url.trim()))
+            .transpose()?
+            .map(|url| url.to_string());
+        let domain = canonical_url.as_deref().map(domain::of_url);
+        let archive_status = if draft || kind == SubmissionKind::Text {
+            None
+        } else {
+            ctx.archiver().map(|_| ArchiveStatus::Pending)
+        };
+
+        // verify URL is unique, or follows its latest submission
+        if let Some(canonical_url) = &canonical_url {
+            let latest = Self::find_by_canonical_url(ctx, canonical_url).await?;
+            if latest.map(|latest| latest.id) != previous_id {
+                return Err(anyhow!("The url was already submitted"));
+            }
+        }
+        let description_html = render_description(&*ctx.conn().await?, description.as_deref())?;
+        let text_html = render_description(&*ctx.conn().await?, text.as_deref())?;
+
+        let url = Url {
+            id: UrlID::new(),
+            created_at: created_at.naive_utc(),
+            updated_at: ctx.now().naive_utc(),
+
+            url,
+            status_code: 0,
+            title,
+            description,
+            image: None,
+            created_by,
+            canonical_url,
+            fetched_title: None,
+            fetched_description: None,
+            metadata_status: MetadataStatus::Pending,
+            edited_at: None,
+            deleted_at: None,
+            comment_count: 0,
+            score: 0,
+            upvotes: 0,
+            downvotes: 0,
+            hot_rank: 0.0,
+            pinned_at: None,
+            domain,
+            removed_at: None,
+            shadow_removed: false,
+            preview_image: None,
+            last_checked_at: None,
+            http_status: None,
+            link_status: LinkStatus::Unknown,
+            archived_url: None,
+            archive_status,
+            archive_attempts: 0,
+            next_archive_at: archive_status.map(|_| ctx.now().naive_utc()),
+            draft,
+            clicks: 0,
+            views: 0,
+            nsfw: nsfw.unwrap_or(false),
+            nsfw_locked: false,
+            language: None,
+            description_html,
+            revision_count: 0,
+            locked_at: None,
+            lock_reason: None,
+            previous_id,
+            anonymous,
+            kind,
+            text,
+            text_html,
+            held_at: None,
+            visibility: visibility.unwrap_or(SubmissionVisibility::Public),
+            published_at: created_at.naive_utc(),
+            group_id,
+            publish_at: publish_at.map(|at| at.naive_utc()),
+        };
+
+        diesel::insert_into(urls::table)
+            .values(&url)
+            .execute(&*ctx.conn().await?)?;
+        if let Some(tags) = tags {
+            Tag::set_for_url(ctx, url.id, &tags).await?;
+        }
+        Ok(url)
+    }
+
+    /// Submit a new URL. If the canonical form of the URL was already
+    /// submitted, the existing submission is returned instead of
+    /// failing, such that clients can direct users to the discussion.
+    /// Once the existing submission is older than
+    /// [`Config::resubmit_after`](crate::Config::resubmit_after), or if
+    /// it was deleted or removed, the URL is submitted again instead,
+    /// linking to the existing submission from
+    /// [`previous_submissions`](Url::previous_submissions).
+    /// New submissions are stored under the canonical URL the link
+    /// resolves to, see [`canonical::resolve`], while the submitted URL
+    /// is kept for display.
+    /// Drafts are private, which is why submitting the URL of a draft
+    /// publishes the draft as the new submission instead, even if it
+    /// is the draft of another user. Text posts are never duplicates.
+    /// Submissions are checked by the spam filter first, which rejects
+    /// spam, and holds suspect submissions until a moderator reviews
+    /// them, see [`check_spam`](Url::check_spam). Submitting is rate
+    /// limited per author, see [`RateLimit`]. Only members of a group
+    /// may submit to it. Submissions scheduled to be published later
+    /// are drafts until then, and their URL can not be submitted by
+    /// other users in the meantime.
+    /// Only links to public web pages can be submitted, see
+    /// [`link::check`].
+    pub async fn submit(
+        ctx: &Context,
+        input: NewUrlInput,
+        created_by: UserID,
+    ) -> Result<SubmitUrlResult> {
+        input.check_url(ctx)?;
+        Validator::new().check("input", &input).finish()?;
+        if input.anonymous == Some(true) && !ctx.config().anonymous_submissions() {
+            return Err(anyhow!("Anonymous submissions are not enabled"));
+        }
+        input.kind()?;
+        if let Some(publish_at) = input.publish_at {
+            check_publish_at(ctx, publish_at)?;
+        }
+        if let Some(group_id) = input.group_id {
+            let group = Group::find(ctx, group_id).await?;
+            if !group.is_member(ctx, created_by).await? {
+                return Err(anyhow!("Only members of a group can submit to it"));
+            }
+        }
+        let author = User::find(ctx, created_by).await?;
+        RateLimit::check(ctx, &author, PostingAction::SubmitUrl).await?;
+        let held = Self::check_spam(ctx, &input, author).await?;
+        let link = match &input.url {
+            Some(link) => link.clone(),
+            None => {
+                let url = Self::create_resolved(ctx, input, None, None, created_by, held).await?;
+                return Ok(SubmitUrlResult {
+                    url,
+                    duplicate: false,
+                });
+            }
+        };
+        let (canonical_url, duplicate) = Self::resolve_duplicate(ctx, &link).await?;
+        match duplicate {
+            Some(url) if url.is_scheduled() && url.created_by != created_by => {
+                Err(anyhow!("The url was already submitted"))
+            }
+            Some(mut url) if url.draft && !url.is_deleted() => {
+                let tags = input.tags.as_deref().map(tag::normalize_all).transpose()?;
+                Tag::set_for_url(ctx, url.id, &tags.unwrap_or_default()).await?;
+                url.title = input.title;
+                url.description_html =
+                    render_description(&*ctx.conn().await?, input.description.as_deref())?;
+                url.description = input.description;
+                url.nsfw = input.nsfw.unwrap_or(url.nsfw);
+                url.anonymous = input.anonymous.unwrap_or(false);
+                url.visibility = input.visibility.unwrap_or(SubmissionVisibility::Public);
+                url.group_id = input.group_id;
+                url.held_at = Some(ctx.now().naive_utc()).filter(|_| held);
+                url.publish_draft(ctx, created_by, input.publish_at).await?;
+                url.fetch_metadata(ctx).await?;
+                Ok(SubmitUrlResult {
+                    url,
+                    duplicate: false,
+                })
+            }
+            Some(url) if !url.may_resubmit(ctx) => Ok(SubmitUrlResult {
+                url,
+                duplicate: true,
+            }),
+            previous => Ok(SubmitUrlResult {
+                url: Self::create_resolved(
+                    ctx,
+                    input,
+                    Some(canonical_url),
+                    previous.map(|url| url.id),
+                    created_by,
+                    held,
+                )
+                .await?,
+                duplicate: false,
+            }),
+        }
+    }
+
+    /// Check the given submission with the spam filter, see
+    /// [`spam`](crate::spam). This fails if the submission is spam,
+    /// and returns whether it should be held for review by a moderator.
+    async fn check_spam(ctx: &Context, input: &NewUrlInput, author: User) -> Result<bool> {
+        let mut content = SpamCheckInput::new(ctx, SpamContentKind::Submission, author);
+        content.url = input.url.clone();
+        content.title = input.title.clone();
+        content.text = input.text.clone().or_else(|| input.description.clone());
+        match ctx.spam_filter().check(ctx, content).await {
+            SpamVerdict::Ham => Ok(false),
+            SpamVerdict::Suspect => Ok(true),
+            SpamVerdict::Spam => Err(anyhow!("This submission can not be accepted")),
+        }
+    }
+
+    /// Whether the URL of this submission may be submitted again, which
+    /// is the case once it is older than
+    /// [`Config::resubmit_after`](crate::Config::resubmit_after), or if
+    /// it was deleted or removed.
+    fn may_resubmit(&self, ctx: &Context) -> bool {
+        self.is_deleted()
+            || self.removed_at.is_some()
+            || self.created_at() + ctx.config().resubmit_after() <= ctx.now()
+    }
+
+    /// Earlier submissions of the same URL, latest first, such that
+    /// readers can find older discussions. Deleted and removed
+    /// submissions are left out.
+    pub async fn previous_submissions(&self, ctx: &Context) -> Result<Vec<Self>> {
+        if self.canonical_url.is_none() {
+            return Ok(vec![]);
+        }
+        let mut earlier: HashMap<UrlID, Self> = urls::table
+            .filter(urls::dsl::canonical_url.eq(&self.canonical_url))
+            .filter(urls::dsl::id.ne(self.id))
+            .load::<Self>(&*ctx.conn().await?)?
+            .into_iter()
+            .map(|url| (url.id, url))
+            .collect();
+        let mut previous = vec![];
+        let mut previous_id = self.previous_id;
+        while let Some(url) = previous_id.and_then(|id| earlier.remove(&id)) {
+            previous_id = url.previous_id;
+            if !url.is_deleted() && url.removed_at.is_none() {
+                previous.push(url);
+            }
+        }
+        Ok(previous)
+    }
+
+    /// The latest earlier submission a submission of the given URL would
+    /// be a duplicate of, if any. URLs are duplicates if their canonical
+    /// forms are equal. Deleted and removed submissions count as well,
+    /// see [`submit`](Url::submit) for when URLs may be submitted again.
+    pub(crate) async fn find_duplicate(ctx: &Context, url: &str) -> Result<Option<Self>> {
+        let canonical = canonical::canonicalize(url, ctx.config().tracking_params())?;
+        Self::find_by_canonical_url(ctx, &canonical).await
+    }
+
+    /// The latest submission of the given canonical URL, which is the
+    /// one no other submission of it follows.
+    async fn find_by_canonical_url(ctx: &Context, canonical_url: &str) -> Result<Option<Self>> {
+        let submissions: Vec<Self> = urls::table
+            .filter(urls::dsl::canonical_url.eq(canonical_url))
+            .load(&*ctx.conn().await?)?;
+        let followed: HashSet<UrlID> = submissions
+            .iter()
+            .filter_map(|url| url.previous_id)
+            .collect();
+        Ok(submissions
+            .into_iter()
+            .find(|url| !followed.contains(&url.id)))
+    }
+
+    /// Resolve the canonical URL a new submission of the given URL would
+    /// be stored under, returning it together with the earlier submission
+    /// the URL is a duplicate of, if any. The page is only fetched if the
+    /// URL is not a duplicate as is, see [`find_duplicate`](Url::find_duplicate).
+    async fn resolve_duplicate(ctx: &Context, url: &str) -> Result<(String, Option<Self>)> {
+        if let Some(duplicate) = Self::find_duplicate(ctx, url).await? {
+            let canonical_url = duplicate.canonical_url.clone().unwrap_or_default();
+            return Ok((canonical_url, Some(duplicate)));
+        }
+        let canonical_url = canonical::resolve(ctx, url).await?;
+        let duplicate = Self::find_by_canonical_url(ctx, &canonical_url).await?;
+        Ok((canonical_url, duplicate))
+    }
+
+    /// Check whether the given URL was submitted before, without
+    /// submitting it. The URL is validated and resolved the same
+    /// way as by [`submit`](Url::submit), such that the check finds an
+    /// earlier submission exactly if submitting the URL would return it
+    /// as a duplicate. Checks are available to anonymous users, and are
+    /// therefore rate limited per user, or per IP address.
+    pub async fn check(ctx: &Context, url: &str) -> Result<UrlCheck> {
+        let client = match (ctx.maybe_user_id(), ctx.remote_ip_address()) {
+            (Some(user_id), _) => user_id.to_string(),
+            (None, Some(ip)) => ip.to_string(),
+            (None, None) => String::new(),
+        };
+        ctx.rate_limiter().check(
+            "check_url",
+            &client,
+            CHECK_LIMIT_PER_MINUTE,
+            Duration::minutes(1),
+            ctx.now(),
+        )?;
+        let input = NewUrlInput {
+            url: Some(url.to_string()),
+            text: None,
+            title: None,
+            description: None,
+            tags: None,
+            nsfw: None,
+            anonymous: None,
+            visibility: None,
+            group_id: None,
+            publish_at: None,
+        };
+        input.check_url(ctx)?;
+        input.validate()?;
+        let url = Self::resolve_duplicate(ctx, url).await?.1.filter(|url| {
+            if url.draft && !url.is_deleted() {
+                Some(url.created_by) == ctx.maybe_user_id()
+            } else {
+                !url.may_resubmit(ctx)
+            }
+        });
+        Ok(UrlCheck { url })
+    }
+
+    /// Fetch the current contents of the URL and update the meta
+    /// information and status code. Failing to fetch the page is
+    /// recorded in the meta data status, rather than returned. If the
+    /// page has a preview image, a copy of it is stored, and failing to
+    /// fetch the image leaves the URL without preview image. The language
+    /// of the page is detected from its title and description. Text
+    /// posts have no page, and are left as they are.
+    pub async fn fetch_metadata(&mut self, ctx: &Context) -> Result<()> {
+        let canonical_url = match self.canonical_url.clone() {
+            Some(canonical_url) => canonical_url,
+            None => return Ok(()),
+        };
+        match fetch::fetch_page(ctx, &canonical_url).await {
+            Ok(page) if page.status.is_success() => {
+                self.status_code = page.status.as_u16().into();
+                self.fetched_title = page.meta.title.or_else(|| self.fetched_title.clone());
+                self.fetched_description = page
+                    .meta
+                    .description
+                    .or_else(|| self.fetched_description.clone());
+                self.image = page.meta.image.or_else(|| self.image.clone());
+                self.metadata_status = MetadataStatus::Ok;
+                let text = [&self.fetched_title, &self.fetched_description]
+                    .iter()
+                    .filter_map(|text| text.as_deref())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                self.language = language::detect(&text).map(str::to_string);
+                // never fall back to the remote image, which
+                // would leak the IP addresses of readers
+                self.preview_image = match &self.image {
+                    Some(image) => match preview::store(ctx, self.id, image).await {
+                        Ok(key) => Some(key),
+                        Err(err) => {
+                            log::info!("Failed to store preview image {}: {}", image, err);
+                            None
+                        }
+                    },
+                    None => None,
+                };
+            }
+            Ok(page) => {
+                self.status_code = page.status.as_u16().into();
+                self.metadata_status = MetadataStatus::Failed;
+            }
+            Err(err) => {
+                log::info!("Failed to fetch {}: {}", canonical_url, err);
+                self.metadata_status = MetadataStatus::Failed;
+            }
+        }
+        self.updated_at = ctx.now().naive_utc();
+
+        *self = self.save_changes(&*ctx.conn().await?)?;
+        if let Err(err) = UrlEmbed::refresh(ctx, self).await {
+            log::info!("Failed to unfurl {}: {}", canonical_url, err);
+        }
+        Ok(())
+    }
+
+    /// Check whether the link still works, and record the status found.
+    /// Checks are limited per registrable domain, to not overwhelm any
+    /// single site, and a check exceeding the limit fails with
+    /// [`RateLimited`] without recording anything. Failing to reach the
+    /// page is recorded as an [unknown](LinkStatus::Unknown) status.
+    /// Text posts have no link to check.
+    pub async fn check_link(&mut self, ctx: &Context) -> Result<()> {
+        let (canonical_url, domain) = match (&self.canonical_url, &self.domain) {
+            (Some(canonical_url), Some(domain)) => (canonical_url.clone(), domain.clone()),
+            _ => return Ok(()),
+        };
+        ctx.rate_limiter().check(
+            "link_check",
+            &domain,
+            LINK_CHECKS_PER_DOMAIN_PER_MINUTE,
+            Duration::minutes(1),
+            ctx.now(),
+        )?;
+        let check = fetch::check_link(ctx, &canonical_url).await;
+        if let Err(err) = &check {
+            log::info!("Failed to check {}: {}", canonical_url, err);
+        }
+        let http_status: Option<i32> = check.as_ref().ok().map(|link| link.status.as_u16().into());
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set((
+                urls::dsl::last_checked_at.eq(ctx.now().naive_utc()),
+                urls::dsl::http_status.eq(http_status),
+                urls::dsl::link_status.eq(LinkStatus::from_check(&check)),
+            ))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        drop(conn);
+
+        // give pages which broke without a snapshot another chance,
+        // in case the archive still has an older copy
+        let archiving =
+            self.archived_url.is_some() || self.archive_status == Some(ArchiveStatus::Pending);
+        if self.link_status == LinkStatus::Broken && !archiving && ctx.archiver().is_some() {
+            let conn = ctx.conn().await?;
+            diesel::update(&*self)
+                .set((
+                    urls::dsl::archive_status.eq(ArchiveStatus::Pending),
+                    urls::dsl::archive_attempts.eq(0),
+                    urls::dsl::next_archive_at.eq(ctx.now().naive_utc()),
+                ))
+                .execute(&*conn)?;
+            *self = urls::table.find(self.id).get_result(&*conn)?;
+        }
+        Ok(())
+    }
+
+    /// Attempt to archive the linked page, recording the snapshot if
+    /// the archive stored one. Failed attempts are retried with an
+    /// exponential backoff, until archiving is given up after
+    /// [`MAX_ARCHIVE_ATTEMPTS`] attempts. Pages on hosts which may not
+    /// be fetched by the server are never handed to the archive, since
+    /// the archive may reach hosts the server can not.
+    pub async fn archive(&mut self, ctx: &Context) -> Result<()> {
+        let (archiver, canonical_url) = match (ctx.archiver(), self.canonical_url.clone()) {
+            (Some(archiver), Some(canonical_url)) => (archiver, canonical_url),
+            _ => return Ok(()),
+        };
+        let result = match fetch::check_fetchable(ctx, &canonical_url).await {
+            Ok(()) => archiver.archive(ctx, &canonical_url).await,
+            Err(err) => Err(err),
+        };
+
+        let attempts = self.archive_attempts + 1;
+        let (archived_url, status, next_archive_at) = match result {
+            Ok(archived_url) => (Some(archived_url), ArchiveStatus::Archived, None),
+            Err(err) => {
+                log::info!("Failed to archive {}: {}", canonical_url, err);
+                if attempts >= MAX_ARCHIVE_ATTEMPTS {
+                    (self.archived_url.clone(), ArchiveStatus::Failed, None)
+                } else {
+                    let next = ctx.now() + archive_backoff(attempts);
+                    (
+                        self.archived_url.clone(),
+                        ArchiveStatus::Pending,
+                        Some(next.naive_utc()),
+                    )
+                }
+            }
+        };
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set((
+                urls::dsl::archived_url.eq(archived_url),
+                urls::dsl::archive_status.eq(status),
+                urls::dsl::archive_attempts.eq(attempts),
+                urls::dsl::next_archive_at.eq(next_archive_at),
+            ))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        Ok(())
+    }
+
+    /// Archive the pages of submissions which are waiting to be
+    /// archived, and whose next attempt is due, see
+    /// [`archive`](Url::archive). Returns the number of attempts made.
+    pub async fn archive_pending(ctx: &Context) -> Result<usize> {
+        if ctx.archiver().is_none() {
+            return Ok(0);
+        }
+        let pending: Vec<Self> = urls::table
+            .filter(urls::dsl::archive_status.eq(ArchiveStatus::Pending))
+            .filter(urls::dsl::next_archive_at.le(ctx.now().naive_utc()))
+            .filter(urls::dsl::deleted_at.is_null())
+            .order_by(urls::dsl::next_archive_at.asc())
+            .limit(ARCHIVE_BATCH_SIZE)
+            .load(&*ctx.conn().await?)?;
+
+        let count = pending.len();
+        for mut url in pending {
+            url.archive(ctx)
+                .await
+                .map_err(|err| log::error!("Failed to archive {}: {}", url.id, err))
+                .ok();
+        }
+        Ok(count)
+    }
+
+    /// Check the link right away, rather than waiting for the next
+    /// periodic check. This is only available to administrators.
+    pub async fn recheck_link(&mut self, ctx: &Context) -> Result<()> {
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.recheck_urls())
+            .await?;
+        self.check_link(ctx).await
+    }
+
+    /// Check the links of submissions which were never checked, or
+    /// not within the last week, least recently checked first. Links
+    /// of domains which were checked too often recently are skipped,
+    /// and checked by a later run instead. Returns the number of
+    /// links checked.
+    pub async fn check_due_links(ctx: &Context) -> Result<usize> {
+        let due_before = (ctx.now() - Duration::days(DAYS_BETWEEN_LINK_CHECKS)).naive_utc();
+        let due: Vec<Self> = urls::table
+            .filter(urls::dsl::deleted_at.is_null())
+            .filter(urls::dsl::kind.eq(SubmissionKind::Link))
+            .filter(
+                urls::dsl::last_checked_at
+                    .is_null()
+                    .or(urls::dsl::last_checked_at.lt(due_before)),
+            )
+            .order_by(urls::dsl::last_checked_at.asc())
+            .then_order_by(urls::dsl::created_at.desc())
+            .limit(LINK_CHECK_BATCH_SIZE)
+            .load(&*ctx.conn().await?)?;
+
+        let mut checked = 0;
+        for mut url in due {
+            match url.check_link(ctx).await {
+                Ok(()) => checked += 1,
+                Err(err) if err.is::<RateLimited>() => {}
+                Err(err) => log::error!("Failed to check link of {}: {}", url.id, err),
+            }
+        }
+        Ok(checked)
+    }
+
+    /// Check if the logged in user may edit this URL. Submitters may
+    /// edit their own submissions within the configured edit window,
+    /// administrators may edit any submission at any time.
+    async fn check_may_edit(&self, ctx: &Context) -> Result<()> {
+        let user = ctx.verified_user().await?;
+        if self.is_deleted() {
+            return Err(anyhow!("This submission was deleted"));
+        }
+        let may_edit_any = user
+            .check_permissions(ctx, |perm| perm.edit_any_url())
+            .await
+            .is_ok();
+        if !may_edit_any {
+            if self.created_by != user.id() {
+                return Err(EditNotAllowed::new(EditNotAllowedReason::NotAuthor).into());
+            }
+            if !self.draft && self.created_at() + ctx.config().url_edit_window() < ctx.now() {
+                return Err(EditNotAllowed::new(EditNotAllowedReason::WindowClosed).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Edit the title, description, tags, NSFW mark, and visibility of
+    /// this URL, see [`check_may_edit`](Url::check_may_edit) for who may
+    /// edit a URL, and [`set_visibility`](Url::set_visibility) for who may
+    /// change its visibility. The URL itself can not be changed. The
+    /// previous title and description are kept as a [`Revision`] if they
+    /// changed. Drafts can be scheduled, rescheduled, and unscheduled,
+    /// see [`schedule`](Url::schedule).
+    pub async fn update(&mut self, ctx: &Context, input: UpdateUrlInput) -> Result<()> {
+        let input = UpdateUrlInput {
+            title: input.title.map(|title| title.trim().into()),
+            description: input.description.map(|desc| desc.trim().into()),
+            tags: input.tags,
+            nsfw: input.nsfw,
+            visibility: input.visibility,
+            publish_at: input.publish_at,
+            unschedule: input.unschedule,
+        };
+        input.validate()?;
+        let UpdateUrlInput {
+            title,
+            description,
+            tags,
+            nsfw,
+            visibility,
+            publish_at,
+            unschedule,
+        } = input;
+        let unschedule = unschedule.unwrap_or(false);
+        let edits = title.is_some()
+            || description.is_some()
+            || tags.is_some()
+            || nsfw.is_some()
+            || publish_at.is_some()
+            || unschedule;
+        if edits || visibility.is_none() {
+            self.check_may_edit(ctx).await?;
+        }
+        if publish_at.is_some() || unschedule {
+            self.schedule(ctx, publish_at.filter(|_| !unschedule))
+                .await?;
+        }
+        if let Some(visibility) = visibility {
+            self.set_visibility(ctx, visibility).await?;
+        }
+        if let Some(tags) = tags {
+            Tag::set_for_url(ctx, self.id, &tag::normalize_all(&tags)?).await?;
+        }
+        if let Some(nsfw) = nsfw {
+            self.set_nsfw(ctx, nsfw).await?;
+        }
+        if title.is_none() && description.is_none() {
+            return Ok(());
+        }
+
+        let title = title.or_else(|| self.title.clone());
+        let description = description.or_else(|| self.description.clone());
+        let conn = ctx.conn().await?;
+        // drafts are private, their edits are not kept
+        let revision = if !self.draft && (title != self.title || description != self.description) {
+            self.revision_count += 1;
+            Some(Revision::of_url(
+                self.id,
+                ctx.user_id()?,
+                self.title.clone(),
+                self.description.clone(),
+                ctx.now().naive_utc(),
+            ))
+        } else {
+            None
+        };
+        if description != self.description {
+            self.description_html = render_description(&*conn, description.as_deref())?;
+        }
+        self.title = title;
+        self.description = description;
+        self.edited_at = Some(ctx.now().naive_utc());
+        self.updated_at = ctx.now().naive_utc();
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            if let Some(revision) = &revision {
+                diesel::insert_into(revisions::table)
+                    .values(revision)
+                    .execute(&*conn)?;
+            }
+            Ok(self.save_changes(&*conn)?)
+        })?;
+        Ok(())
+    }
+
+    /// Mark this URL as not safe for work, or clear the mark. This
+    /// follows the same rules as [`update`](Url::update), except that
+    /// a mark set by a moderator can only be cleared by a moderator.
+    pub async fn set_nsfw(&mut self, ctx: &Context, nsfw: bool) -> Result<()> {
+        self.check_may_edit(ctx).await?;
+        let moderator = ctx
+            .user()
+            .await?
+            .check_permissions(ctx, |perm| perm.edit_any_url())
+            .await
+            .is_ok();
+        let locked = if moderator {
+            nsfw
+        } else if self.nsfw_locked && !nsfw {
+            return Err(anyhow!(
+                "A moderator marked this submission as NSFW, only moderators can clear the mark"
+            ));
+        } else {
+            self.nsfw_locked
+        };
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set((
+                urls::dsl::nsfw.eq(nsfw),
+                urls::dsl::nsfw_locked.eq(locked),
+                urls::dsl::updated_at.eq(ctx.now().naive_utc()),
+            ))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        Ok(())
+    }
+
+    /// List or unlist this URL. Unlike other edits, this is not limited
+    /// to the edit window, but only the submitter and moderators may
+    /// change the visibility of a URL. Unlisted URLs are left out of all
+    /// listings and the search index, and their tags don't count towards
+    /// the usage of the tags. Making a URL public dates it to now, such
+    /// that it is listed like a new submission, and announces it to
+    /// webhooks unless it is held.
+    pub async fn set_visibility(
+        &mut self,
+        ctx: &Context,
+        visibility: SubmissionVisibility,
+    ) -> Result<()> {
+        let user = ctx.verified_user().await?;
+        if self.is_deleted() {
+            return Err(anyhow!("This submission was deleted"));
+        }
+        let may_edit_any = user
+            .check_permissions(ctx, |perm| perm.edit_any_url())
+            .await
+            .is_ok();
+        if !may_edit_any && self.created_by != user.id() {
+            return Err(EditNotAllowed::new(EditNotAllowedReason::NotAuthor).into());
+        }
+        if visibility == self.visibility {
+            return Ok(());
+        }
+        // drafts are dated to the time they are published instead
+        let now = ctx.now().naive_utc();
+        let published_at = match visibility {
+            SubmissionVisibility::Public if !self.draft => now,
+            _ => self.published_at,
+        };
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set((
+                urls::dsl::visibility.eq(visibility),
+                urls::dsl::published_at.eq(published_at),
+                urls::dsl::updated_at.eq(now),
+            ))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        drop(conn);
+        if self.draft {
+            return Ok(());
+        }
+        match visibility {
+            SubmissionVisibility::Public => {
+                Tag::count_for_url(ctx, self.id, 1).await?;
+                ctx.search().index_url(self)?;
+                if !self.is_held() {
+                    Webhook::url_submitted(ctx, self).await;
+                    ctx.events().publish(Event::UrlSubmitted(self.id()));
+                }
+            }
+            SubmissionVisibility::Unlisted => {
+                Tag::count_for_url(ctx, self.id, -1).await?;
+                ctx.search().delete_url(self)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the tags of this URL. This follows the same rules
+    /// as [`update`](Url::update).
+    pub async fn set_tags(&self, ctx: &Context, tags: &[String]) -> Result<()> {
+        self.check_may_edit(ctx).await?;
+        Tag::set_for_url(ctx, self.id, &tag::normalize_all(tags)?).await
+    }
+
+    /// Deletes the given URL. URLs can only be deleted by moderators or
+    /// the user who created them. Deleted URLs are removed from all
+    /// listings and the search index, but the row and its comments are
+    /// kept. Deleting a URL again does nothing.
+    pub async fn delete(&mut self, ctx: &Context) -> Result<()> {
+        if self.created_by != ctx.user_id()? {
+            ctx.user()
+                .await?
+                .check_permissions(ctx, |perm| perm.delete_any_url())
+                .await?;
+        }
+        if self.is_deleted() {
+            return Ok(());
+        }
+        // deleted submissions no longer take up a pin
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set((
+                urls::dsl::deleted_at.eq(ctx.now().naive_utc()),
+                urls::dsl::updated_at.eq(ctx.now().naive_utc()),
+                urls::dsl::pinned_at.eq(None::<NaiveDateTime>),
+            ))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        ctx.search().delete_url(self)?;
+        Ok(())
+    }
+
+    /// Pin the URL to the profile of the logged in user. Users can
+    /// only pin their own submissions, and at most [`MAX_PINNED_URLS`]
+    /// of them. Anonymous submissions can not be pinned, since that
+    /// would reveal their submitter. Pinning a pinned URL again does
+    /// nothing.
+    pub async fn pin(&mut self, ctx: &Context) -> Result<()> {
+        let user_id = ctx.user_id()?;
+        if self.created_by != user_id {
+            return Err(anyhow!("You can only pin your own submissions"));
+        }
+        if self.anonymous {
+            return Err(anyhow!("Anonymous submissions can not be pinned"));
+        }
+        if self.is_deleted() {
+            return Err(anyhow!("This submission was deleted"));
+        }
+        if self.is_pinned() {
+            return Ok(());
+        }
+        let conn = ctx.conn().await?;
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            let pinned: i64 = urls::table
+                .filter(urls::dsl::created_by.eq(user_id))
+                .filter(urls::dsl::pinned_at.is_not_null())
+                .select(diesel::dsl::count_star())
+                .get_result(&*conn)?;
+            if pinned >= MAX_PINNED_URLS {
+                return Err(anyhow!(
+                    "You can pin at most {} submissions, please unpin one first",
+                    MAX_PINNED_URLS
+                ));
+            }
+            diesel::update(&*self)
+                .set(urls::dsl::pinned_at.eq(ctx.now().naive_utc()))
+                .execute(&*conn)?;
+            Ok(urls::table.find(self.id).get_result(&*conn)?)
+        })?;
+        Ok(())
+    }
+
+    /// Unpin the URL from the profile of the logged in user.
+    /// Unpinning a URL which is not pinned does nothing.
+    pub async fn unpin(&mut self, ctx: &Context) -> Result<()> {
+        if self.created_by != ctx.user_id()? {
+            return Err(anyhow!("You can only pin your own submissions"));
+        }
+        if !self.is_pinned() {
+            return Ok(());
+        }
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set(urls::dsl::pinned_at.eq(None::<NaiveDateTime>))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        Ok(())
+    }
+
+    /// Returns the submissions on the profile of the given user, in a
+    /// way that's suitable for use with a Relay connection. Pinned
+    /// submissions are listed first in the order they were pinned,
+    /// followed by all other submissions in the given order, see
+    /// [`all_submissions`](Url::all_submissions).
+    pub async fn profile(
+        ctx: &Context,
+        filter: UrlFilter<'_>,
+        sort: UrlSort,
+        after: Option<ProfileCursor>,
+        before: Option<ProfileCursor>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        use urls::dsl::{id, pinned_at};
+
+        let created_by = filter
+            .created_by
+            .ok_or_else(|| anyhow!("Profiles list the submissions of a single user"))?;
+        let mut urls = vec![];
+
+        if !matches!(after, Some(ProfileCursor::Listed(_))) {
+            let listed = Listed::to_viewer(ctx).await?;
+            let conn = ctx.conn().await?;
+            let mut query = urls::table
+                .filter(urls::dsl::created_by.eq(created_by))
+                .filter(listed.filter())
+                .filter(urls::dsl::anonymous.eq_any(Self::listed_anonymous(ctx, created_by)))
+                .filter(pinned_at.is_not_null())
+                .order_by(pinned_at.asc())
+                .then_order_by(id.asc())
+                .into_boxed();
+            if let Some(ProfileCursor::Pinned {
+                pinned_at: at,
+                id: after,
+            }) = after
+            {
+                query = query.filter(pinned_at.gt(at).or(pinned_at.eq(at).and(id.gt(after))));
+            }
+            if let Some(ProfileCursor::Pinned {
+                pinned_at: at,
+                id: before,
+            }) = before
+            {
+                query = query.filter(pinned_at.lt(at).or(pinned_at.eq(at).and(id.lt(before))));
+            }
+            if let Some(limit) = limit {
+                query = query.limit(limit);
+            }
+            urls = query.load(&*conn)?;
+        }
+
+        let remaining = limit.map(|limit| limit - urls.len() as i64);
+        if matches!(before, Some(ProfileCursor::Pinned { .. })) || remaining == Some(0) {
+            return Ok(urls);
+        }
+        let listed = |cursor| match cursor {
+            Some(ProfileCursor::Listed(cursor)) => Some(cursor),
+            _ => None,
+        };
+        let filter = UrlFilter {
+            unpinned: true,
+            ..filter
+        };
+        urls.extend(
+            Self::all_submissions(ctx, filter, sort, listed(after), listed(before), remaining)
+                .await?,
+        );
+        Ok(urls)
+    }
+
+    /// Vote on the URL as the logged in user, and reload it with the
+    /// updated score. Voting again in the same direction does nothing,
+    /// while voting in the other direction changes the existing vote.
+    /// Downvotes are only accepted if they are enabled, and from users
+    /// with at least [`Config::downvote_min_karma`](crate::Config::downvote_min_karma).
+    /// Whether submitters may vote on their own URLs is configurable.
+    /// Votes change the karma of the submitter, unless the URL was
+    /// submitted anonymously.
+    pub async fn vote(&mut self, ctx: &Context, direction: VoteDirection) -> Result<()> {
+        let user_id = ctx.user_id()?;
+        if self.is_deleted() {
+            return Err(anyhow!("This submission was deleted"));
+        }
+        if direction == VoteDirection::Down {
+            if !ctx.config().downvotes_enabled() {
+                return Err(anyhow!("Downvotes are not enabled"));
+            }
+            let min_karma = ctx.config().downvote_min_karma();
+            if ctx.user().await?.karma() < min_karma {
+                return Err(anyhow!("You need at least {} karma to downvote", min_karma));
+            }
+        }
+        if self.created_by == user_id && !ctx.config().allow_self_votes() {
+            return Err(anyhow!("You can not vote on your own submission"));
+        }
+        let conn = ctx.conn().await?;
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            // write before reading anything, such that concurrent
+            // votes are serialized by the database
+            let inserted = diesel::insert_or_ignore_into(url_upvotes::table)
+                .values((
+                    url_upvotes::dsl::user_id.eq(user_id),
+                    url_upvotes::dsl::url_id.eq(self.id),
+                    url_upvotes::dsl::created_at.eq(ctx.now().naive_utc()),
+                    url_upvotes::dsl::direction.eq(direction),
+                ))
+                .execute(&*conn)?;
+            let mut score = 0;
+            if inserted > 0 {
+                score += count_vote(&*conn, self.id, direction, 1)?;
+            } else {
+                let changed = url_upvotes::table
+                    .filter(url_upvotes::dsl::url_id.eq(self.id))
+                    .filter(url_upvotes::dsl::user_id.eq(user_id))
+                    .filter(url_upvotes::dsl::direction.ne(direction));
+                let changed = diesel::update(changed)
+                    .set(url_upvotes::dsl::direction.eq(direction))
+                    .execute(&*conn)?;
+                if changed > 0 {
+                    score += count_vote(&*conn, self.id, direction.opposite(), -1)?;
+                    score += count_vote(&*conn, self.id, direction, 1)?;
+                }
+            }
+            self.count_karma(ctx, &*conn, score)?;
+            Ok(urls::table.find(self.id).get_result(&*conn)?)
+        })?;
+        ctx.loaders()
+            .votes
+            .prime((user_id, self.id), Some(direction));
+        Ok(())
+    }
+
+    /// Rescind a vote for the URL as the logged in user, and reload
+    /// it with the updated score. Rescinding a missing vote does
+    /// nothing.
+    pub async fn unvote(&mut self, ctx: &Context) -> Result<()> {
+        let user_id = ctx.user_id()?;
+        let conn = ctx.conn().await?;
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            let vote = url_upvotes::table
+                .filter(url_upvotes::dsl::url_id.eq(self.id))
+                .filter(url_upvotes::dsl::user_id.eq(user_id));
+            let mut score = 0;
+            for direction in [VoteDirection::Up, VoteDirection::Down] {
+                let deleted =
+                    diesel::delete(vote.filter(url_upvotes::dsl::direction.eq(direction)))
+                        .execute(&*conn)?;
+                if deleted > 0 {
+                    score += count_vote(&*conn, self.id, direction, -1)?;
+                }
+            }
+            self.count_karma(ctx, &*conn, score)?;
+            Ok(urls::table.find(self.id).get_result(&*conn)?)
+        })?;
+        ctx.loaders().votes.prime((user_id, self.id), None);
+        Ok(())
+    }
+
+    /// Credit the submitter with the karma for the given change of the
+    /// score of this URL, as part of the transaction of a vote.
+    fn count_karma<C>(&self, ctx: &Context, conn: &C, score: i64) -> QueryResult<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        if self.anonymous {
+            return Ok(());
+        }
+        let karma = score * ctx.config().karma_submission_weight();
+        User::add_karma(conn, self.created_by, karma)
+    }
+
+    /// Users who upvoted this URL, most recent vote first, in a way
+    /// that's suitable for use with a Relay connection. Voters are
+    /// only visible to the submitter and administrators, unless the
+    /// server makes them public. Users who opted out of being listed
+    /// are left out, but their votes are still counted, see
+    /// [`UserPreferences::hide_votes`](crate::db::models::UserPreferences::hide_votes).
+    pub async fn voters(
+        &self,
+        ctx: &Context,
+        after: Option<UserID>,
+        before: Option<UserID>,
+        limit: Option<i64>,
+    ) -> Result<Vec<User>> {
+        use url_upvotes::dsl::{created_at, direction, url_id, user_id};
+
+        if !ctx.config().public_voters() {
+            let viewer = ctx.user().await?;
+            if viewer.id() != self.created_by {
+                viewer
+                    .check_permissions(ctx, |perm| perm.view_voters())
+                    .await?;
+            }
+        }
+
+        let conn = ctx.conn().await?;
+        let hidden = user_preferences::table
+            .filter(user_preferences::dsl::hide_votes.eq(true))
+            .select(user_preferences::dsl::user_id);
+        let mut query = users::table
+            .inner_join(url_upvotes::table.on(user_id.eq(users::dsl::id)))
+            .filter(url_id.eq(self.id))
+            .filter(direction.eq(VoteDirection::Up))
+            .filter(users::dsl::id.ne_all(hidden))
+            .order_by(created_at.desc())
+            .then_order_by(users::dsl::id.desc())
+            .select(users::all_columns)
+            .into_boxed();
+
+        let voted_at = |voter: UserID| {
+            url_upvotes::table
+                .find((self.id, voter))
+                .select(created_at)
+                .get_result::<NaiveDateTime>(&*conn)
+        };
+        if let Some(after) = after {
+            let voted_at = voted_at(after)?;
+            query = query.filter(
+                created_at
+                    .lt(voted_at)
+                    .or(created_at.eq(voted_at).and(users::dsl::id.lt(after))),
+            );
+        }
+        if let Some(before) = before {
+            let voted_at = voted_at(before)?;
+            query = query.filter(
+                created_at
+                    .gt(voted_at)
+                    .or(created_at.eq(voted_at).and(users::dsl::id.gt(before))),
+            );
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        Ok(query.load(&*conn)?)
+    }
+}
+
+/// Render the markdown description of a submission. Mentions are
+/// only linked in comments, but hashtags of existing tags are linked
+/// in descriptions as well.
+fn render_description<C>(conn: &C, description: Option<&str>) -> Result<Option<String>>
+where
+    C: Connection<Backend = Sqlite>,
+{
+    description
+        .map(|text| {
+            let tags = Tag::existing(conn, &markdown::hashtags(text))?;
+            let links = markdown::Links {
+                tags: &tags,
+                ..Default::default()
+            };
+            Ok(markdown::render(text, links))
+        })
+        .transpose()
+}
+
+/// How related submissions are to a given submission. This adds up
+/// the tags they share with it, whether they link the same domain,
+/// and how many words of its title appear in their titles, such that
+/// submissions can be ranked by relatedness as part of a single query.
+#[derive(Debug, Clone)]
+struct Relatedness {
+    url_id: UrlID,
+    domain: Option<String>,
+    terms: Vec<String>,
+}
+
+impl Expression for Relatedness {
+    type SqlType = BigInt;
+}
+
+impl NonAggregate for Relatedness {}
+
+impl AppearsOnTable<urls::table> for Relatedness {}
+
+impl SelectableExpression<urls::table> for Relatedness {}
+
+impl QueryId for Relatedness {
+    type QueryId = ();
+
+    // the query depends on the number of terms
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl QueryFragment<Sqlite> for Relatedness {
+    fn walk_ast(&self, mut out: AstPass<Sqlite>) -> QueryResult<()> {
+        out.push_sql(
+            "((SELECT COUNT(*) FROM url_tags AS shared \
+            WHERE shared.url_id = urls.id AND shared.tag_name IN \
+            (SELECT tag_name FROM url_tags WHERE url_id = ",
+        );
+        out.push_bind_param::<Text, _>(&self.url_id)?;
+        // text posts have no domain to share
+        out.push_sql(&format!(
+            ")) * {} + COALESCE(urls.domain = ",
+            RELATED_TAG_WEIGHT
+        ));
+        out.push_bind_param::<Nullable<Text>, _>(&self.domain)?;
+        out.push_sql(&format!(", 0) * {}", RELATED_DOMAIN_WEIGHT));
+        for term in &self.terms {
+            out.push_sql(" + (instr(lower(COALESCE(urls.title, urls.fetched_title, '')), ");
+            out.push_bind_param::<Text, _>(term)?;
+            out.push_sql(") > 0)");
+        }
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+/// The distinct lowercase words of the given title which are
+/// compared to the titles of other submissions, in the order
+/// they first appear.
+fn title_terms(title: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    let words = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= MIN_RELATED_TERM_LEN)
+        .map(str::to_lowercase);
+    for word in words {
+        if !terms.contains(&word) {
+            terms.push(word);
+        }
+        if terms.len() == MAX_RELATED_TERMS {
+            break;
+        }
+    }
+    terms
+}
+
+/// Add `delta` votes in the given direction to the
+/// counters of the given URL.
+fn count_vote<C>(conn: &C, url_id: UrlID, direction: VoteDirection, delta: i64) -> QueryResult<i64>
+where
+    C: Connection<Backend = Sqlite>,
+{
+    use urls::dsl::{downvotes, score, upvotes};
+
+    let url = urls::table.find(url_id);
+    match direction {
+        VoteDirection::Up => {
+            diesel::update(url)
+                .set((upvotes.eq(upvotes + delta), score.eq(score + delta)))
+                .execute(conn)?;
+            Ok(delta)
+        }
+        VoteDirection::Down => {
+            diesel::update(url)
+                .set((downvotes.eq(downvotes + delta), score.eq(score - delta)))
+                .execute(conn)?;
+            Ok(-delta)
+        }
+    }
+}
+
+impl VoteDirection {
+    fn opposite(self) -> Self {
+        match self {
+            VoteDirection::Up => VoteDirection::Down,
+            VoteDirection::Down => VoteDirection::Up,
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for MetadataStatus
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            MetadataStatus::Pending => "pending",
+            MetadataStatus::Ok => "ok",
+            MetadataStatus::Failed => "failed",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> ToSql<Text, DB> for SubmissionKind
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            SubmissionKind::Link => "link",
+            SubmissionKind::Text => "text",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> ToSql<Text, DB> for LinkStatus
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            LinkStatus::Ok => "ok",
+            LinkStatus::Redirected => "redirected",
+            LinkStatus::Broken => "broken",
+            LinkStatus::Unknown => "unknown",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> ToSql<Text, DB> for ArchiveStatus
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            ArchiveStatus::Pending => "pending",
+            ArchiveStatus::Archived => "archived",
+            ArchiveStatus::Failed => "failed",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> ToSql<Text, DB> for SubmissionVisibility
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            SubmissionVisibility::Public => "public",
+            SubmissionVisibility::Unlisted => "unlisted",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> ToSql<Text, DB> for VoteDirection
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            VoteDirection::Up => "up",
+            VoteDirection::Down => "down",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for VoteDirection
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "up" => Ok(VoteDirection::Up),
+            "down" => Ok(VoteDirection::Down),
+            _ => Err("Unrecognized vote direction".into()),
+        }
+    }
+}
+
+impl<DB> FromSql<Text, DB> for MetadataStatus
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "pending" => Ok(MetadataStatus::Pending),
+            "ok" => Ok(MetadataStatus::Ok),
+            "failed" => Ok(MetadataStatus::Failed),
+            _ => Err("Unrecognized metadata status".into()),
+        }
+    }
+}
+
+impl<DB> FromSql<Text, DB> for SubmissionKind
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "link" => Ok(SubmissionKind::Link),
+            "text" => Ok(SubmissionKind::Text),
+            _ => Err("Unrecognized submission kind".into()),
+        }
+    }
+}
+
+impl<DB> FromSql<Text, DB> for SubmissionVisibility
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "public" => Ok(SubmissionVisibility::Public),
+            "unlisted" => Ok(SubmissionVisibility::Unlisted),
+            _ => Err("Unrecognized submission visibility".into()),
+        }
+    }
+}
+
+impl<DB> FromSql<Text, DB> for LinkStatus
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "ok" => Ok(LinkStatus::Ok),
+            "redirected" => Ok(LinkStatus::Redirected),
+            "broken" => Ok(LinkStatus::Broken),
+            "unknown" => Ok(LinkStatus::Unknown),
+            _ => Err("Unrecognized link status".into()),
+        }
+    }
+}
+
+impl<DB> FromSql<Text, DB> for ArchiveStatus
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "pending" => Ok(ArchiveStatus::Pending),
+            "archived" => Ok(ArchiveStatus::Archived),
+            "failed" => Ok(ArchiveStatus::Failed),
+            _ => Err("Unrecognized archive status".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, NaiveTime};
+
+    #[test]
+    fn test_hot_rank() {
+        let hours = Duration::hours;
+        assert!(hot_rank(10, hours(1), 1.6) > hot_rank(10, hours(2), 1.6));
+        assert!(hot_rank(20, hours(5), 1.6) > hot_rank(10, hours(5), 1.6));
+        assert!(hot_rank(10, hours(5), 1.8) < hot_rank(10, hours(5), 1.6));
+        assert_eq!(hot_rank(0, hours(5), 1.6), 0.0);
+        assert!(hot_rank(-1, hours(5), 1.6) < 0.0);
+        assert_eq!(hot_rank(4, hours(-1), 2.0), 1.0);
+    }
+
+    #[test]
+    fn test_archive_backoff() {
+        let minutes: Vec<i64> = (1..=4)
+            .map(|attempts| archive_backoff(attempts).num_minutes())
+            .collect();
+        assert_eq!(minutes, vec![10, 20, 40, 80]);
+        assert_eq!(archive_backoff(100), archive_backoff(16));
+    }
+
+    #[test]
+    fn test_url_cursor() {
+        let cursor = UrlCursor {
+            sort: UrlSort::Trending,
+            score: -3,
+            hot_rank: 0.1 + 0.2,
+            published_at: NaiveDateTime::from_timestamp(1_632_000_000, 123_456_789),
+            id: UrlID::new(),
+        };
+        assert_eq!(cursor.to_string().parse::<UrlCursor>(), Ok(cursor));
+        assert!("not a cursor".parse::<UrlCursor>().is_err());
+    }
+
+    #[test]
+    fn test_profile_cursor() {
+        let listed = ProfileCursor::Listed(UrlCursor {
+            sort: UrlSort::Newest,
+            score: 1,
+            hot_rank: 0.5,
+            published_at: NaiveDateTime::from_timestamp(1_632_000_000, 0),
+            id: UrlID::new(),
+        });
+        let pinned = ProfileCursor::Pinned {
+            pinned_at: NaiveDateTime::from_timestamp(1_632_000_000, 987_654_321),
+            id: UrlID::new(),
+        };
+        for cursor in [listed, pinned] {
+            assert_eq!(cursor.to_string().parse::<ProfileCursor>(), Ok(cursor));
+        }
+        assert!("not a cursor".parse::<ProfileCursor>().is_err());
+    }
+
+    #[test]
+    fn test_slug() {
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd(2022, 10, 15),
+            NaiveTime::from_hms(16, 5, 00),
+        );
+        let url = Url {
+            id: UrlID::new(),
+            created_at: date,
+            updated_at: date,
+            url: Some("https://urls.fyi/error/404".into()),
+            status_code: 404,
+            title: Some("404 :: Page Not Found".into()),
+            description: None,
+            image: None,
+            created_by: UserID::new(),
+            canonical_url: Some("https://urls.fyi/error/404".into()),
+            fetched_title: None,
+            fetched_description: None,
+            metadata_status: MetadataStatus::Ok,
+            edited_at: None,
+            deleted_at: None,
+            comment_count: 0,
+            score: 0,
+            upvotes: 0,
+            downvotes: 0,
+            hot_rank: 0.0,
+            pinned_at: None,
+            domain: Some("urls.fyi".into()),
+            removed_at: None,
+            shadow_removed: false,
+            preview_image: None,
+            last_checked_at: None,
+            http_status: None,
+            link_status: LinkStatus::Unknown,
+            archived_url: None,
+            archive_status: None,
+            archive_attempts: 0,
+            next_archive_at: None,
+            draft: false,
+            clicks: 0,
+            views: 0,
+            nsfw: false,
+            nsfw_locked: false,
+            language: None,
+            description_html: None,
+            revision_count: 0,
+            locked_at: None,
+            lock_reason: None,
+            previous_id: None,
+            anonymous: false,
+            kind: SubmissionKind::Link,
+            text: None,
+            text_html: None,
+            held_at: None,
+            visibility: SubmissionVisibility::Public,
+            published_at: date,
+            group_id: None,
+            publish_at: None,
+        };
+        assert_eq!(url.slug().unwrap(), "404-page-not-found");
+        let url = Url { title: None, ..url };
+        assert_eq!(url.slug().unwrap(), "urls-fyi-error-404");
+        let url = Url {
+            url: Some("https://tilman.dev/".into()),
+            ..url
+        };
+        assert_eq!(url.slug().unwrap(), "tilman-dev");
+    }
+
+    #[test]
+    fn test_title_terms() {
+        assert_eq!(
+            title_terms("Writing a Web-Server in Rust: writing tests"),
+            vec!["writing", "server", "rust", "tests"]
+        );
+        assert!(title_terms("A to Z").is_empty());
+        let long = "alpha bravo charlie delta echo foxtrot golf hotel india juliett";
+        assert_eq!(title_terms(long).len(), MAX_RELATED_TERMS);
+    }
+}
@@ -0,0 +1,204 @@
+use super::Url;
+use crate::db::models::{Group, ModerationLog, Permission, User, Webhook};
+use crate::error::Locked;
+use crate::events::Event;
+use crate::schema::{moderation_log, urls};
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+/// Maximum length of the reason given when removing a submission.
+const MAX_REMOVAL_REASON_LEN: usize = 1000;
+
+impl Url {
+    /// Release this URL after the spam filter held it, publishing it
+    /// as if it was never held. This is only available to administrators
+    /// and moderators, including the moderators of the group it was
+    /// submitted to. Releasing a URL which is not held does nothing.
+    pub async fn release(&mut self, ctx: &Context) -> Result<()> {
+        self.check_may_moderate(ctx, |perm| perm.review_reports())
+            .await?;
+        if !self.is_held() {
+            return Ok(());
+        }
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set(urls::dsl::held_at.eq(None::<NaiveDateTime>))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        drop(conn);
+        if !self.draft && !self.is_deleted() && !self.is_unlisted() {
+            Webhook::url_submitted(ctx, self).await;
+            ctx.events().publish(Event::UrlSubmitted(self.id()));
+        }
+        Ok(())
+    }
+
+    /// Restores a deleted URL. URLs can only be restored by
+    /// administrators.
+    pub async fn restore(&mut self, ctx: &Context) -> Result<()> {
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.restore_urls())
+            .await?;
+        if !self.is_deleted() {
+            return Ok(());
+        }
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set((
+                urls::dsl::deleted_at.eq(None::<NaiveDateTime>),
+                urls::dsl::updated_at.eq(ctx.now().naive_utc()),
+            ))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        ctx.search().index_url(self)?;
+        Ok(())
+    }
+
+    /// Check that the logged in user may moderate this URL, i.e. has a
+    /// permission the given predicate holds for, or moderates the group
+    /// this URL was submitted to, see [`Group::is_moderator`]. Returns
+    /// the logged in user.
+    pub(crate) async fn check_may_moderate<F>(&self, ctx: &Context, predicate: F) -> Result<User>
+    where
+        F: Fn(Permission) -> bool,
+    {
+        let user = ctx.user().await?;
+        if user.check_permissions(ctx, predicate).await.is_ok() {
+            return Ok(user);
+        }
+        if let Some(group_id) = self.group_id {
+            let group = Group::find(ctx, group_id).await?;
+            if group.is_moderator(ctx, user.id()).await? {
+                return Ok(user);
+            }
+        }
+        Err(anyhow!("Not authorized"))
+    }
+
+    /// Removes the URL as a moderator, recording the moderator and the
+    /// reason in the moderation log. A removed URL is excluded from all
+    /// listings and the search index, and its page shows that it was
+    /// removed by a moderator. A shadow removed URL is only excluded for
+    /// other users, its author and moderators keep seeing it as before.
+    /// Moderators of the group the URL was submitted to may remove it
+    /// as well, see [`check_may_moderate`](Url::check_may_moderate).
+    pub async fn remove(&mut self, ctx: &Context, reason: &str, shadow: bool) -> Result<()> {
+        let moderator = self
+            .check_may_moderate(ctx, |perm| perm.remove_urls())
+            .await?;
+        if self.is_deleted() {
+            return Err(anyhow!("This submission was deleted"));
+        }
+        if self.removed_at.is_some() {
+            return Err(anyhow!("This submission was already removed"));
+        }
+        let reason = reason.trim();
+        let len = reason.chars().count();
+        if !(1..=MAX_REMOVAL_REASON_LEN).contains(&len) {
+            return Err(anyhow!(
+                "Reasons must have between 1 and {} characters",
+                MAX_REMOVAL_REASON_LEN
+            ));
+        }
+
+        let now = ctx.now().naive_utc();
+        let entry = ModerationLog::new(moderator.id(), self.id, shadow, reason, now);
+        // shadow removed submissions keep their pin, such that
+        // their author doesn't notice the removal
+        let pinned_at = if shadow { self.pinned_at } else { None };
+        let conn = ctx.conn().await?;
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            diesel::insert_into(moderation_log::table)
+                .values(&entry)
+                .execute(&*conn)?;
+            diesel::update(&*self)
+                .set((
+                    urls::dsl::removed_at.eq(now),
+                    urls::dsl::shadow_removed.eq(shadow),
+                    urls::dsl::pinned_at.eq(pinned_at),
+                ))
+                .execute(&*conn)?;
+            Ok(urls::table.find(self.id).get_result(&*conn)?)
+        })?;
+        if !shadow {
+            ctx.search().delete_url(self)?;
+        }
+        Ok(())
+    }
+
+    /// Locks the discussion of this URL as a moderator, optionally
+    /// giving a reason which is shown with the discussion. Existing
+    /// comments stay visible, but only administrators may comment,
+    /// vote on comments or edit comments until it is unlocked.
+    /// Locking a locked URL again replaces the reason.
+    pub async fn lock(&mut self, ctx: &Context, reason: Option<String>) -> Result<()> {
+        self.check_may_moderate(ctx, |perm| perm.lock_urls())
+            .await?;
+        if self.is_deleted() {
+            return Err(anyhow!("This submission was deleted"));
+        }
+        let reason = reason
+            .map(|reason| reason.trim().to_string())
+            .filter(|reason| !reason.is_empty());
+        if let Some(reason) = &reason {
+            if reason.chars().count() > MAX_REMOVAL_REASON_LEN {
+                return Err(anyhow!(
+                    "Reasons can have at most {} characters",
+                    MAX_REMOVAL_REASON_LEN
+                ));
+            }
+        }
+        let locked_at = self.locked_at.unwrap_or_else(|| ctx.now().naive_utc());
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set((
+                urls::dsl::locked_at.eq(locked_at),
+                urls::dsl::lock_reason.eq(reason),
+            ))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        Ok(())
+    }
+
+    /// Unlocks the discussion of this URL as a moderator. Unlocking
+    /// a URL which is not locked does nothing.
+    pub async fn unlock(&mut self, ctx: &Context) -> Result<()> {
+        self.check_may_moderate(ctx, |perm| perm.lock_urls())
+            .await?;
+        if !self.is_locked() {
+            return Ok(());
+        }
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set((
+                urls::dsl::locked_at.eq(None::<NaiveDateTime>),
+                urls::dsl::lock_reason.eq(None::<String>),
+            ))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        Ok(())
+    }
+
+    /// Check that the logged in user may take part in the discussion
+    /// of this URL, which is the case unless it is locked.
+    /// Administrators may always take part.
+    pub async fn check_not_locked(&self, ctx: &Context) -> Result<()> {
+        if !self.is_locked() {
+            return Ok(());
+        }
+        let may_bypass = ctx
+            .user()
+            .await?
+            .check_permissions(ctx, |perm| perm.bypass_url_locks())
+            .await
+            .is_ok();
+        if may_bypass {
+            Ok(())
+        } else {
+            Err(Locked.into())
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use super::Url;
+use crate::schema::urls;
+use crate::Context;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+
+impl Url {
+    /// Schedule this draft to be published at the given time, replacing
+    /// an earlier time, or cancel its scheduled publication, keeping it
+    /// as a draft. Only drafts can be scheduled, and the caller checks
+    /// that the logged in user may edit this URL.
+    pub(super) async fn schedule(
+        &mut self,
+        ctx: &Context,
+        publish_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        if !self.draft {
+            return Err(anyhow!("This submission was already published"));
+        }
+        if let Some(publish_at) = publish_at {
+            check_publish_at(ctx, publish_at)?;
+        }
+        let conn = ctx.conn().await?;
+        diesel::update(&*self)
+            .set((
+                urls::dsl::publish_at.eq(publish_at.map(|at| at.naive_utc())),
+                urls::dsl::updated_at.eq(ctx.now().naive_utc()),
+            ))
+            .execute(&*conn)?;
+        *self = urls::table.find(self.id).get_result(&*conn)?;
+        Ok(())
+    }
+
+    /// Publish the scheduled drafts whose time has come, dating them
+    /// to the time they are published, such that they are listed like
+    /// new submissions. This is run periodically, and returns the
+    /// number of published submissions.
+    pub async fn publish_scheduled(ctx: &Context) -> Result<usize> {
+        let due: Vec<Self> = urls::table
+            .filter(urls::dsl::draft.eq(true))
+            .filter(urls::dsl::deleted_at.is_null())
+            .filter(urls::dsl::publish_at.le(ctx.now().naive_utc()))
+            .order_by(urls::dsl::publish_at.asc())
+            .then_order_by(urls::dsl::id.asc())
+            .load(&*ctx.conn().await?)?;
+
+        let mut published = 0;
+        for mut url in due {
+            let created_by = url.created_by;
+            match url.publish_draft(ctx, created_by, None).await {
+                Ok(()) => published += 1,
+                Err(err) => log::error!("Failed to publish {}: {}", url.id, err),
+            }
+        }
+        Ok(published)
+    }
+}
+
+/// Fail unless the given time to publish a submission at is
+/// in the future.
+pub(super) fn check_publish_at(ctx: &Context, publish_at: DateTime<Utc>) -> Result<()> {
+    if publish_at <= ctx.now() {
+        return Err(anyhow!("Submissions can only be scheduled for the future"));
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use crate::db::id::UrlID;
+use crate::db::models::Url;
+use crate::embed::{self, Embed};
+use crate::schema::url_embeds;
+use crate::Context;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+/// An inline player or card shown on the discussion page of a
+/// submission linking to a known provider, see [`embed`].
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable)]
+#[primary_key(url_id)]
+pub struct UrlEmbed {
+    url_id: UrlID,
+    updated_at: NaiveDateTime,
+
+    provider: String,
+    html: String,
+    width: Option<i32>,
+    height: Option<i32>,
+}
+
+impl UrlEmbed {
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.updated_at, Utc)
+    }
+
+    /// Name of the provider of the embed, e.g. `YouTube`.
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    /// Sanitized markup of the embed, which is safe
+    /// to include in pages as is.
+    pub fn html(&self) -> &str {
+        &self.html
+    }
+
+    pub fn width(&self) -> Option<i32> {
+        self.width
+    }
+
+    pub fn height(&self) -> Option<i32> {
+        self.height
+    }
+}
+
+impl UrlEmbed {
+    /// The embed of the given submission, if its link was unfurled.
+    pub async fn find(ctx: &Context, url_id: UrlID) -> Result<Option<Self>> {
+        Ok(url_embeds::table
+            .find(url_id)
+            .get_result(&*ctx.conn().await?)
+            .optional()?)
+    }
+
+    /// Unfurl the link of the given submission and store the resulting
+    /// embed. Links which no provider matches are skipped, and the
+    /// stored embed is kept if the provider can not be reached. Text
+    /// posts have no link to unfurl.
+    pub async fn refresh(ctx: &Context, url: &Url) -> Result<Option<Self>> {
+        let link = match url.url_str() {
+            Some(link) => link,
+            None => return Ok(None),
+        };
+        let Embed {
+            provider,
+            html,
+            width,
+            height,
+        } = match embed::unfurl(ctx, link).await? {
+            Some(embed) => embed,
+            None => return Ok(None),
+        };
+        let embed = UrlEmbed {
+            url_id: url.id(),
+            updated_at: ctx.now().naive_utc(),
+
+            provider,
+            html,
+            width,
+            height,
+        };
+        diesel::replace_into(url_embeds::table)
+            .values(&embed)
+            .execute(&*ctx.conn().await?)?;
+        Ok(Some(embed))
+    }
+}
@@ -0,0 +1,92 @@
+use crate::db::id::UrlID;
+use crate::db::models::Url;
+use crate::schema::{url_views, urls};
+use crate::{clicks, signing, Context};
+use anyhow::Result;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+
+/// A day on which a viewer opened the discussion of a submission.
+/// Views are counted at most once per viewer and UTC day, and rows
+/// of past days are pruned, since only the count stored with the
+/// submission is kept.
+///
+/// Logged in viewers are identified by their user. Anonymous viewers
+/// are identified by a keyed hash of their IP address and user agent,
+/// which changes daily. Counts of anonymous views are thus approximate:
+/// people sharing an address and browser count once, and people
+/// changing their address count again. Anonymous requests without an
+/// address, or made by bots, are not counted.
+#[derive(Debug, Clone, Queryable, Insertable)]
+pub struct UrlView {
+    url_id: UrlID,
+    viewer: String,
+    viewed_on: NaiveDate,
+}
+
+impl UrlView {
+    /// The key identifying the viewer of the current request
+    /// on the given day, if their views are counted.
+    fn viewer(ctx: &Context, day: NaiveDate) -> Option<String> {
+        if let Some(user_id) = ctx.maybe_user_id() {
+            return Some(format!("user:{}", user_id));
+        }
+        let ip = ctx.remote_ip_address()?;
+        if clicks::is_bot(ctx.user_agent()) {
+            return None;
+        }
+        let data = format!("{}|{}|{}", day, ip, ctx.user_agent().unwrap_or(""));
+        Some(format!("anon:{}", signing::digest(ctx, "url_view", &data)))
+    }
+
+    /// Record that the viewer opened the discussion of the given
+    /// submission. The view is stored in the background, so as to
+    /// not delay the request, see [`store`](UrlView::store).
+    pub fn record(ctx: &Context, url_id: UrlID) {
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            if let Err(err) = Self::store(&ctx, url_id).await {
+                log::warn!("Failed to record view of {}: {}", url_id, err);
+            }
+        });
+    }
+
+    /// Store a view of the given submission by the viewer, and
+    /// increment its view count unless the viewer already viewed it
+    /// today. Returns whether the view was counted. Views of deleted
+    /// submissions and of drafts are not counted.
+    pub async fn store(ctx: &Context, url_id: UrlID) -> Result<bool> {
+        let url = Url::find(ctx, url_id).await?;
+        let day = ctx.now().date().naive_utc();
+        let viewer = match Self::viewer(ctx, day) {
+            Some(viewer) if !url.is_deleted() && !url.is_draft() => viewer,
+            _ => return Ok(false),
+        };
+        let view = UrlView {
+            url_id,
+            viewer,
+            viewed_on: day,
+        };
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            let inserted = diesel::insert_or_ignore_into(url_views::table)
+                .values(&view)
+                .execute(&*conn)?;
+            if inserted > 0 {
+                diesel::update(urls::table.find(url_id))
+                    .set(urls::dsl::views.eq(urls::dsl::views + 1))
+                    .execute(&*conn)?;
+            }
+            Ok(inserted > 0)
+        })
+    }
+
+    /// Delete the stored views of past days, which are no longer
+    /// needed to tell repeated views apart. Returns the number of
+    /// deleted views.
+    pub async fn prune(ctx: &Context) -> Result<usize> {
+        let today = ctx.now().date().naive_utc();
+        let past = url_views::table.filter(url_views::dsl::viewed_on.lt(today));
+        Ok(diesel::delete(past).execute(&*ctx.conn().await?)?)
+    }
+}
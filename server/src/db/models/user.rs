@@ -1,15 +1,33 @@
 use crate::db::id::UserID;
-use crate::db::models::{Invite, Login, Permission, Role};
-use crate::schema::{invites, logins, roles, users};
-use crate::Context;
+use crate::db::models::{
+    EmailCategory, Invite, KnownDevice, Listed, Login, LoginLocation, Permission, Role,
+    SecurityEvent, SecurityEventKind, UnsubscribeToken, Url, UserPreferences,
+};
+use crate::error::{AppError, BlockedEmailDomain, RateLimited};
+use crate::schema::{comments, invites, logins, roles, urls, users};
+use crate::validation::Validator;
+use crate::{signing, Config, Context, RegistrationMode};
 use anyhow::{anyhow, Result};
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use diesel::prelude::*;
-use juniper::GraphQLInputObject;
+use diesel::sqlite::Sqlite;
+use juniper::{GraphQLInputObject, GraphQLObject};
 use lettre::address::Address;
 use lettre::message::{Mailbox, Message};
+use nanoid::nanoid;
+use std::collections::HashMap;
 use std::str::FromStr;
-use validator::{Validate, ValidationError};
+use validator::{validate_email, Validate, ValidationError};
+
+const VERIFICATION_PURPOSE: &str = "verify_email";
+const VERIFICATION_VALID_HOURS: i64 = 48;
+const VERIFICATION_COOLDOWN_MINUTES: i64 = 5;
+const FEED_TOKEN_LENGTH: usize = 32;
+const LOGIN_FAILURES_BEFORE_LOCK: i32 = 20;
+const LOGIN_FAILURE_WINDOW_MINUTES: i64 = 60;
+const LOGIN_LOCK_MINUTES: i64 = 60;
+const MIN_USERNAME_LEN: usize = 2;
+const MAX_USERNAME_LEN: usize = 30;
 
 #[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset)]
 pub struct User {
@@ -19,6 +37,21 @@ pub struct User {
 
     name: String,
     email: String,
+    invite_quota: Option<i32>,
+    invite_credits: i32,
+    last_accrual_at: NaiveDateTime,
+    email_verified_at: Option<NaiveDateTime>,
+    verification_sent_at: Option<NaiveDateTime>,
+    last_login_at: Option<NaiveDateTime>,
+    login_count: i32,
+    invited_by: Option<UserID>,
+    feed_token: Option<String>,
+    failed_login_count: i32,
+    failed_login_since: Option<NaiveDateTime>,
+    login_locked_until: Option<NaiveDateTime>,
+    banned_at: Option<NaiveDateTime>,
+    username: String,
+    karma: i64,
 }
 
 #[derive(Debug, Clone, Validate, GraphQLInputObject)]
@@ -39,6 +72,14 @@ pub struct NewUserInput {
 pub struct UpdateUserInput {
     #[validate(length(min = 1, max = 256, message = "A name is required"))]
     name: Option<String>,
+    /// A new username, which is unique and used to link to the
+    /// profile of the user. Usernames are case insensitive and can
+    /// contain letters, digits, dashes, and underscores.
+    #[validate(custom(
+        function = "valid_username",
+        message = "Usernames must have between 2 and 30 letters, digits, dashes, or underscores"
+    ))]
+    username: Option<String>,
     #[validate(
         email(message = "A valid email address is required"),
         custom(
@@ -49,9 +90,65 @@ pub struct UpdateUserInput {
     email: Option<String>,
 }
 
+/// Urls of the private feeds of a user, which contain
+/// the secret feed token of the user.
+#[derive(Debug, Clone, GraphQLObject)]
+pub struct FeedUrls {
+    /// Atom feed of the front page, as seen by the user, i.e.
+    /// without muted domains and blocked users.
+    pub front_page: String,
+    /// Atom feed of the submissions saved by the user, most
+    /// recently saved first.
+    pub saved: String,
+}
+
+/// Normalize an email address, such that differently
+/// formatted variants of the same address are stored
+/// and looked up consistently.
+fn normalize_email(email: &str) -> String {
+    email.trim().to_ascii_lowercase()
+}
+
+/// Normalize a username, such that usernames are
+/// case insensitive.
+fn normalize_username(username: &str) -> String {
+    username.trim().to_ascii_lowercase()
+}
+
+fn valid_username(username: &str) -> Result<(), ValidationError> {
+    let valid_len = (MIN_USERNAME_LEN..=MAX_USERNAME_LEN).contains(&username.len());
+    let valid_chars = username
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if valid_len && valid_chars {
+        Ok(())
+    } else {
+        Err(ValidationError::new("INVALID_USERNAME"))
+    }
+}
+
+/// Derive a username from a display name, e.g. `test-user`
+/// for `Test User`. This is not necessarily unique.
+fn derive_username(name: &str) -> String {
+    let mut username = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+    // leave room for a suffix to make the name unique
+    username.truncate(MAX_USERNAME_LEN - 4);
+    let username = username.trim_end_matches('-').to_string();
+    if username.len() < MIN_USERNAME_LEN {
+        "user".to_string()
+    } else {
+        username
+    }
+}
+
 fn disposable_email(email: &str) -> Result<(), ValidationError> {
     if disposable::is_disposable(email) {
-        Err(ValidationError::new("disposable_email"))
+        Err(ValidationError::new("DISPOSABLE_EMAIL"))
     } else {
         Ok(())
     }
@@ -70,6 +167,12 @@ impl User {
         self.name.as_str()
     }
 
+    /// The unique username of this user, which is used
+    /// to link to their profile.
+    pub fn username(&self) -> &str {
+        self.username.as_str()
+    }
+
     /// Return the email address of this user.
     pub fn email(&self) -> Result<Address> {
         let address = Address::from_str(&self.email)?;
@@ -80,6 +183,15 @@ impl User {
         DateTime::from_utc(self.created_at, Utc)
     }
 
+    /// The reputation of this user, i.e. the score of their
+    /// submissions and comments, weighed as configured by
+    /// [`Config::karma_submission_weight`](crate::Config::karma_submission_weight)
+    /// and [`Config::karma_comment_weight`](crate::Config::karma_comment_weight).
+    /// Anonymous submissions do not count.
+    pub fn karma(&self) -> i64 {
+        self.karma
+    }
+
     pub fn updated_at(&self) -> DateTime<Utc> {
         DateTime::from_utc(self.updated_at, Utc)
     }
@@ -106,8 +218,158 @@ impl User {
         if self.permissions(ctx).await?.into_iter().any(predicate) {
             Ok(())
         } else {
-            Err(anyhow!("Not authorized"))
+            Err(AppError::PermissionDenied("Not authorized".into()).into())
+        }
+    }
+
+    /// Number of invitations this user may have issued at any time.
+    /// This is the configured default (unless it was overridden for
+    /// this user), plus any earned or granted invitations. Returns `None` if the user may issue unlimited
+    /// invitations.
+    pub async fn invite_quota(&self, ctx: &Context) -> Result<Option<i64>> {
+        if self
+            .check_permissions(ctx, |perm| perm.unlimited_invites())
+            .await
+            .is_ok()
+        {
+            return Ok(None);
         }
+        let quota = self
+            .invite_quota
+            .map(i64::from)
+            .unwrap_or_else(|| ctx.config().invite_quota());
+        Ok(Some(quota + i64::from(self.invite_credits)))
+    }
+
+    /// Determine if this user verified their current
+    /// email address.
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
+
+    /// Determine if this user was banned. Banned users
+    /// can not submit, comment, or vote.
+    pub fn is_banned(&self) -> bool {
+        self.banned_at.is_some()
+    }
+
+    pub fn banned_at(&self) -> Option<DateTime<Utc>> {
+        self.banned_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    pub fn email_verified_at(&self) -> Option<DateTime<Utc>> {
+        self.email_verified_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    /// The last time this user logged in, if they
+    /// ever did.
+    pub fn last_login_at(&self) -> Option<DateTime<Utc>> {
+        self.last_login_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    /// The number of times this user logged in.
+    pub fn login_count(&self) -> i32 {
+        self.login_count
+    }
+
+    /// If logging into this account is locked because of too
+    /// many failed attempts, the time until the lock lifts.
+    pub fn login_locked_until(&self) -> Option<DateTime<Utc>> {
+        self.login_locked_until
+            .map(|at| DateTime::from_utc(at, Utc))
+    }
+
+    /// ID of the user who issued the invitation this
+    /// user registered with, if any.
+    pub fn invited_by_id(&self) -> Option<UserID> {
+        self.invited_by
+    }
+
+    /// Time at which invitations were last accrued for
+    /// this user.
+    pub fn last_accrual_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.last_accrual_at, Utc)
+    }
+
+    /// The user who issued the invitation this user
+    /// registered with, if any.
+    pub async fn invited_by(&self, ctx: &Context) -> Result<Option<User>> {
+        match self.invited_by {
+            Some(id) => Ok(Some(User::find(ctx, id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The token which authenticates the private feed of this
+    /// user. The token is generated when it is first needed.
+    pub async fn feed_token(&mut self, ctx: &Context) -> Result<String> {
+        if self.feed_token.is_none() {
+            let conn = ctx.conn().await?;
+            self.feed_token = conn.transaction::<_, anyhow::Error, _>(|| {
+                let created = diesel::update(users::table.find(self.id))
+                    .filter(users::dsl::feed_token.is_null())
+                    .set(users::dsl::feed_token.eq(nanoid!(FEED_TOKEN_LENGTH)))
+                    .execute(&*conn)?;
+                if created > 0 {
+                    SecurityEvent::record(ctx, &*conn, self.id, SecurityEventKind::TokenCreated)?;
+                }
+                // the token might have been generated concurrently
+                Ok(users::table
+                    .find(self.id)
+                    .select(users::dsl::feed_token)
+                    .get_result(&*conn)?)
+            })?;
+        }
+        self.feed_token
+            .clone()
+            .ok_or_else(|| anyhow!("Failed to generate feed token"))
+    }
+
+    /// The url of the private feed of this user. Anyone who
+    /// knows this url can read the feed.
+    pub async fn feed_url(&mut self, ctx: &Context) -> Result<String> {
+        let token = self.feed_token(ctx).await?;
+        Ok(format!(
+            "https://{}/feed.xml?token={}.{}",
+            ctx.config().hostname(),
+            self.id,
+            token
+        ))
+    }
+
+    /// The urls of the private feeds of this user. Like the
+    /// [`feed_url`](User::feed_url), they are authenticated
+    /// by the feed token.
+    pub async fn feed_urls(&mut self, ctx: &Context) -> Result<FeedUrls> {
+        let token = self.feed_token(ctx).await?;
+        let base = format!("https://{}/u/{}", ctx.config().hostname(), self.username);
+        Ok(FeedUrls {
+            front_page: format!("{}/feed.atom?token={}", base, token),
+            saved: format!("{}/saved.atom?token={}", base, token),
+        })
+    }
+
+    /// Replace the feed token of this user, such that
+    /// previously shared feed urls stop working.
+    pub async fn regenerate_feed_token(&mut self, ctx: &Context) -> Result<()> {
+        let token = nanoid!(FEED_TOKEN_LENGTH);
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            diesel::update(users::table.find(self.id))
+                .set((
+                    users::dsl::feed_token.eq(&token),
+                    users::dsl::updated_at.eq(ctx.now().naive_utc()),
+                ))
+                .execute(&*conn)?;
+            if self.feed_token.is_some() {
+                SecurityEvent::record(ctx, &*conn, self.id, SecurityEventKind::TokenRevoked)?;
+            }
+            SecurityEvent::record(ctx, &*conn, self.id, SecurityEventKind::TokenCreated)?;
+            Ok(())
+        })?;
+        self.feed_token = Some(token);
+        self.updated_at = ctx.now().naive_utc();
+        Ok(())
     }
 
     /// Invite used to register this user.
@@ -127,13 +389,247 @@ impl User {
         Ok(user)
     }
 
-    /// Retrieve a user by it's email address.
+    /// Retrieve a user by their ID like [`find`](User::find), together
+    /// with the users looked up alongside it, e.g. the authors of a page
+    /// of submissions, see [`loader`](crate::loader).
+    pub async fn load(ctx: &Context, id: UserID) -> Result<Self> {
+        let user = ctx
+            .loaders()
+            .users
+            .load(id, |ids| async move {
+                let users: Vec<Self> = users::table
+                    .filter(users::dsl::id.eq_any(ids))
+                    .load(&*ctx.conn().await?)?;
+                Ok(users.into_iter().map(|user| (user.id, user)).collect())
+            })
+            .await?;
+        Ok(user.ok_or(diesel::result::Error::NotFound)?)
+    }
+
+    /// Retrieve a user by their username, if there is one. Usernames
+    /// are case insensitive.
+    pub async fn find_by_username(ctx: &Context, username: &str) -> Result<Option<Self>> {
+        let user = users::table
+            .filter(users::dsl::username.eq(normalize_username(username)))
+            .get_result(&*ctx.conn().await?)
+            .optional()?;
+        Ok(user)
+    }
+
+    /// Returns a username derived from the given display name, which
+    /// isn't taken by any other user yet.
+    fn unique_username<C>(conn: &C, name: &str) -> Result<String>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let base = derive_username(name);
+        for suffix in 1.. {
+            let username = match suffix {
+                1 => base.clone(),
+                _ => format!("{}-{}", base, suffix),
+            };
+            let taken: i64 = users::table
+                .filter(users::dsl::username.eq(&username))
+                .count()
+                .get_result(conn)?;
+            if taken == 0 {
+                return Ok(username);
+            }
+        }
+        unreachable!()
+    }
+
+    /// Number of submissions by this user which are listed to the
+    /// viewer, see [`Listed::to_viewer`], excluding anonymous
+    /// submissions unless the viewer is this user.
+    pub async fn url_count(&self, ctx: &Context) -> Result<i64> {
+        let listed = Listed::to_viewer(ctx).await?;
+        let conn = ctx.conn().await?;
+        Ok(urls::table
+            .filter(urls::dsl::created_by.eq(self.id))
+            .filter(urls::dsl::anonymous.eq_any(Url::listed_anonymous(ctx, self.id)))
+            .filter(listed.filter())
+            .count()
+            .get_result(&*conn)?)
+    }
+
+    /// Number of comments by this user, excluding
+    /// deleted and held comments.
+    pub async fn comment_count(&self, ctx: &Context) -> Result<i64> {
+        Ok(comments::table
+            .filter(comments::dsl::created_by.eq(self.id))
+            .filter(comments::dsl::deleted_at.is_null())
+            .filter(comments::dsl::held_at.is_null())
+            .count()
+            .get_result(&*ctx.conn().await?)?)
+    }
+
+    /// Change the karma of the given user by `delta`. This is used
+    /// by votes to maintain karma as part of their transaction.
+    pub(crate) fn add_karma<C>(conn: &C, user_id: UserID, delta: i64) -> QueryResult<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        if delta != 0 {
+            diesel::update(users::table.find(user_id))
+                .set(users::dsl::karma.eq(users::dsl::karma + delta))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Compute the karma of all users with submissions or comments from
+    /// the scores of their content, rather than from the stored karma.
+    fn computed_karma<C>(conn: &C, config: &Config) -> Result<HashMap<UserID, i64>>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let submissions: Vec<(UserID, i64)> = urls::table
+            .filter(urls::dsl::anonymous.eq(false))
+            .select((urls::dsl::created_by, urls::dsl::score))
+            .load(conn)?;
+        let comments: Vec<(UserID, i64)> = comments::table
+            .select((comments::dsl::created_by, comments::dsl::score))
+            .load(conn)?;
+        let mut karma = HashMap::new();
+        for (id, score) in submissions {
+            *karma.entry(id).or_insert(0) += score * config.karma_submission_weight();
+        }
+        for (id, score) in comments {
+            *karma.entry(id).or_insert(0) += score * config.karma_comment_weight();
+        }
+        Ok(karma)
+    }
+
+    /// Compute the karma of the given user from the scores
+    /// of their content, rather than from the stored karma.
+    fn computed_karma_of<C>(conn: &C, config: &Config, id: UserID) -> Result<i64>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        let submissions: Vec<i64> = urls::table
+            .filter(urls::dsl::created_by.eq(id))
+            .filter(urls::dsl::anonymous.eq(false))
+            .select(urls::dsl::score)
+            .load(conn)?;
+        let comments: Vec<i64> = comments::table
+            .filter(comments::dsl::created_by.eq(id))
+            .select(comments::dsl::score)
+            .load(conn)?;
+        Ok(
+            submissions.iter().sum::<i64>() * config.karma_submission_weight()
+                + comments.iter().sum::<i64>() * config.karma_comment_weight(),
+        )
+    }
+
+    /// Compute the karma of users who don't have any yet, i.e. those
+    /// who had content before karma was stored. This is run on startup.
+    pub fn backfill_karma<C>(conn: &C, config: &Config) -> Result<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            let missing: Vec<UserID> = users::table
+                .filter(users::dsl::karma.eq(0))
+                .select(users::dsl::id)
+                .load(conn)?;
+            let computed = Self::computed_karma(conn, config)?;
+            for id in missing {
+                if let Some(&karma) = computed.get(&id).filter(|karma| **karma != 0) {
+                    diesel::update(users::table.find(id))
+                        .set(users::dsl::karma.eq(karma))
+                        .execute(conn)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Compare the stored karma of all users with the karma computed
+    /// from the scores of their content, logging and correcting any
+    /// discrepancies, which e.g. occur when the karma weights change.
+    /// Returns the number of users whose karma was corrected.
+    pub async fn reconcile_karma(ctx: &Context) -> Result<usize> {
+        let conn = ctx.conn().await?;
+        let stored: Vec<(UserID, i64)> = users::table
+            .select((users::dsl::id, users::dsl::karma))
+            .load(&*conn)?;
+        let computed = Self::computed_karma(&*conn, ctx.config())?;
+        let mut corrected = 0;
+        for (id, karma) in stored {
+            if computed.get(&id).copied().unwrap_or(0) == karma {
+                continue;
+            }
+            // recompute with all votes on the content of the user
+            // serialized, to not lose votes cast in the meantime
+            let fixed = conn.transaction::<_, anyhow::Error, _>(|| {
+                diesel::update(users::table.find(id))
+                    .set(users::dsl::karma.eq(users::dsl::karma))
+                    .execute(&*conn)?;
+                let stored: i64 = users::table
+                    .find(id)
+                    .select(users::dsl::karma)
+                    .get_result(&*conn)?;
+                let karma = Self::computed_karma_of(&*conn, ctx.config(), id)?;
+                if stored == karma {
+                    return Ok(false);
+                }
+                log::warn!(
+                    "Karma of user {} drifted, stored {} but computed {}",
+                    id,
+                    stored,
+                    karma
+                );
+                diesel::update(users::table.find(id))
+                    .set(users::dsl::karma.eq(karma))
+                    .execute(&*conn)?;
+                Ok(true)
+            })?;
+            if fixed {
+                corrected += 1;
+            }
+        }
+        Ok(corrected)
+    }
+
+    /// Retrieve a user by it's email address. The address
+    /// is normalized before looking up the user.
     pub async fn find_by_email(ctx: &Context, email: &str) -> Result<Self> {
+        let email = normalize_email(email);
+        if !validate_email(&email) {
+            let message = "A valid email address is required";
+            return Err(AppError::ValidationFailed(message.into()).into());
+        }
         let conn = ctx.conn().await?;
         let user = users::table
-            .filter(users::dsl::email.eq(email.trim().to_ascii_lowercase()))
-            .get_result(&*conn)?;
-        Ok(user)
+            .filter(users::dsl::email.eq(email))
+            .get_result(&*conn)
+            .optional()?;
+        user.ok_or_else(|| AppError::NotFound("User not found".into()).into())
+    }
+
+    /// Check the domain of the given email address against the
+    /// configured blocklist, if any.
+    async fn check_email_blocklist(ctx: &Context, email: &str) -> Result<()> {
+        let path = match ctx.config().email_blocklist() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let domain = normalize_email(email)
+            .rsplit_once('@')
+            .map(|(_, domain)| domain.to_string())
+            .unwrap_or_default();
+        let blocklist = tokio::fs::read_to_string(path).await?;
+        let is_blocked = blocklist
+            .lines()
+            .map(|line| line.trim().to_ascii_lowercase())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .any(|blocked| domain == blocked || domain.ends_with(&format!(".{}", blocked)));
+        if is_blocked {
+            Err(BlockedEmailDomain { domain }.into())
+        } else {
+            Ok(())
+        }
     }
 }
 
@@ -145,21 +641,47 @@ impl User {
     pub async fn create(ctx: &Context, input: NewUserInput) -> Result<Self> {
         let input = NewUserInput {
             name: input.name.trim().into(),
-            email: input.email.trim().to_ascii_lowercase(),
+            email: normalize_email(&input.email),
         };
-        input.validate()?;
+        Validator::new().check("input", &input).finish()?;
         let NewUserInput { name, email } = input;
 
+        let conn = ctx.conn().await?;
+        let email_taken: i64 = users::table
+            .filter(users::dsl::email.eq(&email))
+            .count()
+            .get_result(&*conn)?;
+        if email_taken > 0 {
+            let message = "An account with this email address already exists";
+            return Err(AppError::Conflict(message.into()).into());
+        }
+        let username = Self::unique_username(&*conn, &name)?;
+
         let user = User {
             id: UserID::new(),
             name,
             email,
+            username,
+
+            invite_quota: None,
+            invite_credits: 0,
+            last_accrual_at: ctx.now().naive_utc(),
+            email_verified_at: None,
+            verification_sent_at: None,
+            last_login_at: None,
+            login_count: 0,
+            invited_by: None,
+            feed_token: None,
+            failed_login_count: 0,
+            failed_login_since: None,
+            login_locked_until: None,
+            banned_at: None,
+            karma: 0,
 
             created_at: ctx.now().naive_utc(),
             updated_at: ctx.now().naive_utc(),
         };
 
-        let conn = ctx.conn().await?;
         diesel::insert_into(users::table)
             .values(&user)
             .execute(&*conn)?;
@@ -167,15 +689,47 @@ impl User {
         Ok(user)
     }
 
-    /// Create a user by claiming the given invite.
+    /// Register a new user, according to the configured registration
+    /// mode. If registration requires an invitation, the invitation
+    /// `token` must be given. Otherwise the token is ignored.
+    pub async fn register(ctx: &Context, input: NewUserInput, token: Option<&str>) -> Result<Self> {
+        let mut user = match ctx.config().registration_mode() {
+            RegistrationMode::Closed => {
+                let message = "Registration of new accounts is closed";
+                return Err(AppError::PermissionDenied(message.into()).into());
+            }
+            RegistrationMode::Open => {
+                Self::check_email_blocklist(ctx, &input.email).await?;
+                Self::create(ctx, input).await?
+            }
+            RegistrationMode::InviteOnly => {
+                let token = token.ok_or_else(|| {
+                    AppError::ValidationFailed("An invitation is required to register".into())
+                })?;
+                let invite = Invite::find_by_token(ctx, token).await?;
+                Self::create_with_invite(ctx, input, invite).await?
+            }
+        };
+        user.request_verification_logged(ctx).await;
+        Ok(user)
+    }
+
+    /// Create a user by claiming the given invite. Email addresses
+    /// on the configured blocklist are rejected.
     pub async fn create_with_invite(
         ctx: &Context,
         input: NewUserInput,
         mut invite: Invite,
     ) -> Result<Self> {
-        let user = Self::create(ctx, input).await?;
+        Self::check_email_blocklist(ctx, &input.email).await?;
+        let mut user = Self::create(ctx, input).await?;
         match invite.claim(ctx, &user).await {
-            Ok(()) => Ok(user),
+            Ok(()) => {
+                user.invited_by = Some(invite.created_by_id());
+                user.updated_at = ctx.now().naive_utc();
+                user = user.save_changes(&*ctx.conn().await?)?;
+                Ok(user)
+            }
             Err(err) => {
                 // TODO: Should this use a transaction? Yes, but ..
                 diesel::delete(&user).execute(&*ctx.conn().await?)?;
@@ -189,25 +743,282 @@ impl User {
     pub async fn update(&mut self, ctx: &Context, input: UpdateUserInput) -> Result<()> {
         let input = UpdateUserInput {
             name: input.name.map(|name| name.trim().into()),
-            email: input.email.map(|email| email.trim().to_ascii_lowercase()),
+            username: input.username.map(|username| normalize_username(&username)),
+            email: input.email.map(|email| normalize_email(&email)),
         };
-        input.validate()?;
-        let UpdateUserInput { name, email } = input;
+        Validator::new().check("input", &input).finish()?;
+        let UpdateUserInput {
+            name,
+            username,
+            email,
+        } = input;
 
         if let Some(name) = name {
             self.name = name;
             self.updated_at = ctx.now().naive_utc();
         }
 
+        if let Some(username) = username.filter(|username| username != &self.username) {
+            let taken: i64 = users::table
+                .filter(users::dsl::username.eq(&username))
+                .count()
+                .get_result(&*ctx.conn().await?)?;
+            if taken > 0 {
+                return Err(AppError::Conflict("This username is already taken".into()).into());
+            }
+            self.username = username;
+            self.updated_at = ctx.now().naive_utc();
+        }
+
+        let email_changed = email.as_ref().map(|email| email != &self.email) == Some(true);
         if let Some(email) = email {
             self.email = email;
             self.updated_at = ctx.now().naive_utc();
         }
 
+        let conn = ctx.conn().await?;
+        *self = conn.transaction::<_, anyhow::Error, _>(|| {
+            let mut user: User = self.save_changes(&*conn)?;
+            if email_changed {
+                SecurityEvent::record(
+                    ctx,
+                    &*conn,
+                    user.id,
+                    SecurityEventKind::EmailChangeRequested,
+                )?;
+                Login::revoke_others(ctx, &*conn, user.id)?;
+
+                // the new address needs to be verified again
+                user.email_verified_at = None;
+                user.verification_sent_at = None;
+                diesel::update(&user)
+                    .set((
+                        users::dsl::email_verified_at.eq(user.email_verified_at),
+                        users::dsl::verification_sent_at.eq(user.verification_sent_at),
+                    ))
+                    .execute(&*conn)?;
+            }
+            Ok(user)
+        })?;
+        drop(conn);
+
+        if email_changed {
+            self.request_verification_logged(ctx).await;
+        }
+        Ok(())
+    }
+
+    /// Mark the email address of this user as verified, without
+    /// requiring the user to confirm it. This is meant for accounts
+    /// created by an operator.
+    pub async fn mark_email_verified(&mut self, ctx: &Context) -> Result<()> {
+        self.email_verified_at = Some(ctx.now().naive_utc());
+        self.updated_at = ctx.now().naive_utc();
         *self = self.save_changes(&*ctx.conn().await?)?;
         Ok(())
     }
 
+    /// Send an email containing a link to verify the email address
+    /// of this user. To prevent spamming users, verification emails
+    /// can only be requested again after a short cooldown.
+    pub async fn request_verification(&mut self, ctx: &Context) -> Result<()> {
+        if self.is_email_verified() {
+            let message = "The email address is already verified";
+            return Err(AppError::Conflict(message.into()).into());
+        }
+        if let Some(sent_at) = self.verification_sent_at {
+            let retry_at = DateTime::<Utc>::from_utc(sent_at, Utc)
+                + Duration::minutes(VERIFICATION_COOLDOWN_MINUTES);
+            if retry_at > ctx.now() {
+                return Err(RateLimited::new("resend_verification", retry_at - ctx.now()).into());
+            }
+        }
+
+        let expires_at = ctx.now() + Duration::hours(VERIFICATION_VALID_HOURS);
+        let payload = format!("{}:{}:{}", self.id, expires_at.timestamp(), self.email);
+        let token = signing::sign(ctx, VERIFICATION_PURPOSE, &payload);
+
+        self.verification_sent_at = Some(ctx.now().naive_utc());
+        self.updated_at = ctx.now().naive_utc();
+        *self = self.save_changes(&*ctx.conn().await?)?;
+
+        let email = Message::builder()
+            .from("noreply@urls.fyi <noreply@urls.fyi>".parse().unwrap()) // TODO: Make configurable ...
+            .to(Mailbox::new(Some(self.name.clone()), self.email()?))
+            .subject("Verify your email address")
+            .body(format!(
+                "Please verify the email address of your account ({email}) by \
+                visiting the following link:\n\n\
+                https://{host}/verify-email/{token}\n\n\
+                The link is valid for {hours} hours. If you did not create an \
+                account, you may safely ignore this email.",
+                email = self.email,
+                host = ctx.config().hostname(),
+                token = token,
+                hours = VERIFICATION_VALID_HOURS,
+            ))?;
+        ctx.mailer().send(email).await?;
+        Ok(())
+    }
+
+    /// Request a verification email, logging instead of returning
+    /// any errors. Used when the verification is not the primary
+    /// purpose of the operation.
+    async fn request_verification_logged(&mut self, ctx: &Context) {
+        if let Err(err) = self.request_verification(ctx).await {
+            log::error!("Failed to send verification email: {}", err);
+        }
+    }
+
+    /// Find the user owning the given private feed token, as
+    /// contained in the url returned by [`feed_url`](User::feed_url).
+    pub async fn find_by_feed_token(ctx: &Context, token: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid feed token");
+
+        let (user_id, secret) = token.split_once('.').ok_or_else(invalid)?;
+        let user_id: UserID = user_id.parse().map_err(|_| invalid())?;
+        let user = Self::find(ctx, user_id).await.map_err(|_| invalid())?;
+        match &user.feed_token {
+            Some(stored) if signing::constant_time_eq(stored.as_bytes(), secret.as_bytes()) => {
+                Ok(user)
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Find the user with the given username, if the given token is
+    /// their feed token, as contained in the urls returned by
+    /// [`feed_urls`](User::feed_urls).
+    pub async fn find_by_username_and_feed_token(
+        ctx: &Context,
+        username: &str,
+        token: &str,
+    ) -> Result<Self> {
+        let invalid = || anyhow!("Invalid feed token");
+
+        let user = Self::find_by_username(ctx, username)
+            .await?
+            .ok_or_else(invalid)?;
+        match &user.feed_token {
+            Some(stored) if signing::constant_time_eq(stored.as_bytes(), token.as_bytes()) => {
+                Ok(user)
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Verify the email address of a user using a token sent by
+    /// [`request_verification`](request_verification). Tokens are
+    /// only valid for the email address they were sent to. Verifying
+    /// an already verified address has no effect.
+    pub async fn verify_email(ctx: &Context, token: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid or expired verification token");
+
+        let payload = signing::verify(ctx, VERIFICATION_PURPOSE, token).ok_or_else(invalid)?;
+        let mut parts = payload.splitn(3, ':');
+        let (user_id, expires_at, email) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(user_id), Some(expires_at), Some(email)) => (user_id, expires_at, email),
+            _ => return Err(invalid()),
+        };
+        let expires_at: i64 = expires_at.parse().map_err(|_| invalid())?;
+        if expires_at < ctx.now().timestamp() {
+            return Err(invalid());
+        }
+
+        let user_id: UserID = user_id.parse().map_err(|_| invalid())?;
+        let mut user = Self::find(ctx, user_id).await.map_err(|_| invalid())?;
+        if user.email != email {
+            return Err(invalid());
+        }
+        if !user.is_email_verified() {
+            user.email_verified_at = Some(ctx.now().naive_utc());
+            user.updated_at = ctx.now().naive_utc();
+            let conn = ctx.conn().await?;
+            user = conn.transaction::<_, anyhow::Error, _>(|| {
+                let user: User = user.save_changes(&*conn)?;
+                // only addresses which replaced an earlier one are confirmed
+                // changes, the address used to register is not
+                let changed = SecurityEvent::exists(
+                    &*conn,
+                    user.id,
+                    SecurityEventKind::EmailChangeRequested,
+                )?;
+                if changed {
+                    SecurityEvent::record(
+                        ctx,
+                        &*conn,
+                        user.id,
+                        SecurityEventKind::EmailChangeConfirmed,
+                    )?;
+                }
+                Ok(user)
+            })?;
+        }
+        Ok(user)
+    }
+
+    /// Override the invitation quota for this user. Passing `None`
+    /// resets the user to the configured default quota.
+    pub async fn set_invite_quota(&mut self, ctx: &Context, quota: Option<i32>) -> Result<()> {
+        if quota.map(|quota| quota < 0) == Some(true) {
+            let message = "The invitation quota can not be negative";
+            return Err(AppError::ValidationFailed(message.into()).into());
+        }
+        self.invite_quota = quota;
+        self.updated_at = ctx.now().naive_utc();
+        // `save_changes` would skip a `None` quota, so
+        // this updates the column explicitly
+        diesel::update(&*self)
+            .set((
+                users::dsl::invite_quota.eq(self.invite_quota),
+                users::dsl::updated_at.eq(self.updated_at),
+            ))
+            .execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Grant additional invitations to this user, on top
+    /// of their regular quota.
+    pub async fn grant_invites(&mut self, ctx: &Context, count: i32) -> Result<()> {
+        if count <= 0 {
+            let message = "At least one invitation must be granted";
+            return Err(AppError::ValidationFailed(message.into()).into());
+        }
+        diesel::update(&*self)
+            .set((
+                users::dsl::invite_credits.eq(users::dsl::invite_credits + count),
+                users::dsl::updated_at.eq(ctx.now().naive_utc()),
+            ))
+            .execute(&*ctx.conn().await?)?;
+        *self = Self::find(ctx, self.id).await?;
+        Ok(())
+    }
+
+    /// Record invitations earned since the last accrual. The
+    /// update only applies if no other request accrued invitations
+    /// since this user was loaded, such that concurrent requests
+    /// don't earn invitations twice. Returns `true` if the accrual
+    /// was recorded.
+    pub async fn accrue_invites(
+        &mut self,
+        ctx: &Context,
+        earned: i32,
+        accrued_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let updated = diesel::update(
+            users::table
+                .find(self.id)
+                .filter(users::dsl::last_accrual_at.eq(self.last_accrual_at)),
+        )
+        .set((
+            users::dsl::invite_credits.eq(users::dsl::invite_credits + earned),
+            users::dsl::last_accrual_at.eq(accrued_at.naive_utc()),
+        ))
+        .execute(&*ctx.conn().await?)?;
+        *self = Self::find(ctx, self.id).await?;
+        Ok(updated == 1)
+    }
+
     /// Creates a login and sends an email to the user, containing the
     /// login token.
     pub async fn request_login(&self, ctx: &Context) -> Result<()> {
@@ -228,13 +1039,296 @@ impl User {
     }
 
     /// Login this user by consuming a login token and returning a
-    /// session token.
+    /// session token. If the login originates from a device which was
+    /// not seen recently, the user is notified by email.
+    ///
+    /// Failed attempts are counted against the outstanding login codes and
+    /// the account. Once too many attempts failed within an hour, logging
+    /// in is locked for a while and the user is notified by email.
     pub async fn login(&self, ctx: &Context, token: &str) -> Result<String> {
-        let mut login: Login = Login::belonging_to(self)
+        let locked_until: Option<NaiveDateTime> = users::table
+            .find(self.id)
+            .select(users::dsl::login_locked_until)
+            .get_result(&*ctx.conn().await?)?;
+        if let Some(locked_until) = locked_until {
+            let retry_after = DateTime::<Utc>::from_utc(locked_until, Utc) - ctx.now();
+            if retry_after > Duration::zero() {
+                return Err(RateLimited::new("login", retry_after).into());
+            }
+        }
+
+        let login: Option<Login> = Login::belonging_to(self)
             .filter(logins::dsl::email_token.eq(token))
             .filter(logins::dsl::claim_until.gt(ctx.now().naive_utc()))
-            .get_result(&*ctx.conn().await?)?;
-        let session = login.claim(ctx, token).await?;
+            .get_result(&*ctx.conn().await?)
+            .optional()?;
+        let session = match login {
+            Some(mut login) => login.claim(ctx, token).await,
+            None => Err(AppError::Unauthenticated("Invalid login token".into()).into()),
+        };
+        let session = match session {
+            Ok(session) => {
+                if let Err(err) = self.record_login(ctx).await {
+                    log::error!("Failed to record login activity: {}", err);
+                }
+                session
+            }
+            Err(err) => {
+                self.record_failed_login(ctx).await?;
+                return Err(err);
+            }
+        };
+        match KnownDevice::remember(ctx, self.id()).await {
+            Ok(true) => self.notify_new_login(ctx),
+            Ok(false) => (),
+            Err(err) => log::error!("Failed to record login device: {}", err),
+        }
         Ok(session)
     }
+
+    /// Update the last login time and login count of this user. This is
+    /// a single atomic update, such that concurrent logins are counted
+    /// correctly. Call this after a login session was created.
+    async fn record_login(&self, ctx: &Context) -> Result<()> {
+        diesel::update(users::table.find(self.id))
+            .set((
+                users::dsl::last_login_at.eq(ctx.now().naive_utc()),
+                users::dsl::login_count.eq(users::dsl::login_count + 1),
+            ))
+            .execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Count a failed login attempt against this account and its outstanding
+    /// login codes, locking the login if too many attempts failed within the
+    /// current window. Each step is a single conditional update, such that
+    /// concurrent attempts are counted correctly and the account is locked
+    /// exactly once. The failure (and lock) are recorded as security events
+    /// in the same transaction.
+    async fn record_failed_login(&self, ctx: &Context) -> Result<()> {
+        let conn = ctx.conn().await?;
+        let now = ctx.now().naive_utc();
+        let window_start =
+            (ctx.now() - Duration::minutes(LOGIN_FAILURE_WINDOW_MINUTES)).naive_utc();
+        let locked_until = ctx.now() + Duration::minutes(LOGIN_LOCK_MINUTES);
+
+        let locked = conn.transaction::<_, anyhow::Error, _>(|| {
+            SecurityEvent::record(ctx, &*conn, self.id, SecurityEventKind::LoginFailed)?;
+            Login::record_failed_attempt(ctx, &*conn, self.id)?;
+
+            let restarted = diesel::update(
+                users::table.find(self.id).filter(
+                    users::dsl::failed_login_since
+                        .is_null()
+                        .or(users::dsl::failed_login_since.lt(window_start)),
+                ),
+            )
+            .set((
+                users::dsl::failed_login_count.eq(1),
+                users::dsl::failed_login_since.eq(now),
+            ))
+            .execute(&*conn)?;
+            if restarted == 0 {
+                diesel::update(users::table.find(self.id))
+                    .set(users::dsl::failed_login_count.eq(users::dsl::failed_login_count + 1))
+                    .execute(&*conn)?;
+            }
+
+            let locked = diesel::update(
+                users::table
+                    .find(self.id)
+                    .filter(users::dsl::failed_login_count.ge(LOGIN_FAILURES_BEFORE_LOCK)),
+            )
+            .set((
+                users::dsl::failed_login_count.eq(0),
+                users::dsl::failed_login_since.eq(None::<NaiveDateTime>),
+                users::dsl::login_locked_until.eq(locked_until.naive_utc()),
+            ))
+            .execute(&*conn)?;
+            if locked == 1 {
+                SecurityEvent::record(ctx, &*conn, self.id, SecurityEventKind::LoginLocked)?;
+            }
+            Ok(locked == 1)
+        })?;
+        drop(conn);
+
+        if locked {
+            if let Err(err) = self.send_login_locked_email(ctx, locked_until).await {
+                log::error!("Failed to send login lock notification: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    async fn send_login_locked_email(&self, ctx: &Context, until: DateTime<Utc>) -> Result<()> {
+        let email = Message::builder()
+            .from("noreply@urls.fyi <noreply@urls.fyi>".parse().unwrap()) // TODO: Make configurable ...
+            .to(Mailbox::new(Some(self.name.clone()), self.email()?))
+            .subject("Login to your account was locked")
+            .body(format!(
+                "There were too many failed attempts to log into your account ({email}).\n\n\
+                Logging in is locked until {until}. If you did not try to log in, someone \
+                else might be trying to access your account.",
+                email = self.email,
+                until = until.format("%Y-%m-%d %H:%M UTC"),
+            ))?;
+        ctx.mailer().send(email).await?;
+        Ok(())
+    }
+
+    /// Ban this user. Banning an already banned user keeps
+    /// the original ban time.
+    pub async fn ban(&mut self, ctx: &Context) -> Result<()> {
+        if self.banned_at.is_none() {
+            self.banned_at = Some(ctx.now().naive_utc());
+            self.updated_at = ctx.now().naive_utc();
+            *self = self.save_changes(&*ctx.conn().await?)?;
+        }
+        Ok(())
+    }
+
+    /// Lift the ban of this user.
+    pub async fn unban(&mut self, ctx: &Context) -> Result<()> {
+        self.banned_at = None;
+        self.updated_at = ctx.now().naive_utc();
+        diesel::update(users::table.find(self.id))
+            .set((
+                users::dsl::banned_at.eq(None::<NaiveDateTime>),
+                users::dsl::updated_at.eq(self.updated_at),
+            ))
+            .execute(&*ctx.conn().await?)?;
+        Ok(())
+    }
+
+    /// Lift a login lock on this account and reset the count of
+    /// failed login attempts.
+    pub async fn unlock_login(&mut self, ctx: &Context) -> Result<()> {
+        self.failed_login_count = 0;
+        self.failed_login_since = None;
+        self.login_locked_until = None;
+        self.updated_at = ctx.now().naive_utc();
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            diesel::update(users::table.find(self.id))
+                .set((
+                    users::dsl::failed_login_count.eq(0),
+                    users::dsl::failed_login_since.eq(None::<NaiveDateTime>),
+                    users::dsl::login_locked_until.eq(None::<NaiveDateTime>),
+                    users::dsl::updated_at.eq(self.updated_at),
+                ))
+                .execute(&*conn)?;
+            SecurityEvent::record(ctx, &*conn, self.id, SecurityEventKind::LoginUnlocked)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Sends an email informing the user about a login from a new
+    /// device. The email is sent in the background, so as to not
+    /// delay the login.
+    fn notify_new_login(&self, ctx: &Context) {
+        let user = self.clone();
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            if let Err(err) = user.send_new_login_email(&ctx).await {
+                log::error!("Failed to send new login notification: {}", err);
+            }
+        });
+    }
+
+    async fn send_new_login_email(&self, ctx: &Context) -> Result<()> {
+        if !UserPreferences::find(ctx, self.id())
+            .await?
+            .is_subscribed(EmailCategory::NewLogin)
+        {
+            return Ok(());
+        }
+
+        let device = ctx
+            .user_agent()
+            .and_then(|raw| woothee::parser::Parser::new().parse(raw))
+            .map(|res| format!("{} on {}", res.name, res.os))
+            .unwrap_or_else(|| "Unknown device".to_string());
+        let location = match ctx.remote_ip_address() {
+            Some(ip_addr) => LoginLocation::lookup(ctx, ip_addr)
+                .await
+                .map_err(|err| log::warn!("Failed to look up login location: {}", err))
+                .ok()
+                .flatten()
+                .map(|location| format!("{} (approximate)", location.describe()))
+                .unwrap_or_else(|| "Unknown".to_string()),
+            None => "Unknown".to_string(),
+        };
+        let ip_address = ctx
+            .remote_ip_address()
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "Unknown".to_string());
+
+        let email = Message::builder()
+            .from("noreply@urls.fyi <noreply@urls.fyi>".parse().unwrap()) // TODO: Make configurable ...
+            .to(Mailbox::new(Some(self.name.clone()), self.email()?))
+            .subject("New sign-in to your account")
+            .body(format!(
+                "Your account ({email}) was signed into from a new device.\n\n\
+                Device: {device}\n\
+                Location: {location}\n\
+                IP address: {ip_address}\n\n\
+                If this was you, you may safely ignore this email. Otherwise, you \
+                can review and revoke active sessions at https://{host}/account\n\n\
+                --\n\
+                {footer}",
+                email = self.email,
+                device = device,
+                location = location,
+                ip_address = ip_address,
+                host = ctx.config().hostname(),
+                footer = UnsubscribeToken::email_footer(ctx, self.id(), EmailCategory::NewLogin),
+            ))?;
+        ctx.mailer().send(email).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_username() {
+        let cases = [
+            ("Test User", "test-user"),
+            ("  alice  ", "alice"),
+            ("Dr. Jane O'Neil", "dr-jane-o-neil"),
+            ("Über", "ber"),
+            ("李", "user"),
+            ("x", "user"),
+            (
+                "A very long display name for somebody",
+                "a-very-long-display-name-f",
+            ),
+        ];
+        for (name, username) in cases {
+            assert_eq!(derive_username(name), username, "{}", name);
+            assert!(valid_username(&derive_username(name)).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_valid_username() {
+        for username in ["ab", "test-user", "user_42", "user-abcdefghijklmnopqrstu"] {
+            assert!(valid_username(username).is_ok(), "{}", username);
+        }
+        let long = "a".repeat(31);
+        for username in [
+            "",
+            "a",
+            "Test",
+            "test user",
+            "test.user",
+            "über",
+            long.as_str(),
+        ] {
+            assert!(valid_username(username).is_err(), "{}", username);
+        }
+    }
 }
@@ -0,0 +1,599 @@
+use crate::db::id::{UserID, WebhookDeliveryID, WebhookID};
+use crate::db::models::{Comment, Url, User};
+use crate::schema::{webhook_deliveries, webhooks};
+use crate::{fetch, Context};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::Text;
+use hmac::{Hmac, Mac, NewMac};
+use juniper::GraphQLEnum;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::io::Write;
+
+/// Maximum number of webhooks a single user may create.
+pub const MAX_WEBHOOKS_PER_USER: usize = 10;
+/// Number of failed attempts after which a
+/// delivery is given up.
+const MAX_DELIVERY_ATTEMPTS: i32 = 6;
+/// Shortest and longest secrets accepted for a webhook.
+const MIN_SECRET_LEN: usize = 16;
+const MAX_SECRET_LEN: usize = 256;
+/// Longest target URL accepted for a webhook.
+const MAX_URL_LEN: usize = 2048;
+
+/// Header carrying the signature of a delivery.
+pub const SIGNATURE_HEADER: &str = "X-Urls-Signature";
+
+/// Something which happened on the site, which
+/// webhooks can subscribe to.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum WebhookEvent {
+    /// A link was submitted, or a draft was published.
+    UrlSubmitted,
+    /// A comment was added to a submission.
+    CommentAdded,
+}
+
+impl WebhookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::UrlSubmitted => "url_submitted",
+            WebhookEvent::CommentAdded => "comment_added",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "url_submitted" => Some(WebhookEvent::UrlSubmitted),
+            "comment_added" => Some(WebhookEvent::CommentAdded),
+            _ => None,
+        }
+    }
+}
+
+/// The progress of a webhook delivery.
+#[derive(GraphQLEnum, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[sql_type = "Text"]
+pub enum WebhookDeliveryStatus {
+    /// The delivery is waiting for its next attempt.
+    Pending,
+    /// The delivery is being sent.
+    Processing,
+    /// The target accepted the delivery.
+    Delivered,
+    /// The delivery was given up after repeated failures.
+    Failed,
+}
+
+/// A URL which receives events as JSON payloads. Webhooks of users
+/// receive events concerning their own submissions, while site wide
+/// webhooks, which only administrators may create, receive all events.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset, Associations)]
+#[belongs_to(User)]
+pub struct Webhook {
+    id: WebhookID,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+
+    user_id: UserID,
+    site_wide: bool,
+    url: String,
+    events: String,
+    secret: String,
+}
+
+/// A single event sent to a webhook, which is retried with an
+/// exponential backoff until the target responds with a 2xx status.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset, Associations)]
+#[belongs_to(Webhook)]
+#[table_name = "webhook_deliveries"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct WebhookDelivery {
+    id: WebhookDeliveryID,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+
+    webhook_id: WebhookID,
+    event: WebhookEvent,
+    payload: String,
+    status: WebhookDeliveryStatus,
+    attempts: i32,
+    next_attempt_at: NaiveDateTime,
+    response_status: Option<i32>,
+    last_error: Option<String>,
+    delivered_at: Option<NaiveDateTime>,
+}
+
+/// Time to wait before attempting a delivery again, after the
+/// given number of failed attempts. This starts at one minute,
+/// and doubles with each attempt.
+fn delivery_backoff(attempts: i32) -> Duration {
+    Duration::minutes(2i64.pow(attempts.clamp(1, 16) as u32 - 1))
+}
+
+/// Sign the given body with the secret of a webhook, such that
+/// receivers can verify deliveries came from us. The signature
+/// is the hex encoded HMAC-SHA256 of the body, prefixed with
+/// `sha256=`.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", digest)
+}
+
+fn url_payload(ctx: &Context, url: &Url, submitted_by: &User) -> Value {
+    json!({
+        "id": url.id().to_string(),
+        "url": url.url_str(),
+        "title": url.title(),
+        "submitted_by": submitted_by.username(),
+        "created_at": url.created_at().to_rfc3339(),
+        "discussion": format!("https://{}/comments/{}", ctx.config().hostname(), url.id()),
+    })
+}
+
+impl Webhook {
+    pub fn id(&self) -> WebhookID {
+        self.id
+    }
+
+    pub fn user_id(&self) -> UserID {
+        self.user_id
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Whether this webhook receives the events of the whole
+    /// site, rather than those of its owner.
+    pub fn site_wide(&self) -> bool {
+        self.site_wide
+    }
+
+    /// The events this webhook is subscribed to.
+    pub fn events(&self) -> Vec<WebhookEvent> {
+        self.events
+            .split(',')
+            .filter_map(WebhookEvent::from_name)
+            .collect()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+}
+
+impl Webhook {
+    pub async fn find(ctx: &Context, id: WebhookID) -> Result<Self> {
+        let webhook = webhooks::table.find(id).get_result(&*ctx.conn().await?)?;
+        Ok(webhook)
+    }
+
+    /// Webhooks created by the given user, oldest first.
+    pub async fn for_user(ctx: &Context, user_id: UserID) -> Result<Vec<Self>> {
+        let webhooks = webhooks::table
+            .filter(webhooks::dsl::user_id.eq(user_id))
+            .order_by(webhooks::dsl::created_at.asc())
+            .load(&*ctx.conn().await?)?;
+        Ok(webhooks)
+    }
+
+    /// Create a webhook for the currently logged in user, which
+    /// sends the given events to the given URL. The URL must be
+    /// reachable on the public internet, like the links fetched
+    /// for submissions. Site wide webhooks require the permission
+    /// to manage them.
+    pub async fn create(
+        ctx: &Context,
+        url: &str,
+        events: &[WebhookEvent],
+        secret: &str,
+        site_wide: bool,
+    ) -> Result<Self> {
+        let user = ctx.verified_user().await?;
+        if site_wide {
+            user.check_permissions(ctx, |perm| perm.manage_site_webhooks())
+                .await?;
+        }
+        let url = url.trim();
+        if url.len() > MAX_URL_LEN {
+            return Err(anyhow!("Webhook URLs can be at most {} bytes", MAX_URL_LEN));
+        }
+        if events.is_empty() {
+            return Err(anyhow!("Webhooks must subscribe to at least one event"));
+        }
+        if secret.len() < MIN_SECRET_LEN || secret.len() > MAX_SECRET_LEN {
+            return Err(anyhow!(
+                "Webhook secrets must be between {} and {} bytes",
+                MIN_SECRET_LEN,
+                MAX_SECRET_LEN
+            ));
+        }
+        if Self::for_user(ctx, user.id()).await?.len() >= MAX_WEBHOOKS_PER_USER {
+            return Err(anyhow!(
+                "You can create at most {} webhooks",
+                MAX_WEBHOOKS_PER_USER
+            ));
+        }
+        fetch::check_fetchable(ctx, url)
+            .await
+            .map_err(|err| anyhow!("Invalid webhook URL: {}", err))?;
+
+        let mut names: Vec<&str> = vec![];
+        for event in events {
+            if !names.contains(&event.name()) {
+                names.push(event.name());
+            }
+        }
+        let webhook = Webhook {
+            id: WebhookID::new(),
+            created_at: ctx.now().naive_utc(),
+            updated_at: ctx.now().naive_utc(),
+
+            user_id: user.id(),
+            site_wide,
+            url: url.to_string(),
+            events: names.join(","),
+            secret: secret.to_string(),
+        };
+        diesel::insert_into(webhooks::table)
+            .values(&webhook)
+            .execute(&*ctx.conn().await?)?;
+        Ok(webhook)
+    }
+
+    /// Delete this webhook and its delivery log. Webhooks can be
+    /// deleted by their owner, and site wide webhooks by anyone
+    /// with the permission to manage them.
+    pub async fn delete(&self, ctx: &Context) -> Result<()> {
+        let user = ctx.user().await?;
+        if user.id() != self.user_id {
+            if !self.site_wide {
+                return Err(anyhow!("Not authorized"));
+            }
+            user.check_permissions(ctx, |perm| perm.manage_site_webhooks())
+                .await?;
+        }
+        let conn = ctx.conn().await?;
+        conn.transaction::<_, anyhow::Error, _>(|| {
+            diesel::delete(
+                webhook_deliveries::table.filter(webhook_deliveries::dsl::webhook_id.eq(self.id)),
+            )
+            .execute(&*conn)?;
+            diesel::delete(self).execute(&*conn)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// The latest deliveries to this webhook, newest first.
+    pub async fn deliveries(&self, ctx: &Context, limit: i64) -> Result<Vec<WebhookDelivery>> {
+        let deliveries = webhook_deliveries::table
+            .filter(webhook_deliveries::dsl::webhook_id.eq(self.id))
+            .order_by((
+                webhook_deliveries::dsl::created_at.desc(),
+                webhook_deliveries::dsl::id.desc(),
+            ))
+            .limit(limit)
+            .load(&*ctx.conn().await?)?;
+        Ok(deliveries)
+    }
+
+    /// Queue a delivery of the given event to all webhooks subscribed
+    /// to it, which are site wide or owned by the given user.
+    async fn dispatch(
+        ctx: &Context,
+        event: WebhookEvent,
+        owner: UserID,
+        mut payload: Value,
+    ) -> Result<()> {
+        let subscribed: Vec<Webhook> = webhooks::table
+            .filter(
+                webhooks::dsl::site_wide
+                    .eq(true)
+                    .or(webhooks::dsl::user_id.eq(owner)),
+            )
+            .load::<Webhook>(&*ctx.conn().await?)?
+            .into_iter()
+            .filter(|webhook| webhook.events().contains(&event))
+            .collect();
+        if subscribed.is_empty() {
+            return Ok(());
+        }
+
+        payload["event"] = json!(event.name());
+        payload["created_at"] = json!(ctx.now().to_rfc3339());
+        let payload = payload.to_string();
+        let deliveries: Vec<WebhookDelivery> = subscribed
+            .iter()
+            .map(|webhook| WebhookDelivery {
+                id: WebhookDeliveryID::new(),
+                created_at: ctx.now().naive_utc(),
+                updated_at: ctx.now().naive_utc(),
+
+                webhook_id: webhook.id,
+                event,
+                payload: payload.clone(),
+                status: WebhookDeliveryStatus::Pending,
+                attempts: 0,
+                next_attempt_at: ctx.now().naive_utc(),
+                response_status: None,
+                last_error: None,
+                delivered_at: None,
+            })
+            .collect();
+        let conn = ctx.conn().await?;
+        for delivery in &deliveries {
+            diesel::insert_into(webhook_deliveries::table)
+                .values(delivery)
+                .execute(&*conn)?;
+        }
+        Ok(())
+    }
+
+    /// Notify webhooks of a new submission. Failing to queue
+    /// deliveries does not fail the submission.
+    pub(crate) async fn url_submitted(ctx: &Context, url: &Url) {
+        let result = async {
+            let submitted_by = url.created_by(ctx).await?;
+            let payload = json!({ "url": url_payload(ctx, url, &submitted_by) });
+            Self::dispatch(
+                ctx,
+                WebhookEvent::UrlSubmitted,
+                url.created_by_id(),
+                payload,
+            )
+            .await
+        };
+        if let Err(err) = result.await {
+            log::error!("Failed to queue webhooks for url {}: {}", url.id(), err);
+        }
+    }
+
+    /// Notify webhooks of a new comment, which are site wide or
+    /// owned by the author of the submission. Failing to queue
+    /// deliveries does not fail the comment.
+    pub(crate) async fn comment_added(ctx: &Context, comment: &Comment) {
+        let result = async {
+            let url = comment.url(ctx).await?;
+            let submitted_by = url.created_by(ctx).await?;
+            let author = comment.created_by(ctx).await?;
+            let payload = json!({
+                "comment": {
+                    "id": comment.id().to_string(),
+                    "comment": comment.text(),
+                    "created_by": author.username(),
+                    "replies_to": comment.replies_to_id().map(|id| id.to_string()),
+                    "created_at": comment.created_at().to_rfc3339(),
+                },
+                "url": url_payload(ctx, &url, &submitted_by),
+            });
+            Self::dispatch(
+                ctx,
+                WebhookEvent::CommentAdded,
+                url.created_by_id(),
+                payload,
+            )
+            .await
+        };
+        if let Err(err) = result.await {
+            log::error!(
+                "Failed to queue webhooks for comment {}: {}",
+                comment.id(),
+                err
+            );
+        }
+    }
+}
+
+impl WebhookDelivery {
+    pub fn id(&self) -> WebhookDeliveryID {
+        self.id
+    }
+
+    pub fn event(&self) -> WebhookEvent {
+        self.event
+    }
+
+    /// The JSON document which is sent.
+    pub fn payload(&self) -> &str {
+        &self.payload
+    }
+
+    pub fn status(&self) -> WebhookDeliveryStatus {
+        self.status
+    }
+
+    /// Number of failed attempts so far.
+    pub fn attempts(&self) -> i32 {
+        self.attempts
+    }
+
+    /// The status of the last response, if any.
+    pub fn response_status(&self) -> Option<i32> {
+        self.response_status
+    }
+
+    /// Why the last attempt failed, if it did.
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.created_at, Utc)
+    }
+
+    /// When the next attempt is made, if the
+    /// delivery is still pending.
+    pub fn next_attempt_at(&self) -> Option<DateTime<Utc>> {
+        match self.status {
+            WebhookDeliveryStatus::Pending => Some(DateTime::from_utc(self.next_attempt_at, Utc)),
+            _ => None,
+        }
+    }
+
+    pub fn delivered_at(&self) -> Option<DateTime<Utc>> {
+        self.delivered_at.map(|at| DateTime::from_utc(at, Utc))
+    }
+}
+
+impl WebhookDelivery {
+    /// Attempt all pending deliveries which are due.
+    pub async fn deliver_pending(ctx: &Context) -> Result<()> {
+        let pending: Vec<(WebhookDelivery, Webhook)> = webhook_deliveries::table
+            .inner_join(webhooks::table)
+            .filter(webhook_deliveries::dsl::status.eq(WebhookDeliveryStatus::Pending))
+            .filter(webhook_deliveries::dsl::next_attempt_at.le(ctx.now().naive_utc()))
+            .order_by(webhook_deliveries::dsl::next_attempt_at.asc())
+            .load(&*ctx.conn().await?)?;
+
+        for (mut delivery, webhook) in pending {
+            if let Err(err) = delivery.deliver(ctx, &webhook).await {
+                log::error!(
+                    "Failed to process webhook delivery {}: {}",
+                    delivery.id,
+                    err
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the payload to the webhook. Deliveries the target does not
+    /// accept with a 2xx status are retried with an exponential backoff,
+    /// until they are given up after [`MAX_DELIVERY_ATTEMPTS`] attempts.
+    async fn deliver(&mut self, ctx: &Context, webhook: &Webhook) -> Result<()> {
+        // claim the delivery, so concurrent job
+        // runs do not send it twice
+        let claimed = diesel::update(&*self)
+            .filter(webhook_deliveries::dsl::status.eq(WebhookDeliveryStatus::Pending))
+            .set((
+                webhook_deliveries::dsl::status.eq(WebhookDeliveryStatus::Processing),
+                webhook_deliveries::dsl::updated_at.eq(ctx.now().naive_utc()),
+            ))
+            .execute(&*ctx.conn().await?)?;
+        if claimed != 1 {
+            return Ok(());
+        }
+        self.status = WebhookDeliveryStatus::Processing;
+
+        let headers = [
+            ("X-Urls-Event", self.event.name().to_string()),
+            ("X-Urls-Delivery", self.id.to_string()),
+            (
+                SIGNATURE_HEADER,
+                signature(&webhook.secret, self.payload.as_bytes()),
+            ),
+        ];
+        let result = fetch::post_json(
+            ctx,
+            &webhook.url,
+            &headers,
+            self.payload.clone().into_bytes(),
+        )
+        .await;
+        let error = match result {
+            Ok(status) => {
+                self.response_status = Some(status.as_u16().into());
+                if status.is_success() {
+                    None
+                } else {
+                    Some(format!("The webhook responded with {}", status))
+                }
+            }
+            Err(err) => {
+                self.response_status = None;
+                Some(err.to_string())
+            }
+        };
+        match error {
+            None => {
+                self.status = WebhookDeliveryStatus::Delivered;
+                self.last_error = None;
+                self.delivered_at = Some(ctx.now().naive_utc());
+            }
+            Some(error) => {
+                log::info!("Failed to deliver webhook {}: {}", self.id, error);
+                self.attempts += 1;
+                self.last_error = Some(error);
+                if self.attempts >= MAX_DELIVERY_ATTEMPTS {
+                    self.status = WebhookDeliveryStatus::Failed;
+                } else {
+                    self.status = WebhookDeliveryStatus::Pending;
+                    self.next_attempt_at =
+                        (ctx.now() + delivery_backoff(self.attempts)).naive_utc();
+                }
+            }
+        }
+        self.updated_at = ctx.now().naive_utc();
+        *self = self.save_changes(&*ctx.conn().await?)?;
+        Ok(())
+    }
+}
+
+impl<DB> ToSql<Text, DB> for WebhookEvent
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        self.name().to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for WebhookEvent
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        WebhookEvent::from_name(&String::from_sql(bytes)?)
+            .ok_or_else(|| "Unrecognized webhook event".into())
+    }
+}
+
+impl<DB> ToSql<Text, DB> for WebhookDeliveryStatus
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> diesel::serialize::Result {
+        let t = match *self {
+            WebhookDeliveryStatus::Pending => "pending",
+            WebhookDeliveryStatus::Processing => "processing",
+            WebhookDeliveryStatus::Delivered => "delivered",
+            WebhookDeliveryStatus::Failed => "failed",
+        };
+        t.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for WebhookDeliveryStatus
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "pending" => Ok(WebhookDeliveryStatus::Pending),
+            "processing" => Ok(WebhookDeliveryStatus::Processing),
+            "delivered" => Ok(WebhookDeliveryStatus::Delivered),
+            "failed" => Ok(WebhookDeliveryStatus::Failed),
+            _ => Err("Unrecognized webhook delivery status".into()),
+        }
+    }
+}
@@ -1,20 +1,95 @@
 use crate::db::id::UrlID;
 use crate::db::models::Url;
+use crate::error::AppError;
 use crate::Config;
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use std::cmp::Ordering;
 use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
 use tantivy::{
     collector::TopDocs,
     directory::MmapDirectory,
     doc,
     query::Query,
-    query::{BooleanQuery, FuzzyTermQuery},
-    schema::{Field, Schema},
-    Index, IndexReader, Term,
+    query::{BooleanQuery, BoostQuery, FuzzyTermQuery, TermQuery},
+    schema::{Field, IndexRecordOption, Schema},
+    DocAddress, Document, Index, IndexReader, Score, Searcher, Term,
 };
 use tokio::task::block_in_place;
 
 const WRITER_HEAP: usize = 100_000_000;
+/// Queries with fewer characters don't match anything.
+const MIN_QUERY_LEN: usize = 2;
+/// Maximum number of terms of a query which are searched for.
+const MAX_QUERY_TERMS: usize = 16;
+/// Number of hits fetched from the index at once, while
+/// paging through the results of a query.
+const BATCH_SIZE: usize = 100;
+/// Maximum number of hits of a query which are paged through.
+const MAX_RESULTS: usize = 1000;
+/// Weight of fuzzy matches, relative to exact matches.
+const FUZZY_BOOST: f32 = 0.1;
+
+/// Position of a url in a list of search results. This holds the
+/// rank of the url, and the values ties are broken by, such that
+/// later pages continue strictly after the cursor, even if new urls
+/// were indexed in the meantime. Cursors are formatted as plain text,
+/// and should be signed before handing them out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchCursor {
+    rank: f32,
+    created_at: i64,
+    id: UrlID,
+}
+
+impl SearchCursor {
+    pub fn id(&self) -> UrlID {
+        self.id
+    }
+
+    /// Whether the result at this cursor is listed after
+    /// the result at the given cursor.
+    pub fn follows(&self, other: &Self) -> bool {
+        self.order(other) == Ordering::Greater
+    }
+
+    /// Orders results best match first, then newest first.
+    fn order(&self, other: &Self) -> Ordering {
+        other
+            .rank
+            .partial_cmp(&self.rank)
+            .unwrap_or(Ordering::Equal)
+            .then(other.created_at.cmp(&self.created_at))
+            .then(other.id.as_str().cmp(self.id.as_str()))
+    }
+}
+
+impl fmt::Display for SearchCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.rank, self.created_at, self.id)
+    }
+}
+
+impl FromStr for SearchCursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ERR: &str = "Invalid search cursor";
+        let mut parts = s.splitn(3, ':');
+        let rank: f32 = parts.next().and_then(|r| r.parse().ok()).ok_or(ERR)?;
+        if !rank.is_finite() {
+            return Err(ERR);
+        }
+        let created_at = parts.next().and_then(|t| t.parse().ok()).ok_or(ERR)?;
+        let id = parts.next().and_then(|id| id.parse().ok()).ok_or(ERR)?;
+        Ok(Self {
+            rank,
+            created_at,
+            id,
+        })
+    }
+}
 
 #[derive(Clone)]
 pub struct SearchIndex {
@@ -23,6 +98,8 @@ pub struct SearchIndex {
     f_id: Field,
     f_title: Field,
     f_description: Field,
+    f_domain: Field,
+    f_created_at: Field,
 }
 
 impl SearchIndex {
@@ -33,11 +110,23 @@ impl SearchIndex {
         let f_id = builder.add_bytes_field("id", STORED);
         let f_title = builder.add_text_field("title", TEXT);
         let f_description = builder.add_text_field("description", TEXT);
+        let f_domain = builder.add_text_field("domain", TEXT);
+        let f_created_at = builder.add_i64_field("created_at", STORED);
         let schema = builder.build();
 
         let index = if let Some(path) = conf.search_index() {
             tokio::fs::create_dir_all(path).await?;
-            Index::open_or_create(MmapDirectory::open(path)?, schema)?
+            match Index::open_or_create(MmapDirectory::open(path)?, schema.clone()) {
+                Ok(index) => index,
+                Err(err) => {
+                    // the index is rebuilt on startup, so an index with
+                    // an outdated schema can be discarded
+                    log::warn!("Discarding existing search index: {}", err);
+                    tokio::fs::remove_dir_all(path).await?;
+                    tokio::fs::create_dir_all(path).await?;
+                    Index::open_or_create(MmapDirectory::open(path)?, schema)?
+                }
+            }
         } else {
             Index::create_in_ram(schema)
         };
@@ -48,6 +137,50 @@ impl SearchIndex {
             f_id,
             f_title,
             f_description,
+            f_domain,
+            f_created_at,
+        })
+    }
+
+    fn document(&self, url: &Url) -> Document {
+        let domain = url
+            .url()
+            .ok()
+            .flatten()
+            .and_then(|uri| uri.host().map(str::to_string))
+            .unwrap_or_default();
+        doc! {
+            self.f_id => url.id().as_str().as_bytes(),
+            self.f_title => url.title().unwrap_or(""),
+            self.f_description => url.description().or_else(|| url.text()).unwrap_or(""),
+            self.f_domain => domain,
+            self.f_created_at => url.created_at().timestamp_nanos(),
+        }
+    }
+
+    /// Whether the given `Url` is included in the index, which leaves
+    /// out deleted and unlisted `Url`s, drafts, and `Url`s removed by
+    /// a moderator.
+    fn indexed(url: &Url) -> bool {
+        !url.is_deleted() && !url.is_removed() && !url.is_draft() && !url.is_unlisted()
+    }
+
+    /// Replaces all documents in the index with the
+    /// given `Url`s. `Url`s which are not indexed, see
+    /// [`indexed`](SearchIndex::indexed), are skipped.
+    pub fn rebuild<'a, I>(&self, urls: I) -> Result<()>
+    where
+        I: std::iter::Iterator<Item = &'a Url>,
+    {
+        block_in_place(|| {
+            let mut writer = self.index.writer(WRITER_HEAP)?;
+            writer.delete_all_documents()?;
+            for url in urls.filter(|url| Self::indexed(url)) {
+                writer.add_document(self.document(url));
+            }
+            writer.commit()?;
+            self.reader.reload()?;
+            Ok(())
         })
     }
 
@@ -56,21 +189,19 @@ impl SearchIndex {
         self.index_urls(std::iter::once(url))
     }
 
-    /// Adds a list of given `Url`s to the index.
+    /// Adds a list of given `Url`s to the index. `Url`s
+    /// which are not indexed are skipped.
     pub fn index_urls<'a, I>(&self, urls: I) -> Result<()>
     where
         I: std::iter::Iterator<Item = &'a Url>,
     {
         block_in_place(|| {
             let mut writer = self.index.writer(WRITER_HEAP)?;
-            for url in urls {
-                writer.add_document(doc! {
-                    self.f_id => url.id().as_str().as_bytes(),
-                    self.f_title => url.title().unwrap_or(""),
-                    self.f_description => url.description().unwrap_or(""),
-                });
+            for url in urls.filter(|url| Self::indexed(url)) {
+                writer.add_document(self.document(url));
             }
             writer.commit()?;
+            self.reader.reload()?;
             Ok(())
         })
     }
@@ -92,42 +223,195 @@ impl SearchIndex {
                 writer.delete_term(id_term);
             }
             writer.commit()?;
+            self.reader.reload()?;
             Ok(())
         })
     }
 
-    /// Searches the index and returns all url IDs
-    /// matching the given query.
-    pub fn find(&self, query: &str) -> Result<Vec<UrlID>> {
+    /// Searches the index and returns cursors pointing to the best
+    /// matching urls, ranked by how well their title, description, and
+    /// domain match the query, with more recent urls first if they rank
+    /// the same. The query is split into terms the same way the indexed
+    /// text is, so it can't contain any operators, and queries shorter
+    /// than two characters don't match anything.
+    ///
+    /// This returns at most `limit` results, which are listed strictly
+    /// after the cursor `after` and before the cursor `before`, if given.
+    /// Hits are fetched from the index in batches until enough results
+    /// are found.
+    ///
+    /// Ranks depend on how common the terms of the query are across all
+    /// indexed urls, so they change as urls are indexed. Cursors are thus
+    /// compared at the current rank of the url they point to, and only
+    /// at their own rank if the url no longer matches.
+    pub fn find(
+        &self,
+        query: &str,
+        after: Option<&SearchCursor>,
+        before: Option<&SearchCursor>,
+        limit: usize,
+    ) -> Result<Vec<SearchCursor>> {
         if query.len() > 1024 {
-            return Err(anyhow!("The search query is too long"));
+            let message = "The search query is too long";
+            return Err(AppError::ValidationFailed(message.into()).into());
+        }
+        if query.trim().chars().count() < MIN_QUERY_LEN || limit == 0 {
+            return Ok(vec![]);
         }
 
         block_in_place(|| {
-            let terms: Vec<Box<dyn Query>> = query
-                .split_whitespace()
-                .flat_map(|term| -> Vec<Box<dyn Query>> {
-                    let title = Term::from_field_text(self.f_title, term);
-                    let title = FuzzyTermQuery::new(title, 2, true);
-                    let desc = Term::from_field_text(self.f_description, term);
-                    let desc = FuzzyTermQuery::new(desc, 2, true);
-                    vec![Box::new(title), Box::new(desc)]
-                })
-                .collect();
-            let query = BooleanQuery::union(terms);
-
+            let query = match self.query(query)? {
+                Some(query) => query,
+                None => return Ok(vec![]),
+            };
             let searcher = self.reader.searcher();
-            let top_docs = TopDocs::with_limit(64); // TODO(dyedgreen): Fix this ...
-            let docs = searcher.search(&query, &top_docs)?;
-            let ids = docs
-                .into_iter()
-                .filter_map(|(_, addr)| searcher.doc(addr).ok())
-                .filter_map(|doc| {
-                    let id = doc.get_first(self.f_id)?.bytes_value()?.try_into().ok()?;
-                    Some(id)
+            let mut bounds = [after.copied(), before.copied()];
+            let mut found = [after.is_none(), before.is_none()];
+            let mut hits = vec![];
+            let mut offset = 0;
+            while offset < MAX_RESULTS {
+                let batch = TopDocs::with_limit(BATCH_SIZE).and_offset(offset);
+                let docs = searcher.search(&query, &batch)?;
+                offset += docs.len();
+                let lowest = match docs.last() {
+                    Some(&(rank, _)) => rank,
+                    None => break,
+                };
+                let exhausted = docs.len() < BATCH_SIZE;
+                for (rank, addr) in docs {
+                    let hit = match self.cursor(&searcher, rank, addr) {
+                        Some(hit) => hit,
+                        None => continue,
+                    };
+                    for (bound, found) in bounds.iter_mut().zip(&mut found) {
+                        if let Some(bound) = bound.as_mut().filter(|bound| bound.id == hit.id) {
+                            bound.rank = hit.rank;
+                            *found = true;
+                        }
+                    }
+                    hits.push(hit);
+                }
+                if exhausted {
+                    break;
+                }
+                // until the urls of the cursors are found, later
+                // hits may still change where the page starts
+                if found != [true, true] {
+                    continue;
+                }
+                let [after, before] = bounds;
+                if before.map_or(false, |before| lowest < before.rank) {
+                    break;
+                }
+                // later hits rank lower, unless they tie
+                // with the last result of the page
+                let results = Self::between(&hits, after.as_ref(), before.as_ref());
+                if results.len() >= limit && lowest < results[limit - 1].rank {
+                    break;
+                }
+            }
+            let [after, before] = bounds;
+            let mut results = Self::between(&hits, after.as_ref(), before.as_ref());
+            results.truncate(limit);
+            Ok(results)
+        })
+    }
+
+    /// The given hits which are listed strictly between the given
+    /// cursors, sorted best match first.
+    fn between(
+        hits: &[SearchCursor],
+        after: Option<&SearchCursor>,
+        before: Option<&SearchCursor>,
+    ) -> Vec<SearchCursor> {
+        let mut results: Vec<SearchCursor> = hits
+            .iter()
+            .filter(|hit| after.map_or(true, |after| hit.follows(after)))
+            .filter(|hit| before.map_or(true, |before| before.follows(hit)))
+            .copied()
+            .collect();
+        results.sort_by(SearchCursor::order);
+        results
+    }
+
+    /// The index query for the given search query, or `None`
+    /// if it doesn't contain any terms.
+    fn query(&self, query: &str) -> Result<Option<BooleanQuery>> {
+        let mut terms = vec![];
+        let mut tokens = self
+            .index
+            .tokenizer_for_field(self.f_title)?
+            .token_stream(query);
+        while tokens.advance() && terms.len() < MAX_QUERY_TERMS {
+            let text = tokens.token().text.clone();
+            if !terms.contains(&text) {
+                terms.push(text);
+            }
+        }
+
+        let fields = [
+            (self.f_title, 2.0),
+            (self.f_domain, 1.5),
+            (self.f_description, 1.0),
+        ];
+        let queries: Vec<Box<dyn Query>> = terms
+            .iter()
+            .flat_map(|text| {
+                fields.iter().flat_map(move |&(field, boost)| {
+                    let term = Term::from_field_text(field, text);
+                    let exact = TermQuery::new(term.clone(), IndexRecordOption::WithFreqs);
+                    let fuzzy = FuzzyTermQuery::new(term, 2, true);
+                    let queries: [Box<dyn Query>; 2] = [
+                        Box::new(BoostQuery::new(Box::new(exact), boost)),
+                        Box::new(BoostQuery::new(Box::new(fuzzy), boost * FUZZY_BOOST)),
+                    ];
+                    queries
                 })
-                .collect();
-            Ok(ids)
+            })
+            .collect();
+        if queries.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(BooleanQuery::union(queries)))
+    }
+
+    /// The cursor pointing to the given hit.
+    fn cursor(&self, searcher: &Searcher, rank: Score, addr: DocAddress) -> Option<SearchCursor> {
+        let doc = searcher.doc(addr).ok()?;
+        let id = doc.get_first(self.f_id)?.bytes_value()?.try_into().ok()?;
+        let created_at = doc.get_first(self.f_created_at)?.i64_value()?;
+        Some(SearchCursor {
+            rank,
+            created_at,
+            id,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_cursor() {
+        let cursor = SearchCursor {
+            rank: 2.2 + 0.1,
+            created_at: -42,
+            id: UrlID::new(),
+        };
+        assert_eq!(cursor.to_string().parse::<SearchCursor>(), Ok(cursor));
+        assert!("not a cursor".parse::<SearchCursor>().is_err());
+
+        let better = SearchCursor {
+            rank: 3.0,
+            ..cursor
+        };
+        let newer = SearchCursor {
+            created_at: 0,
+            ..cursor
+        };
+        assert!(cursor.follows(&better));
+        assert!(cursor.follows(&newer));
+        assert!(!cursor.follows(&cursor));
+    }
+}
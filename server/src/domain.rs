@@ -0,0 +1,124 @@
+//! Registrable domains of submitted URLs. Submissions are grouped by
+//! the domain a user could register, rather than by their host name,
+//! such that `blog.example.co.uk` and `www.example.co.uk` both belong
+//! to `example.co.uk`. Which part of a host name is a public suffix is
+//! looked up in the public suffix list.
+//!
+//! Domains are stored in their ASCII form, i.e. international domain
+//! names are in punycode, and converted to unicode for display.
+
+use reqwest::Url;
+use std::net::IpAddr;
+
+/// Registrable domain of the given host name. Hosts which have no
+/// registrable domain, e.g. IP addresses, `localhost`, or public
+/// suffixes themselves, are their own domain.
+pub fn registrable(host: &str) -> String {
+    let host = host.trim().trim_end_matches('.');
+    if host.parse::<IpAddr>().is_ok() || host.starts_with('[') {
+        return host.to_ascii_lowercase();
+    }
+    let host = idna::domain_to_ascii(host).unwrap_or_else(|_| host.to_ascii_lowercase());
+    psl::domain_str(&host).map(str::to_string).unwrap_or(host)
+}
+
+/// Registrable domain of the host of the given URL, or an empty string
+/// if the URL has no host.
+pub fn of_url(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(registrable))
+        .unwrap_or_default()
+}
+
+/// Registrable domain of the given domain, host name, or URL, as
+/// entered by a user. Input without a scheme is taken to be a host
+/// name, optionally followed by a path. This is `None` if the input
+/// has no host.
+pub fn normalize(input: &str) -> Option<String> {
+    let input = input.trim();
+    [input.to_string(), format!("http://{}", input)]
+        .iter()
+        .filter_map(|url| Url::parse(url).ok())
+        .find_map(|url| url.host_str().map(registrable))
+        .filter(|domain| !domain.is_empty())
+}
+
+/// The unicode form of the given ASCII domain, for display.
+pub fn to_unicode(domain: &str) -> String {
+    let (unicode, result) = idna::domain_to_unicode(domain);
+    match result {
+        Ok(()) => unicode,
+        Err(_) => domain.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registrable() {
+        let cases = [
+            ("example.com", "example.com"),
+            ("www.example.com", "example.com"),
+            ("a.b.c.example.com", "example.com"),
+            ("EXAMPLE.com.", "example.com"),
+            // multi label public suffixes
+            ("blog.example.co.uk", "example.co.uk"),
+            ("example.co.uk", "example.co.uk"),
+            ("news.bbc.co.uk", "bbc.co.uk"),
+            ("www.city.kawasaki.jp", "city.kawasaki.jp"),
+            ("user.github.io", "user.github.io"),
+            ("a.user.github.io", "user.github.io"),
+            ("www.example.com.au", "example.com.au"),
+            // international domain names
+            ("www.bücher.de", "xn--bcher-kva.de"),
+            ("www.xn--bcher-kva.de", "xn--bcher-kva.de"),
+            // hosts without a registrable domain
+            ("co.uk", "co.uk"),
+            ("localhost", "localhost"),
+            ("127.0.0.1", "127.0.0.1"),
+            ("[::1]", "[::1]"),
+        ];
+        for (host, domain) in cases {
+            assert_eq!(registrable(host), domain, "{}", host);
+        }
+    }
+
+    #[test]
+    fn test_of_url() {
+        assert_eq!(of_url("https://blog.example.co.uk/post"), "example.co.uk");
+        assert_eq!(of_url("http://127.0.0.1:8080/page"), "127.0.0.1");
+        assert_eq!(
+            of_url("https://xn--bcher-kva.de/katalog"),
+            "xn--bcher-kva.de"
+        );
+        assert_eq!(of_url("not a url"), "");
+    }
+
+    #[test]
+    fn test_normalize() {
+        let cases = [
+            ("example.co.uk", Some("example.co.uk")),
+            ("  Blog.Example.co.uk  ", Some("example.co.uk")),
+            ("blog.example.co.uk/some/post", Some("example.co.uk")),
+            ("https://www.example.co.uk/post?q=1", Some("example.co.uk")),
+            ("localhost:8080", Some("localhost")),
+            ("bücher.de", Some("xn--bcher-kva.de")),
+            ("", None),
+            ("   ", None),
+            ("mailto:someone", None),
+        ];
+        for (input, domain) in cases {
+            assert_eq!(normalize(input).as_deref(), domain, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_to_unicode() {
+        assert_eq!(to_unicode("xn--bcher-kva.de"), "bücher.de");
+        assert_eq!(to_unicode("xn--r8jz45g.jp"), "例え.jp");
+        assert_eq!(to_unicode("example.co.uk"), "example.co.uk");
+    }
+}
@@ -0,0 +1,444 @@
+//! Unfurling of links to known providers, such as video hosts, into
+//! embeds shown on the discussion page. Embeds are described by the
+//! oEmbed responses of the providers, which can not be trusted to
+//! contain safe markup. The markup of an embed is therefore never
+//! taken from the response as is, but constructed from safe fields:
+//!
+//! - players are only embedded as an `iframe` whose source is an
+//!   `https` URL on one of the iframe hosts of the provider, and all
+//!   other markup and attributes of the response are dropped
+//! - providers without iframe hosts, or responses without a valid
+//!   player, are shown as a card linking to the page instead
+//!
+//! Providers are matched by the URL schemes of their endpoints, as in
+//! the [standard providers list](https://oembed.com/providers.json).
+//! Additional providers can be configured in the same format, see
+//! [`Config::oembed_providers`](crate::Config::oembed_providers).
+
+use crate::{fetch, Context};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// Maximum width requested from providers, and allowed for players.
+pub const MAX_WIDTH: i32 = 640;
+/// Maximum height requested from providers, and allowed for players.
+pub const MAX_HEIGHT: i32 = 480;
+
+/// A site which describes its pages in oEmbed responses.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Provider {
+    #[serde(rename = "provider_name")]
+    name: String,
+    endpoints: Vec<Endpoint>,
+    /// Hosts from which players of this provider may be embedded.
+    /// This extends the standard format of the providers list.
+    #[serde(default)]
+    iframe_hosts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Endpoint {
+    #[serde(default)]
+    schemes: Vec<String>,
+    url: String,
+}
+
+/// An embed constructed from the oEmbed response of a provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Embed {
+    pub provider: String,
+    pub html: String,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+}
+
+impl Provider {
+    /// A provider answering requests for URLs matching any of the
+    /// given schemes at the given endpoint. Schemes may contain `*`
+    /// wildcards, e.g. `https://vimeo.com/*`.
+    pub fn new(name: &str, schemes: &[&str], endpoint: &str, iframe_hosts: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            endpoints: vec![Endpoint {
+                schemes: schemes.iter().map(|scheme| scheme.to_string()).collect(),
+                url: endpoint.to_string(),
+            }],
+            iframe_hosts: iframe_hosts.iter().map(|host| host.to_string()).collect(),
+        }
+    }
+
+    /// Read providers from the given file, which contains a
+    /// list of providers in the format of the standard list.
+    pub fn load_all(path: &Path) -> Result<Vec<Self>> {
+        let file = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&file)?)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The endpoint answering requests for the given URL,
+    /// if it matches any of the schemes of this provider.
+    fn endpoint(&self, url: &str) -> Option<&str> {
+        self.endpoints
+            .iter()
+            .find(|endpoint| {
+                endpoint
+                    .schemes
+                    .iter()
+                    .any(|scheme| matches_scheme(scheme, url))
+            })
+            .map(|endpoint| endpoint.url.as_str())
+    }
+
+    /// Whether players of this provider may be
+    /// embedded from the given URL.
+    fn allows_iframe(&self, src: &reqwest::Url) -> bool {
+        src.scheme() == "https"
+            && src.username().is_empty()
+            && src.password().is_none()
+            && src.port().is_none()
+            && src.host_str().map_or(false, |host| {
+                self.iframe_hosts
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(host))
+            })
+    }
+}
+
+/// The built-in providers, taken from the standard providers list.
+pub fn default_providers() -> Vec<Provider> {
+    vec![
+        Provider::new(
+            "YouTube",
+            &[
+                "https://*.youtube.com/watch*",
+                "https://*.youtube.com/v/*",
+                "https://youtu.be/*",
+                "https://*.youtube.com/shorts/*",
+            ],
+            "https://www.youtube.com/oembed",
+            &["www.youtube.com", "www.youtube-nocookie.com"],
+        ),
+        Provider::new(
+            "Vimeo",
+            &[
+                "https://vimeo.com/*",
+                "https://vimeo.com/channels/*/*",
+                "https://player.vimeo.com/video/*",
+            ],
+            "https://vimeo.com/api/oembed.json",
+            &["player.vimeo.com"],
+        ),
+        // tweets are embedded with scripts, and are thus
+        // always shown as cards
+        Provider::new(
+            "Twitter",
+            &[
+                "https://twitter.com/*/status/*",
+                "https://*.twitter.com/*/status/*",
+                "https://x.com/*/status/*",
+            ],
+            "https://publish.twitter.com/oembed",
+            &[],
+        ),
+    ]
+}
+
+/// Whether the given URL matches the given scheme, where
+/// `*` matches any sequence of characters.
+fn matches_scheme(scheme: &str, url: &str) -> bool {
+    let mut parts = scheme.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match url.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+/// Escape the given text for use in HTML content and
+/// quoted attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The value of the attribute with the given name of the first
+/// `iframe` element in the given markup. Attribute values are
+/// returned as is, without resolving character references.
+fn iframe_attribute<'a>(html: &'a str, name: &str) -> Option<&'a str> {
+    let start = html.to_ascii_lowercase().find("<iframe")?;
+    let mut rest = &html[start + "<iframe".len()..];
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() || rest.starts_with('>') || rest.starts_with("/>") {
+            return None;
+        }
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>')
+            .unwrap_or(rest.len());
+        let attribute = &rest[..end];
+        rest = rest[end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (value, after) = match value.chars().next() {
+                    Some(quote @ '"') | Some(quote @ '\'') => {
+                        let end = value[1..].find(quote)? + 1;
+                        (&value[1..end], &value[end + 1..])
+                    }
+                    _ => {
+                        let end = value
+                            .find(|c: char| c.is_whitespace() || c == '>')
+                            .unwrap_or(value.len());
+                        (&value[..end], &value[end..])
+                    }
+                };
+                rest = after;
+                value
+            }
+            None => "",
+        };
+        if attribute.eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+    }
+}
+
+/// A dimension of the given response, which providers
+/// give as either a number or a string.
+fn dimension(response: &Value, key: &str, max: i32) -> Option<i32> {
+    let value = match &response[key] {
+        Value::Number(number) => number.as_i64()?,
+        Value::String(text) => text.trim().parse().ok()?,
+        _ => return None,
+    };
+    Some(value.clamp(1, max.into()) as i32)
+}
+
+/// Construct the embed of the given URL from the oEmbed
+/// response of the given provider.
+fn from_response(provider: &Provider, url: &str, response: &Value) -> Option<Embed> {
+    let width = dimension(response, "width", MAX_WIDTH);
+    let height = dimension(response, "height", MAX_HEIGHT);
+    let player = match response["type"].as_str() {
+        Some("video") | Some("rich") => response["html"]
+            .as_str()
+            .and_then(|html| iframe_attribute(html, "src"))
+            .and_then(|src| reqwest::Url::parse(&src.trim().replace("&amp;", "&")).ok())
+            .filter(|src| provider.allows_iframe(src)),
+        _ => None,
+    };
+    if let Some(src) = player {
+        let html = format!(
+            "<iframe src=\"{}\" width=\"{}\" height=\"{}\" frameborder=\"0\" \
+            allow=\"encrypted-media; fullscreen; picture-in-picture\" allowfullscreen \
+            loading=\"lazy\"></iframe>",
+            escape(src.as_str()),
+            width.unwrap_or(MAX_WIDTH),
+            height.unwrap_or(MAX_HEIGHT),
+        );
+        return Some(Embed {
+            provider: provider.name.clone(),
+            html,
+            width,
+            height,
+        });
+    }
+
+    let title = response["title"].as_str().map(str::trim).unwrap_or("");
+    let author = response["author_name"]
+        .as_str()
+        .map(str::trim)
+        .unwrap_or("");
+    if title.is_empty() && author.is_empty() {
+        return None;
+    }
+    let mut html = String::from("<blockquote class=\"embed\">");
+    if !title.is_empty() {
+        html.push_str(&format!("<p>{}</p>", escape(title)));
+    }
+    html.push_str(&format!(
+        "&mdash; <a href=\"{}\">{}</a></blockquote>",
+        escape(url),
+        escape(if author.is_empty() {
+            &provider.name
+        } else {
+            author
+        }),
+    ));
+    Some(Embed {
+        provider: provider.name.clone(),
+        html,
+        width: None,
+        height: None,
+    })
+}
+
+/// Unfurl the given URL using the configured providers. Returns
+/// `None` if no provider matches the URL, or if the response of the
+/// provider does not describe anything which can be embedded, and
+/// fails if the response can not be fetched.
+pub async fn unfurl(ctx: &Context, url: &str) -> Result<Option<Embed>> {
+    let link = reqwest::Url::parse(url)?;
+    if !matches!(link.scheme(), "http" | "https") {
+        return Ok(None);
+    }
+    let (provider, endpoint) = match ctx
+        .config()
+        .oembed_providers()
+        .iter()
+        .find_map(|provider| provider.endpoint(url).map(|endpoint| (provider, endpoint)))
+    {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+
+    let mut request = reqwest::Url::parse(&endpoint.replace("{format}", "json"))?;
+    request
+        .query_pairs_mut()
+        .append_pair("url", url)
+        .append_pair("format", "json")
+        .append_pair("maxwidth", &MAX_WIDTH.to_string())
+        .append_pair("maxheight", &MAX_HEIGHT.to_string());
+    let response = fetch::fetch_json(ctx, request.as_str()).await?;
+    if !response.is_object() {
+        return Err(anyhow!("Malformed oEmbed response from {}", provider.name));
+    }
+    Ok(from_response(provider, url, &response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_matches_scheme() {
+        let cases = [
+            (
+                "https://*.youtube.com/watch*",
+                "https://www.youtube.com/watch?v=1",
+                true,
+            ),
+            (
+                "https://*.youtube.com/watch*",
+                "https://youtube.com/watch?v=1",
+                false,
+            ),
+            ("https://youtu.be/*", "https://youtu.be/abc", true),
+            ("https://youtu.be/*", "http://youtu.be/abc", false),
+            (
+                "https://x.com/*/status/*",
+                "https://x.com/someone/status/1",
+                true,
+            ),
+            ("https://x.com/*/status/*", "https://x.com/someone", false),
+            ("https://vimeo.com/*", "https://vimeo.com/123", true),
+            (
+                "https://vimeo.com/*",
+                "https://vimeo.com.evil.example/1",
+                false,
+            ),
+            (
+                "https://example.com/video",
+                "https://example.com/video",
+                true,
+            ),
+            (
+                "https://example.com/video",
+                "https://example.com/video/2",
+                false,
+            ),
+        ];
+        for (scheme, url, matches) in cases {
+            assert_eq!(matches_scheme(scheme, url), matches, "{} {}", scheme, url);
+        }
+    }
+
+    #[test]
+    fn test_iframe_attribute() {
+        let html = "<div><IFRAME width=200 title='a \"player\"' src=\"https://a.example/1\" \
+            allowfullscreen></iframe></div>";
+        assert_eq!(iframe_attribute(html, "src"), Some("https://a.example/1"));
+        assert_eq!(iframe_attribute(html, "width"), Some("200"));
+        assert_eq!(iframe_attribute(html, "title"), Some("a \"player\""));
+        assert_eq!(iframe_attribute(html, "allowfullscreen"), Some(""));
+        assert_eq!(iframe_attribute(html, "height"), None);
+        assert_eq!(iframe_attribute("<p>no player</p>", "src"), None);
+        assert_eq!(iframe_attribute("<iframe src=\"unterminated", "src"), None);
+    }
+
+    #[test]
+    fn test_from_response() {
+        let provider = Provider::new("Video", &[], "", &["player.example"]);
+        let response = json!({
+            "type": "video",
+            "width": "1280",
+            "height": 360,
+            "html": "<iframe src=\"https://player.example/1?a=1&b=2\" onload=\"alert(1)\"></iframe>\
+                <script>alert(1)</script>",
+        });
+        let embed = from_response(&provider, "https://example.com/1", &response).unwrap();
+        assert_eq!(
+            embed.html,
+            "<iframe src=\"https://player.example/1?a=1&amp;b=2\" width=\"640\" height=\"360\" \
+            frameborder=\"0\" allow=\"encrypted-media; fullscreen; picture-in-picture\" \
+            allowfullscreen loading=\"lazy\"></iframe>"
+        );
+        assert_eq!((embed.width, embed.height), (Some(640), Some(360)));
+
+        // players on other hosts fall back to a card
+        for src in [
+            "http://player.example/1",
+            "https://evil.example/1",
+            "https://player.example.evil.example/1",
+            "https://user@player.example/1",
+            "javascript:alert(1)",
+        ] {
+            let response = json!({
+                "type": "rich",
+                "title": "<b>Title</b>",
+                "html": format!("<iframe src=\"{}\"></iframe>", src),
+            });
+            let embed = from_response(&provider, "https://example.com/1", &response).unwrap();
+            assert_eq!(
+                embed.html,
+                "<blockquote class=\"embed\"><p>&lt;b&gt;Title&lt;/b&gt;</p>&mdash; \
+                <a href=\"https://example.com/1\">Video</a></blockquote>",
+                "{}",
+                src
+            );
+        }
+
+        let response = json!({ "type": "photo", "url": "https://player.example/1.png" });
+        assert_eq!(
+            from_response(&provider, "https://example.com/1", &response),
+            None
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use crate::validation::FieldViolation;
+use chrono::Duration;
+use juniper::{graphql_value, FieldError, IntoFieldError, Object, ScalarValue, Value};
+use std::fmt;
+
+/// Error returned when an action was attempted too often
+/// within some time window. The `scope` identifies which
+/// limit was hit, and `retry_after` how long the client
+/// should wait before attempting the action again.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimited {
+    pub scope: &'static str,
+    pub retry_after: Duration,
+}
+
+impl RateLimited {
+    pub fn new(scope: &'static str, retry_after: Duration) -> Self {
+        Self { scope, retry_after }
+    }
+
+    /// Number of whole seconds the client should wait
+    /// before retrying. This is always at least one.
+    pub fn retry_after_secs(&self) -> i64 {
+        self.retry_after.num_seconds().max(1)
+    }
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Rate limit exceeded, try again in {} seconds",
+            self.retry_after_secs()
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+impl<S: ScalarValue> IntoFieldError<S> for RateLimited {
+    fn into_field_error(self) -> FieldError<S> {
+        let scope = self.scope;
+        let retry_after = self.retry_after_secs() as i32;
+        FieldError::new(
+            self,
+            graphql_value!({
+                "code": "RATE_LIMITED",
+                "scope": scope,
+                "retryAfter": retry_after,
+            }),
+        )
+    }
+}
+
+/// Error returned when attempting to register with an
+/// email address whose domain is on the blocklist.
+#[derive(Debug, Clone)]
+pub struct BlockedEmailDomain {
+    pub domain: String,
+}
+
+impl fmt::Display for BlockedEmailDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Email addresses from {} can not be used to register",
+            self.domain
+        )
+    }
+}
+
+impl std::error::Error for BlockedEmailDomain {}
+
+impl<S: ScalarValue> IntoFieldError<S> for BlockedEmailDomain {
+    fn into_field_error(self) -> FieldError<S> {
+        let domain = self.domain.clone();
+        FieldError::new(
+            self,
+            graphql_value!({
+                "code": "EMAIL_DOMAIN_BLOCKED",
+                "domain": domain,
+            }),
+        )
+    }
+}
+
+/// Error returned when attempting to edit content which the
+/// viewer may not edit, either because they are not the author
+/// or because the edit window has closed.
+#[derive(Debug, Clone, Copy)]
+pub struct EditNotAllowed {
+    pub reason: EditNotAllowedReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditNotAllowedReason {
+    NotAuthor,
+    WindowClosed,
+}
+
+impl EditNotAllowed {
+    pub fn new(reason: EditNotAllowedReason) -> Self {
+        Self { reason }
+    }
+}
+
+impl fmt::Display for EditNotAllowed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            EditNotAllowedReason::NotAuthor => write!(f, "Only the author can edit this"),
+            EditNotAllowedReason::WindowClosed => write!(f, "This can no longer be edited"),
+        }
+    }
+}
+
+impl std::error::Error for EditNotAllowed {}
+
+impl<S: ScalarValue> IntoFieldError<S> for EditNotAllowed {
+    fn into_field_error(self) -> FieldError<S> {
+        let reason = match self.reason {
+            EditNotAllowedReason::NotAuthor => "NOT_AUTHOR",
+            EditNotAllowedReason::WindowClosed => "WINDOW_CLOSED",
+        };
+        FieldError::new(
+            self,
+            graphql_value!({
+                "code": "EDIT_NOT_ALLOWED",
+                "reason": reason,
+            }),
+        )
+    }
+}
+
+/// Error returned when attempting to comment, vote or edit in
+/// the discussion of a submission which a moderator locked.
+#[derive(Debug, Clone, Copy)]
+pub struct Locked;
+
+impl fmt::Display for Locked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "This discussion was locked by a moderator")
+    }
+}
+
+impl std::error::Error for Locked {}
+
+impl<S: ScalarValue> IntoFieldError<S> for Locked {
+    fn into_field_error(self) -> FieldError<S> {
+        FieldError::new(self, graphql_value!({ "code": "LOCKED" }))
+    }
+}
+
+/// Error returned when a submitted URL is refused, see
+/// [`link::check`](crate::link::check) for which URLs are accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidUrl {
+    Malformed,
+    TooLong,
+    Scheme,
+    Userinfo,
+    PrivateHost,
+    IpAddress,
+}
+
+impl InvalidUrl {
+    /// The error code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            InvalidUrl::Malformed => "URL_MALFORMED",
+            InvalidUrl::TooLong => "URL_TOO_LONG",
+            InvalidUrl::Scheme => "URL_SCHEME_NOT_ALLOWED",
+            InvalidUrl::Userinfo => "URL_USERINFO_NOT_ALLOWED",
+            InvalidUrl::PrivateHost => "URL_PRIVATE_HOST",
+            InvalidUrl::IpAddress => "URL_IP_ADDRESS",
+        }
+    }
+}
+
+impl fmt::Display for InvalidUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidUrl::Malformed => write!(f, "Please submit a valid URL"),
+            InvalidUrl::TooLong => write!(
+                f,
+                "URLs can be at most {} characters long",
+                crate::link::MAX_URL_LEN
+            ),
+            InvalidUrl::Scheme => write!(f, "Only http and https URLs can be submitted"),
+            InvalidUrl::Userinfo => {
+                write!(f, "URLs with a user name or password can not be submitted")
+            }
+            InvalidUrl::PrivateHost => write!(f, "URLs on private networks can not be submitted"),
+            InvalidUrl::IpAddress => write!(f, "URLs with an IP address can not be submitted"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidUrl {}
+
+impl<S: ScalarValue> IntoFieldError<S> for InvalidUrl {
+    fn into_field_error(self) -> FieldError<S> {
+        let code = self.code();
+        FieldError::new(self, graphql_value!({ "code": code }))
+    }
+}
+
+/// Error with a stable error code, which clients can rely on instead
+/// of matching on the error message. Errors of the models convert into
+/// this, keeping the errors known to have a code. Other errors, such as
+/// database errors, are reported as `INTERNAL`, and their details are
+/// only logged on the server. GraphQL resolvers return this, such that
+/// no error reaches clients without a code.
+#[derive(Debug)]
+pub enum AppError {
+    /// The requested object does not exist.
+    NotFound(String),
+    /// The request requires a valid login.
+    Unauthenticated(String),
+    /// The viewer may not perform the request.
+    PermissionDenied(String),
+    /// The input of the request is invalid.
+    ValidationFailed(String),
+    /// Input fields of the request violate their constraints,
+    /// see [`Validator`](crate::validation::Validator).
+    InvalidInput(Vec<FieldViolation>),
+    /// The request conflicts with existing data, e.g. an
+    /// email address which is already in use.
+    Conflict(String),
+    RateLimited(RateLimited),
+    EmailDomainBlocked(BlockedEmailDomain),
+    EditNotAllowed(EditNotAllowed),
+    Locked(Locked),
+    InvalidUrl(InvalidUrl),
+    Internal(anyhow::Error),
+}
+
+impl AppError {
+    /// The error code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::Unauthenticated(_) => "UNAUTHENTICATED",
+            AppError::PermissionDenied(_) => "PERMISSION_DENIED",
+            AppError::ValidationFailed(_) | AppError::InvalidInput(_) => "VALIDATION_FAILED",
+            AppError::Conflict(_) => "CONFLICT",
+            AppError::RateLimited(_) => "RATE_LIMITED",
+            AppError::EmailDomainBlocked(_) => "EMAIL_DOMAIN_BLOCKED",
+            AppError::EditNotAllowed(_) => "EDIT_NOT_ALLOWED",
+            AppError::Locked(_) => "LOCKED",
+            AppError::InvalidUrl(invalid) => invalid.code(),
+            AppError::Internal(_) => "INTERNAL",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Unauthenticated(message)
+            | AppError::PermissionDenied(message)
+            | AppError::ValidationFailed(message)
+            | AppError::Conflict(message) => write!(f, "{}", message),
+            AppError::InvalidInput(violations) => {
+                let messages: Vec<&str> = violations
+                    .iter()
+                    .map(|violation| violation.message.as_str())
+                    .collect();
+                write!(f, "{}", messages.join("; "))
+            }
+            AppError::RateLimited(rate_limited) => write!(f, "{}", rate_limited),
+            AppError::EmailDomainBlocked(blocked) => write!(f, "{}", blocked),
+            AppError::EditNotAllowed(not_allowed) => write!(f, "{}", not_allowed),
+            AppError::Locked(locked) => write!(f, "{}", locked),
+            AppError::InvalidUrl(invalid) => write!(f, "{}", invalid),
+            AppError::Internal(_) => write!(f, "Internal error"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Internal(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<AppError>() {
+            Ok(app_error) => return app_error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<RateLimited>() {
+            Ok(rate_limited) => return AppError::RateLimited(rate_limited),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<BlockedEmailDomain>() {
+            Ok(blocked) => return AppError::EmailDomainBlocked(blocked),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<EditNotAllowed>() {
+            Ok(not_allowed) => return AppError::EditNotAllowed(not_allowed),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<Locked>() {
+            Ok(locked) => return AppError::Locked(locked),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<InvalidUrl>() {
+            Ok(invalid) => return AppError::InvalidUrl(invalid),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<validator::ValidationErrors>() {
+            Ok(errors) => return errors.into(),
+            Err(error) => error,
+        };
+        match error.downcast_ref::<diesel::result::Error>() {
+            Some(diesel::result::Error::NotFound) => AppError::NotFound("Not found".into()),
+            _ => AppError::Internal(error),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for AppError {
+    fn from(error: diesel::result::Error) -> Self {
+        anyhow::Error::from(error).into()
+    }
+}
+
+impl From<std::num::TryFromIntError> for AppError {
+    fn from(error: std::num::TryFromIntError) -> Self {
+        AppError::Internal(error.into())
+    }
+}
+
+impl From<validator::ValidationErrors> for AppError {
+    fn from(errors: validator::ValidationErrors) -> Self {
+        AppError::ValidationFailed(errors.to_string())
+    }
+}
+
+impl<S: ScalarValue> IntoFieldError<S> for AppError {
+    fn into_field_error(self) -> FieldError<S> {
+        match self {
+            AppError::RateLimited(rate_limited) => rate_limited.into_field_error(),
+            AppError::EmailDomainBlocked(blocked) => blocked.into_field_error(),
+            AppError::EditNotAllowed(not_allowed) => not_allowed.into_field_error(),
+            AppError::Locked(locked) => locked.into_field_error(),
+            AppError::InvalidUrl(invalid) => invalid.into_field_error(),
+            AppError::Internal(error) => {
+                log::error!("Internal error: {:#}", error);
+                FieldError::new("Internal error", graphql_value!({ "code": "INTERNAL" }))
+            }
+            AppError::InvalidInput(ref violations) => {
+                let field_errors = violations.iter().map(field_violation).collect();
+                let mut extensions = Object::with_capacity(2);
+                extensions.add_field("code", Value::from(self.code()));
+                extensions.add_field("fieldErrors", Value::list(field_errors));
+                FieldError::new(self, Value::object(extensions))
+            }
+            error => {
+                let code = error.code();
+                FieldError::new(error, graphql_value!({ "code": code }))
+            }
+        }
+    }
+}
+
+/// The `fieldErrors` entry of the given violation.
+fn field_violation<S: ScalarValue>(violation: &FieldViolation) -> Value<S> {
+    let path = violation
+        .path
+        .iter()
+        .map(|segment| Value::scalar(segment.clone()));
+    let mut error = Object::with_capacity(4);
+    error.add_field("path", Value::list(path.collect()));
+    error.add_field("code", Value::scalar(violation.code.clone()));
+    error.add_field("message", Value::scalar(violation.message.clone()));
+    if let Some(limit) = violation.limit {
+        error.add_field("limit", Value::scalar(limit as i32));
+    }
+    Value::object(error)
+}
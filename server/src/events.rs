@@ -0,0 +1,77 @@
+//! In-process bus of events about new content, which GraphQL subscriptions
+//! follow. Events are published when content becomes public, i.e. at the
+//! same time as webhooks are notified, and only carry the IDs of the content,
+//! which every subscriber looks up on its own behalf, such that each only
+//! receives what it may see. Events are kept per server process, and
+//! subscribers which fall too far behind miss some of them.
+
+use crate::db::id::{CommentID, UrlID};
+use futures_util::stream::{self, Stream};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Number of events kept for subscribers
+/// which did not receive them yet.
+const CAPACITY: usize = 1_024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A submission was published.
+    UrlSubmitted(UrlID),
+    /// A comment was published on the given submission.
+    CommentAdded(CommentID, UrlID),
+}
+
+/// The bus events are published on, shared by all requests.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl EventBus {
+    /// Publish the given event to all current subscribers.
+    pub fn publish(&self, event: Event) {
+        // this only fails if there are no subscribers
+        let _ = self.sender.send(event);
+    }
+
+    /// The events published from now on.
+    pub fn subscribe(&self) -> impl Stream<Item = Event> + Send + 'static {
+        stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(missed)) => {
+                        log::warn!("Subscriber missed {} events", missed)
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn test_subscribers_receive_later_events() {
+        let bus = EventBus::default();
+        let url = UrlID::new();
+        bus.publish(Event::UrlSubmitted(UrlID::new()));
+        let events = bus.subscribe();
+        bus.publish(Event::UrlSubmitted(url));
+        drop(bus);
+        let events: Vec<Event> = events.collect().await;
+        assert_eq!(events, vec![Event::UrlSubmitted(url)]);
+    }
+}
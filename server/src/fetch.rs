@@ -0,0 +1,395 @@
+//! Fetching of submitted pages to extract their meta data, and
+//! checking whether submitted links still work. Since
+//! the fetched URLs are provided by users, the fetcher is careful
+//! to not be abused:
+//!
+//! - only the first [`MAX_BODY_BYTES`] of a page are read, and
+//!   images larger than [`MAX_IMAGE_BYTES`] and JSON documents
+//!   larger than [`MAX_JSON_BYTES`] are refused
+//! - at most [`MAX_REDIRECTS`] redirects are followed
+//! - hosts resolving to private network addresses are refused
+//!   (unless configured otherwise), and connections are pinned to
+//!   the checked address to avoid DNS rebinding
+//! - at most [`MAX_FETCHES_PER_HOST`] fetches run concurrently
+//!   for any given host
+//! - the whole fetch is bounded by the configured timeout
+//!
+//! The same restrictions apply when delivering webhooks, see
+//! [`post_json`].
+
+use crate::Context;
+use anyhow::{anyhow, Result};
+use futures_util::StreamExt;
+use meta_parser::Meta;
+use once_cell::sync::Lazy;
+use reqwest::{Method, StatusCode, Url};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Maximum number of bytes read from a response.
+pub const MAX_BODY_BYTES: usize = 512 * 1024;
+/// Maximum size of a fetched image in bytes.
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+/// Maximum size of a fetched JSON document in bytes.
+pub const MAX_JSON_BYTES: usize = 64 * 1024;
+/// Maximum number of redirects followed for a single fetch.
+pub const MAX_REDIRECTS: usize = 5;
+/// Maximum number of concurrent fetches for a single host.
+pub const MAX_FETCHES_PER_HOST: usize = 2;
+
+static HOST_LIMITS: Lazy<Mutex<HashMap<String, Arc<Semaphore>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A fetched page and the meta data found in it.
+#[derive(Debug)]
+pub struct FetchedPage {
+    /// URL of the final response, after following redirects.
+    pub url: Url,
+    pub status: StatusCode,
+    pub meta: Meta,
+}
+
+/// The response found when checking a link.
+#[derive(Debug)]
+pub struct CheckedLink {
+    /// Status of the final response.
+    pub status: StatusCode,
+    /// Whether any redirects were followed.
+    pub redirected: bool,
+}
+
+/// Determine if the given address is reachable on the
+/// public internet.
+pub(crate) fn is_public_address(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => {
+            let [a, b, ..] = addr.octets();
+            !(addr.is_private()
+                || addr.is_loopback()
+                || addr.is_link_local()
+                || addr.is_broadcast()
+                || addr.is_documentation()
+                || addr.is_unspecified()
+                || addr.is_multicast()
+                // shared address space (RFC 6598)
+                || (a == 100 && (64..128).contains(&b))
+                // reserved for future use
+                || a >= 240
+                || a == 0)
+        }
+        IpAddr::V6(addr) => {
+            if let Some(mapped) = addr.to_ipv4() {
+                if addr.segments()[..5] == [0; 5] {
+                    return is_public_address(IpAddr::V4(mapped));
+                }
+            }
+            let first = addr.segments()[0];
+            !(addr.is_loopback()
+                || addr.is_unspecified()
+                || addr.is_multicast()
+                // unique local addresses
+                || (first & 0xfe00) == 0xfc00
+                // link local addresses
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Resolve the host of the given URL to an address
+/// which may be fetched.
+async fn resolve(ctx: &Context, url: &Url) -> Result<SocketAddr> {
+    let host = url.host_str().ok_or_else(|| anyhow!("Malformed URL"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("Malformed URL"))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    if addrs.is_empty() {
+        return Err(anyhow!("Failed to resolve {}", host));
+    }
+    if !ctx.config().fetch_private_addresses()
+        && addrs.iter().any(|addr| !is_public_address(addr.ip()))
+    {
+        return Err(anyhow!("Refusing to fetch private address of {}", host));
+    }
+    Ok(addrs[0])
+}
+
+/// Check that the host of the given URL may be fetched, e.g.
+/// before handing the URL to another service which fetches
+/// it on our behalf.
+pub async fn check_fetchable(ctx: &Context, url: &str) -> Result<()> {
+    let url = Url::parse(url)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!("Refusing to fetch {} URL", url.scheme()));
+    }
+    resolve(ctx, &url).await?;
+    Ok(())
+}
+
+/// Wait until a fetch for the given host may start.
+async fn acquire_host(host: &str) -> Result<OwnedSemaphorePermit> {
+    let limit = {
+        let mut hosts = HOST_LIMITS.lock().unwrap();
+        // forget hosts without running fetches
+        hosts.retain(|_, limit| Arc::strong_count(limit) > 1);
+        hosts
+            .entry(host.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(MAX_FETCHES_PER_HOST)))
+            .clone()
+    };
+    Ok(limit.acquire_owned().await?)
+}
+
+/// Send a request for the given URL, following redirects. The
+/// returned permit counts towards the limit of the host, and must
+/// be held until the response body was read.
+async fn send(
+    ctx: &Context,
+    method: Method,
+    url: &str,
+) -> Result<(reqwest::Response, OwnedSemaphorePermit)> {
+    let mut url = Url::parse(url)?;
+    for _ in 0..=MAX_REDIRECTS {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow!("Refusing to fetch {} URL", url.scheme()));
+        }
+        let host = url.host_str().unwrap_or_default().to_string();
+        let permit = acquire_host(&host).await?;
+        let addr = resolve(ctx, &url).await?;
+        let client = reqwest::Client::builder()
+            .user_agent("Mozilla/5.0 (compatible; Urlsbot/0.1.0; +https://urls.fyi/bot.html)")
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve(&host, addr)
+            .gzip(true)
+            .brotli(true)
+            .build()?;
+        let resp = client.request(method.clone(), url.clone()).send().await?;
+
+        let status = resp.status();
+        if status.is_redirection() {
+            let location = resp
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or_else(|| anyhow!("Redirect without location"))?;
+            url = url.join(location)?;
+            continue;
+        }
+        return Ok((resp, permit));
+    }
+    Err(anyhow!("Too many redirects"))
+}
+
+async fn fetch_unbounded(ctx: &Context, url: &str) -> Result<FetchedPage> {
+    let (resp, _permit) = send(ctx, Method::GET, url).await?;
+    let url = resp.url().clone();
+    let status = resp.status();
+    let mut meta = Meta::new();
+    let mut read = 0;
+    let mut stream = resp.bytes_stream();
+    while let Some(part) = stream.next().await {
+        let part = part?;
+        let remaining = MAX_BODY_BYTES - read;
+        meta.parse(&part[..part.len().min(remaining)]);
+        read += part.len().min(remaining);
+        if read >= MAX_BODY_BYTES {
+            break;
+        }
+    }
+    Ok(FetchedPage { url, status, meta })
+}
+
+async fn fetch_image_unbounded(ctx: &Context, url: &str) -> Result<Vec<u8>> {
+    let (resp, _permit) = send(ctx, Method::GET, url).await?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("Fetching image failed with {}", resp.status()));
+    }
+    let is_image = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .map_or(false, |content_type| content_type.starts_with("image/"));
+    if !is_image {
+        return Err(anyhow!("Refusing to fetch response which is not an image"));
+    }
+    if resp
+        .content_length()
+        .map_or(false, |len| len > MAX_IMAGE_BYTES as u64)
+    {
+        return Err(anyhow!(
+            "Refusing to fetch image larger than {} bytes",
+            MAX_IMAGE_BYTES
+        ));
+    }
+
+    // the length may be missing or wrong, so the body
+    // is checked as it is read
+    let mut data = vec![];
+    let mut stream = resp.bytes_stream();
+    while let Some(part) = stream.next().await {
+        let part = part?;
+        if data.len() + part.len() > MAX_IMAGE_BYTES {
+            return Err(anyhow!(
+                "Refusing to fetch image larger than {} bytes",
+                MAX_IMAGE_BYTES
+            ));
+        }
+        data.extend_from_slice(&part);
+    }
+    Ok(data)
+}
+
+async fn fetch_json_unbounded(ctx: &Context, url: &str) -> Result<serde_json::Value> {
+    let (resp, _permit) = send(ctx, Method::GET, url).await?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("Fetching JSON failed with {}", resp.status()));
+    }
+    let mut data = vec![];
+    let mut stream = resp.bytes_stream();
+    while let Some(part) = stream.next().await {
+        let part = part?;
+        if data.len() + part.len() > MAX_JSON_BYTES {
+            return Err(anyhow!(
+                "Refusing to fetch JSON larger than {} bytes",
+                MAX_JSON_BYTES
+            ));
+        }
+        data.extend_from_slice(&part);
+    }
+    Ok(serde_json::from_slice(&data)?)
+}
+
+async fn check_unbounded(ctx: &Context, url: &str) -> Result<CheckedLink> {
+    let (resp, permit) = send(ctx, Method::HEAD, url).await?;
+    // not all servers answer HEAD requests, the body
+    // of the GET response is never read
+    let resp = match resp.status() {
+        StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => {
+            drop(permit);
+            send(ctx, Method::GET, url).await?.0
+        }
+        _ => resp,
+    };
+    Ok(CheckedLink {
+        status: resp.status(),
+        redirected: resp.url() != &Url::parse(url)?,
+    })
+}
+
+async fn post_json_unbounded(
+    ctx: &Context,
+    url: &str,
+    headers: &[(&str, String)],
+    body: Vec<u8>,
+) -> Result<StatusCode> {
+    let url = Url::parse(url)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!("Refusing to fetch {} URL", url.scheme()));
+    }
+    let host = url.host_str().unwrap_or_default().to_string();
+    let _permit = acquire_host(&host).await?;
+    let addr = resolve(ctx, &url).await?;
+    let client = reqwest::Client::builder()
+        .user_agent("Urlsbot-Webhooks/0.1.0 (+https://urls.fyi/bot.html)")
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve(&host, addr)
+        .build()?;
+    let mut request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+    for (name, value) in headers {
+        request = request.header(*name, value);
+    }
+    Ok(request.body(body).send().await?.status())
+}
+
+/// Post the given JSON body to the given URL, returning the status
+/// of the response. Redirects are not followed, and the body of the
+/// response is never read. This is otherwise subject to the same
+/// restrictions as [`fetch_page`].
+pub async fn post_json(
+    ctx: &Context,
+    url: &str,
+    headers: &[(&str, String)],
+    body: Vec<u8>,
+) -> Result<StatusCode> {
+    tokio::time::timeout(
+        ctx.config().fetch_timeout(),
+        post_json_unbounded(ctx, url, headers, body),
+    )
+    .await
+    .map_err(|_| anyhow!("Timed out posting to {}", url))?
+}
+
+/// Fetch the given URL and extract the meta data of the page. This
+/// fails if the page can not be fetched within the configured
+/// timeout, or if any of the restrictions described in the
+/// [module documentation](self) are violated.
+pub async fn fetch_page(ctx: &Context, url: &str) -> Result<FetchedPage> {
+    tokio::time::timeout(ctx.config().fetch_timeout(), fetch_unbounded(ctx, url))
+        .await
+        .map_err(|_| anyhow!("Timed out fetching {}", url))?
+}
+
+/// Fetch the image at the given URL, returning its raw bytes. This
+/// fails if the response is not an image, or larger than
+/// [`MAX_IMAGE_BYTES`], and is otherwise subject to the same
+/// restrictions as [`fetch_page`].
+pub async fn fetch_image(ctx: &Context, url: &str) -> Result<Vec<u8>> {
+    tokio::time::timeout(
+        ctx.config().fetch_timeout(),
+        fetch_image_unbounded(ctx, url),
+    )
+    .await
+    .map_err(|_| anyhow!("Timed out fetching {}", url))?
+}
+
+/// Fetch the JSON document at the given URL, e.g. an API response.
+/// This fails if the response is not successful, not valid JSON, or
+/// larger than [`MAX_JSON_BYTES`], and is otherwise subject to the
+/// same restrictions as [`fetch_page`].
+pub async fn fetch_json(ctx: &Context, url: &str) -> Result<serde_json::Value> {
+    tokio::time::timeout(ctx.config().fetch_timeout(), fetch_json_unbounded(ctx, url))
+        .await
+        .map_err(|_| anyhow!("Timed out fetching {}", url))?
+}
+
+/// Check whether the given URL still works, returning the final
+/// response after following redirects. This is subject to the same
+/// restrictions as [`fetch_page`], but doesn't read the body.
+pub async fn check_link(ctx: &Context, url: &str) -> Result<CheckedLink> {
+    tokio::time::timeout(ctx.config().fetch_timeout(), check_unbounded(ctx, url))
+        .await
+        .map_err(|_| anyhow!("Timed out checking {}", url))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_public_address() {
+        let cases = [
+            ("93.184.216.34", true),
+            ("2606:2800:220:1:248:1893:25c8:1946", true),
+            ("127.0.0.1", false),
+            ("10.1.2.3", false),
+            ("172.16.0.1", false),
+            ("192.168.1.1", false),
+            ("169.254.169.254", false),
+            ("100.64.0.1", false),
+            ("0.0.0.0", false),
+            ("::1", false),
+            ("fd00::1", false),
+            ("fe80::1", false),
+            ("::ffff:127.0.0.1", false),
+            ("::ffff:93.184.216.34", true),
+        ];
+        for (addr, public) in cases {
+            assert_eq!(is_public_address(addr.parse().unwrap()), public, "{}", addr);
+        }
+    }
+}
@@ -0,0 +1,550 @@
+//! Limits on the depth and complexity of GraphQL queries, which are
+//! checked before a request is executed, such that a single request
+//! can not make the server load an unbounded amount of data.
+//!
+//! The depth of a query is how deeply its fields are nested. Its
+//! complexity estimates how many values resolving it produces: every
+//! field costs one, plus what its selections cost. Connections produce
+//! their selections once per item, so their selections cost as much
+//! times the requested page size, or [`MAX_PAGE_SIZE`] times if no
+//! page size is given. Fields which take a `first` or `last` argument,
+//! or select `edges` or `nodes`, are connections. Page sizes given by
+//! variables which are missing from the request take their default.
+//!
+//! Introspection only reads the schema, and is exempt from both, but
+//! its nesting is limited to [`MAX_INTROSPECTION_DEPTH`] levels.
+
+use super::Schema;
+use juniper::parser::parse_document_source;
+use juniper::{DefaultScalarValue, Definition, InputValue, Selection};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Page size assumed for connections which don't specify one.
+pub const MAX_PAGE_SIZE: u64 = 100;
+
+/// Deepest nesting allowed within introspection fields, enough
+/// for the introspection queries of common GraphQL clients.
+pub const MAX_INTROSPECTION_DEPTH: usize = 16;
+
+/// Why a request was rejected before executing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejected {
+    Unparsable(String),
+    TooDeep { depth: usize, max: usize },
+    TooComplex { complexity: u64, max: u64 },
+    IntrospectionTooDeep { depth: usize, max: usize },
+}
+
+impl Rejected {
+    /// The error extensions describing this rejection.
+    pub fn extensions(&self) -> Value {
+        match *self {
+            Rejected::Unparsable(_) => json!({ "code": "BAD_REQUEST" }),
+            Rejected::TooDeep { depth, max } | Rejected::IntrospectionTooDeep { depth, max } => {
+                json!({
+                    "code": "QUERY_TOO_DEEP",
+                    "depth": depth,
+                    "maxDepth": max,
+                })
+            }
+            Rejected::TooComplex { complexity, max } => json!({
+                "code": "QUERY_TOO_COMPLEX",
+                "complexity": complexity,
+                "maxComplexity": max,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejected::Unparsable(err) => write!(f, "Invalid GraphQL document: {}", err),
+            Rejected::TooDeep { depth, max } => write!(
+                f,
+                "Query depth of {} exceeds the maximum depth of {}",
+                depth, max
+            ),
+            Rejected::TooComplex { complexity, max } => write!(
+                f,
+                "Query complexity of {} exceeds the maximum complexity of {}",
+                complexity, max
+            ),
+            Rejected::IntrospectionTooDeep { depth, max } => write!(
+                f,
+                "Introspection depth of {} exceeds the maximum depth of {}",
+                depth, max
+            ),
+        }
+    }
+}
+
+/// The depth and complexity of a selection set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Cost {
+    depth: usize,
+    complexity: u64,
+    introspection_depth: usize,
+}
+
+impl Cost {
+    /// The cost of two selections of the same selection set.
+    fn and(self, other: Cost) -> Cost {
+        Cost {
+            depth: self.depth.max(other.depth),
+            complexity: self.complexity.saturating_add(other.complexity),
+            introspection_depth: self.introspection_depth.max(other.introspection_depth),
+        }
+    }
+}
+
+type Selections<'a> = [Selection<'a, DefaultScalarValue>];
+
+/// The value of an integer literal.
+fn int(value: &InputValue<DefaultScalarValue>) -> Option<i64> {
+    match value {
+        InputValue::Scalar(DefaultScalarValue::Int(int)) => Some(i64::from(*int)),
+        _ => None,
+    }
+}
+
+/// Whether the given selection selects the items of a connection.
+fn is_page(selection: &Selection<DefaultScalarValue>) -> bool {
+    match selection {
+        Selection::Field(field) => matches!(field.item.name.item, "edges" | "nodes"),
+        _ => false,
+    }
+}
+
+/// Computes the cost of the selections of an operation, remembering
+/// the cost of fragments, which only depends on the variables.
+struct Coster<'a> {
+    fragments: &'a HashMap<&'a str, &'a Selections<'a>>,
+    /// The integer value of each variable of the operation, from
+    /// the request or else from its default.
+    variables: HashMap<&'a str, Option<i64>>,
+    costs: HashMap<&'a str, Cost>,
+    visiting: Vec<&'a str>,
+}
+
+impl<'a> Coster<'a> {
+    fn page_size(&self, value: &InputValue<DefaultScalarValue>) -> u64 {
+        let size = match value {
+            InputValue::Variable(name) => self.variables.get(name.as_str()).copied().flatten(),
+            value => int(value),
+        };
+        size.map_or(MAX_PAGE_SIZE, |size| size.max(0) as u64)
+    }
+
+    fn selections(&mut self, selections: &'a Selections<'a>) -> Cost {
+        selections.iter().fold(Cost::default(), |cost, selection| {
+            cost.and(self.selection(selection))
+        })
+    }
+
+    fn selection(&mut self, selection: &'a Selection<'a, DefaultScalarValue>) -> Cost {
+        match selection {
+            Selection::Field(field) => {
+                let field = &field.item;
+                let selections = field.selection_set.as_deref().unwrap_or(&[]);
+                let children = self.selections(selections);
+                let name = field.name.item;
+                if name == "__typename" {
+                    return Cost::default();
+                }
+                if name.starts_with("__") {
+                    return Cost {
+                        introspection_depth: children.depth.max(children.introspection_depth) + 1,
+                        ..Cost::default()
+                    };
+                }
+                let page_sizes: Vec<u64> = field
+                    .arguments
+                    .iter()
+                    .flat_map(|arguments| arguments.item.items.iter())
+                    .filter(|(argument, _)| matches!(argument.item, "first" | "last"))
+                    .map(|(_, value)| self.page_size(&value.item))
+                    .collect();
+                let connection = !page_sizes.is_empty() || selections.iter().any(is_page);
+                let items = if connection {
+                    page_sizes.into_iter().max().unwrap_or(MAX_PAGE_SIZE)
+                } else {
+                    1
+                };
+                Cost {
+                    depth: children.depth + 1,
+                    complexity: items.saturating_mul(children.complexity).saturating_add(1),
+                    introspection_depth: children.introspection_depth,
+                }
+            }
+            Selection::InlineFragment(fragment) => self.selections(&fragment.item.selection_set),
+            Selection::FragmentSpread(spread) => {
+                let name = spread.item.name.item;
+                if let Some(cost) = self.costs.get(name) {
+                    return *cost;
+                }
+                let selections = match self.fragments.get(name) {
+                    // cycles are rejected when executing the query
+                    Some(_) if self.visiting.contains(&name) => return Cost::default(),
+                    Some(selections) => *selections,
+                    None => return Cost::default(),
+                };
+                self.visiting.push(name);
+                let cost = self.selections(selections);
+                self.visiting.pop();
+                self.costs.insert(name, cost);
+                cost
+            }
+        }
+    }
+}
+
+/// The cost of each operation of the given GraphQL query, or only of
+/// the operation with the given name. Variables which are not given
+/// take the default value of their definition.
+fn costs(
+    schema: &Schema,
+    query: &str,
+    operation_name: Option<&str>,
+    variables: &Map<String, Value>,
+) -> Result<Vec<Cost>, Rejected> {
+    let document = parse_document_source(query, &schema.schema)
+        .map_err(|err| Rejected::Unparsable(err.item.to_string()))?;
+    let mut operations = vec![];
+    let mut fragments = HashMap::new();
+    for definition in &document {
+        match definition {
+            Definition::Operation(operation) => operations.push(&operation.item),
+            Definition::Fragment(fragment) => {
+                let fragment = &fragment.item;
+                fragments.insert(fragment.name.item, &fragment.selection_set[..]);
+            }
+        }
+    }
+    let costs = operations
+        .into_iter()
+        .filter(|operation| {
+            operation_name.is_none()
+                || operation.name.as_ref().map(|name| name.item) == operation_name
+        })
+        .map(|operation| {
+            let values = operation
+                .variable_definitions
+                .iter()
+                .flat_map(|definitions| definitions.item.items.iter())
+                .map(|(name, definition)| {
+                    let value = match variables.get(name.item) {
+                        Some(value) => value.as_i64(),
+                        None => definition
+                            .default_value
+                            .as_ref()
+                            .and_then(|default| int(&default.item)),
+                    };
+                    (name.item, value)
+                })
+                .collect();
+            Coster {
+                fragments: &fragments,
+                variables: values,
+                costs: HashMap::new(),
+                visiting: vec![],
+            }
+            .selections(&operation.selection_set)
+        })
+        .collect();
+    Ok(costs)
+}
+
+/// Check the depth and complexity of the given GraphQL query, selecting
+/// the given operation. Without an operation name, all operations of the
+/// document are checked. Documents which can not be parsed are rejected.
+pub fn check_query(
+    schema: &Schema,
+    query: &str,
+    operation_name: Option<&str>,
+    variables: &Map<String, Value>,
+    max_depth: usize,
+    max_complexity: u64,
+) -> Result<(), Rejected> {
+    let complexity = checked_complexity(schema, query, operation_name, variables, max_depth)?;
+    check_complexity(complexity, max_complexity)
+}
+
+/// Check the depth of the operations of the given GraphQL query, see
+/// [`check_query`], returning the complexity of the costliest one.
+fn checked_complexity(
+    schema: &Schema,
+    query: &str,
+    operation_name: Option<&str>,
+    variables: &Map<String, Value>,
+    max_depth: usize,
+) -> Result<u64, Rejected> {
+    let mut complexity = 0;
+    for cost in costs(schema, query, operation_name, variables)? {
+        if cost.introspection_depth > MAX_INTROSPECTION_DEPTH {
+            return Err(Rejected::IntrospectionTooDeep {
+                depth: cost.introspection_depth,
+                max: MAX_INTROSPECTION_DEPTH,
+            });
+        }
+        if cost.depth > max_depth {
+            return Err(Rejected::TooDeep {
+                depth: cost.depth,
+                max: max_depth,
+            });
+        }
+        complexity = complexity.max(cost.complexity);
+    }
+    Ok(complexity)
+}
+
+/// Reject the given complexity if it exceeds the maximum.
+fn check_complexity(complexity: u64, max_complexity: u64) -> Result<(), Rejected> {
+    if complexity > max_complexity {
+        return Err(Rejected::TooComplex {
+            complexity,
+            max: max_complexity,
+        });
+    }
+    Ok(())
+}
+
+/// Check the depth and complexity of the queries of the given JSON
+/// encoded GraphQL request (or batch of requests), see [`check_query`].
+/// A batch is executed at once, so the complexity of its queries is
+/// summed up and limited as a whole. Malformed requests are left for
+/// the executor to reject.
+pub fn check(
+    schema: &Schema,
+    body: &[u8],
+    max_depth: usize,
+    max_complexity: u64,
+) -> Result<(), Rejected> {
+    let request: Value = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(_) => return Ok(()),
+    };
+    let requests = match request {
+        Value::Array(requests) => requests,
+        request => vec![request],
+    };
+    let no_variables = Map::new();
+    let mut complexity = 0u64;
+    for request in &requests {
+        let query = match request["query"].as_str() {
+            Some(query) => query,
+            None => continue,
+        };
+        let variables = request["variables"].as_object().unwrap_or(&no_variables);
+        let operation_name = request["operationName"].as_str();
+        complexity = complexity.saturating_add(checked_complexity(
+            schema,
+            query,
+            operation_name,
+            variables,
+            max_depth,
+        )?);
+    }
+    check_complexity(complexity, max_complexity)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graphql::schema;
+
+    /// The depth and complexity of the given query.
+    fn cost(query: &str, variables: Value) -> (usize, u64) {
+        let variables = variables.as_object().cloned().unwrap_or_default();
+        let costs = costs(&schema(), query, None, &variables).expect("unparsable query");
+        let cost = costs.into_iter().fold(Cost::default(), Cost::and);
+        (cost.depth, cost.complexity)
+    }
+
+    #[test]
+    fn test_fields_cost_one() {
+        assert_eq!(cost("{ viewer { id username } }", json!({})), (2, 3));
+        assert_eq!(
+            cost("query Q { a: viewer { id } b: viewer { id } }", json!({})),
+            (2, 4)
+        );
+        assert_eq!(
+            cost(
+                "# a comment\n{ fetch__Url(id: \"1\") { title tags { name } } }",
+                json!({})
+            ),
+            (3, 4)
+        );
+    }
+
+    #[test]
+    fn test_connections_cost_per_item() {
+        let query = "{ submissions(first: 10) { edges { node { id title } } } }";
+        assert_eq!(cost(query, json!({})), (4, 1 + 10 * 4));
+        let query = "{ submissions { edges { node { id } } } }";
+        assert_eq!(cost(query, json!({})), (4, 1 + MAX_PAGE_SIZE * 3));
+        let query = "{ submissions { nodes { id } } }";
+        assert_eq!(cost(query, json!({})), (3, 1 + MAX_PAGE_SIZE * 2));
+        let query = "query Q($n: Int) { submissions(first: $n) { edges { cursor } } }";
+        assert_eq!(cost(query, json!({ "n": 5 })), (3, 1 + 5 * 2));
+        assert_eq!(cost(query, json!({})), (3, 1 + MAX_PAGE_SIZE * 2));
+        let query = "{ submissions(last: -5) { edges { cursor } } }";
+        assert_eq!(cost(query, json!({})), (3, 1));
+
+        // nested connections multiply
+        let query = "{
+            submissions(first: 10) {
+                edges {
+                    node {
+                        comments(first: 20) { edges { node { id } } }
+                    }
+                }
+            }
+        }";
+        assert_eq!(cost(query, json!({})), (7, 1 + 10 * (2 + 1 + 20 * 3)));
+    }
+
+    #[test]
+    fn test_fragments_are_expanded() {
+        let query = "
+            query Q { submissions(first: 2) { edges { node { ...Url ... on Url { id } } } } }
+            fragment Url on Url { title createdBy { ...User } }
+            fragment User on User { username }
+        ";
+        assert_eq!(cost(query, json!({})), (5, 1 + 2 * (2 + 1 + 3)));
+
+        // fragments which spread themselves don't loop
+        let query = "{ viewer { ...A } } fragment A on User { id ...A }";
+        assert_eq!(cost(query, json!({})), (2, 2));
+
+        // fragments spread many times are only costed once
+        let mut query = String::from("{ viewer { ...F0 } }");
+        for i in 0..40 {
+            let spread = format!("...F{} ", i + 1);
+            query.push_str(&format!(
+                "fragment F{} on User {{ {} }}",
+                i,
+                spread.repeat(2)
+            ));
+        }
+        query.push_str("fragment F40 on User { id }");
+        assert_eq!(cost(&query, json!({})), (2, 1 + (1 << 40)));
+    }
+
+    #[test]
+    fn test_introspection_is_exempt() {
+        let query = "{ __typename __schema { types { name fields { name type { name } } } } }";
+        assert_eq!(
+            check_query(&schema(), query, None, &Map::new(), 1, 1),
+            Ok(())
+        );
+        let nested = format!("{{ __schema {}{} }}", "{ types ".repeat(16), "}".repeat(16));
+        assert_eq!(
+            check_query(&schema(), &nested, None, &Map::new(), 1, 1),
+            Err(Rejected::IntrospectionTooDeep { depth: 17, max: 16 })
+        );
+    }
+
+    #[test]
+    fn test_operations_are_selected() {
+        let query = "query Small { viewer { id } } query Large { viewer { id username } }";
+        let schema = schema();
+        let variables = Map::new();
+        assert_eq!(
+            check_query(&schema, query, Some("Small"), &variables, 2, 2),
+            Ok(())
+        );
+        assert_eq!(
+            check_query(&schema, query, Some("Large"), &variables, 2, 2),
+            Err(Rejected::TooComplex {
+                complexity: 3,
+                max: 2
+            })
+        );
+        assert!(check_query(&schema, query, None, &variables, 2, 2).is_err());
+    }
+
+    #[test]
+    fn test_values_and_strings_are_skipped() {
+        let query = r#"
+            mutation Submit($input: NewUrlInput! = { tags: ["a", "b"] }) @dir(x: 1) {
+                submitUrl(input: { url: "https://example.com/{ a }", tags: [$tag, "}"] }) {
+                    url @include(if: true) { id }
+                    note: duplicate
+                }
+                other(text: "with \"quotes\" { }", score: -1.5e3)
+            }
+        "#;
+        assert_eq!(cost(query, json!({})), (3, 5));
+    }
+
+    #[test]
+    fn test_variable_defaults_are_used() {
+        let query = "query Q($n: Int = 5) { submissions(first: $n) { edges { cursor } } }";
+        assert_eq!(cost(query, json!({})), (3, 1 + 5 * 2));
+        assert_eq!(cost(query, json!({ "n": 2 })), (3, 1 + 2 * 2));
+        assert_eq!(
+            cost(query, json!({ "n": null })),
+            (3, 1 + MAX_PAGE_SIZE * 2)
+        );
+
+        // defaults are those of the operation spreading a fragment
+        let query = "
+            query A($n: Int = 1) { viewer { ...F } }
+            query B($n: Int = 3) { viewer { ...F } }
+            fragment F on User { submissions(first: $n) { nodes { id } } }
+        ";
+        assert_eq!(cost(query, json!({})), (4, (2 + 1 * 2) + (2 + 3 * 2)));
+    }
+
+    #[test]
+    fn test_unparsable_documents_are_rejected() {
+        let schema = schema();
+        for query in &["{ viewer { id }", "{ \"unterminated }", ""] {
+            assert!(matches!(
+                check_query(&schema, query, None, &Map::new(), 10, 100),
+                Err(Rejected::Unparsable(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_batches_are_checked() {
+        let schema = schema();
+        let body = br#"[{"query": "{ viewer { id } }"}, {"query": "{ viewer { id username } }"}]"#;
+        assert_eq!(check(&schema, body, 5, 5), Ok(()));
+        assert_eq!(
+            check(&schema, body, 1, 5),
+            Err(Rejected::TooDeep { depth: 2, max: 1 })
+        );
+
+        // each query is within budget, but the batch is not
+        assert_eq!(
+            check(&schema, body, 5, 4),
+            Err(Rejected::TooComplex {
+                complexity: 5,
+                max: 4
+            })
+        );
+        let body = Value::Array(vec![json!({ "query": "{ viewer { id } }" }); 200]);
+        assert_eq!(
+            check(&schema, body.to_string().as_bytes(), 5, 100),
+            Err(Rejected::TooComplex {
+                complexity: 400,
+                max: 100
+            })
+        );
+        let body = json!({
+            "query": "query Q($n: Int) { urls(first: $n) { edges { cursor } } }",
+            "variables": { "n": 1 },
+        });
+        assert_eq!(check(&schema, body.to_string().as_bytes(), 5, 3), Ok(()));
+        assert_eq!(check(&schema, b"not json", 0, 0), Ok(()));
+        assert!(matches!(
+            check(&schema, br#"{"query": "{ unparsable"}"#, 0, 0),
+            Err(Rejected::Unparsable(_))
+        ));
+    }
+}
@@ -0,0 +1,70 @@
+//! Relay connections whose nodes are loaded by fallible application
+//! code. These wrap [`RelayConnection`], whose loaders fail with plain
+//! GraphQL errors, such that errors keep their code.
+
+use crate::error::AppError;
+use juniper::FieldError;
+use juniper_relay_connection::{RelayConnection, RelayConnectionNode};
+use std::future::Future;
+
+/// Build a connection from the nodes returned by `load`, see
+/// `RelayConnection::new`. Invalid pagination arguments are
+/// rejected as `VALIDATION_FAILED`.
+pub fn new<N, F>(
+    first: Option<i32>,
+    after: Option<String>,
+    last: Option<i32>,
+    before: Option<String>,
+    load: F,
+) -> Result<RelayConnection<N>, AppError>
+where
+    N: RelayConnectionNode,
+    F: FnOnce(Option<N::Cursor>, Option<N::Cursor>, Option<i64>) -> Result<Vec<N>, AppError>,
+{
+    let mut failed = None;
+    let connection = RelayConnection::new(first, after, last, before, |after, before, limit| {
+        load(after, before, limit).map_err(|error| {
+            failed = Some(error);
+            FieldError::from("Failed to load connection")
+        })
+    });
+    connection.map_err(|error| rejected(error, failed))
+}
+
+/// Build a connection from the nodes returned by `load`, see
+/// `RelayConnection::new_async`.
+pub async fn new_async<N, F, Fut>(
+    first: Option<i32>,
+    after: Option<String>,
+    last: Option<i32>,
+    before: Option<String>,
+    load: F,
+) -> Result<RelayConnection<N>, AppError>
+where
+    N: RelayConnectionNode,
+    F: FnOnce(Option<N::Cursor>, Option<N::Cursor>, Option<i64>) -> Fut,
+    Fut: Future<Output = Result<Vec<N>, AppError>>,
+{
+    let mut failed = None;
+    let failed_load = &mut failed;
+    let connection = RelayConnection::new_async(
+        first,
+        after,
+        last,
+        before,
+        |after, before, limit| async move {
+            load(after, before, limit).await.map_err(|error| {
+                *failed_load = Some(error);
+                FieldError::from("Failed to load connection")
+            })
+        },
+    )
+    .await;
+    connection.map_err(|error| rejected(error, failed))
+}
+
+/// The error a connection was rejected with. Unless loading its
+/// nodes failed, its pagination arguments or cursors are invalid.
+fn rejected(error: FieldError, failed: Option<AppError>) -> AppError {
+    failed.unwrap_or_else(|| AppError::ValidationFailed(error.message().to_string()))
+}
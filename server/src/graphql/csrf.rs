@@ -0,0 +1,120 @@
+//! Detection of GraphQL requests which may have side effects, and
+//! thus need to be protected against cross-site request forgery.
+
+use super::Schema;
+use juniper::parser::parse_document_source;
+use juniper::{Definition, OperationType};
+use serde_json::Value;
+
+/// Whether the given JSON encoded GraphQL request (or batch of
+/// requests) contains a mutation. This errs on the side of caution,
+/// and reports a mutation for every request which is not known to
+/// execute a query, e.g. because its document can't be parsed.
+pub fn contains_mutation(schema: &Schema, body: &[u8]) -> bool {
+    let request: Value = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(_) => return true,
+    };
+    let requests = match request {
+        Value::Array(requests) => requests,
+        request => vec![request],
+    };
+    requests.iter().any(|request| {
+        let operation = request["query"]
+            .as_str()
+            .and_then(|query| operation_type(schema, query, request["operationName"].as_str()));
+        operation != Some(OperationType::Query)
+    })
+}
+
+/// The type of the operation of a GraphQL document which is executed
+/// for the given operation name, as parsed by juniper. This is `None`
+/// if the document can't be parsed, or the operation can't be found.
+pub fn operation_type(
+    schema: &Schema,
+    document: &str,
+    operation_name: Option<&str>,
+) -> Option<OperationType> {
+    let document = parse_document_source(document, &schema.schema).ok()?;
+    let mut operations = document.iter().filter_map(|definition| match definition {
+        Definition::Operation(operation) => Some(&operation.item),
+        Definition::Fragment(_) => None,
+    });
+    let operation = match operation_name {
+        Some(name) => {
+            operations.find(|operation| operation.name.as_ref().map(|n| n.item) == Some(name))?
+        }
+        None => {
+            // unnamed operations must be the only one in the document
+            let operation = operations.next()?;
+            if operations.next().is_some() {
+                return None;
+            }
+            operation
+        }
+    };
+    Some(operation.operation_type)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graphql::schema;
+
+    fn is_mutation(document: &str) -> bool {
+        let body = serde_json::json!({ "query": document }).to_string();
+        contains_mutation(&schema(), body.as_bytes())
+    }
+
+    #[test]
+    fn test_mutations_are_detected() {
+        assert!(is_mutation("mutation { logoutOtherSessions }"));
+        assert!(is_mutation(
+            "# comment\nmutation Logout { logoutOtherSessions }"
+        ));
+        assert!(is_mutation(
+            "query A { viewer { id } }\nmutation B { logoutOtherSessions }"
+        ));
+
+        assert!(!is_mutation("{ viewer { id } }"));
+        assert!(!is_mutation("query Mutation { viewer { id } }"));
+        assert!(!is_mutation(
+            "query { search(query: \"mutation\") { __typename } }"
+        ));
+        assert!(!is_mutation("# mutation\n{ viewer { id } }"));
+
+        // documents which can't be parsed are assumed to be mutations
+        assert!(is_mutation("{ viewer { id }"));
+        assert!(is_mutation(""));
+    }
+
+    #[test]
+    fn test_operations_are_selected_by_name() {
+        let schema = schema();
+        let document = "query A { viewer { id } }\nmutation B { logoutOtherSessions }";
+        assert_eq!(
+            operation_type(&schema, document, Some("A")),
+            Some(OperationType::Query)
+        );
+        assert_eq!(
+            operation_type(&schema, document, Some("B")),
+            Some(OperationType::Mutation)
+        );
+        assert_eq!(operation_type(&schema, document, Some("C")), None);
+        assert_eq!(operation_type(&schema, document, None), None);
+
+        let body = br#"{"query": "query A { viewer { id } }\nmutation B { logoutOtherSessions }", "operationName": "A"}"#;
+        assert!(!contains_mutation(&schema, body));
+    }
+
+    #[test]
+    fn test_batches_are_checked() {
+        let schema = schema();
+        let body =
+            br#"[{"query": "{ viewer { id } }"}, {"query": "mutation { logoutOtherSessions }"}]"#;
+        assert!(contains_mutation(&schema, body));
+        let body = br#"{"query": "{ viewer { id } }", "operationName": "mutation"}"#;
+        assert!(contains_mutation(&schema, body));
+        assert!(contains_mutation(&schema, b"not json"));
+    }
+}
@@ -1,36 +1,401 @@
-use crate::pages::ContextFilter;
+use crate::context::SessionSource;
+use crate::pages::{session, xsrf, ContextFilter};
 use crate::Context;
-use juniper::{EmptySubscription, RootNode};
-use warp::{filters::BoxedFilter, Filter};
+use futures_util::future::join_all;
+use futures_util::TryStreamExt;
+use juniper::http::{GraphQLBatchResponse, GraphQLResponse};
+use juniper::{DefaultScalarValue, ExecutionError, GraphQLError, RootNode, ScalarValue, Variables};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use warp::http::{header, HeaderValue, Response, StatusCode};
+use warp::hyper::body::{Buf, Bytes};
+use warp::multipart::{FormData, Part};
+use warp::{filters::BoxedFilter, Filter, Rejection};
 
+mod complexity;
+mod connection;
+mod csrf;
 mod mutation;
 mod objects;
+mod persisted;
 mod query;
 mod search;
+mod subscription;
+mod upload;
 mod viewer;
+mod ws;
 
-type Schema = RootNode<'static, query::Query, mutation::Mutation, EmptySubscription<Context>>;
+type Schema = RootNode<'static, query::Query, mutation::Mutation, subscription::Subscription>;
 
 const XSRF_HEADER_NAME: &str = "X-XSRF-Token";
 
-/// GraphQL API endpoint filter. The filter checks
-/// for a valid XSRF token in a custom header.
+/// Maximum size of a multipart request, including all
+/// uploaded files.
+const MAX_MULTIPART_BYTES: u64 = 6 * 1024 * 1024;
+
+/// The operations of a request, and the files uploaded with it.
+type RequestBody = (Bytes, HashMap<String, Vec<u8>>);
+
+/// Construct the GraphQL schema.
+fn schema() -> Schema {
+    Schema::new(query::Query, mutation::Mutation, subscription::Subscription)
+}
+
+/// GraphQL API endpoint filter. Mutations from requests which are
+/// authenticated using the session cookie must carry a valid XSRF
+/// token in a custom header, which can be obtained from the `csrfToken`
+/// query. Requests authenticated using the `Authorization` header can
+/// not be forged by other sites, and are not checked. Rejected
+/// responses due to rate limits carry a `Retry-After` header.
+///
+/// Requests are either JSON, or multipart forms which carry file
+/// uploads, following the GraphQL multipart request specification.
+/// Queries may also be sent using GET, with the `query`, `variables`,
+/// `operationName` and `extensions` as query parameters. Mutations
+/// sent using GET are rejected. Subscriptions are served over
+/// websockets, see [`ws`].
 pub fn api(ctx: impl ContextFilter + 'static) -> BoxedFilter<(impl warp::Reply,)> {
-    let filter = warp::path::end()
-        .and(ctx)
-        .and(warp::header(XSRF_HEADER_NAME))
-        .and_then(|ctx: Context, xsrf_token: String| async move {
-            if ctx.check_xsrf_token(&xsrf_token) {
-                Ok(ctx)
-            } else {
-                Err(warp::reject())
+    let schema = Arc::new(schema());
+    let get_schema = schema.clone();
+    let ws_schema = schema.clone();
+    let requests = warp::path::end()
+        .and(warp::post())
+        .and(ctx.clone())
+        .and(warp::header::optional::<String>(XSRF_HEADER_NAME))
+        .and(request_body())
+        .and_then(move |ctx, xsrf_token, body| {
+            let schema = schema.clone();
+            async move {
+                Ok::<_, Infallible>(execute(&schema, ctx, xsrf_token, body, Method::Post).await)
             }
+        });
+    let queries = warp::path::end()
+        .and(warp::get())
+        .and(ctx.clone())
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(move |ctx, params| {
+            let schema = get_schema.clone();
+            let body = query_request(params).map(|body| (body, HashMap::new()));
+            async move { Ok::<_, Infallible>(execute(&schema, ctx, None, body, Method::Get).await) }
+        });
+    let subscriptions =
+        warp::path::end()
+            .and(warp::ws())
+            .and(ctx)
+            .map(move |ws: warp::ws::Ws, ctx| {
+                let schema = ws_schema.clone();
+                let reply = ws.on_upgrade(move |socket| ws::serve(socket, schema, ctx));
+                warp::reply::with_header(reply, "Sec-WebSocket-Protocol", ws::PROTOCOL)
+            });
+    requests.or(subscriptions).or(queries).boxed()
+}
+
+/// A single GraphQL request, or a batch of requests, which
+/// are executed at once.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BatchRequest {
+    Single(Request),
+    Batch(Vec<Request>),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    query: String,
+    operation_name: Option<String>,
+    variables: Option<Variables>,
+}
+
+/// The result of executing a single request.
+type ExecutionResult<'a> =
+    Result<(juniper::Value, Vec<ExecutionError<DefaultScalarValue>>), GraphQLError<'a>>;
+
+/// The HTTP method of a GraphQL request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    Get,
+    Post,
+}
+
+/// Encode the query parameters of a GET request like the body of
+/// a JSON request. The `variables` and `extensions` parameters are
+/// JSON encoded, other parameters are ignored.
+fn query_request(params: HashMap<String, String>) -> Result<Bytes, String> {
+    let mut request = Map::new();
+    for (key, value) in params {
+        let value = match key.as_str() {
+            "query" | "operationName" => Value::String(value),
+            "variables" | "extensions" => serde_json::from_str(&value)
+                .map_err(|err| format!("Invalid query parameter {}: {}", key, err))?,
+            _ => continue,
+        };
+        request.insert(key, value);
+    }
+    let body = serde_json::to_vec(&Value::Object(request)).map_err(|err| err.to_string())?;
+    Ok(body.into())
+}
+
+/// The body of a GraphQL request, which is either a JSON encoded
+/// request, or a multipart form. Invalid multipart forms are passed
+/// on as errors, such that they are reported like other invalid
+/// requests.
+fn request_body() -> BoxedFilter<(Result<RequestBody, String>,)> {
+    warp::multipart::form()
+        .max_length(MAX_MULTIPART_BYTES)
+        .and_then(|form| async move {
+            let request = multipart_request(form)
+                .await
+                .map_err(|err| format!("Invalid multipart request: {}", err));
+            Ok::<_, Rejection>(request)
         })
-        .boxed();
-    let schema = Schema::new(
-        query::Query,
-        mutation::Mutation,
-        EmptySubscription::<Context>::new(),
+        .or(warp::body::bytes().map(|body| Ok((body, HashMap::new()))))
+        .unify()
+        .boxed()
+}
+
+/// Read a multipart request. The `operations` part holds the JSON
+/// encoded request, and the `map` part lists for every file part the
+/// paths of the variables which refer to it. Those variables are set
+/// to the name of the file part, which can be resolved to the file
+/// contents using [`Context::upload`].
+async fn multipart_request(mut form: FormData) -> Result<RequestBody, String> {
+    let mut operations = None;
+    let mut map = None;
+    let mut files = HashMap::new();
+    while let Some(part) = form.try_next().await.map_err(|err| err.to_string())? {
+        let name = part.name().to_string();
+        let data = read_part(part).await?;
+        match name.as_str() {
+            "operations" => operations = Some(data),
+            "map" => map = Some(data),
+            _ => {
+                files.insert(name, data);
+            }
+        }
+    }
+
+    let operations = operations.ok_or("Missing operations")?;
+    let mut operations: Value = serde_json::from_slice(&operations)
+        .map_err(|err| format!("Invalid operations: {}", err))?;
+    let map: HashMap<String, Vec<String>> = serde_json::from_slice(&map.ok_or("Missing map")?)
+        .map_err(|err| format!("Invalid map: {}", err))?;
+    for (key, paths) in map {
+        if !files.contains_key(&key) {
+            return Err(format!("Missing file {}", key));
+        }
+        for path in paths {
+            let target = path
+                .split('.')
+                .try_fold(&mut operations, |value, segment| match value {
+                    Value::Array(items) => segment
+                        .parse()
+                        .ok()
+                        .and_then(move |i: usize| items.get_mut(i)),
+                    Value::Object(fields) => fields.get_mut(segment),
+                    _ => None,
+                })
+                .ok_or_else(|| format!("Invalid path {}", path))?;
+            *target = Value::String(key.clone());
+        }
+    }
+    let operations = serde_json::to_vec(&operations).map_err(|err| err.to_string())?;
+    Ok((operations.into(), files))
+}
+
+/// Read the contents of a part of a multipart form.
+async fn read_part(part: Part) -> Result<Vec<u8>, String> {
+    part.stream()
+        .try_fold(Vec::new(), |mut data, chunk| async move {
+            data.extend_from_slice(chunk.chunk());
+            Ok(data)
+        })
+        .await
+        .map_err(|err| err.to_string())
+}
+
+/// Execute a (batched) GraphQL request, and attach the XSRF
+/// cookie and possibly a new session cookie to the response.
+async fn execute(
+    schema: &Schema,
+    mut ctx: Context,
+    xsrf_token: Option<String>,
+    body: Result<RequestBody, String>,
+    method: Method,
+) -> Response<Vec<u8>> {
+    if xsrf_token.map_or(false, |token| ctx.check_xsrf_token(&token)) {
+        ctx.set_xsrf_verified();
+    }
+
+    let (status, body, retry_after) = match body {
+        Err(err) => (
+            StatusCode::BAD_REQUEST,
+            error_body(&err, "BAD_REQUEST"),
+            None,
+        ),
+        Ok((body, uploads)) => {
+            ctx.set_uploads(uploads);
+            execute_request(schema, &ctx, &body, method).await
+        }
+    };
+
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
     );
-    juniper_warp::make_graphql_filter(schema, filter)
+    if let Some(cookie) = xsrf::header_value(&ctx) {
+        headers.append(header::SET_COOKIE, cookie);
+    }
+    if let Some(cookie) = ctx
+        .take_session_cookie()
+        .and_then(|token| session::header_value(&token))
+    {
+        headers.append(header::SET_COOKIE, cookie);
+    }
+    if let Some(secs) = retry_after {
+        headers.insert(header::RETRY_AFTER, HeaderValue::from(secs));
+    }
+    response
+}
+
+/// Execute an already read GraphQL request, unless it contains
+/// mutations which need the XSRF token but don't carry it or were
+/// sent using GET, or its queries are too deep or too complex.
+/// Persisted queries are resolved first, such that they are checked
+/// like any other. Also returns after how many seconds a request
+/// which was rejected due to a rate limit may be retried.
+async fn execute_request(
+    schema: &Schema,
+    ctx: &Context,
+    body: &[u8],
+    method: Method,
+) -> (StatusCode, Vec<u8>, Option<i32>) {
+    let body = match persisted::resolve(ctx, body).await {
+        Ok(body) => body,
+        Err(rejected) => {
+            let body = error_body(&rejected.to_string(), rejected.code());
+            return (rejected.status(), body, None);
+        }
+    };
+    let body = &*body;
+    let (status, body) = match serde_json::from_slice::<BatchRequest>(body) {
+        Err(err) => (
+            StatusCode::BAD_REQUEST,
+            error_body(&format!("Invalid GraphQL request: {}", err), "BAD_REQUEST"),
+        ),
+        Ok(_) if method == Method::Get && csrf::contains_mutation(schema, body) => (
+            StatusCode::METHOD_NOT_ALLOWED,
+            error_body("Only queries can be sent using GET", "METHOD_NOT_ALLOWED"),
+        ),
+        Ok(_)
+            if ctx.session_source() == Some(SessionSource::Cookie)
+                && !ctx.is_xsrf_verified()
+                && csrf::contains_mutation(schema, body) =>
+        {
+            (
+                StatusCode::FORBIDDEN,
+                error_body("Missing or invalid CSRF token", "CSRF_TOKEN_INVALID"),
+            )
+        }
+        Ok(request) => {
+            let config = ctx.config();
+            let limits = complexity::check(
+                schema,
+                body,
+                config.max_query_depth(),
+                config.max_query_complexity(),
+            );
+            if let Err(rejected) = limits {
+                let body = rejection_body(&rejected.to_string(), rejected.extensions());
+                return (StatusCode::BAD_REQUEST, body, None);
+            }
+            return execute_batch(schema, ctx, &request).await;
+        }
+    };
+    (status, body, None)
+}
+
+/// Execute the requests of a batch at once, see [`execute_request`].
+async fn execute_batch(
+    schema: &Schema,
+    ctx: &Context,
+    request: &BatchRequest,
+) -> (StatusCode, Vec<u8>, Option<i32>) {
+    let requests = match request {
+        BatchRequest::Single(request) => std::slice::from_ref(request),
+        BatchRequest::Batch(requests) => &requests[..],
+    };
+    let results = join_all(requests.iter().map(|request| async move {
+        let variables = request.variables.clone().unwrap_or_default();
+        let operation_name = request.operation_name.as_deref();
+        juniper::execute(&request.query, operation_name, schema, &variables, ctx).await
+    }))
+    .await;
+
+    // a batch may only be retried once all of its requests may be
+    let retry_after = results
+        .iter()
+        .map(retry_after)
+        .collect::<Option<Vec<i32>>>()
+        .and_then(|retry_after| retry_after.into_iter().max());
+    let status = if results.iter().all(Result::is_ok) {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_REQUEST
+    };
+    let response = match request {
+        BatchRequest::Single(_) => {
+            let result = results.into_iter().next().expect("missing result");
+            GraphQLBatchResponse::Single(GraphQLResponse::from_result(result))
+        }
+        BatchRequest::Batch(_) => GraphQLBatchResponse::Batch(
+            results
+                .into_iter()
+                .map(GraphQLResponse::from_result)
+                .collect(),
+        ),
+    };
+    let body = serde_json::to_vec(&response).unwrap_or_default();
+    (status, body, retry_after)
+}
+
+/// After how many seconds the request may be retried, if it was
+/// rejected entirely (no data was returned) and one of its errors
+/// is a rate limit, see [`RateLimited`](crate::error::RateLimited).
+fn retry_after(result: &ExecutionResult) -> Option<i32> {
+    let (data, errors) = result.as_ref().ok()?;
+    if !data.is_null() {
+        return None;
+    }
+    errors
+        .iter()
+        .filter_map(|error| {
+            let extensions = error.error().extensions().as_object_value()?;
+            extensions
+                .get_field_value("retryAfter")?
+                .as_scalar()?
+                .as_int()
+        })
+        .max()
+}
+
+/// A GraphQL response body for a request
+/// which was rejected before execution.
+fn error_body(message: &str, code: &str) -> Vec<u8> {
+    rejection_body(message, json!({ "code": code }))
+}
+
+/// Like [`error_body`], with the given error extensions.
+fn rejection_body(message: &str, extensions: Value) -> Vec<u8> {
+    let body = json!({
+        "data": null,
+        "errors": [{ "message": message, "extensions": extensions }],
+    });
+    body.to_string().into_bytes()
 }
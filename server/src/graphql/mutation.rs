@@ -1,12 +1,21 @@
+use super::upload::Upload;
 use super::viewer::Viewer;
-use crate::db::id::{CommentID, LoginID, UrlID};
+use crate::db::id::{
+    CollectionID, CommentID, GroupID, InviteID, LoginID, NotificationID, UrlID, UserID, WebhookID,
+};
 use crate::db::models::{
-    Comment, Invite, Login, NewCommentInput, NewUrlInput, NewUserInput, Permission, Role,
-    UpdateUserInput, Url, User,
+    self, Block, BookmarkExport, Collection, Comment, DataExport, ExportFormat, Follow, Group,
+    ImportReport, ImportService, ImportVisibility, Invite, Login, ModerationAction, MutedDomain,
+    NewCollectionInput, NewCommentInput, NewGroupInput, NewUrlInput, NewUserInput, Notification,
+    Permission, PreferencesInput, Report, ReportReason, Role, SavedUrl, ServiceImport,
+    SubmitUrlResult, Tag, UnsubscribeToken, UpdateCollectionInput, UpdateGroupInput,
+    UpdateUrlInput, UpdateUserInput, Url, UrlView, User, UserPreferences, VoteDirection, Webhook,
+    WebhookEvent,
 };
+use crate::error::AppError;
+use crate::validation::Validator;
 use crate::Context;
-use juniper::{graphql_object, FieldResult, GraphQLObject};
-use validator::Validate;
+use juniper::{graphql_object, GraphQLObject};
 
 pub struct Mutation;
 
@@ -21,35 +30,94 @@ struct Void {
 }
 
 impl Void {
-    fn ok() -> FieldResult<Self> {
+    fn ok() -> Result<Self, AppError> {
         Ok(Self { ok: true })
     }
 }
 
 #[graphql_object(context = Context)]
 impl Mutation {
-    /// Register a new user by claiming the provided invitation code `token`.
-    async fn register_user(ctx: &Context, input: NewUserInput, token: String) -> FieldResult<User> {
-        input.validate()?; // surface input errors early for better UX
-        let invite = Invite::find_by_token(ctx, &token).await?;
-        let user = User::create_with_invite(ctx, input, invite).await?;
-        Ok(user)
+    /// Register a new user. Unless registration is open, this claims the
+    /// provided invitation code `token`, which is required in that case.
+    async fn register_user(
+        ctx: &Context,
+        input: NewUserInput,
+        token: Option<String>,
+    ) -> Result<User, AppError> {
+        // surface input errors early for better UX
+        Validator::new().check("input", &input).finish()?;
+        Ok(User::register(ctx, input, token.as_deref()).await?)
     }
 
     /// Update details for the currently logged in user.
-    async fn update_user(ctx: &Context, input: UpdateUserInput) -> FieldResult<Viewer> {
+    async fn update_user(ctx: &Context, input: UpdateUserInput) -> Result<Viewer, AppError> {
         let mut user = ctx.user().await?;
         user.update(ctx, input).await?;
         Ok(Viewer)
     }
 
+    /// Update the settings of the currently logged in user. Only
+    /// the provided fields are changed.
+    async fn update_preferences(
+        ctx: &Context,
+        input: PreferencesInput,
+    ) -> Result<Viewer, AppError> {
+        let mut preferences = UserPreferences::find(ctx, ctx.user_id()?).await?;
+        preferences.update(ctx, input).await?;
+        Ok(Viewer)
+    }
+
+    /// Verify the email address of an account, using the token sent
+    /// by email after registering or changing the email address.
+    async fn verify_email(ctx: &Context, token: String) -> Result<Void, AppError> {
+        User::verify_email(ctx, &token).await?;
+        Void::ok()
+    }
+
+    /// Send another verification email to the currently logged in
+    /// user. Note this might fail because of rate limiting.
+    async fn resend_verification(ctx: &Context) -> Result<Void, AppError> {
+        let mut user = ctx.user().await?;
+        user.request_verification(ctx).await?;
+        Ok(Void { ok: true })
+    }
+
+    /// Request an export of all data associated with the currently
+    /// logged in account. The export is assembled in the background,
+    /// and a download link is sent by email once it is ready. Only
+    /// one export can be in progress at any time.
+    async fn request_data_export(ctx: &Context) -> Result<Void, AppError> {
+        let user = ctx.user().await?;
+        DataExport::request(ctx, &user).await?;
+        Ok(Void { ok: true })
+    }
+
+    /// Request an export of the submissions and saves of the currently
+    /// logged in user, including their tags, scores, and read state. The
+    /// export is generated in the background, and a download link is
+    /// listed by `Viewer.exports` once it is ready. Only one export can
+    /// be in progress at any time.
+    async fn export_bookmarks(ctx: &Context, format: ExportFormat) -> Result<Void, AppError> {
+        let user = ctx.user().await?;
+        BookmarkExport::request(ctx, &user, format).await?;
+        Ok(Void { ok: true })
+    }
+
+    /// Unsubscribe from a category of emails, using the signed token
+    /// included in the email. This does not require being logged in,
+    /// and repeating the request has no further effect.
+    async fn unsubscribe(ctx: &Context, token: String) -> Result<Void, AppError> {
+        UnsubscribeToken::decode(ctx, &token)?.apply(ctx).await?;
+        Void::ok()
+    }
+
     /// Grants the given permission to the user with the
     /// provided email.
     async fn grant_permission(
         ctx: &Context,
         permission: Permission,
         email: String,
-    ) -> FieldResult<User> {
+    ) -> Result<User, AppError> {
         ctx.user()
             .await?
             .check_permissions(ctx, |perm| perm.modify_user_roles())
@@ -65,7 +133,7 @@ impl Mutation {
         ctx: &Context,
         permission: Permission,
         email: String,
-    ) -> FieldResult<User> {
+    ) -> Result<User, AppError> {
         ctx.user()
             .await?
             .check_permissions(ctx, |perm| perm.modify_user_roles())
@@ -77,72 +145,715 @@ impl Mutation {
 
     /// Request a login code for the user associated with the given `email`. Note
     /// this this might fail because of rate limiting.
-    async fn request_login(ctx: &Context, email: String) -> FieldResult<Void> {
+    async fn request_login(ctx: &Context, email: String) -> Result<Void, AppError> {
         let user = User::find_by_email(ctx, &email).await?;
         user.request_login(ctx).await?;
-        Void::ok()
+        Ok(Void { ok: true })
     }
 
     /// Login using the given `email` and a login code (or token) previously obtained
-    /// from `request_login`.
-    async fn login(ctx: &Context, email: String, token: String) -> FieldResult<String> {
+    /// from `request_login`. This returns the session token, which should be sent
+    /// in an `Authorization: Bearer` header. If `set_cookie` is true, the session
+    /// is instead stored in an http-only cookie and an empty string is returned.
+    /// Setting a cookie requires a valid CSRF token, see `csrf_token`.
+    async fn login(
+        ctx: &Context,
+        email: String,
+        token: String,
+        #[graphql(default = false)] set_cookie: bool,
+    ) -> Result<String, AppError> {
+        if set_cookie && !ctx.is_xsrf_verified() {
+            let message = "Missing or invalid CSRF token";
+            return Err(AppError::PermissionDenied(message.into()));
+        }
         let user = User::find_by_email(ctx, &email).await?;
         let session = user.login(ctx, &token).await?;
-        Ok(session)
+        if set_cookie {
+            ctx.set_session_cookie(session);
+            Ok(String::new())
+        } else {
+            Ok(session)
+        }
+    }
+
+    /// Lift the login lock of the given user, which is put in place
+    /// after too many failed login attempts.
+    async fn unlock_account(ctx: &Context, user_id: UserID) -> Result<Void, AppError> {
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.unlock_accounts())
+            .await?;
+        let mut user = User::find(ctx, user_id).await?;
+        user.unlock_login(ctx).await?;
+        Ok(Void { ok: true })
     }
 
     /// Revoke a login session for the currently logged in
     /// user.
-    async fn revoke_login(ctx: &Context, login: LoginID) -> FieldResult<Void> {
+    async fn revoke_login(ctx: &Context, login: LoginID) -> Result<Void, AppError> {
         let mut login = Login::find(ctx, login).await?;
         login.revoke(ctx).await?;
         Void::ok()
     }
 
+    /// Revoke all sessions of the currently logged in user except the
+    /// current one, as well as any outstanding login codes. Returns
+    /// the number of revoked sessions. This also happens automatically
+    /// when the email address of the account is changed.
+    async fn logout_other_sessions(ctx: &Context) -> Result<i32, AppError> {
+        Ok(Login::revoke_other_sessions(ctx, ctx.user_id()?).await?)
+    }
+
+    /// Replace the private feed token of the currently logged in user.
+    /// The previous feed url stops working immediately.
+    async fn regenerate_feed_token(ctx: &Context) -> Result<Viewer, AppError> {
+        let mut user = ctx.user().await?;
+        user.regenerate_feed_token(ctx).await?;
+        Ok(Viewer)
+    }
+
     /// Create a new invite, issued by the currently logged in user.
-    async fn issue_invite(ctx: &Context) -> FieldResult<Invite> {
+    async fn issue_invite(ctx: &Context) -> Result<Invite, AppError> {
         let user = ctx.user().await?;
         Ok(Invite::create(ctx, &user).await?)
     }
 
+    /// Revoke an unclaimed invite previously issued by the currently
+    /// logged in user. Revoked invites no longer count towards the
+    /// invitation quota.
+    async fn revoke_invite(ctx: &Context, invite: InviteID) -> Result<Invite, AppError> {
+        let mut invite = Invite::find(ctx, invite).await?;
+        invite.revoke(ctx).await?;
+        Ok(invite)
+    }
+
+    /// Override the invitation quota of the user with the provided
+    /// email. Passing a null `quota` resets the user to the default.
+    async fn set_invite_quota(
+        ctx: &Context,
+        email: String,
+        quota: Option<i32>,
+    ) -> Result<User, AppError> {
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.modify_invite_quotas())
+            .await?;
+        let mut user = User::find_by_email(ctx, &email).await?;
+        user.set_invite_quota(ctx, quota).await?;
+        Ok(user)
+    }
+
+    /// Grant `count` additional invitations to the given user, on
+    /// top of their regular quota.
+    async fn grant_invites(ctx: &Context, user: UserID, count: i32) -> Result<User, AppError> {
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.modify_invite_quotas())
+            .await?;
+        let mut user = User::find(ctx, user).await?;
+        user.grant_invites(ctx, count).await?;
+        Ok(user)
+    }
+
     /// Create a new URL and crawls the associated HTML page for
-    /// meta data.
-    async fn submit_url(ctx: &Context, input: NewUrlInput) -> FieldResult<Url> {
-        Ok(Url::create(ctx, input, ctx.user_id()?).await?)
+    /// meta data. If the URL was submitted before, the existing
+    /// submission is returned and marked as a duplicate, unless
+    /// it is old enough to submit the URL again, or was deleted
+    /// or removed. Submitting a URL again lists the earlier
+    /// submission in `Url.previousSubmissions`. Submitting a
+    /// text instead of a URL creates a text post, which needs
+    /// a title and is never a duplicate. Submissions the spam
+    /// filter suspects are held for review by a moderator, and
+    /// spam is rejected. Submissions with a `publishAt` time are
+    /// kept as drafts until then. URLs which don't link to a public
+    /// web page are rejected with an error code like
+    /// `URL_SCHEME_NOT_ALLOWED` or `URL_PRIVATE_HOST`.
+    async fn submit_url(ctx: &Context, input: NewUrlInput) -> Result<SubmitUrlResult, AppError> {
+        let user = ctx.verified_user().await?;
+        Ok(Url::submit(ctx, input, user.id()).await?)
+    }
+
+    /// Ban the given user, preventing them from submitting,
+    /// commenting, or voting.
+    async fn ban_user(ctx: &Context, user_id: UserID) -> Result<User, AppError> {
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.ban_users())
+            .await?;
+        let mut user = User::find(ctx, user_id).await?;
+        user.ban(ctx).await?;
+        Ok(user)
+    }
+
+    /// Lift the ban of the given user.
+    async fn unban_user(ctx: &Context, user_id: UserID) -> Result<User, AppError> {
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.ban_users())
+            .await?;
+        let mut user = User::find(ctx, user_id).await?;
+        user.unban(ctx).await?;
+        Ok(user)
+    }
+
+    /// Report the given URL to the moderators. Each user has at most
+    /// one open report per URL, reporting it again replaces the reason
+    /// and note. Reporters are not told how their reports are resolved.
+    async fn report_url(
+        ctx: &Context,
+        id: UrlID,
+        reason: ReportReason,
+        note: Option<String>,
+    ) -> Result<Void, AppError> {
+        let url = Url::find_visible(ctx, id).await?;
+        Report::create(ctx, &url, reason, note).await?;
+        Void::ok()
+    }
+
+    /// Resolve all open reports of the given URL by either dismissing
+    /// them, removing the URL, or banning its submitter. This is only
+    /// available to administrators and moderators.
+    async fn resolve_report(
+        ctx: &Context,
+        url_id: UrlID,
+        action: ModerationAction,
+    ) -> Result<Void, AppError> {
+        Report::resolve(ctx, url_id, action).await?;
+        Void::ok()
+    }
+
+    /// Edit the title or description of a submitted URL. Submitters
+    /// may edit their submissions for a short while after submitting,
+    /// administrators at any time. An NSFW mark set by a moderator
+    /// can not be cleared by the submitter. Submitters may list or
+    /// unlist their submissions at any time, and making a submission
+    /// public lists it like a new submission. Scheduled drafts can be
+    /// rescheduled or unscheduled until they are published.
+    async fn update_url(ctx: &Context, id: UrlID, input: UpdateUrlInput) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.update(ctx, input).await?;
+        Ok(url)
+    }
+
+    /// Replace the tags of a submitted URL. Tags which don't exist
+    /// yet are created. This follows the same rules as `updateUrl`.
+    async fn set_url_tags(ctx: &Context, id: UrlID, tags: Vec<String>) -> Result<Url, AppError> {
+        let url = Url::find_visible(ctx, id).await?;
+        url.set_tags(ctx, &tags).await?;
+        Ok(url)
     }
 
     /// Deletes a submitted URL. URLs can only be deleted by moderators
-    /// or the user who originally submitted them.
-    async fn delete_url(ctx: &Context, url: UrlID) -> FieldResult<Url> {
-        let url = Url::find(ctx, url).await?;
+    /// or the user who originally submitted them. Deleting a URL twice
+    /// has no further effect.
+    async fn delete_url(ctx: &Context, id: UrlID) -> Result<Void, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
         url.delete(ctx).await?;
+        Void::ok()
+    }
+
+    /// Publish one of the viewer's drafts, e.g. an imported bookmark,
+    /// such that it is listed like any other submission. Publishing
+    /// a scheduled draft publishes it right away.
+    async fn publish_url(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.publish(ctx).await?;
+        Ok(url)
+    }
+
+    /// Import bookmarks from a browser export in the Netscape bookmark
+    /// file format, which all major browsers produce. Bookmarks are
+    /// either saved privately, or created as drafts of the viewer, and
+    /// their folders become tags. Bookmarks which were already saved or
+    /// submitted are skipped.
+    async fn import_bookmarks(
+        ctx: &Context,
+        file: Upload,
+        visibility: ImportVisibility,
+    ) -> Result<ImportReport, AppError> {
+        ctx.verified_user().await?;
+        let file = ctx
+            .upload(file.key())
+            .ok_or_else(|| AppError::ValidationFailed(format!("Missing upload {}", file.key())))?;
+        Ok(models::import_bookmarks(ctx, file, visibility).await?)
+    }
+
+    /// Import the bookmarks the viewer stored with a remote service, like
+    /// Pinboard or Pocket, into their reading list, keeping tags, read
+    /// status, and creation times. The import runs in the background,
+    /// and its progress is listed by `Viewer.imports`. Only one import
+    /// from each service can be in progress at any time.
+    async fn import_from_service(
+        ctx: &Context,
+        service: ImportService,
+        credentials: String,
+    ) -> Result<ServiceImport, AppError> {
+        Ok(ServiceImport::request(ctx, service, &credentials).await?)
+    }
+
+    /// Create a webhook, which receives the given events as JSON
+    /// payloads posted to the given URL. Each payload is signed with
+    /// the given secret, see `X-Urls-Signature`. Webhooks receive the
+    /// events concerning the submissions of the viewer, or all events
+    /// if they are site wide, which requires administrator permissions.
+    async fn create_webhook(
+        ctx: &Context,
+        url: String,
+        events: Vec<WebhookEvent>,
+        secret: String,
+        site_wide: Option<bool>,
+    ) -> Result<Webhook, AppError> {
+        Ok(Webhook::create(ctx, &url, &events, &secret, site_wide.unwrap_or(false)).await?)
+    }
+
+    /// Delete a webhook of the viewer, or a site wide webhook, along
+    /// with its delivery log. Pending deliveries are not sent.
+    async fn delete_webhook(ctx: &Context, id: WebhookID) -> Result<Void, AppError> {
+        let webhook = Webhook::find(ctx, id).await?;
+        webhook.delete(ctx).await?;
+        Void::ok()
+    }
+
+    /// Pin one of the viewer's own submissions to the top of their
+    /// profile. At most three submissions can be pinned at a time.
+    async fn pin_url(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.pin(ctx).await?;
+        Ok(url)
+    }
+
+    /// Unpin one of the viewer's own submissions from their profile.
+    async fn unpin_url(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.unpin(ctx).await?;
+        Ok(url)
+    }
+
+    /// Removes a submission as a moderator, giving a reason which is
+    /// recorded in the moderation log. Removed submissions are hidden
+    /// everywhere and their page shows that they were removed by a
+    /// moderator. Shadow removed submissions are only hidden from users
+    /// other than their author and moderators. Moderators of a group
+    /// may remove its submissions.
+    async fn remove_url(
+        ctx: &Context,
+        id: UrlID,
+        reason: String,
+        #[graphql(default = false)] shadow: bool,
+    ) -> Result<Void, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.remove(ctx, &reason, shadow).await?;
+        Void::ok()
+    }
+
+    /// Releases a submission the spam filter held for review as a
+    /// moderator, publishing it. Held submissions are rejected by
+    /// removing them instead.
+    async fn release_url(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.release(ctx).await?;
+        Ok(url)
+    }
+
+    /// Releases a comment the spam filter held for review as a
+    /// moderator, publishing it. Held comments are rejected by
+    /// deleting them instead.
+    async fn release_comment(ctx: &Context, id: CommentID) -> Result<Comment, AppError> {
+        let mut comment = Comment::find(ctx, id).await?;
+        comment.release(ctx).await?;
+        Ok(comment)
+    }
+
+    /// Locks the discussion of a submission as a moderator, with an
+    /// optional reason shown with the discussion. Existing comments
+    /// stay visible, but new comments, votes on comments and edits
+    /// of comments are rejected with the `LOCKED` error code, except
+    /// for administrators.
+    async fn lock_url(ctx: &Context, id: UrlID, reason: Option<String>) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.lock(ctx, reason).await?;
         Ok(url)
     }
 
+    /// Unlocks the discussion of a submission as a moderator.
+    async fn unlock_url(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.unlock(ctx).await?;
+        Ok(url)
+    }
+
+    /// Restores a deleted URL. URLs can only be restored by
+    /// administrators.
+    async fn restore_url(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.restore(ctx).await?;
+        Ok(url)
+    }
+
+    /// Checks whether the link of a submission still works right away,
+    /// rather than waiting for the next periodic check. This is only
+    /// available to administrators, and is subject to the same limits
+    /// per domain as periodic checks.
+    async fn recheck_url(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.recheck_link(ctx).await?;
+        Ok(url)
+    }
+
+    /// Vote on the given URL as the viewer, returning the URL with
+    /// its updated score. Voting twice in the same direction has no
+    /// further effect, voting in the other direction changes the vote.
+    /// Downvotes are rejected unless enabled on the server, and
+    /// require the viewer to have some karma.
+    async fn vote_url(
+        ctx: &Context,
+        id: UrlID,
+        #[graphql(default = VoteDirection::Up)] direction: VoteDirection,
+    ) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.vote(ctx, direction).await?;
+        Ok(url)
+    }
+
+    /// Rescind a previous vote for the given URL, returning the URL
+    /// with its updated score.
+    async fn unvote_url(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, id).await?;
+        url.unvote(ctx).await?;
+        Ok(url)
+    }
+
+    /// Vote for the given comment as the viewer, returning the
+    /// comment with its updated score. Voting twice has no further
+    /// effect, and deleted comments can not be voted on.
+    async fn vote_comment(ctx: &Context, id: CommentID) -> Result<Comment, AppError> {
+        let mut comment = Comment::find(ctx, id).await?;
+        comment.vote(ctx).await?;
+        Ok(comment)
+    }
+
+    /// Rescind a previous vote for the given comment, returning
+    /// the comment with its updated score.
+    async fn unvote_comment(ctx: &Context, id: CommentID) -> Result<Comment, AppError> {
+        let mut comment = Comment::find(ctx, id).await?;
+        comment.unvote(ctx).await?;
+        Ok(comment)
+    }
+
+    /// Record that the viewer opened the discussion of the given
+    /// submission, which counts towards `Url.views` at most once per
+    /// viewer and day. The view is stored in the background, so this
+    /// succeeds even if the view is not counted.
+    async fn record_url_view(ctx: &Context, id: UrlID) -> Result<Void, AppError> {
+        UrlView::record(ctx, id);
+        Void::ok()
+    }
+
+    /// Save the given URL to the reading list of the viewer. Saving
+    /// an already saved URL moves it to the top of the reading list.
+    async fn save_url(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let url = Url::find_visible(ctx, id).await?;
+        SavedUrl::save(ctx, &url).await?;
+        Ok(url)
+    }
+
+    /// Remove the given URL from the reading list of the viewer.
+    async fn unsave_url(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let url = Url::find_visible(ctx, id).await?;
+        SavedUrl::unsave(ctx, url.id()).await?;
+        Ok(url)
+    }
+
+    /// Mark the given URL as read in the reading list of the viewer.
+    /// URLs which are not saved yet are saved as read.
+    async fn mark_url_read(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let url = Url::find_visible(ctx, id).await?;
+        SavedUrl::mark(ctx, &url, true).await?;
+        Ok(url)
+    }
+
+    /// Mark the given URL as unread in the reading list of the viewer.
+    /// URLs which are not saved yet are saved as unread.
+    async fn mark_url_unread(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        let url = Url::find_visible(ctx, id).await?;
+        SavedUrl::mark(ctx, &url, false).await?;
+        Ok(url)
+    }
+
+    /// Create a collection, a list of URLs curated by the viewer.
+    async fn create_collection(
+        ctx: &Context,
+        input: NewCollectionInput,
+    ) -> Result<Collection, AppError> {
+        Ok(Collection::create(ctx, input).await?)
+    }
+
+    /// Update a collection of the viewer.
+    async fn update_collection(
+        ctx: &Context,
+        id: CollectionID,
+        input: UpdateCollectionInput,
+    ) -> Result<Collection, AppError> {
+        Ok(Collection::update(ctx, id, input).await?)
+    }
+
+    /// Delete a collection of the viewer. The URLs in
+    /// it are not affected.
+    async fn delete_collection(ctx: &Context, id: CollectionID) -> Result<Void, AppError> {
+        Collection::delete(ctx, id).await?;
+        Void::ok()
+    }
+
+    /// Add a URL to the end of a collection of the viewer, with an
+    /// optional note. Adding a URL which is in the collection already
+    /// replaces its note.
+    async fn add_to_collection(
+        ctx: &Context,
+        collection_id: CollectionID,
+        url_id: UrlID,
+        note: Option<String>,
+    ) -> Result<Collection, AppError> {
+        let url = Url::find_visible(ctx, url_id).await?;
+        Ok(Collection::add_item(ctx, collection_id, &url, note).await?)
+    }
+
+    /// Remove a URL from a collection of the viewer.
+    async fn remove_from_collection(
+        ctx: &Context,
+        collection_id: CollectionID,
+        url_id: UrlID,
+    ) -> Result<Collection, AppError> {
+        Ok(Collection::remove_item(ctx, collection_id, url_id).await?)
+    }
+
+    /// Move a URL to the given position in a collection of the
+    /// viewer, starting at zero. Positions past the end move the
+    /// URL to the end.
+    async fn move_collection_item(
+        ctx: &Context,
+        collection_id: CollectionID,
+        url_id: UrlID,
+        position: i32,
+    ) -> Result<Collection, AppError> {
+        Ok(Collection::move_item(ctx, collection_id, url_id, position).await?)
+    }
+
+    /// Create a group, a space for submissions on a topic. The
+    /// viewer becomes its first member, and moderates it.
+    async fn create_group(ctx: &Context, input: NewGroupInput) -> Result<Group, AppError> {
+        Ok(Group::create(ctx, input).await?)
+    }
+
+    /// Update a group created by the viewer.
+    async fn update_group(
+        ctx: &Context,
+        id: GroupID,
+        input: UpdateGroupInput,
+    ) -> Result<Group, AppError> {
+        let mut group = Group::find(ctx, id).await?;
+        group.update(ctx, input).await?;
+        Ok(group)
+    }
+
+    /// Join a group as the viewer, which allows submitting to it.
+    async fn join_group(ctx: &Context, id: GroupID) -> Result<Group, AppError> {
+        let mut group = Group::find(ctx, id).await?;
+        group.join(ctx).await?;
+        Ok(group)
+    }
+
+    /// Leave a group as the viewer. Submissions to the
+    /// group stay in it.
+    async fn leave_group(ctx: &Context, id: GroupID) -> Result<Group, AppError> {
+        let mut group = Group::find(ctx, id).await?;
+        group.leave(ctx).await?;
+        Ok(group)
+    }
+
+    /// Appoint a member of a group created by the viewer as a
+    /// moderator of the group. Group moderators may remove, lock,
+    /// and release its submissions, and delete comments on them.
+    async fn appoint_group_moderator(
+        ctx: &Context,
+        group_id: GroupID,
+        user_id: UserID,
+    ) -> Result<Group, AppError> {
+        let group = Group::find(ctx, group_id).await?;
+        group.appoint_moderator(ctx, user_id).await?;
+        Ok(group)
+    }
+
+    /// Dismiss a moderator of a group created by the viewer.
+    async fn dismiss_group_moderator(
+        ctx: &Context,
+        group_id: GroupID,
+        user_id: UserID,
+    ) -> Result<Group, AppError> {
+        let group = Group::find(ctx, group_id).await?;
+        group.dismiss_moderator(ctx, user_id).await?;
+        Ok(group)
+    }
+
+    /// Mark the given notification of the viewer as read.
+    async fn mark_notification_read(
+        ctx: &Context,
+        id: NotificationID,
+    ) -> Result<Notification, AppError> {
+        let mut notification = Notification::find(ctx, id).await?;
+        notification.mark_read(ctx).await?;
+        Ok(notification)
+    }
+
+    /// Mark all notifications of the viewer as read.
+    async fn mark_all_notifications_read(ctx: &Context) -> Result<Void, AppError> {
+        Notification::mark_all_read(ctx).await?;
+        Void::ok()
+    }
+
     /// Upvote the given URL as the viewer.
-    async fn upvote_url(ctx: &Context, url: UrlID) -> FieldResult<Url> {
-        let url = Url::find(ctx, url).await?;
-        url.upvote(ctx).await?;
+    #[graphql(deprecated = "Use `voteUrl`")]
+    async fn upvote_url(ctx: &Context, url: UrlID) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, url).await?;
+        url.vote(ctx, VoteDirection::Up).await?;
         Ok(url)
     }
 
     /// Rescind a previous upvote for the given URL.
-    async fn rescind_url_upvote(ctx: &Context, url: UrlID) -> FieldResult<Url> {
-        let url = Url::find(ctx, url).await?;
-        url.rescind_upvote(ctx).await?;
+    #[graphql(deprecated = "Use `unvoteUrl`")]
+    async fn rescind_url_upvote(ctx: &Context, url: UrlID) -> Result<Url, AppError> {
+        let mut url = Url::find_visible(ctx, url).await?;
+        url.unvote(ctx).await?;
         Ok(url)
     }
 
-    /// Comment on the given URL as the viewer.
-    async fn comment(ctx: &Context, input: NewCommentInput) -> FieldResult<Comment> {
+    /// Block the given user for the viewer. Submissions and comments
+    /// of blocked users are hidden from the viewer, and blocked users
+    /// can not comment on the submissions of the viewer.
+    async fn block_user(ctx: &Context, user_id: UserID) -> Result<Void, AppError> {
+        Block::create(ctx, user_id).await?;
+        Void::ok()
+    }
+
+    /// Unblock a previously blocked user for the viewer.
+    async fn unblock_user(ctx: &Context, user_id: UserID) -> Result<Void, AppError> {
+        Block::delete(ctx, user_id).await?;
+        Void::ok()
+    }
+
+    /// Follow the given user as the viewer, listing their submissions
+    /// in the following feed of the viewer. Banned users, and users
+    /// who blocked the viewer or were blocked by them, can not be
+    /// followed.
+    async fn follow_user(ctx: &Context, user_id: UserID) -> Result<User, AppError> {
+        Follow::create(ctx, user_id).await?;
+        Ok(User::find(ctx, user_id).await?)
+    }
+
+    /// Unfollow a previously followed user for the viewer.
+    async fn unfollow_user(ctx: &Context, user_id: UserID) -> Result<User, AppError> {
+        Follow::delete(ctx, user_id).await?;
+        Ok(User::find(ctx, user_id).await?)
+    }
+
+    /// Follow the given tag as the viewer, listing its submissions
+    /// in the following feed of the viewer.
+    async fn follow_tag(ctx: &Context, name: String) -> Result<Tag, AppError> {
+        Ok(Tag::follow(ctx, &name).await?)
+    }
+
+    /// Unfollow a previously followed tag for the viewer.
+    async fn unfollow_tag(ctx: &Context, name: String) -> Result<Void, AppError> {
+        Tag::unfollow(ctx, &name).await?;
+        Void::ok()
+    }
+
+    /// Rename a tag as an administrator. Submissions and followers
+    /// of the tag keep it under its new name, and the old name
+    /// redirects to the new one, such that old links and filters
+    /// keep working. Tags can not be renamed to the name of an
+    /// existing tag, see `mergeTags`. The rename is recorded in
+    /// the tag log.
+    async fn rename_tag(ctx: &Context, from: String, to: String) -> Result<Tag, AppError> {
+        Ok(Tag::rename(ctx, &from, &to).await?)
+    }
+
+    /// Merge the given tags into the target tag as an administrator,
+    /// creating the target if it doesn't exist. Submissions and
+    /// followers of the merged tags move to the target, submissions
+    /// which had several of the tags get the target once, and the
+    /// merged tags redirect to the target. The merge is recorded in
+    /// the tag log.
+    async fn merge_tags(
+        ctx: &Context,
+        sources: Vec<String>,
+        target: String,
+    ) -> Result<Tag, AppError> {
+        Ok(Tag::merge(ctx, &sources, &target).await?)
+    }
+
+    /// Mute the given domain for the viewer. Submissions from muted
+    /// domains are hidden from the front page, tag listings, and search
+    /// results of the viewer. Any host name or URL of the domain may be
+    /// given, e.g. `blog.example.co.uk` mutes `example.co.uk`.
+    async fn mute_domain(ctx: &Context, domain: String) -> Result<Void, AppError> {
+        MutedDomain::create(ctx, &domain).await?;
+        Void::ok()
+    }
+
+    /// Unmute a previously muted domain for the viewer.
+    async fn unmute_domain(ctx: &Context, domain: String) -> Result<Void, AppError> {
+        MutedDomain::delete(ctx, &domain).await?;
+        Void::ok()
+    }
+
+    /// Comment on the given URL as the viewer. Comments the spam
+    /// filter suspects are held for review by a moderator, and spam
+    /// is rejected.
+    async fn comment(ctx: &Context, input: NewCommentInput) -> Result<Comment, AppError> {
+        ctx.verified_user().await?;
         Ok(Comment::create(ctx, input).await?)
     }
 
+    /// Comment on the given URL as the viewer, optionally replying to
+    /// the comment `parentId` on the same URL. This is a shorthand for
+    /// `comment`.
+    async fn add_comment(
+        ctx: &Context,
+        url_id: UrlID,
+        body: String,
+        parent_id: Option<CommentID>,
+    ) -> Result<Comment, AppError> {
+        ctx.verified_user().await?;
+        let input = match parent_id {
+            Some(parent_id) => NewCommentInput::reply(url_id, parent_id, body),
+            None => NewCommentInput::new(url_id, body),
+        };
+        Ok(Comment::create(ctx, input).await?)
+    }
+
+    /// Edit the text of the given comment. Authors may edit their
+    /// comments for a short while after commenting, administrators
+    /// at any time.
+    async fn update_comment(
+        ctx: &Context,
+        id: CommentID,
+        body: String,
+    ) -> Result<Comment, AppError> {
+        let mut comment = Comment::find(ctx, id).await?;
+        comment.update(ctx, body).await?;
+        Ok(comment)
+    }
+
     /// Delete the given comment. Only the original author, or a moderator
-    /// is allowed to delete comments.
-    async fn delete_comment(ctx: &Context, comment: CommentID) -> FieldResult<Comment> {
+    /// is allowed to delete comments. Moderators may give a `reason`,
+    /// which is only visible to other moderators.
+    async fn delete_comment(
+        ctx: &Context,
+        comment: CommentID,
+        reason: Option<String>,
+    ) -> Result<Comment, AppError> {
         let mut comment = Comment::find(ctx, comment).await?;
-        comment.delete(ctx).await?;
+        comment.delete(ctx, reason).await?;
         Ok(comment)
     }
 }
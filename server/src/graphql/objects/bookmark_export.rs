@@ -0,0 +1,49 @@
+use crate::db::id::BookmarkExportID;
+use crate::db::models::{BookmarkExport, DataExportStatus, ExportFormat};
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+
+#[graphql_object(context = Context)]
+impl BookmarkExport {
+    /// A globally unique identifier for this
+    /// export.
+    fn id(&self) -> BookmarkExportID {
+        self.id()
+    }
+
+    /// The file format of the export.
+    fn format(&self) -> ExportFormat {
+        self.format()
+    }
+
+    /// Whether the export is still being generated,
+    /// or is available for download.
+    fn status(&self) -> DataExportStatus {
+        self.status()
+    }
+
+    /// The time at which the export was requested.
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+
+    /// The time at which the export was generated,
+    /// if it completed.
+    fn completed_at(&self) -> Option<DateTime<Utc>> {
+        self.completed_at()
+    }
+
+    /// The time after which the export can no
+    /// longer be downloaded, if it completed.
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at()
+    }
+
+    /// A link to download the export from, which does not
+    /// require being logged in. Only available once the
+    /// export is ready, and until it expires.
+    fn download_url(&self, ctx: &Context) -> Option<String> {
+        self.download_url(ctx)
+    }
+}
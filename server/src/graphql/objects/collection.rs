@@ -0,0 +1,138 @@
+use crate::db::id::CollectionID;
+use crate::db::models::{
+    Collection, CollectionItem, CollectionItemCursor, CollectionVisibility, Url, User,
+};
+use crate::error::AppError;
+use crate::graphql::connection;
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+use juniper_relay_connection::{RelayConnection, RelayConnectionNode};
+use std::convert::TryInto;
+
+impl RelayConnectionNode for Collection {
+    type Cursor = CollectionID;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.id()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "CollectionConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "CollectionConnectionEdge"
+    }
+}
+
+/// A submission in a collection, along with the item
+/// holding its position and note.
+pub struct CollectionEntry {
+    item: CollectionItem,
+    url: Url,
+}
+
+impl RelayConnectionNode for CollectionEntry {
+    type Cursor = CollectionItemCursor;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.item.cursor()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "CollectionItemConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "CollectionItemConnectionEdge"
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Collection {
+    /// A globally unique identifier for this
+    /// collection.
+    fn id(&self) -> CollectionID {
+        self.id()
+    }
+
+    fn title(&self) -> &str {
+        self.title()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description()
+    }
+
+    fn visibility(&self) -> CollectionVisibility {
+        self.visibility()
+    }
+
+    /// The user who curates this collection.
+    async fn owner(&self, ctx: &Context) -> Result<User, AppError> {
+        Ok(self.owner(ctx).await?)
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+
+    /// When this collection or its items were
+    /// last changed.
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at()
+    }
+
+    /// The number of submissions in this collection.
+    async fn item_count(&self, ctx: &Context) -> Result<i32, AppError> {
+        Ok(self.item_count(ctx).await?.try_into()?)
+    }
+
+    /// The submissions in this collection, in the order chosen
+    /// by its owner. Deleted submissions, and submissions hidden
+    /// from the viewer, are excluded.
+    async fn items(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<RelayConnection<CollectionEntry>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            None,
+            None,
+            |after, before, limit| async move {
+                let items = self.items(ctx, after, before, limit).await?;
+                Ok(items
+                    .into_iter()
+                    .map(|(item, url)| CollectionEntry { item, url })
+                    .collect())
+            },
+        )
+        .await
+    }
+}
+
+#[graphql_object(context = Context, name = "CollectionItem")]
+impl CollectionEntry {
+    fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The position of this submission in the collection,
+    /// starting at zero.
+    fn position(&self) -> i32 {
+        self.item.position()
+    }
+
+    /// Why the owner added this submission.
+    fn note(&self) -> Option<&str> {
+        self.item.note()
+    }
+
+    fn added_at(&self) -> DateTime<Utc> {
+        self.item.added_at()
+    }
+}
@@ -1,17 +1,39 @@
 use crate::db::id::CommentID;
-use crate::db::models::{Comment, Url, User};
+use crate::db::models::{Block, Comment, CommentCursor, CommentSort, Revision, Url, User};
+use crate::error::AppError;
+use crate::graphql::connection;
 use crate::schema::comments;
 use crate::Context;
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
-use juniper::{graphql_object, FieldResult};
+use juniper::meta::MetaType;
+use juniper::{
+    graphql_object, marker, Arguments, BoxFuture, ExecutionResult, Executor, GraphQLType,
+    GraphQLValue, GraphQLValueAsync, Registry, ScalarValue, Selection,
+};
 use juniper_relay_connection::{RelayConnection, RelayConnectionNode};
+use std::convert::TryInto;
 
-impl RelayConnectionNode for Comment {
-    type Cursor = CommentID;
+/// A comment in a list sorted in a given order, which hands out
+/// cursors that can't be replayed against a different order. This
+/// resolves exactly like a [`Comment`].
+pub(crate) struct CursorComment {
+    comment: Comment,
+    cursor: CommentCursor,
+}
+
+impl CursorComment {
+    pub(crate) fn sorted(comment: Comment, sort: CommentSort) -> Self {
+        let cursor = comment.cursor(sort);
+        Self { comment, cursor }
+    }
+}
+
+impl RelayConnectionNode for CursorComment {
+    type Cursor = CommentCursor;
 
     fn cursor(&self) -> Self::Cursor {
-        self.id()
+        self.cursor
     }
 
     fn connection_type_name() -> &'static str {
@@ -23,6 +45,70 @@ impl RelayConnectionNode for Comment {
     }
 }
 
+impl<S> GraphQLType<S> for CursorComment
+where
+    S: ScalarValue,
+{
+    fn name(info: &()) -> Option<&str> {
+        <Comment as GraphQLType<S>>::name(info)
+    }
+
+    fn meta<'r>(info: &(), registry: &mut Registry<'r, S>) -> MetaType<'r, S>
+    where
+        S: 'r,
+    {
+        <Comment as GraphQLType<S>>::meta(info, registry)
+    }
+}
+
+impl<S> GraphQLValue<S> for CursorComment
+where
+    S: ScalarValue,
+{
+    type Context = Context;
+    type TypeInfo = ();
+
+    fn type_name<'i>(&self, info: &'i ()) -> Option<&'i str> {
+        <Comment as GraphQLValue<S>>::type_name(&self.comment, info)
+    }
+
+    fn resolve(
+        &self,
+        info: &(),
+        selection_set: Option<&[Selection<S>]>,
+        executor: &Executor<Context, S>,
+    ) -> ExecutionResult<S> {
+        self.comment.resolve(info, selection_set, executor)
+    }
+}
+
+impl<S> GraphQLValueAsync<S> for CursorComment
+where
+    S: ScalarValue + Send + Sync,
+{
+    fn resolve_async<'a>(
+        &'a self,
+        info: &'a (),
+        selection_set: Option<&'a [Selection<S>]>,
+        executor: &'a Executor<Context, S>,
+    ) -> BoxFuture<'a, ExecutionResult<S>> {
+        self.comment.resolve_async(info, selection_set, executor)
+    }
+
+    fn resolve_field_async<'a>(
+        &'a self,
+        info: &'a (),
+        field_name: &'a str,
+        arguments: &'a Arguments<S>,
+        executor: &'a Executor<Context, S>,
+    ) -> BoxFuture<'a, ExecutionResult<S>> {
+        self.comment
+            .resolve_field_async(info, field_name, arguments, executor)
+    }
+}
+
+impl<S> marker::IsOutputType<S> for CursorComment where S: ScalarValue {}
+
 #[graphql_object(context = Context)]
 impl Comment {
     /// A globally unique identifier for this
@@ -32,19 +118,40 @@ impl Comment {
     }
 
     /// The raw markdown text content of this comment.
+    fn body(&self) -> &str {
+        self.text()
+    }
+
+    /// An html rendered version of this comment. The raw
+    /// markdown has been sanitized and can be considered safe.
+    /// Raw html is escaped, links to other sites are marked as
+    /// `nofollow ugc`, and bare URLs are linked. Mentions of users
+    /// link to their profiles, and hashtags of existing tags to
+    /// the feed of the tag.
+    fn body_html(&self) -> &str {
+        self.html()
+    }
+
+    /// The raw markdown text content of this comment.
+    #[graphql(deprecated = "Use `body`")]
     fn text(&self) -> &str {
         self.text()
     }
 
-    /// An html rendered version of this comment. The
-    /// raw markdown has been sanitized and can be considered
-    /// safe.
-    fn html(&self) -> String {
+    /// An html rendered version of this comment.
+    #[graphql(deprecated = "Use `bodyHtml`")]
+    fn html(&self) -> &str {
         self.html()
     }
 
+    /// The users mentioned in this comment as `@username`,
+    /// ordered by username.
+    async fn mentions(&self, ctx: &Context) -> Result<Vec<User>, AppError> {
+        Ok(self.mentions(ctx).await?)
+    }
+
     /// The URL that was commented on.
-    async fn url(&self, ctx: &Context) -> FieldResult<Url> {
+    async fn url(&self, ctx: &Context) -> Result<Url, AppError> {
         Ok(self.url(ctx).await?)
     }
 
@@ -53,18 +160,110 @@ impl Comment {
         self.created_at()
     }
 
+    /// The last time the author edited this comment, if ever.
+    fn edited_at(&self) -> Option<DateTime<Utc>> {
+        self.edited_at()
+    }
+
+    /// The number of times the text of this comment was
+    /// changed or deleted, see `revisions`.
+    fn revision_count(&self) -> Result<i32, AppError> {
+        Ok(self.revision_count().try_into()?)
+    }
+
+    /// Previous versions of the text of this comment, most
+    /// recent first. The edit history is only visible to the
+    /// author and administrators, unless the server makes it
+    /// public for comments which were not deleted.
+    async fn revisions(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<Revision>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                Ok(Revision::for_comment(ctx, self, after, before, limit).await?)
+            },
+        )
+        .await
+    }
+
+    /// Whether the spam filter held this comment for review by
+    /// a moderator. Held comments are only visible to their author
+    /// and moderators until a moderator releases them.
+    fn held(&self) -> bool {
+        self.is_held()
+    }
+
+    /// The time this comment was deleted, if it was. Deleted
+    /// comments are only listed while they have replies, and
+    /// their text is replaced with a placeholder.
+    fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at()
+    }
+
+    /// Why a moderator deleted this comment, if a reason was
+    /// given. This is only visible to moderators.
+    async fn deletion_reason(&self, ctx: &Context) -> Result<Option<String>, AppError> {
+        let may_view = match ctx.maybe_user().await? {
+            Some(viewer) => viewer
+                .permissions(ctx)
+                .await?
+                .iter()
+                .any(|perm| perm.delete_any_comment()),
+            None => false,
+        };
+        if may_view {
+            Ok(self.deletion_reason().map(str::to_string))
+        } else {
+            Ok(None)
+        }
+    }
+
     /// The user who made this comment.
-    async fn created_by(&self, ctx: &Context) -> FieldResult<User> {
+    async fn created_by(&self, ctx: &Context) -> Result<User, AppError> {
         Ok(self.created_by(ctx).await?)
     }
 
     /// The comment which was directly replied to,
     /// is any.
-    async fn replies_to(&self, ctx: &Context) -> FieldResult<Option<Comment>> {
+    async fn replies_to(&self, ctx: &Context) -> Result<Option<Comment>, AppError> {
         Ok(self.replies_to(ctx).await?)
     }
 
-    /// Comments which directly reply to this comment.
+    /// How deeply this comment is nested. Comments which
+    /// don't reply to another comment are at depth zero.
+    fn depth(&self) -> i32 {
+        self.depth()
+    }
+
+    /// The number of comments which directly reply to
+    /// this comment.
+    fn reply_count(&self) -> Result<i32, AppError> {
+        Ok(self.reply_count().try_into()?)
+    }
+
+    /// The number of users who voted for this comment.
+    fn score(&self) -> Result<i32, AppError> {
+        Ok(self.score().try_into()?)
+    }
+
+    /// If the viewer voted for this comment.
+    async fn viewer_has_voted(&self, ctx: &Context) -> Result<bool, AppError> {
+        Ok(self.viewer_has_voted(ctx).await?)
+    }
+
+    /// Comments which directly reply to this comment, excluding
+    /// those by users blocked by the viewer, and deleted comments
+    /// without replies. Replies are listed in the order they were
+    /// written.
     async fn replies(
         &self,
         ctx: &Context,
@@ -72,29 +271,28 @@ impl Comment {
         after: Option<String>,
         last: Option<i32>,
         before: Option<String>,
-    ) -> FieldResult<RelayConnection<Comment>> {
+    ) -> Result<RelayConnection<CursorComment>, AppError> {
+        let sort = CommentSort::Old;
         let conn = ctx.conn().await?;
-        RelayConnection::new(first, after, last, before, |after, before, limit| {
+        connection::new(first, after, last, before, |after, before, limit| {
             let mut query = comments::table
                 .filter(comments::dsl::replies_to.eq(self.id()))
-                .order_by(comments::dsl::created_at.asc())
+                .filter(Comment::not_held(ctx))
+                .filter(
+                    comments::dsl::deleted_at
+                        .is_null()
+                        .or(comments::dsl::reply_count.gt(0)),
+                )
                 .into_boxed();
-
-            if let Some(after) = after {
-                let after: Comment = comments::table.find(after).get_result(&*conn)?;
-                query = query.filter(comments::dsl::created_at.gt(after.created_at().naive_utc()));
-            }
-
-            if let Some(before) = before {
-                let before: Comment = comments::table.find(before).get_result(&*conn)?;
-                query = query.filter(comments::dsl::created_at.lt(before.created_at().naive_utc()));
-            }
-
-            if let Some(limit) = limit {
-                query = query.limit(limit);
+            if let Some(viewer) = ctx.maybe_user_id() {
+                query =
+                    query.filter(comments::dsl::created_by.ne_all(Block::hidden_authors(viewer)));
             }
-
-            Ok(query.load(&*conn)?)
+            let comments = Comment::paginate(&*conn, query, sort, after, before, limit)?;
+            Ok(comments
+                .into_iter()
+                .map(|comment| CursorComment::sorted(comment, sort))
+                .collect())
         })
     }
 }
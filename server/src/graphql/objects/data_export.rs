@@ -0,0 +1,37 @@
+use crate::db::id::DataExportID;
+use crate::db::models::{DataExport, DataExportStatus};
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+
+#[graphql_object(context = Context)]
+impl DataExport {
+    /// A globally unique identifier for this
+    /// export.
+    fn id(&self) -> DataExportID {
+        self.id()
+    }
+
+    /// Whether the export is still being assembled,
+    /// or is available for download.
+    fn status(&self) -> DataExportStatus {
+        self.status()
+    }
+
+    /// The time at which the export was requested.
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+
+    /// The time at which the export archive was
+    /// assembled, if it completed.
+    fn completed_at(&self) -> Option<DateTime<Utc>> {
+        self.completed_at()
+    }
+
+    /// The time after which the download link sent
+    /// by email stops working, if the export completed.
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at()
+    }
+}
@@ -0,0 +1,46 @@
+use crate::db::models::{FeedSource, Url, UrlCursor, UrlSort};
+use crate::Context;
+use juniper::graphql_object;
+use juniper_relay_connection::RelayConnectionNode;
+
+/// A submission in the following feed of the viewer, along with
+/// why it is listed. Edges of relay connections only hold a node and
+/// a cursor, so the source is attached to the node.
+pub(crate) struct FeedItem {
+    url: Url,
+    source: FeedSource,
+}
+
+impl FeedItem {
+    pub(crate) fn new(url: Url, source: FeedSource) -> Self {
+        Self { url, source }
+    }
+}
+
+impl RelayConnectionNode for FeedItem {
+    type Cursor = UrlCursor;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.url.cursor(UrlSort::Newest)
+    }
+
+    fn connection_type_name() -> &'static str {
+        "FollowingFeedConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "FollowingFeedConnectionEdge"
+    }
+}
+
+#[graphql_object(context = Context, name = "FollowingFeedItem")]
+impl FeedItem {
+    fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Why this submission is listed.
+    fn source(&self) -> &FeedSource {
+        &self.source
+    }
+}
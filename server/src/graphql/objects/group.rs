@@ -0,0 +1,136 @@
+use crate::db::id::GroupID;
+use crate::db::models::{
+    Group, GroupVisibility, TopRange, Url, UrlCursor, UrlFilter, UrlSort, User,
+};
+use crate::error::AppError;
+use crate::graphql::connection;
+use crate::graphql::objects::CursorUrl;
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+use juniper_relay_connection::{RelayConnection, RelayConnectionNode};
+use std::convert::TryInto;
+
+impl RelayConnectionNode for Group {
+    type Cursor = GroupID;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.id()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "GroupConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "GroupConnectionEdge"
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Group {
+    /// A globally unique identifier for this
+    /// group.
+    fn id(&self) -> GroupID {
+        self.id()
+    }
+
+    /// The unique slug used to link to this group.
+    fn slug(&self) -> &str {
+        self.slug()
+    }
+
+    fn title(&self) -> &str {
+        self.title()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description()
+    }
+
+    fn visibility(&self) -> GroupVisibility {
+        self.visibility()
+    }
+
+    /// Whether submissions to this group are
+    /// listed on the front page.
+    fn syndicated(&self) -> bool {
+        self.is_syndicated()
+    }
+
+    fn member_count(&self) -> Result<i32, AppError> {
+        Ok(self.member_count().try_into()?)
+    }
+
+    /// The user who created this group.
+    async fn creator(&self, ctx: &Context) -> Result<User, AppError> {
+        Ok(self.creator(ctx).await?)
+    }
+
+    /// The users who moderate this group, its
+    /// creator first.
+    async fn moderators(&self, ctx: &Context) -> Result<Vec<User>, AppError> {
+        Ok(self.moderators(ctx).await?)
+    }
+
+    /// Whether the viewer is a member of this group.
+    async fn viewer_is_member(&self, ctx: &Context) -> Result<bool, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(self.is_member(ctx, user_id).await?),
+            None => Ok(false),
+        }
+    }
+
+    /// Whether the viewer moderates this group.
+    async fn viewer_is_moderator(&self, ctx: &Context) -> Result<bool, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(self.is_moderator(ctx, user_id).await?),
+            None => Ok(false),
+        }
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at()
+    }
+
+    /// Submissions to this group in the given order, newest first
+    /// by default, excluding submissions which are hidden like on
+    /// the front page. The `TOP` order includes submissions of all
+    /// time.
+    async fn urls(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        #[graphql(default = UrlSort::Newest)] sort: UrlSort,
+    ) -> Result<RelayConnection<CursorUrl<UrlCursor>>, AppError> {
+        let filter = UrlFilter {
+            group: Some(self.id()),
+            range: match sort {
+                UrlSort::Top => Some(TopRange::All),
+                _ => None,
+            },
+            ..Default::default()
+        };
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                let urls = Url::all_submissions(ctx, filter, sort, after, before, limit).await?;
+                Ok(urls
+                    .into_iter()
+                    .map(|url| CursorUrl::sorted(url, sort))
+                    .collect())
+            },
+        )
+        .await
+    }
+}
@@ -1,7 +1,9 @@
 use crate::db::id::InviteID;
 use crate::db::models::{Invite, User};
+use crate::error::AppError;
 use crate::Context;
-use juniper::{graphql_object, FieldResult};
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
 use juniper_relay_connection::RelayConnectionNode;
 
 impl RelayConnectionNode for Invite {
@@ -35,13 +37,20 @@ impl Invite {
     }
 
     /// The user who issued this invitation.
-    async fn created_by(&self, ctx: &Context) -> FieldResult<User> {
+    async fn created_by(&self, ctx: &Context) -> Result<User, AppError> {
         Ok(self.created_by(ctx).await?)
     }
 
     /// The user who claimed this invitation when registering, or null
     /// if it has not been claimed yet.
-    async fn claimed_by(&self, ctx: &Context) -> FieldResult<Option<User>> {
+    async fn claimed_by(&self, ctx: &Context) -> Result<Option<User>, AppError> {
         Ok(self.claimed_by(ctx).await?)
     }
+
+    /// When this invitation was revoked, or null if it
+    /// was not revoked. Revoked invitations can not be
+    /// claimed.
+    fn revoked_at(&self) -> Option<DateTime<Utc>> {
+        self.revoked_at()
+    }
 }
@@ -0,0 +1,31 @@
+use crate::db::models::{InviteTree, User};
+use crate::Context;
+use juniper::graphql_object;
+
+#[graphql_object(context = Context)]
+impl InviteTree {
+    /// The user at this node of the tree.
+    fn user(&self) -> &User {
+        self.user()
+    }
+
+    /// Number of levels this node is below the
+    /// root of the tree.
+    fn depth(&self) -> i32 {
+        self.depth()
+    }
+
+    /// Number of users who registered using one of
+    /// this users invitations. This is accurate even
+    /// if the invitees were cut off by the depth limit.
+    fn invitee_count(&self) -> i32 {
+        self.invitee_count()
+    }
+
+    /// Users who registered using one of this users
+    /// invitations, oldest first. This is empty below
+    /// the requested depth.
+    fn invitees(&self) -> &[InviteTree] {
+        self.invitees()
+    }
+}
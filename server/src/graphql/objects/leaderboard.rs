@@ -0,0 +1,35 @@
+use crate::db::models::{LeaderboardCursor, LeaderboardEntry, User};
+use crate::error::AppError;
+use crate::Context;
+use juniper::graphql_object;
+use juniper_relay_connection::RelayConnectionNode;
+use std::convert::TryInto;
+
+impl RelayConnectionNode for LeaderboardEntry {
+    type Cursor = LeaderboardCursor;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.cursor()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "LeaderboardEntryConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "LeaderboardEntryConnectionEdge"
+    }
+}
+
+#[graphql_object(context = Context)]
+impl LeaderboardEntry {
+    fn user(&self) -> &User {
+        self.user()
+    }
+
+    /// Points the user earned within the
+    /// period of the leaderboard.
+    fn points(&self) -> Result<i32, AppError> {
+        Ok(self.points().try_into()?)
+    }
+}
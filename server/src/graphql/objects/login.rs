@@ -1,10 +1,10 @@
 use crate::db::id::LoginID;
-use crate::db::models::Login;
+use crate::db::models::{Login, LoginLocation};
+use crate::error::AppError;
 use crate::Context;
 use chrono::{DateTime, Utc};
-use juniper::{graphql_object, FieldResult, GraphQLObject};
+use juniper::{graphql_object, GraphQLObject};
 use juniper_relay_connection::RelayConnectionNode;
-use serde::Deserialize;
 
 impl RelayConnectionNode for Login {
     type Cursor = LoginID;
@@ -32,12 +32,16 @@ struct UserAgent<'a> {
     operating_system: &'a str,
 }
 
-#[derive(GraphQLObject, Deserialize)]
-struct LoginLocation {
-    ip_address: String,
-    country_code: Option<String>,
-    country_name: Option<String>,
-    city_name: Option<String>,
+impl<'a> UserAgent<'a> {
+    fn parse(raw: &'a str) -> Option<Self> {
+        use woothee::parser::Parser;
+        let parser = Parser::new();
+        parser.parse(raw).map(|res| UserAgent {
+            raw,
+            name: res.name,
+            operating_system: res.os,
+        })
+    }
 }
 
 #[graphql_object(context = Context)]
@@ -48,7 +52,27 @@ impl Login {
         self.id()
     }
 
-    /// Last time this login session was used.
+    /// The time at which this login session
+    /// was created.
+    fn created_at(&self) -> DateTime<Utc> {
+        self.claimed_at().unwrap_or_else(|| self.created_at())
+    }
+
+    /// The user agent that created this
+    /// session.
+    fn user_agent(&self) -> Option<UserAgent<'_>> {
+        self.user_agent().and_then(UserAgent::parse)
+    }
+
+    /// The IP address from which this session was created.
+    /// Depending on the server configuration, this may be
+    /// truncated or hashed.
+    fn ip_address(&self) -> Option<&str> {
+        self.stored_remote_ip()
+    }
+
+    /// Last time this login session was used. This
+    /// is updated at most once every five minutes.
     fn last_used(&self) -> DateTime<Utc> {
         self.last_used()
     }
@@ -56,31 +80,23 @@ impl Login {
     /// The user agent that last used this
     /// session.
     fn last_user_agent(&self) -> Option<UserAgent<'_>> {
-        self.last_user_agent().and_then(|raw| {
-            use woothee::parser::Parser;
-            let parser = Parser::new();
-            parser.parse(raw).map(|res| UserAgent {
-                raw,
-                name: res.name,
-                operating_system: res.os,
-            })
-        })
+        self.last_user_agent().and_then(UserAgent::parse)
+    }
+
+    /// The IP address which last used this session. Depending
+    /// on the server configuration, this may be truncated or
+    /// hashed.
+    fn last_ip_address(&self) -> Option<&str> {
+        self.last_stored_remote_ip()
     }
 
     /// The last known location from where this
     /// login session was used. This information
     /// is approximate and based on the remote IP
     /// address.
-    async fn last_location(&self, ctx: &Context) -> FieldResult<Option<LoginLocation>> {
+    async fn last_location(&self, ctx: &Context) -> Result<Option<LoginLocation>, AppError> {
         if let Some(ip_addr) = self.last_remote_ip() {
-            let location: LoginLocation = ctx
-                .http_client()
-                .get(format!("https://api.geoip.rs/?ip={}", ip_addr))
-                .send()
-                .await?
-                .json()
-                .await?;
-            Ok(Some(location))
+            Ok(LoginLocation::lookup(ctx, ip_addr).await?)
         } else {
             Ok(None)
         }
@@ -1,5 +1,27 @@
+mod bookmark_export;
+mod collection;
 mod comment;
+mod data_export;
+mod feed_item;
+mod group;
 mod invite;
+mod invite_tree;
+mod leaderboard;
 mod login;
+mod moderation_log;
+mod notification;
+mod preferences;
+mod report;
+mod revision;
+mod security_event;
+mod service_import;
+mod tag;
+mod tag_log;
 mod url;
+mod url_embed;
 mod user;
+mod webhook;
+
+pub(crate) use comment::CursorComment;
+pub(crate) use feed_item::FeedItem;
+pub(crate) use url::CursorUrl;
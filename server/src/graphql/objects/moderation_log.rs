@@ -0,0 +1,50 @@
+use crate::db::models::{ModerationLog, ModerationLogCursor, Url, User};
+use crate::error::AppError;
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+use juniper_relay_connection::RelayConnectionNode;
+
+impl RelayConnectionNode for ModerationLog {
+    type Cursor = ModerationLogCursor;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.cursor()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "ModerationLogConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "ModerationLogConnectionEdge"
+    }
+}
+
+#[graphql_object(context = Context)]
+impl ModerationLog {
+    /// The moderator who removed the submission.
+    async fn moderator(&self, ctx: &Context) -> Result<User, AppError> {
+        Ok(self.moderator(ctx).await?)
+    }
+
+    /// The removed submission.
+    async fn url(&self, ctx: &Context) -> Result<Url, AppError> {
+        Ok(self.url(ctx).await?)
+    }
+
+    /// Whether the submission was shadow removed, i.e.
+    /// only hidden from users other than its author.
+    fn shadow(&self) -> bool {
+        self.shadow()
+    }
+
+    /// The reason given by the moderator.
+    fn reason(&self) -> &str {
+        self.reason()
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+}
@@ -0,0 +1,63 @@
+use crate::db::id::NotificationID;
+use crate::db::models::{Comment, Notification, NotificationKind, Url, User};
+use crate::error::AppError;
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+use juniper_relay_connection::RelayConnectionNode;
+
+impl RelayConnectionNode for Notification {
+    type Cursor = NotificationID;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.id()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "NotificationConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "NotificationConnectionEdge"
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Notification {
+    /// A globally unique identifier for this
+    /// notification.
+    fn id(&self) -> NotificationID {
+        self.id()
+    }
+
+    /// What caused this notification.
+    fn kind(&self) -> NotificationKind {
+        self.kind()
+    }
+
+    /// The user whose comment caused this notification.
+    async fn actor(&self, ctx: &Context) -> Result<User, AppError> {
+        Ok(self.actor(ctx).await?)
+    }
+
+    /// The submission which was commented on.
+    async fn url(&self, ctx: &Context) -> Result<Url, AppError> {
+        Ok(self.url(ctx).await?)
+    }
+
+    /// The comment which caused this notification.
+    async fn comment(&self, ctx: &Context) -> Result<Comment, AppError> {
+        Ok(self.comment(ctx).await?)
+    }
+
+    /// The time at which this notification was created.
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+
+    /// The time at which this notification was marked as
+    /// read, or null if it was not read yet.
+    fn read_at(&self) -> Option<DateTime<Utc>> {
+        self.read_at()
+    }
+}
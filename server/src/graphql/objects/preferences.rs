@@ -0,0 +1,59 @@
+use crate::db::models::{DigestFrequency, FeedSort, ShowNsfw, UserPreferences};
+use crate::Context;
+use juniper::graphql_object;
+
+#[graphql_object(context = Context)]
+impl UserPreferences {
+    /// Name of the users timezone in the tz
+    /// database, e.g. `Europe/Berlin`.
+    fn timezone(&self) -> &str {
+        self.timezone_name()
+    }
+
+    /// Preferred locale of the user interface.
+    fn locale(&self) -> &str {
+        self.locale()
+    }
+
+    /// How the front page feed is sorted by
+    /// default.
+    fn default_sort(&self) -> FeedSort {
+        self.default_sort()
+    }
+
+    /// Whether to send an email when the account
+    /// is logged into from a new device.
+    fn notify_new_login(&self) -> bool {
+        self.notify_new_login()
+    }
+
+    /// How often to send an email digest of the
+    /// top submissions.
+    fn digest(&self) -> DigestFrequency {
+        self.digest()
+    }
+
+    /// How submissions marked as not safe for
+    /// work are shown.
+    fn show_nsfw(&self) -> ShowNsfw {
+        self.show_nsfw()
+    }
+
+    /// ISO 639-1 codes of the languages listings are
+    /// restricted to by default, or an empty list to
+    /// list all languages.
+    fn languages(&self) -> Vec<String> {
+        self.languages()
+    }
+
+    /// Whether to leave the user out when listing who voted
+    /// for a submission. Their votes are still counted.
+    fn hide_votes(&self) -> bool {
+        self.hide_votes()
+    }
+
+    /// Whether to leave the user out of the leaderboard.
+    fn hide_from_leaderboard(&self) -> bool {
+        self.hide_from_leaderboard()
+    }
+}
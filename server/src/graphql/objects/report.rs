@@ -0,0 +1,88 @@
+use crate::db::models::{
+    Comment, ModerationAction, Report, ReportCursor, ReportReason, ReportedUrl, Url,
+};
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+use juniper_relay_connection::RelayConnectionNode;
+
+impl RelayConnectionNode for ReportedUrl {
+    type Cursor = ReportCursor;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.cursor()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "ReportedUrlConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "ReportedUrlConnectionEdge"
+    }
+}
+
+#[graphql_object(context = Context)]
+impl ReportedUrl {
+    /// The reported submission.
+    fn url(&self) -> &Url {
+        self.url()
+    }
+
+    /// Number of reports of the submission.
+    fn report_count(&self) -> i32 {
+        self.reports().len() as i32
+    }
+
+    /// The distinct reasons the submission was
+    /// reported for, most common first.
+    fn reasons(&self) -> Vec<ReportReason> {
+        self.reasons()
+    }
+
+    /// The reports of the submission, oldest first.
+    /// Reports don't reveal who reported the submission.
+    fn reports(&self) -> &[Report] {
+        self.reports()
+    }
+
+    /// The comments on the submission which the spam filter
+    /// held for review, oldest first. These are only listed in
+    /// the queue of open reports.
+    fn held_comments(&self) -> &[Comment] {
+        self.held_comments()
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Report {
+    fn reason(&self) -> ReportReason {
+        self.reason()
+    }
+
+    /// Additional details given by the reporter.
+    fn note(&self) -> Option<&str> {
+        self.note()
+    }
+
+    /// The time the submission was first reported
+    /// by the reporter.
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+
+    /// The last time the reporter changed their report.
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at()
+    }
+
+    /// The time the report was resolved, if it was.
+    fn resolved_at(&self) -> Option<DateTime<Utc>> {
+        self.resolved_at()
+    }
+
+    /// How the report was resolved, if it was.
+    fn action(&self) -> Option<ModerationAction> {
+        self.action()
+    }
+}
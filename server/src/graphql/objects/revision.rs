@@ -0,0 +1,61 @@
+use crate::db::id::RevisionID;
+use crate::db::models::{Revision, RevisionCursor, User};
+use crate::error::AppError;
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+use juniper_relay_connection::RelayConnectionNode;
+
+impl RelayConnectionNode for Revision {
+    type Cursor = RevisionCursor;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.cursor()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "RevisionConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "RevisionConnectionEdge"
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Revision {
+    /// A globally unique identifier for this
+    /// revision.
+    fn id(&self) -> RevisionID {
+        self.id()
+    }
+
+    /// The previous title of a submission, if it had one.
+    /// This is always null for comments.
+    fn title(&self) -> Option<&str> {
+        self.title()
+    }
+
+    /// The previous description of a submission, if it had
+    /// one. This is always null for comments.
+    fn description(&self) -> Option<&str> {
+        self.description()
+    }
+
+    /// The previous raw markdown text of a comment. This is
+    /// always null for submissions.
+    fn body(&self) -> Option<&str> {
+        self.body()
+    }
+
+    /// The user who made the edit which replaced this
+    /// version, either the author or a moderator.
+    async fn editor(&self, ctx: &Context) -> Result<User, AppError> {
+        Ok(self.editor(ctx).await?)
+    }
+
+    /// The time this version was replaced.
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+}
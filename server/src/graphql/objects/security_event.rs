@@ -0,0 +1,54 @@
+use crate::db::id::SecurityEventID;
+use crate::db::models::{SecurityEvent, SecurityEventKind};
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+use juniper_relay_connection::RelayConnectionNode;
+
+impl RelayConnectionNode for SecurityEvent {
+    type Cursor = SecurityEventID;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.id()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "SecurityEventConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "SecurityEventConnectionEdge"
+    }
+}
+
+#[graphql_object(context = Context)]
+impl SecurityEvent {
+    /// A globally unique identifier for this
+    /// event.
+    fn id(&self) -> SecurityEventID {
+        self.id()
+    }
+
+    /// The kind of action which was recorded.
+    fn kind(&self) -> SecurityEventKind {
+        self.kind()
+    }
+
+    /// The IP address from which the action was performed,
+    /// if known. Depending on the server configuration, this
+    /// may be truncated or hashed.
+    fn ip_address(&self) -> Option<&str> {
+        self.remote_ip()
+    }
+
+    /// The raw user agent string of the client
+    /// which performed the action, if known.
+    fn user_agent(&self) -> Option<&str> {
+        self.user_agent()
+    }
+
+    /// The time at which this event was recorded.
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+}
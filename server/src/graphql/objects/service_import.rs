@@ -0,0 +1,58 @@
+use crate::db::id::ServiceImportID;
+use crate::db::models::{ImportService, ServiceImport, ServiceImportStatus};
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+
+#[graphql_object(context = Context)]
+impl ServiceImport {
+    /// A globally unique identifier for this
+    /// import.
+    fn id(&self) -> ServiceImportID {
+        self.id()
+    }
+
+    /// The service bookmarks are imported from.
+    fn service(&self) -> ImportService {
+        self.service()
+    }
+
+    /// Whether the import is still running,
+    /// or completed.
+    fn status(&self) -> ServiceImportStatus {
+        self.status()
+    }
+
+    /// Number of bookmarks which were saved so far.
+    fn imported(&self) -> i32 {
+        self.imported()
+    }
+
+    /// Number of bookmarks which were already saved,
+    /// and were skipped so far.
+    fn skipped_duplicates(&self) -> i32 {
+        self.skipped_duplicates()
+    }
+
+    /// Number of bookmarks which could not be imported.
+    fn error_count(&self) -> i32 {
+        self.error_count()
+    }
+
+    /// Why the last bookmark which could not be imported
+    /// failed, or why the import was given up.
+    fn last_error(&self) -> Option<&str> {
+        self.last_error()
+    }
+
+    /// The time at which the import was requested.
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+
+    /// The time at which the import completed
+    /// or was given up, if it did.
+    fn completed_at(&self) -> Option<DateTime<Utc>> {
+        self.completed_at()
+    }
+}
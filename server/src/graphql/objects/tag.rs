@@ -0,0 +1,42 @@
+use crate::db::models::{Tag, TagCursor};
+use crate::error::AppError;
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+use juniper_relay_connection::RelayConnectionNode;
+use std::convert::TryInto;
+
+impl RelayConnectionNode for Tag {
+    type Cursor = TagCursor;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.cursor()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "TagConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "TagConnectionEdge"
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Tag {
+    /// The normalized name of this tag, which
+    /// uniquely identifies it.
+    fn name(&self) -> &str {
+        self.name()
+    }
+
+    /// Number of submissions using this tag.
+    fn count(&self) -> Result<i32, AppError> {
+        Ok(self.url_count().try_into()?)
+    }
+
+    /// The time this tag was first used.
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+}
@@ -0,0 +1,54 @@
+use crate::db::models::{TagLog, TagLogCursor, User};
+use crate::error::AppError;
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+use juniper_relay_connection::RelayConnectionNode;
+use std::convert::TryInto;
+
+impl RelayConnectionNode for TagLog {
+    type Cursor = TagLogCursor;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.cursor()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "TagLogConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "TagLogConnectionEdge"
+    }
+}
+
+#[graphql_object(context = Context)]
+impl TagLog {
+    /// The administrator who renamed or merged the tags.
+    async fn admin(&self, ctx: &Context) -> Result<User, AppError> {
+        Ok(self.admin(ctx).await?)
+    }
+
+    /// The names of the renamed or merged tags, which
+    /// now redirect to the target tag.
+    fn sources(&self) -> Vec<String> {
+        self.sources()
+    }
+
+    /// The name of the tag the tags were renamed
+    /// or merged into.
+    fn target(&self) -> &str {
+        self.target()
+    }
+
+    /// Number of submissions which were given the
+    /// target tag, leaving out those which had it
+    /// already.
+    fn url_count(&self) -> Result<i32, AppError> {
+        Ok(self.url_count().try_into()?)
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+}
@@ -1,18 +1,32 @@
 use crate::db::id::{CommentID, UrlID};
-use crate::db::models::{Comment, Url, User};
+use crate::db::models::{
+    ArchiveStatus, Block, Comment, CommentSort, Group, LinkStatus, MetadataStatus, Revision,
+    SavedUrl, SubmissionKind, SubmissionVisibility, SubmitUrlResult, Tag, Url, UrlCheck, UrlCursor,
+    UrlEmbed, UrlSort, User, VoteDirection,
+};
+use crate::error::AppError;
+use crate::graphql::connection;
+use crate::graphql::objects::CursorComment;
 use crate::schema::comments;
-use crate::Context;
+use crate::{domain, preview, Context};
+use anyhow::Result;
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
-use juniper::{graphql_object, FieldResult, Nullable};
+use juniper::meta::MetaType;
+use juniper::{
+    graphql_object, marker, Arguments, BoxFuture, ExecutionResult, Executor, GraphQLType,
+    GraphQLValue, GraphQLValueAsync, Nullable, Registry, ScalarValue, Selection,
+};
 use juniper_relay_connection::{RelayConnection, RelayConnectionNode};
 use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
 
 impl RelayConnectionNode for Url {
-    type Cursor = UrlID;
+    type Cursor = UrlCursor;
 
     fn cursor(&self) -> Self::Cursor {
-        self.id()
+        self.cursor(UrlSort::Newest)
     }
 
     fn connection_type_name() -> &'static str {
@@ -24,6 +38,126 @@ impl RelayConnectionNode for Url {
     }
 }
 
+/// A submission in a list which hands out cursors other than the
+/// default [`UrlCursor`], e.g. one which remembers the order of the
+/// list, or the rank of a search result. This resolves exactly like
+/// a [`Url`].
+pub(crate) struct CursorUrl<C> {
+    url: Url,
+    cursor: C,
+}
+
+impl<C> CursorUrl<C> {
+    pub(crate) fn new(url: Url, cursor: C) -> Self {
+        Self { url, cursor }
+    }
+}
+
+impl CursorUrl<UrlCursor> {
+    /// A submission in a list sorted in the given order, such that
+    /// its cursor can't be replayed against a different order.
+    pub(crate) fn sorted(url: Url, sort: UrlSort) -> Self {
+        let cursor = url.cursor(sort);
+        Self::new(url, cursor)
+    }
+}
+
+impl<C> RelayConnectionNode for CursorUrl<C>
+where
+    C: FromStr + fmt::Display + Clone + Send + Sync,
+{
+    type Cursor = C;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.cursor.clone()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "UrlConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "UrlConnectionEdge"
+    }
+}
+
+impl<C, S> GraphQLType<S> for CursorUrl<C>
+where
+    S: ScalarValue,
+{
+    fn name(info: &()) -> Option<&str> {
+        <Url as GraphQLType<S>>::name(info)
+    }
+
+    fn meta<'r>(info: &(), registry: &mut Registry<'r, S>) -> MetaType<'r, S>
+    where
+        S: 'r,
+    {
+        <Url as GraphQLType<S>>::meta(info, registry)
+    }
+}
+
+impl<C, S> GraphQLValue<S> for CursorUrl<C>
+where
+    S: ScalarValue,
+{
+    type Context = Context;
+    type TypeInfo = ();
+
+    fn type_name<'i>(&self, info: &'i ()) -> Option<&'i str> {
+        <Url as GraphQLValue<S>>::type_name(&self.url, info)
+    }
+
+    fn resolve(
+        &self,
+        info: &(),
+        selection_set: Option<&[Selection<S>]>,
+        executor: &Executor<Context, S>,
+    ) -> ExecutionResult<S> {
+        self.url.resolve(info, selection_set, executor)
+    }
+}
+
+impl<C: Sync, S> GraphQLValueAsync<S> for CursorUrl<C>
+where
+    S: ScalarValue + Send + Sync,
+{
+    fn resolve_async<'a>(
+        &'a self,
+        info: &'a (),
+        selection_set: Option<&'a [Selection<S>]>,
+        executor: &'a Executor<Context, S>,
+    ) -> BoxFuture<'a, ExecutionResult<S>> {
+        self.url.resolve_async(info, selection_set, executor)
+    }
+
+    fn resolve_field_async<'a>(
+        &'a self,
+        info: &'a (),
+        field_name: &'a str,
+        arguments: &'a Arguments<S>,
+        executor: &'a Executor<Context, S>,
+    ) -> BoxFuture<'a, ExecutionResult<S>> {
+        self.url
+            .resolve_field_async(info, field_name, arguments, executor)
+    }
+}
+
+impl<C, S> marker::IsOutputType<S> for CursorUrl<C> where S: ScalarValue {}
+
+/// Whether the viewer may see that a submission was
+/// shadow removed.
+async fn may_view_shadow_removals(ctx: &Context) -> Result<bool> {
+    match ctx.maybe_user().await? {
+        Some(viewer) => Ok(viewer
+            .permissions(ctx)
+            .await?
+            .iter()
+            .any(|perm| perm.remove_urls())),
+        None => Ok(false),
+    }
+}
+
 #[graphql_object(context = Context)]
 impl Url {
     /// A globally unique identifier for this
@@ -32,9 +166,185 @@ impl Url {
         self.id()
     }
 
-    /// The URL that was submitted.
-    fn url(&self) -> FieldResult<String> {
-        Ok(self.url()?.to_string())
+    /// The URL that was submitted. Text posts have
+    /// no URL.
+    fn url(&self) -> Result<Option<String>, AppError> {
+        Ok(self.url()?.map(|url| url.to_string()))
+    }
+
+    /// The canonical form of the submitted URL, which
+    /// is used to detect duplicate submissions.
+    fn canonical_url(&self) -> Option<&str> {
+        self.canonical_url()
+    }
+
+    /// Whether this submission links a page, or is
+    /// a text post.
+    fn kind(&self) -> SubmissionKind {
+        self.kind()
+    }
+
+    /// The markdown text of a text post.
+    fn text(&self) -> Option<&str> {
+        self.text()
+    }
+
+    /// The text of a text post rendered as sanitized
+    /// html.
+    fn text_html(&self) -> Option<&str> {
+        self.text_html()
+    }
+
+    /// The time this url was deleted, if it was. Deleted
+    /// urls are not included in any listings.
+    fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at()
+    }
+
+    /// The time a moderator removed this url, if they did.
+    /// Removed urls are not included in any listings. Shadow
+    /// removals are only revealed to moderators.
+    async fn removed_at(&self, ctx: &Context) -> Result<Option<DateTime<Utc>>, AppError> {
+        if self.is_shadow_removed() && !may_view_shadow_removals(ctx).await? {
+            return Ok(None);
+        }
+        Ok(self.removed_at())
+    }
+
+    /// Whether a moderator shadow removed this url, hiding it
+    /// from everyone but its submitter and moderators. This is
+    /// only revealed to moderators.
+    async fn shadow_removed(&self, ctx: &Context) -> Result<bool, AppError> {
+        Ok(self.is_shadow_removed() && may_view_shadow_removals(ctx).await?)
+    }
+
+    /// Whether the spam filter held this url for review by a
+    /// moderator. Held urls are only visible to their author
+    /// and moderators until a moderator releases them.
+    fn held(&self) -> bool {
+        self.is_held()
+    }
+
+    /// Whether a moderator locked the discussion of this url.
+    /// Comments on locked urls stay visible, but only
+    /// administrators may comment, vote or edit comments.
+    fn locked(&self) -> bool {
+        self.is_locked()
+    }
+
+    /// The time a moderator locked the discussion of this
+    /// url, if they did.
+    fn locked_at(&self) -> Option<DateTime<Utc>> {
+        self.locked_at()
+    }
+
+    /// The reason given for locking the discussion, if any.
+    fn lock_reason(&self) -> Option<&str> {
+        self.lock_reason()
+    }
+
+    /// The last time the title or description were edited
+    /// by the submitter, if ever.
+    fn edited_at(&self) -> Option<DateTime<Utc>> {
+        self.edited_at()
+    }
+
+    /// The number of times the title or description were
+    /// changed, see `revisions`.
+    fn revision_count(&self) -> Result<i32, AppError> {
+        Ok(self.revision_count().try_into()?)
+    }
+
+    /// Previous titles and descriptions of this submission,
+    /// most recent first. The edit history is only visible to
+    /// the submitter and administrators, unless the server
+    /// makes it public for submissions which were not deleted.
+    async fn revisions(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<Revision>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                Ok(Revision::for_url(ctx, self, after, before, limit).await?)
+            },
+        )
+        .await
+    }
+
+    /// The registrable domain of this url, e.g. `example.co.uk`
+    /// for `https://blog.example.co.uk/`. International domain
+    /// names are in their unicode form. Text posts have no
+    /// domain.
+    fn domain(&self) -> Option<String> {
+        self.domain().map(domain::to_unicode)
+    }
+
+    /// The registrable domain of this url in its ASCII form,
+    /// i.e. with international domain names in punycode.
+    fn domain_ascii(&self) -> Option<&str> {
+        self.domain()
+    }
+
+    /// Number of submissions from the same domain as this
+    /// url, including this one.
+    async fn domain_submission_count(&self, ctx: &Context) -> Result<i32, AppError> {
+        Ok(self.domain_submission_count(ctx).await? as i32)
+    }
+
+    /// Whether the submitter pinned this url to their
+    /// profile.
+    fn pinned(&self) -> bool {
+        self.is_pinned()
+    }
+
+    /// The time the submitter pinned this url to their
+    /// profile, if they did.
+    fn pinned_at(&self) -> Option<DateTime<Utc>> {
+        self.pinned_at()
+    }
+
+    /// Whether this url is a draft, which is only visible
+    /// to the submitter until it is published.
+    fn is_draft(&self) -> bool {
+        self.is_draft()
+    }
+
+    /// When this draft is scheduled to be published,
+    /// if it is.
+    fn publish_at(&self) -> Option<DateTime<Utc>> {
+        self.publish_at()
+    }
+
+    /// Where this url is listed. Unlisted urls are only
+    /// reachable by their link.
+    fn visibility(&self) -> SubmissionVisibility {
+        self.visibility()
+    }
+
+    /// The group this url was submitted to, if any.
+    async fn group(&self, ctx: &Context) -> Result<Option<Group>, AppError> {
+        Ok(self.group(ctx).await?)
+    }
+
+    /// Whether the linked page is not safe for work. Clients
+    /// should blur such submissions if the viewer prefers it,
+    /// see `UserPreferences.showNsfw`.
+    fn nsfw(&self) -> bool {
+        self.is_nsfw()
+    }
+
+    /// ISO 639-1 code of the language of the linked page, e.g.
+    /// `de`, or null if it could not be detected confidently.
+    fn language(&self) -> Option<&str> {
+        self.language()
     }
 
     /// The HTTP status code returned when
@@ -43,23 +353,94 @@ impl Url {
         self.status().as_u16().into()
     }
 
-    /// The title of the linked page. This is parsed
-    /// from the page when the url is submitted.
+    /// The title of the linked page. This is provided by
+    /// the submitter, or parsed from the page when the url
+    /// is submitted.
     fn title(&self) -> Option<&str> {
         self.title()
     }
 
-    /// A description of the linked page. This is parsed
-    /// from the page when the url is submitted.
+    /// A description of the linked page. This is provided
+    /// by the submitter, or parsed from the page when the
+    /// url is submitted.
     fn description(&self) -> Option<&str> {
         self.description()
     }
 
+    /// The description rendered as sanitized html. Descriptions
+    /// provided by the submitter are markdown, descriptions parsed
+    /// from the page are plain text.
+    fn description_html(&self) -> Option<String> {
+        self.description_html()
+    }
+
+    /// The title found in the linked page, regardless
+    /// of any title provided by the submitter.
+    fn fetched_title(&self) -> Option<&str> {
+        self.fetched_title()
+    }
+
+    /// The description found in the linked page, regardless
+    /// of any description provided by the submitter.
+    fn fetched_description(&self) -> Option<&str> {
+        self.fetched_description()
+    }
+
+    /// The path of the preview image of the linked page, usually
+    /// taken from its `og:image` meta tag. Preview images are served
+    /// from a copy stored by this server, rather than the linked
+    /// site, and this is `null` if no copy could be stored.
+    fn preview_image_url(&self, ctx: &Context) -> Option<String> {
+        self.preview_image().map(|key| preview::path(ctx, key))
+    }
+
+    /// An inline player or card for links to known providers, such
+    /// as video hosts, or `null` if the link was not unfurled.
+    async fn embed(&self, ctx: &Context) -> Result<Option<UrlEmbed>, AppError> {
+        Ok(self.embed(ctx).await?)
+    }
+
+    /// Whether the linked page was fetched successfully.
+    fn metadata_status(&self) -> MetadataStatus {
+        self.metadata_status()
+    }
+
+    /// The last time the link was checked, if ever. Links are
+    /// checked periodically to find broken links.
+    fn last_checked_at(&self) -> Option<DateTime<Utc>> {
+        self.last_checked_at()
+    }
+
+    /// The HTTP status code of the final response found by the
+    /// last link check, after following redirects. This is `null`
+    /// if the link was not checked yet, or there was no response.
+    fn http_status(&self) -> Option<i32> {
+        self.http_status()
+    }
+
+    /// Whether the link still works, as found by the last check.
+    fn link_status(&self) -> LinkStatus {
+        self.link_status()
+    }
+
+    /// URL of a snapshot of the linked page in the archive, if
+    /// the page was archived. Clients should link to the snapshot
+    /// when the `linkStatus` is `BROKEN`.
+    fn archived_url(&self) -> Option<&str> {
+        self.archived_url()
+    }
+
+    /// Whether the linked page was archived, or `null` if
+    /// archiving is disabled on this server.
+    fn archive_status(&self) -> Option<ArchiveStatus> {
+        self.archive_status()
+    }
+
     /// The image url of the linked page. This is the
     /// image that would e.g. be displayed in a Twitter
     /// timeline. These images typically have a 2:1 aspect
     /// ratio.
-    fn image(&self) -> FieldResult<Option<String>> {
+    fn image(&self) -> Result<Option<String>, AppError> {
         Ok(self.image()?.map(|uri| uri.to_string()))
     }
 
@@ -75,25 +456,149 @@ impl Url {
         self.created_at()
     }
 
-    /// The user who submitted this URL.
-    async fn created_by(&self, ctx: &Context) -> FieldResult<User> {
-        Ok(self.created_by(ctx).await?)
+    /// The time this url was last made public, which
+    /// orders the newest submissions.
+    fn published_at(&self) -> DateTime<Utc> {
+        self.published_at()
+    }
+
+    /// The user who submitted this URL. This is null for
+    /// anonymous submissions, unless the viewer submitted
+    /// the URL or is an administrator.
+    async fn created_by(&self, ctx: &Context) -> Result<Option<User>, AppError> {
+        Ok(self.author(ctx).await?)
+    }
+
+    /// Whether this URL was submitted anonymously.
+    fn anonymous(&self) -> bool {
+        self.is_anonymous()
+    }
+
+    /// Tags categorizing this URL, ordered by name.
+    async fn tags(&self, ctx: &Context) -> Result<Vec<Tag>, AppError> {
+        Ok(self.tags(ctx).await?)
+    }
+
+    /// Other submissions related to this one, most related first.
+    /// Submissions are related by sharing tags, by linking the same
+    /// domain, and by sharing words of their titles. Submissions are
+    /// excluded like when listing all submissions, and at most 25
+    /// submissions are returned.
+    async fn related(
+        &self,
+        ctx: &Context,
+        #[graphql(default = 5)] limit: i32,
+    ) -> Result<Vec<Url>, AppError> {
+        Ok(self.related(ctx, limit.into()).await?)
+    }
+
+    /// Earlier submissions of the same url, latest first. Urls
+    /// may be submitted again once their previous submission is
+    /// old enough, or if it was deleted or removed, in which case
+    /// this links to the earlier discussions. Deleted and removed
+    /// submissions are left out.
+    async fn previous_submissions(&self, ctx: &Context) -> Result<Vec<Url>, AppError> {
+        Ok(self.previous_submissions(ctx).await?)
+    }
+
+    /// The number of comments on this submission, excluding
+    /// those by users blocked by the viewer.
+    async fn comment_count(&self, ctx: &Context) -> Result<i32, AppError> {
+        Ok(self.comment_count(ctx).await?.try_into()?)
+    }
+
+    /// The number of upvotes minus the number of downvotes
+    /// this submission has received.
+    fn score(&self) -> Result<i32, AppError> {
+        Ok(self.score().try_into()?)
+    }
+
+    /// The number of upvotes this submission has received.
+    fn upvotes(&self) -> Result<i32, AppError> {
+        Ok(self.upvotes().try_into()?)
+    }
+
+    /// Users who upvoted this submission, most recent vote
+    /// first. Voters are only visible to the submitter and
+    /// administrators, unless the server makes them public,
+    /// and resolve to an error for everyone else. Users who
+    /// opted out of being listed are left out, but still
+    /// counted in `upvotes`.
+    async fn voters(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<User>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move { Ok(self.voters(ctx, after, before, limit).await?) },
+        )
+        .await
+    }
+
+    /// The number of times readers followed the link of this
+    /// submission. Counts are updated periodically, and are
+    /// rounded down to a multiple of ten unless the viewer
+    /// submitted the link.
+    fn clicks(&self, ctx: &Context) -> Result<i32, AppError> {
+        Ok(self.viewer_clicks(ctx).try_into()?)
+    }
+
+    /// The number of people who opened the discussion of this
+    /// submission, counting each viewer at most once per day.
+    /// Anonymous viewers are told apart approximately.
+    fn views(&self) -> Result<i32, AppError> {
+        Ok(self.views().try_into()?)
+    }
+
+    /// The number of downvotes this submission has received.
+    /// This is always zero unless downvotes are enabled.
+    fn downvotes(&self) -> Result<i32, AppError> {
+        Ok(self.downvotes().try_into()?)
+    }
+
+    /// If the current viewer voted on this submission.
+    async fn viewer_has_voted(&self, ctx: &Context) -> Result<bool, AppError> {
+        Ok(self.voted_by_viewer(ctx).await?)
+    }
+
+    /// If the current viewer saved this submission to
+    /// their reading list.
+    async fn viewer_has_saved(&self, ctx: &Context) -> Result<bool, AppError> {
+        Ok(SavedUrl::exists(ctx, self.id()).await?)
+    }
+
+    /// How the current viewer voted on this submission, if at all.
+    async fn viewer_vote(&self, ctx: &Context) -> Result<Option<VoteDirection>, AppError> {
+        Ok(self.viewer_vote(ctx).await?)
     }
 
     /// The total number of upvotes this submission has received.
-    async fn upvote_count(&self, ctx: &Context) -> FieldResult<i32> {
-        Ok(self.upvote_count(ctx).await?.try_into()?)
+    #[graphql(deprecated = "Use `upvotes`")]
+    fn upvote_count(&self) -> Result<i32, AppError> {
+        Ok(self.upvotes().try_into()?)
     }
 
     /// If the URL was upvoted by the current viewer.
-    async fn upvoted_by_viewer(&self, ctx: &Context) -> FieldResult<bool> {
-        Ok(self.upvoted_by_viewer(ctx).await?)
+    #[graphql(deprecated = "Use `viewerVote`")]
+    async fn upvoted_by_viewer(&self, ctx: &Context) -> Result<bool, AppError> {
+        Ok(self.viewer_vote(ctx).await? == Some(VoteDirection::Up))
     }
 
     /// List comments and optionally filter by `repliesTo`
-    /// thread. If `repliesTo` is explicitly provided as
-    /// `null`, it will filter for all comments which do not
-    /// reply to any specific other thread.
+    /// thread. Unless `repliesTo` is provided, only comments
+    /// which do not reply to any other comment are returned,
+    /// see `Comment.replies` for the rest of each thread.
+    /// Comments by users blocked by the viewer, and deleted
+    /// comments without replies are excluded. The `TOP` order
+    /// only applies to comments which do not reply to any other
+    /// comment, replies are listed in the order they were written.
     async fn comments(
         &self,
         ctx: &Context,
@@ -102,37 +607,77 @@ impl Url {
         last: Option<i32>,
         before: Option<String>,
         replies_to: Nullable<CommentID>,
-    ) -> FieldResult<RelayConnection<Comment>> {
+        #[graphql(default = CommentSort::Top)] sort: CommentSort,
+    ) -> Result<RelayConnection<CursorComment>, AppError> {
+        let sort = match (&replies_to, sort) {
+            (Nullable::Some(_), CommentSort::Top) => CommentSort::Old,
+            (_, sort) => sort,
+        };
         let conn = ctx.conn().await?;
-        RelayConnection::new(first, after, last, before, |after, before, limit| {
+        connection::new(first, after, last, before, |after, before, limit| {
             let mut query = comments::table
                 .filter(comments::dsl::url_id.eq(self.id()))
-                .order_by(comments::dsl::created_at.asc())
+                .filter(Comment::not_held(ctx))
+                .filter(
+                    comments::dsl::deleted_at
+                        .is_null()
+                        .or(comments::dsl::reply_count.gt(0)),
+                )
                 .into_boxed();
 
-            if let Some(after) = after {
-                let after: Comment = comments::table.find(after).get_result(&*conn)?;
-                query = query.filter(comments::dsl::created_at.gt(after.created_at().naive_utc()));
-            }
-
-            if let Some(before) = before {
-                let before: Comment = comments::table.find(before).get_result(&*conn)?;
-                query = query.filter(comments::dsl::created_at.lt(before.created_at().naive_utc()));
-            }
-
-            if let Some(limit) = limit {
-                query = query.limit(limit);
+            if let Some(viewer) = ctx.maybe_user_id() {
+                query =
+                    query.filter(comments::dsl::created_by.ne_all(Block::hidden_authors(viewer)));
             }
 
             query = match replies_to {
                 Nullable::Some(comment_id) => {
                     query.filter(comments::dsl::replies_to.eq(comment_id))
                 }
-                Nullable::ExplicitNull => query.filter(comments::dsl::replies_to.is_null()),
-                Nullable::ImplicitNull => query,
+                Nullable::ExplicitNull | Nullable::ImplicitNull => {
+                    query.filter(comments::dsl::replies_to.is_null())
+                }
             };
 
-            Ok(query.load(&*conn)?)
+            let comments = Comment::paginate(&*conn, query, sort, after, before, limit)?;
+            Ok(comments
+                .into_iter()
+                .map(|comment| CursorComment::sorted(comment, sort))
+                .collect())
         })
     }
 }
+
+#[graphql_object(context = Context)]
+impl SubmitUrlResult {
+    /// The submitted URL. For duplicates, this is the
+    /// earlier submission.
+    fn url(&self) -> &Url {
+        self.url()
+    }
+
+    /// Whether the URL was submitted before. Clients should
+    /// direct the user to the existing discussion instead.
+    fn duplicate(&self) -> bool {
+        self.is_duplicate()
+    }
+}
+
+#[graphql_object(context = Context)]
+impl UrlCheck {
+    /// Whether the URL was submitted before, in which
+    /// case submitting it returns the earlier submission.
+    fn exists(&self) -> bool {
+        self.exists()
+    }
+
+    /// The earlier submission of the URL, if any.
+    fn url(&self) -> Option<&Url> {
+        self.url()
+    }
+
+    /// The time the URL was submitted before, if it was.
+    fn submitted_at(&self) -> Option<DateTime<Utc>> {
+        self.url().map(Url::created_at)
+    }
+}
@@ -0,0 +1,28 @@
+use crate::db::models::UrlEmbed;
+use crate::Context;
+use juniper::graphql_object;
+
+#[graphql_object(context = Context)]
+impl UrlEmbed {
+    /// The name of the site providing the embed, e.g. `YouTube`.
+    fn provider(&self) -> &str {
+        self.provider()
+    }
+
+    /// The markup of the embed. This is either an `iframe` with a
+    /// player from a trusted host, or a card linking to the page,
+    /// and is safe to include in pages as is.
+    fn html(&self) -> &str {
+        self.html()
+    }
+
+    /// The width of the player in pixels, if known.
+    fn width(&self) -> Option<i32> {
+        self.width()
+    }
+
+    /// The height of the player in pixels, if known.
+    fn height(&self) -> Option<i32> {
+        self.height()
+    }
+}
@@ -1,11 +1,51 @@
 use crate::db::id::UserID;
-use crate::db::models::{Invite, Permission, Url, User};
-use crate::schema::urls;
+use crate::db::models::{
+    Collection, Follow, Invite, Listed, Permission, ProfileCursor, TopRange, Url, UrlFilter,
+    UrlSort, User,
+};
+use crate::error::AppError;
+use crate::graphql::connection;
+use crate::graphql::objects::CursorUrl;
+use crate::schema::{urls, users};
 use crate::Context;
+use anyhow::Result;
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
-use juniper::{graphql_object, FieldResult};
-use juniper_relay_connection::RelayConnection;
+use juniper::graphql_object;
+use juniper_relay_connection::{RelayConnection, RelayConnectionNode};
+use std::convert::TryInto;
+
+impl RelayConnectionNode for User {
+    type Cursor = UserID;
+
+    fn cursor(&self) -> Self::Cursor {
+        self.id()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "UserConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "UserConnectionEdge"
+    }
+}
+
+/// Whether the viewer may see the login activity of
+/// the given user.
+async fn may_view_login_activity(ctx: &Context, user: &User) -> Result<bool> {
+    if ctx.maybe_user_id() == Some(user.id()) {
+        return Ok(true);
+    }
+    match ctx.maybe_user().await? {
+        Some(viewer) => Ok(viewer
+            .permissions(ctx)
+            .await?
+            .iter()
+            .any(|perm| perm.view_login_activity())),
+        None => Ok(false),
+    }
+}
 
 #[graphql_object(context = Context)]
 impl User {
@@ -20,20 +60,262 @@ impl User {
         self.name()
     }
 
+    /// The unique username of this user, which is used to
+    /// link to their profile.
+    fn username(&self) -> &str {
+        self.username()
+    }
+
+    /// The reputation of this user, i.e. the score of their
+    /// submissions and comments. Anonymous submissions do not
+    /// count.
+    fn karma(&self) -> Result<i32, AppError> {
+        Ok(self.karma().try_into()?)
+    }
+
+    /// Number of submissions by this user, excluding
+    /// deleted submissions.
+    async fn url_count(&self, ctx: &Context) -> Result<i32, AppError> {
+        Ok(self.url_count(ctx).await?.try_into()?)
+    }
+
+    /// Number of comments by this user, excluding
+    /// deleted comments.
+    async fn comment_count(&self, ctx: &Context) -> Result<i32, AppError> {
+        Ok(self.comment_count(ctx).await?.try_into()?)
+    }
+
+    /// Number of users following this user.
+    async fn follower_count(&self, ctx: &Context) -> Result<i32, AppError> {
+        Ok(Follow::follower_count(ctx, self.id()).await?.try_into()?)
+    }
+
+    /// Number of users this user follows.
+    async fn following_count(&self, ctx: &Context) -> Result<i32, AppError> {
+        Ok(Follow::following_count(ctx, self.id()).await?.try_into()?)
+    }
+
+    /// If the current viewer follows this user.
+    async fn viewer_follows(&self, ctx: &Context) -> Result<bool, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(Follow::exists(ctx, user_id, self.id()).await?),
+            None => Ok(false),
+        }
+    }
+
+    /// Users following this user, most recently
+    /// followed first.
+    async fn followers(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<User>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                Ok(Follow::followers(ctx, self.id(), after, before, limit).await?)
+            },
+        )
+        .await
+    }
+
+    /// Users this user follows, most recently
+    /// followed first.
+    async fn following(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<User>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                Ok(Follow::following(ctx, self.id(), after, before, limit).await?)
+            },
+        )
+        .await
+    }
+
     /// The date when this user account
     /// was created.
     fn joined(&self) -> DateTime<Utc> {
         self.created_at()
     }
 
+    /// The last time this user logged in. This is
+    /// only visible to administrators and the user
+    /// themselves.
+    async fn last_login_at(&self, ctx: &Context) -> Result<Option<DateTime<Utc>>, AppError> {
+        if may_view_login_activity(ctx, self).await? {
+            Ok(self.last_login_at())
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The number of times this user logged in. This
+    /// is only visible to administrators and the user
+    /// themselves.
+    async fn login_count(&self, ctx: &Context) -> Result<Option<i32>, AppError> {
+        if may_view_login_activity(ctx, self).await? {
+            Ok(Some(self.login_count()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The time this user was banned, if they are
+    /// currently banned.
+    fn banned_at(&self) -> Option<DateTime<Utc>> {
+        self.banned_at()
+    }
+
+    /// If logging into this account is locked after too
+    /// many failed attempts, the time the lock lifts. This
+    /// is only visible to administrators and the user
+    /// themselves.
+    async fn login_locked_until(&self, ctx: &Context) -> Result<Option<DateTime<Utc>>, AppError> {
+        if may_view_login_activity(ctx, self).await? {
+            Ok(self.login_locked_until())
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The user who issued the invitation this user
+    /// registered with, if any.
+    async fn invited_by(&self, ctx: &Context) -> Result<Option<User>, AppError> {
+        Ok(self.invited_by(ctx).await?)
+    }
+
+    /// Users who registered using one of this users invitations,
+    /// newest first. Users can list their own invitees, listing
+    /// the invitees of other users is only available to
+    /// administrators and moderators.
+    async fn invitees(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<User>, AppError> {
+        if ctx.user_id()? != self.id() {
+            ctx.user()
+                .await?
+                .check_permissions(ctx, |perm| perm.view_invite_tree())
+                .await?;
+        }
+
+        let conn = ctx.conn().await?;
+        connection::new(first, after, last, before, |after, before, limit| {
+            let mut query = users::table
+                .filter(users::dsl::invited_by.eq(self.id()))
+                .order_by(users::dsl::created_at.desc())
+                .into_boxed();
+
+            if let Some(after) = after {
+                let after: User = users::table.find(after).get_result(&*conn)?;
+                query = query.filter(users::dsl::created_at.lt(after.created_at().naive_utc()));
+            }
+
+            if let Some(before) = before {
+                let before: User = users::table.find(before).get_result(&*conn)?;
+                query = query.filter(users::dsl::created_at.gt(before.created_at().naive_utc()));
+            }
+
+            if let Some(limit) = limit {
+                query = query.limit(limit);
+            }
+
+            Ok(query.load(&*conn)?)
+        })
+    }
+
     /// Invitation used by this user to register
     /// their account, if any.
-    async fn invite(&self, ctx: &Context) -> FieldResult<Option<Invite>> {
+    async fn invite(&self, ctx: &Context) -> Result<Option<Invite>, AppError> {
         Ok(self.invite(ctx).await?)
     }
 
+    /// Urls submitted by this user in the given order, newest first
+    /// by default. Submissions the user pinned are listed before all
+    /// others, in the order they were pinned. Deleted submissions are
+    /// excluded, as are anonymous submissions unless the viewer is
+    /// this user.
+    async fn urls(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        #[graphql(default = UrlSort::Newest)] sort: UrlSort,
+    ) -> Result<RelayConnection<CursorUrl<ProfileCursor>>, AppError> {
+        let filter = UrlFilter {
+            created_by: Some(self.id()),
+            // profiles list all submissions in the top
+            // order, rather than only recent ones
+            range: match sort {
+                UrlSort::Top => Some(TopRange::All),
+                _ => None,
+            },
+            ..Default::default()
+        };
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                let urls = Url::profile(ctx, filter, sort, after, before, limit).await?;
+                Ok(urls
+                    .into_iter()
+                    .map(|url| {
+                        let cursor = url.profile_cursor(sort);
+                        CursorUrl::new(url, cursor)
+                    })
+                    .collect())
+            },
+        )
+        .await
+    }
+
+    /// Public collections of this user, newest first.
+    async fn collections(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<Collection>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                Ok(Collection::for_user(ctx, self.id(), true, after, before, limit).await?)
+            },
+        )
+        .await
+    }
+
     /// Urls submitted by this user in reverse
     /// chronological order.
+    #[graphql(deprecated = "Use `urls` instead")]
     async fn submissions(
         &self,
         ctx: &Context,
@@ -41,22 +323,24 @@ impl User {
         after: Option<String>,
         last: Option<i32>,
         before: Option<String>,
-    ) -> FieldResult<RelayConnection<Url>> {
+    ) -> Result<RelayConnection<Url>, AppError> {
+        let listed = Listed::to_viewer(ctx).await?;
+        let anonymous = Url::listed_anonymous(ctx, self.id());
         let conn = ctx.conn().await?;
-        RelayConnection::new(first, after, last, before, |after, before, limit| {
+        connection::new::<Url, _>(first, after, last, before, |after, before, limit| {
             let mut query = urls::table
+                .filter(listed.filter())
                 .filter(urls::dsl::created_by.eq(self.id()))
-                .order_by(urls::dsl::created_at.desc())
+                .filter(urls::dsl::anonymous.eq_any(&anonymous))
+                .order_by(urls::dsl::published_at.desc())
                 .into_boxed();
 
             if let Some(after) = after {
-                let after: Url = urls::table.find(after).get_result(&*conn)?;
-                query = query.filter(urls::dsl::created_at.lt(after.created_at().naive_utc()));
+                query = query.filter(urls::dsl::published_at.lt(after.published_at()));
             }
 
             if let Some(before) = before {
-                let before: Url = urls::table.find(before).get_result(&*conn)?;
-                query = query.filter(urls::dsl::created_at.gt(before.created_at().naive_utc()));
+                query = query.filter(urls::dsl::published_at.gt(before.published_at()));
             }
 
             if let Some(limit) = limit {
@@ -69,7 +353,7 @@ impl User {
 
     /// List of active permissions for this
     /// user.
-    async fn permissions(&self, ctx: &Context) -> FieldResult<Vec<Permission>> {
+    async fn permissions(&self, ctx: &Context) -> Result<Vec<Permission>, AppError> {
         Ok(self.permissions(ctx).await?)
     }
 }
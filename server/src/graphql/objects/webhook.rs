@@ -0,0 +1,104 @@
+use crate::db::id::{WebhookDeliveryID, WebhookID};
+use crate::db::models::{Webhook, WebhookDelivery, WebhookDeliveryStatus, WebhookEvent};
+use crate::error::AppError;
+use crate::Context;
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+
+/// Maximum number of deliveries listed at once.
+const MAX_DELIVERIES: i32 = 100;
+
+#[graphql_object(context = Context)]
+impl Webhook {
+    /// A globally unique identifier for this
+    /// webhook.
+    fn id(&self) -> WebhookID {
+        self.id()
+    }
+
+    /// The URL events are posted to.
+    fn url(&self) -> &str {
+        self.url()
+    }
+
+    /// The events this webhook receives.
+    fn events(&self) -> Vec<WebhookEvent> {
+        self.events()
+    }
+
+    /// Whether this webhook receives the events of the
+    /// whole site, rather than only those concerning the
+    /// submissions of its owner.
+    fn site_wide(&self) -> bool {
+        self.site_wide()
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+
+    /// The latest deliveries to this webhook, newest
+    /// first, at most 100.
+    async fn deliveries(
+        &self,
+        ctx: &Context,
+        #[graphql(default = 20)] first: i32,
+    ) -> Result<Vec<WebhookDelivery>, AppError> {
+        let limit = first.clamp(0, MAX_DELIVERIES);
+        Ok(self.deliveries(ctx, limit.into()).await?)
+    }
+}
+
+#[graphql_object(context = Context)]
+impl WebhookDelivery {
+    /// A globally unique identifier for this delivery,
+    /// which is sent as `X-Urls-Delivery`.
+    fn id(&self) -> WebhookDeliveryID {
+        self.id()
+    }
+
+    fn event(&self) -> WebhookEvent {
+        self.event()
+    }
+
+    /// The JSON document posted to the webhook.
+    fn payload(&self) -> &str {
+        self.payload()
+    }
+
+    fn status(&self) -> WebhookDeliveryStatus {
+        self.status()
+    }
+
+    /// Number of attempts which failed so far.
+    fn attempts(&self) -> i32 {
+        self.attempts()
+    }
+
+    /// The HTTP status of the last response, if
+    /// any response was received.
+    fn response_status(&self) -> Option<i32> {
+        self.response_status()
+    }
+
+    /// Why the last attempt failed, if it did.
+    fn last_error(&self) -> Option<&str> {
+        self.last_error()
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at()
+    }
+
+    /// When the delivery is attempted next, if
+    /// it is still pending.
+    fn next_attempt_at(&self) -> Option<DateTime<Utc>> {
+        self.next_attempt_at()
+    }
+
+    /// When the webhook accepted the delivery,
+    /// if it did.
+    fn delivered_at(&self) -> Option<DateTime<Utc>> {
+        self.delivered_at()
+    }
+}
@@ -0,0 +1,154 @@
+//! Requests for persisted queries, following the automatic persisted
+//! queries protocol of Apollo. Requests carry the hash of their query
+//! as the `persistedQuery` extension, and either leave out the query,
+//! which is then looked up by its hash, or send it along to register
+//! it, see [`persisted`](crate::persisted).
+
+use crate::persisted;
+use crate::Context;
+use chrono::Duration;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fmt;
+use warp::http::StatusCode;
+
+/// Number of queries a client may register per hour.
+const REGISTRATIONS_PER_HOUR: usize = 100;
+
+/// Why a request for persisted queries was rejected
+/// before executing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejected {
+    NotSupported,
+    NotFound,
+    HashMismatch,
+    Required,
+}
+
+impl Rejected {
+    /// The status of the rejected response.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Rejected::Required => StatusCode::FORBIDDEN,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// The error code of the rejected response.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Rejected::NotSupported => "PERSISTED_QUERY_NOT_SUPPORTED",
+            Rejected::NotFound => "PERSISTED_QUERY_NOT_FOUND",
+            Rejected::HashMismatch => "PERSISTED_QUERY_HASH_MISMATCH",
+            Rejected::Required => "PERSISTED_QUERY_REQUIRED",
+        }
+    }
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // clients match on the messages of missing queries
+        match self {
+            Rejected::NotSupported => write!(f, "PersistedQueryNotSupported"),
+            Rejected::NotFound => write!(f, "PersistedQueryNotFound"),
+            Rejected::HashMismatch => write!(f, "The query does not match its hash"),
+            Rejected::Required => write!(f, "Only persisted queries are allowed"),
+        }
+    }
+}
+
+/// Whether the viewer may send queries which aren't
+/// persisted, when only persisted queries are allowed.
+async fn allows_ad_hoc_queries(ctx: &Context) -> bool {
+    match ctx.maybe_user().await {
+        Ok(Some(user)) => user
+            .check_permissions(ctx, |perm| perm.ad_hoc_queries())
+            .await
+            .is_ok(),
+        _ => false,
+    }
+}
+
+/// Register the given queries, which a client sent along with their
+/// hash. Clients may register [`REGISTRATIONS_PER_HOUR`] queries, per
+/// user or IP address, and further queries are executed without being
+/// registered.
+fn register<'a>(ctx: &Context, queries: impl Iterator<Item = &'a String>) {
+    let client = match (ctx.maybe_user_id(), ctx.remote_ip_address()) {
+        (Some(user_id), _) => user_id.to_string(),
+        (None, Some(ip)) => ip.to_string(),
+        (None, None) => String::new(),
+    };
+    // long queries are never registered, and don't count
+    for query in queries.filter(|query| query.len() <= persisted::MAX_QUERY_BYTES) {
+        let limit = ctx.rate_limiter().check(
+            "register_persisted_query",
+            &client,
+            REGISTRATIONS_PER_HOUR,
+            Duration::hours(1),
+            ctx.now(),
+        );
+        if limit.is_err() {
+            return;
+        }
+        ctx.persisted_queries().register(query);
+    }
+}
+
+/// Resolve the persisted queries of the given JSON encoded GraphQL request
+/// (or batch of requests), returning the request with their query texts.
+/// Queries sent along with their hash are registered, if enabled, see
+/// [`register`]. Malformed requests are left for the executor to reject.
+pub async fn resolve<'a>(ctx: &Context, body: &'a [u8]) -> Result<Cow<'a, [u8]>, Rejected> {
+    let mut request: Value = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(_) => return Ok(Cow::Borrowed(body)),
+    };
+    let config = ctx.config();
+    let queries = ctx.persisted_queries();
+    let requests: Vec<&mut Value> = match &mut request {
+        Value::Array(requests) => requests.iter_mut().collect(),
+        request => vec![request],
+    };
+
+    let mut resolved = false;
+    let mut ad_hoc = vec![];
+    for request in requests {
+        let hash = match request["extensions"]["persistedQuery"]["sha256Hash"].as_str() {
+            Some(hash) => hash.to_ascii_lowercase(),
+            None => {
+                ad_hoc.push(None);
+                continue;
+            }
+        };
+        match request["query"].as_str() {
+            Some(query) if persisted::hash(query) != hash => return Err(Rejected::HashMismatch),
+            Some(query) if queries.get(&hash).is_none() => ad_hoc.push(Some(query.to_string())),
+            Some(_) => (),
+            None => {
+                let query = match queries.get(&hash) {
+                    Some(query) => query,
+                    None if config.persisted_queries().is_none()
+                        && !config.register_persisted_queries() =>
+                    {
+                        return Err(Rejected::NotSupported)
+                    }
+                    None => return Err(Rejected::NotFound),
+                };
+                request["query"] = Value::String(query);
+                resolved = true;
+            }
+        }
+    }
+
+    if !ad_hoc.is_empty() && config.persisted_queries_only() && !allows_ad_hoc_queries(ctx).await {
+        return Err(Rejected::Required);
+    }
+    if config.register_persisted_queries() {
+        register(ctx, ad_hoc.iter().flatten());
+    }
+    if !resolved {
+        return Ok(Cow::Borrowed(body));
+    }
+    Ok(Cow::Owned(request.to_string().into_bytes()))
+}
@@ -1,8 +1,13 @@
-use crate::db::id::{CommentID, UrlID, UserID};
-use crate::db::models::{Comment, Url, User};
-use crate::graphql::{search::Search, viewer::Viewer};
-use crate::Context;
-use juniper::{graphql_object, FieldResult};
+use crate::db::id::{CollectionID, CommentID, UrlID, UserID};
+use crate::db::models::{
+    Collection, Comment, Group, InviteTree, LeaderboardEntry, LeaderboardPeriod, LinkStatus,
+    ModerationLog, Report, ReportStatus, ReportedUrl, Tag, TagLog, TagSort, TagSuggestion,
+    TopRange, Url, UrlCheck, UrlCursor, UrlFilter, UrlSort, User,
+};
+use crate::error::AppError;
+use crate::graphql::{connection, objects::CursorUrl, search::Search, viewer::Viewer};
+use crate::{domain, language, Context, RegistrationMode};
+use juniper::graphql_object;
 use juniper_relay_connection::RelayConnection;
 
 pub struct Query;
@@ -10,11 +15,170 @@ pub struct Query;
 #[graphql_object(context = Context)]
 impl Query {
     /// The `viewer` field represents the
-    /// current visitor or authenticated user,
-    /// and groups fields which depend on the
-    /// current viewer.
-    fn viewer() -> Viewer {
-        Viewer
+    /// current authenticated user, and groups
+    /// fields which depend on the current viewer.
+    /// This is `null` if no valid session is
+    /// present.
+    fn viewer(ctx: &Context) -> Option<Viewer> {
+        if ctx.is_logged_in() {
+            Some(Viewer)
+        } else {
+            None
+        }
+    }
+
+    /// The token which must be sent in the `X-XSRF-Token` header
+    /// with mutations which are authenticated using the session
+    /// cookie. The token is tied to the `xsrf` cookie set on every
+    /// API response.
+    fn csrf_token(ctx: &Context) -> String {
+        ctx.xsrf_token().to_string()
+    }
+
+    /// Who may currently register a new account.
+    fn registration_mode(ctx: &Context) -> RegistrationMode {
+        ctx.config().registration_mode()
+    }
+
+    /// The tree of users who registered using invitations issued
+    /// by the given user, recursively, up to `depth` levels below
+    /// it. The depth is capped at 10 levels. This is only available
+    /// to administrators and moderators.
+    async fn invite_tree(
+        ctx: &Context,
+        root_user_id: UserID,
+        #[graphql(default = 3)] depth: i32,
+    ) -> Result<InviteTree, AppError> {
+        ctx.user()
+            .await?
+            .check_permissions(ctx, |perm| perm.view_invite_tree())
+            .await?;
+        Ok(InviteTree::load(ctx, root_user_id, depth).await?)
+    }
+
+    /// Reported submissions, grouped by submission, with the submissions
+    /// reported first at the top. By default, only submissions with open
+    /// reports are listed, which includes submissions the spam filter
+    /// held for review, or on which it held comments. This is only
+    /// available to administrators and moderators.
+    async fn moderation_queue(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        #[graphql(default = ReportStatus::Open)] status: ReportStatus,
+    ) -> Result<RelayConnection<ReportedUrl>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                Ok(Report::queue(ctx, status, after, before, limit).await?)
+            },
+        )
+        .await
+    }
+
+    /// Removals of submissions by moderators, most recent first, with
+    /// the moderator and the reason they gave. This is only available
+    /// to administrators.
+    async fn moderation_log(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<ModerationLog>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                Ok(ModerationLog::all(ctx, after, before, limit).await?)
+            },
+        )
+        .await
+    }
+
+    /// Renames and merges of tags by administrators, most recent
+    /// first. This is only available to administrators.
+    async fn tag_log(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<TagLog>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move { Ok(TagLog::all(ctx, after, before, limit).await?) },
+        )
+        .await
+    }
+
+    /// Tags starting with the given prefix, most used first, for
+    /// suggesting tags while typing. At most 25 tags are returned,
+    /// and prefixes shorter than two characters yield no tags.
+    async fn tag_suggestions(
+        ctx: &Context,
+        prefix: String,
+        #[graphql(default = 10)] limit: i32,
+    ) -> Result<Vec<TagSuggestion>, AppError> {
+        Ok(Tag::suggestions(ctx, &prefix, limit.into()).await?)
+    }
+
+    /// All tags which are in use, most used
+    /// or most recent first.
+    async fn tags(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        #[graphql(default = TagSort::Popular)] sort: TagSort,
+    ) -> Result<RelayConnection<Tag>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                Ok(Tag::all(ctx, sort, after, before, limit).await?)
+            },
+        )
+        .await
+    }
+
+    /// The users who earned the most points from votes cast within
+    /// the given period, the last week by default. Users earn points
+    /// when others vote on their submissions and comments, weighted
+    /// like karma. Users with the same number of points are listed by
+    /// their username. Banned users, and users who opted out of the
+    /// leaderboard in their preferences, are not listed.
+    async fn leaderboard(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        #[graphql(default = LeaderboardPeriod::Week)] period: LeaderboardPeriod,
+    ) -> Result<RelayConnection<LeaderboardEntry>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                Ok(LeaderboardEntry::list(ctx, period, after, before, limit).await?)
+            },
+        )
+        .await
     }
 
     /// Search through all submitted urls.
@@ -22,39 +186,172 @@ impl Query {
         Search::new(query)
     }
 
-    /// All submitted urls in reverse
-    /// chronological order.
+    /// All submitted urls in the given order, newest first by
+    /// default, optionally only those with the given `tag`. Cursors
+    /// are only valid for the order they were returned in. The `range`
+    /// limits the `TOP` order to recent submissions, and defaults to
+    /// `DAY`. It can not be given for other orders. The `linkStatus`
+    /// restricts the list to submissions whose link was last found
+    /// in that status, e.g. to find broken links. The `languages`
+    /// restrict the list to submissions in those languages, given as
+    /// ISO 639-1 codes, and default to the preferred languages of the
+    /// viewer. Submissions whose language is unknown are always listed,
+    /// and an empty list includes all languages. Submissions to groups
+    /// are only listed if their group is syndicated.
     async fn submissions(
         ctx: &Context,
         first: Option<i32>,
         after: Option<String>,
         last: Option<i32>,
         before: Option<String>,
-    ) -> FieldResult<RelayConnection<Url>> {
-        RelayConnection::new_async(
+        tag: Option<String>,
+        #[graphql(default = UrlSort::Newest)] sort: UrlSort,
+        range: Option<TopRange>,
+        link_status: Option<LinkStatus>,
+        languages: Option<Vec<String>>,
+    ) -> Result<RelayConnection<CursorUrl<UrlCursor>>, AppError> {
+        let languages = language::filter(ctx, languages).await?;
+        let filter = UrlFilter {
+            tag: tag.as_deref(),
+            range,
+            link_status,
+            languages: languages.as_deref(),
+            ..Default::default()
+        };
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                let urls = Url::all_submissions(ctx, filter, sort, after, before, limit).await?;
+                Ok(urls
+                    .into_iter()
+                    .map(|url| CursorUrl::sorted(url, sort))
+                    .collect())
+            },
+        )
+        .await
+    }
+
+    /// Check whether the given URL was submitted before, e.g. to warn
+    /// about duplicates before submitting it. The URL is validated and
+    /// compared exactly like when submitting it. This doesn't require
+    /// logging in, but is rate limited.
+    async fn check_url(ctx: &Context, url: String) -> Result<UrlCheck, AppError> {
+        Ok(Url::check(ctx, &url).await?)
+    }
+
+    /// All submitted urls from the given registrable domain, in the
+    /// given order, newest first by default. Any host name or URL of
+    /// the domain may be given, e.g. `blog.example.co.uk` lists all
+    /// submissions from `example.co.uk`. The `TOP` order includes
+    /// submissions of all time. The `linkStatus` filters like for
+    /// `submissions`.
+    async fn domain_feed(
+        ctx: &Context,
+        domain: String,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        #[graphql(default = UrlSort::Newest)] sort: UrlSort,
+        link_status: Option<LinkStatus>,
+    ) -> Result<RelayConnection<CursorUrl<UrlCursor>>, AppError> {
+        let domain = domain::normalize(&domain).unwrap_or_default();
+        let filter = UrlFilter {
+            domain: Some(&domain),
+            link_status,
+            range: match sort {
+                UrlSort::Top => Some(TopRange::All),
+                _ => None,
+            },
+            ..Default::default()
+        };
+        connection::new_async(
             first,
             after,
             last,
             before,
             |after, before, limit| async move {
-                Ok(Url::all_submissions(ctx, after, before, limit).await?)
+                let urls = Url::all_submissions(ctx, filter, sort, after, before, limit).await?;
+                Ok(urls
+                    .into_iter()
+                    .map(|url| CursorUrl::sorted(url, sort))
+                    .collect())
             },
         )
         .await
     }
 
+    /// The user with the given username, if any. Banned users are
+    /// only returned to moderators and administrators.
+    async fn user(ctx: &Context, username: String) -> Result<Option<User>, AppError> {
+        let user = match User::find_by_username(ctx, &username).await? {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+        if user.is_banned() {
+            let may_view_banned = match ctx.maybe_user().await? {
+                Some(viewer) => viewer
+                    .permissions(ctx)
+                    .await?
+                    .iter()
+                    .any(|perm| perm.ban_users()),
+                None => false,
+            };
+            if !may_view_banned {
+                return Ok(None);
+            }
+        }
+        Ok(Some(user))
+    }
+
+    /// The collection with the given ID. Public and unlisted
+    /// collections can be viewed by anyone, private collections
+    /// only by their owner.
+    async fn collection(ctx: &Context, id: CollectionID) -> Result<Collection, AppError> {
+        Ok(Collection::find(ctx, id).await?)
+    }
+
+    /// The group with the given slug, if any. Unlisted groups
+    /// can be found by their slug as well.
+    async fn group(ctx: &Context, slug: String) -> Result<Option<Group>, AppError> {
+        Ok(Group::find_by_slug(ctx, &slug).await?)
+    }
+
+    /// All public groups, newest first.
+    async fn groups(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<Group>, AppError> {
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move { Ok(Group::all(ctx, after, before, limit).await?) },
+        )
+        .await
+    }
+
     #[graphql(name = "fetch__Url")]
-    async fn fetch_url(ctx: &Context, id: UrlID) -> FieldResult<Url> {
-        Ok(Url::find(ctx, id).await?)
+    async fn fetch_url(ctx: &Context, id: UrlID) -> Result<Url, AppError> {
+        Ok(Url::find_visible(ctx, id).await?)
     }
 
     #[graphql(name = "fetch__Comment")]
-    async fn fetch_comment(ctx: &Context, id: CommentID) -> FieldResult<Comment> {
-        Ok(Comment::find(ctx, id).await?)
+    async fn fetch_comment(ctx: &Context, id: CommentID) -> Result<Comment, AppError> {
+        let comment = Comment::find(ctx, id).await?;
+        comment.check_not_held(ctx).await?;
+        Ok(comment)
     }
 
     #[graphql(name = "fetch__User")]
-    async fn fetch_user(ctx: &Context, id: UserID) -> FieldResult<User> {
+    async fn fetch_user(ctx: &Context, id: UserID) -> Result<User, AppError> {
         Ok(User::find(ctx, id).await?)
     }
 }
@@ -1,11 +1,54 @@
 use crate::db::id::UrlID;
-use crate::db::models::Url;
+use crate::db::models::{Listed, Url};
+use crate::db::SearchCursor;
+use crate::error::AppError;
+use crate::graphql::connection;
+use crate::graphql::objects::CursorUrl;
 use crate::schema::urls;
-use crate::Context;
+use crate::{language, signing, Context};
 use diesel::prelude::*;
-use juniper::{graphql_object, FieldResult, ID};
+use juniper::{graphql_object, ID};
 use juniper_relay_connection::RelayConnection;
 use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of results which are checked for visibility at once.
+const BATCH_SIZE: usize = 100;
+
+const CURSOR_PURPOSE: &str = "search_cursor";
+
+/// The cursor of a search result as handed out to clients, which is
+/// signed such that clients can't make up the rank a page starts at.
+#[derive(Clone)]
+pub struct SignedCursor(String);
+
+impl SignedCursor {
+    fn sign(ctx: &Context, cursor: &SearchCursor) -> Self {
+        Self(signing::sign(ctx, CURSOR_PURPOSE, &cursor.to_string()))
+    }
+
+    fn verify(&self, ctx: &Context) -> Result<SearchCursor, AppError> {
+        signing::verify(ctx, CURSOR_PURPOSE, &self.0)
+            .and_then(|cursor| cursor.parse().ok())
+            .ok_or_else(|| AppError::ValidationFailed("Invalid search cursor".into()))
+    }
+}
+
+impl fmt::Display for SignedCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for SignedCursor {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.to_string()))
+    }
+}
 
 pub struct Search(String);
 
@@ -22,7 +65,16 @@ impl Search {
         format!("search-{}", base64::encode(self.0.as_bytes())).into()
     }
 
-    /// The list of results returned by this search.
+    /// The list of results returned by this search, best matches first,
+    /// excluding deleted submissions, removed submissions hidden from the
+    /// viewer, NSFW submissions hidden from the viewer, submissions by
+    /// users blocked by the viewer, submissions from domains muted by
+    /// the viewer, and submissions to groups which are not syndicated.
+    /// Queries shorter than two characters have no results.
+    /// Later pages continue after the rank of the cursor, such that
+    /// new submissions don't shift results between pages. Cursors are
+    /// signed, and can't be made up by clients. Results can be
+    /// restricted to the given `languages`, like `submissions`.
     pub async fn results(
         &self,
         ctx: &Context,
@@ -30,28 +82,62 @@ impl Search {
         after: Option<String>,
         last: Option<i32>,
         before: Option<String>,
-    ) -> FieldResult<RelayConnection<Url>> {
-        // TODO(dyedgreen): Use the offset/ limit as cursors ...
-        let results = ctx.search().find(&self.0)?;
-        let conn = ctx.conn().await?;
-        let urls = RelayConnection::new(first, after, last, before, |after, before, _| {
-            let mut urls: HashMap<UrlID, Url> = urls::table
-                .filter(urls::id.eq_any(&results))
-                .load::<Url>(&*conn)?
-                .into_iter()
-                .map(|url| (url.id(), url))
-                .collect();
-            drop(conn);
-
-            let results = results
-                .into_iter()
-                .skip_while(|&id| after.map(|a| a != id).unwrap_or(false))
-                .skip(after.map(|_| 1).unwrap_or(0))
-                .take_while(|&id| before.map(|b| b != id).unwrap_or(true))
-                .filter_map(|id| urls.remove(&id))
-                .collect();
-            Ok(results)
-        })?;
-        Ok(urls)
+        languages: Option<Vec<String>>,
+    ) -> Result<RelayConnection<CursorUrl<SignedCursor>>, AppError> {
+        let listed = &Listed::to_viewer(ctx).await?.without_muted().syndicated();
+        let languages = &language::filter(ctx, languages).await?;
+        connection::new_async(
+            first,
+            after,
+            last,
+            before,
+            |after, before, limit| async move {
+                // page through the results until enough of them are
+                // visible, checking one batch of results at a time
+                let limit = limit.map_or(usize::MAX, |limit| limit.max(0) as usize);
+                let mut after = after.map(|after| after.verify(ctx)).transpose()?;
+                let before = before.map(|before| before.verify(ctx)).transpose()?;
+                let mut page = vec![];
+                while page.len() < limit {
+                    let wanted = (limit - page.len()).min(BATCH_SIZE);
+                    let results =
+                        ctx.search()
+                            .find(&self.0, after.as_ref(), before.as_ref(), wanted)?;
+                    let exhausted = results.len() < wanted;
+                    after = results.last().copied();
+
+                    let ids: Vec<UrlID> = results.iter().map(SearchCursor::id).collect();
+                    let conn = ctx.conn().await?;
+                    let mut query = urls::table
+                        .filter(urls::id.eq_any(&ids))
+                        .filter(listed.filter())
+                        .into_boxed();
+                    if let Some(languages) = languages {
+                        query = query.filter(
+                            urls::language
+                                .is_null()
+                                .or(urls::language.eq_any(languages)),
+                        );
+                    }
+                    let mut urls: HashMap<UrlID, Url> = query
+                        .load::<Url>(&*conn)?
+                        .into_iter()
+                        .map(|url| (url.id(), url))
+                        .collect();
+                    drop(conn);
+
+                    page.extend(results.into_iter().filter_map(|result| {
+                        let url = urls.remove(&result.id())?;
+                        Some(CursorUrl::new(url, SignedCursor::sign(ctx, &result)))
+                    }));
+                    if exhausted {
+                        break;
+                    }
+                }
+                page.truncate(limit);
+                Ok(page)
+            },
+        )
+        .await
     }
 }
@@ -0,0 +1,64 @@
+use crate::db::id::UrlID;
+use crate::db::models::{Comment, Url};
+use crate::events::Event;
+use crate::Context;
+use futures_util::Stream;
+use juniper::graphql_subscription;
+use std::pin::Pin;
+
+type UrlStream = Pin<Box<dyn Stream<Item = Url> + Send>>;
+type CommentStream = Pin<Box<dyn Stream<Item = Comment> + Send>>;
+
+pub struct Subscription;
+
+#[graphql_subscription(context = Context)]
+impl Subscription {
+    /// Submissions as they are published, which would be listed
+    /// to the viewer among all submissions, or those with the
+    /// given tag. Submissions which are published while they are
+    /// unlisted, e.g. drafts being published as unlisted, are not
+    /// included.
+    async fn url_submitted(ctx: &Context, tag: Option<String>) -> UrlStream {
+        let ctx = ctx.clone();
+        let urls = ctx.events().subscribe().filter_map(move |event| {
+            let (ctx, tag) = (ctx.clone(), tag.clone());
+            async move {
+                let url_id = match event {
+                    Event::UrlSubmitted(url_id) => url_id,
+                    _ => return None,
+                };
+                match Url::listed(&ctx, url_id, tag.as_deref()).await {
+                    Ok(url) => url,
+                    Err(err) => {
+                        log::error!("Failed to look up submitted url {}: {}", url_id, err);
+                        None
+                    }
+                }
+            }
+        });
+        Box::pin(urls)
+    }
+
+    /// Comments as they are published, which are visible to the
+    /// viewer, on the given submission.
+    async fn comment_added(ctx: &Context, url_id: UrlID) -> CommentStream {
+        let ctx = ctx.clone();
+        let comments = ctx.events().subscribe().filter_map(move |event| {
+            let ctx = ctx.clone();
+            async move {
+                let comment_id = match event {
+                    Event::CommentAdded(comment_id, on) if on == url_id => comment_id,
+                    _ => return None,
+                };
+                match Comment::listed(&ctx, comment_id).await {
+                    Ok(comment) => comment,
+                    Err(err) => {
+                        log::error!("Failed to look up comment {}: {}", comment_id, err);
+                        None
+                    }
+                }
+            }
+        });
+        Box::pin(comments)
+    }
+}
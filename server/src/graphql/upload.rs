@@ -0,0 +1,39 @@
+//! File uploads following the GraphQL multipart request specification,
+//! see <https://github.com/jaydenseric/graphql-multipart-request-spec>.
+//! The files of a request are stored in its context, and variables
+//! which refer to a file are replaced with the name of its part.
+
+use juniper::{ParseScalarResult, ParseScalarValue, Value};
+
+/// A file uploaded along with the request, which
+/// is read using [`crate::Context::upload`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upload(String);
+
+impl Upload {
+    /// The name of the multipart form part
+    /// which holds the file.
+    pub fn key(&self) -> &str {
+        &self.0
+    }
+}
+
+#[juniper::graphql_scalar(
+    description = "A file uploaded using a multipart request, following the GraphQL multipart request specification."
+)]
+impl<S> GraphQLScalar for Upload
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        Value::scalar(self.0.clone())
+    }
+
+    fn from_input_value(value: &InputValue) -> Option<Upload> {
+        value.as_string_value().map(|key| Upload(key.to_string()))
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> ParseScalarResult<'a, S> {
+        <String as ParseScalarValue<S>>::from_str(value)
+    }
+}
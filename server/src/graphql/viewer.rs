@@ -1,9 +1,19 @@
-use crate::db::models::{Invite, Login, User};
-use crate::schema::{invites, logins};
-use crate::Context;
+use crate::db::models::{
+    Block, BookmarkExport, Collection, DataExport, FeedUrls, Follow, Invite, InviteQuota, Login,
+    MutedDomain, Notification, NotificationFilter, RateLimit, SavedCounts, SavedStatus, SavedUrl,
+    SavedUrlCursor, SecurityEvent, ServiceImport, Tag, Url, UrlFilter, UrlSort, User,
+    UserPreferences, Webhook,
+};
+use crate::error::AppError;
+use crate::graphql::connection;
+use crate::graphql::objects::{CursorUrl, FeedItem};
+use crate::schema::{data_exports, invites, logins, security_events, urls};
+use crate::{domain, Context};
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
-use juniper::{graphql_object, FieldResult, ID};
+use juniper::{graphql_object, ID};
 use juniper_relay_connection::RelayConnection;
+use std::convert::TryInto;
 
 pub struct Viewer;
 
@@ -20,12 +30,12 @@ impl Viewer {
     }
 
     /// The currently logged in user, if any.
-    async fn user(ctx: &Context) -> FieldResult<Option<User>> {
+    async fn user(ctx: &Context) -> Result<Option<User>, AppError> {
         Ok(ctx.maybe_user().await?)
     }
 
     /// Email address of the currently logged in user.
-    async fn email(ctx: &Context) -> FieldResult<Option<String>> {
+    async fn email(ctx: &Context) -> Result<Option<String>, AppError> {
         // This field is on the viewer, since the email of other uses
         // should not be accessible without being logged in as that user.
         // By having it on the viewer, the graphql type system can enforce
@@ -38,9 +48,164 @@ impl Viewer {
         Ok(email)
     }
 
+    /// Whether the currently logged in user verified their
+    /// email address. Unverified users can not submit urls
+    /// or comment.
+    async fn email_verified(ctx: &Context) -> Result<Option<bool>, AppError> {
+        Ok(ctx.maybe_user().await?.map(|user| user.is_email_verified()))
+    }
+
+    /// The last time the currently logged in user logged
+    /// in, or null if no user is logged in.
+    async fn last_login_at(ctx: &Context) -> Result<Option<DateTime<Utc>>, AppError> {
+        Ok(ctx
+            .maybe_user()
+            .await?
+            .and_then(|user| user.last_login_at()))
+    }
+
+    /// The number of times the currently logged in user
+    /// logged in, or null if no user is logged in.
+    async fn login_count(ctx: &Context) -> Result<Option<i32>, AppError> {
+        Ok(ctx.maybe_user().await?.map(|user| user.login_count()))
+    }
+
+    /// The karma of the currently logged in user, or null if no
+    /// user is logged in. Downvoting requires some karma.
+    async fn karma(ctx: &Context) -> Result<Option<i32>, AppError> {
+        match ctx.maybe_user().await? {
+            Some(user) => Ok(Some(user.karma().try_into()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The url of the private feed of the currently logged in user,
+    /// or null if no user is logged in. The url contains a secret
+    /// token, and can be invalidated using `regenerateFeedToken`.
+    async fn feed_url(ctx: &Context) -> Result<Option<String>, AppError> {
+        match ctx.maybe_user().await? {
+            Some(mut user) => Ok(Some(user.feed_url(ctx).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The urls of the private per-user feeds of the currently logged
+    /// in user, or null if no user is logged in. Like `feedUrl`, they
+    /// stop working when the feed token is regenerated.
+    async fn feed_urls(ctx: &Context) -> Result<Option<FeedUrls>, AppError> {
+        match ctx.maybe_user().await? {
+            Some(mut user) => Ok(Some(user.feed_urls(ctx).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Settings of the currently logged in user, or null
+    /// if no user is logged in.
+    async fn preferences(ctx: &Context) -> Result<Option<UserPreferences>, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(Some(UserPreferences::find(ctx, user_id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Data exports requested by the currently logged in user,
+    /// ordered newest first. If no user is logged in, the list
+    /// will be empty.
+    async fn data_exports(ctx: &Context) -> Result<Vec<DataExport>, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(data_exports::table
+                .filter(data_exports::dsl::user_id.eq(user_id))
+                .order_by(data_exports::dsl::created_at.desc())
+                .load(&*ctx.conn().await?)?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Bookmark exports requested by the currently logged in user,
+    /// ordered newest first. If no user is logged in, the list will
+    /// be empty.
+    async fn exports(ctx: &Context) -> Result<Vec<BookmarkExport>, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(BookmarkExport::for_user(ctx, user_id).await?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Imports from remote services requested by the currently logged
+    /// in user, ordered newest first, which show the progress of running
+    /// imports. If no user is logged in, the list will be empty.
+    async fn imports(ctx: &Context) -> Result<Vec<ServiceImport>, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(ServiceImport::for_user(ctx, user_id).await?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Webhooks created by the currently logged in user, oldest
+    /// first. If no user is logged in, the list will be empty.
+    async fn webhooks(ctx: &Context) -> Result<Vec<Webhook>, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(Webhook::for_user(ctx, user_id).await?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Users blocked by the currently logged in user, most recently
+    /// blocked first. If no user is logged in, the list will be empty.
+    async fn blocked_users(ctx: &Context) -> Result<Vec<User>, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(Block::blocked_users(ctx, user_id).await?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Tags followed by the currently logged in user, ordered by
+    /// name. If no user is logged in, the list will be empty.
+    async fn followed_tags(ctx: &Context) -> Result<Vec<Tag>, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(Tag::followed_by(ctx, user_id).await?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Domains muted by the currently logged in user, most recently
+    /// muted first, in their unicode form. If no user is logged in,
+    /// the list will be empty.
+    async fn muted_domains(ctx: &Context) -> Result<Vec<String>, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(MutedDomain::muted_by(ctx, user_id)
+                .await?
+                .iter()
+                .map(|muted| domain::to_unicode(muted))
+                .collect()),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// The number of invitations the currently logged in user
+    /// may issue, or null if no user is logged in.
+    async fn invite_quota(ctx: &Context) -> Result<Option<InviteQuota>, AppError> {
+        match ctx.maybe_user().await? {
+            Some(user) => Ok(Some(Invite::quota(ctx, &user).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// How many urls and comments the currently logged in user may
+    /// still post before being rate limited, or null if no user is
+    /// logged in. New accounts have lower limits, and administrators
+    /// are not limited, in which case the list is empty.
+    async fn rate_limits(ctx: &Context) -> Result<Option<Vec<RateLimit>>, AppError> {
+        match ctx.maybe_user().await? {
+            Some(user) => Ok(Some(RateLimit::all(ctx, &user).await?)),
+            None => Ok(None),
+        }
+    }
+
     /// Invitations issued by the currently logged in user. If no
-    /// user is logged in, the connection will be empty. The invitations
-    /// can optionally be filtered by claimed or available.
+    /// user is logged in, the connection will be empty. Revoked
+    /// invitations are not included. The invitations can optionally
+    /// be filtered by claimed or available.
     async fn invites(
         ctx: &Context,
         first: Option<i32>,
@@ -48,13 +213,14 @@ impl Viewer {
         last: Option<i32>,
         before: Option<String>,
         claimed: Option<bool>,
-    ) -> FieldResult<RelayConnection<Invite>> {
+    ) -> Result<RelayConnection<Invite>, AppError> {
         if let Some(user_id) = ctx.maybe_user_id() {
             let conn = ctx.conn().await?;
             // TODO: We might want to move this to some other place ...
-            RelayConnection::new(first, after, last, before, |after, before, limit| {
+            connection::new(first, after, last, before, |after, before, limit| {
                 let mut query = invites::table
                     .filter(invites::dsl::created_by.eq(user_id))
+                    .filter(invites::dsl::revoked_at.is_null())
                     .order_by(invites::dsl::created_at.desc())
                     .into_boxed();
 
@@ -88,6 +254,92 @@ impl Viewer {
         }
     }
 
+    /// Drafts of the currently logged in user, e.g. imported bookmarks,
+    /// most recently created first. If no user is logged in, the
+    /// connection will be empty.
+    async fn drafts(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<Url>, AppError> {
+        if let Some(user_id) = ctx.maybe_user_id() {
+            let conn = ctx.conn().await?;
+            connection::new::<Url, _>(first, after, last, before, |after, before, limit| {
+                use urls::dsl::{id, published_at};
+
+                let mut query = urls::table
+                    .filter(urls::dsl::created_by.eq(user_id))
+                    .filter(urls::dsl::draft.eq(true))
+                    .filter(urls::dsl::deleted_at.is_null())
+                    .order_by(published_at.desc())
+                    .then_order_by(id.desc())
+                    .into_boxed();
+
+                if let Some(after) = after {
+                    query = query.filter(
+                        published_at
+                            .lt(after.published_at())
+                            .or(published_at.eq(after.published_at()).and(id.lt(after.id()))),
+                    );
+                }
+                if let Some(before) = before {
+                    query = query.filter(
+                        published_at.gt(before.published_at()).or(published_at
+                            .eq(before.published_at())
+                            .and(id.gt(before.id()))),
+                    );
+                }
+                if let Some(limit) = limit {
+                    query = query.limit(limit);
+                }
+
+                Ok(query.load(&*conn)?)
+            })
+        } else {
+            Ok(RelayConnection::empty())
+        }
+    }
+
+    /// Submissions by users the currently logged in user follows, or
+    /// with tags they follow, newest first. Each submission is listed
+    /// once, along with why it is listed. This excludes submissions
+    /// hidden from the viewer like on the front page. If no user is
+    /// logged in, the connection will be empty.
+    async fn following_feed(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<RelayConnection<FeedItem>, AppError> {
+        if let Some(user_id) = ctx.maybe_user_id() {
+            let filter = UrlFilter {
+                followed_by: Some(user_id),
+                ..Default::default()
+            };
+            connection::new_async(
+                first,
+                after,
+                None,
+                None,
+                |after, before, limit| async move {
+                    let urls =
+                        Url::all_submissions(ctx, filter, UrlSort::Newest, after, before, limit)
+                            .await?;
+                    let sources = Follow::feed_sources(ctx, user_id, &urls).await?;
+                    Ok(urls
+                        .into_iter()
+                        .zip(sources)
+                        .map(|(url, source)| FeedItem::new(url, source))
+                        .collect())
+                },
+            )
+            .await
+        } else {
+            Ok(RelayConnection::empty())
+        }
+    }
+
     /// Active login sessions for the currently logged in user. If no
     /// user is logged in, the connection will be empty.
     async fn logins(
@@ -96,10 +348,10 @@ impl Viewer {
         after: Option<String>,
         last: Option<i32>,
         before: Option<String>,
-    ) -> FieldResult<RelayConnection<Login>> {
+    ) -> Result<RelayConnection<Login>, AppError> {
         if let Some(user_id) = ctx.maybe_user_id() {
             let conn = ctx.conn().await?;
-            RelayConnection::new(first, after, last, before, |after, before, _| {
+            connection::new(first, after, last, before, |after, before, _| {
                 let mut query = logins::table
                     .filter(logins::dsl::user_id.eq(user_id))
                     .filter(logins::dsl::claimed.eq(true))
@@ -129,4 +381,160 @@ impl Viewer {
             Ok(RelayConnection::empty())
         }
     }
+
+    /// Submissions the currently logged in user saved to their reading
+    /// list, most recently saved first. If no user is logged in, the
+    /// connection will be empty. By default, only unread saves are
+    /// listed.
+    async fn saved_urls(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        #[graphql(default = SavedStatus::Unread)] status: SavedStatus,
+    ) -> Result<RelayConnection<CursorUrl<SavedUrlCursor>>, AppError> {
+        if let Some(user_id) = ctx.maybe_user_id() {
+            connection::new_async(
+                first,
+                after,
+                last,
+                before,
+                |after, before, limit| async move {
+                    let saves =
+                        SavedUrl::reading_list(ctx, user_id, status, after, before, limit).await?;
+                    Ok(saves
+                        .into_iter()
+                        .map(|(save, url)| CursorUrl::new(url, save.cursor()))
+                        .collect())
+                },
+            )
+            .await
+        } else {
+            Ok(RelayConnection::empty())
+        }
+    }
+
+    /// Collections of the currently logged in user, newest first,
+    /// including unlisted and private ones. If no user is logged in,
+    /// the connection will be empty.
+    async fn collections(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<Collection>, AppError> {
+        if let Some(user_id) = ctx.maybe_user_id() {
+            connection::new_async(
+                first,
+                after,
+                last,
+                before,
+                |after, before, limit| async move {
+                    Ok(Collection::for_user(ctx, user_id, false, after, before, limit).await?)
+                },
+            )
+            .await
+        } else {
+            Ok(RelayConnection::empty())
+        }
+    }
+
+    /// The number of read and unread saves in the reading list of
+    /// the currently logged in user, or null if no user is logged in.
+    async fn saved_counts(ctx: &Context) -> Result<Option<SavedCounts>, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(Some(SavedUrl::counts(ctx, user_id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Notifications about replies to submissions and comments of the
+    /// currently logged in user, newest first. If no user is logged in,
+    /// the connection will be empty. By default, only unread
+    /// notifications are listed.
+    async fn notifications(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        #[graphql(default = NotificationFilter::Unread)] filter: NotificationFilter,
+    ) -> Result<RelayConnection<Notification>, AppError> {
+        if let Some(user_id) = ctx.maybe_user_id() {
+            connection::new_async(
+                first,
+                after,
+                last,
+                before,
+                |after, before, limit| async move {
+                    Ok(Notification::list(ctx, user_id, filter, after, before, limit).await?)
+                },
+            )
+            .await
+        } else {
+            Ok(RelayConnection::empty())
+        }
+    }
+
+    /// The number of unread notifications of the currently
+    /// logged in user, or null if no user is logged in.
+    async fn unread_notification_count(ctx: &Context) -> Result<Option<i32>, AppError> {
+        match ctx.maybe_user_id() {
+            Some(user_id) => Ok(Some(Notification::unread_count(ctx, user_id).await? as i32)),
+            None => Ok(None),
+        }
+    }
+
+    /// Recent security relevant events (e.g. logins or revoked sessions)
+    /// for the currently logged in user, ordered newest first. If no user
+    /// is logged in, the connection will be empty.
+    async fn security_events(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<RelayConnection<SecurityEvent>, AppError> {
+        if let Some(user_id) = ctx.maybe_user_id() {
+            let conn = ctx.conn().await?;
+            connection::new(first, after, last, before, |after, before, limit| {
+                use security_events::dsl;
+                let mut query = security_events::table
+                    .filter(dsl::user_id.eq(user_id))
+                    .order_by(dsl::created_at.desc())
+                    .then_order_by(dsl::id.desc())
+                    .into_boxed();
+
+                if let Some(after) = after {
+                    let after: SecurityEvent =
+                        security_events::table.find(after).get_result(&*conn)?;
+                    let created_at = after.created_at().naive_utc();
+                    query = query.filter(
+                        dsl::created_at
+                            .lt(created_at)
+                            .or(dsl::created_at.eq(created_at).and(dsl::id.lt(after.id()))),
+                    );
+                }
+                if let Some(before) = before {
+                    let before: SecurityEvent =
+                        security_events::table.find(before).get_result(&*conn)?;
+                    let created_at = before.created_at().naive_utc();
+                    query = query.filter(
+                        dsl::created_at
+                            .gt(created_at)
+                            .or(dsl::created_at.eq(created_at).and(dsl::id.gt(before.id()))),
+                    );
+                }
+                if let Some(limit) = limit {
+                    query = query.limit(limit);
+                }
+
+                Ok(query.load(&*conn)?)
+            })
+        } else {
+            Ok(RelayConnection::empty())
+        }
+    }
 }
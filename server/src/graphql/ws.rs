@@ -0,0 +1,420 @@
+//! GraphQL subscriptions over websockets, following the `graphql-ws`
+//! protocol (`graphql-transport-ws`). Connections authenticate using
+//! the `sessionToken` of their `connection_init` payload, since the
+//! session cookie would let other sites subscribe on behalf of the
+//! viewer. Connections without a session token subscribe logged out.
+//! The session is checked again periodically, and connections whose
+//! session ended are closed.
+
+use super::{complexity, persisted, Schema};
+use crate::context::SessionSource;
+use crate::db::models::Login;
+use crate::Context;
+use futures_util::stream::{self, SplitStream};
+use futures_util::{FutureExt, SinkExt, StreamExt};
+use juniper::{Value as GraphQLValue, Variables};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use warp::ws::{Message, WebSocket};
+
+/// The websocket subprotocol of the `graphql-ws` protocol.
+pub const PROTOCOL: &str = "graphql-transport-ws";
+
+/// Time clients have to initialize their connection.
+const CONNECTION_INIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval in which the session of a connection is checked again.
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Subscriptions a single connection may have running at once.
+const MAX_SUBSCRIPTIONS: usize = 16;
+
+/// Messages which may be waiting to be sent to a client. Clients
+/// which fall further behind are disconnected.
+const OUTGOING_BUFFER: usize = 64;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    ConnectionInit {
+        #[serde(default)]
+        payload: Option<InitPayload>,
+    },
+    Ping,
+    Pong,
+    Subscribe {
+        id: String,
+        payload: Value,
+    },
+    Complete {
+        id: String,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitPayload {
+    session_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubscribeRequest {
+    query: String,
+    operation_name: Option<String>,
+    variables: Option<Variables>,
+}
+
+/// Why the server closed a connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Close {
+    InvalidMessage,
+    Unauthorized,
+    Forbidden,
+    InitTimeout,
+    SubscriberExists(String),
+    TooManyInits,
+    SlowClient,
+}
+
+impl Close {
+    fn message(&self) -> Message {
+        match self {
+            Close::InvalidMessage => Message::close_with(4400u16, "Invalid message"),
+            Close::Unauthorized => Message::close_with(4401u16, "Unauthorized"),
+            Close::Forbidden => Message::close_with(4403u16, "Forbidden"),
+            Close::InitTimeout => Message::close_with(4408u16, "Connection initialisation timeout"),
+            Close::SubscriberExists(id) => {
+                Message::close_with(4409u16, format!("Subscriber for {} already exists", id))
+            }
+            Close::TooManyInits => Message::close_with(4429u16, "Too many initialisation requests"),
+            Close::SlowClient => Message::close_with(1008u16, "Too many pending messages"),
+        }
+    }
+}
+
+/// Queues messages to be sent to the client. If the client doesn't
+/// keep up with its messages, the connection is closed instead.
+#[derive(Clone)]
+struct Sender {
+    messages: mpsc::Sender<Message>,
+    overflow: Arc<Notify>,
+}
+
+impl Sender {
+    fn send_message(&self, message: Message) {
+        match self.messages.try_send(message) {
+            // the connection was closed already
+            Ok(()) | Err(TrySendError::Closed(_)) => (),
+            Err(TrySendError::Full(_)) => self.overflow.notify_one(),
+        }
+    }
+}
+
+fn send(sender: &Sender, message: Value) {
+    sender.send_message(Message::text(message.to_string()));
+}
+
+/// Serve a websocket connection, until either side closes it. The
+/// context is that of the upgrade request, whose session is ignored.
+pub async fn serve(socket: WebSocket, schema: Arc<Schema>, ctx: Context) {
+    let (mut sink, mut incoming) = socket.split();
+    let (messages, mut outgoing) = mpsc::channel::<Message>(OUTGOING_BUFFER);
+    let overflow = Arc::new(Notify::new());
+    let sender = Sender {
+        messages,
+        overflow: overflow.clone(),
+    };
+    let writer = tokio::spawn(async move {
+        loop {
+            let message = tokio::select! {
+                message = outgoing.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                _ = overflow.notified() => {
+                    // drop the pending messages, and close the connection
+                    // without waiting for the client to catch up
+                    let _ = sink.send(Close::SlowClient.message()).await;
+                    break;
+                }
+            };
+            let close = message.is_close();
+            if sink.send(message).await.is_err() || close {
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    });
+
+    let mut subscriptions = HashMap::new();
+    let result = run(&schema, ctx, &mut incoming, &sender, &mut subscriptions).await;
+    for (_, subscription) in subscriptions {
+        subscription.abort();
+    }
+    if let Err(close) = result {
+        sender.send_message(close.message());
+    }
+    drop(sender);
+    let _ = writer.await;
+}
+
+/// The next message sent by the client, if the connection is open.
+async fn next_message(
+    incoming: &mut SplitStream<WebSocket>,
+) -> Option<Result<ClientMessage, Close>> {
+    while let Some(Ok(message)) = incoming.next().await {
+        if message.is_close() {
+            return None;
+        }
+        if let Ok(text) = message.to_str() {
+            return Some(serde_json::from_str(text).map_err(|_| Close::InvalidMessage));
+        }
+        if message.is_binary() {
+            return Some(Err(Close::InvalidMessage));
+        }
+    }
+    None
+}
+
+/// Wait for the client to initialize the connection, returning the
+/// payload of its `connection_init` message, if the connection is open.
+async fn connection_init(
+    incoming: &mut SplitStream<WebSocket>,
+    sender: &Sender,
+) -> Result<Option<Option<InitPayload>>, Close> {
+    while let Some(message) = next_message(incoming).await {
+        match message? {
+            ClientMessage::ConnectionInit { payload } => return Ok(Some(payload)),
+            ClientMessage::Ping => send(sender, json!({ "type": "pong" })),
+            ClientMessage::Pong => (),
+            _ => return Err(Close::Unauthorized),
+        }
+    }
+    Ok(None)
+}
+
+/// Initialize the connection, and start and stop subscriptions
+/// as requested by the client.
+async fn run(
+    schema: &Arc<Schema>,
+    ctx: Context,
+    incoming: &mut SplitStream<WebSocket>,
+    sender: &Sender,
+    subscriptions: &mut HashMap<String, JoinHandle<()>>,
+) -> Result<(), Close> {
+    let init = tokio::time::timeout(CONNECTION_INIT_TIMEOUT, connection_init(incoming, sender));
+    let payload = match init.await.map_err(|_| Close::InitTimeout)?? {
+        Some(payload) => payload,
+        None => return Ok(()),
+    };
+
+    let mut ctx = ctx.logged_out();
+    if let Some(token) = payload.and_then(|payload| payload.session_token) {
+        if let Err(err) = Login::use_session(&mut ctx, &token).await {
+            log::info!("Rejecting invalid session token: {}", err);
+            return Err(Close::Forbidden);
+        }
+        ctx.set_session_source(SessionSource::Header);
+    }
+    send(sender, json!({ "type": "connection_ack" }));
+
+    let mut session_check = tokio::time::interval_at(
+        Instant::now() + SESSION_CHECK_INTERVAL,
+        SESSION_CHECK_INTERVAL,
+    );
+    let (done_sender, mut done) = mpsc::unbounded_channel();
+    loop {
+        let message = tokio::select! {
+            message = next_message(incoming) => match message {
+                Some(message) => message?,
+                None => return Ok(()),
+            },
+            Some(id) = done.recv() => {
+                subscriptions.remove(&id);
+                continue;
+            }
+            _ = session_check.tick(), if ctx.is_logged_in() => {
+                check_session(&ctx).await?;
+                continue;
+            }
+        };
+        match message {
+            ClientMessage::ConnectionInit { .. } => return Err(Close::TooManyInits),
+            ClientMessage::Ping => send(sender, json!({ "type": "pong" })),
+            ClientMessage::Pong => (),
+            ClientMessage::Subscribe { id, payload } => {
+                while let Some(Some(id)) = done.recv().now_or_never() {
+                    subscriptions.remove(&id);
+                }
+                if subscriptions.contains_key(&id) {
+                    return Err(Close::SubscriberExists(id));
+                }
+                if subscriptions.len() >= MAX_SUBSCRIPTIONS {
+                    let error = json!({
+                        "message": format!("Connections may have at most {} subscriptions", MAX_SUBSCRIPTIONS),
+                        "extensions": { "code": "TOO_MANY_SUBSCRIPTIONS" },
+                    });
+                    send(
+                        sender,
+                        json!({ "type": "error", "id": id, "payload": [error] }),
+                    );
+                    continue;
+                }
+                // wait for the subscription to start, such that it
+                // receives all events after the client's next message
+                let (ready_sender, ready) = oneshot::channel();
+                let subscription = subscribe(
+                    schema.clone(),
+                    ctx.for_operation(),
+                    id.clone(),
+                    payload,
+                    sender.clone(),
+                    ready_sender,
+                );
+                let done_sender = done_sender.clone();
+                let task_id = id.clone();
+                let task = tokio::spawn(async move {
+                    subscription.await;
+                    let _ = done_sender.send(task_id);
+                });
+                subscriptions.insert(id, task);
+                let _ = ready.await;
+            }
+            ClientMessage::Complete { id } => {
+                if let Some(subscription) = subscriptions.remove(&id) {
+                    subscription.abort();
+                }
+            }
+        }
+    }
+}
+
+/// Check the session of the connection is still valid, i.e. that
+/// it wasn't logged out, revoked or expired since it was opened.
+async fn check_session(ctx: &Context) -> Result<(), Close> {
+    let token = match ctx.session_token() {
+        Some(token) => token,
+        None => return Ok(()),
+    };
+    let mut check = ctx.for_operation().logged_out();
+    match Login::use_session(&mut check, token).await {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            log::info!("Closing connection with ended session: {}", err);
+            Err(Close::Forbidden)
+        }
+    }
+}
+
+/// Read the request of a subscription, which is checked like other
+/// GraphQL requests, returning the GraphQL error it was rejected with.
+async fn subscribe_request(
+    schema: &Schema,
+    ctx: &Context,
+    payload: Value,
+) -> Result<SubscribeRequest, Value> {
+    let body = payload.to_string().into_bytes();
+    let body = persisted::resolve(ctx, &body).await.map_err(|rejected| {
+        json!({ "message": rejected.to_string(), "extensions": { "code": rejected.code() } })
+    })?;
+    let config = ctx.config();
+    complexity::check(
+        schema,
+        &body,
+        config.max_query_depth(),
+        config.max_query_complexity(),
+    )
+    .map_err(
+        |rejected| json!({ "message": rejected.to_string(), "extensions": rejected.extensions() }),
+    )?;
+    serde_json::from_slice(&body).map_err(|err| {
+        json!({
+            "message": format!("Invalid GraphQL request: {}", err),
+            "extensions": { "code": "BAD_REQUEST" },
+        })
+    })
+}
+
+/// Execute a subscription, sending its results until its streams end.
+/// The subscription is ready once it receives events.
+async fn subscribe(
+    schema: Arc<Schema>,
+    ctx: Context,
+    id: String,
+    payload: Value,
+    sender: Sender,
+    ready: oneshot::Sender<()>,
+) {
+    let request = match subscribe_request(&schema, &ctx, payload).await {
+        Ok(request) => request,
+        Err(error) => {
+            send(
+                &sender,
+                json!({ "type": "error", "id": id, "payload": [error] }),
+            );
+            return;
+        }
+    };
+    let variables = request.variables.unwrap_or_default();
+    let resolved = juniper::resolve_into_stream(
+        &request.query,
+        request.operation_name.as_deref(),
+        &*schema,
+        &variables,
+        &ctx,
+    )
+    .await;
+    let (value, errors) = match resolved {
+        Ok(resolved) => resolved,
+        Err(err) => {
+            let error = json!({ "message": err.to_string() });
+            send(
+                &sender,
+                json!({ "type": "error", "id": id, "payload": [error] }),
+            );
+            return;
+        }
+    };
+    let _ = ready.send(());
+
+    if errors.is_empty() {
+        let streams = match value {
+            GraphQLValue::Object(fields) => fields
+                .into_iter()
+                .filter_map(|(name, value)| match value {
+                    GraphQLValue::Scalar(stream) => {
+                        Some(stream.map(move |result| (name.clone(), result)).boxed())
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+        let mut results = stream::select_all(streams);
+        while let Some((name, result)) = results.next().await {
+            let payload = match result {
+                Ok(value) => json!({ "data": { name: value } }),
+                Err(err) => json!({ "data": { name: null }, "errors": [err] }),
+            };
+            send(
+                &sender,
+                json!({ "type": "next", "id": id, "payload": payload }),
+            );
+        }
+    } else {
+        let payload = json!({ "data": null, "errors": errors });
+        send(
+            &sender,
+            json!({ "type": "next", "id": id, "payload": payload }),
+        );
+    }
+    send(&sender, json!({ "type": "complete", "id": id }));
+}
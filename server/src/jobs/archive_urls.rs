@@ -0,0 +1,13 @@
+use crate::db::models::Url;
+use crate::Context;
+use anyhow::Result;
+
+/// Archives the pages of new and broken submissions,
+/// retrying failed attempts.
+pub async fn job(ctx: Context) -> Result<()> {
+    let attempts = Url::archive_pending(&ctx).await?;
+    if attempts > 0 {
+        log::info!("Attempted to archive {} pages", attempts);
+    }
+    Ok(())
+}
@@ -0,0 +1,8 @@
+use crate::db::models::BookmarkExport;
+use crate::Context;
+use anyhow::Result;
+
+/// Generates requested bookmark exports.
+pub async fn job(ctx: Context) -> Result<()> {
+    BookmarkExport::process_pending(&ctx).await
+}
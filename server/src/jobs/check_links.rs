@@ -0,0 +1,11 @@
+use crate::db::models::Url;
+use crate::Context;
+use anyhow::Result;
+
+/// Checks whether the links of submissions which
+/// were not checked recently still work.
+pub async fn job(ctx: Context) -> Result<()> {
+    let checked = Url::check_due_links(&ctx).await?;
+    log::info!("Checked {} links", checked);
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::db::models::Url;
+use crate::db::models::{MetadataStatus, SubmissionKind, Url};
 use crate::schema::urls;
 use crate::Context;
 use anyhow::Result;
@@ -8,16 +8,23 @@ use diesel::prelude::*;
 const DAYS_BETWEEN_CHECKS: i64 = 30;
 
 /// Update the URL meta information and status for
-/// old submissions.
+/// old submissions, and for submissions which were
+/// never fetched (e.g. because of a restart). Text
+/// posts have no page to fetch.
 pub async fn job(ctx: Context) -> Result<()> {
     let update_before = ctx.now() - Duration::days(DAYS_BETWEEN_CHECKS);
     let old_urls: Vec<Url> = urls::table
-        .filter(urls::dsl::updated_at.lt(update_before.naive_utc()))
+        .filter(urls::dsl::kind.eq(SubmissionKind::Link))
+        .filter(
+            urls::dsl::updated_at
+                .lt(update_before.naive_utc())
+                .or(urls::dsl::metadata_status.eq(MetadataStatus::Pending)),
+        )
         .load(&*ctx.conn().await?)?;
 
     log::info!("Updating meta information for {} urls", old_urls.len());
     for mut url in old_urls {
-        url.update_url_meta(&ctx)
+        url.fetch_metadata(&ctx)
             .await
             .map_err(|err| log::error!("Failed to update url meta: {}", err))
             .ok();
@@ -0,0 +1,9 @@
+use crate::db::models::DataExport;
+use crate::Context;
+use anyhow::Result;
+
+/// Assembles requested data exports and sends
+/// download links to the requesting users.
+pub async fn job(ctx: Context) -> Result<()> {
+    DataExport::process_pending(&ctx).await
+}
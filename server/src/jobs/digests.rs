@@ -0,0 +1,9 @@
+use crate::db::models::Digest;
+use crate::Context;
+use anyhow::Result;
+
+/// Sends email digests of the top submissions
+/// to users who are due to receive one.
+pub async fn job(ctx: Context) -> Result<()> {
+    Digest::send_due(&ctx).await
+}
@@ -0,0 +1,10 @@
+use crate::db::models::Url;
+use crate::Context;
+use anyhow::Result;
+
+/// Adds buffered outbound clicks to the
+/// stored click counts.
+pub async fn job(ctx: Context) -> Result<()> {
+    Url::flush_clicks(&ctx).await?;
+    Ok(())
+}
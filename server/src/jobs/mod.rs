@@ -1,14 +1,28 @@
-use crate::{db, email, Context};
+use crate::{db, email, Config, Context};
 use clokwerk::{Interval, ScheduleHandle, Scheduler};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::runtime::Handle;
 
+mod archive_urls;
+mod bookmark_exports;
+mod check_links;
 mod check_old_urls;
+mod data_exports;
+mod digests;
+mod flush_clicks;
 mod index_urls;
+mod prune_url_views;
+mod publish_scheduled;
+mod reconcile_karma;
+mod refresh_hot_ranks;
+mod service_imports;
+mod webhooks;
 
 fn schedule<J, F>(
     scheduler: &mut Scheduler,
     interval: Interval,
+    config: &Arc<Config>,
     pool: &db::Pool,
     mailer: &email::Mailer,
     runtime: &Handle,
@@ -18,11 +32,12 @@ fn schedule<J, F>(
     F: std::future::Future + Send + 'static,
     F::Output: Send + 'static,
 {
+    let config = config.clone();
     let pool = pool.clone();
     let mailer = mailer.clone();
     let runtime = runtime.clone();
     scheduler.every(interval).run(move || {
-        let ctx = Context::for_server(&pool, &mailer);
+        let ctx = Context::for_server(&config, &pool, &mailer);
         runtime.spawn((job)(ctx));
     });
 }
@@ -30,6 +45,7 @@ fn schedule<J, F>(
 /// Run scheduled background jobs.
 pub fn watch_thread(
     async_runtime: Handle,
+    config: Arc<Config>,
     pool: db::Pool,
     mailer: email::Mailer,
 ) -> ScheduleHandle {
@@ -38,20 +54,142 @@ pub fn watch_thread(
     schedule(
         &mut scheduler,
         Interval::Days(1),
+        &config,
         &pool,
         &mailer,
         &async_runtime,
         check_old_urls::job,
     );
 
+    schedule(
+        &mut scheduler,
+        Interval::Days(1),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        prune_url_views::job,
+    );
+
+    schedule(
+        &mut scheduler,
+        Interval::Days(1),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        reconcile_karma::job,
+    );
+
+    schedule(
+        &mut scheduler,
+        Interval::Hours(1),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        check_links::job,
+    );
+
+    schedule(
+        &mut scheduler,
+        Interval::Hours(1),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        digests::job,
+    );
+
     schedule(
         &mut scheduler,
         Interval::Minutes(1),
+        &config,
         &pool,
         &mailer,
         &async_runtime,
         index_urls::job,
     );
 
+    schedule(
+        &mut scheduler,
+        Interval::Minutes(1),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        flush_clicks::job,
+    );
+
+    schedule(
+        &mut scheduler,
+        Interval::Minutes(1),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        publish_scheduled::job,
+    );
+
+    schedule(
+        &mut scheduler,
+        Interval::Minutes(1),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        data_exports::job,
+    );
+
+    schedule(
+        &mut scheduler,
+        Interval::Minutes(1),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        bookmark_exports::job,
+    );
+
+    schedule(
+        &mut scheduler,
+        Interval::Minutes(1),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        service_imports::job,
+    );
+
+    schedule(
+        &mut scheduler,
+        Interval::Minutes(1),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        webhooks::job,
+    );
+
+    schedule(
+        &mut scheduler,
+        Interval::Minutes(5),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        refresh_hot_ranks::job,
+    );
+
+    schedule(
+        &mut scheduler,
+        Interval::Minutes(5),
+        &config,
+        &pool,
+        &mailer,
+        &async_runtime,
+        archive_urls::job,
+    );
+
     scheduler.watch_thread(Duration::from_millis(1000))
 }
@@ -0,0 +1,10 @@
+use crate::db::models::UrlView;
+use crate::Context;
+use anyhow::Result;
+
+/// Deletes stored views of past days, which
+/// are no longer needed for counting views.
+pub async fn job(ctx: Context) -> Result<()> {
+    UrlView::prune(&ctx).await?;
+    Ok(())
+}
@@ -0,0 +1,13 @@
+use crate::db::models::Url;
+use crate::Context;
+use anyhow::Result;
+
+/// Publishes scheduled submissions whose time
+/// has come.
+pub async fn job(ctx: Context) -> Result<()> {
+    let published = Url::publish_scheduled(&ctx).await?;
+    if published > 0 {
+        log::info!("Published {} scheduled urls", published);
+    }
+    Ok(())
+}
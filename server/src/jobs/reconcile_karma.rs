@@ -0,0 +1,13 @@
+use crate::db::models::User;
+use crate::Context;
+use anyhow::Result;
+
+/// Corrects the karma of users which drifted from
+/// the scores of their submissions and comments.
+pub async fn job(ctx: Context) -> Result<()> {
+    let corrected = User::reconcile_karma(&ctx).await?;
+    if corrected > 0 {
+        log::warn!("Corrected the karma of {} users", corrected);
+    }
+    Ok(())
+}
@@ -0,0 +1,9 @@
+use crate::db::models::Url;
+use crate::Context;
+use anyhow::Result;
+
+/// Recomputes the trending rank of recent
+/// submissions.
+pub async fn job(ctx: Context) -> Result<()> {
+    Url::refresh_hot_ranks(&ctx).await
+}
@@ -0,0 +1,9 @@
+use crate::db::models::ServiceImport;
+use crate::Context;
+use anyhow::Result;
+
+/// Imports the next page of bookmarks of pending
+/// imports from remote services.
+pub async fn job(ctx: Context) -> Result<()> {
+    ServiceImport::process_pending(&ctx).await
+}
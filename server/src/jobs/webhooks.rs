@@ -0,0 +1,9 @@
+use crate::db::models::WebhookDelivery;
+use crate::Context;
+use anyhow::Result;
+
+/// Sends pending webhook deliveries, and
+/// retries failed ones once they are due.
+pub async fn job(ctx: Context) -> Result<()> {
+    WebhookDelivery::deliver_pending(&ctx).await
+}
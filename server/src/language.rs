@@ -0,0 +1,184 @@
+//! Languages of submissions, which are detected from the fetched title
+//! and description of the linked page, such that listings can be
+//! restricted to the languages a viewer reads. Languages are identified
+//! by their ISO 639-1 code, e.g. `de`.
+//!
+//! Detection is unreliable for short or mixed texts. Languages are only
+//! stored if the detection is confident, and submissions without a known
+//! language are included in every listing rather than hidden.
+
+use crate::db::models::UserPreferences;
+use crate::Context;
+use anyhow::{anyhow, Result};
+
+/// Languages which can be detected, as pairs of ISO 639-3
+/// and ISO 639-1 codes.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("afr", "af"),
+    ("aka", "ak"),
+    ("amh", "am"),
+    ("ara", "ar"),
+    ("aze", "az"),
+    ("bel", "be"),
+    ("ben", "bn"),
+    ("bul", "bg"),
+    ("cat", "ca"),
+    ("ces", "cs"),
+    ("cmn", "zh"),
+    ("dan", "da"),
+    ("deu", "de"),
+    ("ell", "el"),
+    ("eng", "en"),
+    ("epo", "eo"),
+    ("est", "et"),
+    ("fin", "fi"),
+    ("fra", "fr"),
+    ("guj", "gu"),
+    ("heb", "he"),
+    ("hin", "hi"),
+    ("hrv", "hr"),
+    ("hun", "hu"),
+    ("hye", "hy"),
+    ("ind", "id"),
+    ("ita", "it"),
+    ("jav", "jv"),
+    ("jpn", "ja"),
+    ("kan", "kn"),
+    ("kat", "ka"),
+    ("khm", "km"),
+    ("kor", "ko"),
+    ("lat", "la"),
+    ("lav", "lv"),
+    ("lit", "lt"),
+    ("mal", "ml"),
+    ("mar", "mr"),
+    ("mkd", "mk"),
+    ("mya", "my"),
+    ("nep", "ne"),
+    ("nld", "nl"),
+    ("nob", "nb"),
+    ("ori", "or"),
+    ("pan", "pa"),
+    ("pes", "fa"),
+    ("pol", "pl"),
+    ("por", "pt"),
+    ("ron", "ro"),
+    ("rus", "ru"),
+    ("sin", "si"),
+    ("slk", "sk"),
+    ("slv", "sl"),
+    ("sna", "sn"),
+    ("spa", "es"),
+    ("srp", "sr"),
+    ("swe", "sv"),
+    ("tam", "ta"),
+    ("tel", "te"),
+    ("tgl", "tl"),
+    ("tha", "th"),
+    ("tuk", "tk"),
+    ("tur", "tr"),
+    ("ukr", "uk"),
+    ("urd", "ur"),
+    ("uzb", "uz"),
+    ("vie", "vi"),
+    ("yid", "yi"),
+    ("zul", "zu"),
+];
+
+/// Detect the language of the given text, returning its ISO 639-1
+/// code. Returns `None` if the detection is not confident.
+pub fn detect(text: &str) -> Option<&'static str> {
+    let info = whatlang::detect(text).filter(|info| info.is_reliable())?;
+    let code = info.lang().code();
+    LANGUAGES
+        .iter()
+        .find(|(iso_639_3, _)| *iso_639_3 == code)
+        .map(|(_, iso_639_1)| *iso_639_1)
+}
+
+/// Normalize the given ISO 639-1 code, failing if
+/// the language can not be detected.
+pub fn normalize(code: &str) -> Result<String> {
+    let code = code.trim().to_ascii_lowercase();
+    if LANGUAGES.iter().any(|(_, iso_639_1)| *iso_639_1 == code) {
+        Ok(code)
+    } else {
+        Err(anyhow!("Unknown language {}", code))
+    }
+}
+
+/// Normalize the given ISO 639-1 codes, see [`normalize`].
+/// Duplicates are removed.
+pub fn normalize_all(codes: &[String]) -> Result<Vec<String>> {
+    let mut languages = codes
+        .iter()
+        .map(|code| normalize(code))
+        .collect::<Result<Vec<_>>>()?;
+    languages.sort();
+    languages.dedup();
+    Ok(languages)
+}
+
+/// The languages a listing is restricted to. If no languages are
+/// given, the preferred languages of the viewer apply. Returns `None`
+/// if the listing includes all languages, which is the case for an
+/// empty list, and for anonymous viewers without a list.
+pub async fn filter(ctx: &Context, languages: Option<Vec<String>>) -> Result<Option<Vec<String>>> {
+    let languages = match (languages, ctx.maybe_user_id()) {
+        (Some(languages), _) => normalize_all(&languages)?,
+        (None, Some(user_id)) => UserPreferences::find(ctx, user_id).await?.languages(),
+        (None, None) => vec![],
+    };
+    if languages.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(languages))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let cases = [
+            (
+                "en",
+                "The quick brown fox jumps over the lazy dog, and then it runs \
+                back into the forest to find something to eat before nightfall.",
+            ),
+            (
+                "de",
+                "Der schnelle braune Fuchs springt über den faulen Hund und läuft \
+                danach zurück in den Wald, um vor der Dunkelheit etwas zu essen.",
+            ),
+            (
+                "es",
+                "El rápido zorro marrón salta sobre el perro perezoso y luego \
+                vuelve corriendo al bosque para buscar algo de comer antes de la noche.",
+            ),
+        ];
+        for (language, text) in cases {
+            assert_eq!(detect(text), Some(language), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_detect_not_confident() {
+        for text in ["", "Rust 1.56", "OK", "https://example.com/"] {
+            assert_eq!(detect(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(" DE ").unwrap(), "de");
+        assert!(normalize("deu").is_err());
+        assert!(normalize("xx").is_err());
+        assert_eq!(
+            normalize_all(&["fr".into(), "en".into(), "FR".into()]).unwrap(),
+            vec!["en", "fr"]
+        );
+    }
+}
@@ -5,35 +5,56 @@ extern crate diesel;
 extern crate diesel_migrations;
 
 use std::convert::Infallible;
+use std::sync::Arc;
 use warp::{Filter, Reply};
 
+pub mod archive;
+pub mod bookmark_source;
+pub mod bookmarks;
+pub mod canonical;
+pub mod clicks;
 pub mod config;
 pub mod context;
 pub mod db;
+pub mod domain;
 pub mod email;
+pub mod embed;
+pub mod error;
+pub mod events;
+pub mod fetch;
 pub mod graphql;
 pub mod jobs;
+pub mod language;
+pub mod link;
+pub mod loader;
+pub mod markdown;
+pub mod mentions;
 pub mod pages;
+pub mod persisted;
+pub mod preview;
+pub mod rate_limit;
 pub mod schema;
 pub mod setup;
+pub mod signing;
+pub mod spam;
+pub mod storage;
+pub mod validation;
 
-pub use config::Config;
+pub use config::{Config, ConfigError, IpPrivacy, PostingLimits, RegistrationMode, VarError};
 pub use context::Context;
 
 /// Global routes for the app. These are separated out to enable
 /// simple integration testing on the whole server without running
 /// an actual web server.
 ///
-/// Note that some places use the global `Config.env()`, to access
-/// configuration information, so some of the config values set in
-/// e.g. a test config might not always be honored by the resulting
-/// filter. (There is the aspiration to change this in the future.)
+/// The given config is made available to request handlers through
+/// the request `Context`.
 pub fn global_routes(
     conf: &config::Config,
     pool: db::Pool,
     mailer: email::Mailer,
 ) -> impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone {
-    let ctx = pages::context(pool, mailer);
+    let ctx = pages::context(Arc::new(conf.clone()), pool, mailer);
 
     let index = ctx.clone().with(warp::wrap_fn(pages::url_lists::ranked));
     let index = warp::any().and(index);
@@ -52,9 +73,14 @@ pub fn global_routes(
 
     let feed = ctx.clone().with(warp::wrap_fn(pages::feed::page));
 
+    let sitemap = ctx.clone().with(warp::wrap_fn(pages::sitemap::page));
+
     let comments = ctx.clone().with(warp::wrap_fn(pages::comments::page));
     let comments = warp::path("comments").and(comments);
 
+    let outbound = ctx.clone().with(warp::wrap_fn(pages::outbound::page));
+    let outbound = warp::path("out").and(outbound);
+
     let login = ctx.clone().with(warp::wrap_fn(pages::login::page));
     let login = warp::path("login").and(login);
 
@@ -63,13 +89,32 @@ pub fn global_routes(
 
     let logout = warp::path("logout").and(pages::logout::filter());
 
+    let unsubscribe = ctx.clone().with(warp::wrap_fn(pages::unsubscribe::page));
+    let unsubscribe = warp::path("unsubscribe").and(unsubscribe);
+
+    let verify_email = ctx.clone().with(warp::wrap_fn(pages::verify_email::page));
+    let verify_email = warp::path("verify-email").and(verify_email);
+
+    let data_export = ctx.clone().with(warp::wrap_fn(pages::data_export::page));
+    let data_export = warp::path("data-export").and(data_export);
+
+    let bookmark_export = ctx
+        .clone()
+        .with(warp::wrap_fn(pages::bookmark_export::page));
+    let bookmark_export = warp::path("bookmark-export").and(bookmark_export);
+
+    let preview = ctx.clone().with(warp::wrap_fn(pages::preview::page));
+    let preview = warp::path("previews").and(preview);
+
     let account = ctx.clone().with(warp::wrap_fn(pages::account::page));
     let account = warp::path("account").and(account);
 
     let search = ctx.clone().with(warp::wrap_fn(pages::search::page));
     let search = warp::path("search").and(search);
 
-    let admin = ctx.clone().with(warp::wrap_fn(pages::admin::backup));
+    let admin = ctx.clone().with(warp::wrap_fn(|ctx| {
+        pages::admin::backup(ctx, conf.database_file())
+    }));
     let admin = warp::path!("admin" / "backup").and(admin);
 
     let api = ctx.clone().with(warp::wrap_fn(graphql::api));
@@ -86,10 +131,17 @@ pub fn global_routes(
         .or(mine)
         .or(user)
         .or(feed)
+        .or(sitemap)
         .or(comments)
+        .or(outbound)
         .or(login)
         .or(register)
         .or(logout)
+        .or(unsubscribe)
+        .or(verify_email)
+        .or(data_export)
+        .or(bookmark_export)
+        .or(preview)
         .or(account)
         .or(search)
         .or(admin)
@@ -0,0 +1,166 @@
+//! Batched lookups while resolving a request. GraphQL resolves the
+//! items of a list concurrently, so when every item looks up e.g. its
+//! author, the lookups can be collected into a single query for all of
+//! them, instead of one query per item.
+//!
+//! A [`Loader`] collects the keys of concurrent lookups into a batch,
+//! which the first lookup loads once the others had a chance to join it.
+//! Loaded values, and keys without value, are remembered for the rest of
+//! the request, see [`Context::loaders`](crate::Context::loaders).
+
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+/// How often the first lookup of a batch yields at most, to
+/// let concurrent lookups add their keys to the batch.
+const MAX_BATCH_YIELDS: usize = 10;
+
+struct State<K, V> {
+    loaded: HashMap<K, Option<V>>,
+    pending: HashSet<K>,
+    loading: bool,
+    waiters: Vec<oneshot::Sender<()>>,
+}
+
+/// Remembered values of type `V` by their key `K`,
+/// loaded in batches.
+pub struct Loader<K, V> {
+    state: Mutex<State<K, V>>,
+}
+
+impl<K, V> Default for Loader<K, V> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(State {
+                loaded: HashMap::new(),
+                pending: HashSet::new(),
+                loading: false,
+                waiters: vec![],
+            }),
+        }
+    }
+}
+
+/// Marks the batch of a loader as done when dropped, waking
+/// the lookups which waited for it, even if loading the batch
+/// failed or was cancelled.
+struct Loading<'a, K, V>(&'a Loader<K, V>);
+
+impl<'a, K, V> Drop for Loading<'a, K, V> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.loading = false;
+        for waiter in state.waiters.drain(..) {
+            let _ = waiter.send(());
+        }
+    }
+}
+
+impl<K, V> Loader<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    /// The value for the given key, loaded together with the keys of
+    /// concurrent lookups. Whichever lookup starts a batch loads it
+    /// with `fetch`, which returns the values found for the keys of
+    /// the batch. Keys it returns no value for have none.
+    pub async fn load<F, Fut>(&self, key: K, fetch: F) -> Result<Option<V>>
+    where
+        F: FnOnce(Vec<K>) -> Fut,
+        Fut: Future<Output = Result<Vec<(K, V)>>>,
+    {
+        loop {
+            let done = {
+                let mut state = self.state.lock().unwrap();
+                if let Some(value) = state.loaded.get(&key) {
+                    return Ok(value.clone());
+                }
+                state.pending.insert(key.clone());
+                if !state.loading {
+                    state.loading = true;
+                    break;
+                }
+                let (done, waiting) = oneshot::channel();
+                state.waiters.push(done);
+                waiting
+            };
+            // a failed batch wakes its waiters as well,
+            // which then load their keys themselves
+            let _ = done.await;
+        }
+
+        let loading = Loading(self);
+        let mut batch_size = 0;
+        for _ in 0..MAX_BATCH_YIELDS {
+            let pending = self.state.lock().unwrap().pending.len();
+            if pending == batch_size {
+                break;
+            }
+            batch_size = pending;
+            tokio::task::yield_now().await;
+        }
+        let keys: Vec<K> = self.state.lock().unwrap().pending.drain().collect();
+        let mut found: HashMap<K, V> = fetch(keys.clone()).await?.into_iter().collect();
+        let value = {
+            let mut state = self.state.lock().unwrap();
+            for key in keys {
+                let value = found.remove(&key);
+                state.loaded.insert(key, value);
+            }
+            state.loaded.get(&key).cloned().flatten()
+        };
+        drop(loading);
+        Ok(value)
+    }
+
+    /// Remember the given value for the given key, e.g. after
+    /// changing it, such that later lookups don't load it again.
+    pub fn prime(&self, key: K, value: Option<V>) {
+        self.state.lock().unwrap().loaded.insert(key, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::future::join_all;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_concurrent_loads_are_batched() {
+        let loader: Loader<i32, String> = Loader::default();
+        let batches = AtomicUsize::new(0);
+        let fetch = |keys: Vec<i32>| {
+            batches.fetch_add(1, Ordering::Relaxed);
+            async move {
+                Ok::<_, anyhow::Error>(
+                    keys.into_iter()
+                        .filter(|key| key % 2 == 0)
+                        .map(|key| (key, key.to_string()))
+                        .collect(),
+                )
+            }
+        };
+
+        let values = join_all((0..50).map(|key| loader.load(key, fetch))).await;
+        let values: Vec<Option<String>> = values.into_iter().map(Result::unwrap).collect();
+        assert_eq!(values[0].as_deref(), Some("0"));
+        assert_eq!(values[1], None);
+        assert_eq!(values[48].as_deref(), Some("48"));
+        assert_eq!(batches.load(Ordering::Relaxed), 1);
+
+        // loaded values and missing keys are remembered
+        assert_eq!(loader.load(1, fetch).await.unwrap(), None);
+        assert_eq!(loader.load(2, fetch).await.unwrap().as_deref(), Some("2"));
+        assert_eq!(loader.load(50, fetch).await.unwrap().as_deref(), Some("50"));
+        assert_eq!(batches.load(Ordering::Relaxed), 2);
+
+        loader.prime(1, Some("one".into()));
+        assert_eq!(loader.load(1, fetch).await.unwrap().as_deref(), Some("one"));
+    }
+}
@@ -1,30 +1,38 @@
 pub use server::*;
+use std::sync::Arc;
 
 #[tokio::main]
 async fn main() {
     env_logger::Builder::from_env(env_logger::Env::default().filter_or("LOG", "info")).init();
 
-    let pool = db::connect(Config::env())
+    // fail on startup with all missing or invalid variables
+    let config = Config::init()
+        .map_err(|err| log::error!("Failed to load configuration: {}", err))
+        .unwrap();
+    let config = Arc::new(config.clone());
+
+    let pool = db::connect(&config)
         .await
         .map_err(|err| log::error!("Failed to connect to database: {}", err))
         .unwrap();
-    let mailer = email::connect(Config::env())
+    let mailer = email::connect(&config)
         .await
         .map_err(|err| log::error!("Failed to connect to mailer: {}", err))
         .unwrap();
 
-    setup::run(&pool, &mailer)
+    setup::run(&config, &pool, &mailer)
         .await
         .map_err(|err| log::error!("Failed to run setup: {}", err))
         .unwrap();
 
     let job_schedule_handle = jobs::watch_thread(
         tokio::runtime::Handle::current(),
+        config.clone(),
         pool.clone(),
         mailer.clone(),
     );
 
-    let server = global_routes(Config::env(), pool, mailer);
+    let server = global_routes(&config, pool, mailer);
     warp::serve(server).run(([0, 0, 0, 0], 8080)).await;
     job_schedule_handle.stop();
 }
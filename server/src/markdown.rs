@@ -0,0 +1,402 @@
+//! Rendering of the markdown text of comments and submission descriptions.
+//!
+//! Text is rendered as CommonMark, and sanitized such that the result can
+//! be embedded in pages as is. Raw html in the text is shown literally,
+//! links and images are only kept if they point to relative, `http`,
+//! `https`, or `mailto` URLs, and absolute links are marked with
+//! `rel="nofollow ugc"`. Bare URLs in the text are linked, as are
+//! mentions of users (see [`mentions`](crate::mentions)) and hashtags
+//! of existing tags. Neither are recognized in code, or in the text of
+//! links.
+
+use crate::db::models::tag;
+use crate::mentions;
+use pulldown_cmark::escape::{escape_href, escape_html};
+use pulldown_cmark::{html, CowStr, Event, LinkType, Options, Parser, Tag};
+
+/// URL schemes links and images may use, other than relative links.
+const SAFE_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// Links recognized in the text of a comment or description, besides
+/// those written in markdown.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Links<'a> {
+    /// Mentioned users, as pairs of lowercase usernames and
+    /// the links of their profiles.
+    pub profiles: &'a [(String, String)],
+    /// Names of the existing tags which may be linked as `#tag`.
+    pub tags: &'a [String],
+}
+
+/// Parse the given markdown text.
+pub fn parse(text: &str) -> Parser<'_> {
+    let mut opts = Options::empty();
+    opts.insert(Options::ENABLE_STRIKETHROUGH);
+    opts.insert(Options::ENABLE_TASKLISTS);
+    opts.insert(Options::ENABLE_SMART_PUNCTUATION);
+    Parser::new_ext(text, opts)
+}
+
+/// Render the given markdown text as sanitized html.
+pub fn render(text: &str, links: Links<'_>) -> String {
+    let events = autolink(sanitize(parse(text)));
+    let events = mentions::link(events.into_iter(), links.profiles);
+    let events = link_hashtags(events.into_iter(), links.tags);
+    let mut out = String::new();
+    html::push_html(&mut out, events.into_iter().map(mark_external));
+    out
+}
+
+/// The normalized names of the tags mentioned as `#tag` in the
+/// given markdown text, in the order they are first mentioned.
+pub fn hashtags(text: &str) -> Vec<String> {
+    let events = autolink(sanitize(parse(text)));
+    let mut names: Vec<String> = vec![];
+    mentions::for_each_text(events.into_iter(), |event, recognized| {
+        if let (Event::Text(text), true) = (event, recognized) {
+            for (_, name) in hashtag_spans(&text) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+    });
+    names
+}
+
+/// The number of links to other sites in the given markdown text,
+/// counting both links written in markdown and bare URLs, the way
+/// they are rendered.
+pub fn external_link_count(text: &str) -> usize {
+    autolink(sanitize(parse(text)))
+        .iter()
+        .filter(|event| matches!(event, Event::Start(Tag::Link(_, dest, _)) if is_external(dest)))
+        .count()
+}
+
+/// Whether the given link destination is relative, or uses
+/// one of the [`SAFE_SCHEMES`]. Browsers ignore whitespace and
+/// control characters in schemes, so they are ignored here too.
+fn is_safe(dest: &str) -> bool {
+    let dest: String = dest
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    match dest.find(|c| matches!(c, ':' | '/' | '?' | '#')) {
+        Some(end) if dest[end..].starts_with(':') => SAFE_SCHEMES.contains(&&dest[..end]),
+        _ => true,
+    }
+}
+
+/// Whether the given link destination points to another site.
+fn is_external(dest: &str) -> bool {
+    let dest = dest.trim().to_ascii_lowercase();
+    dest.starts_with("//") || dest.starts_with("http:") || dest.starts_with("https:")
+}
+
+/// Show raw html literally, and drop links and images with
+/// unsafe destinations, keeping their text.
+fn sanitize<'a>(events: impl Iterator<Item = Event<'a>>) -> Vec<Event<'a>> {
+    events
+        .filter_map(|event| match event {
+            Event::Html(html) => Some(Event::Text(html)),
+            Event::Start(Tag::Link(_, ref dest, _))
+            | Event::End(Tag::Link(_, ref dest, _))
+            | Event::Start(Tag::Image(_, ref dest, _))
+            | Event::End(Tag::Image(_, ref dest, _))
+                if !is_safe(dest) =>
+            {
+                None
+            }
+            event => Some(event),
+        })
+        .collect()
+}
+
+/// Byte ranges of the bare `http` and `https` URLs in the given
+/// plain text. Trailing punctuation, and closing parentheses which
+/// are not opened in the URL, are not part of it.
+fn url_spans(text: &str) -> Vec<std::ops::Range<usize>> {
+    let mut spans = vec![];
+    let mut from = 0;
+    while let Some(found) = text[from..].find("http") {
+        let start = from + found;
+        let rest = &text[start..];
+        let follows_word = text[..start]
+            .chars()
+            .next_back()
+            .map_or(false, |c| c.is_alphanumeric());
+        let is_url = rest.starts_with("http://") || rest.starts_with("https://");
+        from = start + "http".len();
+        if follows_word || !is_url {
+            continue;
+        }
+        let mut len = rest
+            .find(|c: char| c.is_whitespace() || c == '<' || c == '>' || c == '"')
+            .unwrap_or(rest.len());
+        loop {
+            let url = &rest[..len];
+            let unbalanced =
+                url.ends_with(')') && url.matches(')').count() > url.matches('(').count();
+            match url.chars().next_back() {
+                Some(c) if ".,:;!?'*_~".contains(c) || unbalanced => len -= c.len_utf8(),
+                _ => break,
+            }
+        }
+        if rest[..len].contains("://") && !rest[..len].ends_with("://") {
+            spans.push(start..start + len);
+        }
+        from = start + len.max("http".len());
+    }
+    spans
+}
+
+/// Link bare URLs outside of code and links.
+fn autolink<'a>(events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+    let mut linked = vec![];
+    mentions::for_each_text(events.into_iter(), |event, recognized| {
+        let text = match (event, recognized) {
+            (Event::Text(text), true) => text,
+            (event, _) => return linked.push(event),
+        };
+        let mut start = 0;
+        for span in url_spans(&text) {
+            if start < span.start {
+                linked.push(Event::Text(text[start..span.start].to_string().into()));
+            }
+            let url: CowStr = text[span.clone()].to_string().into();
+            let link = Tag::Link(LinkType::Autolink, url.clone(), "".into());
+            linked.push(Event::Start(link.clone()));
+            linked.push(Event::Text(url));
+            linked.push(Event::End(link));
+            start = span.end;
+        }
+        match start {
+            0 => linked.push(Event::Text(text)),
+            _ if start < text.len() => linked.push(Event::Text(text[start..].to_string().into())),
+            _ => (),
+        }
+    });
+    linked
+}
+
+/// Hashtags in the given plain text, as byte ranges including the
+/// leading `#`, and the normalized name of the tag. A hashtag may not
+/// directly follow a word character, such that e.g. `C#` is ignored.
+fn hashtag_spans(text: &str) -> Vec<(std::ops::Range<usize>, String)> {
+    let is_tag_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    let mut spans = vec![];
+    let mut prev = None;
+    for (at, c) in text.char_indices() {
+        let follows_word = prev.map_or(false, |p: char| p.is_alphanumeric() || p == '&');
+        prev = Some(c);
+        if c != '#' || follows_word {
+            continue;
+        }
+        let rest = &text[at + 1..];
+        let len = rest.find(|c| !is_tag_char(c)).unwrap_or(rest.len());
+        if let Ok(name) = tag::normalize(&rest[..len]) {
+            spans.push((at..at + 1 + len, name));
+        }
+    }
+    spans
+}
+
+/// Link hashtags of the given existing tags to their feeds.
+fn link_hashtags<'a>(events: impl Iterator<Item = Event<'a>>, tags: &[String]) -> Vec<Event<'a>> {
+    let mut linked = vec![];
+    mentions::for_each_text(events, |event, recognized| {
+        let text = match (event, recognized) {
+            (Event::Text(text), true) if !tags.is_empty() => text,
+            (event, _) => return linked.push(event),
+        };
+        let mut start = 0;
+        for (span, name) in hashtag_spans(&text) {
+            if !tags.contains(&name) {
+                continue;
+            }
+            if start < span.start {
+                linked.push(Event::Text(text[start..span.start].to_string().into()));
+            }
+            let mut href = String::new();
+            escape_href(&mut href, &format!("/t/{}/feed.atom", name)).unwrap();
+            let mut hashtag = String::new();
+            escape_html(&mut hashtag, &text[span.clone()]).unwrap();
+            linked.push(Event::Html(
+                format!("<a href=\"{}\">{}</a>", href, hashtag).into(),
+            ));
+            start = span.end;
+        }
+        match start {
+            0 => linked.push(Event::Text(text)),
+            _ if start < text.len() => linked.push(Event::Text(text[start..].to_string().into())),
+            _ => (),
+        }
+    });
+    linked
+}
+
+/// Mark links to other sites as user generated, and not endorsed.
+fn mark_external(event: Event<'_>) -> Event<'_> {
+    match event {
+        Event::Start(Tag::Link(_, dest, title)) if is_external(&dest) => {
+            let mut html = String::from("<a href=\"");
+            escape_href(&mut html, &dest).unwrap();
+            html.push_str("\" rel=\"nofollow ugc\"");
+            if !title.is_empty() {
+                html.push_str(" title=\"");
+                escape_html(&mut html, &title).unwrap();
+                html.push('"');
+            }
+            html.push('>');
+            Event::Html(html.into())
+        }
+        event => event,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_plain(text: &str) -> String {
+        render(text, Links::default())
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let corpus = [
+            "<script>alert(1)</script>",
+            "<img src=x onerror=alert(1)>",
+            "<a href=\"javascript:alert(1)\">x</a>",
+            "[x](javascript:alert(1))",
+            "[x](JaVaScRiPt:alert(1))",
+            "[x](<java\tscript:alert(1)>)",
+            "[x](javascript&#58;alert(1))",
+            "[x](vbscript:msgbox(1))",
+            "[x](data:text/html;base64,PHNjcmlwdD4=)",
+            "![x](javascript:alert(1))",
+            "<javascript:alert(1)>",
+            "[x]\n\n[x]: javascript:alert(1)",
+            "[x](https://example.com \"\\\"><script>alert(1)</script>\")",
+            "<div onmouseover=\"alert(1)\">\n\nhover\n\n</div>",
+            "`<script>` and <!-- comment -->",
+        ];
+        for text in corpus {
+            let out = render_plain(text).to_ascii_lowercase();
+            assert!(!out.contains("<script"), "{} => {}", text, out);
+            assert!(!out.contains("<img"), "{} => {}", text, out);
+            assert!(!out.contains("<div"), "{} => {}", text, out);
+            assert!(!out.contains("<!--"), "{} => {}", text, out);
+            assert!(!out.contains("href=\"javascript"), "{} => {}", text, out);
+            assert!(!out.contains("href=\"vbscript"), "{} => {}", text, out);
+            assert!(!out.contains("href=\"data"), "{} => {}", text, out);
+        }
+        assert_eq!(
+            render_plain("<b>bold</b>"),
+            "<p>&lt;b&gt;bold&lt;/b&gt;</p>\n"
+        );
+        assert_eq!(render_plain("[x](javascript:alert(1))"), "<p>x</p>\n");
+    }
+
+    #[test]
+    fn test_links() {
+        let cases = [
+            (
+                "[x](https://example.com/a?b=1&c=2)",
+                "<p><a href=\"https://example.com/a?b=1&amp;c=2\" rel=\"nofollow ugc\">x</a></p>\n",
+            ),
+            (
+                "[x](https://example.com \"A title\")",
+                "<p><a href=\"https://example.com\" rel=\"nofollow ugc\" title=\"A title\">x</a></p>\n",
+            ),
+            ("[about](/about)", "<p><a href=\"/about\">about</a></p>\n"),
+            (
+                "<mailto:hi@urls.fyi>",
+                "<p><a href=\"mailto:hi@urls.fyi\">mailto:hi@urls.fyi</a></p>\n",
+            ),
+            ("*emphasis*", "<p><em>emphasis</em></p>\n"),
+            (
+                "```\nfn main() {}\n```",
+                "<pre><code>fn main() {}\n</code></pre>\n",
+            ),
+        ];
+        for (text, html) in cases {
+            assert_eq!(render_plain(text), html, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_external_link_count() {
+        let cases = [
+            ("no links here", 0),
+            ("[about](/about) and <mailto:hi@urls.fyi>", 0),
+            ("see https://example.com and [x](http://example.org)", 2),
+            ("`https://example.com` in code", 0),
+            ("https://a.example https://b.example https://c.example", 3),
+        ];
+        for (text, count) in cases {
+            assert_eq!(external_link_count(text), count, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_autolink() {
+        let link = |url: &str| format!("<a href=\"{0}\" rel=\"nofollow ugc\">{0}</a>", url);
+        let cases = [
+            (
+                "see https://example.com/a_b_c.",
+                format!("<p>see {}.</p>\n", link("https://example.com/a_b_c")),
+            ),
+            (
+                "(http://example.com/wiki/Rust_(language))",
+                format!(
+                    "<p>({})</p>\n",
+                    link("http://example.com/wiki/Rust_(language)")
+                ),
+            ),
+            (
+                "https://a.example, https://b.example",
+                format!(
+                    "<p>{}, {}</p>\n",
+                    link("https://a.example"),
+                    link("https://b.example")
+                ),
+            ),
+            (
+                "`https://example.com` and [x](https://example.com)",
+                format!(
+                    "<p><code>https://example.com</code> and {}</p>\n",
+                    "<a href=\"https://example.com\" rel=\"nofollow ugc\">x</a>"
+                ),
+            ),
+            (
+                "nohttps://example.com https://",
+                "<p>nohttps://example.com https://</p>\n".into(),
+            ),
+        ];
+        for (text, html) in cases {
+            assert_eq!(render_plain(text), html, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_mentions_and_hashtags() {
+        let profiles = vec![("alice".to_string(), "/user/1".to_string())];
+        let tags = vec!["rust-lang".to_string()];
+        let links = Links {
+            profiles: &profiles,
+            tags: &tags,
+        };
+        assert_eq!(
+            render("@alice likes #rust-lang, not #go or C#", links),
+            "<p><a href=\"/user/1\">@alice</a> likes \
+             <a href=\"/t/rust-lang/feed.atom\">#rust-lang</a>, not #go or C#</p>\n"
+        );
+        assert_eq!(
+            hashtags("#Rust_Lang #go `#code` https://example.com/#frag #go"),
+            vec!["rust-lang", "go"]
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Mentions of users as `@username` in the markdown text of comments.
+//!
+//! A mention starts with an `@` which does not directly follow a username
+//! character, a dot, or a slash, such that email addresses and links are
+//! not mistaken for mentions, and ends at the first character which is
+//! not allowed in usernames. Trailing punctuation is therefore not part
+//! of the mention. Mentions in code and in the text of links are ignored.
+
+use pulldown_cmark::{CowStr, Event, Tag};
+use std::ops::Range;
+
+fn is_username_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Byte ranges of the mentions in the given plain text,
+/// excluding the leading `@`.
+fn spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = vec![];
+    let mut prev = None;
+    for (at, c) in text.char_indices() {
+        let follows_word =
+            prev.map_or(false, |p: char| is_username_char(p) || p == '.' || p == '/');
+        prev = Some(c);
+        if c != '@' || follows_word {
+            continue;
+        }
+        let rest = &text[at + 1..];
+        let len = rest.find(|c| !is_username_char(c)).unwrap_or(rest.len());
+        // e.g. `@example.com` or `@user@example.com`
+        let mut after = rest[len..].chars();
+        let looks_like_address = match after.next() {
+            Some('@') => true,
+            Some('.') => after.next().map_or(false, |c| c.is_ascii_alphanumeric()),
+            _ => false,
+        };
+        if len > 0 && !looks_like_address {
+            spans.push(at + 1..at + 1 + len);
+        }
+    }
+    spans
+}
+
+/// Calls `f` with every markdown event, and whether mentions are
+/// recognized in it. Adjacent text events, which the parser emits
+/// e.g. around unmatched emphasis delimiters, are merged first.
+pub(crate) fn for_each_text<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+    mut f: impl FnMut(Event<'a>, bool),
+) {
+    let mut merged: Vec<Event> = vec![];
+    for event in events {
+        if let (Some(Event::Text(text)), Event::Text(next)) = (merged.last_mut(), &event) {
+            *text = format!("{}{}", text, next).into();
+            continue;
+        }
+        merged.push(event);
+    }
+
+    let mut ignored = 0;
+    for event in merged {
+        let recognized = ignored == 0 && matches!(event, Event::Text(_));
+        match &event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Start(Tag::Link(..)) => ignored += 1,
+            Event::End(Tag::CodeBlock(_)) | Event::End(Tag::Link(..)) => ignored -= 1,
+            _ => (),
+        }
+        f(event, recognized);
+    }
+}
+
+/// The usernames mentioned in the given markdown events, in
+/// lowercase and in the order they are first mentioned.
+pub fn parse<'a>(events: impl Iterator<Item = Event<'a>>) -> Vec<String> {
+    let mut usernames: Vec<String> = vec![];
+    for_each_text(events, |event, recognized| {
+        if let (Event::Text(text), true) = (event, recognized) {
+            for span in spans(&text) {
+                let username = text[span].to_ascii_lowercase();
+                if !usernames.contains(&username) {
+                    usernames.push(username);
+                }
+            }
+        }
+    });
+    usernames
+}
+
+/// Replace mentions in the markdown events with links, where
+/// `profiles` maps lowercase usernames to the link of their
+/// profile. Mentions of other usernames are kept as plain text.
+pub fn link<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+    profiles: &[(String, String)],
+) -> Vec<Event<'a>> {
+    let mut linked = vec![];
+    for_each_text(events, |event, recognized| {
+        let text = match (event, recognized) {
+            (Event::Text(text), true) => text,
+            (event, _) => return linked.push(event),
+        };
+        let mut start = 0;
+        for span in spans(&text) {
+            let username = text[span.clone()].to_ascii_lowercase();
+            let href = match profiles.iter().find(|(name, _)| name == &username) {
+                Some((_, href)) => href,
+                None => continue,
+            };
+            // the `@` directly precedes the username
+            let mention = span.start - 1;
+            if start < mention {
+                linked.push(Event::Text(text[start..mention].to_string().into()));
+            }
+            linked.push(Event::Html(CowStr::from(format!(
+                "<a href=\"{}\">@{}</a>",
+                href,
+                &text[span.clone()]
+            ))));
+            start = span.end;
+        }
+        match start {
+            0 => linked.push(Event::Text(text)),
+            _ if start < text.len() => linked.push(Event::Text(text[start..].to_string().into())),
+            _ => (),
+        }
+    });
+    linked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulldown_cmark::{html, Options, Parser};
+
+    fn mentions(text: &str) -> Vec<String> {
+        parse(Parser::new_ext(text, Options::all()))
+    }
+
+    #[test]
+    fn test_parse() {
+        let cases: [(&str, &[&str]); 17] = [
+            ("@alice", &["alice"]),
+            ("hi @Alice and @bob_2!", &["alice", "bob_2"]),
+            (
+                "(@alice), @bob. @carol: @dave?",
+                &["alice", "bob", "carol", "dave"],
+            ),
+            ("@alice's link", &["alice"]),
+            ("thanks @test-user.", &["test-user"]),
+            ("@alice @ALICE @alice", &["alice"]),
+            ("_hi_ @bob_2_ and @alice", &["bob_2_", "alice"]),
+            ("*@alice*, **@bob**", &["alice", "bob"]),
+            // email addresses and similar
+            ("mail alice@example.com", &[]),
+            ("mail @example.com", &[]),
+            ("follow @alice@example.social", &[]),
+            ("a.@alice", &[]),
+            ("@ alice @", &[]),
+            // code and links
+            ("`@alice` and @bob", &["bob"]),
+            ("```\n@alice\n```\n\n@bob", &["bob"]),
+            (
+                "[@alice](https://example.com/@alice) <https://example.com/@bob>",
+                &[],
+            ),
+            ("https://example.com/@alice", &[]),
+        ];
+        for (text, usernames) in cases {
+            assert_eq!(mentions(text), usernames, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_link() {
+        let profiles = vec![("alice".to_string(), "/user/1".to_string())];
+        let events = link(Parser::new("Hi @Alice, @bob and `@alice`!"), &profiles);
+        let mut out = String::new();
+        html::push_html(&mut out, events.into_iter());
+        assert_eq!(
+            out,
+            "<p>Hi <a href=\"/user/1\">@Alice</a>, @bob and <code>@alice</code>!</p>\n"
+        );
+    }
+}
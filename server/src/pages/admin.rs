@@ -1,12 +1,13 @@
 use crate::pages::ContextFilter;
-use crate::{Config, Context};
+use crate::Context;
+use std::path::Path;
 use warp::{filters::BoxedFilter, Filter, Rejection, Reply};
 
 async fn check_backup_permissions(ctx: Context) -> Result<(), Rejection> {
-    let log_err = |err| {
+    fn log_err(err: impl std::fmt::Display) -> Rejection {
         log::warn!("Unauthorized attempt to download database backup: {}", err);
         warp::reject::not_found()
-    };
+    }
     ctx.user()
         .await
         .map_err(log_err)?
@@ -16,12 +17,14 @@ async fn check_backup_permissions(ctx: Context) -> Result<(), Rejection> {
     Ok(())
 }
 
-pub fn backup(ctx: impl ContextFilter + 'static) -> BoxedFilter<(impl Reply,)> {
+/// Download the given database file, which is
+/// only available to users with backup access.
+pub fn backup(ctx: impl ContextFilter + 'static, database: &Path) -> BoxedFilter<(impl Reply,)> {
     warp::path::end()
         .and(ctx)
         .and_then(check_backup_permissions)
         .untuple_one()
-        .and(warp::fs::file(Config::env().database_file()))
+        .and(warp::fs::file(database.to_path_buf()))
         .map(|file| {
             warp::reply::with_header(
                 file,
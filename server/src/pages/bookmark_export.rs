@@ -0,0 +1,33 @@
+use crate::db::models::BookmarkExport;
+use crate::pages::{error, ContextFilter};
+use crate::Context;
+use warp::{filters::BoxedFilter, reply::Response, Filter, Reply};
+
+async fn handle(ctx: &Context, token: &str) -> Result<Response, error::ServerError> {
+    let export = BookmarkExport::find_by_download_token(ctx, token)
+        .await
+        .map_err(error::request)?;
+    let file = export.read_file(ctx).await?;
+    let format = export.format();
+    let reply = warp::reply::with_header(file, "Content-Type", format.content_type());
+    let reply = warp::reply::with_header(
+        reply,
+        "Content-Disposition",
+        format!(
+            "attachment; filename=\"urls-bookmarks-{}.{}\"",
+            export.created_at().format("%Y-%m-%d"),
+            format.extension()
+        ),
+    );
+    Ok(reply.into_response())
+}
+
+pub fn page(ctx: impl ContextFilter + 'static) -> BoxedFilter<(Response,)> {
+    warp::path::param()
+        .and(warp::path::end())
+        .and(ctx)
+        .and_then(|token: String, ctx: Context| async move {
+            error::reply(&ctx, handle(&ctx, &token).await)
+        })
+        .boxed()
+}
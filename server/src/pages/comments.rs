@@ -1,7 +1,7 @@
 use crate::db::id::UrlID;
-use crate::db::models::{Comment, Url, User};
+use crate::db::models::{Comment, Url, UrlEmbed, UrlView, User, VoteDirection};
 use crate::pages::{error, ContextFilter};
-use crate::Context;
+use crate::{preview, Context};
 use askama::Template;
 use warp::{filters::BoxedFilter, reply::Response, Filter, Reply};
 
@@ -9,6 +9,8 @@ use warp::{filters::BoxedFilter, reply::Response, Filter, Reply};
 #[template(path = "pages/comments.html")]
 struct Page<'a> {
     url_partial: UrlPartial,
+    preview_image: Option<String>,
+    embed: Option<UrlEmbed>,
     comment_list: &'a [CommentPartial],
     xsrf_token: &'a str,
     is_logged_in: bool,
@@ -18,7 +20,7 @@ struct Page<'a> {
 #[template(path = "partials/url.html")]
 struct UrlPartial {
     url: Url,
-    created_by: User,
+    created_by: Option<User>,
     upvote_count: i64,
     is_upvoted_by_viewer: bool,
     comment_count: i64,
@@ -44,6 +46,8 @@ impl std::str::FromStr for IgnoreSlug {
 
 async fn handle(ctx: &Context, url_id: UrlID) -> Result<Response, error::ServerError> {
     let url = Url::find(ctx, url_id).await.map_err(error::not_found)?;
+    url.check_visible(ctx).await.map_err(error::not_found)?;
+    UrlView::record(ctx, url.id());
 
     let comments = url.comments(ctx, 1024 /* some sane limit ... */).await?;
     let mut comment_list = vec![];
@@ -54,11 +58,18 @@ async fn handle(ctx: &Context, url_id: UrlID) -> Result<Response, error::ServerE
         });
     }
 
+    let preview_image = if url.is_deleted() || url.is_removed() {
+        None
+    } else {
+        url.preview_image().map(|key| preview::path(ctx, key))
+    };
     let page = Page {
+        preview_image,
+        embed: url.embed(ctx).await?,
         url_partial: UrlPartial {
-            created_by: url.created_by(ctx).await?,
-            upvote_count: url.upvote_count(ctx).await?,
-            is_upvoted_by_viewer: url.upvoted_by_viewer(ctx).await?,
+            created_by: url.author(ctx).await?,
+            upvote_count: url.upvotes(),
+            is_upvoted_by_viewer: url.viewer_vote(ctx).await? == Some(VoteDirection::Up),
             comment_count: url.comment_count(ctx).await?,
             is_logged_in: ctx.is_logged_in(),
             url,
@@ -0,0 +1,31 @@
+use crate::db::models::DataExport;
+use crate::pages::{error, ContextFilter};
+use crate::Context;
+use warp::{filters::BoxedFilter, reply::Response, Filter, Reply};
+
+async fn handle(ctx: &Context, token: &str) -> Result<Response, error::ServerError> {
+    let export = DataExport::find_by_download_token(ctx, token)
+        .await
+        .map_err(error::request)?;
+    let archive = export.read_archive(ctx).await?;
+    let reply = warp::reply::with_header(archive, "Content-Type", "application/zip");
+    let reply = warp::reply::with_header(
+        reply,
+        "Content-Disposition",
+        format!(
+            "attachment; filename=\"urls-export-{}.zip\"",
+            export.created_at().format("%Y-%m-%d")
+        ),
+    );
+    Ok(reply.into_response())
+}
+
+pub fn page(ctx: impl ContextFilter + 'static) -> BoxedFilter<(Response,)> {
+    warp::path::param()
+        .and(warp::path::end())
+        .and(ctx)
+        .and_then(|token: String, ctx: Context| async move {
+            error::reply(&ctx, handle(&ctx, &token).await)
+        })
+        .boxed()
+}
@@ -9,6 +9,7 @@ pub enum ServerError {
     Internal,
     NotFound,
     Request,
+    Forbidden,
 }
 
 #[derive(Template)]
@@ -41,6 +42,13 @@ pub fn request(error: impl Display) -> ServerError {
     ServerError::Request
 }
 
+/// Map a general error to a forbidden error. This bails out
+/// and renders a generic 403 forbidden error page.
+pub fn forbidden(error: impl Display) -> ServerError {
+    log::info!("Coercing to forbidden error: {}", error);
+    ServerError::Forbidden
+}
+
 /// Turns a result into a reply. This is supposed to be used when
 /// returning from a filter handler.
 ///
@@ -61,6 +69,7 @@ where
                 ServerError::Internal => http::StatusCode::INTERNAL_SERVER_ERROR,
                 ServerError::Request => http::StatusCode::BAD_REQUEST,
                 ServerError::NotFound => http::StatusCode::NOT_FOUND,
+                ServerError::Forbidden => http::StatusCode::FORBIDDEN,
             };
             let page = ErrorPage { status };
             Ok(warp::reply::with_status(page, status).into_response())
@@ -1,32 +1,429 @@
-use crate::db::models::{Url, UrlOrdering};
+use crate::db::models::{
+    SavedStatus, SavedUrl, Tag, Url, UrlFilter, UrlSort, User, UserPreferences,
+};
 use crate::pages::{error, ContextFilter};
 use crate::Context;
 use askama::Template;
 use chrono::{DateTime, Utc};
-use warp::{filters::BoxedFilter, reply::Response, Filter};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use warp::http::{header, StatusCode};
+use warp::{filters::BoxedFilter, reply::Response, Filter, Reply};
 
-const FEED_SIZE: i64 = 32;
+/// Number of submissions listed in a feed.
+const FEED_SIZE: i64 = 30;
+
+/// A submission listed in a feed.
+struct Entry {
+    url: Url,
+    author: String,
+    updated: DateTime<Utc>,
+    discussion: String,
+    tags: Vec<String>,
+}
+
+impl Entry {
+    /// The stable identifier of the entry, which is the canonical
+    /// URL of links, and the discussion of text posts.
+    fn id(&self) -> &str {
+        self.url.canonical_url().unwrap_or(&self.discussion)
+    }
+
+    /// The submitted link, or the discussion of text posts.
+    fn link(&self) -> &str {
+        self.url.url_str().unwrap_or(&self.discussion)
+    }
+
+    /// The title of the submission, falling back to its link.
+    fn title(&self) -> &str {
+        self.url.title().unwrap_or_else(|| self.link())
+    }
+}
 
 #[derive(Template)]
 #[template(path = "pages/feed.xml")]
-struct Page {
-    pub_date: DateTime<Utc>,
-    urls: Vec<Url>,
+struct RssPage<'a> {
+    title: &'a str,
+    link: &'a str,
+    self_link: &'a str,
+    updated: DateTime<Utc>,
+    entries: &'a [Entry],
+}
+
+#[derive(Template)]
+#[template(path = "pages/feed_atom.xml")]
+struct AtomPage<'a> {
+    title: &'a str,
+    link: &'a str,
+    self_link: &'a str,
+    updated: DateTime<Utc>,
+    entries: &'a [Entry],
+}
+
+/// A feed in the JSON Feed format, see
+/// <https://www.jsonfeed.org/version/1.1/>.
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: &'a str,
+    feed_url: &'a str,
+    items: Vec<JsonItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonItem<'a> {
+    id: &'a str,
+    /// The discussion of the submission.
+    url: &'a str,
+    /// The submitted link. Text posts have none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_url: Option<&'a str>,
+    title: &'a str,
+    /// Items must have content, which is why submissions
+    /// without a description fall back to their title.
+    content_text: &'a str,
+    date_published: String,
+    date_modified: String,
+    authors: Vec<JsonAuthor<'a>>,
+    tags: &'a [String],
+}
+
+#[derive(Serialize)]
+struct JsonAuthor<'a> {
+    name: &'a str,
+}
+
+impl<'a> JsonFeed<'a> {
+    fn new(title: &'a str, link: &'a str, self_link: &'a str, entries: &'a [Entry]) -> Self {
+        let items = entries
+            .iter()
+            .map(|entry| {
+                let title = entry.title();
+                JsonItem {
+                    id: entry.id(),
+                    url: &entry.discussion,
+                    external_url: entry.url.url_str(),
+                    title,
+                    content_text: entry.url.description().unwrap_or(title),
+                    date_published: entry.url.created_at().to_rfc3339(),
+                    date_modified: entry.updated.to_rfc3339(),
+                    authors: vec![JsonAuthor {
+                        name: &entry.author,
+                    }],
+                    tags: &entry.tags,
+                }
+            })
+            .collect();
+        Self {
+            version: "https://jsonfeed.org/version/1.1",
+            title,
+            home_page_url: link,
+            feed_url: self_link,
+            items,
+        }
+    }
+}
+
+/// The submissions a feed lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Listing {
+    /// The newest submissions, optionally only
+    /// those with the given tag.
+    Newest(Option<String>),
+    /// The front page as seen by the user with
+    /// the given username.
+    FrontPage(String),
+    /// The submissions saved by the user with
+    /// the given username.
+    Saved(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Rss,
+    Atom,
+    Json,
+}
+
+impl Format {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Format::Rss => "application/rss+xml; charset=utf-8",
+            Format::Atom => "application/atom+xml; charset=utf-8",
+            Format::Json => "application/feed+json; charset=utf-8",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Rss => "rss",
+            Format::Atom => "atom",
+            Format::Json => "json",
+        }
+    }
+}
+
+/// Query parameters of the feed. Private feeds carry
+/// a token which authenticates the user they belong to.
+#[derive(Debug, Deserialize)]
+struct FeedQuery {
+    token: Option<String>,
+}
+
+/// Headers sent by feed readers which fetched the feed
+/// before, to only receive it again if it changed.
+#[derive(Debug)]
+struct Conditions {
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+impl Conditions {
+    /// Determine if the client already has the version of the feed
+    /// with the given tag and modification time. As for any
+    /// conditional request, the entity tag takes precedence.
+    fn is_fresh(&self, etag: &str, last_modified: Option<DateTime<Utc>>) -> bool {
+        if let Some(if_none_match) = &self.if_none_match {
+            return if_none_match
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag || tag == "*");
+        }
+        match (&self.if_modified_since, last_modified) {
+            (Some(since), Some(last_modified)) => DateTime::parse_from_rfc2822(since)
+                .map_or(false, |since| {
+                    last_modified.timestamp() <= since.timestamp()
+                }),
+            _ => false,
+        }
+    }
 }
 
-async fn handle(ctx: &Context) -> Result<Page, error::ServerError> {
-    let (urls, _) = Url::paginate(ctx, UrlOrdering::Recent, 0, FEED_SIZE).await?;
-    let pub_date = urls
-        .get(0)
-        .map(|url| url.created_at())
-        .unwrap_or_else(|| ctx.now());
-    Ok(Page { pub_date, urls })
+/// Format a time as an HTTP date.
+fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
 }
 
+/// Derive an entity tag from the listed submissions, such that it
+/// changes whenever a submission is added, removed, or edited.
+fn etag(format: Format, listing: &Listing, entries: &[Entry]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{:?}:{:?}", format, listing));
+    for entry in entries {
+        hasher.update(format!(
+            ":{}:{}",
+            entry.url.id(),
+            entry.updated.timestamp_nanos()
+        ));
+    }
+    let digest: String = hasher.finalize()[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("\"{}\"", digest)
+}
+
+/// Load the submissions listed in a feed, and when they were last
+/// updated. Feeds of a user require their feed token, and only list
+/// what the user would see on the site, which is why they are loaded
+/// with a context acting on behalf of the user.
+async fn load(
+    ctx: &Context,
+    listing: &Listing,
+    token: Option<&str>,
+) -> Result<Vec<(Url, DateTime<Utc>)>, error::ServerError> {
+    let with_updated = |url: Url| {
+        let updated = url.edited_at().unwrap_or_else(|| url.created_at());
+        (url, updated)
+    };
+    match listing {
+        Listing::Newest(tag) => {
+            if let Some(token) = token {
+                User::find_by_feed_token(ctx, token)
+                    .await
+                    .map_err(error::forbidden)?;
+            }
+            // the same submissions as the newest submissions
+            // listed by the GraphQL API
+            let filter = UrlFilter {
+                tag: tag.as_deref(),
+                ..Default::default()
+            };
+            let urls =
+                Url::all_submissions(ctx, filter, UrlSort::Newest, None, None, Some(FEED_SIZE))
+                    .await?;
+            Ok(urls.into_iter().map(with_updated).collect())
+        }
+        Listing::FrontPage(username) | Listing::Saved(username) => {
+            let user =
+                User::find_by_username_and_feed_token(ctx, username, token.unwrap_or_default())
+                    .await
+                    .map_err(error::forbidden)?;
+            let ctx = ctx.acting_as(user.id());
+            if let Listing::Saved(_) = listing {
+                let saves = SavedUrl::reading_list(
+                    &ctx,
+                    user.id(),
+                    SavedStatus::All,
+                    None,
+                    None,
+                    Some(FEED_SIZE),
+                )
+                .await?;
+                return Ok(saves
+                    .into_iter()
+                    .map(|(saved, url)| (url, saved.saved_at()))
+                    .collect());
+            }
+            let preferences = UserPreferences::find(&ctx, user.id()).await?;
+            let (urls, _) =
+                Url::paginate(&ctx, preferences.default_sort().into(), 0, FEED_SIZE).await?;
+            Ok(urls.into_iter().map(with_updated).collect())
+        }
+    }
+}
+
+async fn handle(
+    ctx: &Context,
+    format: Format,
+    listing: Listing,
+    query: FeedQuery,
+    conditions: Conditions,
+) -> Result<Response, error::ServerError> {
+    let urls = load(ctx, &listing, query.token.as_deref()).await?;
+    let hostname = ctx.config().hostname();
+    let mut entries = vec![];
+    for (url, updated) in urls {
+        entries.push(Entry {
+            author: match url.author(ctx).await? {
+                Some(user) => user.name().to_string(),
+                None => "anonymous".to_string(),
+            },
+            updated,
+            discussion: format!("https://{}/comments/{}", hostname, url.id()),
+            tags: Tag::for_url(ctx, url.id())
+                .await?
+                .iter()
+                .map(|tag| tag.name().to_string())
+                .collect(),
+            url,
+        });
+    }
+
+    let last_modified = entries.iter().map(|entry| entry.updated).max();
+    let etag = etag(format, &listing, &entries);
+    if conditions.is_fresh(&etag, last_modified) {
+        let mut reply =
+            warp::reply::with_header(StatusCode::NOT_MODIFIED, header::ETAG, etag).into_response();
+        if let Some(last_modified) = last_modified {
+            let value = http_date(last_modified).parse()?;
+            reply.headers_mut().insert(header::LAST_MODIFIED, value);
+        }
+        return Ok(reply);
+    }
+
+    // the token is left out of the self link, such that it
+    // isn't handed on along with the content of the feed
+    let extension = format.extension();
+    let (title, feed_path) = match &listing {
+        Listing::Newest(Some(tag)) => (
+            format!("urls.fyi: {}", tag),
+            format!("/t/{}/feed.{}", tag, extension),
+        ),
+        Listing::Newest(None) => ("urls.fyi".to_string(), format!("/feed.{}", extension)),
+        Listing::FrontPage(username) => (
+            format!("urls.fyi for {}", username),
+            format!("/u/{}/feed.{}", username, extension),
+        ),
+        Listing::Saved(username) => (
+            format!("urls.fyi: saved by {}", username),
+            format!("/u/{}/saved.{}", username, extension),
+        ),
+    };
+    let link = format!("https://{}/", hostname);
+    let self_link = format!("https://{}{}", hostname, feed_path);
+    let updated = last_modified.unwrap_or_else(|| ctx.now());
+    let body = match format {
+        Format::Rss => RssPage {
+            title: &title,
+            link: &link,
+            self_link: &self_link,
+            updated,
+            entries: &entries,
+        }
+        .render()?,
+        Format::Atom => AtomPage {
+            title: &title,
+            link: &link,
+            self_link: &self_link,
+            updated,
+            entries: &entries,
+        }
+        .render()?,
+        Format::Json => serde_json::to_string(&JsonFeed::new(&title, &link, &self_link, &entries))?,
+    };
+
+    let mut reply =
+        warp::reply::with_header(body, header::CONTENT_TYPE, format.content_type()).into_response();
+    reply.headers_mut().insert(header::ETAG, etag.parse()?);
+    if let Some(last_modified) = last_modified {
+        let value = http_date(last_modified).parse()?;
+        reply.headers_mut().insert(header::LAST_MODIFIED, value);
+    }
+    Ok(reply)
+}
+
+/// Feeds of the newest submissions, at `/feed.rss` (or the older
+/// `/feed.xml`) and `/feed.atom`, and of the newest submissions
+/// with a tag, at `/t/<tag>/feed.atom`. Both are also available as
+/// JSON Feed, at `/feed.json` and `/t/<tag>/feed.json`. The private
+/// feeds of a user, at `/u/<username>/feed.atom` for their front page
+/// and `/u/<username>/saved.atom` for their saves, require the feed
+/// token of the user.
 pub fn page(ctx: impl ContextFilter + 'static) -> BoxedFilter<(Response,)> {
-    warp::path("feed.xml")
+    let front = warp::path("feed.atom")
+        .map(|| Format::Atom)
+        .or(warp::path("feed.rss").map(|| Format::Rss))
+        .unify()
+        .or(warp::path("feed.xml").map(|| Format::Rss))
+        .unify()
+        .or(warp::path("feed.json").map(|| Format::Json))
+        .unify()
         .and(warp::path::end())
+        .map(|format| (format, Listing::Newest(None)));
+    let tagged = warp::path!("t" / String / "feed.atom")
+        .map(|tag| (Format::Atom, Listing::Newest(Some(tag))))
+        .or(warp::path!("t" / String / "feed.json")
+            .map(|tag| (Format::Json, Listing::Newest(Some(tag)))))
+        .unify();
+    let private = warp::path!("u" / String / "feed.atom")
+        .map(|username| (Format::Atom, Listing::FrontPage(username)))
+        .or(warp::path!("u" / String / "saved.atom")
+            .map(|username| (Format::Atom, Listing::Saved(username))))
+        .unify();
+    let conditions = warp::header::optional::<String>("if-none-match")
+        .and(warp::header::optional::<String>("if-modified-since"))
+        .map(|if_none_match, if_modified_since| Conditions {
+            if_none_match,
+            if_modified_since,
+        });
+
+    front
+        .or(tagged)
+        .unify()
+        .or(private)
+        .unify()
+        .and(warp::query::<FeedQuery>())
+        .and(conditions)
         .and(ctx)
-        .and_then(|ctx: Context| async move { error::reply(&ctx, handle(&ctx).await) })
+        .and_then(
+            |(format, listing): (Format, Listing),
+             query: FeedQuery,
+             conditions: Conditions,
+             ctx: Context| async move {
+                error::reply(&ctx, handle(&ctx, format, listing, query, conditions).await)
+            },
+        )
         .boxed()
 }
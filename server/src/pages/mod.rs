@@ -1,20 +1,29 @@
-use crate::{db::models::Login, db::Pool, email::Mailer, Context};
+use crate::context::SessionSource;
+use crate::{db::models::Login, db::Pool, email::Mailer, Config, Context};
 use std::convert::Infallible;
 use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use warp::Filter;
 
 pub mod account;
 pub mod admin;
+pub mod bookmark_export;
 pub mod comments;
+pub mod data_export;
 pub mod error;
 pub mod feed;
 pub mod graphiql;
 pub mod login;
 pub mod logout;
+pub mod outbound;
+pub mod preview;
 pub mod register;
 pub mod search;
 pub mod session;
+pub mod sitemap;
+pub mod unsubscribe;
 pub mod url_lists;
+pub mod verify_email;
 pub mod xsrf;
 
 const XSRF_COOKIE_NAME: &str = "xsrf";
@@ -23,13 +32,16 @@ const AUTH_COOKIE_NAME: &str = "session";
 /// Captures a context from the given request. This never fails, and
 /// thus should be used at the end of a filter chain to extract the context
 /// only if the request will be processed by that filter.
-pub fn context(pool: Pool, mailer: Mailer) -> impl ContextFilter {
+pub fn context(config: Arc<Config>, pool: Pool, mailer: Mailer) -> impl ContextFilter {
     async fn attempt_login(
         mut ctx: Context,
-        session: Option<String>,
+        session: Option<(String, SessionSource)>,
     ) -> Result<Context, Infallible> {
-        if let Some(session_token) = session {
-            Login::use_session(&mut ctx, &session_token).await.ok();
+        if let Some((session_token, source)) = session {
+            match Login::use_session(&mut ctx, &session_token).await {
+                Ok(()) => ctx.set_session_source(source),
+                Err(err) => log::info!("Ignoring invalid session token: {}", err),
+            }
         }
         Ok(ctx)
     }
@@ -53,7 +65,8 @@ pub fn context(pool: Pool, mailer: Mailer) -> impl ContextFilter {
         .and(session::token())
         .and(xsrf::token())
         .and_then(move |user_agent, remote_address, session, xsrf| {
-            let ctx = Context::for_request(&pool, &mailer, xsrf, user_agent, remote_address);
+            let ctx =
+                Context::for_request(&config, &pool, &mailer, xsrf, user_agent, remote_address);
             attempt_login(ctx, session)
         })
 }
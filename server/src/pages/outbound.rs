@@ -0,0 +1,47 @@
+use crate::clicks;
+use crate::db::id::UrlID;
+use crate::db::models::{Listed, Url};
+use crate::pages::{error, ContextFilter};
+use crate::Context;
+use warp::http::StatusCode;
+use warp::{filters::BoxedFilter, reply::Response, Filter, Reply};
+
+async fn handle(
+    ctx: &Context,
+    id: UrlID,
+    purpose: Option<String>,
+) -> Result<Response, error::ServerError> {
+    let url = Url::find(ctx, id).await.map_err(error::not_found)?;
+    let reachable = Listed::reachable(ctx).await?;
+    if !reachable.includes(ctx, url.id()).await? {
+        return Err(error::ServerError::NotFound);
+    }
+    let link = url.outbound_link(ctx).map_err(error::not_found)?;
+
+    // speculative loads are not clicks
+    let prefetch = purpose.map_or(false, |purpose| purpose.contains("prefetch"));
+    if !prefetch && !clicks::is_bot(ctx.user_agent()) {
+        ctx.click_counter().record(url.id());
+    }
+
+    let reply = warp::reply::with_header(warp::reply(), "Location", link.as_str());
+    let reply = warp::reply::with_header(reply, "Referrer-Policy", "no-referrer");
+    // every click should reach the server to be counted
+    let reply = warp::reply::with_header(reply, "Cache-Control", "no-store");
+    Ok(warp::reply::with_status(reply, StatusCode::FOUND).into_response())
+}
+
+pub fn page(ctx: impl ContextFilter + 'static) -> BoxedFilter<(Response,)> {
+    let purpose = warp::header::optional::<String>("sec-purpose")
+        .and(warp::header::optional::<String>("purpose"))
+        .map(|sec_purpose: Option<String>, purpose: Option<String>| sec_purpose.or(purpose));
+    warp::path::param()
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(purpose)
+        .and(ctx)
+        .and_then(|id: UrlID, purpose, ctx: Context| async move {
+            error::reply(&ctx, handle(&ctx, id, purpose).await)
+        })
+        .boxed()
+}
@@ -0,0 +1,25 @@
+use crate::pages::{error, ContextFilter};
+use crate::{preview, Context};
+use warp::{filters::BoxedFilter, reply::Response, Filter, Reply};
+
+async fn handle(ctx: &Context, token: &str) -> Result<Response, error::ServerError> {
+    let image = preview::load(ctx, token).await.map_err(error::not_found)?;
+    let reply = warp::reply::with_header(image, "Content-Type", "image/jpeg");
+    // paths change whenever the image does
+    let reply = warp::reply::with_header(
+        reply,
+        "Cache-Control",
+        "public, max-age=31536000, immutable",
+    );
+    Ok(reply.into_response())
+}
+
+pub fn page(ctx: impl ContextFilter + 'static) -> BoxedFilter<(Response,)> {
+    warp::path::param()
+        .and(warp::path::end())
+        .and(ctx)
+        .and_then(|token: String, ctx: Context| async move {
+            error::reply(&ctx, handle(&ctx, &token).await)
+        })
+        .boxed()
+}
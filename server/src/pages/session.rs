@@ -1,24 +1,48 @@
+use crate::context::SessionSource;
 use crate::Context;
 use std::convert::Infallible;
 use warp::{http::HeaderValue, Filter, Reply};
 
 /// A filter which extracts the login / auth session token
-/// from the request.
-pub fn token() -> impl Filter<Extract = (Option<String>,), Error = Infallible> + Clone {
-    warp::cookie::optional(super::AUTH_COOKIE_NAME)
+/// from the request. A token in an `Authorization: Bearer`
+/// header takes precedence over the session cookie.
+pub fn token(
+) -> impl Filter<Extract = (Option<(String, SessionSource)>,), Error = Infallible> + Clone {
+    warp::header::optional::<String>("authorization")
+        .or(warp::any().map(|| None))
+        .unify()
+        .and(warp::cookie::optional(super::AUTH_COOKIE_NAME))
+        .map(|header: Option<String>, cookie: Option<String>| {
+            let header = header.and_then(|value| {
+                value
+                    .strip_prefix("Bearer ")
+                    .map(|token| token.trim().to_string())
+            });
+            match (header, cookie) {
+                (Some(token), _) => Some((token, SessionSource::Header)),
+                (None, Some(token)) => Some((token, SessionSource::Cookie)),
+                (None, None) => None,
+            }
+        })
+}
+
+/// The `Set-Cookie` header value which stores the given
+/// session token in the auth session cookie.
+pub fn header_value(session_token: &str) -> Option<HeaderValue> {
+    let value = format!(
+        "{}={}; Path=/; HttpOnly; Secure; SameSite=Lax; Max-Age=7776000",
+        super::AUTH_COOKIE_NAME,
+        session_token,
+    );
+    HeaderValue::from_str(&value).ok()
 }
 
 /// This can be used to set the auth session cookie with the correct extracted
 /// cookie value.
 pub fn cookie(ctx: &Context, reply: impl Reply) -> impl Reply {
     let mut resp = reply.into_response();
-    if let Some(session_token) = ctx.session_token() {
-        let value = format!(
-            "{}={}; Path=/; HttpOnly; SameSite=Strict; max-age=7776000",
-            super::AUTH_COOKIE_NAME,
-            session_token,
-        );
-        if let Ok(header) = HeaderValue::from_str(&value) {
+    if ctx.session_source() == Some(SessionSource::Cookie) {
+        if let Some(header) = ctx.session_token().and_then(header_value) {
             resp.headers_mut().append("Set-Cookie", header);
         }
     }
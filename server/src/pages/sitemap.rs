@@ -0,0 +1,137 @@
+use crate::db::models::Url;
+use crate::pages::{error, ContextFilter};
+use crate::Context;
+use askama::Template;
+use chrono::SecondsFormat;
+use warp::http::header;
+use warp::{filters::BoxedFilter, reply::Response, Filter, Reply};
+
+/// Crawlers may use a sitemap for this long before
+/// fetching it again.
+const CACHE_CONTROL: &str = "public, max-age=3600";
+
+/// A discussion page listed in a sitemap.
+struct Entry {
+    loc: String,
+    lastmod: String,
+}
+
+#[derive(Template)]
+#[template(path = "pages/sitemap.xml")]
+struct SitemapPage<'a> {
+    entries: &'a [Entry],
+}
+
+#[derive(Template)]
+#[template(path = "pages/sitemap_index.xml")]
+struct SitemapIndexPage<'a> {
+    sitemaps: &'a [String],
+}
+
+/// The number of sitemaps needed to list the given number of
+/// submissions, with at most `size` submissions per sitemap.
+/// There is always at least one, possibly empty, sitemap.
+fn page_count(count: i64, size: i64) -> i64 {
+    ((count + size - 1) / size).max(1)
+}
+
+/// Parse the name of a numbered sitemap, e.g. `2.xml`.
+fn parse_page(name: &str) -> Option<i64> {
+    name.strip_suffix(".xml")?
+        .parse()
+        .ok()
+        .filter(|page| *page >= 1)
+}
+
+/// Reply with the given sitemap, which may be cached.
+fn xml_reply(body: String) -> Response {
+    let reply =
+        warp::reply::with_header(body, header::CONTENT_TYPE, "application/xml; charset=utf-8");
+    warp::reply::with_header(reply, header::CACHE_CONTROL, CACHE_CONTROL).into_response()
+}
+
+/// Render the discussion pages of the submissions on the
+/// given page, counting from one.
+async fn sitemap(ctx: &Context, page: i64) -> Result<Response, error::ServerError> {
+    let size = ctx.config().sitemap_size();
+    let hostname = ctx.config().hostname();
+    let entries: Vec<Entry> = Url::sitemap(ctx, (page - 1) * size, size)
+        .await?
+        .into_iter()
+        .map(|(id, changed_at)| Entry {
+            loc: format!("https://{}/comments/{}", hostname, id),
+            lastmod: changed_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        })
+        .collect();
+    Ok(xml_reply(SitemapPage { entries: &entries }.render()?))
+}
+
+async fn handle(ctx: &Context, page: Option<i64>) -> Result<Response, error::ServerError> {
+    let pages = page_count(Url::sitemap_count(ctx).await?, ctx.config().sitemap_size());
+    match page {
+        Some(page) if page > pages => Err(error::ServerError::NotFound),
+        Some(page) => sitemap(ctx, page).await,
+        None if pages == 1 => sitemap(ctx, 1).await,
+        None => {
+            let hostname = ctx.config().hostname();
+            let sitemaps: Vec<String> = (1..=pages)
+                .map(|page| format!("https://{}/sitemaps/{}.xml", hostname, page))
+                .collect();
+            Ok(xml_reply(
+                SitemapIndexPage {
+                    sitemaps: &sitemaps,
+                }
+                .render()?,
+            ))
+        }
+    }
+}
+
+/// The sitemap of all discussion pages, at `/sitemap.xml`. Once
+/// there are more submissions than a single sitemap may list, this
+/// is a sitemap index instead, which links to the numbered sitemaps
+/// at `/sitemaps/<page>.xml`.
+pub fn page(ctx: impl ContextFilter + 'static) -> BoxedFilter<(Response,)> {
+    let index = warp::path("sitemap.xml")
+        .and(warp::path::end())
+        .map(|| None::<i64>);
+    let numbered = warp::path!("sitemaps" / String).and_then(|name: String| async move {
+        parse_page(&name)
+            .map(Some)
+            .ok_or_else(warp::reject::not_found)
+    });
+    index
+        .or(numbered)
+        .unify()
+        .and(warp::get())
+        .and(ctx)
+        .and_then(|page: Option<i64>, ctx: Context| async move {
+            error::reply(&ctx, handle(&ctx, page).await)
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_count() {
+        assert_eq!(page_count(0, 50_000), 1);
+        assert_eq!(page_count(1, 50_000), 1);
+        assert_eq!(page_count(50_000, 50_000), 1);
+        assert_eq!(page_count(50_001, 50_000), 2);
+        assert_eq!(page_count(100_000, 50_000), 2);
+        assert_eq!(page_count(100_001, 50_000), 3);
+    }
+
+    #[test]
+    fn test_parse_page() {
+        assert_eq!(parse_page("1.xml"), Some(1));
+        assert_eq!(parse_page("12.xml"), Some(12));
+        assert_eq!(parse_page("0.xml"), None);
+        assert_eq!(parse_page("-1.xml"), None);
+        assert_eq!(parse_page("1"), None);
+        assert_eq!(parse_page("one.xml"), None);
+    }
+}
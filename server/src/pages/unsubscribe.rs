@@ -0,0 +1,30 @@
+use crate::db::models::UnsubscribeToken;
+use crate::pages::{error, ContextFilter};
+use crate::Context;
+use askama::Template;
+use warp::{filters::BoxedFilter, reply::Response, Filter, Reply};
+
+#[derive(Template)]
+#[template(path = "pages/unsubscribe.html")]
+struct Page<'a> {
+    category: &'a str,
+}
+
+async fn handle(ctx: &Context, token: &str) -> Result<Response, error::ServerError> {
+    let token = UnsubscribeToken::decode(ctx, token).map_err(error::request)?;
+    token.apply(ctx).await?;
+    let page = Page {
+        category: token.category().describe(),
+    };
+    Ok(page.into_response())
+}
+
+pub fn page(ctx: impl ContextFilter + 'static) -> BoxedFilter<(Response,)> {
+    warp::path::param()
+        .and(warp::path::end())
+        .and(ctx)
+        .and_then(|token: String, ctx: Context| async move {
+            error::reply(&ctx, handle(&ctx, &token).await)
+        })
+        .boxed()
+}
@@ -1,5 +1,5 @@
 use crate::db::id::UserID;
-use crate::db::models::{Url, UrlOrdering, User};
+use crate::db::models::{Url, UrlOrdering, User, VoteDirection};
 use crate::pages::{error, ContextFilter};
 use crate::Context;
 use askama::Template;
@@ -32,7 +32,7 @@ struct ListHeader<'a> {
 #[template(path = "partials/url.html")]
 struct UrlPartial {
     url: Url,
-    created_by: User,
+    created_by: Option<User>,
     upvote_count: i64,
     is_upvoted_by_viewer: bool,
     comment_count: i64,
@@ -93,9 +93,9 @@ async fn handle(
     let mut url_list = vec![];
     for url in urls {
         url_list.push(UrlPartial {
-            created_by: url.created_by(ctx).await?,
-            upvote_count: url.upvote_count(ctx).await?,
-            is_upvoted_by_viewer: url.upvoted_by_viewer(ctx).await?,
+            created_by: url.author(ctx).await?,
+            upvote_count: url.upvotes(),
+            is_upvoted_by_viewer: url.viewer_vote(ctx).await? == Some(VoteDirection::Up),
             comment_count: url.comment_count(ctx).await?,
             url,
             is_logged_in: ctx.is_logged_in(),
@@ -0,0 +1,30 @@
+use crate::db::models::User;
+use crate::pages::{error, ContextFilter};
+use crate::Context;
+use askama::Template;
+use warp::{filters::BoxedFilter, reply::Response, Filter, Reply};
+
+#[derive(Template)]
+#[template(path = "pages/verify_email.html")]
+struct Page<'a> {
+    email: &'a str,
+}
+
+async fn handle(ctx: &Context, token: &str) -> Result<Response, error::ServerError> {
+    let user = User::verify_email(ctx, token)
+        .await
+        .map_err(error::request)?;
+    let email = user.email()?.to_string();
+    let page = Page { email: &email };
+    Ok(page.into_response())
+}
+
+pub fn page(ctx: impl ContextFilter + 'static) -> BoxedFilter<(Response,)> {
+    warp::path::param()
+        .and(warp::path::end())
+        .and(ctx)
+        .and_then(|token: String, ctx: Context| async move {
+            error::reply(&ctx, handle(&ctx, &token).await)
+        })
+        .boxed()
+}
@@ -10,16 +10,22 @@ pub fn token() -> impl Filter<Extract = (String,), Error = Infallible> + Clone {
         .map(|t: Option<String>| t.unwrap_or_else(|| nanoid!()))
 }
 
-/// This can be used to set the xsrf cookie with the correct extracted
-/// cookie value.
-pub fn cookie(ctx: &Context, reply: impl Reply) -> impl Reply {
-    let mut resp = reply.into_response();
+/// The `Set-Cookie` header value which stores
+/// the XSRF token of the given context.
+pub fn header_value(ctx: &Context) -> Option<HeaderValue> {
     let value = format!(
         "{}={}; Path=/; HttpOnly; SameSite=Strict",
         super::XSRF_COOKIE_NAME,
         ctx.xsrf_token(),
     );
-    if let Ok(header) = HeaderValue::from_str(&value) {
+    HeaderValue::from_str(&value).ok()
+}
+
+/// This can be used to set the xsrf cookie with the correct extracted
+/// cookie value.
+pub fn cookie(ctx: &Context, reply: impl Reply) -> impl Reply {
+    let mut resp = reply.into_response();
+    if let Some(header) = header_value(ctx) {
         resp.headers_mut().append("Set-Cookie", header);
     }
     resp
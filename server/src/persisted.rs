@@ -0,0 +1,91 @@
+//! Persisted GraphQL queries, which clients refer to by the hex encoded
+//! SHA-256 hash of their text, instead of sending the text with every
+//! request. Queries are persisted from a manifest on startup, see
+//! [`Config::persisted_queries`](crate::Config::persisted_queries), and,
+//! if enabled, when clients register them on first use. Registered queries
+//! are kept per server process, and lost when the server stops.
+
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Maximum number of queries clients may register, which
+/// keeps clients from filling memory with arbitrary queries.
+const MAX_REGISTERED_QUERIES: usize = 10_000;
+
+/// Longest query clients may register, in bytes. Longer
+/// queries have to be sent along with every request.
+pub const MAX_QUERY_BYTES: usize = 16 * 1024;
+
+/// The hash which refers to the given query.
+pub fn hash(query: &str) -> String {
+    Sha256::digest(query.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Persisted queries by their hash, shared by all requests.
+#[derive(Clone, Default)]
+pub struct PersistedQueries {
+    manifest: Arc<HashMap<String, String>>,
+    registered: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl PersistedQueries {
+    /// Load the queries of the given manifest, a JSON object of the
+    /// query texts by their hash, as generated by e.g. the Relay
+    /// compiler. Every hash must match its query.
+    pub fn load(path: &Path) -> Result<Self> {
+        let manifest = std::fs::read(path)?;
+        let manifest: HashMap<String, String> = serde_json::from_slice(&manifest)?;
+        for (expected, query) in &manifest {
+            if hash(query) != *expected {
+                return Err(anyhow!(
+                    "Persisted query {} does not match its hash",
+                    expected
+                ));
+            }
+        }
+        Ok(Self {
+            manifest: Arc::new(manifest),
+            registered: Default::default(),
+        })
+    }
+
+    /// The persisted query with the given hash, if any.
+    pub fn get(&self, hash: &str) -> Option<String> {
+        match self.manifest.get(hash) {
+            Some(query) => Some(query.clone()),
+            None => self.registered.read().unwrap().get(hash).cloned(),
+        }
+    }
+
+    /// Persist the given query, unless it is longer than
+    /// [`MAX_QUERY_BYTES`], or too many queries were
+    /// registered already.
+    pub fn register(&self, query: &str) {
+        if query.len() > MAX_QUERY_BYTES {
+            return;
+        }
+        let mut registered = self.registered.write().unwrap();
+        if registered.len() < MAX_REGISTERED_QUERIES {
+            registered.insert(hash(query), query.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(
+            hash("{ viewer { id } }"),
+            "3c5cde484c335605fe71515655ff4723f67a754f9af53cb4c54aed06e64f87ee"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Preview images of submitted pages. Rather than hotlinking the
+//! images pages link to, which leaks the IP addresses of readers to
+//! arbitrary third parties and breaks once sites disappear, images
+//! are fetched once, resized, and kept in the storage backend.
+//!
+//! Stored images are served from signed paths, such that the server
+//! only ever serves images it stored itself, and can not be used as
+//! an open proxy.
+
+use crate::db::id::UrlID;
+use crate::{fetch, signing, Context};
+use anyhow::{anyhow, Result};
+use image::imageops::FilterType;
+use image::io::{Limits, Reader};
+use image::{DynamicImage, ImageOutputFormat};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+
+/// Signing purpose of preview image paths.
+const PURPOSE: &str = "preview_image";
+/// Prefix of the storage keys of preview images.
+const KEY_PREFIX: &str = "previews/";
+/// Maximum dimensions of stored preview images. Larger
+/// images are scaled down, keeping their aspect ratio.
+pub const MAX_WIDTH: u32 = 1200;
+pub const MAX_HEIGHT: u32 = 630;
+/// Maximum dimensions of images which are decoded at all.
+const MAX_DECODED_DIMENSION: u32 = 8192;
+/// Quality of stored preview images, between 1 and 100.
+const JPEG_QUALITY: u8 = 85;
+
+/// Decode the given image, and encode a copy as JPEG which fits
+/// within the maximum dimensions.
+fn resize(data: &[u8]) -> Result<Vec<u8>> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODED_DIMENSION);
+    limits.max_image_height = Some(MAX_DECODED_DIMENSION);
+    let mut reader = Reader::new(Cursor::new(data)).with_guessed_format()?;
+    reader.limits(limits);
+    let image = reader.decode()?;
+    let image = if image.width() > MAX_WIDTH || image.height() > MAX_HEIGHT {
+        image.resize(MAX_WIDTH, MAX_HEIGHT, FilterType::Triangle)
+    } else {
+        image
+    };
+    let mut resized = vec![];
+    DynamicImage::ImageRgb8(image.to_rgb8()).write_to(
+        &mut Cursor::new(&mut resized),
+        ImageOutputFormat::Jpeg(JPEG_QUALITY),
+    )?;
+    Ok(resized)
+}
+
+/// Fetch the image at the given URL, and store a resized copy as the
+/// preview image of the submission with the given ID. Returns the
+/// storage key of the copy, which changes whenever the image does.
+pub async fn store(ctx: &Context, url_id: UrlID, image_url: &str) -> Result<String> {
+    let data = fetch::fetch_image(ctx, image_url).await?;
+    let resized = tokio::task::block_in_place(|| resize(&data))?;
+    let digest: String = Sha256::digest(&resized)
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let key = format!("{}{}-{}.jpg", KEY_PREFIX, url_id, digest);
+    ctx.storage().put(&key, &resized).await?;
+    Ok(key)
+}
+
+/// The path the preview image stored under the given
+/// key is served from.
+pub fn path(ctx: &Context, key: &str) -> String {
+    format!("/previews/{}", signing::sign(ctx, PURPOSE, key))
+}
+
+/// Load the preview image served from the path with the given
+/// token, see [`path`]. This fails for tokens which were not
+/// issued by this server.
+pub async fn load(ctx: &Context, token: &str) -> Result<Vec<u8>> {
+    let key = signing::verify(ctx, PURPOSE, token)
+        .filter(|key| key.starts_with(KEY_PREFIX))
+        .ok_or_else(|| anyhow!("Invalid preview image"))?;
+    ctx.storage().get(&key).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, RgbImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = vec![];
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+            .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_resize() {
+        let cases = [
+            ((100, 50), (100, 50)),
+            ((2400, 1260), (1200, 630)),
+            ((1260, 2520), (315, 630)),
+            ((4000, 100), (1200, 30)),
+        ];
+        for ((width, height), dimensions) in cases {
+            let resized = image::load_from_memory(&resize(&png(width, height)).unwrap()).unwrap();
+            assert_eq!(resized.dimensions(), dimensions, "{}x{}", width, height);
+        }
+    }
+
+    #[test]
+    fn test_resize_rejects_invalid() {
+        assert!(resize(b"not an image").is_err());
+        assert!(resize(&png(MAX_DECODED_DIMENSION + 1, 1)).is_err());
+    }
+}
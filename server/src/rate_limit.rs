@@ -0,0 +1,81 @@
+//! In-memory rate limits, for actions which don't leave a trace in
+//! the database to count attempts by, such as lookups by anonymous
+//! clients. Limits are kept per server process, and reset when the
+//! server restarts.
+
+use crate::error::RateLimited;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Number of tracked clients after which clients without recent
+/// attempts are forgotten.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+type Attempts = HashMap<(&'static str, String), VecDeque<DateTime<Utc>>>;
+
+/// Sliding window rate limiter shared by all requests.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    attempts: Arc<Mutex<Attempts>>,
+}
+
+impl RateLimiter {
+    /// Record an attempt of the action identified by `scope` by the
+    /// client identified by `key`. This fails if the client already
+    /// attempted the action `limit` times within the last `window`,
+    /// in which case the attempt is not recorded.
+    pub fn check(
+        &self,
+        scope: &'static str,
+        key: &str,
+        limit: usize,
+        window: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<(), RateLimited> {
+        let mut attempts = self.attempts.lock().unwrap();
+        if attempts.len() > PRUNE_THRESHOLD {
+            attempts.retain(|_, times| times.back().map_or(false, |at| *at + window > now));
+        }
+        let times = attempts.entry((scope, key.to_string())).or_default();
+        while times.front().map_or(false, |at| *at + window <= now) {
+            times.pop_front();
+        }
+        if times.len() >= limit {
+            // the limit frees up once the oldest attempt leaves the window
+            let retry_after = times[times.len() - limit] + window - now;
+            return Err(RateLimited::new(scope, retry_after));
+        }
+        times.push_back(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let limiter = RateLimiter::default();
+        let now = Utc::now();
+        let window = Duration::minutes(1);
+        for i in 0..3 {
+            let at = now + Duration::seconds(i);
+            assert!(limiter.check("test", "a", 3, window, at).is_ok());
+        }
+        let err = limiter
+            .check("test", "a", 3, window, now + Duration::seconds(10))
+            .unwrap_err();
+        assert_eq!(err.retry_after, Duration::seconds(50));
+
+        // other clients and scopes are limited separately
+        assert!(limiter.check("test", "b", 3, window, now).is_ok());
+        assert!(limiter.check("other", "a", 3, window, now).is_ok());
+
+        // the oldest attempt left the window
+        let later = now + Duration::seconds(60);
+        assert!(limiter.check("test", "a", 3, window, later).is_ok());
+        assert!(limiter.check("test", "a", 3, window, later).is_err());
+    }
+}
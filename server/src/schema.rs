@@ -1,3 +1,62 @@
+table! {
+    blocks (user_id, blocked_user_id) {
+        user_id -> Text,
+        blocked_user_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    bookmark_exports (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        user_id -> Text,
+        format -> Text,
+        status -> Text,
+        completed_at -> Nullable<Timestamp>,
+        storage_key -> Nullable<Text>,
+    }
+}
+
+table! {
+    collection_items (collection_id, url_id) {
+        collection_id -> Text,
+        url_id -> Text,
+        position -> Integer,
+        note -> Nullable<Text>,
+        added_at -> Timestamp,
+    }
+}
+
+table! {
+    collections (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        user_id -> Text,
+        title -> Text,
+        description -> Nullable<Text>,
+        visibility -> Text,
+    }
+}
+
+table! {
+    comment_mentions (comment_id, user_id) {
+        comment_id -> Text,
+        user_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    comment_votes (comment_id, user_id) {
+        comment_id -> Text,
+        user_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     comments (id) {
         id -> Text,
@@ -7,6 +66,84 @@ table! {
         url_id -> Text,
         created_by -> Text,
         replies_to -> Nullable<Text>,
+        depth -> Integer,
+        edited_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+        deletion_reason -> Nullable<Text>,
+        reply_count -> Integer,
+        score -> BigInt,
+        html -> Nullable<Text>,
+        revision_count -> BigInt,
+        held_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    data_exports (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        user_id -> Text,
+        status -> Text,
+        completed_at -> Nullable<Timestamp>,
+        storage_key -> Nullable<Text>,
+    }
+}
+
+table! {
+    digest_items (user_id, url_id) {
+        user_id -> Text,
+        url_id -> Text,
+        digest_id -> Text,
+    }
+}
+
+table! {
+    digests (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        user_id -> Text,
+        frequency -> Text,
+        url_count -> Integer,
+    }
+}
+
+table! {
+    follows (user_id, followed_user_id) {
+        user_id -> Text,
+        followed_user_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    group_members (group_id, user_id) {
+        group_id -> Text,
+        user_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    group_moderators (group_id, user_id) {
+        group_id -> Text,
+        user_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    groups (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        slug -> Text,
+        title -> Text,
+        description -> Nullable<Text>,
+        created_by -> Text,
+        visibility -> Text,
+        syndicated -> Bool,
+        member_count -> BigInt,
     }
 }
 
@@ -18,6 +155,18 @@ table! {
         token -> Text,
         created_by -> Text,
         claimed_by -> Nullable<Text>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    known_devices (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        user_id -> Text,
+        fingerprint -> Text,
+        last_seen -> Timestamp,
     }
 }
 
@@ -35,6 +184,70 @@ table! {
         last_user_agent -> Nullable<Text>,
         revoked -> Bool,
         last_remote_ip -> Nullable<Text>,
+        claimed_at -> Nullable<Timestamp>,
+        user_agent -> Nullable<Text>,
+        remote_ip -> Nullable<Text>,
+        failed_attempts -> Integer,
+    }
+}
+
+table! {
+    moderation_log (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        moderator_id -> Text,
+        url_id -> Text,
+        shadow -> Bool,
+        reason -> Text,
+    }
+}
+
+table! {
+    muted_domains (user_id, domain) {
+        user_id -> Text,
+        domain -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    notifications (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        user_id -> Text,
+        kind -> Text,
+        actor_id -> Text,
+        url_id -> Text,
+        comment_id -> Text,
+        read_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    reports (id) {
+        id -> Text,
+        url_id -> Text,
+        created_by -> Text,
+        reason -> Text,
+        note -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+        resolved_by -> Nullable<Text>,
+        action -> Nullable<Text>,
+    }
+}
+
+table! {
+    revisions (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        editor_id -> Text,
+        url_id -> Nullable<Text>,
+        comment_id -> Nullable<Text>,
+        title -> Nullable<Text>,
+        description -> Nullable<Text>,
+        body -> Nullable<Text>,
     }
 }
 
@@ -48,11 +261,114 @@ table! {
     }
 }
 
+table! {
+    saved_urls (id) {
+        id -> Text,
+        user_id -> Text,
+        url_id -> Text,
+        saved_at -> Timestamp,
+        read_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    security_events (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        user_id -> Text,
+        kind -> Text,
+        remote_ip -> Nullable<Text>,
+        user_agent -> Nullable<Text>,
+    }
+}
+
+table! {
+    service_imports (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        user_id -> Text,
+        service -> Text,
+        credentials -> Nullable<Text>,
+        status -> Text,
+        cursor -> Nullable<Text>,
+        imported -> Integer,
+        skipped_duplicates -> Integer,
+        error_count -> Integer,
+        last_error -> Nullable<Text>,
+        attempts -> Integer,
+        next_attempt_at -> Timestamp,
+        completed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    tag_follows (user_id, tag_name) {
+        user_id -> Text,
+        tag_name -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    tag_log (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        admin_id -> Text,
+        sources -> Text,
+        target -> Text,
+        url_count -> BigInt,
+    }
+}
+
+table! {
+    tag_redirects (name) {
+        name -> Text,
+        target -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    tags (name) {
+        name -> Text,
+        created_at -> Timestamp,
+        url_count -> BigInt,
+    }
+}
+
+table! {
+    url_embeds (url_id) {
+        url_id -> Text,
+        updated_at -> Timestamp,
+        provider -> Text,
+        html -> Text,
+        width -> Nullable<Integer>,
+        height -> Nullable<Integer>,
+    }
+}
+
+table! {
+    url_tags (url_id, tag_name) {
+        url_id -> Text,
+        tag_name -> Text,
+    }
+}
+
 table! {
     url_upvotes (url_id, user_id) {
         url_id -> Text,
         user_id -> Text,
         created_at -> Timestamp,
+        direction -> Text,
+    }
+}
+
+table! {
+    url_views (url_id, viewer, viewed_on) {
+        url_id -> Text,
+        viewer -> Text,
+        viewed_on -> Date,
     }
 }
 
@@ -61,12 +377,72 @@ table! {
         id -> Text,
         created_at -> Timestamp,
         updated_at -> Timestamp,
-        url -> Text,
+        url -> Nullable<Text>,
         status_code -> Integer,
         title -> Nullable<Text>,
         description -> Nullable<Text>,
         image -> Nullable<Text>,
         created_by -> Text,
+        canonical_url -> Nullable<Text>,
+        fetched_title -> Nullable<Text>,
+        fetched_description -> Nullable<Text>,
+        metadata_status -> Text,
+        edited_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+        comment_count -> BigInt,
+        score -> BigInt,
+        upvotes -> BigInt,
+        downvotes -> BigInt,
+        hot_rank -> Double,
+        pinned_at -> Nullable<Timestamp>,
+        domain -> Nullable<Text>,
+        removed_at -> Nullable<Timestamp>,
+        shadow_removed -> Bool,
+        preview_image -> Nullable<Text>,
+        last_checked_at -> Nullable<Timestamp>,
+        http_status -> Nullable<Integer>,
+        link_status -> Text,
+        archived_url -> Nullable<Text>,
+        archive_status -> Nullable<Text>,
+        archive_attempts -> Integer,
+        next_archive_at -> Nullable<Timestamp>,
+        draft -> Bool,
+        clicks -> BigInt,
+        views -> BigInt,
+        nsfw -> Bool,
+        nsfw_locked -> Bool,
+        language -> Nullable<Text>,
+        description_html -> Nullable<Text>,
+        revision_count -> BigInt,
+        locked_at -> Nullable<Timestamp>,
+        lock_reason -> Nullable<Text>,
+        previous_id -> Nullable<Text>,
+        anonymous -> Bool,
+        kind -> Text,
+        text -> Nullable<Text>,
+        text_html -> Nullable<Text>,
+        held_at -> Nullable<Timestamp>,
+        visibility -> Text,
+        published_at -> Timestamp,
+        group_id -> Nullable<Text>,
+        publish_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    user_preferences (user_id) {
+        user_id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        notify_new_login -> Bool,
+        timezone -> Text,
+        locale -> Text,
+        default_sort -> Text,
+        digest -> Text,
+        show_nsfw -> Text,
+        languages -> Text,
+        hide_votes -> Bool,
+        hide_from_leaderboard -> Bool,
     }
 }
 
@@ -77,15 +453,145 @@ table! {
         updated_at -> Timestamp,
         name -> Text,
         email -> Text,
+        invite_quota -> Nullable<Integer>,
+        invite_credits -> Integer,
+        last_accrual_at -> Timestamp,
+        email_verified_at -> Nullable<Timestamp>,
+        verification_sent_at -> Nullable<Timestamp>,
+        last_login_at -> Nullable<Timestamp>,
+        login_count -> Integer,
+        invited_by -> Nullable<Text>,
+        feed_token -> Nullable<Text>,
+        failed_login_count -> Integer,
+        failed_login_since -> Nullable<Timestamp>,
+        login_locked_until -> Nullable<Timestamp>,
+        banned_at -> Nullable<Timestamp>,
+        username -> Text,
+        karma -> BigInt,
+    }
+}
+
+table! {
+    webhook_deliveries (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        webhook_id -> Text,
+        event -> Text,
+        payload -> Text,
+        status -> Text,
+        attempts -> Integer,
+        next_attempt_at -> Timestamp,
+        response_status -> Nullable<Integer>,
+        last_error -> Nullable<Text>,
+        delivered_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    webhooks (id) {
+        id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        user_id -> Text,
+        site_wide -> Bool,
+        url -> Text,
+        events -> Text,
+        secret -> Text,
     }
 }
 
+joinable!(bookmark_exports -> users (user_id));
+joinable!(collection_items -> collections (collection_id));
+joinable!(collection_items -> urls (url_id));
+joinable!(collections -> users (user_id));
+joinable!(comment_mentions -> comments (comment_id));
+joinable!(comment_mentions -> users (user_id));
+joinable!(comment_votes -> comments (comment_id));
+joinable!(comment_votes -> users (user_id));
 joinable!(comments -> urls (url_id));
 joinable!(comments -> users (created_by));
+joinable!(data_exports -> users (user_id));
+joinable!(digest_items -> digests (digest_id));
+joinable!(digest_items -> urls (url_id));
+joinable!(digest_items -> users (user_id));
+joinable!(digests -> users (user_id));
+joinable!(group_members -> groups (group_id));
+joinable!(group_members -> users (user_id));
+joinable!(group_moderators -> groups (group_id));
+joinable!(group_moderators -> users (user_id));
+joinable!(groups -> users (created_by));
+joinable!(known_devices -> users (user_id));
 joinable!(logins -> users (user_id));
+joinable!(moderation_log -> urls (url_id));
+joinable!(moderation_log -> users (moderator_id));
+joinable!(muted_domains -> users (user_id));
+joinable!(notifications -> comments (comment_id));
+joinable!(notifications -> urls (url_id));
+joinable!(notifications -> users (user_id));
+joinable!(reports -> urls (url_id));
+joinable!(revisions -> comments (comment_id));
+joinable!(revisions -> urls (url_id));
+joinable!(revisions -> users (editor_id));
 joinable!(roles -> users (user_id));
+joinable!(saved_urls -> urls (url_id));
+joinable!(saved_urls -> users (user_id));
+joinable!(security_events -> users (user_id));
+joinable!(service_imports -> users (user_id));
+joinable!(tag_follows -> tags (tag_name));
+joinable!(tag_follows -> users (user_id));
+joinable!(tag_log -> users (admin_id));
+joinable!(tag_redirects -> tags (target));
+joinable!(url_embeds -> urls (url_id));
+joinable!(url_tags -> tags (tag_name));
+joinable!(url_tags -> urls (url_id));
 joinable!(url_upvotes -> urls (url_id));
 joinable!(url_upvotes -> users (user_id));
+joinable!(url_views -> urls (url_id));
+joinable!(urls -> groups (group_id));
 joinable!(urls -> users (created_by));
+joinable!(user_preferences -> users (user_id));
+joinable!(webhook_deliveries -> webhooks (webhook_id));
+joinable!(webhooks -> users (user_id));
 
-allow_tables_to_appear_in_same_query!(comments, invites, logins, roles, url_upvotes, urls, users,);
+allow_tables_to_appear_in_same_query!(
+    blocks,
+    bookmark_exports,
+    collection_items,
+    collections,
+    comment_mentions,
+    comment_votes,
+    comments,
+    data_exports,
+    digest_items,
+    digests,
+    follows,
+    group_members,
+    group_moderators,
+    groups,
+    invites,
+    known_devices,
+    logins,
+    moderation_log,
+    muted_domains,
+    notifications,
+    reports,
+    revisions,
+    roles,
+    saved_urls,
+    security_events,
+    service_imports,
+    tag_follows,
+    tag_log,
+    tag_redirects,
+    tags,
+    url_embeds,
+    url_tags,
+    url_upvotes,
+    url_views,
+    urls,
+    user_preferences,
+    users,
+    webhook_deliveries,
+    webhooks,
+);
@@ -2,16 +2,17 @@ use crate::db::models::{NewUserInput, Permission, Role, User};
 use crate::db::Pool;
 use crate::email::Mailer;
 use crate::schema::roles;
-use crate::Context;
+use crate::{Config, Context};
 use anyhow::Result;
 use diesel::prelude::*;
 use std::io::{stdin, stdout, Write};
+use std::sync::Arc;
 
 /// Check if any administrator is registered and if not,
 /// start an interactive registration flow in the terminal
 /// on startup.
-pub async fn run(pool: &Pool, mailer: &Mailer) -> Result<()> {
-    let ctx = Context::for_server(pool, mailer);
+pub async fn run(config: &Arc<Config>, pool: &Pool, mailer: &Mailer) -> Result<()> {
+    let ctx = Context::for_server(config, pool, mailer);
 
     let admin_count: i64 = roles::table
         .filter(roles::dsl::permission.eq(Permission::Administrator))
@@ -31,7 +32,8 @@ pub async fn run(pool: &Pool, mailer: &Mailer) -> Result<()> {
         let mut email = String::new();
         stdin().read_line(&mut email)?;
 
-        let admin = User::create(&ctx, NewUserInput { name, email }).await?;
+        let mut admin = User::create(&ctx, NewUserInput { name, email }).await?;
+        admin.mark_email_verified(&ctx).await?;
         Role::create(&ctx, admin.id(), Permission::Administrator).await?;
 
         println!(
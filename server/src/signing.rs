@@ -0,0 +1,100 @@
+//! Signed tokens which can be handed out to users, e.g. as
+//! part of links in emails. Tokens are signed using the
+//! configured session key, and carry a purpose such that
+//! tokens issued for one purpose can not be used for another.
+//!
+//! Multiple session keys may be configured to rotate keys.
+//! New tokens are always signed using the primary key, while
+//! tokens signed with any configured key are accepted.
+
+use crate::Context;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of tokens verified using a key other than the primary
+/// session key since the server started.
+static NON_PRIMARY_KEY_VERIFICATIONS: AtomicU64 = AtomicU64::new(0);
+
+fn mac(key: &[u8], purpose: &str, payload: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(purpose.as_bytes());
+    mac.update(b"\n");
+    mac.update(payload);
+    mac
+}
+
+/// Sign the given payload for the given purpose. The
+/// returned token is safe to use in urls. Note that the
+/// payload is only encoded, not encrypted.
+pub fn sign(ctx: &Context, purpose: &str, payload: &str) -> String {
+    let signature = mac(ctx.config().session_key(), purpose, payload.as_bytes())
+        .finalize()
+        .into_bytes();
+    format!(
+        "{}.{}",
+        base64::encode_config(payload, base64::URL_SAFE_NO_PAD),
+        base64::encode_config(signature, base64::URL_SAFE_NO_PAD),
+    )
+}
+
+/// Verify a token created with [`sign`](sign) for the
+/// same purpose, returning the signed payload. Returns
+/// `None` if the token is malformed or was tampered with.
+pub fn verify(ctx: &Context, purpose: &str, token: &str) -> Option<String> {
+    let (payload, signature) = token.split_once('.')?;
+    let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok()?;
+    let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD).ok()?;
+    let (index, key) = ctx
+        .config()
+        .session_keys()
+        .enumerate()
+        .find(|(_, key)| mac(key, purpose, &payload).verify(&signature).is_ok())?;
+    if index > 0 {
+        let count = NON_PRIMARY_KEY_VERIFICATIONS.fetch_add(1, Ordering::Relaxed) + 1;
+        log::info!(
+            "Verified {} token using non-primary session key {} ({} since startup)",
+            purpose,
+            fingerprint(key),
+            count,
+        );
+    }
+    String::from_utf8(payload).ok()
+}
+
+/// Number of tokens which were verified using a key other
+/// than the primary session key since the server started.
+/// Once this stops increasing, old keys can be removed.
+pub fn non_primary_key_verifications() -> u64 {
+    NON_PRIMARY_KEY_VERIFICATIONS.load(Ordering::Relaxed)
+}
+
+/// A short fingerprint identifying the given key,
+/// which is safe to log.
+pub fn fingerprint(key: &[u8]) -> String {
+    Sha256::digest(key)
+        .iter()
+        .take(4)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Compare two byte strings in constant time, such that the time
+/// taken does not reveal how much of a secret was guessed correctly.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Compute a keyed digest of the given data for the given
+/// purpose, encoded as hex. Use this to store values which
+/// only need to be compared, but must not be recoverable.
+/// Digests always use the primary key, so they change when
+/// the primary key is rotated.
+pub fn digest(ctx: &Context, purpose: &str, data: &str) -> String {
+    mac(ctx.config().session_key(), purpose, data.as_bytes())
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,271 @@
+//! Filtering of spam in new submissions and comments. Content is
+//! checked by the [`SpamFilter`] chosen by the configuration before
+//! it is published: suspect content is held until a moderator reviews
+//! it in the moderation queue, and spam is rejected.
+//!
+//! By default, content is checked by the [`Heuristic`] filter. With
+//! the `akismet` feature, content is checked by a service implementing
+//! the Akismet API instead, if [`Config::akismet_key`] is set.
+
+use crate::db::models::User;
+use crate::schema::{comments, urls};
+use crate::{markdown, Config, Context};
+use async_trait::async_trait;
+use chrono::Duration;
+use diesel::prelude::*;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Links posted again by the same author within this
+/// many hours count as duplicate content.
+const DUPLICATE_WINDOW_HOURS: i64 = 24;
+
+/// What is being checked for spam.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpamContentKind {
+    Submission,
+    Comment,
+}
+
+/// Content to check for spam, together with its author and the
+/// request it was posted with.
+#[derive(Debug, Clone)]
+pub struct SpamCheckInput {
+    pub kind: SpamContentKind,
+    pub author: User,
+    /// The submitted link, if any.
+    pub url: Option<String>,
+    pub title: Option<String>,
+    /// The markdown text of the comment, or the text or description
+    /// of the submission.
+    pub text: Option<String>,
+    pub remote_ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+impl SpamCheckInput {
+    /// Content posted by `author` in the request of the given
+    /// context.
+    pub fn new(ctx: &Context, kind: SpamContentKind, author: User) -> Self {
+        Self {
+            kind,
+            author,
+            url: None,
+            title: None,
+            text: None,
+            remote_ip: ctx.remote_ip_address(),
+            user_agent: ctx.user_agent().map(str::to_string),
+        }
+    }
+}
+
+/// How a spam filter judged some content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpamVerdict {
+    /// The content is fine, and published right away.
+    Ham,
+    /// The content might be spam, and is held until
+    /// a moderator reviews it.
+    Suspect,
+    /// The content is spam, and rejected.
+    Spam,
+}
+
+/// A filter which judges whether content is spam. Filters should
+/// not reject content because they failed to check it, e.g. because
+/// a remote service is unavailable, and return [`SpamVerdict::Ham`]
+/// instead.
+#[async_trait]
+pub trait SpamFilter: Send + Sync + std::fmt::Debug {
+    async fn check(&self, ctx: &Context, content: SpamCheckInput) -> SpamVerdict;
+}
+
+/// Judges content by a few rules set in the configuration: content
+/// with more links than [`Config::spam_max_links`], content by accounts
+/// younger than [`Config::spam_new_account_age`] with more links than
+/// [`Config::spam_new_account_max_links`], and content with links which
+/// the same author already posted within the last day. Content matching
+/// one of the rules is suspect, and content matching several is spam.
+#[derive(Debug, Clone)]
+pub struct Heuristic {
+    max_links: usize,
+    new_account_age: Duration,
+    new_account_max_links: usize,
+}
+
+impl Heuristic {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            max_links: config.spam_max_links(),
+            new_account_age: config.spam_new_account_age(),
+            new_account_max_links: config.spam_new_account_max_links(),
+        }
+    }
+
+    /// Whether the author already posted the given text within
+    /// the last [`DUPLICATE_WINDOW_HOURS`].
+    async fn is_duplicate(
+        &self,
+        ctx: &Context,
+        content: &SpamCheckInput,
+        text: &str,
+    ) -> anyhow::Result<bool> {
+        let since = (ctx.now() - Duration::hours(DUPLICATE_WINDOW_HOURS)).naive_utc();
+        let author = content.author.id();
+        let conn = ctx.conn().await?;
+        let count: i64 = match content.kind {
+            SpamContentKind::Comment => comments::table
+                .filter(comments::dsl::created_by.eq(author))
+                .filter(comments::dsl::created_at.ge(since))
+                .filter(comments::dsl::deleted_at.is_null())
+                .filter(comments::dsl::comment.eq(text))
+                .select(diesel::dsl::count_star())
+                .get_result(&*conn)?,
+            SpamContentKind::Submission => urls::table
+                .filter(urls::dsl::created_by.eq(author))
+                .filter(urls::dsl::created_at.ge(since))
+                .filter(urls::dsl::deleted_at.is_null())
+                .filter(urls::dsl::text.eq(text).or(urls::dsl::description.eq(text)))
+                .select(diesel::dsl::count_star())
+                .get_result(&*conn)?,
+        };
+        Ok(count > 0)
+    }
+}
+
+#[async_trait]
+impl SpamFilter for Heuristic {
+    async fn check(&self, ctx: &Context, content: SpamCheckInput) -> SpamVerdict {
+        let text = content.text.as_deref().unwrap_or("").trim();
+        let links = markdown::external_link_count(text);
+        let is_new = content.author.created_at() + self.new_account_age > ctx.now();
+        let is_duplicate = links > 0
+            && self
+                .is_duplicate(ctx, &content, text)
+                .await
+                .unwrap_or_else(|err| {
+                    log::warn!("Failed to check for duplicate content: {}", err);
+                    false
+                });
+        let matched = [
+            links > self.max_links,
+            is_new && links > self.new_account_max_links,
+            is_duplicate,
+        ]
+        .iter()
+        .filter(|&&matched| matched)
+        .count();
+        match matched {
+            0 => SpamVerdict::Ham,
+            1 => SpamVerdict::Suspect,
+            _ => SpamVerdict::Spam,
+        }
+    }
+}
+
+/// Judges content using the comment check of a service implementing
+/// the Akismet API. Content Akismet considers spam is suspect, unless
+/// Akismet advises to discard it, in which case it is spam.
+#[cfg(feature = "akismet")]
+#[derive(Debug, Clone)]
+pub struct Akismet {
+    base_url: String,
+    key: String,
+    blog: String,
+}
+
+#[cfg(feature = "akismet")]
+impl Akismet {
+    /// Use the service at the given URL, e.g.
+    /// `https://rest.akismet.com`, with the given API key.
+    pub fn new(base_url: &str, key: &str, hostname: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            key: key.to_string(),
+            blog: format!("https://{}", hostname),
+        }
+    }
+
+    async fn comment_check(
+        &self,
+        ctx: &Context,
+        content: &SpamCheckInput,
+    ) -> anyhow::Result<SpamVerdict> {
+        let comment_type = match content.kind {
+            SpamContentKind::Submission => "forum-post",
+            SpamContentKind::Comment => "reply",
+        };
+        let body = [content.title.as_deref(), content.text.as_deref()]
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let email = content
+            .author
+            .email()
+            .map(|email| email.to_string())
+            .unwrap_or_default();
+        let params = [
+            ("api_key", self.key.clone()),
+            ("blog", self.blog.clone()),
+            (
+                "user_ip",
+                content
+                    .remote_ip
+                    .map(|ip| ip.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("user_agent", content.user_agent.clone().unwrap_or_default()),
+            ("comment_type", comment_type.to_string()),
+            ("comment_author", content.author.username().to_string()),
+            ("comment_author_email", email),
+            (
+                "comment_author_url",
+                content.url.clone().unwrap_or_default(),
+            ),
+            ("comment_content", body),
+        ];
+        let resp = ctx
+            .http_client()
+            .post(format!("{}/1.1/comment-check", self.base_url))
+            .form(&params)
+            .send()
+            .await?;
+        let discard = resp
+            .headers()
+            .get("X-akismet-pro-tip")
+            .map_or(false, |tip| tip.as_bytes() == b"discard");
+        match resp.text().await?.trim() {
+            "false" => Ok(SpamVerdict::Ham),
+            "true" if discard => Ok(SpamVerdict::Spam),
+            "true" => Ok(SpamVerdict::Suspect),
+            other => Err(anyhow::anyhow!("Unexpected Akismet response {:?}", other)),
+        }
+    }
+}
+
+#[cfg(feature = "akismet")]
+#[async_trait]
+impl SpamFilter for Akismet {
+    async fn check(&self, ctx: &Context, content: SpamCheckInput) -> SpamVerdict {
+        self.comment_check(ctx, &content)
+            .await
+            .unwrap_or_else(|err| {
+                log::warn!("Failed to check content with Akismet: {}", err);
+                SpamVerdict::Ham
+            })
+    }
+}
+
+/// The spam filter described by the given configuration.
+pub fn from_config(config: &Config) -> Arc<dyn SpamFilter> {
+    if let Some(filter) = config.spam_filter() {
+        return filter.clone();
+    }
+    #[cfg(feature = "akismet")]
+    if let Some(key) = config.akismet_key() {
+        return Arc::new(Akismet::new(config.akismet_url(), key, config.hostname()));
+    }
+    Arc::new(Heuristic::new(config))
+}
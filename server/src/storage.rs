@@ -0,0 +1,63 @@
+//! Storage for files generated by the server, such as data
+//! exports. Files are addressed by keys, which may contain `/`
+//! to group related files.
+
+use crate::Config;
+use anyhow::{anyhow, Result};
+use std::path::{Component, Path, PathBuf};
+
+/// A storage backend files can be written to and
+/// read from.
+#[derive(Debug, Clone)]
+pub enum Storage {
+    /// Files are stored in a directory on the
+    /// local file system.
+    Local(PathBuf),
+}
+
+impl Storage {
+    /// The storage backend described by the given
+    /// configuration.
+    pub fn from_config(config: &Config) -> Self {
+        Storage::Local(config.storage_dir().to_path_buf())
+    }
+
+    /// Resolve a key to a path below the given root. Keys
+    /// which would escape the root are rejected.
+    fn local_path(root: &Path, key: &str) -> Result<PathBuf> {
+        let key = Path::new(key);
+        if key
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            Ok(root.join(key))
+        } else {
+            Err(anyhow!("Invalid storage key '{}'", key.display()))
+        }
+    }
+
+    /// Store the given data under the given key, replacing
+    /// any existing file.
+    pub async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        match self {
+            Storage::Local(root) => {
+                let path = Self::local_path(root, key)?;
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                tokio::fs::write(path, data).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Retrieve the data stored under the given key.
+    pub async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        match self {
+            Storage::Local(root) => {
+                let path = Self::local_path(root, key)?;
+                Ok(tokio::fs::read(path).await?)
+            }
+        }
+    }
+}
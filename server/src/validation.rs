@@ -0,0 +1,177 @@
+//! Validation of mutation arguments, which reports all violations of
+//! their constraints at once, instead of only the first one. Input
+//! objects declare their constraints using `#[derive(Validate)]`, and
+//! are checked using a [`Validator`], which records every violation with
+//! the path of the input field, e.g. `["input", "title"]`. Violations
+//! are reported as a single `VALIDATION_FAILED` error, whose `fieldErrors`
+//! extension lists them, see [`AppError::InvalidInput`].
+
+use crate::error::AppError;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
+
+/// A violated constraint of an input field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldViolation {
+    /// Path to the input field, starting with the name
+    /// of the argument, using the names of the schema.
+    pub path: Vec<String>,
+    /// Stable code of the violated constraint, e.g. `TOO_LONG`.
+    pub code: String,
+    pub message: String,
+    /// The exceeded limit of length constraints.
+    pub limit: Option<u64>,
+}
+
+/// Accumulates the violations of the arguments of a request.
+#[derive(Debug, Default)]
+pub struct Validator {
+    violations: Vec<FieldViolation>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check the constraints of the input object passed
+    /// as the given argument.
+    pub fn check(mut self, argument: &str, input: &impl Validate) -> Self {
+        if let Err(errors) = input.validate() {
+            let mut violations = vec![];
+            collect(&mut violations, &[argument.to_string()], &errors);
+            violations.sort_by(|a, b| a.path.cmp(&b.path));
+            self.violations.extend(violations);
+        }
+        self
+    }
+
+    /// Fail with all violations, if any.
+    pub fn finish(self) -> Result<(), AppError> {
+        if self.violations.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::InvalidInput(self.violations))
+        }
+    }
+}
+
+/// Record the violations of the given errors, of the
+/// input object at the given path.
+fn collect(violations: &mut Vec<FieldViolation>, path: &[String], errors: &ValidationErrors) {
+    for (field, kind) in errors.errors() {
+        let mut path = path.to_vec();
+        path.push(schema_name(field));
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                violations.extend(errors.iter().map(|error| violation(&path, error)))
+            }
+            ValidationErrorsKind::Struct(errors) => collect(violations, &path, errors),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    let mut path = path.clone();
+                    path.push(index.to_string());
+                    collect(violations, &path, errors);
+                }
+            }
+        }
+    }
+}
+
+/// The violation of the given field error. Constraints of the
+/// validator crate are mapped to stable codes, custom constraints
+/// are expected to use stable codes already.
+fn violation(path: &[String], error: &ValidationError) -> FieldViolation {
+    let (code, limit) = match error.code.as_ref() {
+        "length" => length_violation(&error.params),
+        "email" => ("INVALID_EMAIL".into(), None),
+        "url" => ("INVALID_URL".into(), None),
+        code => (code.to_ascii_uppercase(), None),
+    };
+    FieldViolation {
+        path: path.to_vec(),
+        message: error.message.as_ref().map_or_else(
+            || "The value is invalid".into(),
+            |message| message.to_string(),
+        ),
+        code,
+        limit,
+    }
+}
+
+/// The code and the exceeded limit of a violated length constraint.
+fn length_violation(params: &HashMap<Cow<'static, str>, Value>) -> (String, Option<u64>) {
+    let len = match params.get("value") {
+        Some(Value::String(value)) => value.chars().count() as u64,
+        Some(Value::Array(items)) => items.len() as u64,
+        _ => 0,
+    };
+    let max = params.get("max").and_then(Value::as_u64);
+    let min = params.get("min").and_then(Value::as_u64);
+    match (min, max) {
+        (_, Some(max)) if len > max => ("TOO_LONG".into(), Some(max)),
+        (Some(min), _) if len < min => ("TOO_SHORT".into(), Some(min)),
+        _ => ("INVALID_LENGTH".into(), None),
+    }
+}
+
+/// The name of the given struct field in the schema.
+fn schema_name(field: &str) -> String {
+    let mut name = String::with_capacity(field.len());
+    let mut upper = false;
+    for c in field.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            name.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Validate)]
+    struct Input {
+        #[validate(length(min = 1, max = 4, message = "The title is too long"))]
+        title_text: String,
+        #[validate(email)]
+        email: String,
+    }
+
+    #[test]
+    fn test_all_violations_are_reported() {
+        let input = Input {
+            title_text: "Too long".into(),
+            email: "invalid".into(),
+        };
+        let violations = match Validator::new().check("input", &input).finish() {
+            Err(AppError::InvalidInput(violations)) => violations,
+            _ => panic!("Expected invalid input"),
+        };
+        assert_eq!(
+            violations,
+            vec![
+                FieldViolation {
+                    path: vec!["input".into(), "email".into()],
+                    code: "INVALID_EMAIL".into(),
+                    message: "The value is invalid".into(),
+                    limit: None,
+                },
+                FieldViolation {
+                    path: vec!["input".into(), "titleText".into()],
+                    code: "TOO_LONG".into(),
+                    message: "The title is too long".into(),
+                    limit: Some(4),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use chrono::Duration;
+use once_cell::sync::Lazy;
+use server::{Config, IpPrivacy, RegistrationMode, VarError};
+use std::ffi::OsString;
+use std::sync::{Mutex, MutexGuard};
+
+/// Serializes the tests, which share the environment of the process.
+static ENV_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+const SESSION_KEY: &str = "a-session-key-of-at-least-32-bytes";
+
+/// Replaces the environment of the process with the given variables,
+/// until it is dropped, which restores the previous environment.
+struct ScopedEnv {
+    previous: Vec<(OsString, OsString)>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl ScopedEnv {
+    fn new(vars: &[(&str, &str)]) -> Self {
+        let lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let previous: Vec<_> = std::env::vars_os().collect();
+        for (name, _) in &previous {
+            std::env::remove_var(name);
+        }
+        for (name, value) in vars {
+            std::env::set_var(name, value);
+        }
+        Self {
+            previous,
+            _lock: lock,
+        }
+    }
+}
+
+impl Drop for ScopedEnv {
+    fn drop(&mut self) {
+        for (name, _) in std::env::vars_os() {
+            std::env::remove_var(name);
+        }
+        for (name, value) in &self.previous {
+            std::env::set_var(name, value);
+        }
+    }
+}
+
+/// The errors loading the configuration from the given variables fails with.
+fn failed_vars(vars: &[(&str, &str)]) -> Vec<VarError> {
+    let _env = ScopedEnv::new(vars);
+    Config::load().unwrap_err().errors().to_vec()
+}
+
+#[test]
+fn test_missing_variables() {
+    let errors = failed_vars(&[]);
+    assert_eq!(
+        errors,
+        vec![
+            VarError::Missing("DATABASE_URL"),
+            VarError::Missing("HOSTNAME"),
+            VarError::Missing("SESSION_KEY"),
+        ]
+    );
+
+    // parts of the smtp config are required together
+    let errors = failed_vars(&[
+        ("DATABASE_URL", "file:database.db"),
+        ("HOSTNAME", "localhost:8080"),
+        ("SESSION_KEY", SESSION_KEY),
+        ("SMTP_HOST", "smtp.urls.fyi"),
+    ]);
+    assert_eq!(
+        errors,
+        vec![
+            VarError::Missing("SMTP_USER"),
+            VarError::Missing("SMTP_PASS"),
+        ]
+    );
+}
+
+#[test]
+fn test_malformed_variables() {
+    let _env = ScopedEnv::new(&[
+        ("DATABASE_URL", "file:database.db"),
+        ("HOSTNAME", "https://urls.fyi/"),
+        ("SESSION_KEY", &format!("{},too-short", SESSION_KEY)),
+        ("GEOIP_API", "not a url"),
+        ("INVITE_QUOTA", "-1"),
+        ("REGISTRATION_MODE", "sometimes"),
+        ("FETCH_TIMEOUT_SECS", "ten"),
+        ("URL_EDIT_WINDOW_MINUTES", "-5"),
+        ("MAX_COMMENT_DEPTH", "-2"),
+        ("ALLOW_SELF_VOTES", "yes"),
+        ("ARCHIVE_URL", "ftp://archive.urls.fyi"),
+    ]);
+    let err = Config::load().unwrap_err();
+    let names: Vec<&str> = err.errors().iter().map(VarError::name).collect();
+    assert_eq!(
+        names,
+        vec![
+            "HOSTNAME",
+            "GEOIP_API",
+            "INVITE_QUOTA",
+            "REGISTRATION_MODE",
+            "SESSION_KEY",
+            "FETCH_TIMEOUT_SECS",
+            "URL_EDIT_WINDOW_MINUTES",
+            "MAX_COMMENT_DEPTH",
+            "ALLOW_SELF_VOTES",
+            "ARCHIVE_URL",
+        ]
+    );
+    assert!(err
+        .errors()
+        .iter()
+        .all(|error| matches!(error, VarError::Invalid { .. })));
+
+    // every variable is reported, without leaking key material
+    let message = err.to_string();
+    assert!(message.starts_with("10 configuration variable(s) missing or invalid"));
+    assert!(message.contains("REGISTRATION_MODE is invalid: 'sometimes' is not one of"));
+    assert!(message.contains("URL_EDIT_WINDOW_MINUTES is invalid: '-5' is negative"));
+    assert!(message.contains("MAX_COMMENT_DEPTH is invalid: '-2' is negative"));
+    assert!(!message.contains("too-short"), "{}", message);
+}
+
+#[test]
+fn test_complete_configuration() {
+    let _env = ScopedEnv::new(&[
+        ("DATABASE_URL", "file:database.db"),
+        ("HOSTNAME", "urls.fyi"),
+        ("SESSION_KEY", SESSION_KEY),
+        ("SMTP_HOST", "smtp.urls.fyi"),
+        ("SMTP_USER", "urls"),
+        ("SMTP_PASS", "password"),
+        ("SMTP_PORT", "465"),
+        ("REGISTRATION_MODE", "open"),
+        ("IP_PRIVACY", "hash"),
+        ("URL_EDIT_WINDOW_MINUTES", "30"),
+        ("INVITE_ACCRUAL_DAYS", "0"),
+        ("ARCHIVE_URL", "none"),
+    ]);
+    let config = Config::load().unwrap();
+    assert_eq!(config.hostname(), "urls.fyi");
+    assert_eq!(config.session_key(), SESSION_KEY.as_bytes());
+    assert_eq!(config.smtp().unwrap().port(), 465);
+    assert_eq!(config.registration_mode(), RegistrationMode::Open);
+    assert_eq!(config.ip_privacy(), IpPrivacy::Hash);
+    assert_eq!(config.url_edit_window(), Duration::minutes(30));
+    assert_eq!(config.invite_accrual(), None);
+    assert_eq!(config.archive_url(), None);
+
+    // optional variables have their defaults
+    assert_eq!(config.comment_edit_window(), Duration::minutes(15));
+    assert_eq!(config.fetch_timeout(), std::time::Duration::from_secs(10));
+    assert!(!config.allow_private_urls());
+    assert_eq!(config.geoip_api(), None);
+}
@@ -0,0 +1,250 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::json;
+use server::db::id::UserID;
+use server::db::models::{Digest, DigestFrequency, NewUserInput, User, DIGEST_SIZE};
+use server::schema::digests;
+use server::Context;
+mod setup;
+
+const MUTATION_PREFERENCES: &str = "
+    mutation UpdatePreferences($digest: DigestFrequency!) {
+        updatePreferences(input: { digest: $digest }) {
+            preferences { digest }
+        }
+    }
+";
+
+const MUTATION_MUTE: &str = "
+    mutation MuteDomain($domain: String!) {
+        muteDomain(domain: $domain) { ok }
+    }
+";
+
+const MUTATION_BLOCK: &str = "
+    mutation BlockUser($id: ID!) {
+        blockUser(userId: $id) { ok }
+    }
+";
+
+const QUERY_PREFERENCES: &str = "
+    query Preferences {
+        viewer {
+            preferences { digest }
+        }
+    }
+";
+
+/// The last sent email, with soft line breaks removed.
+async fn last_email(ctx: &Context) -> String {
+    setup::last_email(ctx).await.replace("=\r\n", "")
+}
+
+/// The number of digests recorded for the given user.
+async fn digest_count(ctx: &Context, user_id: UserID) -> i64 {
+    digests::table
+        .filter(digests::dsl::user_id.eq(user_id))
+        .count()
+        .get_result(&*ctx.conn().await.unwrap())
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_digest_content() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let input = NewUserInput {
+        name: "Carol".into(),
+        email: "test.carol@urls.fyi".into(),
+    };
+    let carol = User::create(&ctx, input).await.unwrap();
+
+    let data = setup::execute_ok(
+        &server,
+        MUTATION_PREFERENCES,
+        json!({ "digest": "WEEKLY" }),
+        &session,
+    )
+    .await;
+    assert_eq!(data["updatePreferences"]["preferences"]["digest"], "WEEKLY");
+    let vars = json!({ "domain": "muted.example.com" });
+    setup::execute_ok(&server, MUTATION_MUTE, vars, &session).await;
+    let vars = json!({ "id": carol.id().to_string() });
+    setup::execute_ok(&server, MUTATION_BLOCK, vars, &session).await;
+
+    setup::Submission::by(admin.id())
+        .domain("example.com")
+        .title("Top")
+        .score(10)
+        .created_at(ctx.now() - Duration::minutes(60))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(admin.id())
+        .domain("example.com")
+        .title("Second")
+        .score(5)
+        .created_at(ctx.now() - Duration::minutes(120))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(admin.id())
+        .domain("muted.example.com")
+        .title("Muted")
+        .score(20)
+        .created_at(ctx.now() - Duration::minutes(60))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(carol.id())
+        .domain("example.com")
+        .title("Blocked")
+        .score(15)
+        .created_at(ctx.now() - Duration::minutes(60))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(admin.id())
+        .domain("example.com")
+        .title("Stale")
+        .score(30)
+        .created_at(ctx.now() - Duration::minutes(8 * 24 * 60))
+        .insert(&ctx)
+        .await;
+    for i in 0..DIGEST_SIZE {
+        let title = format!("Filler {}", i);
+        setup::Submission::by(admin.id())
+            .domain("example.org")
+            .title(&title)
+            .score(1)
+            .created_at(ctx.now() - Duration::minutes(180))
+            .insert(&ctx)
+            .await;
+    }
+
+    Digest::send_due(&ctx).await.unwrap();
+    let email = last_email(&ctx).await;
+    assert!(email.contains("Subject: Your weekly digest"));
+    assert!(email.contains("1. Top (10 points)"));
+    assert!(email.contains("2. Second (5 points)"));
+    assert_eq!(email.matches(" points)").count(), DIGEST_SIZE as usize);
+    for excluded in ["Muted", "Blocked", "Stale"] {
+        assert!(!email.contains(excluded), "{}", excluded);
+    }
+
+    // users who did not choose to receive digests get none
+    assert_eq!(digest_count(&ctx, user.id()).await, 1);
+    assert_eq!(digest_count(&ctx, admin.id()).await, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_digest_dedup() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let vars = json!({ "digest": "WEEKLY" });
+    setup::execute_ok(&server, MUTATION_PREFERENCES, vars, &session).await;
+
+    setup::Submission::by(user.id())
+        .domain("example.com")
+        .title("First")
+        .score(3)
+        .created_at(ctx.now() - Duration::minutes(60))
+        .insert(&ctx)
+        .await;
+    Digest::send_due(&ctx).await.unwrap();
+    let email = last_email(&ctx).await;
+    assert!(email.contains("1. First"));
+
+    // running the job again does not send another digest
+    setup::Submission::by(user.id())
+        .domain("example.com")
+        .title("Second")
+        .score(2)
+        .created_at(ctx.now() - Duration::minutes(30))
+        .insert(&ctx)
+        .await;
+    Digest::send_due(&ctx).await.unwrap();
+    assert_eq!(digest_count(&ctx, user.id()).await, 1);
+    assert_eq!(last_email(&ctx).await, email);
+
+    // submissions are never included twice
+    let digest = Digest::send(&ctx, &user, DigestFrequency::Weekly)
+        .await
+        .unwrap();
+    assert_eq!(digest.url_count(), 1);
+    let email = last_email(&ctx).await;
+    assert!(email.contains("1. Second"));
+    assert!(!email.contains("First"));
+    let digest = Digest::send(&ctx, &user, DigestFrequency::Weekly)
+        .await
+        .unwrap();
+    assert_eq!(digest.url_count(), 0);
+    assert_eq!(last_email(&ctx).await, email);
+
+    // the next digest is due once the period passed
+    let mut later = ctx.clone();
+    later.set_request_time(ctx.now() + Duration::days(7) + Duration::minutes(1));
+    setup::Submission::by(user.id())
+        .domain("example.com")
+        .title("Third")
+        .score(1)
+        .insert(&later)
+        .await;
+    Digest::send_due(&later).await.unwrap();
+    assert_eq!(digest_count(&ctx, user.id()).await, 4);
+    let email = last_email(&ctx).await;
+    assert!(email.contains("1. Third"));
+    assert!(!email.contains("Second"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_digest_unsubscribe() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let vars = json!({ "digest": "DAILY" });
+    setup::execute_ok(&server, MUTATION_PREFERENCES, vars, &session).await;
+
+    setup::Submission::by(user.id())
+        .domain("example.com")
+        .title("First")
+        .score(3)
+        .created_at(ctx.now() - Duration::minutes(60))
+        .insert(&ctx)
+        .await;
+    Digest::send_due(&ctx).await.unwrap();
+    let email = last_email(&ctx).await;
+    assert!(email.contains("Subject: Your daily digest"));
+    assert!(email.contains("digests of top submissions"));
+    let token = email
+        .split_whitespace()
+        .find_map(|word| word.split("/unsubscribe/").nth(1))
+        .expect("Missing unsubscribe link");
+
+    let res = warp::test::request()
+        .path(&format!("/unsubscribe/{}", token))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let data = setup::execute_ok(&server, QUERY_PREFERENCES, json!({}), &session).await;
+    assert_eq!(data["viewer"]["preferences"]["digest"], "OFF");
+
+    // no further digests are sent
+    let mut later = ctx.clone();
+    later.set_request_time(ctx.now() + Duration::days(2));
+    setup::Submission::by(user.id())
+        .domain("example.com")
+        .title("Second")
+        .score(1)
+        .insert(&later)
+        .await;
+    Digest::send_due(&later).await.unwrap();
+    assert_eq!(digest_count(&ctx, user.id()).await, 1);
+}
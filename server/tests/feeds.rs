@@ -0,0 +1,327 @@
+use chrono::{DateTime, Duration};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use feed_rs::model::FeedType;
+use jsonschema::JSONSchema;
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::User;
+use server::schema::{tags, url_tags, urls};
+use server::Context;
+mod setup;
+
+/// Tag the given submission.
+async fn tag(ctx: &Context, url: UrlID, name: &str) {
+    let conn = ctx.conn().await.unwrap();
+    diesel::insert_or_ignore_into(tags::table)
+        .values((
+            tags::dsl::name.eq(name),
+            tags::dsl::created_at.eq(ctx.now().naive_utc()),
+            tags::dsl::url_count.eq(1),
+        ))
+        .execute(&*conn)
+        .unwrap();
+    diesel::insert_into(url_tags::table)
+        .values((
+            url_tags::dsl::url_id.eq(url),
+            url_tags::dsl::tag_name.eq(name),
+        ))
+        .execute(&*conn)
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_atom_feed() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let first = setup::Submission::by(user.id())
+        .title("First")
+        .created_at(ctx.now() - Duration::minutes(1))
+        .query("a=1&b=2")
+        .insert(&ctx)
+        .await;
+    let second = setup::Submission::by(user.id())
+        .title("<b>Bold</b> & \"quoted\"")
+        .query("a=1&b=2")
+        .insert(&ctx)
+        .await;
+
+    let res = warp::test::request()
+        .path("/feed.atom")
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers()["Content-Type"],
+        "application/atom+xml; charset=utf-8"
+    );
+    let feed = feed_rs::parser::parse(res.body().as_ref()).unwrap();
+    assert_eq!(feed.feed_type, FeedType::Atom);
+    assert_eq!(feed.title.unwrap().content, "urls.fyi");
+    assert_eq!(feed.updated.unwrap().timestamp(), ctx.now().timestamp());
+
+    // newest first, with titles escaped rather than interpreted
+    let entries = &feed.entries;
+    assert_eq!(entries.len(), 2);
+    let entry = &entries[0];
+    assert_eq!(entry.id, format!("https://example.com/{}", second));
+    assert_eq!(
+        entry.title.as_ref().unwrap().content,
+        "<b>Bold</b> & \"quoted\""
+    );
+    assert_eq!(entry.authors[0].name, user.name());
+    let links: Vec<(Option<&str>, &str)> = entry
+        .links
+        .iter()
+        .map(|link| (link.rel.as_deref(), link.href.as_str()))
+        .collect();
+    assert_eq!(
+        links,
+        vec![
+            (
+                Some("alternate"),
+                format!("https://example.com/{}?a=1&b=2", second).as_str()
+            ),
+            (
+                Some("replies"),
+                format!("https://localhost/comments/{}", second).as_str()
+            ),
+        ]
+    );
+    assert_eq!(entries[1].id, format!("https://example.com/{}", first));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rss_feed() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let id = setup::Submission::by(user.id())
+        .title("Fish & <Chips>")
+        .query("a=1&b=2")
+        .insert(&ctx)
+        .await;
+
+    for path in ["/feed.rss", "/feed.xml"] {
+        let res = warp::test::request().path(path).reply(&server).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(
+            res.headers()["Content-Type"],
+            "application/rss+xml; charset=utf-8"
+        );
+        let feed = feed_rs::parser::parse(res.body().as_ref()).unwrap();
+        assert_eq!(feed.feed_type, FeedType::RSS2);
+        let entry = &feed.entries[0];
+        assert_eq!(entry.id, format!("https://example.com/{}", id));
+        assert_eq!(entry.title.as_ref().unwrap().content, "Fish & <Chips>");
+        assert_eq!(
+            entry.links[0].href,
+            format!("https://example.com/{}?a=1&b=2", id)
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tag_feed() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let tagged = setup::Submission::by(user.id())
+        .title("Tagged")
+        .query("a=1&b=2")
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(user.id())
+        .title("Untagged")
+        .created_at(ctx.now() + Duration::minutes(1))
+        .query("a=1&b=2")
+        .insert(&ctx)
+        .await;
+    tag(&ctx, tagged, "rust").await;
+
+    let res = warp::test::request()
+        .path("/t/rust/feed.atom")
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let feed = feed_rs::parser::parse(res.body().as_ref()).unwrap();
+    let titles: Vec<String> = feed
+        .entries
+        .iter()
+        .map(|entry| entry.title.as_ref().unwrap().content.clone())
+        .collect();
+    assert_eq!(titles, vec!["Tagged"]);
+
+    let res = warp::test::request()
+        .path("/t/unknown/feed.atom")
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let feed = feed_rs::parser::parse(res.body().as_ref()).unwrap();
+    assert!(feed.entries.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_json_feed() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let first = setup::Submission::by(user.id())
+        .title("First")
+        .created_at(ctx.now() - Duration::minutes(1))
+        .query("a=1&b=2")
+        .insert(&ctx)
+        .await;
+    let second = setup::Submission::by(user.id())
+        .title("Second")
+        .query("a=1&b=2")
+        .insert(&ctx)
+        .await;
+    tag(&ctx, second, "rust").await;
+    diesel::update(urls::table.find(second))
+        .set(urls::dsl::description.eq("About <Rust>"))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+
+    let schema: Value = serde_json::from_str(include_str!("schemas/jsonfeed-1.1.json")).unwrap();
+    let schema = JSONSchema::compile(&schema).unwrap();
+
+    let res = warp::test::request()
+        .path("/feed.json")
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers()["Content-Type"],
+        "application/feed+json; charset=utf-8"
+    );
+    let feed: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(schema.is_valid(&feed), "{}", feed);
+    assert_eq!(feed["version"], "https://jsonfeed.org/version/1.1");
+    assert_eq!(feed["title"], "urls.fyi");
+    assert_eq!(feed["home_page_url"], "https://localhost/");
+    assert_eq!(feed["feed_url"], "https://localhost/feed.json");
+
+    let items = feed["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    let item = &items[0];
+    assert_eq!(item["id"], format!("https://example.com/{}", second));
+    assert_eq!(
+        item["url"],
+        format!("https://localhost/comments/{}", second)
+    );
+    assert_eq!(
+        item["external_url"],
+        format!("https://example.com/{}?a=1&b=2", second)
+    );
+    assert_eq!(item["title"], "Second");
+    assert_eq!(item["content_text"], "About <Rust>");
+    let published = DateTime::parse_from_rfc3339(item["date_published"].as_str().unwrap());
+    assert_eq!(published.unwrap().timestamp(), ctx.now().timestamp());
+    assert_eq!(item["authors"], json!([{ "name": user.name() }]));
+    assert_eq!(item["tags"], json!(["rust"]));
+
+    // submissions without a description still have content
+    assert_eq!(items[1]["id"], format!("https://example.com/{}", first));
+    assert_eq!(items[1]["content_text"], "First");
+    assert_eq!(items[1]["tags"], json!([]));
+
+    // the same submissions as the other formats
+    let res = warp::test::request()
+        .path("/feed.atom")
+        .reply(&server)
+        .await;
+    let atom = feed_rs::parser::parse(res.body().as_ref()).unwrap();
+    let atom_ids: Vec<&str> = atom.entries.iter().map(|entry| entry.id.as_str()).collect();
+    let json_ids: Vec<&str> = items
+        .iter()
+        .map(|item| item["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(atom_ids, json_ids);
+
+    let res = warp::test::request()
+        .path("/t/rust/feed.json")
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let feed: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(schema.is_valid(&feed), "{}", feed);
+    assert_eq!(feed["title"], "urls.fyi: rust");
+    assert_eq!(feed["feed_url"], "https://localhost/t/rust/feed.json");
+    let ids: Vec<&str> = feed["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, vec![format!("https://example.com/{}", second)]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_conditional_requests() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    setup::Submission::by(user.id())
+        .title("First")
+        .query("a=1&b=2")
+        .insert(&ctx)
+        .await;
+
+    let res = warp::test::request()
+        .path("/feed.atom")
+        .reply(&server)
+        .await;
+    let etag = res.headers()["ETag"].to_str().unwrap().to_string();
+    let last_modified = res.headers()["Last-Modified"].to_str().unwrap().to_string();
+
+    // unchanged feeds are not sent again
+    let res = warp::test::request()
+        .path("/feed.atom")
+        .header("If-None-Match", &etag)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 304);
+    assert!(res.body().is_empty());
+    assert_eq!(res.headers()["ETag"], etag.as_str());
+    let res = warp::test::request()
+        .path("/feed.atom")
+        .header("If-Modified-Since", &last_modified)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 304);
+
+    // the tag differs between formats
+    let res = warp::test::request()
+        .path("/feed.rss")
+        .header("If-None-Match", &etag)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    // new submissions change the feed
+    setup::Submission::by(user.id())
+        .title("Second")
+        .created_at(ctx.now() + Duration::minutes(1))
+        .query("a=1&b=2")
+        .insert(&ctx)
+        .await;
+    let res = warp::test::request()
+        .path("/feed.atom")
+        .header("If-None-Match", &etag)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_ne!(res.headers()["ETag"], etag.as_str());
+    let res = warp::test::request()
+        .path("/feed.atom")
+        .header("If-Modified-Since", &last_modified)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+}
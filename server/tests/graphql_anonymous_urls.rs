@@ -0,0 +1,127 @@
+use serde_json::{json, Value};
+use server::db::models::{NewUserInput, User};
+use server::Config;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url {
+                id
+                anonymous
+                createdBy { username }
+            }
+        }
+    }
+";
+
+const QUERY_URL: &str = "
+    query Url($id: ID!) {
+        fetch__Url(id: $id) {
+            anonymous
+            createdBy { username }
+        }
+    }
+";
+
+const QUERY_PROFILE: &str = "
+    query Profile {
+        user(username: \"test-user\") {
+            urlCount
+            urls(first: 10) {
+                edges {
+                    node { id }
+                }
+            }
+        }
+    }
+";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_anonymous_submitter_visibility() {
+    let config = Config::test().with_anonymous_submissions(true);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let input = NewUserInput {
+        name: "Other".into(),
+        email: "test.other@urls.fyi".into(),
+    };
+    User::create(&ctx, input).await.unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let other_session = setup::session_token(&ctx, "test.other@urls.fyi").await;
+
+    let vars = json!({ "input": { "url": setup::serve_page(), "anonymous": true } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &body["data"]["submitUrl"]["url"];
+    assert_eq!(url["anonymous"], true);
+    assert_eq!(url["createdBy"]["username"], "test-user");
+    let vars = json!({ "id": url["id"] });
+
+    // the submitter and administrators see who submitted the url
+    for session in &[session.as_str(), admin_session.as_str()] {
+        let body = setup::execute(&server, QUERY_URL, vars.clone(), session).await;
+        assert_eq!(
+            body["data"]["fetch__Url"],
+            json!({ "anonymous": true, "createdBy": { "username": "test-user" } })
+        );
+    }
+
+    // everyone else does not
+    for session in &[other_session.as_str(), ""] {
+        let body = setup::execute(&server, QUERY_URL, vars.clone(), session).await;
+        assert_eq!(
+            body["data"]["fetch__Url"],
+            json!({ "anonymous": true, "createdBy": null })
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_anonymous_submissions_profile() {
+    let config = Config::test().with_anonymous_submissions(true);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let vars = json!({ "input": { "url": setup::serve_page(), "anonymous": false } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let public = body["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "input": { "url": setup::serve_page(), "anonymous": true } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let anonymous = body["data"]["submitUrl"]["url"]["id"].clone();
+
+    let ids = |body: &Value| -> Vec<Value> {
+        body["data"]["user"]["urls"]["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["node"]["id"].clone())
+            .collect()
+    };
+
+    // the profile only lists anonymous submissions to the submitter
+    let body = setup::execute(&server, QUERY_PROFILE, json!({}), &session).await;
+    assert_eq!(body["data"]["user"]["urlCount"], 2);
+    let listed = ids(&body);
+    assert_eq!(listed.len(), 2);
+    assert!(listed.contains(&anonymous) && listed.contains(&public));
+    for session in &[admin_session.as_str(), ""] {
+        let body = setup::execute(&server, QUERY_PROFILE, json!({}), session).await;
+        assert_eq!(body["data"]["user"]["urlCount"], 1);
+        assert_eq!(ids(&body), vec![public.clone()]);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_anonymous_submissions_disabled() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "input": { "url": setup::serve_page(), "anonymous": true } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Anonymous submissions are not enabled"
+    );
+}
@@ -0,0 +1,119 @@
+use serde_json::json;
+use server::db::models::{NewUserInput, User};
+use server::Context;
+mod setup;
+
+const MUTATION_BLOCK: &str = "
+    mutation BlockUser($id: ID!) {
+        blockUser(userId: $id) { ok }
+    }
+";
+
+const MUTATION_VOTE: &str = "
+    mutation VoteUrl($id: ID!) {
+        voteUrl(id: $id) { score }
+    }
+";
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!) {
+        addComment(urlId: $url, body: $body) { id }
+    }
+";
+
+/// Lists submissions with the given fields of each submission.
+fn query_submissions(fields: &str) -> String {
+    format!(
+        "query Submissions($first: Int!) {{
+            submissions(first: $first) {{
+                edges {{
+                    node {{ id {} }}
+                }}
+            }}
+        }}",
+        fields
+    )
+}
+
+/// Create a verified user with the given name, returning them.
+async fn create_user(ctx: &Context, name: &str) -> User {
+    let input = NewUserInput {
+        name: name.into(),
+        email: format!("test.{}@urls.fyi", name.to_lowercase()),
+    };
+    let mut user = User::create(ctx, input).await.unwrap();
+    user.mark_email_verified(ctx).await.unwrap();
+    user
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connection_lookups_are_batched() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let mut authors = vec![];
+    for name in ["Alice", "Bob", "Carol", "Dave", "Erin"] {
+        authors.push(create_user(&ctx, name).await);
+    }
+    let mut ids = vec![];
+    for index in 0..50 {
+        ids.push(setup::submit(&ctx, authors[index % authors.len()].id()).await);
+    }
+
+    // the viewer voted on some submissions, and blocked
+    // a user who commented on others
+    for id in ids.iter().step_by(3) {
+        let vars = json!({ "id": id.to_string() });
+        let body = setup::execute(&server, MUTATION_VOTE, vars, &session).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+    let blocked = create_user(&ctx, "Mallory").await;
+    let blocked_session = setup::session_token(&ctx, "test.mallory@urls.fyi").await;
+    for id in ids.iter().step_by(7) {
+        let vars = json!({ "url": id.to_string(), "body": "A comment" });
+        let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &blocked_session).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+    let vars = json!({ "id": blocked.id().to_string() });
+    let body = setup::execute(&server, MUTATION_BLOCK, vars, &session).await;
+    assert!(body["errors"].is_null(), "{}", body);
+
+    // count the queries of listing submissions with the given fields
+    let count = |fields: &'static str, first: i32| {
+        let (server, ctx, session) = (&server, &ctx, &session);
+        async move {
+            let query = query_submissions(fields);
+            let before = ctx.connection_count();
+            let body = setup::execute(server, &query, json!({ "first": first }), session).await;
+            let count = ctx.connection_count() - before;
+            let edges = body["data"]["submissions"]["edges"].as_array().unwrap();
+            assert_eq!(edges.len(), first as usize, "{}", body);
+            (count, body)
+        }
+    };
+
+    // the authors of a page are looked up with a single query
+    let (listed, _) = count("", 50).await;
+    let (with_authors, body) = count("createdBy { username }", 50).await;
+    assert_eq!(with_authors, listed + 1);
+    let edges = body["data"]["submissions"]["edges"].as_array().unwrap();
+    let mut usernames: Vec<&str> = edges
+        .iter()
+        .map(|edge| edge["node"]["createdBy"]["username"].as_str().unwrap())
+        .collect();
+    usernames.sort_unstable();
+    usernames.dedup();
+    assert_eq!(usernames, vec!["alice", "bob", "carol", "dave", "erin"]);
+
+    // as are the votes and comment counts, regardless of the page size
+    let fields = "createdBy { username } viewerHasVoted commentCount";
+    let (small, _) = count(fields, 2).await;
+    let (large, body) = count(fields, 50).await;
+    assert_eq!(small, large);
+    let edges = body["data"]["submissions"]["edges"].as_array().unwrap();
+    let voted = edges
+        .iter()
+        .filter(|edge| edge["node"]["viewerHasVoted"] == true)
+        .count();
+    assert_eq!(voted, ids.iter().step_by(3).count());
+    assert!(edges.iter().all(|edge| edge["node"]["commentCount"] == 0));
+}
@@ -0,0 +1,226 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::models::{Login, Url, UrlOrdering, User};
+mod setup;
+
+const QUERY_SUBMISSIONS: &str = "
+    query Submissions($after: String) {
+        submissions(first: 2, after: $after) {
+            edges {
+                node { title }
+            }
+            pageInfo {
+                hasNextPage
+                endCursor
+            }
+        }
+    }
+";
+
+const MUTATION_BLOCK: &str = "
+    mutation BlockUser($id: ID!) {
+        blockUser(userId: $id) {
+            ok
+        }
+    }
+";
+
+const MUTATION_UNBLOCK: &str = "
+    mutation UnblockUser($id: ID!) {
+        unblockUser(userId: $id) {
+            ok
+        }
+    }
+";
+
+const MUTATION_COMMENT: &str = "
+    mutation Comment($url: ID!) {
+        comment(input: { comment: \"Nice link\", url: $url }) {
+            id
+        }
+    }
+";
+
+/// Page through all submissions visible with
+/// the given session, returning their titles.
+macro_rules! all_titles {
+    ($server:expr, $session:expr) => {{
+        let mut titles = vec![];
+        let mut after = Value::Null;
+        loop {
+            let res = setup::graphql(QUERY_SUBMISSIONS, json!({ "after": after }), $session)
+                .reply($server)
+                .await;
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+            let submissions = &body["data"]["submissions"];
+            let edges = submissions["edges"].as_array().unwrap();
+            assert!(edges.len() <= 2);
+            for edge in edges {
+                titles.push(edge["node"]["title"].as_str().unwrap().to_string());
+            }
+            if submissions["pageInfo"]["hasNextPage"] != true {
+                break titles;
+            }
+            after = submissions["pageInfo"]["endCursor"].clone();
+        }
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_blocked_users_are_hidden() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    for (i, creator) in [&admin, &user, &admin, &user, &admin, &user]
+        .iter()
+        .enumerate()
+    {
+        setup::Submission::by(creator.id())
+            .title(&format!("{} {}", creator.name(), i))
+            .created_at(ctx.now() + Duration::minutes(i as i64))
+            .insert(&ctx)
+            .await;
+    }
+
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let vars = json!({ "id": admin.id().to_string() });
+    let res = setup::graphql(MUTATION_BLOCK, vars.clone(), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["blockUser"]["ok"], true);
+
+    // pages are filled with visible submissions only
+    assert_eq!(
+        all_titles!(&server, &session),
+        vec!["Test User 5", "Test User 3", "Test User 1"]
+    );
+
+    let mut user_ctx = ctx.clone();
+    Login::use_session(&mut user_ctx, &session).await.unwrap();
+    let (page, page_count) = Url::paginate(&user_ctx, UrlOrdering::Recent, 0, 2)
+        .await
+        .unwrap();
+    assert_eq!(page.len(), 2);
+    assert_eq!(page_count, 2);
+
+    // other users are not affected
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    assert_eq!(all_titles!(&server, &admin_session).len(), 6);
+
+    let query = "
+        query BlockedUsers {
+            viewer {
+                blockedUsers { name }
+            }
+        }
+    ";
+    let res = setup::graphql(query, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["blockedUsers"],
+        json!([{ "name": "Test Administrator" }])
+    );
+
+    let res = setup::graphql(MUTATION_UNBLOCK, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["unblockUser"]["ok"], true);
+    assert_eq!(all_titles!(&server, &session).len(), 6);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_blocked_users_can_not_reply() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let user_url = setup::Submission::by(user.id())
+        .title("Test User")
+        .insert(&ctx)
+        .await;
+    let admin_url = setup::Submission::by(admin.id())
+        .title("Test Administrator")
+        .created_at(ctx.now() + Duration::minutes(1))
+        .insert(&ctx)
+        .await;
+
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    // a comment made before the block
+    let vars = json!({ "url": user_url.to_string() });
+    let res = setup::graphql(MUTATION_COMMENT, vars.clone(), &admin_session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"]["comment"]["id"].is_string());
+
+    let res = setup::graphql(
+        MUTATION_BLOCK,
+        json!({ "id": admin.id().to_string() }),
+        &session,
+    )
+    .reply(&server)
+    .await;
+    assert_eq!(res.status(), 200);
+
+    let res = setup::graphql(MUTATION_COMMENT, vars.clone(), &admin_session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "You can not comment on this submission"
+    );
+
+    // the blocking user can still comment on content of the blocked user
+    let res = setup::graphql(
+        MUTATION_COMMENT,
+        json!({ "url": admin_url.to_string() }),
+        &session,
+    )
+    .reply(&server)
+    .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"]["comment"]["id"].is_string());
+
+    // and no longer sees the comment of the blocked user
+    let query = "
+        query Comments($id: ID!) {
+            fetch__Url(id: $id) {
+                comments {
+                    edges {
+                        node { text }
+                    }
+                }
+            }
+        }
+    ";
+    let vars = json!({ "id": user_url.to_string() });
+    let res = setup::graphql(query, vars.clone(), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["fetch__Url"]["comments"]["edges"], json!([]));
+
+    let res = setup::graphql(query, vars, &admin_session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["fetch__Url"]["comments"]["edges"],
+        json!([{ "node": { "text": "Nice link" } }])
+    );
+}
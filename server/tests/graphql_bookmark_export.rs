@@ -0,0 +1,254 @@
+use serde_json::{json, Value};
+use server::db::models::{import_bookmarks, BookmarkExport, ImportVisibility, User};
+mod setup;
+
+/// A link which needs to be escaped in HTML.
+const SQLITE: &str = "https://example.com/sqlite?a=1&b=2";
+
+const MUTATION_EXPORT: &str = "
+    mutation ExportBookmarks($format: ExportFormat!) {
+        exportBookmarks(format: $format) {
+            ok
+        }
+    }
+";
+
+const MUTATION_SET_TAGS: &str = "
+    mutation SetUrlTags($id: ID!, $tags: [String!]!) {
+        setUrlTags(id: $id, tags: $tags) {
+            tags { name }
+        }
+    }
+";
+
+const MUTATION_SAVE: &str = "
+    mutation SaveUrl($id: ID!) {
+        saveUrl(id: $id) {
+            viewerHasSaved
+        }
+    }
+";
+
+const MUTATION_MARK_READ: &str = "
+    mutation MarkUrlRead($id: ID!) {
+        markUrlRead(id: $id) {
+            viewerHasSaved
+        }
+    }
+";
+
+const QUERY_EXPORTS: &str = "
+    query Exports {
+        viewer {
+            exports {
+                format
+                status
+                completedAt
+                downloadUrl
+            }
+        }
+    }
+";
+
+const QUERY_DRAFTS: &str = "
+    query Drafts {
+        viewer {
+            drafts(first: 10) {
+                edges {
+                    node {
+                        url
+                        tags { name }
+                    }
+                }
+            }
+        }
+    }
+";
+
+/// Request an export in the given format, generate it, and
+/// return the path of the download link.
+macro_rules! export {
+    ($server:expr, $ctx:expr, $session:expr, $format:expr) => {{
+        setup::execute_ok($server, MUTATION_EXPORT, json!({ "format": $format }), $session).await;
+        BookmarkExport::process_pending($ctx).await.unwrap();
+        let data = setup::execute_ok($server, QUERY_EXPORTS, json!({}), $session).await;
+        let export = &data["viewer"]["exports"][0];
+        assert_eq!(export["format"], $format);
+        assert_eq!(export["status"], "READY");
+        let url = export["downloadUrl"].as_str().unwrap();
+        let token = url.split("/bookmark-export/").nth(1).unwrap();
+        format!("/bookmark-export/{}", token)
+    }};
+}
+
+/// Submit and tag links as the test user, and save one link
+/// submitted by someone else, as well as one of the submitted
+/// links, which is marked as read.
+macro_rules! seed {
+    ($server:expr, $ctx:expr, $session:expr) => {{
+        let user = User::find_by_email($ctx, "test.user@urls.fyi").await.unwrap();
+        let admin = User::find_by_email($ctx, "test.admin@urls.fyi").await.unwrap();
+
+        let rust = setup::Submission::by(user.id()).url("https://example.com/rust").score(3).insert($ctx).await;
+        let sqlite = setup::Submission::by(user.id()).url(SQLITE).score(3).insert($ctx).await;
+        let news = setup::Submission::by(admin.id()).url("https://example.com/news").score(3).insert($ctx).await;
+        for (id, tags) in [
+            (rust, json!(["rust", "programming"])),
+            (sqlite, json!(["databases"])),
+        ] {
+            let vars = json!({ "id": id.to_string(), "tags": tags });
+            setup::execute_ok($server, MUTATION_SET_TAGS, vars, $session).await;
+        }
+        for id in [news, sqlite] {
+            let vars = json!({ "id": id.to_string() });
+            setup::execute_ok($server, MUTATION_SAVE, vars, $session).await;
+        }
+        let vars = json!({ "id": sqlite.to_string() });
+        setup::execute_ok($server, MUTATION_MARK_READ, vars, $session).await;
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_export_html_round_trip() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    seed!(&server, &ctx, &session);
+
+    let path = export!(&server, &ctx, &session, "HTML");
+    let res = warp::test::request().path(&path).reply(&server).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["Content-Type"], "text/html; charset=utf-8");
+    let html = String::from_utf8(res.body().to_vec()).unwrap();
+    assert!(html.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+    // only the saved link which was not read yet is marked
+    assert_eq!(html.matches("TOREAD").count(), 1);
+
+    // importing the export into a fresh instance recreates
+    // every link with all of its tags
+    let (other_server, other_ctx) = setup::mock().await;
+    let other_session = setup::session_token(&other_ctx, "test.user@urls.fyi").await;
+    let user = User::find_by_email(&other_ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let report = import_bookmarks(
+        &other_ctx.acting_as(user.id()),
+        html.as_bytes(),
+        ImportVisibility::Draft,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.imported, 3);
+    assert!(report.errors.is_empty());
+
+    let data = setup::execute_ok(&other_server, QUERY_DRAFTS, json!({}), &other_session).await;
+    let mut drafts: Vec<(String, Vec<String>)> = data["viewer"]["drafts"]["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| {
+            let mut tags: Vec<String> = edge["node"]["tags"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|tag| tag["name"].as_str().unwrap().to_string())
+                .collect();
+            tags.sort();
+            (edge["node"]["url"].as_str().unwrap().to_string(), tags)
+        })
+        .collect();
+    drafts.sort();
+    assert_eq!(
+        drafts,
+        vec![
+            ("https://example.com/news".into(), vec![]),
+            (
+                "https://example.com/rust".into(),
+                vec!["programming".into(), "rust".into()]
+            ),
+            (SQLITE.into(), vec!["databases".into()]),
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_export_json_and_csv() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    seed!(&server, &ctx, &session);
+
+    let path = export!(&server, &ctx, &session, "JSON");
+    let res = warp::test::request().path(&path).reply(&server).await;
+    assert_eq!(res.headers()["Content-Type"], "application/json");
+    let export: Value = serde_json::from_slice(res.body()).unwrap();
+    let bookmarks = export["bookmarks"].as_array().unwrap();
+    assert_eq!(bookmarks.len(), 3);
+    let sqlite = bookmarks
+        .iter()
+        .find(|bookmark| bookmark["url"] == SQLITE)
+        .unwrap();
+    assert_eq!(sqlite["tags"], json!(["databases"]));
+    assert_eq!(sqlite["score"], 3);
+    assert!(sqlite["submitted_at"].is_string());
+    assert!(sqlite["saved_at"].is_string());
+    assert!(sqlite["read_at"].is_string());
+    let news = bookmarks
+        .iter()
+        .find(|bookmark| bookmark["url"] == "https://example.com/news")
+        .unwrap();
+    assert_eq!(news["submitted_at"], Value::Null);
+    assert_eq!(news["read_at"], Value::Null);
+
+    let path = export!(&server, &ctx, &session, "CSV");
+    let res = warp::test::request().path(&path).reply(&server).await;
+    assert_eq!(res.headers()["Content-Type"], "text/csv; charset=utf-8");
+    let csv = String::from_utf8(res.body().to_vec()).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some("url,title,tags,score,submitted_at,saved_at,read_at")
+    );
+    assert!(csv.contains("https://example.com/rust,,programming rust,3,"));
+    assert_eq!(lines.count(), 3);
+
+    // tampered links are rejected
+    let res = warp::test::request()
+        .path(&format!("{}x", path))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 400);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_export_one_at_a_time() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "format": "CSV" });
+    setup::execute_ok(&server, MUTATION_EXPORT, vars.clone(), &session).await;
+    let body = setup::execute(&server, MUTATION_EXPORT, vars.clone(), &session).await;
+    assert!(body["data"].is_null());
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("already in progress"));
+
+    // pending exports can not be downloaded yet
+    let data = setup::execute_ok(&server, QUERY_EXPORTS, json!({}), &session).await;
+    assert_eq!(
+        data["viewer"]["exports"],
+        json!([{
+            "format": "CSV",
+            "status": "PENDING",
+            "completedAt": null,
+            "downloadUrl": null,
+        }])
+    );
+
+    // once completed, a new export may be requested
+    BookmarkExport::process_pending(&ctx).await.unwrap();
+    setup::execute_ok(&server, MUTATION_EXPORT, vars.clone(), &session).await;
+
+    // exporting requires being logged in
+    let body = setup::execute(&server, MUTATION_EXPORT, vars, "").await;
+    assert!(body["data"].is_null());
+}
@@ -0,0 +1,110 @@
+use serde_json::{json, Value};
+mod setup;
+
+const QUERY_CHECK: &str = "
+    query CheckUrl($url: String!) {
+        checkUrl(url: $url) {
+            exists
+            url { id }
+            submittedAt
+        }
+    }
+";
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id createdAt }
+            duplicate
+        }
+    }
+";
+
+const MUTATION_DELETE: &str = "
+    mutation DeleteUrl($id: ID!) {
+        deleteUrl(id: $id) { ok }
+    }
+";
+
+/// Check the given URL anonymously, returning the
+/// GraphQL response.
+macro_rules! check {
+    ($server:expr, $url:expr) => {{
+        let vars = json!({ "url": $url });
+        setup::execute($server, QUERY_CHECK, vars, "").await
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_check_url_agrees_with_submit() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let page = setup::serve_page();
+
+    let vars = json!({ "input": { "url": page } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let existing = body["data"]["submitUrl"]["url"].clone();
+    let deleted = format!("{}/deleted", page);
+    let vars = json!({ "input": { "url": deleted } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let vars = json!({ "id": body["data"]["submitUrl"]["url"]["id"] });
+    setup::graphql(MUTATION_DELETE, vars, &session)
+        .reply(&server)
+        .await;
+
+    let inputs = vec![
+        page.clone(),
+        format!("{}?utm_source=feed", page),
+        format!("{}/#comments", page.replace("http://", "HTTP://")),
+        format!("  {}  ", page),
+        deleted,
+        format!("{}/new", page),
+        "not a url".to_string(),
+        "ftp://example.com/file".to_string(),
+        String::new(),
+    ];
+    for input in inputs {
+        let check = check!(&server, &input);
+        let vars = json!({ "input": { "url": input } });
+        let submit = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+        if submit["errors"].is_array() {
+            assert!(check["data"].is_null(), "{}", input);
+            assert_eq!(
+                check["errors"][0]["message"], submit["errors"][0]["message"],
+                "{}",
+                input
+            );
+            continue;
+        }
+        let check = &check["data"]["checkUrl"];
+        let submit = &submit["data"]["submitUrl"];
+        assert_eq!(check["exists"], submit["duplicate"], "{}", input);
+        if submit["duplicate"] == true {
+            assert_eq!(check["url"]["id"], submit["url"]["id"], "{}", input);
+            assert_eq!(
+                check["submittedAt"], submit["url"]["createdAt"],
+                "{}",
+                input
+            );
+        } else {
+            assert_eq!(check["url"], Value::Null, "{}", input);
+            assert_eq!(check["submittedAt"], Value::Null, "{}", input);
+        }
+    }
+
+    let body = check!(&server, &page);
+    assert_eq!(body["data"]["checkUrl"]["url"]["id"], existing["id"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_check_url_rate_limit() {
+    let (server, _ctx) = setup::mock().await;
+    for _ in 0..30 {
+        let body = check!(&server, "https://example.com/");
+        assert_eq!(body["data"]["checkUrl"]["exists"], false);
+    }
+    let body = check!(&server, "https://example.com/");
+    assert!(body["data"].is_null());
+    assert_eq!(body["errors"][0]["extensions"]["code"], "RATE_LIMITED");
+    assert_eq!(body["errors"][0]["extensions"]["scope"], "check_url");
+}
@@ -0,0 +1,224 @@
+use serde_json::{json, Value};
+use server::db::models::User;
+mod setup;
+
+const MUTATION_CREATE: &str = "
+    mutation CreateCollection($title: String!, $visibility: CollectionVisibility!) {
+        createCollection(input: { title: $title, visibility: $visibility }) { id }
+    }
+";
+
+const MUTATION_ADD: &str = "
+    mutation AddToCollection($collectionId: ID!, $urlId: ID!, $note: String) {
+        addToCollection(collectionId: $collectionId, urlId: $urlId, note: $note) { itemCount }
+    }
+";
+
+const MUTATION_MOVE: &str = "
+    mutation MoveCollectionItem($collectionId: ID!, $urlId: ID!, $position: Int!) {
+        moveCollectionItem(collectionId: $collectionId, urlId: $urlId, position: $position) {
+            itemCount
+        }
+    }
+";
+
+const MUTATION_REMOVE: &str = "
+    mutation RemoveFromCollection($collectionId: ID!, $urlId: ID!) {
+        removeFromCollection(collectionId: $collectionId, urlId: $urlId) { itemCount }
+    }
+";
+
+const QUERY_COLLECTION: &str = "
+    query Collection($id: ID!, $after: String) {
+        collection(id: $id) {
+            title
+            items(first: 2, after: $after) {
+                edges {
+                    node { position note url { title } }
+                    cursor
+                }
+                pageInfo { hasNextPage }
+            }
+        }
+    }
+";
+
+const QUERY_LISTS: &str = "
+    query Collections {
+        viewer {
+            collections { edges { node { title } } }
+        }
+        user(username: \"test-user\") {
+            collections { edges { node { title } } }
+        }
+    }
+";
+
+/// Create a collection, returning its ID.
+macro_rules! create {
+    ($server:expr, $session:expr, $title:expr, $visibility:expr) => {{
+        let vars = json!({ "title": $title, "visibility": $visibility });
+        let body = setup::execute($server, MUTATION_CREATE, vars, $session).await;
+        assert!(body["errors"].is_null(), "{}", body);
+        body["data"]["createCollection"]["id"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }};
+}
+
+/// The titles of the items of a collection, following the
+/// connection from the start in pages of two.
+macro_rules! titles {
+    ($server:expr, $session:expr, $id:expr) => {{
+        let mut titles = vec![];
+        let mut after = Value::Null;
+        loop {
+            let vars = json!({ "id": $id, "after": after });
+            let body = setup::execute($server, QUERY_COLLECTION, vars, $session).await;
+            assert!(body["errors"].is_null(), "{}", body);
+            let items = &body["data"]["collection"]["items"];
+            for edge in items["edges"].as_array().unwrap() {
+                assert_eq!(edge["node"]["position"], titles.len());
+                titles.push(edge["node"]["url"]["title"].as_str().unwrap().to_string());
+                after = edge["cursor"].clone();
+            }
+            if items["pageInfo"]["hasNextPage"] != true {
+                break titles;
+            }
+        }
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_collection_visibility() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let other = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let public = create!(&server, &session, "Public", "PUBLIC");
+    let unlisted = create!(&server, &session, "Unlisted", "UNLISTED");
+    let private = create!(&server, &session, "Private", "PRIVATE");
+
+    // the owner lists all collections, the profile only public ones
+    for session in [session.as_str(), other.as_str(), ""] {
+        let body = setup::execute(&server, QUERY_LISTS, json!({}), session).await;
+        assert!(body["errors"].is_null(), "{}", body);
+        assert_eq!(
+            body["data"]["user"]["collections"]["edges"],
+            json!([{ "node": { "title": "Public" } }])
+        );
+    }
+    let body = setup::execute(&server, QUERY_LISTS, json!({}), &session).await;
+    assert_eq!(
+        body["data"]["viewer"]["collections"]["edges"],
+        json!([
+            { "node": { "title": "Private" } },
+            { "node": { "title": "Unlisted" } },
+            { "node": { "title": "Public" } },
+        ])
+    );
+
+    // unlisted collections can be viewed by anyone with their ID,
+    // private collections only by their owner
+    for (id, title, visible) in [
+        (&public, "Public", true),
+        (&unlisted, "Unlisted", true),
+        (&private, "Private", false),
+    ] {
+        for session in [other.as_str(), ""] {
+            let body =
+                setup::execute(&server, QUERY_COLLECTION, json!({ "id": id }), session).await;
+            if visible {
+                assert_eq!(body["data"]["collection"]["title"], title);
+            } else {
+                assert!(body["data"].is_null(), "{}", body);
+            }
+        }
+        let body = setup::execute(&server, QUERY_COLLECTION, json!({ "id": id }), &session).await;
+        assert_eq!(body["data"]["collection"]["title"], title);
+    }
+
+    // only the owner can change a collection
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let url_id = setup::Submission::by(admin.id())
+        .title("Collected")
+        .insert(&ctx)
+        .await;
+    for id in [&public, &private] {
+        let vars = json!({ "collectionId": id, "urlId": url_id.to_string() });
+        let body = setup::execute(&server, MUTATION_ADD, vars, &other).await;
+        assert!(body["data"].is_null(), "{}", body);
+    }
+    let vars = json!({ "collectionId": &public, "urlId": url_id.to_string() });
+    let body = setup::execute(&server, MUTATION_ADD, vars, &session).await;
+    assert_eq!(body["data"]["addToCollection"]["itemCount"], 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_collection_manual_order() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let id = create!(&server, &session, "Reading", "PUBLIC");
+
+    let mut url_ids = vec![];
+    for title in ["A", "B", "C", "D", "E"] {
+        let url_id = setup::Submission::by(user.id())
+            .title(title)
+            .insert(&ctx)
+            .await;
+        let vars = json!({ "collectionId": &id, "urlId": url_id.to_string() });
+        let body = setup::execute(&server, MUTATION_ADD, vars, &session).await;
+        assert!(body["errors"].is_null(), "{}", body);
+        url_ids.push(url_id);
+    }
+    assert_eq!(titles!(&server, "", &id), vec!["A", "B", "C", "D", "E"]);
+
+    // adding a submission again keeps its position
+    let vars =
+        json!({ "collectionId": &id, "urlId": url_ids[1].to_string(), "note": "Start here" });
+    let body = setup::execute(&server, MUTATION_ADD, vars, &session).await;
+    assert_eq!(body["data"]["addToCollection"]["itemCount"], 5);
+    let body = setup::execute(&server, QUERY_COLLECTION, json!({ "id": &id }), "").await;
+    assert_eq!(
+        body["data"]["collection"]["items"]["edges"][1]["node"]["note"],
+        "Start here"
+    );
+
+    let moves = [
+        (4, 0, vec!["E", "A", "B", "C", "D"]),
+        (4, 2, vec!["A", "B", "E", "C", "D"]),
+        (4, 99, vec!["A", "B", "C", "D", "E"]),
+        (2, 2, vec!["A", "B", "C", "D", "E"]),
+    ];
+    for (url, position, expected) in moves {
+        let vars = json!({
+            "collectionId": &id,
+            "urlId": url_ids[url].to_string(),
+            "position": position,
+        });
+        let body = setup::execute(&server, MUTATION_MOVE, vars, &session).await;
+        assert!(body["errors"].is_null(), "{}", body);
+        assert_eq!(titles!(&server, "", &id), expected);
+    }
+
+    // removing an item closes the gap
+    let vars = json!({ "collectionId": &id, "urlId": url_ids[1].to_string() });
+    let body = setup::execute(&server, MUTATION_REMOVE, vars, &session).await;
+    assert_eq!(body["data"]["removeFromCollection"]["itemCount"], 4);
+    assert_eq!(titles!(&server, "", &id), vec!["A", "C", "D", "E"]);
+
+    // submissions not in the collection can not be moved
+    let vars = json!({
+        "collectionId": &id,
+        "urlId": url_ids[1].to_string(),
+        "position": 0,
+    });
+    let body = setup::execute(&server, MUTATION_MOVE, vars, &session).await;
+    assert!(body["data"].is_null(), "{}", body);
+}
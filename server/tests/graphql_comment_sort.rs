@@ -0,0 +1,226 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::id::CommentID;
+use server::db::models::User;
+use server::schema::comments;
+use server::Context;
+mod setup;
+
+const QUERY_COMMENTS: &str = "
+    query Comments($url: ID!, $after: String, $sort: CommentSort, $repliesTo: ID) {
+        fetch__Url(id: $url) {
+            comments(first: 10, after: $after, sort: $sort, repliesTo: $repliesTo) {
+                edges {
+                    node { text }
+                }
+                pageInfo {
+                    hasNextPage
+                    endCursor
+                }
+            }
+        }
+    }
+";
+
+const QUERY_REPLIES: &str = "
+    query Replies($url: ID!) {
+        fetch__Url(id: $url) {
+            comments(first: 1) {
+                edges {
+                    node {
+                        replies(first: 10) {
+                            edges {
+                                node { text }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+";
+
+/// Set the score of the given comment.
+async fn set_score(ctx: &Context, id: CommentID, score: i64) {
+    diesel::update(comments::table.find(id))
+        .set(comments::dsl::score.eq(score))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+}
+
+/// Fetch a page of comments on a submission, returning the
+/// GraphQL response.
+macro_rules! comments {
+    ($server:expr, $vars:expr) => {{
+        setup::execute($server, QUERY_COMMENTS, $vars, "").await
+    }};
+}
+
+/// The texts and the end cursor of a page of comments, where
+/// the cursor is null on the last page.
+macro_rules! page {
+    ($server:expr, $vars:expr) => {{
+        let body = comments!($server, $vars);
+        assert!(body["errors"].is_null(), "{}", body);
+        let comments = &body["data"]["fetch__Url"]["comments"];
+        let texts: Vec<String> = comments["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["node"]["text"].as_str().unwrap().to_string())
+            .collect();
+        let next = if comments["pageInfo"]["hasNextPage"] == true {
+            comments["pageInfo"]["endCursor"].clone()
+        } else {
+            Value::Null
+        };
+        (texts, next)
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_top_pagination_while_voting() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url_id = setup::submit(&ctx, user.id()).await;
+
+    let mut ids = vec![];
+    let mut expected = vec![];
+    for index in 0..50 {
+        let text = format!("Comment {}", index);
+        let score = index % 5;
+        let id = setup::Comment::on(url_id, user.id())
+            .text(&text)
+            .score(score)
+            .created_at(ctx.now() - Duration::minutes(50 - index))
+            .insert(&ctx)
+            .await;
+        ids.push((text.clone(), id));
+        expected.push((score, index, text));
+    }
+    expected.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    let expected: Vec<String> = expected.into_iter().map(|(_, _, text)| text).collect();
+
+    // votes arrive between pages, both on the comment the cursor
+    // points to and on comments which were already listed
+    let mut texts: Vec<String> = vec![];
+    let mut after = Value::Null;
+    let mut page = 0;
+    loop {
+        let vars = json!({ "url": url_id.to_string(), "after": after, "sort": "TOP" });
+        let (more, next) = page!(&server, vars);
+        texts.extend(more);
+        if next.is_null() {
+            break;
+        }
+        after = next;
+
+        let id_of = |text: &String| ids.iter().find(|(t, _)| t == text).unwrap().1;
+        let last = id_of(texts.last().unwrap());
+        set_score(&ctx, last, if page % 2 == 0 { 100 } else { 0 }).await;
+        set_score(&ctx, id_of(&texts[0]), 200 + page).await;
+        page += 1;
+    }
+    assert_eq!(texts, expected);
+
+    // the next listing reflects the votes
+    let vars = json!({ "url": url_id.to_string(), "sort": "TOP" });
+    let (first, _) = page!(&server, vars);
+    let top = [&expected[0], &expected[29], &expected[9]];
+    assert_eq!(first[..3].iter().collect::<Vec<_>>(), top);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_replies_stay_chronological() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url_id = setup::submit(&ctx, user.id()).await;
+
+    let root = setup::Comment::on(url_id, user.id())
+        .text("Root")
+        .created_at(ctx.now() - Duration::minutes(10))
+        .insert(&ctx)
+        .await;
+    setup::Comment::on(url_id, user.id())
+        .text("Popular")
+        .score(5)
+        .created_at(ctx.now() - Duration::minutes(9))
+        .insert(&ctx)
+        .await;
+    for (text, score, minutes) in [("First", 0, 8), ("Second", 3, 7), ("Third", 1, 6)] {
+        setup::Comment::on(url_id, user.id())
+            .replies_to(root)
+            .text(text)
+            .score(score)
+            .created_at(ctx.now() - Duration::minutes(minutes))
+            .insert(&ctx)
+            .await;
+    }
+
+    let vars = json!({ "url": url_id.to_string() });
+    let (roots, _) = page!(&server, vars);
+    assert_eq!(roots, vec!["Popular", "Root"]);
+
+    let vars = json!({
+        "url": url_id.to_string(),
+        "sort": "TOP",
+        "repliesTo": root.to_string(),
+    });
+    let (replies, _) = page!(&server, vars);
+    assert_eq!(replies, vec!["First", "Second", "Third"]);
+
+    let vars = json!({
+        "url": url_id.to_string(),
+        "sort": "NEW",
+        "repliesTo": root.to_string(),
+    });
+    let (replies, _) = page!(&server, vars);
+    assert_eq!(replies, vec!["Third", "Second", "First"]);
+
+    set_score(&ctx, root, 10).await;
+    let vars = json!({ "url": url_id.to_string() });
+    let body = setup::execute(&server, QUERY_REPLIES, vars, "").await;
+    assert_eq!(
+        body["data"]["fetch__Url"]["comments"]["edges"][0]["node"]["replies"]["edges"],
+        json!([
+            { "node": { "text": "First" } },
+            { "node": { "text": "Second" } },
+            { "node": { "text": "Third" } },
+        ])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cursor_rejected_across_sorts() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url_id = setup::submit(&ctx, user.id()).await;
+    for index in 0..12 {
+        let text = format!("Comment {}", index);
+        setup::Comment::on(url_id, user.id())
+            .text(&text)
+            .score(index)
+            .created_at(ctx.now() - Duration::minutes(12 - index))
+            .insert(&ctx)
+            .await;
+    }
+
+    let vars = json!({ "url": url_id.to_string(), "sort": "TOP" });
+    let (_, cursor) = page!(&server, vars);
+    let vars = json!({ "url": url_id.to_string(), "after": &cursor, "sort": "NEW" });
+    let body = comments!(&server, vars);
+    assert!(body["data"].is_null(), "{}", body);
+
+    // the cursor still works with the order it was issued for
+    let vars = json!({ "url": url_id.to_string(), "after": &cursor, "sort": "TOP" });
+    let (texts, next) = page!(&server, vars);
+    assert_eq!(texts, vec!["Comment 1", "Comment 0"]);
+    assert!(next.is_null());
+}
@@ -0,0 +1,197 @@
+use serde_json::{json, Value};
+use server::db::models::User;
+mod setup;
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!) {
+        addComment(urlId: $url, body: $body) { id }
+    }
+";
+
+const MUTATION_DELETE_COMMENT: &str = "
+    mutation DeleteComment($id: ID!) {
+        deleteComment(comment: $id) { id }
+    }
+";
+
+const MUTATION_VOTE: &str = "
+    mutation VoteComment($id: ID!) {
+        voteComment(id: $id) { score viewerHasVoted }
+    }
+";
+
+const MUTATION_UNVOTE: &str = "
+    mutation UnvoteComment($id: ID!) {
+        unvoteComment(id: $id) { score viewerHasVoted }
+    }
+";
+
+const QUERY_COMMENTS: &str = "
+    query Comments($url: ID!, $first: Int!, $after: String, $sort: CommentSort) {
+        fetch__Url(id: $url) {
+            comments(first: $first, after: $after, sort: $sort) {
+                edges {
+                    node { text score viewerHasVoted }
+                    cursor
+                }
+                pageInfo { hasNextPage }
+            }
+        }
+    }
+";
+
+/// Comment on the given submission, returning the comment ID.
+macro_rules! comment {
+    ($server:expr, $session:expr, $url:expr, $text:expr) => {{
+        let vars = json!({ "url": $url.to_string(), "body": $text });
+        let body = setup::execute($server, MUTATION_ADD_COMMENT, vars, $session).await;
+        assert!(body["errors"].is_null(), "{}", body);
+        body["data"]["addComment"]["id"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }};
+}
+
+/// The texts of the comments on a submission in the given order,
+/// following the connection from the start in pages of one.
+macro_rules! texts {
+    ($server:expr, $url:expr, $sort:expr) => {{
+        let mut texts = vec![];
+        let mut after = Value::Null;
+        loop {
+            let vars = json!({
+                "url": $url.to_string(),
+                "first": 1,
+                "after": after,
+                "sort": $sort,
+            });
+            let body = setup::execute($server, QUERY_COMMENTS, vars, "").await;
+            assert!(body["errors"].is_null(), "{}", body);
+            let comments = &body["data"]["fetch__Url"]["comments"];
+            for edge in comments["edges"].as_array().unwrap() {
+                texts.push(edge["node"]["text"].as_str().unwrap().to_string());
+                after = edge["cursor"].clone();
+            }
+            if comments["pageInfo"]["hasNextPage"] != true {
+                break texts;
+            }
+        }
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_comment_votes() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url_id = setup::submit(&ctx, user.id()).await;
+    let id = comment!(&server, &session, url_id, "Voted");
+
+    // voting again has no further effect
+    for _ in 0..2 {
+        let body = setup::execute(&server, MUTATION_VOTE, json!({ "id": &id }), &admin).await;
+        assert_eq!(
+            body["data"]["voteComment"],
+            json!({ "score": 1, "viewerHasVoted": true })
+        );
+    }
+    let body = setup::execute(&server, MUTATION_VOTE, json!({ "id": &id }), &session).await;
+    assert_eq!(
+        body["data"]["voteComment"],
+        json!({ "score": 2, "viewerHasVoted": true })
+    );
+
+    // and neither does rescinding a missing vote
+    for _ in 0..2 {
+        let body = setup::execute(&server, MUTATION_UNVOTE, json!({ "id": &id }), &admin).await;
+        assert_eq!(
+            body["data"]["unvoteComment"],
+            json!({ "score": 1, "viewerHasVoted": false })
+        );
+    }
+
+    // logged out viewers can not vote
+    let body = setup::execute(&server, MUTATION_VOTE, json!({ "id": &id }), "").await;
+    assert!(body["data"].is_null(), "{}", body);
+
+    // deleted comments can not be voted on
+    let body = setup::execute(
+        &server,
+        MUTATION_DELETE_COMMENT,
+        json!({ "id": &id }),
+        &session,
+    )
+    .await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let body = setup::execute(&server, MUTATION_VOTE, json!({ "id": &id }), &admin).await;
+    assert!(body["data"].is_null(), "{}", body);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_comment_sort() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url_id = setup::submit(&ctx, user.id()).await;
+
+    let mut ids = vec![];
+    for text in ["A", "B", "C", "D"] {
+        ids.push(comment!(&server, &session, url_id, text));
+    }
+    let votes = [(&admin, &ids[1]), (&admin, &ids[3]), (&session, &ids[3])];
+    for (session, id) in votes {
+        let body = setup::execute(&server, MUTATION_VOTE, json!({ "id": id }), session).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+
+    assert_eq!(texts!(&server, url_id, "OLD"), vec!["A", "B", "C", "D"]);
+    assert_eq!(texts!(&server, url_id, "NEW"), vec!["D", "C", "B", "A"]);
+    assert_eq!(texts!(&server, url_id, "TOP"), vec!["D", "B", "A", "C"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_viewer_votes_are_batched() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url_id = setup::submit(&ctx, user.id()).await;
+
+    for index in 0..10 {
+        let id = comment!(&server, &session, url_id, format!("Comment {}", index));
+        if index % 2 == 0 {
+            let body = setup::execute(&server, MUTATION_VOTE, json!({ "id": id }), &admin).await;
+            assert!(body["errors"].is_null(), "{}", body);
+        }
+    }
+
+    // resolving a page of ten comments takes as many
+    // queries as resolving a page of two
+    let mut counts = vec![];
+    for first in [2, 10] {
+        let vars = json!({ "url": url_id.to_string(), "first": first });
+        let before = ctx.connection_count();
+        let body = setup::execute(&server, QUERY_COMMENTS, vars, &admin).await;
+        counts.push(ctx.connection_count() - before);
+
+        assert!(body["errors"].is_null(), "{}", body);
+        let voted: Vec<Value> = body["data"]["fetch__Url"]["comments"]["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["node"]["viewerHasVoted"].clone())
+            .collect();
+        let expected: Vec<Value> = (0..first).map(|index| json!(index % 2 == 0)).collect();
+        assert_eq!(voted, expected);
+    }
+    assert_eq!(counts[0], counts[1], "{:?}", counts);
+}
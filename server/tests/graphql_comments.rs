@@ -0,0 +1,324 @@
+use diesel::{ExpressionMethods, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::id::CommentID;
+use server::db::models::{NewUserInput, User};
+use server::schema::comments;
+use server::Config;
+mod setup;
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!, $parent: ID) {
+        addComment(urlId: $url, body: $body, parentId: $parent) {
+            id
+            text
+            depth
+            createdBy { name }
+        }
+    }
+";
+
+const MUTATION_DELETE_COMMENT: &str = "
+    mutation DeleteComment($id: ID!) {
+        deleteComment(comment: $id) {
+            id
+        }
+    }
+";
+
+const QUERY_REPLIES: &str = "
+    query Replies($url: ID!, $after: String) {
+        fetch__Url(id: $url) {
+            comments(first: 10) {
+                edges {
+                    node {
+                        text
+                        replyCount
+                        replies(first: 2, after: $after) {
+                            edges {
+                                node { text depth }
+                            }
+                            pageInfo {
+                                hasNextPage
+                                endCursor
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+";
+
+const QUERY_COMMENTS: &str = "
+    query Comments($url: ID!, $after: String) {
+        fetch__Url(id: $url) {
+            commentCount
+            comments(first: 2, after: $after) {
+                edges {
+                    node { text }
+                }
+                pageInfo {
+                    hasNextPage
+                    endCursor
+                }
+            }
+        }
+    }
+";
+
+/// Comment on the given submission, returning the
+/// GraphQL response.
+macro_rules! add_comment {
+    ($server:expr, $session:expr, $url:expr, $body:expr) => {
+        add_comment!($server, $session, $url, $body, Value::Null)
+    };
+    ($server:expr, $session:expr, $url:expr, $body:expr, $parent:expr) => {{
+        let vars = json!({ "url": $url.to_string(), "body": $body, "parent": $parent });
+        let res = setup::graphql(MUTATION_ADD_COMMENT, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Fetch a page of comments on the given submission.
+macro_rules! comments {
+    ($server:expr, $url:expr, $after:expr) => {{
+        let vars = json!({ "url": $url.to_string(), "after": $after });
+        let res = setup::graphql(QUERY_COMMENTS, vars, "")
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body["data"]["fetch__Url"].clone()
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_add_comment() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let url = setup::submit(&ctx, user.id()).await;
+
+    let body = add_comment!(&server, &session, url, "  Nice link  ");
+    let comment = &body["data"]["addComment"];
+    assert_eq!(comment["text"], "Nice link");
+    assert_eq!(comment["createdBy"]["name"], user.name());
+
+    // the counter is maintained
+    let body = add_comment!(&server, &session, url, "Another one");
+    let id = body["data"]["addComment"]["id"].clone();
+    assert_eq!(comments!(&server, url, Value::Null)["commentCount"], 2);
+
+    let vars = json!({ "id": id });
+    let res = setup::graphql(MUTATION_DELETE_COMMENT, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["errors"].is_null());
+    assert_eq!(comments!(&server, url, Value::Null)["commentCount"], 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_add_comment_validation() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let url = setup::submit(&ctx, user.id()).await;
+
+    let long = "a".repeat(5001);
+    for text in ["", "   ", long.as_str()] {
+        let body = add_comment!(&server, &session, url, text);
+        assert!(body["data"].is_null());
+        assert!(body["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Comments must have between 1 and 5000 characters"));
+    }
+    let body = add_comment!(&server, &session, url, "a".repeat(5000));
+    assert!(body["data"]["addComment"]["id"].is_string());
+
+    // anonymous and unverified users can not comment
+    let body = add_comment!(&server, "", url, "Hello");
+    assert!(body["data"].is_null());
+
+    let input = NewUserInput {
+        name: "Unverified".into(),
+        email: "test.unverified@urls.fyi".into(),
+    };
+    User::create(&ctx, input).await.unwrap();
+    let session = setup::session_token(&ctx, "test.unverified@urls.fyi").await;
+    let body = add_comment!(&server, &session, url, "Hello");
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Please verify your email address first"
+    );
+
+    assert_eq!(comments!(&server, url, Value::Null)["commentCount"], 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_comments_pagination() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url = setup::submit(&ctx, user.id()).await;
+
+    // comments created at the same time are ordered by id
+    let mut ids: Vec<CommentID> = (0..5).map(|_| CommentID::new()).collect();
+    ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    for (i, id) in ids.iter().enumerate() {
+        diesel::insert_into(comments::table)
+            .values((
+                comments::dsl::id.eq(*id),
+                comments::dsl::created_at.eq(ctx.now().naive_utc()),
+                comments::dsl::updated_at.eq(ctx.now().naive_utc()),
+                comments::dsl::comment.eq(format!("Comment {}", i)),
+                comments::dsl::url_id.eq(url),
+                comments::dsl::created_by.eq(user.id()),
+            ))
+            .execute(&*ctx.conn().await.unwrap())
+            .unwrap();
+    }
+
+    let mut texts = vec![];
+    let mut after = Value::Null;
+    loop {
+        let url = comments!(&server, url, after);
+        let comments = &url["comments"];
+        texts.extend(
+            comments["edges"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|edge| edge["node"]["text"].as_str().unwrap().to_string()),
+        );
+        if !comments["pageInfo"]["hasNextPage"].as_bool().unwrap() {
+            break;
+        }
+        after = comments["pageInfo"]["endCursor"].clone();
+    }
+    let expected: Vec<String> = (0..5).map(|i| format!("Comment {}", i)).collect();
+    assert_eq!(texts, expected);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_reply_validation() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let url = setup::submit(&ctx, user.id()).await;
+    let other_url = setup::submit(&ctx, user.id()).await;
+
+    let body = add_comment!(&server, &session, url, "Root");
+    let root = body["data"]["addComment"]["id"].clone();
+
+    // replies must be on the same submission
+    let body = add_comment!(&server, &session, other_url, "Reply", root.clone());
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "The parent comment belongs to another submission"
+    );
+
+    // deleted comments can not be replied to
+    let body = add_comment!(&server, &session, url, "Reply", root.clone());
+    assert_eq!(body["data"]["addComment"]["depth"], 1);
+    let vars = json!({ "id": root.clone() });
+    setup::graphql(MUTATION_DELETE_COMMENT, vars, &session)
+        .reply(&server)
+        .await;
+    let body = add_comment!(&server, &session, url, "Another reply", root);
+    assert_eq!(
+        body["errors"][0]["message"],
+        "You can not reply to a deleted comment"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_reply_depth_limit() {
+    let (server, ctx) = setup::mock_with_config(Config::test().with_max_comment_depth(2)).await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let url = setup::submit(&ctx, user.id()).await;
+
+    let mut parent = Value::Null;
+    for depth in 0..=2 {
+        let body = add_comment!(&server, &session, url, "Nested", parent);
+        assert_eq!(body["data"]["addComment"]["depth"], depth);
+        parent = body["data"]["addComment"]["id"].clone();
+    }
+
+    let body = add_comment!(&server, &session, url, "Too deep", parent);
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Replies can not be nested more than 2 levels deep"
+    );
+    assert_eq!(comments!(&server, url, Value::Null)["commentCount"], 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_replies_pagination() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let url = setup::submit(&ctx, user.id()).await;
+
+    let body = add_comment!(&server, &session, url, "Root");
+    let root = body["data"]["addComment"]["id"].clone();
+    for i in 0..3 {
+        let body = add_comment!(&server, &session, url, format!("Reply {}", i), root.clone());
+        assert!(body["errors"].is_null());
+    }
+
+    // only the root is listed on the submission
+    let fetch = |after: Value| {
+        setup::graphql(
+            QUERY_REPLIES,
+            json!({ "url": url.to_string(), "after": after }),
+            "",
+        )
+        .reply(&server)
+    };
+    let body: Value = serde_json::from_slice(fetch(Value::Null).await.body()).unwrap();
+    let edges = body["data"]["fetch__Url"]["comments"]["edges"]
+        .as_array()
+        .unwrap()
+        .clone();
+    assert_eq!(edges.len(), 1);
+    let root = &edges[0]["node"];
+    assert_eq!(root["text"], "Root");
+    assert_eq!(root["replyCount"], 3);
+
+    let replies = &root["replies"];
+    assert_eq!(
+        replies["edges"],
+        json!([
+            { "node": { "text": "Reply 0", "depth": 1 } },
+            { "node": { "text": "Reply 1", "depth": 1 } },
+        ])
+    );
+    assert_eq!(replies["pageInfo"]["hasNextPage"], true);
+
+    let after = replies["pageInfo"]["endCursor"].clone();
+    let body: Value = serde_json::from_slice(fetch(after).await.body()).unwrap();
+    let replies = &body["data"]["fetch__Url"]["comments"]["edges"][0]["node"]["replies"];
+    assert_eq!(
+        replies["edges"],
+        json!([{ "node": { "text": "Reply 2", "depth": 1 } }])
+    );
+    assert_eq!(replies["pageInfo"]["hasNextPage"], false);
+}
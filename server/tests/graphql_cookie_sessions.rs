@@ -0,0 +1,249 @@
+use serde_json::{json, Value};
+use warp::test::RequestBuilder;
+mod setup;
+
+const QUERY_VIEWER: &str = "
+    query Viewer {
+        viewer {
+            user { name }
+        }
+    }
+";
+
+const QUERY_CSRF_TOKEN: &str = "
+    query CsrfToken {
+        csrfToken
+    }
+";
+
+const MUTATION_ISSUE_INVITE: &str = "
+    mutation IssueInvite {
+        issueInvite {
+            id
+        }
+    }
+";
+
+/// Constructs a GraphQL request without
+/// any cookies or tokens attached.
+fn request(query: &str, variables: Value) -> RequestBuilder {
+    let body = json!({ "query": query, "variables": variables });
+    warp::test::request()
+        .path("/graphql")
+        .method("POST")
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+}
+
+/// Return the value of the cookie with the
+/// given name set by a response.
+fn cookie<'a>(res: &'a warp::http::Response<warp::hyper::body::Bytes>, name: &str) -> &'a str {
+    res.headers()
+        .get_all("Set-Cookie")
+        .iter()
+        .map(|value| value.to_str().unwrap())
+        .find(|value| value.starts_with(&format!("{}=", name)))
+        .expect("Missing cookie")
+}
+
+/// Request a login code for the mock user,
+/// and return it.
+macro_rules! login_code {
+    ($server:expr, $ctx:expr) => {{
+        let query = "
+            mutation RequestLogin($email: String!) {
+                requestLogin(email: $email) {
+                    ok
+                }
+            }
+        ";
+        let vars = json!({ "email": "test.user@urls.fyi" });
+        let res = setup::graphql(query, vars, "").reply($server).await;
+        assert_eq!(res.status(), 200);
+
+        let email = setup::last_email($ctx).await;
+        email
+            .split_whitespace()
+            .find(|maybe_token| maybe_token.len() == 12)
+            .expect("Email should contain a 12 character login token")
+            .to_string()
+    }};
+}
+
+const MUTATION_LOGIN: &str = "
+    mutation Login($email: String!, $token: String!, $setCookie: Boolean) {
+        login(email: $email, token: $token, setCookie: $setCookie)
+    }
+";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_login_with_cookie() {
+    let (server, ctx) = setup::mock().await;
+
+    // setting a cookie requires a CSRF token
+    let token = login_code!(&server, &ctx);
+    let vars = json!({ "email": "test.user@urls.fyi", "token": token, "setCookie": true });
+    let res = request(MUTATION_LOGIN, vars).reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Missing or invalid CSRF token"
+    );
+
+    let vars = json!({ "email": "test.user@urls.fyi", "token": token, "setCookie": true });
+    let res = setup::graphql(MUTATION_LOGIN, vars, "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, json!({ "data": { "login": "" } }));
+
+    let session_cookie = cookie(&res, "session");
+    assert!(session_cookie.contains("HttpOnly"));
+    assert!(session_cookie.contains("Secure"));
+    assert!(session_cookie.contains("SameSite=Lax"));
+    let session = session_cookie
+        .split(';')
+        .next()
+        .unwrap()
+        .trim_start_matches("session=");
+    assert!(!session.is_empty());
+
+    // the cookie authenticates subsequent requests
+    let res = request(QUERY_VIEWER, json!({}))
+        .header("Cookie", format!("session={}", session))
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["viewer"]["user"]["name"], "Test User");
+
+    // without setting a cookie, the token is returned
+    let token = login_code!(&server, &ctx);
+    let vars = json!({ "email": "test.user@urls.fyi", "token": token });
+    let res = request(MUTATION_LOGIN, vars).reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["login"].as_str().unwrap().len(), 64);
+    assert!(res
+        .headers()
+        .get_all("Set-Cookie")
+        .iter()
+        .all(|value| !value.to_str().unwrap().starts_with("session=")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cookie_mutations_require_csrf_token() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // a fresh CSRF token is issued together with its cookie
+    let res = request(QUERY_CSRF_TOKEN, json!({})).reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let csrf_token = body["data"]["csrfToken"].as_str().unwrap().to_string();
+    assert!(cookie(&res, "xsrf").starts_with(&format!("xsrf={};", csrf_token)));
+
+    // mutations with a missing or mismatched token are rejected
+    let res = request(MUTATION_ISSUE_INVITE, json!({}))
+        .header(
+            "Cookie",
+            format!("xsrf={}; session={}", csrf_token, session),
+        )
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 403);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["errors"][0]["extensions"]["code"],
+        "CSRF_TOKEN_INVALID"
+    );
+
+    let res = request(MUTATION_ISSUE_INVITE, json!({}))
+        .header(
+            "Cookie",
+            format!("xsrf={}; session={}", csrf_token, session),
+        )
+        .header("X-XSRF-Token", "forged")
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 403);
+
+    // queries do not require a token
+    let res = request(QUERY_VIEWER, json!({}))
+        .header("Cookie", format!("session={}", session))
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["viewer"]["user"]["name"], "Test User");
+
+    let res = request(MUTATION_ISSUE_INVITE, json!({}))
+        .header(
+            "Cookie",
+            format!("xsrf={}; session={}", csrf_token, session),
+        )
+        .header("X-XSRF-Token", &csrf_token)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"]["issueInvite"]["id"].is_string());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_header_auth_bypasses_csrf() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let res = request(MUTATION_ISSUE_INVITE, json!({}))
+        .header("Authorization", format!("Bearer {}", session))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"]["issueInvite"]["id"].is_string());
+
+    // the header takes precedence over a cookie
+    let res = request(QUERY_VIEWER, json!({}))
+        .header("Authorization", format!("Bearer {}", session))
+        .header("Cookie", "session=invalid")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["viewer"]["user"]["name"], "Test User");
+
+    // invalid header tokens do not authenticate the request
+    let res = request(QUERY_VIEWER, json!({}))
+        .header("Authorization", "Bearer invalid")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"]["viewer"].is_null());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_queries_over_get() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // { viewer { user { name } } }
+    let res = warp::test::request()
+        .path("/graphql?query=%7B%20viewer%20%7B%20user%20%7B%20name%20%7D%20%7D%20%7D")
+        .header("Cookie", format!("session={}", session))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["viewer"]["user"]["name"], "Test User");
+
+    // mutations are rejected, even with a valid XSRF token
+    // mutation { issueInvite { id } }
+    let res = warp::test::request()
+        .path("/graphql?query=mutation%20%7B%20issueInvite%20%7B%20id%20%7D%20%7D")
+        .header("Cookie", format!("xsrf=fake_xsrf; session={}", session))
+        .header("X-XSRF-Token", "fake_xsrf")
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 405);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["errors"][0]["extensions"]["code"],
+        "METHOD_NOT_ALLOWED"
+    );
+}
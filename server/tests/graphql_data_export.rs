@@ -0,0 +1,210 @@
+use serde_json::{json, Value};
+use server::db::models::DataExport;
+use std::io::{Cursor, Read};
+mod setup;
+
+const QUERY_REQUEST: &str = "
+    mutation RequestDataExport {
+        requestDataExport {
+            ok
+        }
+    }
+";
+
+const QUERY_EXPORTS: &str = "
+    query DataExports {
+        viewer {
+            dataExports {
+                status
+                completedAt
+            }
+        }
+    }
+";
+
+/// Extract the download path from the last sent email.
+async fn download_path(ctx: &server::Context) -> String {
+    let email = setup::last_email(ctx).await.replace("=\r\n", "");
+    let token = email
+        .split_whitespace()
+        .find_map(|word| word.split("/data-export/").nth(1))
+        .expect("Missing download link");
+    format!("/data-export/{}", token)
+}
+
+fn read_file(archive: &mut zip::ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+    let mut file = archive.by_name(name).expect("Missing file in archive");
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    content
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_data_export() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let query = "
+        mutation IssueInvite {
+            issueInvite {
+                id
+            }
+        }
+    ";
+    let res = setup::graphql(query, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let invite_id = body["data"]["issueInvite"]["id"].clone();
+
+    let res = setup::graphql(QUERY_REQUEST, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body,
+        json!({ "data": { "requestDataExport": { "ok": true } } })
+    );
+
+    let res = setup::graphql(QUERY_EXPORTS, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["dataExports"],
+        json!([{ "status": "PENDING", "completedAt": null }])
+    );
+
+    DataExport::process_pending(&ctx).await.unwrap();
+
+    let res = setup::graphql(QUERY_EXPORTS, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["viewer"]["dataExports"][0]["status"], "READY");
+    assert!(body["data"]["viewer"]["dataExports"][0]["completedAt"].is_string());
+
+    // the download link works without being logged in
+    let res = warp::test::request()
+        .path(&download_path(&ctx).await)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["Content-Type"], "application/zip");
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(res.body().to_vec())).unwrap();
+    let export: Value = serde_json::from_str(&read_file(&mut archive, "export.json")).unwrap();
+    assert_eq!(export["profile"]["name"], "Test User");
+    assert_eq!(export["profile"]["username"], "test-user");
+    assert_eq!(export["profile"]["email"], "test.user@urls.fyi");
+    assert_eq!(export["invites"][0]["id"], invite_id);
+    assert_eq!(export["invites"][0]["claimed_by"], Value::Null);
+    assert_eq!(export["sessions"].as_array().unwrap().len(), 1);
+    assert_eq!(export["submissions"], json!([]));
+    assert_eq!(export["comments"], json!([]));
+    assert_eq!(export["votes"], json!([]));
+    assert_eq!(export["saved"], json!([]));
+
+    // session tokens are never exported
+    assert!(!read_file(&mut archive, "export.json").contains(&session));
+    assert!(!read_file(&mut archive, "sessions.csv").contains(&session));
+
+    let profile = read_file(&mut archive, "profile.csv");
+    let mut lines = profile.lines();
+    assert_eq!(
+        lines.next(),
+        Some("id,name,username,email,created_at,email_verified_at")
+    );
+    assert!(lines
+        .next()
+        .unwrap()
+        .contains(",Test User,test-user,test.user@urls.fyi,"));
+    for table in &[
+        "submissions",
+        "comments",
+        "votes",
+        "saved",
+        "sessions",
+        "invites",
+    ] {
+        assert!(!read_file(&mut archive, &format!("{}.csv", table)).is_empty());
+    }
+
+    // tampered links are rejected
+    let res = warp::test::request()
+        .path(&format!("{}x", download_path(&ctx).await))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 400);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_data_export_one_at_a_time() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let res = setup::graphql(QUERY_REQUEST, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["requestDataExport"]["ok"], true);
+
+    // further requests fail while the export is pending
+    let res = setup::graphql(QUERY_REQUEST, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("already in progress"));
+
+    // other users are not affected
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let res = setup::graphql(QUERY_REQUEST, json!({}), &admin_session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["requestDataExport"]["ok"], true);
+
+    // once completed, a new export may be requested
+    DataExport::process_pending(&ctx).await.unwrap();
+    let res = setup::graphql(QUERY_REQUEST, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["requestDataExport"]["ok"], true);
+
+    let res = setup::graphql(QUERY_EXPORTS, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let statuses: Vec<&str> = body["data"]["viewer"]["dataExports"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|export| export["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(statuses, vec!["PENDING", "READY"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_data_export_concurrent_requests() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let responses = futures_util::future::join_all(
+        (0..8).map(|_| setup::graphql(QUERY_REQUEST, json!({}), &session).reply(&server)),
+    )
+    .await;
+
+    let succeeded = responses
+        .iter()
+        .filter(|res| {
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+            !body["data"].is_null()
+        })
+        .count();
+    assert_eq!(succeeded, 1);
+}
@@ -0,0 +1,197 @@
+use serde_json::{json, Value};
+use server::db::models::User;
+mod setup;
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!, $parent: ID) {
+        addComment(urlId: $url, body: $body, parentId: $parent) {
+            id
+        }
+    }
+";
+
+const MUTATION_DELETE_COMMENT: &str = "
+    mutation DeleteComment($id: ID!, $reason: String) {
+        deleteComment(comment: $id, reason: $reason) {
+            text
+            deletedAt
+        }
+    }
+";
+
+const QUERY_THREAD: &str = "
+    query Thread($url: ID!) {
+        fetch__Url(id: $url) {
+            commentCount
+            comments(first: 10) {
+                edges {
+                    node {
+                        text
+                        replyCount
+                        replies(first: 10) {
+                            edges {
+                                node { text }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+";
+
+const QUERY_DELETION_REASON: &str = "
+    query DeletionReason($id: ID!) {
+        fetch__Comment(id: $id) {
+            deletionReason
+        }
+    }
+";
+
+/// Comment on the given submission, returning the
+/// ID of the new comment.
+macro_rules! add_comment {
+    ($server:expr, $session:expr, $url:expr, $body:expr, $parent:expr) => {{
+        let vars = json!({ "url": $url.to_string(), "body": $body, "parent": $parent });
+        let body = setup::execute($server, MUTATION_ADD_COMMENT, vars, $session).await;
+        body["data"]["addComment"]["id"].clone()
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_delete_leaf_comment() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let url = setup::submit(&ctx, user.id()).await;
+
+    let root = add_comment!(&server, &session, url, "Root", Value::Null);
+    let reply = add_comment!(&server, &session, url, "Reply", root);
+
+    // deleting twice is fine
+    for _ in 0..2 {
+        let body = setup::execute(
+            &server,
+            MUTATION_DELETE_COMMENT,
+            json!({ "id": reply }),
+            &session,
+        )
+        .await;
+        assert_eq!(body["data"]["deleteComment"]["text"], "[deleted]");
+        assert!(body["data"]["deleteComment"]["deletedAt"].is_string());
+    }
+
+    let body = setup::execute(&server, QUERY_THREAD, json!({ "url": url.to_string() }), "").await;
+    assert_eq!(
+        body["data"]["fetch__Url"],
+        json!({
+            "commentCount": 1,
+            "comments": { "edges": [{
+                "node": { "text": "Root", "replyCount": 0, "replies": { "edges": [] } }
+            }] },
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_delete_comment_with_replies() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let url = setup::submit(&ctx, user.id()).await;
+
+    let root = add_comment!(&server, &session, url, "Root", Value::Null);
+    let reply = add_comment!(&server, &session, url, "Reply", root.clone());
+
+    // the parent stays in place as a placeholder
+    setup::execute(
+        &server,
+        MUTATION_DELETE_COMMENT,
+        json!({ "id": root }),
+        &session,
+    )
+    .await;
+    let body = setup::execute(&server, QUERY_THREAD, json!({ "url": url.to_string() }), "").await;
+    assert_eq!(
+        body["data"]["fetch__Url"],
+        json!({
+            "commentCount": 2,
+            "comments": { "edges": [{
+                "node": {
+                    "text": "[deleted]",
+                    "replyCount": 1,
+                    "replies": { "edges": [{ "node": { "text": "Reply" } }] },
+                }
+            }] },
+        })
+    );
+
+    // once the last reply is gone, so is the placeholder
+    setup::execute(
+        &server,
+        MUTATION_DELETE_COMMENT,
+        json!({ "id": reply }),
+        &session,
+    )
+    .await;
+    let body = setup::execute(&server, QUERY_THREAD, json!({ "url": url.to_string() }), "").await;
+    assert_eq!(
+        body["data"]["fetch__Url"],
+        json!({ "commentCount": 0, "comments": { "edges": [] } })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_delete_comment_permissions() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let url = setup::submit(&ctx, user.id()).await;
+
+    let own = add_comment!(&server, &session, url, "Own", Value::Null);
+    let other = add_comment!(&server, &admin_session, url, "Other", Value::Null);
+
+    let body = setup::execute(
+        &server,
+        MUTATION_DELETE_COMMENT,
+        json!({ "id": other }),
+        &session,
+    )
+    .await;
+    assert_eq!(body["errors"][0]["message"], "Not authorized");
+
+    // reasons are only recorded for moderator deletions
+    let vars = json!({ "id": own, "reason": "Off topic" });
+    let body = setup::execute(&server, MUTATION_DELETE_COMMENT, vars, &admin_session).await;
+    assert!(body["errors"].is_null());
+    let vars = json!({ "id": other, "reason": "Not recorded" });
+    setup::execute(&server, MUTATION_DELETE_COMMENT, vars, &admin_session).await;
+
+    let vars = json!({ "id": own });
+    let body = setup::execute(&server, QUERY_DELETION_REASON, vars.clone(), &admin_session).await;
+    assert_eq!(
+        body["data"]["fetch__Comment"]["deletionReason"],
+        "Off topic"
+    );
+    let body = setup::execute(&server, QUERY_DELETION_REASON, vars, &session).await;
+    assert_eq!(
+        body["data"]["fetch__Comment"]["deletionReason"],
+        Value::Null
+    );
+    let vars = json!({ "id": other });
+    let body = setup::execute(&server, QUERY_DELETION_REASON, vars, &admin_session).await;
+    assert_eq!(
+        body["data"]["fetch__Comment"]["deletionReason"],
+        Value::Null
+    );
+
+    let body = setup::execute(&server, QUERY_THREAD, json!({ "url": url.to_string() }), "").await;
+    assert_eq!(body["data"]["fetch__Url"]["commentCount"], 0);
+}
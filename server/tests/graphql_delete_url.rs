@@ -0,0 +1,189 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::models::{Url, User};
+mod setup;
+
+const QUERY_SUBMISSIONS: &str = "
+    query Submissions($after: String) {
+        submissions(first: 2, after: $after) {
+            edges {
+                node { title }
+            }
+            pageInfo {
+                hasNextPage
+                endCursor
+            }
+        }
+    }
+";
+
+const QUERY_SEARCH: &str = "
+    query Search($query: String!) {
+        search(query: $query) {
+            results(first: 10) {
+                edges {
+                    node { title }
+                }
+            }
+        }
+    }
+";
+
+const MUTATION_DELETE: &str = "
+    mutation DeleteUrl($id: ID!) {
+        deleteUrl(id: $id) {
+            ok
+        }
+    }
+";
+
+const MUTATION_RESTORE: &str = "
+    mutation RestoreUrl($id: ID!) {
+        restoreUrl(id: $id) {
+            deletedAt
+        }
+    }
+";
+
+/// Run the given mutation for the given submission,
+/// returning the GraphQL response.
+macro_rules! mutate {
+    ($server:expr, $session:expr, $query:expr, $id:expr) => {{
+        let vars = json!({ "id": $id.to_string() });
+        let res = setup::graphql($query, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Fetch a page of submissions, returning the titles
+/// and the end cursor.
+macro_rules! submissions {
+    ($server:expr, $after:expr) => {{
+        let res = setup::graphql(QUERY_SUBMISSIONS, json!({ "after": $after }), "")
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let submissions = &body["data"]["submissions"];
+        let titles: Vec<String> = submissions["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["node"]["title"].as_str().unwrap().to_string())
+            .collect();
+        (titles, submissions["pageInfo"]["endCursor"].clone())
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_delete_own_url() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let id = setup::Submission::by(user.id())
+        .title("Searchable submission")
+        .indexed()
+        .insert(&ctx)
+        .await;
+
+    let search =
+        || setup::graphql(QUERY_SEARCH, json!({ "query": "searchable" }), "").reply(&server);
+    let body: Value = serde_json::from_slice(search().await.body()).unwrap();
+    assert_eq!(
+        body["data"]["search"]["results"]["edges"][0]["node"]["title"],
+        "Searchable submission"
+    );
+
+    // deleting twice is fine
+    for _ in 0..2 {
+        let body = mutate!(&server, &session, MUTATION_DELETE, id);
+        assert_eq!(body, json!({ "data": { "deleteUrl": { "ok": true } } }));
+    }
+
+    let (titles, _) = submissions!(&server, Value::Null);
+    assert!(titles.is_empty());
+    let body: Value = serde_json::from_slice(search().await.body()).unwrap();
+    assert_eq!(body["data"]["search"]["results"]["edges"], json!([]));
+
+    // the row is kept, but the page no longer shows the link
+    let url = Url::find(&ctx, id).await.unwrap();
+    assert!(url.is_deleted());
+    let res = warp::test::request()
+        .path(&format!("/comments/{}", id))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let page = String::from_utf8_lossy(res.body());
+    assert!(page.contains("[deleted]"));
+    assert!(!page.contains("Searchable submission"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_delete_url_permissions() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let id = setup::Submission::by(admin.id())
+        .title("Admin submission")
+        .indexed()
+        .insert(&ctx)
+        .await;
+
+    // other users can not delete the submission
+    let body = mutate!(&server, &session, MUTATION_DELETE, id);
+    assert_eq!(body["errors"][0]["message"], "Not authorized");
+    assert!(!Url::find(&ctx, id).await.unwrap().is_deleted());
+
+    let body = mutate!(&server, &admin_session, MUTATION_DELETE, id);
+    assert_eq!(body["data"]["deleteUrl"]["ok"], true);
+
+    // only administrators can restore submissions
+    let body = mutate!(&server, &session, MUTATION_RESTORE, id);
+    assert_eq!(body["errors"][0]["message"], "Not authorized");
+    let body = mutate!(&server, &admin_session, MUTATION_RESTORE, id);
+    assert_eq!(
+        body,
+        json!({ "data": { "restoreUrl": { "deletedAt": null } } })
+    );
+
+    let (titles, _) = submissions!(&server, Value::Null);
+    assert_eq!(titles, vec!["Admin submission"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_paginate_across_deletion() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let mut ids = vec![];
+    for (minutes, title) in ["First", "Second", "Third", "Fourth"].iter().enumerate() {
+        ids.push(
+            setup::Submission::by(user.id())
+                .title(title)
+                .created_at(ctx.now() - Duration::minutes(minutes as i64))
+                .indexed()
+                .insert(&ctx)
+                .await,
+        );
+    }
+
+    let (titles, cursor) = submissions!(&server, Value::Null);
+    assert_eq!(titles, vec!["First", "Second"]);
+
+    // the submission the cursor points to disappears
+    let body = mutate!(&server, &session, MUTATION_DELETE, ids[1]);
+    assert_eq!(body["data"]["deleteUrl"]["ok"], true);
+
+    let (titles, _) = submissions!(&server, cursor);
+    assert_eq!(titles, vec!["Third", "Fourth"]);
+}
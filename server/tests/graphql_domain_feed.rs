@@ -0,0 +1,125 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::models::{Url, User};
+use server::schema::urls;
+mod setup;
+
+const QUERY_DOMAIN_FEED: &str = "
+    query DomainFeed($domain: String!, $after: String) {
+        domainFeed(domain: $domain, first: 2, after: $after) {
+            edges {
+                node { title domain domainAscii domainSubmissionCount }
+            }
+            pageInfo {
+                hasNextPage
+                endCursor
+            }
+        }
+    }
+";
+
+/// Fetch all titles in the feed of the given domain, page
+/// by page, and the nodes of the first page.
+macro_rules! feed {
+    ($server:expr, $domain:expr) => {{
+        let mut titles = vec![];
+        let mut first = Value::Null;
+        let mut after = Value::Null;
+        loop {
+            let vars = json!({ "domain": $domain, "after": after });
+            let res = setup::graphql(QUERY_DOMAIN_FEED, vars, "")
+                .reply($server)
+                .await;
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+            let feed = &body["data"]["domainFeed"];
+            if first.is_null() {
+                first = feed["edges"].clone();
+            }
+            titles.extend(
+                feed["edges"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|edge| edge["node"]["title"].as_str().unwrap().to_string()),
+            );
+            if feed["pageInfo"]["hasNextPage"] != true {
+                break;
+            }
+            after = feed["pageInfo"]["endCursor"].clone();
+        }
+        (titles, first)
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_domain_feed() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let urls = [
+        "https://blog.example.co.uk/first",
+        "https://www.example.co.uk/second",
+        "https://example.co.uk/third",
+        "https://other.co.uk/elsewhere",
+        "https://example.com/not-uk",
+        "https://xn--bcher-kva.de/katalog",
+    ];
+    let mut ids = vec![];
+    for (minutes, url) in urls.iter().enumerate() {
+        ids.push(
+            setup::Submission::by(user.id())
+                .url(url)
+                .title(url)
+                .created_at(ctx.now() - Duration::minutes(minutes as i64))
+                .insert(&ctx)
+                .await,
+        );
+    }
+    Url::backfill_domains(&*ctx.conn().await.unwrap()).unwrap();
+    assert_eq!(
+        Url::find(&ctx, ids[0]).await.unwrap().domain(),
+        Some("example.co.uk")
+    );
+
+    // any host of the domain lists the whole domain
+    for domain in [
+        "example.co.uk",
+        "blog.example.co.uk",
+        "https://www.example.co.uk/",
+    ] {
+        let (titles, _) = feed!(&server, domain);
+        assert_eq!(titles, urls[..3].to_vec(), "{}", domain);
+    }
+    let (titles, first) = feed!(&server, "example.com");
+    assert_eq!(titles, vec!["https://example.com/not-uk"]);
+    assert_eq!(first[0]["node"]["domainSubmissionCount"], 1);
+    let (titles, _) = feed!(&server, "co.uk");
+    assert!(titles.is_empty());
+
+    // international domains are shown in unicode
+    for domain in ["bücher.de", "xn--bcher-kva.de"] {
+        let (titles, first) = feed!(&server, domain);
+        assert_eq!(titles, vec!["https://xn--bcher-kva.de/katalog"]);
+        assert_eq!(
+            first[0]["node"],
+            json!({
+                "title": "https://xn--bcher-kva.de/katalog",
+                "domain": "bücher.de",
+                "domainAscii": "xn--bcher-kva.de",
+                "domainSubmissionCount": 1,
+            })
+        );
+    }
+
+    // deleted submissions are neither listed nor counted
+    diesel::update(urls::table)
+        .filter(urls::dsl::id.eq(ids[1]))
+        .set(urls::dsl::deleted_at.eq(ctx.now().naive_utc()))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let (titles, first) = feed!(&server, "example.co.uk");
+    assert_eq!(titles, vec![urls[0], urls[2]]);
+    assert_eq!(first[0]["node"]["domainSubmissionCount"], 2);
+}
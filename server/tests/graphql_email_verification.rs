@@ -0,0 +1,208 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::models::User;
+mod setup;
+
+const QUERY_VERIFIED: &str = "
+    query EmailVerified {
+        viewer {
+            emailVerified
+        }
+    }
+";
+
+const QUERY_VERIFY: &str = "
+    mutation VerifyEmail($token: String!) {
+        verifyEmail(token: $token) {
+            ok
+        }
+    }
+";
+
+const QUERY_RESEND: &str = "
+    mutation ResendVerification {
+        resendVerification {
+            ok
+        }
+    }
+";
+
+/// Extract the verification token from the last sent email.
+async fn verification_token(ctx: &server::Context) -> String {
+    let email = setup::last_email(ctx).await.replace("=\r\n", "");
+    email
+        .split_whitespace()
+        .find_map(|word| word.split("/verify-email/").nth(1))
+        .expect("Missing verification link")
+        .to_string()
+}
+
+/// Register a new user using an invite of the mock user.
+macro_rules! register {
+    ($server:expr, $ctx:expr) => {{
+        let session = setup::session_token($ctx, "test.user@urls.fyi").await;
+        let query = "
+            mutation IssueInvite {
+                issueInvite {
+                    token
+                }
+            }
+        ";
+        let res = setup::graphql(query, json!({}), &session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let token = body["data"]["issueInvite"]["token"].clone();
+
+        let query = "
+            mutation RegisterUser($token: String!) {
+                registerUser(
+                    input: { name: \"Test Register User\", email: \"test.register@urls.fyi\" },
+                    token: $token,
+                ) {
+                    name
+                }
+            }
+        ";
+        let res = setup::graphql(query, json!({ "token": token }), "")
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["data"]["registerUser"]["name"], "Test Register User");
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unverified_users_are_gated() {
+    let (server, ctx) = setup::mock().await;
+    register!(&server, &ctx);
+    let token = verification_token(&ctx).await;
+    let session = setup::session_token(&ctx, "test.register@urls.fyi").await;
+
+    let res = setup::graphql(QUERY_VERIFIED, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body,
+        json!({ "data": { "viewer": { "emailVerified": false } } })
+    );
+
+    let query_submit = "
+        mutation SubmitUrl($url: String!) {
+            submitUrl(input: { url: $url }) {
+                url {
+                    id
+                }
+            }
+        }
+    ";
+    let vars = json!({ "url": "http://localhost/not-submitted" });
+    let res = setup::graphql(query_submit, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Please verify your email address first"
+    );
+
+    // verifying twice is fine
+    for _ in 0..2 {
+        let res = setup::graphql(QUERY_VERIFY, json!({ "token": token }), "")
+            .reply(&server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body, json!({ "data": { "verifyEmail": { "ok": true } } }));
+    }
+
+    let res = setup::graphql(QUERY_VERIFIED, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body,
+        json!({ "data": { "viewer": { "emailVerified": true } } })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_verification_token_expiry() {
+    let (server, ctx) = setup::mock().await;
+    register!(&server, &ctx);
+    let token = verification_token(&ctx).await;
+
+    let mut later = ctx.clone();
+    later.set_request_time(ctx.now() + Duration::days(3));
+    assert!(User::verify_email(&later, &token).await.is_err());
+
+    let tampered = format!("{}x", token);
+    let res = setup::graphql(QUERY_VERIFY, json!({ "token": tampered }), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Invalid or expired verification token"
+    );
+
+    let user = User::find_by_email(&ctx, "test.register@urls.fyi")
+        .await
+        .unwrap();
+    assert!(!user.is_email_verified());
+
+    // the plain link works for mail clients
+    let res = warp::test::request()
+        .path(&format!("/verify-email/{}", token))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let user = User::find_by_email(&ctx, "test.register@urls.fyi")
+        .await
+        .unwrap();
+    assert!(user.is_email_verified());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resend_verification_cooldown() {
+    let (server, ctx) = setup::mock().await;
+    register!(&server, &ctx);
+    let first_token = verification_token(&ctx).await;
+    let session = setup::session_token(&ctx, "test.register@urls.fyi").await;
+
+    // the registration email was just sent
+    let res = setup::graphql(QUERY_RESEND, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert_eq!(body["errors"][0]["extensions"]["code"], "RATE_LIMITED");
+    assert_eq!(
+        body["errors"][0]["extensions"]["scope"],
+        "resend_verification"
+    );
+
+    // once the cooldown passed, another email is sent
+    let mut later = ctx.clone();
+    later.set_request_time(ctx.now() + Duration::minutes(10));
+    let mut user = User::find_by_email(&later, "test.register@urls.fyi")
+        .await
+        .unwrap();
+    user.request_verification(&later).await.unwrap();
+    let second_token = verification_token(&ctx).await;
+    assert_ne!(first_token, second_token);
+
+    // verified users don't need another email
+    User::verify_email(&later, &second_token).await.unwrap();
+    let res = setup::graphql(QUERY_RESEND, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "The email address is already verified"
+    );
+}
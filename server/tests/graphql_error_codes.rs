@@ -0,0 +1,240 @@
+use diesel::RunQueryDsl;
+use serde_json::{json, Value};
+use server::db::models::User;
+use server::{Config, RegistrationMode};
+mod setup;
+
+const MUTATION_REGISTER: &str = "
+    mutation RegisterUser($name: String!, $email: String!, $token: String) {
+        registerUser(input: { name: $name, email: $email }, token: $token) {
+            name
+        }
+    }
+";
+
+const MUTATION_REQUEST_LOGIN: &str = "
+    mutation RequestLogin($email: String!) {
+        requestLogin(email: $email) { ok }
+    }
+";
+
+const MUTATION_LOGIN: &str = "
+    mutation Login($email: String!, $token: String!) {
+        login(email: $email, token: $token)
+    }
+";
+
+const MUTATION_UPDATE_USER: &str = "
+    mutation UpdateUser($input: UpdateUserInput!) {
+        updateUser(input: $input) { email }
+    }
+";
+
+const MUTATION_ISSUE_INVITE: &str = "
+    mutation IssueInvite {
+        issueInvite { token }
+    }
+";
+
+const MUTATION_RESEND_VERIFICATION: &str = "
+    mutation ResendVerification {
+        resendVerification { ok }
+    }
+";
+
+const MUTATION_GRANT_INVITES: &str = "
+    mutation GrantInvites($user: ID!, $count: Int!) {
+        grantInvites(user: $user, count: $count) { name }
+    }
+";
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id }
+        }
+    }
+";
+
+const QUERY_INVITE_TREE: &str = "
+    query InviteTree($root: ID!) {
+        inviteTree(rootUserId: $root) {
+            user { name }
+        }
+    }
+";
+
+/// Run the given GraphQL request, which must fail, and return the
+/// code of the error along with the response.
+macro_rules! error_code {
+    ($server:expr, $session:expr, $query:expr, $vars:expr) => {{
+        let res = setup::graphql($query, $vars, $session).reply($server).await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["data"].is_null(), "{}", body);
+        let code = body["errors"][0]["extensions"]["code"]
+            .as_str()
+            .expect(&body.to_string())
+            .to_string();
+        (code, body)
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_register_user_errors() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "name": "Test New", "email": "test.new@urls.fyi" });
+    let (code, _) = error_code!(&server, "", MUTATION_REGISTER, vars);
+    assert_eq!(code, "VALIDATION_FAILED");
+
+    let vars = json!({ "name": "Test New", "email": "test.new@", "token": "invalid" });
+    let (code, _) = error_code!(&server, "", MUTATION_REGISTER, vars);
+    assert_eq!(code, "VALIDATION_FAILED");
+
+    let vars = json!({ "name": "Test New", "email": "test.new@urls.fyi", "token": "invalid" });
+    let (code, _) = error_code!(&server, "", MUTATION_REGISTER, vars);
+    assert_eq!(code, "NOT_FOUND");
+
+    let res = setup::graphql(MUTATION_ISSUE_INVITE, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let token = body["data"]["issueInvite"]["token"].clone();
+    let vars = json!({ "name": "Test Again", "email": "test.user@urls.fyi", "token": token });
+    let (code, body) = error_code!(&server, "", MUTATION_REGISTER, vars);
+    assert_eq!(code, "CONFLICT");
+    assert_eq!(
+        body["errors"][0]["message"],
+        "An account with this email address already exists"
+    );
+
+    let conf = Config::test().with_registration_mode(RegistrationMode::Closed);
+    let (server, _) = setup::mock_with_config(conf).await;
+    let vars = json!({ "name": "Test New", "email": "test.new@urls.fyi" });
+    let (code, _) = error_code!(&server, "", MUTATION_REGISTER, vars);
+    assert_eq!(code, "PERMISSION_DENIED");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_login_errors() {
+    let (server, _) = setup::mock().await;
+
+    let vars = json!({ "email": "not an email" });
+    let (code, _) = error_code!(&server, "", MUTATION_REQUEST_LOGIN, vars);
+    assert_eq!(code, "VALIDATION_FAILED");
+
+    let vars = json!({ "email": "test.unknown@urls.fyi" });
+    let (code, body) = error_code!(&server, "", MUTATION_REQUEST_LOGIN, vars);
+    assert_eq!(code, "NOT_FOUND");
+    assert_eq!(body["errors"][0]["message"], "User not found");
+
+    let vars = json!({ "email": "test.unknown@urls.fyi", "token": "invalid" });
+    let (code, _) = error_code!(&server, "", MUTATION_LOGIN, vars);
+    assert_eq!(code, "NOT_FOUND");
+
+    let vars = json!({ "email": "test.user@urls.fyi", "token": "invalid" });
+    let (code, body) = error_code!(&server, "", MUTATION_LOGIN, vars);
+    assert_eq!(code, "UNAUTHENTICATED");
+    assert_eq!(body["errors"][0]["message"], "Invalid login token");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_user_errors() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "input": { "name": "Logged Out" } });
+    let (code, body) = error_code!(&server, "", MUTATION_UPDATE_USER, vars);
+    assert_eq!(code, "UNAUTHENTICATED");
+    assert_eq!(body["errors"][0]["message"], "Not logged in");
+
+    let vars = json!({ "input": { "username": "with space" } });
+    let (code, _) = error_code!(&server, &session, MUTATION_UPDATE_USER, vars);
+    assert_eq!(code, "VALIDATION_FAILED");
+
+    let vars = json!({ "input": { "username": "test-administrator" } });
+    let (code, _) = error_code!(&server, &session, MUTATION_UPDATE_USER, vars);
+    assert_eq!(code, "CONFLICT");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_issue_invite_errors() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let (code, _) = error_code!(&server, "", MUTATION_ISSUE_INVITE, json!({}));
+    assert_eq!(code, "UNAUTHENTICATED");
+
+    // database errors are reported without their details
+    diesel::sql_query("DROP TABLE invites")
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let (code, body) = error_code!(&server, &session, MUTATION_ISSUE_INVITE, json!({}));
+    assert_eq!(code, "INTERNAL");
+    assert_eq!(body["errors"][0]["message"], "Internal error");
+    let body = body.to_string().to_lowercase();
+    assert!(
+        !body.contains("invites") && !body.contains("sql"),
+        "{}",
+        body
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_viewer_errors() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    // resolvers which require a viewer keep the code of their errors
+    let (code, _) = error_code!(&server, "", MUTATION_RESEND_VERIFICATION, json!({}));
+    assert_eq!(code, "UNAUTHENTICATED");
+    let (code, body) = error_code!(&server, &session, MUTATION_RESEND_VERIFICATION, json!({}));
+    assert_eq!(code, "CONFLICT");
+    assert_eq!(
+        body["errors"][0]["message"],
+        "The email address is already verified"
+    );
+
+    let vars = json!({ "user": user.id().to_string(), "count": 0 });
+    let (code, _) = error_code!(&server, "", MUTATION_GRANT_INVITES, vars.clone());
+    assert_eq!(code, "UNAUTHENTICATED");
+    let (code, _) = error_code!(&server, &session, MUTATION_GRANT_INVITES, vars.clone());
+    assert_eq!(code, "PERMISSION_DENIED");
+    let (code, _) = error_code!(&server, &admin_session, MUTATION_GRANT_INVITES, vars);
+    assert_eq!(code, "VALIDATION_FAILED");
+
+    let vars = json!({ "root": user.id().to_string() });
+    let (code, _) = error_code!(&server, "", QUERY_INVITE_TREE, vars.clone());
+    assert_eq!(code, "UNAUTHENTICATED");
+    let (code, _) = error_code!(&server, &session, QUERY_INVITE_TREE, vars);
+    assert_eq!(code, "PERMISSION_DENIED");
+
+    let vars = json!({ "input": { "title": "Logged out", "text": "Some text" } });
+    let (code, _) = error_code!(&server, "", MUTATION_SUBMIT, vars);
+    assert_eq!(code, "UNAUTHENTICATED");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resolver_errors() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url = setup::submit(&ctx, user.id()).await;
+
+    // errors of the models keep their code in every resolver
+    let query = "mutation Vote($id: ID!) { voteUrl(id: $id) { id } }";
+    let vars = json!({ "id": url.to_string() });
+    let (code, _) = error_code!(&server, "", query, vars);
+    assert_eq!(code, "UNAUTHENTICATED");
+
+    // as do invalid pagination arguments
+    let query = "query Tags { tags(first: -1) { edges { node { name } } } }";
+    let (code, _) = error_code!(&server, "", query, json!({}));
+    assert_eq!(code, "VALIDATION_FAILED");
+}
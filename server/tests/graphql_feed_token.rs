@@ -0,0 +1,101 @@
+use serde_json::{json, Value};
+mod setup;
+
+const QUERY_FEED_URL: &str = "
+    query FeedUrl {
+        viewer {
+            feedUrl
+        }
+    }
+";
+
+const MUTATION_REGENERATE: &str = "
+    mutation RegenerateFeedToken {
+        regenerateFeedToken {
+            feedUrl
+        }
+    }
+";
+
+/// Extract the path of a feed url, such that it
+/// can be requested from the test server.
+fn feed_path(body: &Value, field: &str) -> String {
+    let url = body["data"][field]["feedUrl"].as_str().unwrap();
+    url.strip_prefix("https://localhost").unwrap().to_string()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_regenerate_feed_token() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let res = setup::graphql(QUERY_FEED_URL, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let old_path = feed_path(&body, "viewer");
+    assert!(old_path.starts_with("/feed.xml?token="));
+
+    // the token is only generated once
+    let res = setup::graphql(QUERY_FEED_URL, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(feed_path(&body, "viewer"), old_path);
+
+    let res = warp::test::request().path(&old_path).reply(&server).await;
+    assert_eq!(res.status(), 200);
+
+    let res = setup::graphql(MUTATION_REGENERATE, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let new_path = feed_path(&body, "regenerateFeedToken");
+    assert_ne!(new_path, old_path);
+
+    let res = warp::test::request().path(&old_path).reply(&server).await;
+    assert_eq!(res.status(), 403);
+    let res = warp::test::request().path(&new_path).reply(&server).await;
+    assert_eq!(res.status(), 200);
+
+    // tampered tokens are rejected
+    let res = warp::test::request()
+        .path(&format!("{}x", new_path))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 403);
+    let res = warp::test::request()
+        .path("/feed.xml?token=invalid")
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 403);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_regenerate_feed_token_requires_login() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let res = setup::graphql(QUERY_FEED_URL, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let path = feed_path(&body, "viewer");
+
+    let res = setup::graphql(MUTATION_REGENERATE, json!({}), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert!(!body["errors"].as_array().unwrap().is_empty());
+
+    let res = setup::graphql(QUERY_FEED_URL, json!({}), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"]["viewer"].is_null());
+
+    // the existing url keeps working
+    let res = warp::test::request().path(&path).reply(&server).await;
+    assert_eq!(res.status(), 200);
+}
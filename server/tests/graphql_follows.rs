@@ -0,0 +1,367 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::User;
+use server::schema::{tags, url_tags, users};
+use server::Context;
+mod setup;
+
+const QUERY_FEED: &str = "
+    query FollowingFeed($after: String) {
+        viewer {
+            followingFeed(first: 2, after: $after) {
+                edges {
+                    node {
+                        url { title }
+                        source { followedUser followedTags }
+                    }
+                }
+                pageInfo {
+                    hasNextPage
+                    endCursor
+                }
+            }
+        }
+    }
+";
+
+const QUERY_COUNTS: &str = "
+    query Counts {
+        user(username: \"test-user\") {
+            followerCount
+            followingCount
+            following { edges { node { username } } }
+        }
+        admin: user(username: \"test-administrator\") {
+            followerCount
+            followingCount
+            viewerFollows
+            followers { edges { node { username } } }
+        }
+    }
+";
+
+const MUTATION_FOLLOW: &str = "
+    mutation FollowUser($id: ID!) {
+        followUser(userId: $id) { viewerFollows }
+    }
+";
+
+const MUTATION_UNFOLLOW: &str = "
+    mutation UnfollowUser($id: ID!) {
+        unfollowUser(userId: $id) { viewerFollows }
+    }
+";
+
+const MUTATION_BLOCK: &str = "
+    mutation BlockUser($id: ID!) {
+        blockUser(userId: $id) { ok }
+    }
+";
+
+const MUTATION_FOLLOW_TAG: &str = "
+    mutation FollowTag($name: String!) {
+        followTag(name: $name) { name }
+    }
+";
+
+const MUTATION_UNFOLLOW_TAG: &str = "
+    mutation UnfollowTag($name: String!) {
+        unfollowTag(name: $name) { ok }
+    }
+";
+
+const QUERY_FEED_SOURCES: &str = "
+    query FollowingFeed($first: Int!, $after: String) {
+        viewer {
+            followedTags { name }
+            followingFeed(first: $first, after: $after) {
+                edges {
+                    node {
+                        url { title }
+                        source { followedUser followedTags }
+                    }
+                }
+                pageInfo { endCursor }
+            }
+        }
+    }
+";
+
+/// Tag the given submission, creating the tag if needed.
+async fn tag(ctx: &Context, url_id: UrlID, name: &str) {
+    diesel::insert_or_ignore_into(tags::table)
+        .values((
+            tags::dsl::name.eq(name),
+            tags::dsl::created_at.eq(ctx.now().naive_utc()),
+            tags::dsl::url_count.eq(1),
+        ))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    diesel::insert_into(url_tags::table)
+        .values((
+            url_tags::dsl::url_id.eq(url_id),
+            url_tags::dsl::tag_name.eq(name),
+        ))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+}
+
+/// Page through the following feed of the given
+/// session, returning the titles.
+macro_rules! feed {
+    ($server:expr, $session:expr) => {{
+        let mut titles = vec![];
+        let mut after = Value::Null;
+        loop {
+            let body = setup::execute($server, QUERY_FEED, json!({ "after": after }), $session).await;
+            assert!(body["errors"].is_null(), "{}", body);
+            let feed = &body["data"]["viewer"]["followingFeed"];
+            for edge in feed["edges"].as_array().unwrap() {
+                titles.push(edge["node"]["url"]["title"].as_str().unwrap().to_string());
+            }
+            if feed["pageInfo"]["hasNextPage"] != true {
+                break titles;
+            }
+            after = feed["pageInfo"]["endCursor"].clone();
+        }
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_following_feed() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    for (title, minutes) in [("First", 1), ("Second", 2), ("Third", 3)] {
+        setup::Submission::by(admin.id())
+            .title(title)
+            .created_at(ctx.now() + Duration::minutes(minutes))
+            .insert(&ctx)
+            .await;
+    }
+    setup::Submission::by(user.id())
+        .title("Own")
+        .created_at(ctx.now() + Duration::minutes(4))
+        .insert(&ctx)
+        .await;
+    let vars = json!({ "id": admin.id().to_string() });
+
+    assert!(feed!(&server, &session).is_empty());
+    let body = setup::execute(&server, MUTATION_FOLLOW, vars.clone(), &session).await;
+    assert_eq!(body["data"]["followUser"]["viewerFollows"], true);
+    assert_eq!(feed!(&server, &session), vec!["Third", "Second", "First"]);
+
+    // following again has no effect
+    setup::execute(&server, MUTATION_FOLLOW, vars.clone(), &session).await;
+    assert_eq!(feed!(&server, &session), vec!["Third", "Second", "First"]);
+
+    let body = setup::execute(&server, MUTATION_UNFOLLOW, vars, &session).await;
+    assert_eq!(body["data"]["unfollowUser"]["viewerFollows"], false);
+    assert!(feed!(&server, &session).is_empty());
+
+    // users can not follow themselves
+    let vars = json!({ "id": user.id().to_string() });
+    let body = setup::execute(&server, MUTATION_FOLLOW, vars, &session).await;
+    assert!(body["data"].is_null(), "{}", body);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_follow_counts() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+
+    let body = setup::execute(&server, QUERY_COUNTS, json!({}), &session).await;
+    assert_eq!(body["data"]["admin"]["followerCount"], 0);
+    assert_eq!(body["data"]["admin"]["viewerFollows"], false);
+
+    let vars = json!({ "id": admin.id().to_string() });
+    setup::execute(&server, MUTATION_FOLLOW, vars, &session).await;
+    let body = setup::execute(&server, QUERY_COUNTS, json!({}), &session).await;
+    assert_eq!(
+        body["data"],
+        json!({
+            "user": {
+                "followerCount": 0,
+                "followingCount": 1,
+                "following": { "edges": [{ "node": { "username": "test-administrator" } }] },
+            },
+            "admin": {
+                "followerCount": 1,
+                "followingCount": 0,
+                "viewerFollows": true,
+                "followers": { "edges": [{ "node": { "username": "test-user" } }] },
+            },
+        })
+    );
+
+    // banned users can not be followed
+    setup::execute(
+        &server,
+        MUTATION_UNFOLLOW,
+        json!({ "id": admin.id().to_string() }),
+        &session,
+    )
+    .await;
+    diesel::update(users::table.find(admin.id()))
+        .set(users::dsl::banned_at.eq(ctx.now().naive_utc()))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let vars = json!({ "id": admin.id().to_string() });
+    let body = setup::execute(&server, MUTATION_FOLLOW, vars, &session).await;
+    assert!(body["data"].is_null(), "{}", body);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_follow_block_interaction() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    setup::Submission::by(admin.id())
+        .title("Followed")
+        .created_at(ctx.now() + Duration::minutes(1))
+        .insert(&ctx)
+        .await;
+
+    let follow_admin = json!({ "id": admin.id().to_string() });
+    let follow_user = json!({ "id": user.id().to_string() });
+    setup::execute(&server, MUTATION_FOLLOW, follow_admin.clone(), &session).await;
+    setup::execute(
+        &server,
+        MUTATION_FOLLOW,
+        follow_user.clone(),
+        &admin_session,
+    )
+    .await;
+    assert_eq!(feed!(&server, &session), vec!["Followed"]);
+
+    // blocking removes the follows both ways
+    let body = setup::execute(&server, MUTATION_BLOCK, follow_user.clone(), &admin_session).await;
+    assert_eq!(body["data"]["blockUser"]["ok"], true);
+    assert!(feed!(&server, &session).is_empty());
+    let body = setup::execute(&server, QUERY_COUNTS, json!({}), &session).await;
+    for user in ["user", "admin"] {
+        assert_eq!(body["data"][user]["followerCount"], 0);
+        assert_eq!(body["data"][user]["followingCount"], 0);
+    }
+
+    // neither side can follow the other while the block lasts
+    let body = setup::execute(&server, MUTATION_FOLLOW, follow_admin, &session).await;
+    assert!(body["data"].is_null(), "{}", body);
+    let body = setup::execute(&server, MUTATION_FOLLOW, follow_user, &admin_session).await;
+    assert!(body["data"].is_null(), "{}", body);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_following_feed_tags() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    setup::Submission::by(admin.id())
+        .title("First")
+        .created_at(ctx.now() + Duration::minutes(1))
+        .insert(&ctx)
+        .await;
+    let both = setup::Submission::by(admin.id())
+        .title("Both")
+        .created_at(ctx.now() + Duration::minutes(2))
+        .insert(&ctx)
+        .await;
+    tag(&ctx, both, "rust").await;
+    let tagged = setup::Submission::by(user.id())
+        .title("Tagged")
+        .created_at(ctx.now() + Duration::minutes(3))
+        .insert(&ctx)
+        .await;
+    tag(&ctx, tagged, "rust").await;
+    tag(&ctx, tagged, "async").await;
+    let unrelated = setup::Submission::by(user.id())
+        .title("Unrelated")
+        .created_at(ctx.now() + Duration::minutes(4))
+        .insert(&ctx)
+        .await;
+    tag(&ctx, unrelated, "go").await;
+
+    let follow_admin = json!({ "id": admin.id().to_string() });
+    setup::execute(&server, MUTATION_FOLLOW, follow_admin.clone(), &session).await;
+    let body = setup::execute(
+        &server,
+        MUTATION_FOLLOW_TAG,
+        json!({ "name": "Rust" }),
+        &session,
+    )
+    .await;
+    assert_eq!(body["data"]["followTag"]["name"], "rust");
+    let body = setup::execute(
+        &server,
+        MUTATION_FOLLOW_TAG,
+        json!({ "name": "unknown" }),
+        &session,
+    )
+    .await;
+    assert!(body["data"].is_null(), "{}", body);
+
+    // submissions matching both a followed user and tag are listed once
+    let body = setup::execute(
+        &server,
+        QUERY_FEED_SOURCES,
+        json!({ "first": 10 }),
+        &session,
+    )
+    .await;
+    assert_eq!(
+        body["data"]["viewer"]["followedTags"],
+        json!([{ "name": "rust" }])
+    );
+    assert_eq!(
+        body["data"]["viewer"]["followingFeed"]["edges"],
+        json!([
+            { "node": { "url": { "title": "Tagged" }, "source": { "followedUser": false, "followedTags": ["rust"] } } },
+            { "node": { "url": { "title": "Both" }, "source": { "followedUser": true, "followedTags": ["rust"] } } },
+            { "node": { "url": { "title": "First" }, "source": { "followedUser": true, "followedTags": [] } } },
+        ])
+    );
+
+    // unfollowing removes submissions from subsequent pages
+    let body = setup::execute(&server, QUERY_FEED_SOURCES, json!({ "first": 1 }), &session).await;
+    let after = body["data"]["viewer"]["followingFeed"]["pageInfo"]["endCursor"].clone();
+    setup::execute(
+        &server,
+        MUTATION_UNFOLLOW_TAG,
+        json!({ "name": "rust" }),
+        &session,
+    )
+    .await;
+    let vars = json!({ "first": 10, "after": after });
+    let body = setup::execute(&server, QUERY_FEED_SOURCES, vars, &session).await;
+    assert_eq!(body["data"]["viewer"]["followedTags"], json!([]));
+    assert_eq!(
+        body["data"]["viewer"]["followingFeed"]["edges"],
+        json!([
+            { "node": { "url": { "title": "Both" }, "source": { "followedUser": true, "followedTags": [] } } },
+            { "node": { "url": { "title": "First" }, "source": { "followedUser": true, "followedTags": [] } } },
+        ])
+    );
+    setup::execute(&server, MUTATION_UNFOLLOW, follow_admin, &session).await;
+    assert!(feed!(&server, &session).is_empty());
+}
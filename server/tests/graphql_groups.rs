@@ -0,0 +1,375 @@
+use serde_json::{json, Value};
+use server::db::models::{NewUserInput, User};
+mod setup;
+
+const MUTATION_CREATE: &str = "
+    mutation CreateGroup($input: NewGroupInput!) {
+        createGroup(input: $input) { id slug memberCount }
+    }
+";
+
+const MUTATION_UPDATE: &str = "
+    mutation UpdateGroup($id: ID!, $input: UpdateGroupInput!) {
+        updateGroup(id: $id, input: $input) { visibility syndicated }
+    }
+";
+
+const MUTATION_JOIN: &str = "
+    mutation JoinGroup($id: ID!) {
+        joinGroup(id: $id) { memberCount viewerIsMember }
+    }
+";
+
+const MUTATION_LEAVE: &str = "
+    mutation LeaveGroup($id: ID!) {
+        leaveGroup(id: $id) { memberCount viewerIsMember viewerIsModerator }
+    }
+";
+
+const MUTATION_APPOINT: &str = "
+    mutation AppointModerator($group: ID!, $user: ID!) {
+        appointGroupModerator(groupId: $group, userId: $user) {
+            moderators { username }
+        }
+    }
+";
+
+const MUTATION_DISMISS: &str = "
+    mutation DismissModerator($group: ID!, $user: ID!) {
+        dismissGroupModerator(groupId: $group, userId: $user) {
+            moderators { username }
+        }
+    }
+";
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id }
+        }
+    }
+";
+
+const MUTATION_LOCK: &str = "
+    mutation LockUrl($id: ID!) {
+        lockUrl(id: $id) { locked }
+    }
+";
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!) {
+        addComment(urlId: $url, body: $body) { id }
+    }
+";
+
+const MUTATION_DELETE_COMMENT: &str = "
+    mutation DeleteComment($id: ID!) {
+        deleteComment(comment: $id) { id }
+    }
+";
+
+const QUERY_FEEDS: &str = "
+    query Feeds($slug: String!) {
+        submissions(first: 10) {
+            edges {
+                node { id }
+            }
+        }
+        group(slug: $slug) {
+            urls(first: 10) {
+                edges {
+                    node {
+                        id
+                        group { slug }
+                    }
+                }
+            }
+        }
+        user(username: \"test-user\") {
+            urls(first: 10) {
+                edges {
+                    node { id }
+                }
+            }
+        }
+    }
+";
+
+const QUERY_GROUPS: &str = "
+    query Groups {
+        groups(first: 10) {
+            edges {
+                node { slug }
+            }
+        }
+    }
+";
+
+/// Create a group with the given slug and visibility, returning its ID.
+macro_rules! create_group {
+    ($server:expr, $session:expr, $slug:expr, $visibility:expr) => {{
+        let vars = json!({
+            "input": { "slug": $slug, "title": "A group", "visibility": $visibility },
+        });
+        let body = setup::execute($server, MUTATION_CREATE, vars, $session).await;
+        assert_eq!(body["data"]["createGroup"]["memberCount"], 1, "{}", body);
+        body["data"]["createGroup"]["id"].clone()
+    }};
+}
+
+/// The IDs of the nodes of the given connection.
+fn ids(connection: &Value) -> Vec<Value> {
+    connection["edges"]
+        .as_array()
+        .expect(&connection.to_string())
+        .iter()
+        .map(|edge| edge["node"]["id"].clone())
+        .collect()
+}
+
+/// Create a verified user with the given name, returning
+/// their ID and session.
+async fn create_user(ctx: &server::Context, name: &str, email: &str) -> (String, String) {
+    let input = NewUserInput {
+        name: name.into(),
+        email: email.into(),
+    };
+    let mut user = User::create(ctx, input).await.unwrap();
+    user.mark_email_verified(ctx).await.unwrap();
+    (
+        user.id().to_string(),
+        setup::session_token(ctx, email).await,
+    )
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_group_membership_and_feeds() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let group = create_group!(&server, &session, "Rust", "PUBLIC");
+    let hidden = create_group!(&server, &session, "hidden", "UNLISTED");
+    let vars = json!({ "input": { "slug": "rust", "title": "Taken" } });
+    let body = setup::execute(&server, MUTATION_CREATE, vars, &admin_session).await;
+    assert_eq!(body["errors"][0]["message"], "This slug is already taken");
+    let body = setup::execute(&server, QUERY_GROUPS, json!({}), "").await;
+    assert_eq!(
+        body["data"]["groups"]["edges"],
+        json!([{ "node": { "slug": "rust" } }])
+    );
+
+    // only members may submit to a group
+    let vars = json!({ "input": { "title": "Grouped", "text": "Some text", "groupId": group } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &admin_session).await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Only members of a group can submit to it"
+    );
+    let vars = json!({ "id": group });
+    let body = setup::execute(&server, MUTATION_JOIN, vars.clone(), &admin_session).await;
+    assert_eq!(
+        body["data"]["joinGroup"],
+        json!({ "memberCount": 2, "viewerIsMember": true })
+    );
+    let body = setup::execute(&server, MUTATION_JOIN, vars.clone(), &admin_session).await;
+    assert_eq!(body["data"]["joinGroup"]["memberCount"], 2);
+    let body = setup::execute(&server, MUTATION_LEAVE, vars.clone(), &admin_session).await;
+    assert_eq!(
+        body["data"]["leaveGroup"],
+        json!({ "memberCount": 1, "viewerIsMember": false, "viewerIsModerator": false })
+    );
+    let body = setup::execute(&server, MUTATION_LEAVE, vars, &session).await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "The creator of a group can not leave it"
+    );
+
+    let vars =
+        json!({ "input": { "title": "Grouped", "text": "Some text", "groupId": Value::Null } });
+    let global = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let global = global["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "input": { "title": "Grouped", "text": "Some text", "groupId": group } });
+    let grouped = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let grouped = grouped["data"]["submitUrl"]["url"]["id"].clone();
+
+    // group submissions are listed in their group and profile,
+    // but not on the front page until the group is syndicated
+    let body = setup::execute(&server, QUERY_FEEDS, json!({ "slug": "RUST" }), "").await;
+    let data = &body["data"];
+    assert_eq!(ids(&data["submissions"]), vec![global.clone()], "{}", body);
+    assert_eq!(ids(&data["group"]["urls"]), vec![grouped.clone()]);
+    assert_eq!(
+        data["group"]["urls"]["edges"][0]["node"]["group"]["slug"],
+        "rust"
+    );
+    assert_eq!(
+        ids(&data["user"]["urls"]),
+        vec![grouped.clone(), global.clone()]
+    );
+
+    let input = json!({ "syndicated": true });
+    let vars = json!({ "id": group, "input": input });
+    let body = setup::execute(&server, MUTATION_UPDATE, vars, &session).await;
+    assert_eq!(
+        body["data"]["updateGroup"],
+        json!({ "visibility": "PUBLIC", "syndicated": true })
+    );
+    let body = setup::execute(&server, QUERY_FEEDS, json!({ "slug": "rust" }), "").await;
+    assert_eq!(
+        ids(&body["data"]["submissions"]),
+        vec![grouped.clone(), global.clone()]
+    );
+
+    // unlisted groups are never syndicated
+    let vars = json!({ "id": hidden, "input": input });
+    let body = setup::execute(&server, MUTATION_UPDATE, vars, &session).await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Only public groups can be syndicated"
+    );
+    let input = json!({ "visibility": "UNLISTED" });
+    let vars = json!({ "id": group, "input": input });
+    let body = setup::execute(&server, MUTATION_UPDATE, vars, &admin_session).await;
+    assert_eq!(
+        body["data"]["updateGroup"],
+        json!({ "visibility": "UNLISTED", "syndicated": false })
+    );
+    let body = setup::execute(&server, QUERY_FEEDS, json!({ "slug": "rust" }), "").await;
+    assert_eq!(ids(&body["data"]["submissions"]), vec![global.clone()]);
+    assert_eq!(ids(&body["data"]["group"]["urls"]), vec![grouped.clone()]);
+}
+
+const QUERY_LISTINGS: &str = "
+    query Listings($id: ID!) {
+        search(query: \"grouped\") {
+            results(first: 10) {
+                edges {
+                    node { id }
+                }
+            }
+        }
+        fetch__Url(id: $id) {
+            related { id }
+        }
+    }
+";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unsyndicated_groups_are_not_listed() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let group = create_group!(&server, &session, "rust", "PUBLIC");
+    let vars =
+        json!({ "input": { "title": "Grouped", "text": "Some text", "groupId": Value::Null } });
+    let global = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let global = global["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "input": { "title": "Grouped", "text": "Some text", "groupId": group } });
+    let grouped = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let grouped = grouped["data"]["submitUrl"]["url"]["id"].clone();
+
+    // submissions to groups which are not syndicated are left out of
+    // search results, related submissions, and the sitemap
+    let vars = json!({ "id": global });
+    let body = setup::execute(&server, QUERY_LISTINGS, vars.clone(), "").await;
+    assert_eq!(
+        ids(&body["data"]["search"]["results"]),
+        vec![global.clone()],
+        "{}",
+        body
+    );
+    assert_eq!(body["data"]["fetch__Url"]["related"], json!([]));
+    let res = warp::test::request()
+        .path("/sitemap.xml")
+        .reply(&server)
+        .await;
+    let sitemap = String::from_utf8(res.body().to_vec()).unwrap();
+    assert!(sitemap.contains(global.as_str().unwrap()));
+    assert!(!sitemap.contains(grouped.as_str().unwrap()));
+
+    let input = json!({ "syndicated": true });
+    let vars = json!({ "id": group, "input": input });
+    let body = setup::execute(&server, MUTATION_UPDATE, vars, &session).await;
+    assert_eq!(body["data"]["updateGroup"]["syndicated"], true);
+    let vars = json!({ "id": global });
+    let body = setup::execute(&server, QUERY_LISTINGS, vars, "").await;
+    let mut results = ids(&body["data"]["search"]["results"]);
+    results.sort_by_key(|id| id.to_string());
+    let mut expected = vec![global.clone(), grouped.clone()];
+    expected.sort_by_key(|id| id.to_string());
+    assert_eq!(results, expected);
+    assert_eq!(
+        body["data"]["fetch__Url"]["related"],
+        json!([{ "id": grouped }])
+    );
+    let res = warp::test::request()
+        .path("/sitemap.xml")
+        .reply(&server)
+        .await;
+    let sitemap = String::from_utf8(res.body().to_vec()).unwrap();
+    assert!(sitemap.contains(grouped.as_str().unwrap()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_group_moderators() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let (other, other_session) = create_user(&ctx, "Other", "test.other@urls.fyi").await;
+
+    let group = create_group!(&server, &session, "rust", "PUBLIC");
+    let vars = json!({ "input": { "title": "Grouped", "text": "Some text", "groupId": group } });
+    let url = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = url["data"]["submitUrl"]["url"]["id"].clone();
+    let vars =
+        json!({ "input": { "title": "Grouped", "text": "Some text", "groupId": Value::Null } });
+    let outside = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let outside = outside["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "url": url, "body": "A comment" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    let comment = body["data"]["addComment"]["id"].clone();
+
+    // only members may be appointed, and only by the creator
+    let vars = json!({ "group": group, "user": other });
+    let body = setup::execute(&server, MUTATION_APPOINT, vars.clone(), &session).await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Only members can moderate a group"
+    );
+    setup::execute(
+        &server,
+        MUTATION_JOIN,
+        json!({ "id": group }),
+        &other_session,
+    )
+    .await;
+    let body = setup::execute(&server, MUTATION_APPOINT, vars.clone(), &other_session).await;
+    assert_eq!(body["data"], Value::Null);
+    let body = setup::execute(&server, MUTATION_LOCK, json!({ "id": url }), &other_session).await;
+    assert_eq!(body["data"], Value::Null);
+    let body = setup::execute(&server, MUTATION_APPOINT, vars.clone(), &session).await;
+    assert_eq!(
+        body["data"]["appointGroupModerator"]["moderators"],
+        json!([{ "username": "test-user" }, { "username": "other" }])
+    );
+
+    // moderators moderate submissions to their group only
+    let vars = json!({ "id": comment });
+    let body = setup::execute(&server, MUTATION_DELETE_COMMENT, vars, &other_session).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let body = setup::execute(&server, MUTATION_LOCK, json!({ "id": url }), &other_session).await;
+    assert_eq!(body["data"]["lockUrl"]["locked"], true, "{}", body);
+    let vars = json!({ "id": outside });
+    let body = setup::execute(&server, MUTATION_LOCK, vars, &other_session).await;
+    assert_eq!(body["errors"][0]["message"], "Not authorized");
+
+    // dismissed moderators lose their permissions
+    let vars = json!({ "group": group, "user": other });
+    let body = setup::execute(&server, MUTATION_DISMISS, vars, &session).await;
+    assert_eq!(
+        body["data"]["dismissGroupModerator"]["moderators"],
+        json!([{ "username": "test-user" }])
+    );
+    let body = setup::execute(&server, MUTATION_LOCK, json!({ "id": url }), &other_session).await;
+    assert_eq!(body["errors"][0]["message"], "Not authorized");
+}
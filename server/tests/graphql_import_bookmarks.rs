@@ -0,0 +1,397 @@
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use server::db::models::User;
+use warp::test::RequestBuilder;
+mod setup;
+
+const FIREFOX: &[u8] = include_bytes!("fixtures/bookmarks_firefox.html");
+const CHROME: &[u8] = include_bytes!("fixtures/bookmarks_chrome.html");
+
+const MUTATION_IMPORT: &str = "
+    mutation ImportBookmarks($file: Upload!, $visibility: ImportVisibility!) {
+        importBookmarks(file: $file, visibility: $visibility) {
+            imported
+            skippedDuplicates
+            errors
+        }
+    }
+";
+
+const MUTATION_PUBLISH: &str = "
+    mutation PublishUrl($id: ID!) {
+        publishUrl(id: $id) { isDraft }
+    }
+";
+
+const MUTATION_VOTE: &str = "
+    mutation VoteUrl($id: ID!) {
+        voteUrl(id: $id) { isDraft }
+    }
+";
+
+const MUTATION_SAVE: &str = "
+    mutation SaveUrl($id: ID!) {
+        saveUrl(id: $id) { isDraft }
+    }
+";
+
+const QUERY_FETCH: &str = "
+    query FetchUrl($id: ID!) {
+        fetch__Url(id: $id) { isDraft }
+    }
+";
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id isDraft title }
+            duplicate
+        }
+    }
+";
+
+const QUERY_DRAFTS: &str = "
+    query Drafts {
+        viewer {
+            drafts(first: 10) {
+                edges {
+                    node {
+                        id
+                        url
+                        title
+                        isDraft
+                        createdAt
+                        tags { name }
+                    }
+                }
+            }
+        }
+    }
+";
+
+const QUERY_SAVED: &str = "
+    query Saved {
+        viewer {
+            savedUrls(first: 10, status: ALL) {
+                edges {
+                    node { url isDraft }
+                }
+            }
+        }
+    }
+";
+
+const QUERY_LISTINGS: &str = "
+    query Listings {
+        submissions(first: 10) {
+            edges {
+                node { url }
+            }
+        }
+        tags(first: 10) {
+            edges {
+                node { name count }
+            }
+        }
+    }
+";
+
+/// Encode the given parts as a multipart form. Parts
+/// other than `operations` and `map` are sent as files.
+fn multipart(parts: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut body = vec![];
+    for (name, data) in parts {
+        let disposition = match *name {
+            "operations" | "map" => format!("form-data; name=\"{}\"", name),
+            _ => format!("form-data; name=\"{}\"; filename=\"bookmarks.html\"", name),
+        };
+        body.extend_from_slice(b"--BOUNDARY\r\n");
+        body.extend_from_slice(format!("Content-Disposition: {}\r\n\r\n", disposition).as_bytes());
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(b"--BOUNDARY--\r\n");
+    body
+}
+
+/// Constructs a multipart GraphQL request, which uploads
+/// the given file as the `file` variable of the request.
+fn upload(query: &str, variables: Value, file: &[u8], session: &str) -> RequestBuilder {
+    let operations = json!({ "query": query, "variables": variables }).to_string();
+    let map = json!({ "0": ["variables.file"] }).to_string();
+    let body = multipart(&[
+        ("operations", operations.as_bytes()),
+        ("map", map.as_bytes()),
+        ("0", file),
+    ]);
+    warp::test::request()
+        .path("/graphql")
+        .method("POST")
+        .header("Cookie", format!("xsrf=fake_xsrf; session={}", session))
+        .header("X-XSRF-Token", "fake_xsrf")
+        .header("Content-Type", "multipart/form-data; boundary=BOUNDARY")
+        .body(body)
+}
+
+/// Import the given bookmarks file, returning
+/// the GraphQL response.
+macro_rules! import {
+    ($server:expr, $session:expr, $file:expr, $visibility:expr) => {{
+        let vars = json!({ "file": null, "visibility": $visibility });
+        let res = upload(MUTATION_IMPORT, vars, $file, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_import_firefox_drafts() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let body = import!(&server, &session, FIREFOX, "DRAFT");
+    let report = &body["data"]["importBookmarks"];
+    assert_eq!(report["imported"], 4, "{}", body);
+    assert_eq!(report["skippedDuplicates"], 1);
+    let errors = report["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].as_str().unwrap().starts_with("place:"));
+    assert!(errors[1].as_str().unwrap().starts_with("javascript:"));
+
+    // the newest bookmark is listed first
+    let data = setup::execute_ok(&server, QUERY_DRAFTS, json!({}), &session).await;
+    let drafts: Vec<Value> = data["viewer"]["drafts"]["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| edge["node"].clone())
+        .collect();
+    let links: Vec<&str> = drafts
+        .iter()
+        .map(|draft| draft["url"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        links,
+        vec![
+            "https://example.com/news",
+            "https://example.com/sqlite",
+            "https://example.com/async?utm_source=newsletter",
+            "https://example.com/rust-book",
+        ]
+    );
+    assert!(drafts.iter().all(|draft| draft["isDraft"] == true));
+
+    let book = &drafts[3];
+    assert_eq!(book["title"], "The Rust Programming Language");
+    let created_at: DateTime<Utc> = book["createdAt"].as_str().unwrap().parse().unwrap();
+    assert_eq!(created_at.timestamp(), 1633100000);
+    let mut tags: Vec<&str> = book["tags"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tag| tag["name"].as_str().unwrap())
+        .collect();
+    tags.sort_unstable();
+    assert_eq!(tags, vec!["programming", "rust"]);
+    assert_eq!(drafts[1]["title"], "SQLite – Documentation");
+    assert_eq!(drafts[2]["title"], "Async & Await");
+    // the toolbar is not a tag
+    assert_eq!(drafts[0]["tags"], json!([]));
+
+    // drafts and their tags are not listed publicly
+    let data = setup::execute_ok(&server, QUERY_LISTINGS, json!({}), "").await;
+    assert_eq!(data["submissions"]["edges"], json!([]));
+    assert_eq!(data["tags"]["edges"], json!([]));
+
+    // importing again skips everything
+    let body = import!(&server, &session, FIREFOX, "DRAFT");
+    let report = &body["data"]["importBookmarks"];
+    assert_eq!(report["imported"], 0);
+    assert_eq!(report["skippedDuplicates"], 5);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_publish_drafts() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    import!(&server, &session, FIREFOX, "DRAFT");
+
+    let data = setup::execute_ok(&server, QUERY_DRAFTS, json!({}), &session).await;
+    let edges = data["viewer"]["drafts"]["edges"].as_array().unwrap();
+    let id = |link: &str| {
+        edges
+            .iter()
+            .find(|edge| edge["node"]["url"] == link)
+            .map(|edge| edge["node"]["id"].clone())
+            .unwrap()
+    };
+    let book = id("https://example.com/rust-book");
+
+    // drafts are private to their author, even when asked for by id
+    let vars = json!({ "id": book });
+    for query in &[QUERY_FETCH, MUTATION_PUBLISH, MUTATION_VOTE, MUTATION_SAVE] {
+        for session in &["", admin_session.as_str()] {
+            let body = setup::execute(&server, query, vars.clone(), session).await;
+            assert!(body["data"].is_null(), "{}", body);
+            assert_eq!(body["errors"][0]["extensions"]["code"], "NOT_FOUND");
+        }
+    }
+    let body = setup::execute(&server, QUERY_FETCH, vars.clone(), &session).await;
+    assert_eq!(body["data"]["fetch__Url"]["isDraft"], true, "{}", body);
+
+    let body = setup::execute(&server, MUTATION_PUBLISH, vars, &session).await;
+    assert_eq!(body["data"]["publishUrl"]["isDraft"], false, "{}", body);
+
+    let data = setup::execute_ok(&server, QUERY_LISTINGS, json!({}), "").await;
+    assert_eq!(
+        data["submissions"]["edges"],
+        json!([{ "node": { "url": "https://example.com/rust-book" } }])
+    );
+    let mut tags = data["tags"]["edges"].as_array().unwrap().clone();
+    tags.sort_by_key(|edge| edge["node"]["name"].as_str().unwrap().to_string());
+    assert_eq!(
+        tags,
+        vec![
+            json!({ "node": { "name": "programming", "count": 1 } }),
+            json!({ "node": { "name": "rust", "count": 1 } }),
+        ]
+    );
+
+    // submitting the link of a draft publishes it for the submitter,
+    // without revealing the draft
+    let vars = json!({ "input": { "url": "https://example.com/sqlite", "title": "SQLite" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &admin_session).await;
+    let result = &body["data"]["submitUrl"];
+    assert_eq!(result["duplicate"], false, "{}", body);
+    assert_eq!(result["url"]["isDraft"], false);
+    assert_eq!(result["url"]["title"], "SQLite");
+    assert_eq!(result["url"]["id"], id("https://example.com/sqlite"));
+
+    let data = setup::execute_ok(&server, QUERY_DRAFTS, json!({}), &session).await;
+    assert_eq!(
+        data["viewer"]["drafts"]["edges"].as_array().unwrap().len(),
+        2
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_import_chrome_private() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    setup::Submission::by(admin.id())
+        .url("https://example.com/news")
+        .insert(&ctx)
+        .await;
+
+    let body = import!(&server, &session, CHROME, "PRIVATE");
+    let report = &body["data"]["importBookmarks"];
+    assert_eq!(report["imported"], 3, "{}", body);
+    assert_eq!(report["skippedDuplicates"], 0);
+    let errors = report["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].as_str().unwrap().starts_with("not a url:"));
+
+    // existing submissions are saved, and new links
+    // are saved as drafts
+    let data = setup::execute_ok(&server, QUERY_SAVED, json!({}), &session).await;
+    let mut saved = data["viewer"]["savedUrls"]["edges"]
+        .as_array()
+        .unwrap()
+        .clone();
+    saved.sort_by_key(|edge| edge["node"]["url"].as_str().unwrap().to_string());
+    assert_eq!(
+        saved,
+        vec![
+            json!({ "node": { "url": "https://example.com/news", "isDraft": false } }),
+            json!({ "node": { "url": "https://example.com/recipes/bread", "isDraft": true } }),
+            json!({ "node": { "url": "https://example.com/recipes/pasta", "isDraft": true } }),
+        ]
+    );
+
+    // the drafts are not listed, and not saved for anyone else
+    let data = setup::execute_ok(&server, QUERY_LISTINGS, json!({}), "").await;
+    assert_eq!(data["submissions"]["edges"].as_array().unwrap().len(), 1);
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let data = setup::execute_ok(&server, QUERY_SAVED, json!({}), &admin_session).await;
+    assert_eq!(data["viewer"]["savedUrls"]["edges"], json!([]));
+
+    let body = import!(&server, &session, CHROME, "PRIVATE");
+    let report = &body["data"]["importBookmarks"];
+    assert_eq!(report["imported"], 0);
+    assert_eq!(report["skippedDuplicates"], 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_import_validation() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // anonymous users can not import bookmarks
+    let body = import!(&server, "", FIREFOX, "DRAFT");
+    assert!(body["data"].is_null());
+    assert!(body["errors"].is_array());
+
+    // the number of bookmarks is limited
+    let mut file = String::from("<DL><p>\n");
+    for i in 0..5001 {
+        file.push_str(&format!(
+            "<DT><A HREF=\"https://example.com/{}\">{}</A>\n",
+            i, i
+        ));
+    }
+    let body = import!(&server, &session, file.as_bytes(), "DRAFT");
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Bookmark files can contain at most 5000 bookmarks"
+    );
+
+    // the file must be part of the request
+    let operations = json!({
+        "query": MUTATION_IMPORT,
+        "variables": { "file": null, "visibility": "DRAFT" },
+    })
+    .to_string();
+    let map = json!({ "0": ["variables.file"] }).to_string();
+    let res = warp::test::request()
+        .path("/graphql")
+        .method("POST")
+        .header("Cookie", format!("xsrf=fake_xsrf; session={}", session))
+        .header("X-XSRF-Token", "fake_xsrf")
+        .header("Content-Type", "multipart/form-data; boundary=BOUNDARY")
+        .body(multipart(&[
+            ("operations", operations.as_bytes()),
+            ("map", map.as_bytes()),
+        ]))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 400);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Invalid multipart request: Missing file 0"
+    );
+
+    // multipart requests need the XSRF token like any other
+    let res = warp::test::request()
+        .path("/graphql")
+        .method("POST")
+        .header("Cookie", format!("xsrf=fake_xsrf; session={}", session))
+        .header("Content-Type", "multipart/form-data; boundary=BOUNDARY")
+        .body(multipart(&[
+            ("operations", operations.as_bytes()),
+            ("map", map.as_bytes()),
+            ("0", FIREFOX),
+        ]))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 403);
+
+    let data = setup::execute_ok(&server, QUERY_DRAFTS, json!({}), &session).await;
+    assert_eq!(data["viewer"]["drafts"]["edges"], json!([]));
+}
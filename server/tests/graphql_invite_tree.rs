@@ -0,0 +1,191 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::models::{Invite, NewUserInput, User};
+use server::Context;
+mod setup;
+
+const QUERY_TREE: &str = "
+    query InviteTree($root: ID!, $depth: Int!) {
+        inviteTree(rootUserId: $root, depth: $depth) {
+            user { name }
+            depth
+            inviteeCount
+            invitees {
+                user { name }
+                depth
+                inviteeCount
+                invitees {
+                    user { name }
+                    depth
+                    inviteeCount
+                    invitees {
+                        user { name }
+                        depth
+                        inviteeCount
+                    }
+                }
+            }
+        }
+    }
+";
+
+const QUERY_INVITEES: &str = "
+    query Invitees($id: ID!) {
+        fetch__User(id: $id) {
+            invitedBy { name }
+            invitees {
+                edges {
+                    node { name }
+                }
+            }
+        }
+    }
+";
+
+/// Register a new user with an invite issued by the given
+/// user. Users are created `minutes` after the mock users,
+/// such that their order is well defined.
+async fn invite_user(ctx: &Context, inviter: &User, name: &str, minutes: i64) -> User {
+    let mut ctx = ctx.clone();
+    ctx.set_request_time(ctx.now() + Duration::minutes(minutes));
+    let invite = Invite::create(&ctx, inviter).await.unwrap();
+    let input = NewUserInput {
+        name: name.into(),
+        email: format!("tree.{}@urls.fyi", name.to_lowercase()),
+    };
+    User::create_with_invite(&ctx, input, invite).await.unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invite_tree() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let alice = invite_user(&ctx, &admin, "Alice", 1).await;
+    let _bob = invite_user(&ctx, &admin, "Bob", 2).await;
+    let carol = invite_user(&ctx, &alice, "Carol", 3).await;
+    let _dave = invite_user(&ctx, &carol, "Dave", 4).await;
+
+    let session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let vars = json!({ "root": admin.id().to_string(), "depth": 3 });
+    let res = setup::graphql(QUERY_TREE, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body,
+        json!({
+            "data": {
+                "inviteTree": {
+                    "user": { "name": "Test Administrator" },
+                    "depth": 0,
+                    "inviteeCount": 2,
+                    "invitees": [
+                        {
+                            "user": { "name": "Alice" },
+                            "depth": 1,
+                            "inviteeCount": 1,
+                            "invitees": [
+                                {
+                                    "user": { "name": "Carol" },
+                                    "depth": 2,
+                                    "inviteeCount": 1,
+                                    "invitees": [
+                                        {
+                                            "user": { "name": "Dave" },
+                                            "depth": 3,
+                                            "inviteeCount": 0,
+                                        },
+                                    ],
+                                },
+                            ],
+                        },
+                        {
+                            "user": { "name": "Bob" },
+                            "depth": 1,
+                            "inviteeCount": 0,
+                            "invitees": [],
+                        },
+                    ],
+                },
+            },
+        })
+    );
+
+    // invitees below the requested depth are counted, but not loaded
+    let vars = json!({ "root": alice.id().to_string(), "depth": 1 });
+    let res = setup::graphql(QUERY_TREE, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let carol_node = &body["data"]["inviteTree"]["invitees"][0];
+    assert_eq!(carol_node["user"]["name"], "Carol");
+    assert_eq!(carol_node["inviteeCount"], 1);
+    assert_eq!(carol_node["invitees"], json!([]));
+
+    // excessive depths are capped rather than rejected
+    let vars = json!({ "root": admin.id().to_string(), "depth": 1000 });
+    let res = setup::graphql(QUERY_TREE, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["inviteTree"]["inviteeCount"], 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invite_tree_permissions() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let alice = invite_user(&ctx, &admin, "Alice", 1).await;
+    let carol = invite_user(&ctx, &alice, "Carol", 2).await;
+    let _dave = invite_user(&ctx, &carol, "Dave", 3).await;
+
+    // regular users may not query the tree
+    let session = setup::session_token(&ctx, "tree.alice@urls.fyi").await;
+    let vars = json!({ "root": alice.id().to_string(), "depth": 3 });
+    let res = setup::graphql(QUERY_TREE, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert_eq!(body["errors"][0]["message"], "Not authorized");
+
+    // but they can list their own invitees
+    let vars = json!({ "id": alice.id().to_string() });
+    let res = setup::graphql(QUERY_INVITEES, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["fetch__User"],
+        json!({
+            "invitedBy": { "name": "Test Administrator" },
+            "invitees": { "edges": [{ "node": { "name": "Carol" } }] },
+        })
+    );
+
+    // and not those of their invitees
+    let vars = json!({ "id": carol.id().to_string() });
+    let res = setup::graphql(QUERY_INVITEES, vars.clone(), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["errors"][0]["message"], "Not authorized");
+
+    // which administrators can
+    let session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let res = setup::graphql(QUERY_INVITEES, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["fetch__User"],
+        json!({
+            "invitedBy": { "name": "Alice" },
+            "invitees": { "edges": [{ "node": { "name": "Dave" } }] },
+        })
+    );
+}
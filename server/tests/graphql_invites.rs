@@ -1,4 +1,6 @@
+use chrono::Duration;
 use serde_json::{json, Value};
+use server::db::models::{Invite, User};
 mod setup;
 
 #[tokio::test(flavor = "multi_thread")]
@@ -150,3 +152,222 @@ async fn test_create_account() {
     assert!(body.as_object().unwrap().get("data").unwrap().is_null());
     assert!(body.as_object().unwrap().contains_key("errors"));
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invite_quota() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let session_admin = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let query_quota = "
+        query InviteQuota {
+            viewer {
+                inviteQuota {
+                    total
+                    used
+                    remaining
+                }
+            }
+        }
+    ";
+    let query_issue = "
+        mutation IssueInvite {
+            issueInvite {
+                id
+            }
+        }
+    ";
+    let query_revoke = "
+        mutation RevokeInvite($invite: ID!) {
+            revokeInvite(invite: $invite) {
+                revokedAt
+            }
+        }
+    ";
+
+    let res = setup::graphql(query_quota, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["inviteQuota"],
+        json!({ "total": 3, "used": 0, "remaining": 3 })
+    );
+
+    let res = setup::graphql(query_issue, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let invite = body["data"]["issueInvite"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let res = setup::graphql(query_quota, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["inviteQuota"],
+        json!({ "total": 3, "used": 1, "remaining": 2 })
+    );
+
+    // revoked invites don't count towards the quota
+    let res = setup::graphql(query_revoke, json!({ "invite": invite }), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"]["revokeInvite"]["revokedAt"].is_string());
+
+    let res = setup::graphql(query_quota, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["inviteQuota"],
+        json!({ "total": 3, "used": 0, "remaining": 3 })
+    );
+
+    // admins can override the quota of other users
+    let query_set = "
+        mutation SetInviteQuota($email: String!, $quota: Int) {
+            setInviteQuota(email: $email, quota: $quota) {
+                name
+            }
+        }
+    ";
+    let vars = json!({ "email": "test.user@urls.fyi", "quota": 5 });
+    let res = setup::graphql(query_set, vars.clone(), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+
+    let res = setup::graphql(query_set, vars, &session_admin)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["setInviteQuota"]["name"], "Test User");
+
+    let res = setup::graphql(query_quota, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["inviteQuota"],
+        json!({ "total": 5, "used": 0, "remaining": 5 })
+    );
+
+    // admins have unlimited invites
+    let res = setup::graphql(query_quota, json!({}), &session_admin)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["inviteQuota"],
+        json!({ "total": null, "used": 0, "remaining": null })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invite_quota_concurrent() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let query = "
+        mutation IssueInvite {
+            issueInvite {
+                id
+            }
+        }
+    ";
+
+    let responses = futures_util::future::join_all(
+        (0..8).map(|_| setup::graphql(query, json!({}), &session).reply(&server)),
+    )
+    .await;
+
+    let issued = responses
+        .iter()
+        .filter(|res| {
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+            !body["data"].is_null()
+        })
+        .count();
+    assert_eq!(issued, 3);
+
+    let errors: Vec<String> = responses
+        .iter()
+        .filter_map(|res| {
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+            body["errors"][0]["message"].as_str().map(str::to_string)
+        })
+        .collect();
+    assert_eq!(errors.len(), 5);
+    assert!(errors
+        .iter()
+        .all(|message| message.starts_with("Invite quota exhausted")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invite_accrual() {
+    let (server, ctx) = setup::mock().await;
+    let session_admin = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let created = user.last_accrual_at();
+
+    // one invitation is earned per week
+    let mut later = ctx.clone();
+    later.set_request_time(ctx.now() + Duration::days(8));
+    let user = User::find_by_email(&later, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let quota = Invite::quota(&later, &user).await.unwrap();
+    assert_eq!(quota.total, Some(4));
+    assert_eq!(quota.remaining, Some(4));
+    assert_eq!(quota.next_accrual_at, Some(created + Duration::days(14)));
+
+    // accrual is recorded, and does not repeat
+    let user = User::find_by_email(&later, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    assert_eq!(user.last_accrual_at(), created + Duration::days(7));
+    let quota = Invite::quota(&later, &user).await.unwrap();
+    assert_eq!(quota.total, Some(4));
+
+    // earning stops once five invitations are available
+    let mut later = ctx.clone();
+    later.set_request_time(ctx.now() + Duration::days(70));
+    let user = User::find_by_email(&later, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let quota = Invite::quota(&later, &user).await.unwrap();
+    assert_eq!(quota.total, Some(5));
+    assert_eq!(quota.remaining, Some(5));
+    assert_eq!(quota.next_accrual_at, None);
+
+    // admins can grant bonus invitations beyond the bank
+    let query = "
+        mutation GrantInvites($user: ID!, $count: Int!) {
+            grantInvites(user: $user, count: $count) {
+                name
+            }
+        }
+    ";
+    let vars = json!({ "user": user.id().to_string(), "count": 2 });
+    let res = setup::graphql(query, vars, &session_admin)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["grantInvites"]["name"], "Test User");
+
+    let user = User::find_by_email(&later, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let quota = Invite::quota(&later, &user).await.unwrap();
+    assert_eq!(quota.total, Some(7));
+    assert_eq!(quota.remaining, Some(7));
+    assert_eq!(quota.next_accrual_at, None);
+}
@@ -0,0 +1,191 @@
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::json;
+use server::db::id::{CommentID, UrlID};
+use server::db::models::User;
+use server::schema::{comments, urls, users};
+use server::Config;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id }
+        }
+    }
+";
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!) {
+        addComment(urlId: $url, body: $body) { id }
+    }
+";
+
+const MUTATION_VOTE: &str = "
+    mutation VoteUrl($id: ID!, $direction: VoteDirection!) {
+        voteUrl(id: $id, direction: $direction) { score }
+    }
+";
+
+const MUTATION_UNVOTE: &str = "
+    mutation UnvoteUrl($id: ID!) {
+        unvoteUrl(id: $id) { score }
+    }
+";
+
+const MUTATION_VOTE_COMMENT: &str = "
+    mutation VoteComment($id: ID!) {
+        voteComment(id: $id) { score }
+    }
+";
+
+const MUTATION_UNVOTE_COMMENT: &str = "
+    mutation UnvoteComment($id: ID!) {
+        unvoteComment(id: $id) { score }
+    }
+";
+
+const QUERY_KARMA: &str = "
+    query Karma {
+        viewer { karma }
+        user(username: \"test-user\") { karma }
+    }
+";
+
+/// The karma of the test user, as seen on their profile,
+/// after checking it matches the karma on the viewer.
+macro_rules! karma {
+    ($server:expr, $session:expr) => {{
+        let body = setup::execute($server, QUERY_KARMA, json!({}), $session).await;
+        assert_eq!(body["data"]["viewer"], body["data"]["user"], "{}", body);
+        body["data"]["user"]["karma"].as_i64().unwrap()
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_karma_follows_votes() {
+    let config = Config::test()
+        .with_downvotes_enabled(true)
+        .with_karma_weights(2, 3)
+        .with_anonymous_submissions(true);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let vars = json!({ "input": { "title": "Karma", "text": "Some text", "anonymous": false } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = body["data"]["submitUrl"]["url"]["id"].clone();
+    assert_eq!(karma!(&server, &session), 0);
+
+    let cases = [("UP", 2), ("UP", 2), ("DOWN", -2), ("UP", 2)];
+    for (direction, expected) in cases {
+        let vars = json!({ "id": url, "direction": direction });
+        let body = setup::execute(&server, MUTATION_VOTE, vars, &admin_session).await;
+        assert!(body["errors"].is_null(), "{}", body);
+        assert_eq!(karma!(&server, &session), expected, "{}", direction);
+    }
+    setup::execute(
+        &server,
+        MUTATION_UNVOTE,
+        json!({ "id": url }),
+        &admin_session,
+    )
+    .await;
+    assert_eq!(karma!(&server, &session), 0);
+
+    let vars = json!({ "url": url, "body": "A comment" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    let comment = body["data"]["addComment"]["id"].clone();
+    let vars = json!({ "id": comment });
+    setup::execute(&server, MUTATION_VOTE_COMMENT, vars.clone(), &admin_session).await;
+    setup::execute(&server, MUTATION_VOTE_COMMENT, vars.clone(), &admin_session).await;
+    assert_eq!(karma!(&server, &session), 3);
+    setup::execute(&server, MUTATION_UNVOTE_COMMENT, vars, &admin_session).await;
+    assert_eq!(karma!(&server, &session), 0);
+
+    // anonymous submissions don't earn karma
+    let vars = json!({ "input": { "title": "Karma", "text": "Some text", "anonymous": true } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let anonymous = body["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "id": anonymous, "direction": "UP" });
+    let body = setup::execute(&server, MUTATION_VOTE, vars, &admin_session).await;
+    assert_eq!(body["data"]["voteUrl"]["score"], 1);
+    assert_eq!(karma!(&server, &session), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_karma_backfill_and_reconciliation() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    // scores from before karma was stored
+    let vars = json!({ "input": { "title": "Karma", "text": "Some text", "anonymous": false } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = body["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "url": url, "body": "A comment" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    let url: UrlID = url.as_str().unwrap().parse().unwrap();
+    let comment: CommentID = body["data"]["addComment"]["id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    let conn = ctx.conn().await.unwrap();
+    diesel::update(urls::table.find(url))
+        .set(urls::dsl::score.eq(5))
+        .execute(&*conn)
+        .unwrap();
+    diesel::update(comments::table.find(comment))
+        .set(comments::dsl::score.eq(2))
+        .execute(&*conn)
+        .unwrap();
+    assert_eq!(karma!(&server, &session), 0);
+
+    User::backfill_karma(&*conn, ctx.config()).unwrap();
+    assert_eq!(karma!(&server, &session), 7);
+
+    // the backfill only computes karma users don't have yet,
+    // while the reconciliation corrects any drift
+    diesel::update(users::table.find(user.id()))
+        .set(users::dsl::karma.eq(100))
+        .execute(&*conn)
+        .unwrap();
+    User::backfill_karma(&*conn, ctx.config()).unwrap();
+    assert_eq!(karma!(&server, &session), 100);
+    assert_eq!(User::reconcile_karma(&ctx).await.unwrap(), 1);
+    assert_eq!(karma!(&server, &session), 7);
+    assert_eq!(User::reconcile_karma(&ctx).await.unwrap(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_downvotes_need_karma() {
+    let config = Config::test()
+        .with_downvotes_enabled(true)
+        .with_downvote_min_karma(1);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let vars = json!({ "input": { "title": "Karma", "text": "Some text", "anonymous": false } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &admin_session).await;
+    let admin_url = body["data"]["submitUrl"]["url"]["id"].clone();
+    let downvote = json!({ "id": admin_url, "direction": "DOWN" });
+    let body = setup::execute(&server, MUTATION_VOTE, downvote.clone(), &session).await;
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "You need at least 1 karma to downvote"
+    );
+
+    // upvotes don't need karma, and earn it
+    let vars = json!({ "input": { "title": "Karma", "text": "Some text", "anonymous": false } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = body["data"]["submitUrl"]["url"]["id"].clone();
+    let upvote = json!({ "id": url, "direction": "UP" });
+    setup::execute(&server, MUTATION_VOTE, upvote, &admin_session).await;
+    assert_eq!(karma!(&server, &session), 1);
+    let body = setup::execute(&server, MUTATION_VOTE, downvote, &session).await;
+    assert_eq!(body["data"]["voteUrl"]["score"], -1, "{}", body);
+}
@@ -0,0 +1,170 @@
+use serde_json::{json, Value};
+use server::db::models::User;
+use warp::Filter;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($url: String!) {
+        submitUrl(input: { url: $url }) {
+            url { language }
+        }
+    }
+";
+
+const QUERY_LISTINGS: &str = "
+    query Listings($languages: [String!]) {
+        submissions(first: 10, languages: $languages) {
+            edges {
+                node { title }
+            }
+        }
+        search(query: \"languages\") {
+            results(first: 10, languages: $languages) {
+                edges {
+                    node { title }
+                }
+            }
+        }
+    }
+";
+
+const MUTATION_PREFERENCES: &str = "
+    mutation UpdatePreferences($languages: [String!]!) {
+        updatePreferences(input: { languages: $languages }) {
+            preferences { languages }
+        }
+    }
+";
+
+/// Pages in different languages, and a page too short
+/// to tell its language.
+const PAGES: &[(&str, &str, &str)] = &[
+    (
+        "en",
+        "How we moved our database to a new data center",
+        "A look back at the migration, what went wrong along the way, \
+        and what we would do differently if we had to do it again.",
+    ),
+    (
+        "de",
+        "Wie wir unsere Datenbank in ein neues Rechenzentrum umgezogen haben",
+        "Ein Rückblick auf den Umzug, was dabei schiefgelaufen ist, und \
+        was wir anders machen würden, wenn wir es noch einmal tun müssten.",
+    ),
+    (
+        "es",
+        "Cómo trasladamos nuestra base de datos a un nuevo centro de datos",
+        "Una mirada atrás a la migración, lo que salió mal por el camino \
+        y lo que haríamos de otra manera si tuviéramos que hacerlo de nuevo.",
+    ),
+    ("short", "OK", "v1.2"),
+];
+
+/// Serve the pages on an ephemeral local port, returning
+/// the base URL of the server.
+fn serve_pages() -> String {
+    let pages = warp::path::param().map(|name: String| {
+        let (_, title, description) = PAGES.iter().find(|(page, _, _)| *page == name).unwrap();
+        warp::reply::html(format!(
+            "<html><head><title>{}</title>\
+            <meta name=\"description\" content=\"{}\">\
+            </head><body></body></html>",
+            title, description
+        ))
+    });
+    let addr = setup::serve(pages);
+    format!("http://{}", addr)
+}
+
+/// The sorted titles of the front page and search listings
+/// seen with the given session and languages.
+macro_rules! listings {
+    ($server:expr, $session:expr, $languages:expr) => {{
+        let vars = json!({ "languages": $languages });
+        let data = setup::execute_ok($server, QUERY_LISTINGS, vars, $session).await;
+        let titles = |edges: &Value| {
+            let mut titles: Vec<String> = edges
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|edge| edge["node"]["title"].as_str().unwrap().to_string())
+                .collect();
+            titles.sort();
+            titles
+        };
+        let listed = titles(&data["submissions"]["edges"]);
+        assert_eq!(listed, titles(&data["search"]["results"]["edges"]));
+        listed
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_language_detected_on_submission() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let base = serve_pages();
+
+    for (page, _, _) in PAGES {
+        let vars = json!({ "url": format!("{}/{}", base, page) });
+        let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+        let language = body["data"]["submitUrl"]["url"]["language"].clone();
+        match *page {
+            // texts which are too short are not guessed at
+            "short" => assert!(language.is_null(), "{}", body),
+            page => assert_eq!(language, page),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_language_filter() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    for language in [Some("en"), Some("de"), None] {
+        setup::Submission::by(admin.id())
+            .title(&format!("Languages {}", language.unwrap_or("unknown")))
+            .language(language)
+            .indexed()
+            .insert(&ctx)
+            .await;
+    }
+    let all = vec!["Languages de", "Languages en", "Languages unknown"];
+
+    // submissions of unknown language pass every filter
+    assert_eq!(
+        listings!(&server, "", ["de"]),
+        vec!["Languages de", "Languages unknown"]
+    );
+    assert_eq!(listings!(&server, "", ["de", "en"]), all);
+    assert_eq!(listings!(&server, "", Value::Null), all);
+    assert_eq!(listings!(&server, &session, Value::Null), all);
+
+    // the preferred languages apply unless others are given
+    let vars = json!({ "languages": ["EN", "en"] });
+    let body = setup::execute(&server, MUTATION_PREFERENCES, vars, &session).await;
+    assert_eq!(
+        body["data"]["updatePreferences"]["preferences"]["languages"],
+        json!(["en"])
+    );
+    assert_eq!(
+        listings!(&server, &session, Value::Null),
+        vec!["Languages en", "Languages unknown"]
+    );
+    assert_eq!(
+        listings!(&server, &session, ["de"]),
+        vec!["Languages de", "Languages unknown"]
+    );
+    assert_eq!(listings!(&server, &session, Vec::<String>::new()), all);
+    assert_eq!(listings!(&server, "", Value::Null), all);
+
+    // unknown languages are rejected
+    let vars = json!({ "languages": ["xx"] });
+    let body = setup::execute(&server, QUERY_LISTINGS, vars, "").await;
+    assert!(!body["errors"].is_null());
+    let vars = json!({ "languages": ["xx"] });
+    let body = setup::execute(&server, MUTATION_PREFERENCES, vars, &session).await;
+    assert!(body["data"].is_null());
+}
@@ -0,0 +1,234 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::models::{NewUserInput, User};
+use server::schema::url_upvotes;
+use server::Config;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id }
+        }
+    }
+";
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!) {
+        addComment(urlId: $url, body: $body) { id }
+    }
+";
+
+const MUTATION_VOTE: &str = "
+    mutation VoteUrl($id: ID!) {
+        voteUrl(id: $id, direction: UP) { score }
+    }
+";
+
+const MUTATION_VOTE_COMMENT: &str = "
+    mutation VoteComment($id: ID!) {
+        voteComment(id: $id) { score }
+    }
+";
+
+const MUTATION_HIDE: &str = "
+    mutation UpdatePreferences {
+        updatePreferences(input: { hideFromLeaderboard: true }) {
+            preferences { hideFromLeaderboard }
+        }
+    }
+";
+
+const QUERY_LEADERBOARD: &str = "
+    query Leaderboard($period: LeaderboardPeriod, $after: String) {
+        leaderboard(period: $period, first: 10, after: $after) {
+            edges {
+                cursor
+                node {
+                    user { username }
+                    points
+                }
+            }
+        }
+    }
+";
+
+/// Upvote the given submission as the given session.
+macro_rules! vote {
+    ($server:expr, $session:expr, $id:expr) => {{
+        setup::execute_ok($server, MUTATION_VOTE, json!({ "id": $id }), $session).await;
+    }};
+}
+
+/// The usernames and points on the leaderboard of the given period.
+macro_rules! leaderboard {
+    ($server:expr, $period:expr) => {{
+        let vars = json!({ "period": $period });
+        let body = setup::execute($server, QUERY_LEADERBOARD, vars, "").await;
+        body["data"]["leaderboard"]["edges"]
+            .as_array()
+            .expect(&body.to_string())
+            .iter()
+            .map(|edge| {
+                let node = &edge["node"];
+                let username = node["user"]["username"].as_str().unwrap().to_string();
+                (username, node["points"].as_i64().unwrap())
+            })
+            .collect::<Vec<(String, i64)>>()
+    }};
+}
+
+/// Create a verified user with the given name, returning their session.
+async fn create_user(ctx: &server::Context, name: &str, email: &str) -> String {
+    let input = NewUserInput {
+        name: name.into(),
+        email: email.into(),
+    };
+    let mut user = User::create(ctx, input).await.unwrap();
+    user.mark_email_verified(ctx).await.unwrap();
+    setup::session_token(ctx, email).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_leaderboard_periods() {
+    let config = Config::test()
+        .with_allow_self_votes(true)
+        .with_karma_weights(2, 1);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    assert!(leaderboard!(&server, "WEEK").is_empty());
+
+    let vars = json!({ "input": { "title": "Points", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = body["data"]["submitUrl"]["url"]["id"].clone();
+    vote!(&server, &admin_session, url);
+    let vars = json!({ "url": url, "body": "A comment" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    let comment = body["data"]["addComment"]["id"].clone();
+    let vars = json!({ "id": comment });
+    setup::execute_ok(&server, MUTATION_VOTE_COMMENT, vars, &admin_session).await;
+
+    // votes on their own content don't earn users points
+    vote!(&server, &session, url);
+    let expected = vec![("test-user".to_string(), 3)];
+    assert_eq!(leaderboard!(&server, "DAY"), expected);
+    assert_eq!(leaderboard!(&server, "WEEK"), expected);
+
+    // points are counted when the vote was cast
+    let conn = ctx.conn().await.unwrap();
+    let backdate = |age: Duration| {
+        let voted_at = (ctx.now() - age).naive_utc();
+        diesel::update(url_upvotes::table)
+            .filter(url_upvotes::dsl::user_id.ne(user.id()))
+            .set(url_upvotes::dsl::created_at.eq(voted_at))
+            .execute(&*conn)
+            .unwrap();
+    };
+    let comment_only = vec![("test-user".to_string(), 1)];
+    backdate(Duration::days(1) + Duration::minutes(1));
+    assert_eq!(leaderboard!(&server, "DAY"), comment_only);
+    assert_eq!(leaderboard!(&server, "WEEK"), expected);
+    backdate(Duration::weeks(1) - Duration::minutes(1));
+    assert_eq!(leaderboard!(&server, "WEEK"), expected);
+    backdate(Duration::weeks(1) + Duration::minutes(1));
+    assert_eq!(leaderboard!(&server, "WEEK"), comment_only);
+    assert_eq!(leaderboard!(&server, "MONTH"), expected);
+    backdate(Duration::days(400));
+    assert_eq!(leaderboard!(&server, "MONTH"), comment_only);
+    assert_eq!(leaderboard!(&server, "ALL"), expected);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_leaderboard_opt_out_and_bans() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let vars = json!({ "input": { "title": "Points", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = body["data"]["submitUrl"]["url"]["id"].clone();
+    vote!(&server, &admin_session, url);
+    let vars = json!({ "input": { "title": "Points", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &admin_session).await;
+    let admin_url = body["data"]["submitUrl"]["url"]["id"].clone();
+    vote!(&server, &session, admin_url);
+    assert_eq!(
+        leaderboard!(&server, "WEEK"),
+        vec![
+            ("test-administrator".to_string(), 1),
+            ("test-user".to_string(), 1)
+        ]
+    );
+
+    // users who opt out are left out, but their votes still count
+    let body = setup::execute(&server, MUTATION_HIDE, json!({}), &session).await;
+    assert_eq!(
+        body["data"]["updatePreferences"]["preferences"]["hideFromLeaderboard"],
+        true
+    );
+    assert_eq!(
+        leaderboard!(&server, "WEEK"),
+        vec![("test-administrator".to_string(), 1)]
+    );
+
+    // banned users are left out as well
+    let mut admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    admin.ban(&ctx).await.unwrap();
+    assert!(leaderboard!(&server, "ALL").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_leaderboard_tie_ordering() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let other_session = create_user(&ctx, "Other", "test.other@urls.fyi").await;
+
+    let vars = json!({ "input": { "title": "Points", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = body["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "input": { "title": "Points", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &admin_session).await;
+    let admin_url = body["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "input": { "title": "Points", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &other_session).await;
+    let other_url = body["data"]["submitUrl"]["url"]["id"].clone();
+    vote!(&server, &admin_session, url);
+    vote!(&server, &admin_session, other_url);
+    vote!(&server, &session, admin_url);
+
+    // ties are listed by username, and pages continue after them
+    let mut after = Value::Null;
+    let mut pages = vec![];
+    loop {
+        let vars = json!({ "after": after });
+        let query = QUERY_LEADERBOARD.replace("first: 10", "first: 1");
+        let body = setup::execute(&server, &query, vars, "").await;
+        let edges = body["data"]["leaderboard"]["edges"].as_array().unwrap();
+        match edges.first() {
+            Some(edge) => {
+                pages.push(edge["node"]["user"]["username"].clone());
+                after = edge["cursor"].clone();
+            }
+            None => break,
+        }
+    }
+    assert_eq!(pages, vec!["other", "test-administrator", "test-user"]);
+
+    vote!(&server, &other_session, url);
+    assert_eq!(
+        leaderboard!(&server, "WEEK"),
+        vec![
+            ("test-user".to_string(), 2),
+            ("other".to_string(), 1),
+            ("test-administrator".to_string(), 1)
+        ]
+    );
+}
@@ -0,0 +1,208 @@
+use diesel::{ExpressionMethods, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::models::{Url, User};
+use server::Config;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use warp::http::StatusCode;
+use warp::Filter;
+mod setup;
+
+const MUTATION_RECHECK: &str = "
+    mutation RecheckUrl($id: ID!) {
+        recheckUrl(id: $id) {
+            httpStatus
+            linkStatus
+            lastCheckedAt
+        }
+    }
+";
+
+const QUERY_SUBMISSIONS: &str = "
+    query Submissions($status: LinkStatus) {
+        submissions(first: 20, linkStatus: $status) {
+            edges {
+                node { title }
+            }
+        }
+    }
+";
+
+/// Serve a few test pages on an ephemeral local port, returning
+/// the address of the server and a counter of the requests it
+/// received.
+fn serve_pages() -> (SocketAddr, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let count = warp::any()
+        .map(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+        .untuple_one();
+
+    let ok = warp::path("ok").map(|| "ok");
+    let moved =
+        warp::path("moved").map(|| warp::redirect::permanent(warp::http::Uri::from_static("/ok")));
+    let missing = warp::path("missing").map(|| StatusCode::NOT_FOUND);
+    let slow = warp::path("slow").and_then(|| async {
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        Ok::<_, Infallible>("ok")
+    });
+    let no_head = warp::path("no-head").and(
+        warp::head()
+            .map(|| StatusCode::METHOD_NOT_ALLOWED)
+            .or(warp::get().map(|| StatusCode::OK)),
+    );
+
+    let routes = count.and(ok.or(moved).or(missing).or(slow).or(no_head));
+    let addr = setup::serve(routes);
+    (addr, hits)
+}
+
+/// Recheck the given submission, returning the GraphQL response.
+macro_rules! recheck {
+    ($server:expr, $session:expr, $id:expr) => {{
+        let vars = json!({ "id": $id.to_string() });
+        setup::execute($server, MUTATION_RECHECK, vars, $session).await
+    }};
+}
+
+/// List the titles of submissions with the given link status.
+macro_rules! titles {
+    ($server:expr, $status:expr) => {{
+        let vars = json!({ "status": $status });
+        let body = setup::execute($server, QUERY_SUBMISSIONS, vars, "").await;
+        let mut titles: Vec<String> = body["data"]["submissions"]["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["node"]["title"].as_str().unwrap().to_string())
+            .collect();
+        titles.sort();
+        titles
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_recheck_url() {
+    let config = Config::test().with_fetch_timeout(Duration::from_secs(1));
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let (addr, _) = serve_pages();
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let cases = [
+        ("ok", json!(200), "OK"),
+        ("moved", json!(200), "REDIRECTED"),
+        ("missing", json!(404), "BROKEN"),
+        ("slow", Value::Null, "UNKNOWN"),
+        ("no-head", json!(200), "OK"),
+    ];
+    for (page, http_status, link_status) in cases {
+        let id = setup::Submission::by(user.id())
+            .url(&format!("http://{}/{}", addr, page))
+            .title(page)
+            .domain("127.0.0.1")
+            .insert(&ctx)
+            .await;
+        let body = recheck!(&server, &session, id);
+        let url = &body["data"]["recheckUrl"];
+        assert_eq!(url["httpStatus"], http_status, "{}", page);
+        assert_eq!(url["linkStatus"], link_status, "{}", page);
+        assert!(url["lastCheckedAt"].is_string(), "{}", page);
+    }
+
+    // the feeds can be filtered by link status
+    assert_eq!(titles!(&server, "BROKEN"), vec!["missing"]);
+    assert_eq!(titles!(&server, "OK"), vec!["no-head", "ok"]);
+    assert_eq!(titles!(&server, Value::Null).len(), 5);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_recheck_url_permissions() {
+    let (server, ctx) = setup::mock().await;
+    let (addr, hits) = serve_pages();
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let id = setup::Submission::by(user.id())
+        .url(&format!("http://{}/ok", addr))
+        .title("ok")
+        .domain("127.0.0.1")
+        .insert(&ctx)
+        .await;
+
+    for session in [session.as_str(), ""] {
+        let body = recheck!(&server, session, id);
+        assert!(body["data"].is_null());
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+    let url = Url::find(&ctx, id).await.unwrap();
+    assert!(url.last_checked_at().is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_link_checks_refuse_private_addresses() {
+    let config = Config::test().with_fetch_private_addresses(false);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let (addr, hits) = serve_pages();
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let id = setup::Submission::by(user.id())
+        .url(&format!("http://{}/ok", addr))
+        .title("ok")
+        .domain("127.0.0.1")
+        .insert(&ctx)
+        .await;
+
+    let body = recheck!(&server, &session, id);
+    assert_eq!(body["data"]["recheckUrl"]["linkStatus"], json!("UNKNOWN"));
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_check_due_links() {
+    let (server, ctx) = setup::mock().await;
+    let (addr, hits) = serve_pages();
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    for _ in 0..12 {
+        setup::Submission::by(user.id())
+            .url(&format!("http://{}/ok", addr))
+            .title("ok")
+            .domain("127.0.0.1")
+            .insert(&ctx)
+            .await;
+    }
+
+    // checks are limited per domain, the remaining
+    // links are left for a later run
+    assert_eq!(Url::check_due_links(&ctx).await.unwrap(), 10);
+    assert_eq!(hits.load(Ordering::SeqCst), 10);
+    assert_eq!(titles!(&server, "OK").len(), 10);
+    assert_eq!(titles!(&server, "UNKNOWN").len(), 2);
+
+    assert_eq!(Url::check_due_links(&ctx).await.unwrap(), 0);
+    assert_eq!(hits.load(Ordering::SeqCst), 10);
+
+    // an on demand check is limited the same way
+    let session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let id = setup::Submission::by(user.id())
+        .url(&format!("http://{}/missing", addr))
+        .title("missing")
+        .domain("127.0.0.1")
+        .insert(&ctx)
+        .await;
+    let body = recheck!(&server, &session, id);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "RATE_LIMITED");
+    assert_eq!(body["errors"][0]["extensions"]["scope"], "link_check");
+}
@@ -181,8 +181,92 @@ async fn test_login() {
         body,
         json!({
             "data": {
-                "viewer": { "email": null },
+                "viewer": null,
             },
         })
     );
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_viewer_is_null_when_logged_out() {
+    let (server, _ctx) = setup::mock().await;
+
+    let query = "
+        query IsLoggedIn {
+            viewer {
+                email
+            }
+        }
+    ";
+
+    // neither a missing nor a broken session is an error
+    for session in &["", "not-a-valid-session"] {
+        let res = setup::graphql(query, json!(null), session)
+            .reply(&server)
+            .await;
+        assert_eq!(res.status(), 200);
+
+        let body: Value = serde_json::from_slice(res.body()).expect("Invalid JSON");
+        assert_eq!(
+            body,
+            json!({
+                "data": {
+                    "viewer": null,
+                },
+            })
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_new_device_notification() {
+    let (server, ctx) = setup::mock().await;
+    let user_agent = "Mozilla/5.0 (X11; Linux x86_64; rv:91.0) Gecko/20100101 Firefox/91.0";
+
+    let query_request = "
+        mutation RequestLogin($email: String!) {
+            requestLogin(email: $email) {
+                ok
+            }
+        }
+    ";
+    let query_login = "
+        mutation Login($email: String!, $token: String!) {
+            login(email: $email, token: $token)
+        }
+    ";
+
+    for attempt in 0..2 {
+        let vars = json!({
+            "email": "test.user@urls.fyi",
+        });
+        let res = setup::graphql(query_request, vars, "")
+            .header("User-Agent", user_agent)
+            .reply(&server)
+            .await;
+        assert_eq!(res.status(), 200);
+
+        let email = setup::last_email(&ctx).await;
+        let token = email
+            .split_whitespace()
+            .find(|maybe_token| maybe_token.len() == 12)
+            .expect("Email should contain a 12 character login token");
+
+        let vars = json!({
+            "email": "test.user@urls.fyi",
+            "token": token,
+        });
+        let res = setup::graphql(query_login, vars, "")
+            .header("User-Agent", user_agent)
+            .reply(&server)
+            .await;
+        assert_eq!(res.status(), 200);
+
+        let body: Value = serde_json::from_slice(res.body()).expect("Invalid JSON");
+        assert!(!body.as_object().unwrap().contains_key("errors"));
+
+        // only the first login from this device should notify
+        let notified = setup::wait_for_email(&ctx, "Subject: New sign-in").await;
+        assert_eq!(notified, attempt == 0);
+    }
+}
@@ -0,0 +1,123 @@
+use diesel::RunQueryDsl;
+use serde_json::{json, Value};
+use server::db::models::User;
+mod setup;
+
+const QUERY_VIEWER: &str = "
+    query Viewer {
+        viewer {
+            lastLoginAt
+            loginCount
+        }
+    }
+";
+
+const QUERY_USER: &str = "
+    query FetchUser($id: ID!) {
+        fetch__User(id: $id) {
+            lastLoginAt
+            loginCount
+        }
+    }
+";
+
+/// Log in as the mock user using the login mutation,
+/// returning the GraphQL response.
+macro_rules! login {
+    ($server:expr, $ctx:expr) => {{
+        let query = "
+            mutation RequestLogin($email: String!) {
+                requestLogin(email: $email) {
+                    ok
+                }
+            }
+        ";
+        let vars = json!({ "email": "test.user@urls.fyi" });
+        let res = setup::graphql(query, vars, "").reply($server).await;
+        assert_eq!(res.status(), 200);
+
+        let email = setup::last_email($ctx).await;
+        let token = email
+            .split_whitespace()
+            .find(|maybe_token| maybe_token.len() == 12)
+            .expect("Email should contain a 12 character login token");
+
+        let query = "
+            mutation Login($email: String!, $token: String!) {
+                login(email: $email, token: $token)
+            }
+        ";
+        let vars = json!({ "email": "test.user@urls.fyi", "token": token });
+        let res = setup::graphql(query, vars, "").reply($server).await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_login_activity() {
+    let (server, ctx) = setup::mock().await;
+
+    let body = login!(&server, &ctx);
+    let session = body["data"]["login"].as_str().unwrap().to_string();
+    let body = login!(&server, &ctx);
+    assert!(body["data"]["login"].is_string());
+
+    let res = setup::graphql(QUERY_VIEWER, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["viewer"]["loginCount"], 2);
+    assert!(body["data"]["viewer"]["lastLoginAt"].is_string());
+    let last_login_at = body["data"]["viewer"]["lastLoginAt"].clone();
+
+    // administrators can see the activity of other users
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let vars = json!({ "id": user.id().to_string() });
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let res = setup::graphql(QUERY_USER, vars.clone(), &admin_session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["fetch__User"],
+        json!({ "lastLoginAt": last_login_at, "loginCount": 2 })
+    );
+
+    // other users can not
+    let res = setup::graphql(QUERY_USER, vars, "").reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["fetch__User"],
+        json!({ "lastLoginAt": null, "loginCount": null })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_login_succeeds_if_activity_update_fails() {
+    let (server, ctx) = setup::mock().await;
+
+    // make any update of the login count fail
+    diesel::sql_query(
+        "CREATE TRIGGER fail_login_count BEFORE UPDATE OF login_count ON users \
+        WHEN NEW.login_count <> OLD.login_count \
+        BEGIN SELECT RAISE(ABORT, 'login count is read-only'); END",
+    )
+    .execute(&*ctx.conn().await.unwrap())
+    .unwrap();
+
+    let body = login!(&server, &ctx);
+    assert!(body["errors"].is_null());
+    let session = body["data"]["login"].as_str().unwrap().to_string();
+
+    let res = setup::graphql(QUERY_VIEWER, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"],
+        json!({ "lastLoginAt": null, "loginCount": 0 })
+    );
+}
@@ -0,0 +1,146 @@
+use diesel::prelude::*;
+use serde_json::{json, Value};
+use server::db::models::{Login, User};
+use server::schema::security_events;
+mod setup;
+
+const MUTATION_LOGIN: &str = "
+    mutation Login($email: String!, $token: String!) {
+        login(email: $email, token: $token)
+    }
+";
+
+const MUTATION_UNLOCK: &str = "
+    mutation UnlockAccount($id: ID!) {
+        unlockAccount(userId: $id) {
+            ok
+        }
+    }
+";
+
+const QUERY_LOCKED_UNTIL: &str = "
+    query LockedUntil($id: ID!) {
+        fetch__User(id: $id) {
+            loginLockedUntil
+        }
+    }
+";
+
+/// Attempt to log in as the mock user with the given
+/// login code, returning the GraphQL response.
+macro_rules! login {
+    ($server:expr, $token:expr) => {{
+        let vars = json!({ "email": "test.user@urls.fyi", "token": $token });
+        let res = setup::graphql(MUTATION_LOGIN, vars, "")
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_login_code_invalidated_after_failed_attempts() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let login = Login::create(&ctx, user.id()).await.unwrap();
+    let token = login.email_token().to_string();
+
+    for _ in 0..5 {
+        let body = login!(&server, "wrong-token!");
+        assert_eq!(body["errors"][0]["message"], "Invalid login token");
+    }
+
+    // the correct code no longer works
+    let body = login!(&server, &token);
+    assert!(body["data"].is_null());
+
+    // but a fresh code does
+    let login = Login::create(&ctx, user.id()).await.unwrap();
+    let body = login!(&server, login.email_token());
+    assert!(body["data"]["login"].is_string());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_account_locked_after_failed_attempts() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    for _ in 0..19 {
+        let body = login!(&server, "wrong-token!");
+        assert_eq!(body["errors"][0]["message"], "Invalid login token");
+    }
+    let body = login!(&server, "wrong-token!");
+    assert_eq!(body["errors"][0]["message"], "Invalid login token");
+    assert!(setup::wait_for_email(&ctx, "Logging in is locked until").await);
+
+    // even a valid code is rejected while locked
+    let login = Login::create(&ctx, user.id()).await.unwrap();
+    let token = login.email_token().to_string();
+    let body = login!(&server, &token);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "RATE_LIMITED");
+    assert_eq!(body["errors"][0]["extensions"]["scope"], "login");
+    assert!(
+        body["errors"][0]["extensions"]["retryAfter"]
+            .as_i64()
+            .unwrap()
+            > 0
+    );
+
+    let vars = json!({ "id": user.id().to_string() });
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let res = setup::graphql(QUERY_LOCKED_UNTIL, vars.clone(), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"]["fetch__User"]["loginLockedUntil"].is_string());
+
+    // regular users can not lift the lock
+    let res = setup::graphql(MUTATION_UNLOCK, vars.clone(), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["errors"][0]["message"], "Not authorized");
+
+    // administrators can
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let res = setup::graphql(MUTATION_UNLOCK, vars.clone(), &admin_session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, json!({ "data": { "unlockAccount": { "ok": true } } }));
+
+    let body = login!(&server, &token);
+    assert!(body["data"]["login"].is_string());
+
+    let res = setup::graphql(QUERY_LOCKED_UNTIL, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"]["fetch__User"]["loginLockedUntil"].is_null());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_account_locked_once_under_concurrent_attempts() {
+    let (server, ctx) = setup::mock().await;
+
+    futures_util::future::join_all((0..24).map(|_| {
+        let vars = json!({ "email": "test.user@urls.fyi", "token": "wrong-token!" });
+        setup::graphql(MUTATION_LOGIN, vars, "").reply(&server)
+    }))
+    .await;
+
+    let kinds: Vec<String> = security_events::table
+        .select(security_events::dsl::kind)
+        .load(&*ctx.conn().await.unwrap())
+        .unwrap();
+    assert_eq!(
+        kinds.iter().filter(|kind| *kind == "login_locked").count(),
+        1
+    );
+    assert!(kinds.iter().filter(|kind| *kind == "login_failed").count() >= 20);
+}
@@ -0,0 +1,207 @@
+use serde_json::{json, Value};
+use server::db::id::UserID;
+use server::db::models::{NewUserInput, User};
+use server::Context;
+mod setup;
+
+const MUTATION_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!) {
+        addComment(urlId: $url, body: $body) {
+            id
+            html
+            mentions { username }
+        }
+    }
+";
+
+const MUTATION_UPDATE: &str = "
+    mutation UpdateComment($id: ID!, $body: String!) {
+        updateComment(id: $id, body: $body) {
+            mentions { username }
+        }
+    }
+";
+
+const MUTATION_BLOCK: &str = "
+    mutation BlockUser($id: ID!) {
+        blockUser(userId: $id) {
+            ok
+        }
+    }
+";
+
+const MUTATION_UNBLOCK: &str = "
+    mutation UnblockUser($id: ID!) {
+        unblockUser(userId: $id) {
+            ok
+        }
+    }
+";
+
+const QUERY_NOTIFICATIONS: &str = "
+    query Notifications {
+        viewer {
+            notifications(first: 10, filter: ALL) {
+                edges {
+                    node {
+                        kind
+                        actor { username }
+                    }
+                }
+            }
+        }
+    }
+";
+
+/// Create another verified user, returning their
+/// ID and a session token for them.
+async fn other_user(ctx: &Context, name: &str) -> (UserID, String) {
+    let email = format!("test.{}@urls.fyi", name.to_lowercase());
+    let input = NewUserInput {
+        name: name.into(),
+        email: email.clone(),
+    };
+    let mut user = User::create(ctx, input).await.unwrap();
+    user.mark_email_verified(ctx).await.unwrap();
+    (user.id(), setup::session_token(ctx, &email).await)
+}
+
+/// The kind and actor of the notifications of the given session.
+macro_rules! notifications {
+    ($server:expr, $session:expr) => {{
+        let data = setup::execute_ok($server, QUERY_NOTIFICATIONS, json!({}), $session).await;
+        data["viewer"]["notifications"]["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| {
+                (
+                    edge["node"]["kind"].as_str().unwrap().to_string(),
+                    edge["node"]["actor"]["username"]
+                        .as_str()
+                        .unwrap()
+                        .to_string(),
+                )
+            })
+            .collect::<Vec<_>>()
+    }};
+}
+
+/// The usernames of the given list of users.
+fn usernames(users: &Value) -> Vec<&str> {
+    users
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|user| user["username"].as_str().unwrap())
+        .collect()
+}
+
+fn entry(kind: &str, actor: &str) -> (String, String) {
+    (kind.to_string(), actor.to_string())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mention_on_comment() {
+    let (server, ctx) = setup::mock().await;
+    let admin = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let (carol_id, carol) = other_user(&ctx, "Carol").await;
+    let author = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let url = setup::submit(&ctx, author.id()).await;
+
+    let body = "Thanks @Test-Administrator! cc (@carol), @nobody, @test-user \
+        and `@carol` at carol@example.com";
+    let vars = json!({ "url": url.to_string(), "body": body });
+    let data = setup::execute_ok(&server, MUTATION_COMMENT, vars, &user).await;
+    let comment = &data["addComment"];
+    assert_eq!(
+        usernames(&comment["mentions"]),
+        vec!["carol", "test-administrator", "test-user"]
+    );
+    let html = comment["html"].as_str().unwrap();
+    assert!(html.contains(&format!("(<a href=\"/user/{}\">@carol</a>)", carol_id)));
+    assert!(html.contains("@nobody,"));
+    assert!(html.contains("<code>@carol</code>"));
+    assert!(html.contains("carol@example.com"));
+
+    // the submitter is only notified about the reply, and
+    // mentioning yourself notifies nobody
+    assert_eq!(
+        notifications!(&server, &admin),
+        vec![entry("SUBMISSION_REPLY", "test-user")]
+    );
+    assert_eq!(
+        notifications!(&server, &carol),
+        vec![entry("MENTION", "test-user")]
+    );
+    assert!(notifications!(&server, &user).is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mentions_on_edit() {
+    let (server, ctx) = setup::mock().await;
+    let admin = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let (carol_id, carol) = other_user(&ctx, "Carol").await;
+    let url = setup::submit(&ctx, carol_id).await;
+
+    let vars = json!({ "url": url.to_string(), "body": "Hi @test-user" });
+    let data = setup::execute_ok(&server, MUTATION_COMMENT, vars, &carol).await;
+    let id = data["addComment"]["id"].clone();
+    assert_eq!(notifications!(&server, &user).len(), 1);
+
+    // only newly mentioned users are notified
+    let vars = json!({ "id": id, "body": "Hi @test-user and @test-administrator" });
+    let data = setup::execute_ok(&server, MUTATION_UPDATE, vars, &carol).await;
+    assert_eq!(
+        usernames(&data["updateComment"]["mentions"]),
+        vec!["test-administrator", "test-user"]
+    );
+    assert_eq!(
+        notifications!(&server, &user),
+        vec![entry("MENTION", "carol")]
+    );
+    assert_eq!(
+        notifications!(&server, &admin),
+        vec![entry("MENTION", "carol")]
+    );
+
+    // removing and adding a mention again does not notify twice
+    let vars = json!({ "id": id, "body": "Hi there" });
+    let data = setup::execute_ok(&server, MUTATION_UPDATE, vars, &carol).await;
+    assert!(usernames(&data["updateComment"]["mentions"]).is_empty());
+    let vars = json!({ "id": id, "body": "Hi @TEST-USER" });
+    let data = setup::execute_ok(&server, MUTATION_UPDATE, vars, &carol).await;
+    assert_eq!(
+        usernames(&data["updateComment"]["mentions"]),
+        vec!["test-user"]
+    );
+    assert_eq!(notifications!(&server, &user).len(), 1);
+    assert_eq!(notifications!(&server, &admin).len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mentions_by_blocked_users() {
+    let (server, ctx) = setup::mock().await;
+    let user = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let (carol_id, carol) = other_user(&ctx, "Carol").await;
+    let url = setup::submit(&ctx, carol_id).await;
+
+    let vars = json!({ "id": carol_id.to_string() });
+    setup::execute_ok(&server, MUTATION_BLOCK, vars.clone(), &user).await;
+
+    let vars = json!({ "url": url.to_string(), "body": "Hi @test-user" });
+    let data = setup::execute_ok(&server, MUTATION_COMMENT, vars, &carol).await;
+    assert_eq!(
+        usernames(&data["addComment"]["mentions"]),
+        vec!["test-user"]
+    );
+    assert!(notifications!(&server, &user).is_empty());
+
+    // the notification was never created, rather than hidden
+    setup::execute_ok(&server, MUTATION_UNBLOCK, vars, &user).await;
+    assert!(notifications!(&server, &user).is_empty());
+}
@@ -0,0 +1,218 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::models::User;
+use server::domain;
+use server::schema::{tags, urls};
+mod setup;
+
+const MUTATION_MUTE: &str = "
+    mutation MuteDomain($domain: String!) {
+        muteDomain(domain: $domain) { ok }
+    }
+";
+
+const MUTATION_UNMUTE: &str = "
+    mutation UnmuteDomain($domain: String!) {
+        unmuteDomain(domain: $domain) { ok }
+    }
+";
+
+const QUERY_MUTED: &str = "
+    query Muted {
+        viewer { mutedDomains }
+    }
+";
+
+const QUERY_SUBMISSIONS: &str = "
+    query Submissions($tag: String, $after: String) {
+        submissions(first: 2, after: $after, tag: $tag) {
+            edges {
+                node { title }
+            }
+            pageInfo {
+                hasNextPage
+                endCursor
+            }
+        }
+    }
+";
+
+const QUERY_SEARCH: &str = "
+    query Search {
+        search(query: \"muting\") {
+            results(first: 10) {
+                edges {
+                    node { title }
+                }
+            }
+        }
+    }
+";
+
+/// Run the given mutation for the given domain, returning
+/// the GraphQL response.
+macro_rules! mutate {
+    ($server:expr, $session:expr, $query:expr, $domain:expr) => {{
+        let res = setup::graphql($query, json!({ "domain": $domain }), $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Fetch all submissions, optionally only those with the
+/// given tag, returning the titles of each page.
+macro_rules! pages {
+    ($server:expr, $session:expr, $tag:expr) => {{
+        let mut pages = vec![];
+        let mut after = Value::Null;
+        loop {
+            let vars = json!({ "tag": $tag, "after": after });
+            let res = setup::graphql(QUERY_SUBMISSIONS, vars, $session)
+                .reply($server)
+                .await;
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+            let submissions = &body["data"]["submissions"];
+            let titles: Vec<String> = submissions["edges"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|edge| edge["node"]["title"].as_str().unwrap().to_string())
+                .collect();
+            pages.push(titles);
+            if submissions["pageInfo"]["hasNextPage"] != true {
+                break;
+            }
+            after = submissions["pageInfo"]["endCursor"].clone();
+        }
+        pages
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_muted_domains_excluded() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    diesel::insert_into(tags::table)
+        .values((
+            tags::dsl::name.eq("muting"),
+            tags::dsl::created_at.eq(ctx.now().naive_utc()),
+            tags::dsl::url_count.eq(6),
+        ))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+
+    let urls = [
+        "https://a.com/1",
+        "https://blog.muted.co.uk/2",
+        "https://b.com/3",
+        "https://muted.co.uk/4",
+        "https://c.com/5",
+        "https://d.com/6",
+    ];
+    let mut ids = vec![];
+    for (minutes, url) in urls.iter().enumerate() {
+        ids.push(
+            setup::Submission::by(admin.id())
+                .url(url)
+                .title(&format!("Muting {}", url))
+                .domain(&domain::of_url(url))
+                .created_at(ctx.now() - Duration::minutes(minutes as i64))
+                .tags(&["muting"])
+                .indexed()
+                .insert(&ctx)
+                .await,
+        );
+    }
+    let title = |i: usize| format!("Muting {}", urls[i]);
+
+    let body = mutate!(&server, &session, MUTATION_MUTE, "www.muted.co.uk");
+    assert_eq!(body, json!({ "data": { "muteDomain": { "ok": true } } }));
+
+    // pages stay full when muted submissions are skipped
+    let expected = vec![vec![title(0), title(2)], vec![title(4), title(5)]];
+    assert_eq!(pages!(&server, &session, Value::Null), expected);
+    assert_eq!(pages!(&server, &session, "muting"), expected);
+
+    let res = setup::graphql(QUERY_SEARCH, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let edges = body["data"]["search"]["results"]["edges"]
+        .as_array()
+        .unwrap();
+    assert_eq!(edges.len(), 4);
+    assert!(edges
+        .iter()
+        .all(|edge| !edge["node"]["title"].as_str().unwrap().contains("muted")));
+
+    // other users still see everything
+    assert_eq!(pages!(&server, "", Value::Null).concat().len(), 6);
+
+    // muted submissions are still reachable directly, and
+    // listed in the feed of their domain
+    let query = "query Url($id: ID!) { fetch__Url(id: $id) { title } }";
+    let res = setup::graphql(query, json!({ "id": ids[1].to_string() }), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["fetch__Url"]["title"], title(1));
+    let query = "{ domainFeed(domain: \"muted.co.uk\", first: 10) { edges { node { title } } } }";
+    let res = setup::graphql(query, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["domainFeed"]["edges"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+
+    let body = mutate!(&server, &session, MUTATION_UNMUTE, "muted.co.uk");
+    assert_eq!(body, json!({ "data": { "unmuteDomain": { "ok": true } } }));
+    assert_eq!(pages!(&server, &session, Value::Null).concat().len(), 6);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mute_domain_normalization() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let muted = || setup::graphql(QUERY_MUTED, json!({}), &session).reply(&server);
+
+    // all spellings mute the same registrable domain
+    for domain in [
+        "https://Blog.Example.co.uk/some/post",
+        "example.co.uk",
+        "www.example.co.uk/",
+    ] {
+        let body = mutate!(&server, &session, MUTATION_MUTE, domain);
+        assert_eq!(body["data"]["muteDomain"]["ok"], true, "{}", domain);
+    }
+    mutate!(&server, &session, MUTATION_MUTE, "xn--bcher-kva.de");
+    let body: Value = serde_json::from_slice(muted().await.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["mutedDomains"],
+        json!(["bücher.de", "example.co.uk"])
+    );
+
+    mutate!(&server, &session, MUTATION_UNMUTE, "bücher.de");
+    let body: Value = serde_json::from_slice(muted().await.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["mutedDomains"],
+        json!(["example.co.uk"])
+    );
+
+    let body = mutate!(&server, &session, MUTATION_MUTE, "   ");
+    assert_eq!(body["errors"][0]["message"], "Please enter a valid domain");
+
+    // anonymous users can not mute domains
+    let body = mutate!(&server, "", MUTATION_MUTE, "example.com");
+    assert!(body["data"].is_null());
+}
@@ -0,0 +1,266 @@
+use serde_json::{json, Value};
+use server::db::id::UserID;
+use server::db::models::{NewUserInput, User};
+use server::Context;
+mod setup;
+
+const MUTATION_COMMENT: &str = "
+    mutation AddComment($url: ID!, $parent: ID) {
+        addComment(urlId: $url, body: \"Nice link\", parentId: $parent) {
+            id
+        }
+    }
+";
+
+const MUTATION_BLOCK: &str = "
+    mutation BlockUser($id: ID!) {
+        blockUser(userId: $id) {
+            ok
+        }
+    }
+";
+
+const MUTATION_UNBLOCK: &str = "
+    mutation UnblockUser($id: ID!) {
+        unblockUser(userId: $id) {
+            ok
+        }
+    }
+";
+
+const MUTATION_MARK_READ: &str = "
+    mutation MarkNotificationRead($id: ID!) {
+        markNotificationRead(id: $id) {
+            id
+            readAt
+        }
+    }
+";
+
+const MUTATION_MARK_ALL_READ: &str = "
+    mutation MarkAllNotificationsRead {
+        markAllNotificationsRead {
+            ok
+        }
+    }
+";
+
+const QUERY_NOTIFICATIONS: &str = "
+    query Notifications($filter: NotificationFilter) {
+        viewer {
+            unreadNotificationCount
+            notifications(first: 10, filter: $filter) {
+                edges {
+                    node {
+                        id
+                        kind
+                        actor { name }
+                        url { id }
+                        comment { id }
+                        readAt
+                    }
+                }
+            }
+        }
+    }
+";
+
+/// Create another verified user, returning their
+/// ID and a session token for them.
+async fn other_user(ctx: &Context, name: &str) -> (UserID, String) {
+    let email = format!("test.{}@urls.fyi", name.to_lowercase());
+    let input = NewUserInput {
+        name: name.into(),
+        email: email.clone(),
+    };
+    let mut user = User::create(ctx, input).await.unwrap();
+    user.mark_email_verified(ctx).await.unwrap();
+    (user.id(), setup::session_token(ctx, &email).await)
+}
+
+/// Comment on the given submission, optionally replying to
+/// the given comment, returning the ID of the new comment.
+macro_rules! comment {
+    ($server:expr, $session:expr, $url:expr, $parent:expr) => {{
+        let vars = json!({ "url": $url.to_string(), "parent": $parent });
+        let body = setup::execute($server, MUTATION_COMMENT, vars, $session).await;
+        assert!(body["errors"].is_null(), "{}", body);
+        body["data"]["addComment"]["id"].clone()
+    }};
+}
+
+/// List the notifications of the given session, returning the
+/// unread count and the notifications.
+macro_rules! notifications {
+    ($server:expr, $session:expr, $filter:expr) => {{
+        let vars = json!({ "filter": $filter });
+        let body = setup::execute($server, QUERY_NOTIFICATIONS, vars, $session).await;
+        assert!(body["errors"].is_null(), "{}", body);
+        let viewer = &body["data"]["viewer"];
+        let nodes: Vec<Value> = viewer["notifications"]["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["node"].clone())
+            .collect();
+        (viewer["unreadNotificationCount"].as_i64().unwrap(), nodes)
+    }};
+}
+
+/// The kind and actor of the given notifications.
+fn summary(nodes: &[Value]) -> Vec<(&str, &str)> {
+    nodes
+        .iter()
+        .map(|node| {
+            (
+                node["kind"].as_str().unwrap(),
+                node["actor"]["name"].as_str().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_notification_emission() {
+    let (server, ctx) = setup::mock().await;
+    let admin = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let (_, carol) = other_user(&ctx, "Carol").await;
+    let author = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url = setup::submit(&ctx, author.id()).await;
+
+    // commenting on your own submission notifies nobody
+    let own = comment!(&server, &user, url, Value::Null);
+    assert_eq!(notifications!(&server, &user, "ALL").0, 0);
+
+    // the submitter is notified about new comments
+    let top = comment!(&server, &admin, url, Value::Null);
+    let (count, nodes) = notifications!(&server, &user, "ALL");
+    assert_eq!(count, 1);
+    assert_eq!(
+        summary(&nodes),
+        vec![("SUBMISSION_REPLY", "Test Administrator")]
+    );
+    assert_eq!(nodes[0]["url"]["id"], url.to_string());
+    assert_eq!(nodes[0]["comment"]["id"], top);
+    assert_eq!(nodes[0]["readAt"], Value::Null);
+
+    // the parent commenter is notified about replies, but the
+    // submitter does not get notified about their own reply
+    comment!(&server, &user, url, top.clone());
+    let (count, nodes) = notifications!(&server, &admin, "ALL");
+    assert_eq!(count, 1);
+    assert_eq!(summary(&nodes), vec![("COMMENT_REPLY", "Test User")]);
+    assert_eq!(notifications!(&server, &user, "ALL").0, 1);
+
+    // replying to a comment of the submitter notifies them once
+    comment!(&server, &carol, url, own);
+    let (count, nodes) = notifications!(&server, &user, "ALL");
+    assert_eq!(count, 2);
+    assert_eq!(
+        summary(&nodes),
+        vec![
+            ("COMMENT_REPLY", "Carol"),
+            ("SUBMISSION_REPLY", "Test Administrator"),
+        ]
+    );
+
+    // replying to someone else notifies both them and the submitter
+    comment!(&server, &carol, url, top);
+    let (_, nodes) = notifications!(&server, &admin, "ALL");
+    assert_eq!(
+        summary(&nodes),
+        vec![("COMMENT_REPLY", "Carol"), ("COMMENT_REPLY", "Test User")]
+    );
+    let (count, nodes) = notifications!(&server, &user, "ALL");
+    assert_eq!(count, 3);
+    assert_eq!(summary(&nodes)[0], ("SUBMISSION_REPLY", "Carol"));
+
+    // nobody notifies Carol
+    assert!(notifications!(&server, &carol, "ALL").1.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_notifications_from_blocked_users() {
+    let (server, ctx) = setup::mock().await;
+    let user = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let (carol_id, carol) = other_user(&ctx, "Carol").await;
+    let author = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url = setup::submit(&ctx, author.id()).await;
+
+    comment!(&server, &carol, url, Value::Null);
+    assert_eq!(notifications!(&server, &user, "UNREAD").0, 1);
+
+    // notifications of blocked users are hidden
+    let vars = json!({ "id": carol_id.to_string() });
+    setup::execute(&server, MUTATION_BLOCK, vars.clone(), &user).await;
+    let (count, nodes) = notifications!(&server, &user, "ALL");
+    assert_eq!(count, 0);
+    assert!(nodes.is_empty());
+
+    // and blocked users can not create new ones
+    let vars_comment = json!({ "url": url.to_string(), "parent": Value::Null });
+    let body = setup::execute(&server, MUTATION_COMMENT, vars_comment, &carol).await;
+    assert!(body["data"].is_null());
+
+    setup::execute(&server, MUTATION_UNBLOCK, vars, &user).await;
+    assert_eq!(notifications!(&server, &user, "UNREAD").0, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mark_notifications_read() {
+    let (server, ctx) = setup::mock().await;
+    let admin = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let (_, carol) = other_user(&ctx, "Carol").await;
+    let author = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url = setup::submit(&ctx, author.id()).await;
+
+    comment!(&server, &admin, url, Value::Null);
+    comment!(&server, &carol, url, Value::Null);
+    comment!(&server, &carol, url, Value::Null);
+    let (count, nodes) = notifications!(&server, &user, "UNREAD");
+    assert_eq!(count, 3);
+    assert_eq!(nodes.len(), 3);
+
+    // other users can not mark the notification as read
+    let vars = json!({ "id": nodes[0]["id"] });
+    let body = setup::execute(&server, MUTATION_MARK_READ, vars.clone(), &carol).await;
+    assert_eq!(body["errors"][0]["message"], "Notification not found");
+
+    let body = setup::execute(&server, MUTATION_MARK_READ, vars.clone(), &user).await;
+    let read_at = body["data"]["markNotificationRead"]["readAt"].clone();
+    assert!(read_at.is_string());
+    let (count, nodes) = notifications!(&server, &user, "UNREAD");
+    assert_eq!(count, 2);
+    assert_eq!(nodes.len(), 2);
+    let (count, nodes) = notifications!(&server, &user, "ALL");
+    assert_eq!(count, 2);
+    assert_eq!(nodes.len(), 3);
+    assert_eq!(nodes[0]["readAt"], read_at);
+
+    // marking a notification as read again has no effect
+    let body = setup::execute(&server, MUTATION_MARK_READ, vars, &user).await;
+    assert_eq!(body["data"]["markNotificationRead"]["readAt"], read_at);
+
+    let body = setup::execute(&server, MUTATION_MARK_ALL_READ, json!({}), &user).await;
+    assert_eq!(body["data"]["markAllNotificationsRead"]["ok"], true);
+    let (count, nodes) = notifications!(&server, &user, "UNREAD");
+    assert_eq!(count, 0);
+    assert!(nodes.is_empty());
+    let (_, nodes) = notifications!(&server, &user, "ALL");
+    assert!(nodes.iter().all(|node| node["readAt"].is_string()));
+
+    // logged out viewers have no notifications
+    let body = setup::execute(&server, QUERY_NOTIFICATIONS, json!({}), "").await;
+    assert_eq!(
+        body["data"]["viewer"]["unreadNotificationCount"],
+        Value::Null
+    );
+}
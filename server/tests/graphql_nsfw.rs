@@ -0,0 +1,230 @@
+use diesel::{ExpressionMethods, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::models::{Url, User};
+use server::schema::tags;
+mod setup;
+
+const QUERY_LISTINGS: &str = "
+    query Listings {
+        submissions(first: 10) {
+            edges {
+                node { title nsfw }
+            }
+        }
+        tagged: submissions(first: 10, tag: \"listing\") {
+            edges {
+                node { title nsfw }
+            }
+        }
+        search(query: \"listing\") {
+            results(first: 10) {
+                edges {
+                    node { title nsfw }
+                }
+            }
+        }
+    }
+";
+
+const MUTATION_PREFERENCES: &str = "
+    mutation UpdatePreferences($show: ShowNsfw!) {
+        updatePreferences(input: { showNsfw: $show }) {
+            preferences { showNsfw }
+        }
+    }
+";
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($url: String!) {
+        submitUrl(input: { url: $url, nsfw: true }) {
+            url { nsfw }
+        }
+    }
+";
+
+const MUTATION_UPDATE: &str = "
+    mutation UpdateUrl($id: ID!, $nsfw: Boolean!) {
+        updateUrl(id: $id, input: { nsfw: $nsfw }) { nsfw }
+    }
+";
+
+/// The titles and NSFW marks of the front page, tag, and
+/// search listings seen with the given session.
+macro_rules! listings {
+    ($server:expr, $session:expr) => {{
+        let res = setup::graphql(QUERY_LISTINGS, json!({}), $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["errors"].is_null(), "{}", body);
+        let data = &body["data"];
+        vec![
+            data["submissions"]["edges"].clone(),
+            data["tagged"]["edges"].clone(),
+            data["search"]["results"]["edges"].clone(),
+        ]
+    }};
+}
+
+/// Set whether the given submission is marked as NSFW,
+/// returning the GraphQL response.
+macro_rules! mark {
+    ($server:expr, $session:expr, $id:expr, $nsfw:expr) => {{
+        let vars = json!({ "id": $id.to_string(), "nsfw": $nsfw });
+        let res = setup::graphql(MUTATION_UPDATE, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_nsfw_preferences() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    diesel::insert_into(tags::table)
+        .values((
+            tags::dsl::name.eq("listing"),
+            tags::dsl::created_at.eq(ctx.now().naive_utc()),
+            tags::dsl::url_count.eq(2),
+        ))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    setup::Submission::by(admin.id())
+        .title("Listing safe")
+        .tags(&["listing"])
+        .indexed()
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(admin.id())
+        .title("Listing explicit")
+        .nsfw(true)
+        .tags(&["listing"])
+        .indexed()
+        .insert(&ctx)
+        .await;
+
+    let safe = json!([{ "node": { "title": "Listing safe", "nsfw": false } }]);
+    let all = json!([
+        { "node": { "title": "Listing explicit", "nsfw": true } },
+        { "node": { "title": "Listing safe", "nsfw": false } },
+    ]);
+    let sorted = |listings: Vec<Value>| -> Vec<Value> {
+        listings
+            .into_iter()
+            .map(|edges| {
+                let mut edges = edges.as_array().unwrap().clone();
+                edges.sort_by_key(|edge| edge["node"]["title"].as_str().unwrap().to_string());
+                Value::Array(edges)
+            })
+            .collect()
+    };
+
+    // NSFW submissions are hidden by default, and always
+    // from anonymous visitors
+    assert_eq!(listings!(&server, &session), vec![safe.clone(); 3]);
+    assert_eq!(listings!(&server, ""), vec![safe.clone(); 3]);
+
+    for (show, expected) in [("BLUR", &all), ("SHOW", &all), ("HIDE", &safe)] {
+        let vars = json!({ "show": show });
+        let res = setup::graphql(MUTATION_PREFERENCES, vars, &session)
+            .reply(&server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(
+            body["data"]["updatePreferences"]["preferences"]["showNsfw"],
+            show
+        );
+        let listings = sorted(listings!(&server, &session));
+        assert_eq!(listings, vec![expected.clone(); 3], "{}", show);
+        assert_eq!(listings!(&server, ""), vec![safe.clone(); 3]);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_nsfw_marked_by_submitter() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "url": "https://example.com/nsfw" });
+    let res = setup::graphql(MUTATION_SUBMIT, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["submitUrl"]["url"]["nsfw"], true);
+
+    let id = setup::Submission::by(user.id())
+        .title("Listing")
+        .tags(&["listing"])
+        .indexed()
+        .insert(&ctx)
+        .await;
+    let body = mark!(&server, &session, id, true);
+    assert_eq!(body["data"]["updateUrl"]["nsfw"], true);
+    let body = mark!(&server, &session, id, false);
+    assert_eq!(body["data"]["updateUrl"]["nsfw"], false);
+
+    // other users can not mark submissions
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let id = setup::Submission::by(admin.id())
+        .title("Listing")
+        .tags(&["listing"])
+        .indexed()
+        .insert(&ctx)
+        .await;
+    let body = mark!(&server, &session, id, true);
+    assert!(body["data"].is_null());
+    assert!(!Url::find(&ctx, id).await.unwrap().is_nsfw());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_nsfw_moderator_override() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let moderator = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let id = setup::Submission::by(user.id())
+        .title("Listing")
+        .tags(&["listing"])
+        .indexed()
+        .insert(&ctx)
+        .await;
+
+    // moderators can mark any submission, and the
+    // submitter can not clear their mark
+    let body = mark!(&server, &moderator, id, true);
+    assert_eq!(body["data"]["updateUrl"]["nsfw"], true);
+    let body = mark!(&server, &session, id, false);
+    assert!(body["data"].is_null());
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("moderator"));
+    let url = Url::find(&ctx, id).await.unwrap();
+    assert!(url.is_nsfw());
+    assert!(url.is_nsfw_locked());
+
+    // marking it again keeps the mark locked
+    let body = mark!(&server, &session, id, true);
+    assert_eq!(body["data"]["updateUrl"]["nsfw"], true);
+    assert!(Url::find(&ctx, id).await.unwrap().is_nsfw_locked());
+
+    // once cleared by a moderator, the submitter decides again
+    let body = mark!(&server, &moderator, id, false);
+    assert_eq!(body["data"]["updateUrl"]["nsfw"], false);
+    let body = mark!(&server, &session, id, true);
+    assert_eq!(body["data"]["updateUrl"]["nsfw"], true);
+    let body = mark!(&server, &session, id, false);
+    assert_eq!(body["data"]["updateUrl"]["nsfw"], false);
+}
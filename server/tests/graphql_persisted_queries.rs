@@ -0,0 +1,186 @@
+use serde_json::{json, Value};
+use server::persisted;
+use server::Config;
+use warp::test::RequestBuilder;
+mod setup;
+
+const MANIFEST: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/persisted_queries.json"
+);
+
+/// Persisted by the manifest.
+const QUERY_EMAIL: &str = "query Email { viewer { email } }";
+
+const QUERY_VERIFIED: &str = "query Verified { viewer { emailVerified } }";
+
+/// Constructs a GraphQL request for a persisted query, which carries
+/// the query text only if given.
+fn persisted_request(hash: &str, query: Option<&str>, session: &str) -> RequestBuilder {
+    let mut body = json!({
+        "extensions": {
+            "persistedQuery": { "version": 1, "sha256Hash": hash },
+        },
+    });
+    if let Some(query) = query {
+        body["query"] = query.into();
+    }
+    warp::test::request()
+        .path("/graphql")
+        .method("POST")
+        .header("Cookie", format!("xsrf=fake_xsrf; session={}", session))
+        .header("X-XSRF-Token", "fake_xsrf")
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+}
+
+/// Send the given request, returning the response status and body.
+macro_rules! reply {
+    ($server:expr, $request:expr) => {{
+        let res = $request.reply($server).await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        (res.status(), body)
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_persisted_query_misses() {
+    let hash = persisted::hash(QUERY_VERIFIED);
+
+    // clients are told when queries can't be persisted at all
+    let (server, _) = setup::mock().await;
+    let (status, body) = reply!(&server, persisted_request(&hash, None, ""));
+    assert_eq!(status, 400);
+    assert_eq!(body["errors"][0]["message"], "PersistedQueryNotSupported");
+
+    let conf = Config::test().with_persisted_queries(MANIFEST);
+    let (server, _) = setup::mock_with_config(conf).await;
+    let (status, body) = reply!(&server, persisted_request(&hash, None, ""));
+    assert_eq!(status, 400);
+    assert_eq!(body["errors"][0]["message"], "PersistedQueryNotFound");
+    assert_eq!(
+        body["errors"][0]["extensions"]["code"],
+        "PERSISTED_QUERY_NOT_FOUND"
+    );
+
+    // queries which don't match their hash are rejected
+    let request = persisted_request(&hash, Some(QUERY_EMAIL), "");
+    let (status, body) = reply!(&server, request);
+    assert_eq!(status, 400);
+    assert_eq!(
+        body["errors"][0]["extensions"]["code"],
+        "PERSISTED_QUERY_HASH_MISMATCH"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_persisted_query_hits() {
+    let conf = Config::test()
+        .with_persisted_queries(MANIFEST)
+        .with_register_persisted_queries(true);
+    let (server, ctx) = setup::mock_with_config(conf).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // queries of the manifest are persisted from the start
+    let hash = persisted::hash(QUERY_EMAIL);
+    let (status, body) = reply!(&server, persisted_request(&hash, None, &session));
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["viewer"]["email"], "test.user@urls.fyi");
+
+    // others are persisted once sent along with their hash
+    let hash = persisted::hash(QUERY_VERIFIED);
+    let (_, body) = reply!(&server, persisted_request(&hash, None, &session));
+    assert_eq!(body["errors"][0]["message"], "PersistedQueryNotFound");
+    let request = persisted_request(&hash, Some(QUERY_VERIFIED), &session);
+    let (status, body) = reply!(&server, request);
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["viewer"]["emailVerified"], true);
+    let (status, body) = reply!(&server, persisted_request(&hash, None, &session));
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["viewer"]["emailVerified"], true);
+    let (status, _) = reply!(&server, persisted_request(&hash.to_uppercase(), None, ""));
+    assert_eq!(status, 200);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_only_persisted_queries() {
+    let conf = Config::test()
+        .with_persisted_queries(MANIFEST)
+        .with_register_persisted_queries(true)
+        .with_persisted_queries_only(true);
+    let (server, ctx) = setup::mock_with_config(conf).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let hash = persisted::hash(QUERY_EMAIL);
+    let (status, body) = reply!(&server, persisted_request(&hash, None, &session));
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["viewer"]["email"], "test.user@urls.fyi");
+
+    // ad-hoc queries are rejected, and can't be registered
+    for session in [session.as_str(), ""] {
+        let (status, body) = reply!(&server, setup::graphql(QUERY_VERIFIED, json!({}), session));
+        assert_eq!(status, 403);
+        assert_eq!(
+            body["errors"][0]["message"],
+            "Only persisted queries are allowed"
+        );
+        assert_eq!(
+            body["errors"][0]["extensions"]["code"],
+            "PERSISTED_QUERY_REQUIRED"
+        );
+    }
+    let hash = persisted::hash(QUERY_VERIFIED);
+    let request = persisted_request(&hash, Some(QUERY_VERIFIED), &session);
+    let (status, _) = reply!(&server, request);
+    assert_eq!(status, 403);
+    let (_, body) = reply!(&server, persisted_request(&hash, None, &session));
+    assert_eq!(body["errors"][0]["message"], "PersistedQueryNotFound");
+
+    // except by administrators, who can register them
+    let request = setup::graphql(QUERY_VERIFIED, json!({}), &admin_session);
+    let (status, body) = reply!(&server, request);
+    assert_eq!(status, 200, "{}", body);
+    let request = persisted_request(&hash, Some(QUERY_VERIFIED), &admin_session);
+    let (status, _) = reply!(&server, request);
+    assert_eq!(status, 200);
+    let (status, body) = reply!(&server, persisted_request(&hash, None, &session));
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["viewer"]["emailVerified"], true);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_registration_limits() {
+    let conf = Config::test().with_register_persisted_queries(true);
+    let (server, ctx) = setup::mock_with_config(conf).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // long queries are executed, but not registered
+    let query = format!(
+        "{} # {}",
+        QUERY_VERIFIED,
+        "padding".repeat(persisted::MAX_QUERY_BYTES / 7)
+    );
+    let hash = persisted::hash(&query);
+    let (status, body) = reply!(&server, persisted_request(&hash, Some(&query), &session));
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["viewer"]["emailVerified"], true);
+    let (_, body) = reply!(&server, persisted_request(&hash, None, &session));
+    assert_eq!(body["errors"][0]["message"], "PersistedQueryNotFound");
+
+    // nor are queries beyond the hourly limit of each client
+    let queries: Vec<String> = (0..101)
+        .map(|index| format!("query Q{} {{ viewer {{ id }} }}", index))
+        .collect();
+    for query in &queries {
+        let hash = persisted::hash(query);
+        let (status, body) = reply!(&server, persisted_request(&hash, Some(query), &session));
+        assert_eq!(status, 200, "{}", body);
+    }
+    let hash = persisted::hash(&queries[99]);
+    let (status, _) = reply!(&server, persisted_request(&hash, None, &session));
+    assert_eq!(status, 200);
+    let hash = persisted::hash(&queries[100]);
+    let (_, body) = reply!(&server, persisted_request(&hash, None, &session));
+    assert_eq!(body["errors"][0]["message"], "PersistedQueryNotFound");
+}
@@ -0,0 +1,228 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::models::User;
+use server::schema::urls;
+mod setup;
+
+const MUTATION_PIN: &str = "
+    mutation PinUrl($id: ID!) {
+        pinUrl(id: $id) {
+            pinned
+            pinnedAt
+        }
+    }
+";
+
+const MUTATION_UNPIN: &str = "
+    mutation UnpinUrl($id: ID!) {
+        unpinUrl(id: $id) {
+            pinned
+            pinnedAt
+        }
+    }
+";
+
+const MUTATION_DELETE: &str = "
+    mutation DeleteUrl($id: ID!) {
+        deleteUrl(id: $id) {
+            ok
+        }
+    }
+";
+
+const QUERY_PROFILE_URLS: &str = "
+    query ProfileUrls($after: String) {
+        user(username: \"test-user\") {
+            urls(first: 2, after: $after) {
+                edges {
+                    node { title pinned }
+                }
+                pageInfo {
+                    hasNextPage
+                    endCursor
+                }
+            }
+        }
+    }
+";
+
+/// Run the given mutation for the given submission,
+/// returning the GraphQL response.
+macro_rules! mutate {
+    ($server:expr, $session:expr, $query:expr, $id:expr) => {{
+        let vars = json!({ "id": $id.to_string() });
+        let res = setup::graphql($query, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Fetch all pages of submissions on the profile of the
+/// test user, returning the titles of each page.
+macro_rules! pages {
+    ($server:expr) => {{
+        let mut pages = vec![];
+        let mut after = Value::Null;
+        loop {
+            let res = setup::graphql(QUERY_PROFILE_URLS, json!({ "after": after }), "")
+                .reply($server)
+                .await;
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+            let urls = &body["data"]["user"]["urls"];
+            let titles: Vec<String> = urls["edges"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|edge| edge["node"]["title"].as_str().unwrap().to_string())
+                .collect();
+            pages.push(titles);
+            if urls["pageInfo"]["hasNextPage"] != true {
+                break;
+            }
+            after = urls["pageInfo"]["endCursor"].clone();
+        }
+        pages
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pin_limit() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let mut ids = vec![];
+    for i in 0..4 {
+        ids.push(
+            setup::Submission::by(user.id())
+                .title(&format!("Submission {}", i))
+                .created_at(ctx.now() - Duration::hours(i))
+                .insert(&ctx)
+                .await,
+        );
+    }
+    for id in &ids[..3] {
+        let body = mutate!(&server, &session, MUTATION_PIN, id);
+        assert_eq!(body["data"]["pinUrl"]["pinned"], true);
+        assert!(body["data"]["pinUrl"]["pinnedAt"].is_string());
+    }
+
+    // pinning again does not take up another pin
+    let body = mutate!(&server, &session, MUTATION_PIN, ids[0]);
+    assert_eq!(body["data"]["pinUrl"]["pinned"], true);
+
+    let body = mutate!(&server, &session, MUTATION_PIN, ids[3]);
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "You can pin at most 3 submissions, please unpin one first"
+    );
+
+    // unpinning or deleting frees a pin
+    let body = mutate!(&server, &session, MUTATION_UNPIN, ids[0]);
+    assert_eq!(
+        body,
+        json!({ "data": { "unpinUrl": { "pinned": false, "pinnedAt": null } } })
+    );
+    let body = mutate!(&server, &session, MUTATION_PIN, ids[3]);
+    assert_eq!(body["data"]["pinUrl"]["pinned"], true);
+
+    let body = mutate!(&server, &session, MUTATION_DELETE, ids[1]);
+    assert_eq!(body["data"]["deleteUrl"]["ok"], true);
+    let body = mutate!(&server, &session, MUTATION_PIN, ids[0]);
+    assert_eq!(body["data"]["pinUrl"]["pinned"], true);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pinned_urls_order() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let mut ids = vec![];
+    for (hours, title) in ["A", "B", "C", "D", "E"].iter().enumerate() {
+        ids.push(
+            setup::Submission::by(user.id())
+                .title(title)
+                .created_at(ctx.now() - Duration::hours(hours as i64))
+                .insert(&ctx)
+                .await,
+        );
+    }
+    assert_eq!(
+        pages!(&server),
+        vec![vec!["A", "B"], vec!["C", "D"], vec!["E"]]
+    );
+
+    // pinned submissions come first, in the order they were
+    // pinned, and pages continue across the boundary
+    for i in [2, 0] {
+        mutate!(&server, &session, MUTATION_PIN, ids[i]);
+    }
+    assert_eq!(
+        pages!(&server),
+        vec![vec!["C", "A"], vec!["B", "D"], vec!["E"]]
+    );
+
+    mutate!(&server, &session, MUTATION_PIN, ids[4]);
+    assert_eq!(
+        pages!(&server),
+        vec![vec!["C", "A"], vec!["E", "B"], vec!["D"]]
+    );
+
+    // a pinned cursor keeps working after unpinning
+    let res = setup::graphql(QUERY_PROFILE_URLS, json!({ "after": null }), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let after = body["data"]["user"]["urls"]["pageInfo"]["endCursor"].clone();
+    mutate!(&server, &session, MUTATION_UNPIN, ids[0]);
+    let res = setup::graphql(QUERY_PROFILE_URLS, json!({ "after": after }), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["user"]["urls"]["edges"],
+        json!([
+            { "node": { "title": "E", "pinned": true } },
+            { "node": { "title": "A", "pinned": false } },
+        ])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pin_ownership() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let id = setup::Submission::by(admin.id())
+        .title("Admin submission")
+        .insert(&ctx)
+        .await;
+
+    // not even administrators can pin to other profiles
+    let body = mutate!(&server, &session, MUTATION_PIN, id);
+    assert_eq!(
+        body["errors"][0]["message"],
+        "You can only pin your own submissions"
+    );
+    let body = mutate!(&server, "", MUTATION_PIN, id);
+    assert!(body["data"].is_null());
+
+    let body = mutate!(&server, &admin_session, MUTATION_PIN, id);
+    assert_eq!(body["data"]["pinUrl"]["pinned"], true);
+    let body = mutate!(&server, &session, MUTATION_UNPIN, id);
+    assert_eq!(
+        body["errors"][0]["message"],
+        "You can only pin your own submissions"
+    );
+}
@@ -0,0 +1,198 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::schema::urls;
+use server::{Config, PostingLimits};
+use warp::http::Response;
+use warp::hyper::body::Bytes;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id }
+        }
+    }
+";
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!) {
+        addComment(urlId: $url, body: $body) { id }
+    }
+";
+
+const QUERY_RATE_LIMITS: &str = "
+    query RateLimits {
+        viewer {
+            rateLimits { scope limit window remaining retryAfter }
+        }
+    }
+";
+
+/// The value of the `Retry-After` header of the given response.
+fn retry_after(res: &Response<Bytes>) -> Option<i64> {
+    res.headers()
+        .get("Retry-After")
+        .map(|value| value.to_str().unwrap().parse().unwrap())
+}
+
+/// The rate limits of the given session, by scope.
+macro_rules! rate_limit {
+    ($server:expr, $session:expr, $scope:expr) => {{
+        let body = setup::execute($server, QUERY_RATE_LIMITS, json!({}), $session).await;
+        body["data"]["viewer"]["rateLimits"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|limit| limit["scope"] == $scope)
+            .cloned()
+            .unwrap_or(Value::Null)
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_submission_limit_window() {
+    let limits = PostingLimits {
+        submissions: 2,
+        comments: 30,
+    };
+    let config = Config::test()
+        .with_posting_limits(limits)
+        .with_rate_limit_new_account_age(Duration::zero())
+        .with_anonymous_submissions(true);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    assert_eq!(
+        rate_limit!(&server, &session, "submit_url"),
+        json!({
+            "scope": "submit_url",
+            "limit": 2,
+            "window": 3600,
+            "remaining": 2,
+            "retryAfter": null,
+        })
+    );
+
+    // anonymous posts count against their author
+    let vars = json!({ "input": { "title": "Limited", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let first = body["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "input": { "title": "Hidden", "text": "Text", "anonymous": true } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    assert_eq!(rate_limit!(&server, &session, "submit_url")["remaining"], 0);
+
+    let vars = json!({ "input": { "title": "Limited", "text": "Some text" } });
+    let res = setup::graphql(MUTATION_SUBMIT, vars, &session)
+        .reply(&server)
+        .await;
+    let retry_after = retry_after(&res);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    let extensions = &body["errors"][0]["extensions"];
+    assert_eq!(extensions["code"], "RATE_LIMITED");
+    assert_eq!(extensions["scope"], "submit_url");
+    let retry_after = retry_after.expect("Missing Retry-After header");
+    assert!(retry_after > 0 && retry_after <= 3600);
+    assert_eq!(extensions["retryAfter"].as_i64(), Some(retry_after));
+    let limit = rate_limit!(&server, &session, "submit_url");
+    assert!(limit["retryAfter"].as_i64().unwrap() <= retry_after);
+
+    // the limit frees up once the first post leaves the window
+    let id: UrlID = first.as_str().unwrap().parse().unwrap();
+    let created_at = (ctx.now() - Duration::minutes(61)).naive_utc();
+    diesel::update(urls::table.find(id))
+        .set(urls::dsl::created_at.eq(created_at))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    assert_eq!(rate_limit!(&server, &session, "submit_url")["remaining"], 1);
+    let vars = json!({ "input": { "title": "Limited", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let vars = json!({ "input": { "title": "Limited", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "RATE_LIMITED");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_new_account_comment_limit() {
+    let established = PostingLimits {
+        submissions: 5,
+        comments: 3,
+    };
+    let new_account = PostingLimits {
+        submissions: 2,
+        comments: 1,
+    };
+    let config = Config::test()
+        .with_posting_limits(established)
+        .with_new_account_posting_limits(new_account);
+    let (server, ctx) = setup::mock_with_config(config.clone()).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    // the test users were just created, so they are new accounts
+    let vars = json!({ "input": { "title": "Limited", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &admin_session).await;
+    let url = body["data"]["submitUrl"]["url"]["id"].clone();
+    let limit = rate_limit!(&server, &session, "add_comment");
+    assert_eq!(limit["limit"], 1);
+    assert_eq!(limit["remaining"], 1);
+    assert_eq!(rate_limit!(&server, &session, "submit_url")["limit"], 2);
+
+    let vars = json!({ "url": url, "body": "First" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let vars = json!({ "url": url, "body": "Second" });
+    let res = setup::graphql(MUTATION_ADD_COMMENT, vars, &session)
+        .reply(&server)
+        .await;
+    let retry_after = retry_after(&res);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    let extensions = &body["errors"][0]["extensions"];
+    assert_eq!(extensions["code"], "RATE_LIMITED");
+    assert_eq!(extensions["scope"], "add_comment");
+    assert!(retry_after.is_some());
+
+    // established accounts have the regular limits
+    let config = config.with_rate_limit_new_account_age(Duration::zero());
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let limit = rate_limit!(&server, &session, "add_comment");
+    assert_eq!(limit["limit"], 3);
+    assert_eq!(rate_limit!(&server, &session, "submit_url")["limit"], 5);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_admins_are_exempt() {
+    let none = PostingLimits {
+        submissions: 0,
+        comments: 0,
+    };
+    let config = Config::test()
+        .with_posting_limits(none)
+        .with_new_account_posting_limits(none);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let body = setup::execute(&server, QUERY_RATE_LIMITS, json!({}), &admin_session).await;
+    assert_eq!(body["data"]["viewer"]["rateLimits"], json!([]));
+    let vars = json!({ "input": { "title": "Limited", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &admin_session).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let url = body["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "url": url, "body": "Unlimited" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars.clone(), &admin_session).await;
+    assert!(body["errors"].is_null(), "{}", body);
+
+    // everyone else is limited
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    assert_eq!(body["errors"][0]["extensions"]["scope"], "add_comment");
+    let vars = json!({ "input": { "title": "Limited", "text": "Some text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    assert_eq!(body["errors"][0]["extensions"]["scope"], "submit_url");
+}
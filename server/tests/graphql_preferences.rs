@@ -0,0 +1,137 @@
+use serde_json::{json, Value};
+mod setup;
+
+const QUERY_PREFERENCES: &str = "
+    query Preferences {
+        viewer {
+            preferences {
+                timezone
+                locale
+                defaultSort
+                notifyNewLogin
+            }
+        }
+    }
+";
+
+const QUERY_UPDATE: &str = "
+    mutation UpdatePreferences($input: PreferencesInput!) {
+        updatePreferences(input: $input) {
+            preferences {
+                timezone
+                locale
+                defaultSort
+                notifyNewLogin
+            }
+        }
+    }
+";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_preferences() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // defaults are returned before anything is changed
+    let res = setup::graphql(QUERY_PREFERENCES, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["preferences"],
+        json!({
+            "timezone": "UTC",
+            "locale": "en",
+            "defaultSort": "RANKED",
+            "notifyNewLogin": true,
+        })
+    );
+
+    // only provided fields change
+    let vars = json!({ "input": { "timezone": "Europe/Berlin" } });
+    let res = setup::graphql(QUERY_UPDATE, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["updatePreferences"]["preferences"],
+        json!({
+            "timezone": "Europe/Berlin",
+            "locale": "en",
+            "defaultSort": "RANKED",
+            "notifyNewLogin": true,
+        })
+    );
+
+    let vars = json!({ "input": { "defaultSort": "RECENT", "notifyNewLogin": false } });
+    let res = setup::graphql(QUERY_UPDATE, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["updatePreferences"]["preferences"],
+        json!({
+            "timezone": "Europe/Berlin",
+            "locale": "en",
+            "defaultSort": "RECENT",
+            "notifyNewLogin": false,
+        })
+    );
+
+    // preferences are not available when logged out
+    let res = setup::graphql(QUERY_UPDATE, json!({ "input": {} }), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_preferences_validation() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let invalid = [
+        (
+            json!({ "timezone": "Mars/Olympus_Mons" }),
+            "The timezone must be a valid tz database name",
+        ),
+        (json!({ "locale": "xx" }), "The locale is not supported"),
+    ];
+    for (input, message) in invalid.iter() {
+        let vars = json!({ "input": input });
+        let res = setup::graphql(QUERY_UPDATE, vars, &session)
+            .reply(&server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["data"].is_null());
+        assert!(body["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains(message));
+    }
+
+    // unknown sort orders are rejected by the schema
+    let vars = json!({ "input": { "defaultSort": "RANDOM" } });
+    let res = setup::graphql(QUERY_UPDATE, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body.get("data").map(Value::is_null).unwrap_or(true));
+    assert!(body["errors"].is_array());
+
+    // nothing was changed by the invalid updates
+    let res = setup::graphql(QUERY_PREFERENCES, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["preferences"],
+        json!({
+            "timezone": "UTC",
+            "locale": "en",
+            "defaultSort": "RANKED",
+            "notifyNewLogin": true,
+        })
+    );
+}
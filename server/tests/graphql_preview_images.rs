@@ -0,0 +1,146 @@
+use image::{DynamicImage, GenericImageView, ImageOutputFormat, RgbImage};
+use serde_json::{json, Value};
+use server::signing;
+use std::io::Cursor;
+use std::net::SocketAddr;
+use warp::Filter;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($url: String!) {
+        submitUrl(input: { url: $url }) {
+            url {
+                id
+                previewImageUrl
+                metadataStatus
+            }
+        }
+    }
+";
+
+/// Encode a blank PNG image of the given dimensions.
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut data = vec![];
+    DynamicImage::ImageRgb8(RgbImage::new(width, height))
+        .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)
+        .unwrap();
+    data
+}
+
+/// Serve pages whose preview image is the image with the same
+/// name on an ephemeral local port, returning the address of
+/// the server.
+fn serve_pages() -> SocketAddr {
+    let page = warp::path!("page" / String)
+        .and(warp::header::<String>("host"))
+        .map(|name: String, host: String| {
+            warp::reply::html(format!(
+                "<html><head>\
+                <meta property=\"og:image\" content=\"http://{}/images/{}\">\
+                </head><body></body></html>",
+                host, name
+            ))
+        });
+    let large = warp::path!("images" / "large")
+        .map(|| warp::reply::with_header(png(2400, 1260), "Content-Type", "image/png"));
+    let oversized = warp::path!("images" / "oversized").map(|| {
+        let mut data = png(10, 10);
+        data.resize(6 * 1024 * 1024, 0);
+        warp::reply::with_header(data, "Content-Type", "image/png")
+    });
+    let html = warp::path!("images" / "html").map(|| warp::reply::html("<html></html>"));
+    let broken = warp::path!("images" / "broken")
+        .map(|| warp::reply::with_header("not an image", "Content-Type", "image/png"));
+
+    let routes = page.or(large).or(oversized).or(html).or(broken);
+    setup::serve(routes)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_preview_image_is_stored() {
+    let (server, ctx) = setup::mock().await;
+    let addr = serve_pages();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "url": format!("http://{}/page/large", addr) });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &body["data"]["submitUrl"]["url"];
+    assert_eq!(url["metadataStatus"], "OK");
+    let path = url["previewImageUrl"].as_str().unwrap();
+    assert!(path.starts_with("/previews/"));
+
+    // the image is served by us, resized
+    let res = warp::test::request().path(path).reply(&server).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["Content-Type"], "image/jpeg");
+    assert_eq!(
+        res.headers()["Cache-Control"],
+        "public, max-age=31536000, immutable"
+    );
+    let image = image::load_from_memory(res.body()).unwrap();
+    assert_eq!(image.dimensions(), (1200, 630));
+
+    // the page links to our copy, never to the remote image
+    let res = warp::test::request()
+        .path(&format!("/comments/{}", url["id"].as_str().unwrap()))
+        .reply(&server)
+        .await;
+    let page = String::from_utf8_lossy(res.body());
+    assert!(page.contains(path.trim_start_matches("/previews/")));
+    assert!(!page.contains("images&#x2f;large"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_preview_image_failures() {
+    let (server, ctx) = setup::mock().await;
+    let addr = serve_pages();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // there is no image, rather than the remote one
+    for image in ["oversized", "html", "broken", "missing"] {
+        let vars = json!({ "url": format!("http://{}/page/{}", addr, image) });
+        let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+        let url = &body["data"]["submitUrl"]["url"];
+        assert_eq!(url["metadataStatus"], "OK", "{}", image);
+        assert_eq!(url["previewImageUrl"], Value::Null, "{}", image);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_preview_images_are_not_an_open_proxy() {
+    let (server, ctx) = setup::mock().await;
+    let addr = serve_pages();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let vars = json!({ "url": format!("http://{}/page/large", addr) });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &body["data"]["submitUrl"]["url"];
+    let path = url["previewImageUrl"].as_str().unwrap();
+    let token = path.trim_start_matches("/previews/");
+    let (key, _) = token.split_once('.').unwrap();
+
+    let remote = format!("http://{}/images/large", addr);
+    let paths = vec![
+        // remote images, signed or not
+        format!("/previews/{}", remote),
+        format!(
+            "/previews/{}",
+            signing::sign(&ctx, "preview_image", &remote)
+        ),
+        // other stored files, or tokens for other purposes
+        format!(
+            "/previews/{}",
+            signing::sign(&ctx, "preview_image", "exports/archive.zip")
+        ),
+        format!(
+            "/previews/{}",
+            signing::sign(&ctx, "data_export", "previews/x.jpg")
+        ),
+        // tampered tokens
+        format!("/previews/{}", key),
+        format!("/previews/{}.{}", key, key),
+    ];
+    for path in paths {
+        let res = warp::test::request().path(&path).reply(&server).await;
+        assert_eq!(res.status(), 404, "{}", path);
+    }
+}
@@ -0,0 +1,193 @@
+use serde_json::{json, Value};
+use server::Config;
+mod setup;
+
+/// Lists the comments of submissions, which is nested seven
+/// fields deep, and has a complexity of `1 + first * (4 + 3 * comments)`.
+const QUERY_NESTED: &str = "
+    query Nested($first: Int!, $comments: Int!) {
+        submissions(first: $first) {
+            edges {
+                node {
+                    id
+                    comments(first: $comments) {
+                        edges {
+                            node { id }
+                        }
+                    }
+                }
+            }
+        }
+    }
+";
+
+/// Lists submissions without a page size, which has a
+/// complexity of `1 + 100 * 3`.
+const QUERY_UNPAGED: &str = "
+    query Unpaged {
+        submissions {
+            edges {
+                node { id }
+            }
+        }
+    }
+";
+
+const QUERY_INTROSPECTION: &str = "
+    query Introspection {
+        __typename
+        __schema {
+            queryType { name }
+            types {
+                name
+                fields {
+                    name
+                    type {
+                        name
+                        ofType { name ofType { name } }
+                    }
+                }
+            }
+        }
+    }
+";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_query_complexity_limit() {
+    let (server, _) = setup::mock_with_config(Config::test().with_query_limits(7, 341)).await;
+
+    let vars = json!({ "first": 10, "comments": 10 });
+    let res = setup::graphql(QUERY_NESTED, vars, "").reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 200, "{}", body);
+    assert!(body["errors"].is_null(), "{}", body);
+
+    // nested connections multiply the cost of their selections
+    let vars = json!({ "first": 10, "comments": 11 });
+    let res = setup::graphql(QUERY_NESTED, vars, "").reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 400);
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Query complexity of 371 exceeds the maximum complexity of 341"
+    );
+    assert_eq!(
+        body["errors"][0]["extensions"],
+        json!({ "code": "QUERY_TOO_COMPLEX", "complexity": 371, "maxComplexity": 341 })
+    );
+
+    // connections without a page size cost as much as the largest page
+    let (server, _) = setup::mock_with_config(Config::test().with_query_limits(7, 301)).await;
+    let res = setup::graphql(QUERY_UNPAGED, json!({}), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 200, "{}", body);
+    let (server, _) = setup::mock_with_config(Config::test().with_query_limits(7, 300)).await;
+    let res = setup::graphql(QUERY_UNPAGED, json!({}), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 400);
+    assert_eq!(body["errors"][0]["extensions"]["complexity"], 301);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_query_depth_limit() {
+    let vars = json!({ "first": 1, "comments": 1 });
+    let (server, _) = setup::mock_with_config(Config::test().with_query_limits(7, 1_000)).await;
+    let res = setup::graphql(QUERY_NESTED, vars.clone(), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 200, "{}", body);
+
+    let (server, _) = setup::mock_with_config(Config::test().with_query_limits(6, 1_000)).await;
+    let res = setup::graphql(QUERY_NESTED, vars, "").reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 400);
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Query depth of 7 exceeds the maximum depth of 6"
+    );
+    assert_eq!(
+        body["errors"][0]["extensions"],
+        json!({ "code": "QUERY_TOO_DEEP", "depth": 7, "maxDepth": 6 })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_introspection_is_not_limited() {
+    let (server, _) = setup::mock_with_config(Config::test().with_query_limits(1, 1)).await;
+    let res = setup::graphql(QUERY_INTROSPECTION, json!({}), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 200, "{}", body);
+    assert_eq!(body["data"]["__schema"]["queryType"]["name"], "Query");
+
+    // but queries which mix in other fields are
+    let query = "{ __typename viewer { id } }";
+    let res = setup::graphql(query, json!({}), "").reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 400);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "QUERY_TOO_DEEP");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_variable_defaults_are_limited() {
+    let query = QUERY_NESTED.replace("$comments: Int!", "$comments: Int = 11");
+    let (server, _) = setup::mock_with_config(Config::test().with_query_limits(7, 341)).await;
+    let res = setup::graphql(&query, json!({ "first": 10 }), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 400);
+    assert_eq!(body["errors"][0]["extensions"]["complexity"], 371);
+
+    let vars = json!({ "first": 10, "comments": 10 });
+    let res = setup::graphql(&query, vars, "").reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 200, "{}", body);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unparsable_queries_are_rejected() {
+    let (server, _) = setup::mock().await;
+    let res = setup::graphql("{ submissions { edges { node { id } }", json!({}), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 400);
+    assert!(body["data"].is_null());
+    assert_eq!(body["errors"][0]["extensions"]["code"], "BAD_REQUEST");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_batches_are_limited_as_a_whole() {
+    let (server, _) = setup::mock_with_config(Config::test().with_query_limits(7, 341)).await;
+    let request = json!({
+        "query": QUERY_NESTED,
+        "variables": { "first": 10, "comments": 10 },
+    });
+    let batch = |size: usize| {
+        warp::test::request()
+            .path("/graphql")
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .body(Value::Array(vec![request.clone(); size]).to_string())
+    };
+    let res = batch(1).reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 200, "{}", body);
+
+    // each query is within budget, but together they are not
+    let res = batch(2).reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(res.status(), 400);
+    assert_eq!(
+        body["errors"][0]["extensions"],
+        json!({ "code": "QUERY_TOO_COMPLEX", "complexity": 682, "maxComplexity": 341 })
+    );
+}
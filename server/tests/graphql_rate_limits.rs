@@ -0,0 +1,80 @@
+use serde_json::{json, Value};
+mod setup;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_request_login_rate_limit() {
+    let (server, _ctx) = setup::mock().await;
+
+    let query = "
+        mutation RequestLogin($email: String!) {
+            requestLogin(email: $email) {
+                ok
+            }
+        }
+    ";
+
+    // users can request 3 logins per hour
+    for _ in 0..3 {
+        let vars = json!({ "email": "test.user@urls.fyi" });
+        let res = setup::graphql(query, vars, "").reply(&server).await;
+        assert_eq!(res.status(), 200);
+        assert!(res.headers().get("Retry-After").is_none());
+    }
+
+    let vars = json!({ "email": "test.user@urls.fyi" });
+    let res = setup::graphql(query, vars, "").reply(&server).await;
+    assert_eq!(res.status(), 200);
+
+    let retry_after: i64 = res
+        .headers()
+        .get("Retry-After")
+        .expect("Missing Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 3600);
+
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    let extensions = &body["errors"][0]["extensions"];
+    assert_eq!(extensions["code"], "RATE_LIMITED");
+    assert_eq!(extensions["scope"], "request_login");
+    assert_eq!(extensions["retryAfter"].as_i64(), Some(retry_after));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_issue_invite_rate_limit() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let query = "
+        mutation IssueInvite {
+            issueInvite {
+                token
+            }
+        }
+    ";
+
+    // even admins can only issue 25 invites per hour
+    for _ in 0..25 {
+        let vars = json!({});
+        let res = setup::graphql(query, vars, &session).reply(&server).await;
+        assert_eq!(res.status(), 200);
+
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["data"]["issueInvite"]["token"].is_string());
+    }
+
+    let vars = json!({});
+    let res = setup::graphql(query, vars, &session).reply(&server).await;
+    assert_eq!(res.status(), 200);
+    assert!(res.headers().get("Retry-After").is_some());
+
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    let extensions = &body["errors"][0]["extensions"];
+    assert_eq!(extensions["code"], "RATE_LIMITED");
+    assert_eq!(extensions["scope"], "issue_invite");
+    assert!(extensions["retryAfter"].as_i64().unwrap() > 0);
+}
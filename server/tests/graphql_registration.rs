@@ -0,0 +1,224 @@
+use serde_json::{json, Value};
+use server::{Config, RegistrationMode};
+mod setup;
+
+const QUERY_ISSUE_INVITE: &str = "
+    mutation IssueInvite {
+        issueInvite {
+            token
+        }
+    }
+";
+
+const QUERY_REGISTER: &str = "
+    mutation RegisterUser($name: String!, $email: String!, $token: String!) {
+        registerUser(input: { name: $name, email: $email }, token: $token) {
+            name
+        }
+    }
+";
+
+macro_rules! issue_invite {
+    ($server:expr, $session:expr) => {{
+        let res = setup::graphql(QUERY_ISSUE_INVITE, json!({}), $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body["data"]["issueInvite"]["token"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_email_normalization() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // differently formatted emails refer to the same account
+    let token = issue_invite!(&server, &session);
+    let vars = json!({
+        "name": "Test User Again",
+        "email": "Test.User@URLS.fyi",
+        "token": token,
+    });
+    let res = setup::graphql(QUERY_REGISTER, vars, "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "An account with this email address already exists"
+    );
+
+    let query = "
+        mutation RequestLogin($email: String!) {
+            requestLogin(email: $email) {
+                ok
+            }
+        }
+    ";
+    let vars = json!({ "email": "  TEST.USER@urls.fyi " });
+    let res = setup::graphql(query, vars, "").reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, json!({ "data": { "requestLogin": { "ok": true } } }));
+
+    // invalid email addresses are rejected
+    let vars = json!({ "email": "not an email" });
+    let res = setup::graphql(query, vars, "").reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "A valid email address is required"
+    );
+
+    let vars = json!({
+        "name": "Test Invalid",
+        "email": "test.invalid@",
+        "token": token,
+    });
+    let res = setup::graphql(QUERY_REGISTER, vars, "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("A valid email address is required"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_email_blocklist() {
+    let blocklist = std::env::temp_dir().join(format!("blocklist-{}.txt", nanoid::nanoid!()));
+    tokio::fs::write(&blocklist, "# disposable domains\nblocked.example\n")
+        .await
+        .unwrap();
+
+    let conf = Config::test().with_email_blocklist(&blocklist);
+    let (server, ctx) = setup::mock_with_config(conf).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    for email in &["someone@blocked.example", "someone@mail.BLOCKED.example"] {
+        let token = issue_invite!(&server, &session);
+        let vars = json!({
+            "name": "Test Blocked",
+            "email": email,
+            "token": token,
+        });
+        let res = setup::graphql(QUERY_REGISTER, vars, "")
+            .reply(&server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["data"].is_null());
+        assert_eq!(
+            body["errors"][0]["extensions"]["code"],
+            "EMAIL_DOMAIN_BLOCKED"
+        );
+    }
+
+    let token = issue_invite!(&server, &session);
+    let vars = json!({
+        "name": "Test Allowed",
+        "email": "someone@allowed.example",
+        "token": token,
+    });
+    let res = setup::graphql(QUERY_REGISTER, vars, "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["registerUser"]["name"], "Test Allowed");
+
+    tokio::fs::remove_file(&blocklist).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_registration_modes() {
+    let query_mode = "
+        query RegistrationMode {
+            registrationMode
+        }
+    ";
+    let query_register = "
+        mutation RegisterUser($name: String!, $email: String!) {
+            registerUser(input: { name: $name, email: $email }) {
+                name
+            }
+        }
+    ";
+    let vars = json!({
+        "name": "Test Register User",
+        "email": "test.register@urls.fyi",
+    });
+
+    // invite only requires a token
+    let (server, _) = setup::mock().await;
+    let res = setup::graphql(query_mode, json!({}), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body,
+        json!({ "data": { "registrationMode": "INVITE_ONLY" } })
+    );
+
+    let res = setup::graphql(query_register, vars.clone(), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "An invitation is required to register"
+    );
+
+    // open registration does not
+    let conf = Config::test().with_registration_mode(RegistrationMode::Open);
+    let (server, _) = setup::mock_with_config(conf).await;
+    let res = setup::graphql(query_mode, json!({}), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, json!({ "data": { "registrationMode": "OPEN" } }));
+
+    let res = setup::graphql(query_register, vars.clone(), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body,
+        json!({ "data": { "registerUser": { "name": "Test Register User" } } })
+    );
+
+    // closed registration refuses even valid invites
+    let conf = Config::test().with_registration_mode(RegistrationMode::Closed);
+    let (server, ctx) = setup::mock_with_config(conf).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let res = setup::graphql(query_mode, json!({}), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, json!({ "data": { "registrationMode": "CLOSED" } }));
+
+    let token = issue_invite!(&server, &session);
+    let res = setup::graphql(
+        QUERY_REGISTER,
+        json!({
+            "name": "Test Register User",
+            "email": "test.register@urls.fyi",
+            "token": token,
+        }),
+        "",
+    )
+    .reply(&server)
+    .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Registration of new accounts is closed"
+    );
+}
@@ -0,0 +1,145 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::models::User;
+use server::domain;
+use server::schema::{tags, urls};
+mod setup;
+
+const QUERY_RELATED: &str = "
+    query Related($id: ID!, $limit: Int) {
+        fetch__Url(id: $id) {
+            related(limit: $limit) { title }
+        }
+    }
+";
+
+const MUTATION_MUTE: &str = "
+    mutation MuteDomain($domain: String!) {
+        muteDomain(domain: $domain) { ok }
+    }
+";
+
+const MUTATION_SHOW_NSFW: &str = "
+    mutation UpdatePreferences {
+        updatePreferences(input: { showNsfw: SHOW }) {
+            preferences { showNsfw }
+        }
+    }
+";
+
+/// Fetch the titles of the submissions related to the
+/// given submission, using the default limit if none is given.
+macro_rules! related {
+    ($server:expr, $session:expr, $id:expr, $limit:expr) => {{
+        let mut vars = json!({ "id": $id });
+        let limit: Value = json!($limit);
+        if !limit.is_null() {
+            vars["limit"] = limit;
+        }
+        let res = setup::graphql(QUERY_RELATED, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body["data"]["fetch__Url"]["related"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|url| url["title"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_related_urls() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let base = "Writing async web servers in Rust";
+    let id = setup::Submission::by(user.id())
+        .url("https://example.com/1")
+        .domain("example.com")
+        .title(base)
+        .tags(&["rust", "web"])
+        .insert(&ctx)
+        .await;
+    let seed: &[(&str, &str, &[&str])] = &[
+        ("https://tags.org/1", "Shared tags", &["rust", "web"]),
+        ("https://example.com/2", "Async servers explained", &[]),
+        ("https://muted.com/1", "One shared tag", &["rust"]),
+        ("https://other.net/1", "Rust in production", &[]),
+        ("https://food.com/1", "Cooking pasta", &["food"]),
+        ("https://tags.org/2", "Deleted", &["rust", "web"]),
+        ("https://tags.org/3", "Removed", &["rust", "web"]),
+        ("https://tags.org/4", "Not safe for work", &["rust", "web"]),
+    ];
+    let mut ids = vec![];
+    for (i, (url, title, tags)) in seed.iter().enumerate() {
+        ids.push(
+            setup::Submission::by(user.id())
+                .url(url)
+                .domain(&domain::of_url(url))
+                .title(title)
+                .tags(tags)
+                .created_at(ctx.now() - Duration::minutes(i as i64 + 1))
+                .insert(&ctx)
+                .await,
+        );
+    }
+    let conn = ctx.conn().await.unwrap();
+    diesel::update(urls::table.find(ids[5]))
+        .set(urls::dsl::deleted_at.eq(ctx.now().naive_utc()))
+        .execute(&*conn)
+        .unwrap();
+    diesel::update(urls::table.find(ids[6]))
+        .set(urls::dsl::removed_at.eq(ctx.now().naive_utc()))
+        .execute(&*conn)
+        .unwrap();
+    diesel::update(urls::table.find(ids[7]))
+        .set(urls::dsl::nsfw.eq(true))
+        .execute(&*conn)
+        .unwrap();
+    drop(conn);
+
+    // two shared tags outweigh the same domain and two shared
+    // words, which outweigh a single shared tag or word
+    assert_eq!(
+        related!(&server, "", id, Value::Null),
+        vec![
+            "Shared tags",
+            "Async servers explained",
+            "One shared tag",
+            "Rust in production",
+        ]
+    );
+    assert_eq!(
+        related!(&server, "", id, 2),
+        vec!["Shared tags", "Async servers explained"]
+    );
+    assert!(related!(&server, "", id, -1).is_empty());
+    assert_eq!(
+        related!(&server, "", ids[4], Value::Null),
+        Vec::<String>::new()
+    );
+
+    // muted domains and NSFW preferences of the viewer apply
+    let vars = json!({ "domain": "muted.com" });
+    setup::graphql(MUTATION_MUTE, vars, &session)
+        .reply(&server)
+        .await;
+    setup::graphql(MUTATION_SHOW_NSFW, json!({}), &session)
+        .reply(&server)
+        .await;
+    assert_eq!(
+        related!(&server, &session, id, Value::Null),
+        vec![
+            "Shared tags",
+            "Not safe for work",
+            "Async servers explained",
+            "Rust in production",
+        ]
+    );
+}
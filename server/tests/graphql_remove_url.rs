@@ -0,0 +1,301 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::models::{NewUserInput, User};
+use server::Context;
+mod setup;
+
+const MUTATION_REMOVE: &str = "
+    mutation RemoveUrl($id: ID!, $reason: String!, $shadow: Boolean!) {
+        removeUrl(id: $id, reason: $reason, shadow: $shadow) { ok }
+    }
+";
+
+const QUERY_SUBMISSIONS: &str = "
+    query Submissions {
+        submissions(first: 10) {
+            edges {
+                node { title }
+            }
+        }
+    }
+";
+
+const QUERY_SEARCH: &str = "
+    query Search($query: String!) {
+        search(query: $query) {
+            results(first: 10) {
+                edges {
+                    node { title }
+                }
+            }
+        }
+    }
+";
+
+const QUERY_URL: &str = "
+    query Url($id: ID!) {
+        fetch__Url(id: $id) {
+            removedAt
+            shadowRemoved
+        }
+    }
+";
+
+const QUERY_LOG: &str = "
+    query ModerationLog {
+        moderationLog(first: 10) {
+            edges {
+                node {
+                    moderator { username }
+                    url { title }
+                    shadow
+                    reason
+                }
+            }
+        }
+    }
+";
+
+/// Create another verified user, returning a session
+/// token for them.
+async fn bystander(ctx: &Context) -> String {
+    let input = NewUserInput {
+        name: "Bystander".into(),
+        email: "test.bystander@urls.fyi".into(),
+    };
+    let mut user = User::create(ctx, input).await.unwrap();
+    user.mark_email_verified(ctx).await.unwrap();
+    setup::session_token(ctx, "test.bystander@urls.fyi").await
+}
+
+/// Remove the given submission, returning the GraphQL
+/// response.
+macro_rules! remove {
+    ($server:expr, $session:expr, $id:expr, $reason:expr, $shadow:expr) => {{
+        let vars = json!({ "id": $id.to_string(), "reason": $reason, "shadow": $shadow });
+        let res = setup::graphql(MUTATION_REMOVE, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Titles of the submissions listed on the front page and
+/// found by searching for "submission", as seen with the
+/// given session.
+macro_rules! listed {
+    ($server:expr, $session:expr) => {{
+        let titles = |edges: &Value| -> Vec<String> {
+            edges
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|edge| edge["node"]["title"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let res = setup::graphql(QUERY_SUBMISSIONS, json!({}), $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let submissions = titles(&body["data"]["submissions"]["edges"]);
+        let vars = json!({ "query": "submission" });
+        let res = setup::graphql(QUERY_SEARCH, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let mut results = titles(&body["data"]["search"]["results"]["edges"]);
+        results.sort();
+        (submissions, results)
+    }};
+}
+
+/// Fetch the removal fields of the given submission
+/// as seen with the given session.
+macro_rules! removal {
+    ($server:expr, $session:expr, $id:expr) => {{
+        let vars = json!({ "id": $id.to_string() });
+        let res = setup::graphql(QUERY_URL, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body["data"]["fetch__Url"].clone()
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_remove_url() {
+    let (server, ctx) = setup::mock().await;
+    let author = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let author_session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let other_session = bystander(&ctx).await;
+    setup::Submission::by(author.id())
+        .title("Kept submission")
+        .indexed()
+        .insert(&ctx)
+        .await;
+    let id = setup::Submission::by(author.id())
+        .title("Removed submission")
+        .created_at(ctx.now() - Duration::minutes(1))
+        .indexed()
+        .insert(&ctx)
+        .await;
+
+    let body = remove!(&server, &admin_session, id, "Spam", false);
+    assert_eq!(body["data"]["removeUrl"]["ok"], true);
+
+    // hidden from everyone, including the author and moderators
+    for session in [&author_session, &other_session, &admin_session] {
+        let (submissions, results) = listed!(&server, session.as_str());
+        assert_eq!(submissions, vec!["Kept submission"]);
+        assert_eq!(results, vec!["Kept submission"]);
+
+        let url = removal!(&server, session.as_str(), id);
+        assert!(url["removedAt"].is_string());
+        assert_eq!(url["shadowRemoved"], false);
+    }
+
+    let res = warp::test::request()
+        .path(&format!("/comments/{}", id))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let page = String::from_utf8_lossy(res.body());
+    assert!(page.contains("[removed by moderator]"));
+    assert!(!page.contains("Removed submission"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_shadow_remove_url() {
+    let (server, ctx) = setup::mock().await;
+    let author = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let author_session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let other_session = bystander(&ctx).await;
+    setup::Submission::by(author.id())
+        .title("Kept submission")
+        .indexed()
+        .insert(&ctx)
+        .await;
+    let id = setup::Submission::by(author.id())
+        .title("Removed submission")
+        .created_at(ctx.now() - Duration::minutes(1))
+        .indexed()
+        .insert(&ctx)
+        .await;
+
+    let body = remove!(&server, &admin_session, id, "Spam", true);
+    assert_eq!(body["data"]["removeUrl"]["ok"], true);
+
+    // the author and moderators still see the submission
+    let both = vec!["Kept submission", "Removed submission"];
+    for session in [&author_session, &admin_session] {
+        let (submissions, results) = listed!(&server, session.as_str());
+        assert_eq!(submissions, both);
+        assert_eq!(results, both);
+    }
+
+    // everyone else doesn't
+    for session in [other_session.as_str(), ""] {
+        let (submissions, results) = listed!(&server, session);
+        assert_eq!(submissions, vec!["Kept submission"]);
+        assert_eq!(results, vec!["Kept submission"]);
+    }
+
+    // only moderators learn about the shadow removal
+    let url = removal!(&server, &author_session, id);
+    assert_eq!(url, json!({ "removedAt": null, "shadowRemoved": false }));
+    let url = removal!(&server, &other_session, id);
+    assert_eq!(url, json!({ "removedAt": null, "shadowRemoved": false }));
+    let url = removal!(&server, &admin_session, id);
+    assert!(url["removedAt"].is_string());
+    assert_eq!(url["shadowRemoved"], true);
+
+    let res = warp::test::request()
+        .path(&format!("/comments/{}", id))
+        .reply(&server)
+        .await;
+    let page = String::from_utf8_lossy(res.body());
+    assert!(!page.contains("[removed by moderator]"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_remove_url_permissions_and_log() {
+    let (server, ctx) = setup::mock().await;
+    let author = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let author_session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let other_session = bystander(&ctx).await;
+    let removed = setup::Submission::by(author.id())
+        .title("Removed submission")
+        .indexed()
+        .insert(&ctx)
+        .await;
+    let shadowed = setup::Submission::by(author.id())
+        .title("Shadowed submission")
+        .created_at(ctx.now() - Duration::minutes(1))
+        .indexed()
+        .insert(&ctx)
+        .await;
+
+    // regular users can not remove submissions, not even their own
+    for session in [&author_session, &other_session] {
+        let body = remove!(&server, session.as_str(), removed, "Spam", false);
+        assert_eq!(body["errors"][0]["message"], "Not authorized");
+    }
+
+    let body = remove!(&server, &admin_session, removed, "   ", false);
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Reasons must have between 1 and 1000 characters"
+    );
+    let body = remove!(&server, &admin_session, removed, "Spam", false);
+    assert_eq!(body["data"]["removeUrl"]["ok"], true);
+    let body = remove!(&server, &admin_session, removed, "Spam", true);
+    assert_eq!(
+        body["errors"][0]["message"],
+        "This submission was already removed"
+    );
+    let body = remove!(&server, &admin_session, shadowed, " Ban evasion ", true);
+    assert_eq!(body["data"]["removeUrl"]["ok"], true);
+
+    // the log is only available to administrators
+    let res = setup::graphql(QUERY_LOG, json!({}), &author_session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["errors"][0]["message"], "Not authorized");
+
+    let res = setup::graphql(QUERY_LOG, json!({}), &admin_session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["moderationLog"]["edges"],
+        json!([
+            {
+                "node": {
+                    "moderator": { "username": "test-administrator" },
+                    "url": { "title": "Shadowed submission" },
+                    "shadow": true,
+                    "reason": "Ban evasion",
+                }
+            },
+            {
+                "node": {
+                    "moderator": { "username": "test-administrator" },
+                    "url": { "title": "Removed submission" },
+                    "shadow": false,
+                    "reason": "Spam",
+                }
+            },
+        ])
+    );
+}
@@ -0,0 +1,272 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::models::{NewUserInput, Url, User};
+use server::schema::urls;
+use server::Context;
+mod setup;
+
+const MUTATION_REPORT: &str = "
+    mutation ReportUrl($id: ID!, $reason: ReportReason!, $note: String) {
+        reportUrl(id: $id, reason: $reason, note: $note) { ok }
+    }
+";
+
+const MUTATION_RESOLVE: &str = "
+    mutation ResolveReport($id: ID!, $action: ModerationAction!) {
+        resolveReport(urlId: $id, action: $action) { ok }
+    }
+";
+
+const QUERY_QUEUE: &str = "
+    query Queue($status: ReportStatus, $after: String) {
+        moderationQueue(first: 2, after: $after, status: $status) {
+            edges {
+                node {
+                    url { title }
+                    reportCount
+                    reasons
+                    reports { reason note action }
+                }
+            }
+            pageInfo {
+                hasNextPage
+                endCursor
+            }
+        }
+    }
+";
+
+/// Create another verified user, returning a session
+/// token for them.
+async fn reporter(ctx: &Context, name: &str) -> String {
+    let email = format!("test.{}@urls.fyi", name.to_lowercase());
+    let input = NewUserInput {
+        name: name.into(),
+        email: email.clone(),
+    };
+    let mut user = User::create(ctx, input).await.unwrap();
+    user.mark_email_verified(ctx).await.unwrap();
+    setup::session_token(ctx, &email).await
+}
+
+/// Report the given submission, returning the GraphQL
+/// response.
+macro_rules! report {
+    ($server:expr, $session:expr, $id:expr, $reason:expr, $note:expr) => {{
+        let vars = json!({ "id": $id.to_string(), "reason": $reason, "note": $note });
+        let res = setup::graphql(MUTATION_REPORT, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Resolve the reports of the given submission, returning
+/// the GraphQL response.
+macro_rules! resolve {
+    ($server:expr, $session:expr, $id:expr, $action:expr) => {{
+        let vars = json!({ "id": $id.to_string(), "action": $action });
+        let res = setup::graphql(MUTATION_RESOLVE, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Fetch all entries of the moderation queue with the given
+/// status, page by page.
+macro_rules! queue {
+    ($server:expr, $session:expr, $status:expr) => {{
+        let mut nodes = vec![];
+        let mut after = Value::Null;
+        loop {
+            let vars = json!({ "status": $status, "after": after });
+            let res = setup::graphql(QUERY_QUEUE, vars, $session)
+                .reply($server)
+                .await;
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+            let queue = &body["data"]["moderationQueue"];
+            nodes.extend(
+                queue["edges"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|edge| edge["node"].clone()),
+            );
+            if queue["pageInfo"]["hasNextPage"] != true {
+                break;
+            }
+            after = queue["pageInfo"]["endCursor"].clone();
+        }
+        nodes
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_report_once_per_user() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let id = setup::Submission::by(admin.id())
+        .title("Reported")
+        .insert(&ctx)
+        .await;
+
+    let body = report!(&server, &session, id, "SPAM", "Buy now");
+    assert_eq!(body, json!({ "data": { "reportUrl": { "ok": true } } }));
+    let body = report!(&server, &session, id, "OFFENSIVE", "  Actually rude  ");
+    assert_eq!(body["data"]["reportUrl"]["ok"], true);
+
+    // reporting again updates the open report
+    assert_eq!(
+        queue!(&server, &admin_session, "OPEN"),
+        vec![json!({
+            "url": { "title": "Reported" },
+            "reportCount": 1,
+            "reasons": ["OFFENSIVE"],
+            "reports": [{ "reason": "OFFENSIVE", "note": "Actually rude", "action": null }],
+        })]
+    );
+
+    // own, deleted, and anonymous reports are rejected
+    let body = report!(&server, &admin_session, id, "SPAM", Value::Null);
+    assert_eq!(
+        body["errors"][0]["message"],
+        "You can not report your own submission"
+    );
+    let body = report!(&server, "", id, "SPAM", Value::Null);
+    assert!(body["data"].is_null());
+    let body = report!(&server, &session, id, "OTHER", "a".repeat(1001));
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Notes can have at most 1000 characters"
+    );
+    diesel::update(urls::table)
+        .filter(urls::dsl::id.eq(id))
+        .set(urls::dsl::deleted_at.eq(ctx.now().naive_utc()))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let body = report!(&server, &session, id, "SPAM", Value::Null);
+    assert_eq!(body["errors"][0]["message"], "This submission was deleted");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_moderation_queue() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let other_session = reporter(&ctx, "Other").await;
+    let third_session = reporter(&ctx, "Third").await;
+
+    let mut ids = vec![];
+    for (minutes, title) in ["First", "Second", "Third"].iter().enumerate() {
+        ids.push(
+            setup::Submission::by(admin.id())
+                .title(title)
+                .created_at(ctx.now() - Duration::minutes(minutes as i64))
+                .insert(&ctx)
+                .await,
+        );
+    }
+    // submissions are queued in the order they were first reported
+    report!(&server, &session, ids[2], "SPAM", Value::Null);
+    report!(&server, &session, ids[0], "SPAM", Value::Null);
+    report!(&server, &other_session, ids[2], "OFF_TOPIC", Value::Null);
+    report!(&server, &third_session, ids[2], "OFF_TOPIC", "Not tech");
+    report!(&server, &other_session, ids[1], "DUPLICATE", Value::Null);
+
+    let queue = queue!(&server, &admin_session, "OPEN");
+    let summary: Vec<(Value, Value, Value)> = queue
+        .iter()
+        .map(|node| {
+            (
+                node["url"]["title"].clone(),
+                node["reportCount"].clone(),
+                node["reasons"].clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (json!("Third"), json!(3), json!(["OFF_TOPIC", "SPAM"])),
+            (json!("First"), json!(1), json!(["SPAM"])),
+            (json!("Second"), json!(1), json!(["DUPLICATE"])),
+        ]
+    );
+    assert!(queue!(&server, &admin_session, "RESOLVED").is_empty());
+
+    // only moderators can see the queue
+    let res = setup::graphql(QUERY_QUEUE, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["errors"][0]["message"], "Not authorized");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resolve_reports() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let other_session = reporter(&ctx, "Other").await;
+
+    let mut ids = vec![];
+    for (minutes, title) in ["Dismissed", "Removed", "Banned"].iter().enumerate() {
+        let id = setup::Submission::by(user.id())
+            .title(title)
+            .created_at(ctx.now() - Duration::minutes(minutes as i64))
+            .insert(&ctx)
+            .await;
+        report!(&server, &other_session, id, "SPAM", Value::Null);
+        ids.push(id);
+    }
+
+    // reporters can not resolve their own reports
+    let body = resolve!(&server, &other_session, ids[0], "DISMISS");
+    assert_eq!(body["errors"][0]["message"], "Not authorized");
+
+    let body = resolve!(&server, &admin_session, ids[0], "DISMISS");
+    assert_eq!(body, json!({ "data": { "resolveReport": { "ok": true } } }));
+    assert!(!Url::find(&ctx, ids[0]).await.unwrap().is_deleted());
+
+    let body = resolve!(&server, &admin_session, ids[1], "REMOVE_URL");
+    assert_eq!(body["data"]["resolveReport"]["ok"], true);
+    assert!(Url::find(&ctx, ids[1]).await.unwrap().is_deleted());
+
+    let body = resolve!(&server, &admin_session, ids[2], "BAN_USER");
+    assert_eq!(body["data"]["resolveReport"]["ok"], true);
+    let user = User::find(&ctx, user.id()).await.unwrap();
+    assert!(user.is_banned());
+    assert!(!Url::find(&ctx, ids[2]).await.unwrap().is_deleted());
+
+    // resolved reports leave the open queue
+    assert!(queue!(&server, &admin_session, "OPEN").is_empty());
+    let actions: Vec<Value> = queue!(&server, &admin_session, "RESOLVED")
+        .iter()
+        .map(|node| node["reports"][0]["action"].clone())
+        .collect();
+    assert_eq!(actions, vec!["DISMISS", "REMOVE_URL", "BAN_USER"]);
+
+    let body = resolve!(&server, &admin_session, ids[0], "DISMISS");
+    assert_eq!(
+        body["errors"][0]["message"],
+        "There are no open reports for this submission"
+    );
+
+    // a resolved report can be followed by a new one
+    let body = report!(&server, &other_session, ids[0], "OTHER", "Still spam");
+    assert_eq!(body["data"]["reportUrl"]["ok"], true);
+    assert_eq!(queue!(&server, &admin_session, "OPEN").len(), 1);
+}
@@ -0,0 +1,141 @@
+use serde_json::json;
+use warp::http::Uri;
+use warp::Filter;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($url: String!) {
+        submitUrl(input: { url: $url }) {
+            url { id url canonicalUrl }
+            duplicate
+        }
+    }
+";
+
+const QUERY_CHECK: &str = "
+    query CheckUrl($url: String!) {
+        checkUrl(url: $url) {
+            url { id }
+        }
+    }
+";
+
+/// A page declaring the given canonical URL.
+fn page_with_canonical(href: &str) -> String {
+    format!(
+        "<html><head><title>A Page</title>\
+        <link rel=\"canonical\" href=\"{}\">\
+        </head><body></body></html>",
+        href
+    )
+}
+
+/// Serve a few test pages on an ephemeral local port,
+/// returning the base URL of the server.
+fn serve_pages() -> String {
+    let article = warp::path("article").map(|| warp::reply::html(page_with_canonical("/article")));
+    let amp = warp::path!("amp" / "article")
+        .map(|| warp::reply::html(page_with_canonical("/article?utm_source=amp")));
+    let stolen = warp::path("stolen")
+        .map(|| warp::reply::html(page_with_canonical("http://other.example/article")));
+    let front = warp::path("front").map(|| warp::reply::html(page_with_canonical("/")));
+    let short_1 =
+        warp::path!("short" / "1").map(|| warp::redirect::permanent(Uri::from_static("/short/2")));
+    let short_2 = warp::path!("short" / "2")
+        .map(|| warp::redirect::temporary(Uri::from_static("/article?utm_source=short")));
+    let missing =
+        warp::path("missing").map(|| warp::redirect::temporary(Uri::from_static("/not-found")));
+    let not_found = warp::path("not-found").map(|| warp::http::StatusCode::NOT_FOUND);
+    let redirect_loop =
+        warp::path("loop").map(|| warp::redirect::temporary(Uri::from_static("/loop")));
+
+    let routes = article
+        .or(amp)
+        .or(stolen)
+        .or(front)
+        .or(short_1)
+        .or(short_2)
+        .or(missing)
+        .or(not_found)
+        .or(redirect_loop);
+    let addr = setup::serve(routes);
+    format!("http://{}", addr)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resolve_redirect_chain() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let base = serve_pages();
+
+    // the submitted URL is kept for display
+    let short = format!("{}/short/1", base);
+    let vars = json!({ "url": short });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let result = &data["submitUrl"];
+    assert_eq!(result["duplicate"], false);
+    assert_eq!(result["url"]["url"], short);
+    assert_eq!(result["url"]["canonicalUrl"], format!("{}/article", base));
+    let id = result["url"]["id"].clone();
+
+    // the target, and other links to it, are duplicates
+    for url in [
+        format!("{}/article", base),
+        format!("{}/short/2", base),
+        format!("{}/amp/article", base),
+    ] {
+        let vars = json!({ "url": url });
+        let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+        let result = &data["submitUrl"];
+        assert_eq!(result["duplicate"], true, "{}", url);
+        assert_eq!(result["url"]["id"], id, "{}", url);
+    }
+
+    // checks resolve the URL the same way
+    let vars = json!({ "url": format!("{}/short/2?utm_medium=x", base) });
+    let body = setup::execute(&server, QUERY_CHECK, vars, "").await;
+    assert_eq!(body["data"]["checkUrl"]["url"]["id"], id);
+
+    // redirects to error pages are not followed
+    let vars = json!({ "url": format!("{}/missing", base) });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let result = &data["submitUrl"];
+    assert_eq!(result["duplicate"], false);
+    assert_eq!(result["url"]["canonicalUrl"], format!("{}/missing", base));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resolve_canonical_link() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let base = serve_pages();
+
+    // alternate versions are stored under the canonical page
+    let amp = format!("{}/amp/article", base);
+    let vars = json!({ "url": amp });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let result = &data["submitUrl"];
+    assert_eq!(result["url"]["url"], amp);
+    assert_eq!(result["url"]["canonicalUrl"], format!("{}/article", base));
+
+    // canonical links to other sites, or to the front page, are ignored
+    for path in ["stolen", "front"] {
+        let url = format!("{}/{}", base, path);
+        let vars = json!({ "url": url });
+        let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+        let result = &data["submitUrl"];
+        assert_eq!(result["duplicate"], false, "{}", url);
+        assert_eq!(result["url"]["canonicalUrl"], url);
+    }
+
+    // looping redirects fall back to the submitted URL
+    let url = format!("{}/loop", base);
+    let vars = json!({ "url": url });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let result = &data["submitUrl"];
+    assert_eq!(result["url"]["canonicalUrl"], url);
+    let vars = json!({ "url": url });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let result = &data["submitUrl"];
+    assert_eq!(result["duplicate"], true);
+}
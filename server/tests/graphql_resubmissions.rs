@@ -0,0 +1,152 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::json;
+use server::db::id::UrlID;
+use server::db::models::User;
+use server::schema::urls;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url {
+                id
+                previousSubmissions { id }
+            }
+            duplicate
+        }
+    }
+";
+
+const QUERY_CHECK: &str = "
+    query CheckUrl($url: String!) {
+        checkUrl(url: $url) { exists }
+    }
+";
+
+/// Check whether the given URL was submitted before.
+macro_rules! exists {
+    ($server:expr, $url:expr) => {{
+        let vars = json!({ "url": $url });
+        let body = setup::execute($server, QUERY_CHECK, vars, "").await;
+        body["data"]["checkUrl"]["exists"].clone()
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resubmit_within_window() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let page = setup::serve_page();
+
+    let id = setup::Submission::by(user.id())
+        .url(&page)
+        .created_at(ctx.now() - Duration::days(300))
+        .insert(&ctx)
+        .await;
+    assert_eq!(exists!(&server, &page), true);
+    let vars = json!({ "input": { "url": page } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let result = &body["data"]["submitUrl"];
+    assert_eq!(result["duplicate"], true);
+    assert_eq!(result["url"]["id"], id.to_string());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resubmit_past_window() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let page = setup::serve_page();
+
+    let first = setup::Submission::by(user.id())
+        .url(&page)
+        .created_at(ctx.now() - Duration::days(800))
+        .insert(&ctx)
+        .await;
+    assert_eq!(exists!(&server, &page), false);
+    let vars = json!({ "input": { "url": page } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let result = &body["data"]["submitUrl"];
+    assert_eq!(result["duplicate"], false);
+    let second = result["url"]["id"].clone();
+    assert_ne!(second, first.to_string());
+    assert_eq!(
+        result["url"]["previousSubmissions"],
+        json!([{ "id": first }])
+    );
+
+    // the new submission is the one duplicates are found for
+    assert_eq!(exists!(&server, &page), true);
+    let vars = json!({ "input": { "url": page } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let result = &body["data"]["submitUrl"];
+    assert_eq!(result["duplicate"], true);
+    assert_eq!(result["url"]["id"], second);
+
+    // once it is old enough as well, the chain continues
+    let created_at = (ctx.now() - Duration::days(400)).naive_utc();
+    let second_id: UrlID = second.as_str().unwrap().parse().unwrap();
+    diesel::update(urls::table.find(second_id))
+        .set(urls::dsl::created_at.eq(created_at))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let vars = json!({ "input": { "url": page } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let result = &body["data"]["submitUrl"];
+    assert_eq!(result["duplicate"], false);
+    assert_eq!(
+        result["url"]["previousSubmissions"],
+        json!([{ "id": second }, { "id": first }])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resubmit_deleted_or_removed() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    for column in &["deleted", "removed"] {
+        let page = setup::serve_page();
+        let id = setup::Submission::by(user.id())
+            .url(&page)
+            .created_at(ctx.now() - Duration::days(1))
+            .insert(&ctx)
+            .await;
+        let target = urls::table.find(id);
+        let now = ctx.now().naive_utc();
+        let conn = ctx.conn().await.unwrap();
+        match *column {
+            "deleted" => diesel::update(target)
+                .set(urls::dsl::deleted_at.eq(now))
+                .execute(&*conn),
+            _ => diesel::update(target)
+                .set(urls::dsl::removed_at.eq(now))
+                .execute(&*conn),
+        }
+        .unwrap();
+        drop(conn);
+
+        // earlier discussions which are gone are not linked
+        assert_eq!(exists!(&server, &page), false, "{}", column);
+        let vars = json!({ "input": { "url": page } });
+        let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+        let result = &body["data"]["submitUrl"];
+        assert_eq!(result["duplicate"], false, "{}", column);
+        assert_ne!(result["url"]["id"], id.to_string(), "{}", column);
+        assert_eq!(
+            result["url"]["previousSubmissions"],
+            json!([]),
+            "{}",
+            column
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use serde_json::json;
+use server::db::models::{NewUserInput, User};
+use server::Config;
+mod setup;
+
+const MUTATION_UPDATE_URL: &str = "
+    mutation UpdateUrl($id: ID!, $input: UpdateUrlInput!) {
+        updateUrl(id: $id, input: $input) {
+            revisionCount
+        }
+    }
+";
+
+const MUTATION_UPDATE_COMMENT: &str = "
+    mutation UpdateComment($id: ID!, $body: String!) {
+        updateComment(id: $id, body: $body) {
+            revisionCount
+        }
+    }
+";
+
+const MUTATION_DELETE_URL: &str = "
+    mutation DeleteUrl($id: ID!) {
+        deleteUrl(id: $id) { ok }
+    }
+";
+
+const MUTATION_DELETE_COMMENT: &str = "
+    mutation DeleteComment($id: ID!) {
+        deleteComment(comment: $id) {
+            revisionCount
+        }
+    }
+";
+
+const QUERY_URL_REVISIONS: &str = "
+    query UrlRevisions($id: ID!) {
+        fetch__Url(id: $id) {
+            revisionCount
+            revisions(first: 10) {
+                edges {
+                    node {
+                        title
+                        description
+                        body
+                        editor { username }
+                    }
+                }
+            }
+        }
+    }
+";
+
+const QUERY_COMMENT_REVISIONS: &str = "
+    query CommentRevisions($id: ID!) {
+        fetch__Comment(id: $id) {
+            revisionCount
+            revisions(first: 10) {
+                edges {
+                    node { body }
+                }
+            }
+        }
+    }
+";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_edits_create_revisions() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let url = setup::Submission::by(user.id())
+        .title("Original title")
+        .insert(&ctx)
+        .await;
+
+    let vars = json!({ "id": url, "input": { "title": "Second title" } });
+    let body = setup::execute(&server, MUTATION_UPDATE_URL, vars, &session).await;
+    assert_eq!(body["data"]["updateUrl"]["revisionCount"], 1);
+    let vars = json!({ "id": url, "input": { "description": "A description" } });
+    let body = setup::execute(&server, MUTATION_UPDATE_URL, vars, &admin_session).await;
+    assert_eq!(body["data"]["updateUrl"]["revisionCount"], 2);
+
+    // edits which change nothing are not recorded
+    let vars = json!({ "id": url, "input": { "title": "Second title" } });
+    let body = setup::execute(&server, MUTATION_UPDATE_URL, vars, &session).await;
+    assert_eq!(body["data"]["updateUrl"]["revisionCount"], 2);
+
+    let body = setup::execute(&server, QUERY_URL_REVISIONS, json!({ "id": url }), &session).await;
+    assert_eq!(
+        body["data"]["fetch__Url"],
+        json!({
+            "revisionCount": 2,
+            "revisions": { "edges": [
+                { "node": {
+                    "title": "Second title",
+                    "description": null,
+                    "body": null,
+                    "editor": { "username": "test-administrator" },
+                } },
+                { "node": {
+                    "title": "Original title",
+                    "description": null,
+                    "body": null,
+                    "editor": { "username": "test-user" },
+                } },
+            ] },
+        })
+    );
+
+    let comment = setup::Comment::on(url, user.id())
+        .text("Original text")
+        .insert(&ctx)
+        .await;
+    for text in &["Second text", "Second text", "Third text"] {
+        let vars = json!({ "id": comment, "body": text });
+        setup::execute(&server, MUTATION_UPDATE_COMMENT, vars, &session).await;
+    }
+    let vars = json!({ "id": comment });
+    let body = setup::execute(&server, QUERY_COMMENT_REVISIONS, vars, &session).await;
+    assert_eq!(
+        body["data"]["fetch__Comment"],
+        json!({
+            "revisionCount": 2,
+            "revisions": { "edges": [
+                { "node": { "body": "Second text" } },
+                { "node": { "body": "Original text" } },
+            ] },
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_revisions_visibility() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let input = NewUserInput {
+        name: "Other".into(),
+        email: "test.other@urls.fyi".into(),
+    };
+    User::create(&ctx, input).await.unwrap();
+    let other_session = setup::session_token(&ctx, "test.other@urls.fyi").await;
+
+    let url = setup::Submission::by(user.id())
+        .title("Original title")
+        .insert(&ctx)
+        .await;
+    let vars = json!({ "id": url, "input": { "title": "Edited title" } });
+    setup::execute(&server, MUTATION_UPDATE_URL, vars, &session).await;
+    let comment = setup::Comment::on(url, user.id())
+        .text("Original text")
+        .insert(&ctx)
+        .await;
+    let vars = json!({ "id": comment, "body": "Edited text" });
+    setup::execute(&server, MUTATION_UPDATE_COMMENT, vars, &session).await;
+
+    for session in &[&session, &admin_session] {
+        let body =
+            setup::execute(&server, QUERY_URL_REVISIONS, json!({ "id": url }), session).await;
+        let revisions = &body["data"]["fetch__Url"]["revisions"]["edges"];
+        assert_eq!(revisions.as_array().unwrap().len(), 1);
+        let vars = json!({ "id": comment });
+        let body = setup::execute(&server, QUERY_COMMENT_REVISIONS, vars, session).await;
+        let revisions = &body["data"]["fetch__Comment"]["revisions"]["edges"];
+        assert_eq!(revisions.as_array().unwrap().len(), 1);
+    }
+
+    // the count is public, the history is not
+    for session in &[other_session.as_str(), ""] {
+        let body =
+            setup::execute(&server, QUERY_URL_REVISIONS, json!({ "id": url }), session).await;
+        assert!(body["data"].is_null());
+        let vars = json!({ "id": comment });
+        let body = setup::execute(&server, QUERY_COMMENT_REVISIONS, vars, session).await;
+        assert!(body["data"].is_null());
+    }
+    let query = "query Count($id: ID!) { fetch__Url(id: $id) { revisionCount } }";
+    let body = setup::execute(&server, query, json!({ "id": url }), "").await;
+    assert_eq!(body["data"]["fetch__Url"]["revisionCount"], 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_public_revisions() {
+    let (server, ctx) = setup::mock_with_config(Config::test().with_public_revisions(true)).await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let url = setup::Submission::by(user.id())
+        .title("Original title")
+        .insert(&ctx)
+        .await;
+    let vars = json!({ "id": url, "input": { "title": "Edited title" } });
+    setup::execute(&server, MUTATION_UPDATE_URL, vars, &session).await;
+    let body = setup::execute(&server, QUERY_URL_REVISIONS, json!({ "id": url }), "").await;
+    let revisions = &body["data"]["fetch__Url"]["revisions"]["edges"];
+    assert_eq!(revisions[0]["node"]["title"], "Original title");
+
+    // deleted content keeps its history private
+    setup::execute(&server, MUTATION_DELETE_URL, json!({ "id": url }), &session).await;
+    let body = setup::execute(&server, QUERY_URL_REVISIONS, json!({ "id": url }), "").await;
+    assert!(body["data"].is_null());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_deleting_keeps_revisions() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let url = setup::Submission::by(user.id())
+        .title("Original title")
+        .insert(&ctx)
+        .await;
+    let vars = json!({ "id": url, "input": { "title": "Edited title" } });
+    setup::execute(&server, MUTATION_UPDATE_URL, vars, &session).await;
+    setup::execute(&server, MUTATION_DELETE_URL, json!({ "id": url }), &session).await;
+    for session in &[&session, &admin_session] {
+        let body =
+            setup::execute(&server, QUERY_URL_REVISIONS, json!({ "id": url }), session).await;
+        let revisions = &body["data"]["fetch__Url"]["revisions"]["edges"];
+        assert_eq!(revisions[0]["node"]["title"], "Original title");
+    }
+
+    // the deleted text of comments is kept as a revision
+    let comment = setup::Comment::on(url, user.id())
+        .text("Original text")
+        .insert(&ctx)
+        .await;
+    let vars = json!({ "id": comment, "body": "Edited text" });
+    setup::execute(&server, MUTATION_UPDATE_COMMENT, vars, &session).await;
+    let vars = json!({ "id": comment });
+    let body = setup::execute(&server, MUTATION_DELETE_COMMENT, vars, &session).await;
+    assert_eq!(body["data"]["deleteComment"]["revisionCount"], 2);
+    let body = setup::execute(&server, QUERY_COMMENT_REVISIONS, vars, &admin_session).await;
+    assert_eq!(
+        body["data"]["fetch__Comment"]["revisions"]["edges"],
+        json!([
+            { "node": { "body": "Edited text" } },
+            { "node": { "body": "Original text" } },
+        ])
+    );
+}
@@ -0,0 +1,348 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::models::User;
+use server::schema::urls;
+mod setup;
+
+const MUTATION_SAVE: &str = "
+    mutation SaveUrl($id: ID!) {
+        saveUrl(id: $id) {
+            viewerHasSaved
+        }
+    }
+";
+
+const MUTATION_UNSAVE: &str = "
+    mutation UnsaveUrl($id: ID!) {
+        unsaveUrl(id: $id) {
+            viewerHasSaved
+        }
+    }
+";
+
+const QUERY_SAVED: &str = "
+    query Saved($after: String) {
+        viewer {
+            savedUrls(first: 2, after: $after) {
+                edges {
+                    node { title }
+                }
+                pageInfo {
+                    hasNextPage
+                    endCursor
+                }
+            }
+        }
+    }
+";
+
+const MUTATION_MARK_READ: &str = "
+    mutation MarkUrlRead($id: ID!) {
+        markUrlRead(id: $id) {
+            viewerHasSaved
+        }
+    }
+";
+
+const MUTATION_MARK_UNREAD: &str = "
+    mutation MarkUrlUnread($id: ID!) {
+        markUrlUnread(id: $id) {
+            viewerHasSaved
+        }
+    }
+";
+
+const QUERY_STATUS: &str = "
+    query Status($status: SavedStatus!) {
+        viewer {
+            savedUrls(first: 10, status: $status) {
+                edges {
+                    node { title }
+                }
+            }
+            savedCounts { unread read }
+        }
+    }
+";
+
+/// Run the given mutation for the given submission,
+/// returning the GraphQL response.
+macro_rules! mutate {
+    ($server:expr, $session:expr, $query:expr, $id:expr) => {{
+        let vars = json!({ "id": $id.to_string() });
+        let res = setup::graphql($query, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Fetch the titles of all saved submissions of the
+/// viewer, page by page.
+macro_rules! saved {
+    ($server:expr, $session:expr) => {{
+        let mut titles = vec![];
+        let mut after = Value::Null;
+        loop {
+            let res = setup::graphql(QUERY_SAVED, json!({ "after": after }), $session)
+                .reply($server)
+                .await;
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+            let saved = &body["data"]["viewer"]["savedUrls"];
+            titles.extend(
+                saved["edges"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|edge| edge["node"]["title"].as_str().unwrap().to_string()),
+            );
+            if saved["pageInfo"]["hasNextPage"] != true {
+                break;
+            }
+            after = saved["pageInfo"]["endCursor"].clone();
+        }
+        titles
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_save_url() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let id = setup::Submission::by(admin.id())
+        .title("Saved submission")
+        .insert(&ctx)
+        .await;
+
+    // saving twice is fine
+    for _ in 0..2 {
+        let body = mutate!(&server, &session, MUTATION_SAVE, id);
+        assert_eq!(
+            body,
+            json!({ "data": { "saveUrl": { "viewerHasSaved": true } } })
+        );
+    }
+    assert_eq!(saved!(&server, &session), vec!["Saved submission"]);
+
+    for _ in 0..2 {
+        let body = mutate!(&server, &session, MUTATION_UNSAVE, id);
+        assert_eq!(
+            body,
+            json!({ "data": { "unsaveUrl": { "viewerHasSaved": false } } })
+        );
+    }
+    assert!(saved!(&server, &session).is_empty());
+
+    // anonymous users can not save submissions
+    let body = mutate!(&server, "", MUTATION_SAVE, id);
+    assert!(body["data"].is_null());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_saved_urls_order() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let mut ids = vec![];
+    for (minutes, title) in ["First", "Second", "Third"].iter().enumerate() {
+        let id = setup::Submission::by(user.id())
+            .title(title)
+            .created_at(ctx.now() - Duration::minutes(minutes as i64))
+            .insert(&ctx)
+            .await;
+        mutate!(&server, &session, MUTATION_SAVE, id);
+        ids.push(id);
+    }
+    // the newest save is listed first, regardless of
+    // when the submission was created
+    assert_eq!(saved!(&server, &session), vec!["Third", "Second", "First"]);
+
+    // saving again moves the submission to the top
+    mutate!(&server, &session, MUTATION_SAVE, ids[0]);
+    assert_eq!(saved!(&server, &session), vec!["First", "Third", "Second"]);
+
+    // deleted submissions are not listed
+    diesel::update(urls::table)
+        .filter(urls::dsl::id.eq(ids[2]))
+        .set(urls::dsl::deleted_at.eq(ctx.now().naive_utc()))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    assert_eq!(saved!(&server, &session), vec!["First", "Second"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_saved_urls_privacy() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let id = setup::Submission::by(user.id())
+        .title("Private save")
+        .insert(&ctx)
+        .await;
+    mutate!(&server, &session, MUTATION_SAVE, id);
+
+    // other users don't see the save
+    assert!(saved!(&server, &admin_session).is_empty());
+    assert!(saved!(&server, "").is_empty());
+    let query = "
+        query Url($id: ID!) {
+            fetch__Url(id: $id) { viewerHasSaved score }
+        }
+    ";
+    let vars = json!({ "id": id.to_string() });
+    for session in [admin_session.as_str(), ""] {
+        let res = setup::graphql(query, vars.clone(), session)
+            .reply(&server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(
+            body,
+            json!({ "data": { "fetch__Url": { "viewerHasSaved": false, "score": 0 } } })
+        );
+    }
+
+    // saves are not part of public profiles
+    let query = "{ __type(name: \"User\") { fields { name } } }";
+    let res = setup::graphql(query, json!({}), "").reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let fields = body["data"]["__type"]["fields"].as_array().unwrap();
+    assert!(!fields.is_empty());
+    assert!(fields
+        .iter()
+        .all(|field| !field["name"].as_str().unwrap().contains("aved")));
+}
+
+/// Fetch the titles of the saves with the given status, and the
+/// read and unread counts.
+macro_rules! status {
+    ($server:expr, $session:expr, $status:expr) => {{
+        let res = setup::graphql(QUERY_STATUS, json!({ "status": $status }), $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let viewer = &body["data"]["viewer"];
+        let titles: Vec<String> = viewer["savedUrls"]["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["node"]["title"].as_str().unwrap().to_string())
+            .collect();
+        (titles, viewer["savedCounts"].clone())
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mark_read_saves_url() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let id = setup::Submission::by(user.id())
+        .title("Read later")
+        .insert(&ctx)
+        .await;
+
+    // marking an unsaved submission as read saves it
+    let body = mutate!(&server, &session, MUTATION_MARK_READ, id);
+    assert_eq!(
+        body,
+        json!({ "data": { "markUrlRead": { "viewerHasSaved": true } } })
+    );
+    let (titles, counts) = status!(&server, &session, "READ");
+    assert_eq!(titles, vec!["Read later"]);
+    assert_eq!(counts, json!({ "unread": 0, "read": 1 }));
+
+    // as does marking it as unread
+    let other = setup::Submission::by(user.id())
+        .title("Unread later")
+        .created_at(ctx.now() - Duration::minutes(1))
+        .insert(&ctx)
+        .await;
+    let body = mutate!(&server, &session, MUTATION_MARK_UNREAD, other);
+    assert_eq!(
+        body,
+        json!({ "data": { "markUrlUnread": { "viewerHasSaved": true } } })
+    );
+    let (titles, counts) = status!(&server, &session, "UNREAD");
+    assert_eq!(titles, vec!["Unread later"]);
+    assert_eq!(counts, json!({ "unread": 1, "read": 1 }));
+
+    // anonymous users have no reading list
+    let body = mutate!(&server, "", MUTATION_MARK_READ, id);
+    assert!(body["data"].is_null());
+    let (titles, counts) = status!(&server, "", "ALL");
+    assert!(titles.is_empty());
+    assert!(counts.is_null());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_saved_status_transitions() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let mut ids = vec![];
+    for (minutes, title) in ["First", "Second", "Third"].iter().enumerate() {
+        let id = setup::Submission::by(user.id())
+            .title(title)
+            .created_at(ctx.now() - Duration::minutes(minutes as i64))
+            .insert(&ctx)
+            .await;
+        mutate!(&server, &session, MUTATION_SAVE, id);
+        ids.push(id);
+    }
+    let (titles, counts) = status!(&server, &session, "UNREAD");
+    assert_eq!(titles, vec!["Third", "Second", "First"]);
+    assert_eq!(counts, json!({ "unread": 3, "read": 0 }));
+
+    // marking as read twice only counts once, and keeps the order
+    for _ in 0..2 {
+        mutate!(&server, &session, MUTATION_MARK_READ, ids[0]);
+    }
+    mutate!(&server, &session, MUTATION_MARK_READ, ids[1]);
+    let (titles, counts) = status!(&server, &session, "UNREAD");
+    assert_eq!(titles, vec!["Third"]);
+    assert_eq!(counts, json!({ "unread": 1, "read": 2 }));
+    let (titles, _) = status!(&server, &session, "READ");
+    assert_eq!(titles, vec!["Second", "First"]);
+    let (titles, _) = status!(&server, &session, "ALL");
+    assert_eq!(titles, vec!["Third", "Second", "First"]);
+
+    // back to unread
+    mutate!(&server, &session, MUTATION_MARK_UNREAD, ids[1]);
+    let (titles, counts) = status!(&server, &session, "UNREAD");
+    assert_eq!(titles, vec!["Third", "Second"]);
+    assert_eq!(counts, json!({ "unread": 2, "read": 1 }));
+
+    // saving a read submission again moves it to the top as unread
+    mutate!(&server, &session, MUTATION_SAVE, ids[0]);
+    let (titles, counts) = status!(&server, &session, "UNREAD");
+    assert_eq!(titles, vec!["First", "Third", "Second"]);
+    assert_eq!(counts, json!({ "unread": 3, "read": 0 }));
+
+    // removed and deleted submissions are no longer counted
+    mutate!(&server, &session, MUTATION_MARK_READ, ids[2]);
+    mutate!(&server, &session, MUTATION_UNSAVE, ids[1]);
+    diesel::update(urls::table)
+        .filter(urls::dsl::id.eq(ids[0]))
+        .set(urls::dsl::deleted_at.eq(ctx.now().naive_utc()))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let (titles, counts) = status!(&server, &session, "ALL");
+    assert_eq!(titles, vec!["Third"]);
+    assert_eq!(counts, json!({ "unread": 0, "read": 1 }));
+}
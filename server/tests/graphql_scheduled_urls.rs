@@ -0,0 +1,181 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::Url;
+use server::schema::urls;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id isDraft publishAt }
+        }
+    }
+";
+
+const MUTATION_UPDATE: &str = "
+    mutation UpdateUrl($id: ID!, $input: UpdateUrlInput!) {
+        updateUrl(id: $id, input: $input) { isDraft publishAt }
+    }
+";
+
+const QUERY_URL: &str = "
+    query Url($id: ID!) {
+        fetch__Url(id: $id) { title isDraft }
+    }
+";
+
+const QUERY_LISTINGS: &str = "
+    query Listings {
+        submissions(first: 10) {
+            edges {
+                node { id }
+            }
+        }
+        viewer {
+            drafts(first: 10) {
+                edges {
+                    node { id }
+                }
+            }
+        }
+    }
+";
+
+/// The IDs of the nodes of the given connection.
+fn ids(connection: &Value) -> Vec<Value> {
+    connection["edges"]
+        .as_array()
+        .expect(&connection.to_string())
+        .iter()
+        .map(|edge| edge["node"]["id"].clone())
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_scheduled_urls_are_published_later() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let publish_at = (Utc::now() + Duration::hours(2)).to_rfc3339();
+    let vars =
+        json!({ "input": { "title": "Scheduled", "text": "Some text", "publishAt": publish_at } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let submitted = &body["data"]["submitUrl"]["url"];
+    assert_eq!(submitted["isDraft"], true, "{}", body);
+    assert!(submitted["publishAt"].is_string());
+    let scheduled = submitted["id"].clone();
+
+    // until then it is only visible to the submitter, as a draft
+    let body = setup::execute(&server, QUERY_LISTINGS, json!({}), &session).await;
+    let data = &body["data"];
+    assert_eq!(ids(&data["submissions"]), Vec::<Value>::new(), "{}", body);
+    assert_eq!(ids(&data["viewer"]["drafts"]), vec![scheduled.clone()]);
+    let vars = json!({ "id": scheduled });
+    let body = setup::execute(&server, QUERY_URL, vars.clone(), &session).await;
+    assert_eq!(
+        body["data"]["fetch__Url"],
+        json!({ "title": "Scheduled", "isDraft": true })
+    );
+    for session in [admin_session.as_str(), ""] {
+        let body = setup::execute(&server, QUERY_URL, vars.clone(), session).await;
+        assert_eq!(
+            body["errors"][0]["message"],
+            "This submission is not published yet"
+        );
+    }
+
+    // nothing is published before its time
+    assert_eq!(Url::publish_scheduled(&ctx).await.unwrap(), 0);
+    let id: UrlID = scheduled.as_str().unwrap().parse().unwrap();
+    let due = (ctx.now() - Duration::minutes(1)).naive_utc();
+    diesel::update(urls::table.find(id))
+        .set(urls::dsl::publish_at.eq(due))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let vars = json!({
+        "input": { "title": "Submitted earlier", "text": "Some text", "publishAt": Value::Null },
+    });
+    let earlier = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let earlier = earlier["data"]["submitUrl"]["url"]["id"].clone();
+
+    // once published it is dated to then, above earlier submissions
+    assert_eq!(Url::publish_scheduled(&ctx).await.unwrap(), 1);
+    assert_eq!(Url::publish_scheduled(&ctx).await.unwrap(), 0);
+    let body = setup::execute(&server, QUERY_LISTINGS, json!({}), &session).await;
+    let data = &body["data"];
+    assert_eq!(
+        ids(&data["submissions"]),
+        vec![scheduled.clone(), earlier.clone()]
+    );
+    assert_eq!(ids(&data["viewer"]["drafts"]), Vec::<Value>::new());
+    let vars = json!({ "id": scheduled });
+    let body = setup::execute(&server, QUERY_URL, vars, "").await;
+    assert_eq!(body["data"]["fetch__Url"]["isDraft"], false, "{}", body);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rescheduling_urls() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let past = (Utc::now() - Duration::hours(1)).to_rfc3339();
+    let vars = json!({
+        "input": { "title": "Scheduled", "text": "Some text", "publishAt": past.clone() },
+    });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Submissions can only be scheduled for the future"
+    );
+    let publish_at = (Utc::now() + Duration::hours(2)).to_rfc3339();
+    let vars =
+        json!({ "input": { "title": "Scheduled", "text": "Some text", "publishAt": publish_at } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let scheduled = body["data"]["submitUrl"]["url"]["id"].clone();
+
+    // scheduled drafts can be moved, but not into the past
+    let later = Utc::now() + Duration::days(1);
+    let vars = json!({ "id": scheduled, "input": { "publishAt": later.to_rfc3339() } });
+    let body = setup::execute(&server, MUTATION_UPDATE, vars, &session).await;
+    let updated = &body["data"]["updateUrl"];
+    assert_eq!(updated["isDraft"], true, "{}", body);
+    let rescheduled = updated["publishAt"].as_str().unwrap();
+    let rescheduled = DateTime::parse_from_rfc3339(rescheduled).unwrap();
+    assert_eq!(rescheduled.timestamp(), later.timestamp());
+    let vars = json!({ "id": scheduled, "input": { "publishAt": past } });
+    let body = setup::execute(&server, MUTATION_UPDATE, vars, &session).await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Submissions can only be scheduled for the future"
+    );
+
+    // unscheduling keeps it as a plain draft
+    let vars = json!({ "id": scheduled, "input": { "unschedule": true } });
+    let body = setup::execute(&server, MUTATION_UPDATE, vars, &session).await;
+    assert_eq!(
+        body["data"]["updateUrl"],
+        json!({ "isDraft": true, "publishAt": null })
+    );
+    let body = setup::execute(&server, QUERY_LISTINGS, json!({}), &session).await;
+    assert_eq!(
+        ids(&body["data"]["viewer"]["drafts"]),
+        vec![scheduled.clone()]
+    );
+    assert_eq!(Url::publish_scheduled(&ctx).await.unwrap(), 0);
+
+    // published submissions can not be scheduled anymore
+    let vars =
+        json!({ "input": { "title": "Published", "text": "Some text", "publishAt": Value::Null } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let published = body["data"]["submitUrl"]["url"]["id"].clone();
+    let future = (Utc::now() + Duration::hours(2)).to_rfc3339();
+    let vars = json!({ "id": published, "input": { "publishAt": future } });
+    let body = setup::execute(&server, MUTATION_UPDATE, vars, &session).await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "This submission was already published"
+    );
+}
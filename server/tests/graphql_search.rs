@@ -0,0 +1,357 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::models::{Url, User};
+mod setup;
+
+const QUERY_SEARCH: &str = "
+    query Search($query: String!, $after: String) {
+        search(query: $query) {
+            results(first: 2, after: $after) {
+                edges {
+                    node { title }
+                }
+                pageInfo {
+                    hasNextPage
+                    endCursor
+                }
+            }
+        }
+    }
+";
+
+/// Fetch a page of search results, returning the GraphQL
+/// response.
+macro_rules! search {
+    ($server:expr, $query:expr, $after:expr) => {{
+        let vars = json!({ "query": $query, "after": $after });
+        let res = setup::graphql(QUERY_SEARCH, vars, "")
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Fetch all search results for the given query, page by page.
+macro_rules! all_results {
+    ($server:expr, $query:expr) => {{
+        let mut titles = vec![];
+        let mut after = Value::Null;
+        loop {
+            let body = search!($server, $query, after);
+            assert!(body["errors"].is_null(), "{}", body);
+            let results = &body["data"]["search"]["results"];
+            titles.extend(
+                results["edges"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|edge| edge["node"]["title"].as_str().unwrap().to_string()),
+            );
+            if results["pageInfo"]["hasNextPage"] != true {
+                break;
+            }
+            after = results["pageInfo"]["endCursor"].clone();
+        }
+        titles
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_ranking() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    let url = "https://example.com/1";
+    setup::Submission::by(user.id())
+        .url(url)
+        .title("Notes on compilers")
+        .description("Why rust is loved")
+        .indexed()
+        .insert(&ctx)
+        .await;
+    let url = "https://example.com/2";
+    setup::Submission::by(user.id())
+        .url(url)
+        .title("Rust in production")
+        .description("A field report")
+        .created_at(ctx.now() - Duration::minutes(10))
+        .indexed()
+        .insert(&ctx)
+        .await;
+    let url = "https://example.com/3";
+    setup::Submission::by(user.id())
+        .url(url)
+        .title("Rusty nails")
+        .description("Hardware store")
+        .created_at(ctx.now() - Duration::minutes(5))
+        .indexed()
+        .insert(&ctx)
+        .await;
+    let url = "https://news.ycombinator.com/item";
+    setup::Submission::by(user.id())
+        .url(url)
+        .title("A discussion")
+        .description("Comments")
+        .indexed()
+        .insert(&ctx)
+        .await;
+
+    // title matches rank above description matches,
+    // which rank above approximate matches
+    assert_eq!(
+        all_results!(&server, "Rust"),
+        vec!["Rust in production", "Notes on compilers", "Rusty nails"]
+    );
+    assert_eq!(all_results!(&server, "ycombinator"), vec!["A discussion"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_recency_tiebreak() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    for (minutes, age) in [(30, "older"), (0, "newest"), (60, "oldest")] {
+        let url = format!("https://example.com/{}", age);
+        setup::Submission::by(user.id())
+            .url(&url)
+            .title("Same title")
+            .created_at(ctx.now() - Duration::minutes(minutes))
+            .indexed()
+            .insert(&ctx)
+            .await;
+    }
+    let body = search!(&server, "same title", Value::Null);
+    let results = &body["data"]["search"]["results"];
+    assert_eq!(
+        results["edges"],
+        json!([
+            { "node": { "title": "Same title" } },
+            { "node": { "title": "Same title" } },
+        ])
+    );
+
+    let results = ctx.search().find("same title", None, None, 10).unwrap();
+    let mut ages = vec![];
+    for id in results.iter().map(|result| result.id()) {
+        ages.push(
+            Url::find(&ctx, id)
+                .await
+                .unwrap()
+                .url()
+                .unwrap()
+                .unwrap()
+                .path()
+                .to_string(),
+        );
+    }
+    assert_eq!(ages, vec!["/newest", "/older", "/oldest"]);
+
+    // pages continue after the cursor
+    let search = ctx.search();
+    let page = search
+        .find("same title", Some(&results[0]), None, 1)
+        .unwrap();
+    assert_eq!(page, vec![results[1]]);
+    let page = search
+        .find("same title", Some(&results[0]), Some(&results[2]), 5)
+        .unwrap();
+    assert_eq!(page, vec![results[1]]);
+    assert!(search
+        .find("same title", Some(&results[2]), None, 5)
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_sanitizes_queries() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let url = "https://example.com/1";
+    setup::Submission::by(user.id())
+        .url(url)
+        .title("Rust in production")
+        .indexed()
+        .insert(&ctx)
+        .await;
+
+    // operators are treated as text
+    for query in [
+        "title:rust",
+        "rust AND",
+        "\"rust",
+        "(rust)",
+        "+rust -go",
+        "rust~2",
+        "rust^10",
+    ] {
+        assert_eq!(
+            all_results!(&server, query),
+            vec!["Rust in production"],
+            "{}",
+            query
+        );
+    }
+    for query in ["*", "\\", ":", "\"\"", "-- --", "()"] {
+        assert!(all_results!(&server, query).is_empty(), "{}", query);
+    }
+
+    // short queries have no results
+    for query in ["", " ", "r", " r "] {
+        assert!(all_results!(&server, query).is_empty(), "{}", query);
+    }
+
+    let body = search!(&server, "a".repeat(1025), Value::Null);
+    assert_eq!(body["errors"][0]["message"], "The search query is too long");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_pagination() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    for i in 0..5 {
+        let url = format!("https://example.com/{}", i);
+        let title = format!("Pagination {}", i);
+        setup::Submission::by(user.id())
+            .url(&url)
+            .title(&title)
+            .created_at(ctx.now() - Duration::minutes(i))
+            .indexed()
+            .insert(&ctx)
+            .await;
+    }
+
+    let titles = all_results!(&server, "pagination");
+    let expected: Vec<String> = (0..5).map(|i| format!("Pagination {}", i)).collect();
+    assert_eq!(titles, expected);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_pagination_with_new_results() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    for i in 0..5 {
+        let url = format!("https://example.com/{}", i);
+        let title = format!("Stable {}", i);
+        setup::Submission::by(user.id())
+            .url(&url)
+            .title(&title)
+            .created_at(ctx.now() - Duration::minutes(10 + i))
+            .indexed()
+            .insert(&ctx)
+            .await;
+    }
+
+    let mut titles = vec![];
+    let mut after = Value::Null;
+    for page in 0.. {
+        let body = search!(&server, "stable", after);
+        let results = &body["data"]["search"]["results"];
+        titles.extend(
+            results["edges"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|edge| edge["node"]["title"].as_str().unwrap().to_string()),
+        );
+        if results["pageInfo"]["hasNextPage"] != true {
+            break;
+        }
+        after = results["pageInfo"]["endCursor"].clone();
+
+        // new results arrive while paging
+        let url = format!("https://example.com/better/{}", page);
+        setup::Submission::by(user.id())
+            .url(&url)
+            .title("Stable and stable")
+            .description("Stable")
+            .indexed()
+            .insert(&ctx)
+            .await;
+        // ranks as high as the original results, but is newer
+        let url = format!("https://example.com/equal/{}", page);
+        setup::Submission::by(user.id())
+            .url(&url)
+            .title(&format!("Stable new{}", page))
+            .indexed()
+            .insert(&ctx)
+            .await;
+        let url = format!("https://example.com/worse/{}", page);
+        setup::Submission::by(user.id())
+            .url(&url)
+            .title("Unrelated")
+            .description("Stable")
+            .indexed()
+            .insert(&ctx)
+            .await;
+    }
+
+    // all original results are listed exactly once, and new
+    // results ranked above the cursor don't appear mid-walk
+    let expected: Vec<String> = (0..5).map(|i| format!("Stable {}", i)).collect();
+    let (original, new): (Vec<String>, Vec<String>) = titles
+        .into_iter()
+        .partition(|title| expected.contains(title));
+    assert_eq!(original, expected);
+    assert!(new.iter().all(|title| title == "Unrelated"), "{:?}", new);
+
+    // new results are found by new searches
+    let titles = all_results!(&server, "stable");
+    assert_eq!(titles[0], "Stable and stable");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_cursors_are_signed() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    for i in 0..3 {
+        let url = format!("https://example.com/{}", i);
+        setup::Submission::by(user.id())
+            .url(&url)
+            .title("Signed")
+            .indexed()
+            .insert(&ctx)
+            .await;
+    }
+
+    let body = search!(&server, "signed", Value::Null);
+    let cursor = body["data"]["search"]["results"]["pageInfo"]["endCursor"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let body = search!(&server, "signed", cursor);
+    assert!(body["errors"].is_null(), "{}", body);
+
+    // cursors with a made up rank are rejected
+    let (payload, signature) = cursor.split_once('.').unwrap();
+    let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).unwrap();
+    let payload = String::from_utf8(payload).unwrap();
+    let (_, rest) = payload.split_once(':').unwrap();
+    let forged = format!(
+        "{}.{}",
+        base64::encode_config(format!("1000:{}", rest), base64::URL_SAFE_NO_PAD),
+        signature
+    );
+    for cursor in [forged.as_str(), "not a cursor"] {
+        let body = search!(&server, "signed", cursor);
+        assert_eq!(
+            body["errors"][0]["message"], "Invalid search cursor",
+            "{}",
+            body
+        );
+    }
+}
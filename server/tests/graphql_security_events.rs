@@ -0,0 +1,258 @@
+use serde_json::{json, Value};
+mod setup;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_login_records_security_events() {
+    let (server, ctx) = setup::mock().await;
+
+    let query = "
+        mutation RequestLogin($email: String!) {
+            requestLogin(email: $email) {
+                ok
+            }
+        }
+    ";
+    let vars = json!({ "email": "test.user@urls.fyi" });
+    let res = setup::graphql(query, vars, "").reply(&server).await;
+    assert_eq!(res.status(), 200);
+
+    let email = setup::last_email(&ctx).await;
+    let token = email
+        .split_whitespace()
+        .find(|maybe_token| maybe_token.len() == 12)
+        .expect("Email should contain a 12 character login token");
+
+    let query = "
+        mutation Login($email: String!, $token: String!) {
+            login(email: $email, token: $token)
+        }
+    ";
+
+    // a wrong login code is recorded as a failure
+    let vars = json!({ "email": "test.user@urls.fyi", "token": "wrong-token!" });
+    let res = setup::graphql(query, vars, "").reply(&server).await;
+    assert_eq!(res.status(), 200);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body.as_object().unwrap().contains_key("errors"));
+
+    let vars = json!({ "email": "test.user@urls.fyi", "token": token });
+    let res = setup::graphql(query, vars, "").reply(&server).await;
+    assert_eq!(res.status(), 200);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let session = body["data"]["login"].as_str().unwrap().to_string();
+
+    let query = "
+        query SecurityEvents {
+            viewer {
+                securityEvents {
+                    nodes {
+                        kind
+                    }
+                }
+            }
+        }
+    ";
+    let res = setup::graphql(query, json!(null), &session)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body,
+        json!({
+            "data": {
+                "viewer": {
+                    "securityEvents": {
+                        "nodes": [
+                            { "kind": "LOGIN_SUCCEEDED" },
+                            { "kind": "LOGIN_FAILED" },
+                            { "kind": "LOGIN_CODE_REQUESTED" },
+                        ],
+                    },
+                },
+            },
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_security_events_pagination() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // each email change is recorded
+    let query = "
+        mutation UpdateUser($email: String!) {
+            updateUser(input: { email: $email }) {
+                email
+            }
+        }
+    ";
+    for idx in 0..3 {
+        let vars = json!({ "email": format!("test.user.{}@urls.fyi", idx) });
+        let res = setup::graphql(query, vars, &session).reply(&server).await;
+        assert_eq!(res.status(), 200);
+    }
+
+    let query = "
+        query SecurityEvents($after: String) {
+            viewer {
+                securityEvents(first: 2, after: $after) {
+                    edges {
+                        cursor
+                        node {
+                            kind
+                        }
+                    }
+                }
+            }
+        }
+    ";
+
+    let res = setup::graphql(query, json!({ "after": null }), &session)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let edges = body["data"]["viewer"]["securityEvents"]["edges"]
+        .as_array()
+        .unwrap()
+        .clone();
+    assert_eq!(edges.len(), 2);
+    assert!(edges
+        .iter()
+        .all(|edge| edge["node"]["kind"] == "EMAIL_CHANGE_REQUESTED"));
+
+    let after = edges[1]["cursor"].as_str().unwrap();
+    let res = setup::graphql(query, json!({ "after": after }), &session)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let edges = body["data"]["viewer"]["securityEvents"]["edges"]
+        .as_array()
+        .unwrap()
+        .clone();
+    // the remaining events are from creating the session
+    assert_eq!(edges.len(), 2);
+    assert_eq!(edges[0]["node"]["kind"], "EMAIL_CHANGE_REQUESTED");
+    assert_ne!(edges[1]["node"]["kind"], "EMAIL_CHANGE_REQUESTED");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_email_change_confirmation_is_recorded() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let query = "
+        mutation UpdateUser($email: String!) {
+            updateUser(input: { email: $email }) {
+                email
+            }
+        }
+    ";
+    let vars = json!({ "email": "test.user.changed@urls.fyi" });
+    let res = setup::graphql(query, vars, &session).reply(&server).await;
+    assert_eq!(res.status(), 200);
+
+    let email = setup::last_email(&ctx).await.replace("=\r\n", "");
+    let token = email
+        .split_whitespace()
+        .find_map(|word| word.split("/verify-email/").nth(1))
+        .expect("Missing verification link")
+        .to_string();
+    let query = "
+        mutation VerifyEmail($token: String!) {
+            verifyEmail(token: $token) {
+                ok
+            }
+        }
+    ";
+    let res = setup::graphql(query, json!({ "token": token }), &session)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let query = "
+        query SecurityEvents {
+            viewer {
+                securityEvents(first: 2) {
+                    nodes {
+                        kind
+                    }
+                }
+            }
+        }
+    ";
+    let res = setup::graphql(query, json!(null), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"]["securityEvents"]["nodes"],
+        json!([
+            { "kind": "EMAIL_CHANGE_CONFIRMED" },
+            { "kind": "EMAIL_CHANGE_REQUESTED" },
+        ])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_feed_token_events() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // the first token is created when the feed url is first requested
+    let query = "
+        query FeedUrl {
+            viewer {
+                feedUrl
+            }
+        }
+    ";
+    let res = setup::graphql(query, json!(null), &session)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let query = "
+        mutation RegenerateFeedToken {
+            regenerateFeedToken {
+                feedUrl
+            }
+        }
+    ";
+    let res = setup::graphql(query, json!(null), &session)
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let query = "
+        query SecurityEvents {
+            viewer {
+                securityEvents(first: 3) {
+                    nodes {
+                        kind
+                    }
+                }
+            }
+        }
+    ";
+    let res = setup::graphql(query, json!(null), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let mut kinds: Vec<&str> = body["data"]["viewer"]["securityEvents"]["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| node["kind"].as_str().unwrap())
+        .collect();
+    // events of the same request share a timestamp
+    kinds.sort_unstable();
+    assert_eq!(
+        kinds,
+        vec!["TOKEN_CREATED", "TOKEN_CREATED", "TOKEN_REVOKED"]
+    );
+}
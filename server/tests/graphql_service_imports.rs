@@ -0,0 +1,380 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use server::bookmark_source::{BookmarkSource, SourceBookmark, SourcePage};
+use server::db::models::{ServiceImport, User};
+use server::error::RateLimited;
+use server::Context;
+use std::sync::{Arc, Mutex};
+mod setup;
+
+const MUTATION_IMPORT: &str = "
+    mutation ImportFromService($service: ImportService!, $credentials: String!) {
+        importFromService(service: $service, credentials: $credentials) {
+            status
+        }
+    }
+";
+
+const MUTATION_SAVE: &str = "
+    mutation SaveUrl($id: ID!) {
+        saveUrl(id: $id) { id }
+    }
+";
+
+const QUERY_IMPORTS: &str = "
+    query Imports {
+        viewer {
+            imports {
+                service
+                status
+                imported
+                skippedDuplicates
+                errorCount
+                lastError
+            }
+        }
+    }
+";
+
+const QUERY_SAVED: &str = "
+    query Saved($status: SavedStatus!) {
+        viewer {
+            savedUrls(first: 10, status: $status) {
+                edges {
+                    node { url }
+                }
+            }
+        }
+    }
+";
+
+/// What the fake source does when asked for a page.
+#[derive(Debug, Clone, Copy)]
+enum Outcome {
+    Page,
+    Fail,
+    RateLimit,
+}
+
+/// A bookmark source serving fixed pages, which can be
+/// told to fail, and records the requested cursors.
+#[derive(Clone, Default)]
+struct FakeSource {
+    pages: Arc<Vec<Vec<SourceBookmark>>>,
+    outcomes: Arc<Mutex<Vec<Outcome>>>,
+    requests: Arc<Mutex<Vec<(String, Option<String>)>>>,
+}
+
+impl FakeSource {
+    fn new(pages: Vec<Vec<SourceBookmark>>) -> Self {
+        Self {
+            pages: Arc::new(pages),
+            ..Default::default()
+        }
+    }
+
+    /// Make the next requests have the given outcomes,
+    /// before serving pages again.
+    fn then(&self, outcomes: &[Outcome]) {
+        self.outcomes.lock().unwrap().extend(outcomes);
+    }
+
+    fn cursors(&self) -> Vec<Option<String>> {
+        let requests = self.requests.lock().unwrap();
+        requests.iter().map(|(_, cursor)| cursor.clone()).collect()
+    }
+}
+
+#[async_trait]
+impl BookmarkSource for FakeSource {
+    async fn fetch(
+        &self,
+        _ctx: &Context,
+        credentials: &str,
+        cursor: Option<&str>,
+    ) -> Result<SourcePage> {
+        self.requests
+            .lock()
+            .unwrap()
+            .push((credentials.to_string(), cursor.map(str::to_string)));
+        let outcome = {
+            let mut outcomes = self.outcomes.lock().unwrap();
+            if outcomes.is_empty() {
+                Outcome::Page
+            } else {
+                outcomes.remove(0)
+            }
+        };
+        match outcome {
+            Outcome::Page => {}
+            Outcome::Fail => return Err(anyhow!("Connection reset")),
+            Outcome::RateLimit => {
+                return Err(RateLimited::new("fake", Duration::minutes(2)).into());
+            }
+        }
+        let page: usize = cursor.unwrap_or("0").parse()?;
+        Ok(SourcePage {
+            bookmarks: self.pages[page].clone(),
+            next: Some(page + 1)
+                .filter(|&next| next < self.pages.len())
+                .map(|next| next.to_string()),
+        })
+    }
+
+    fn request_interval(&self) -> Duration {
+        Duration::seconds(10)
+    }
+}
+
+fn bookmark(url: &str, tags: &[&str], unread: bool) -> SourceBookmark {
+    SourceBookmark {
+        url: url.to_string(),
+        title: Some(format!("Title of {}", url)),
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        unread,
+        added_at: Some(Utc::now() - Duration::days(30)),
+    }
+}
+
+/// Run the import job as of the given number of minutes from now,
+/// with the given source.
+async fn run_job(ctx: &Context, minutes: i64, source: &FakeSource) {
+    let mut ctx = ctx.clone();
+    ctx.set_request_time(Utc::now() + Duration::minutes(minutes));
+    ServiceImport::process_pending_with(&ctx, |_| Some(Box::new(source.clone())))
+        .await
+        .unwrap();
+}
+
+/// Request an import, returning the GraphQL response.
+macro_rules! request_import {
+    ($server:expr, $session:expr, $service:expr) => {{
+        let vars = json!({ "service": $service, "credentials": "user:0123456789ABCDEF" });
+        let res = setup::graphql(MUTATION_IMPORT, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// The imports of the viewer, newest first.
+macro_rules! imports {
+    ($server:expr, $session:expr) => {{
+        let res = setup::graphql(QUERY_IMPORTS, json!({}), $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body["data"]["viewer"]["imports"].clone()
+    }};
+}
+
+/// The links in the reading list of the viewer with the given status.
+macro_rules! saved {
+    ($server:expr, $session:expr, $status:expr) => {{
+        let vars = json!({ "status": $status });
+        let res = setup::graphql(QUERY_SAVED, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let mut links: Vec<String> = body["data"]["viewer"]["savedUrls"]["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["node"]["url"].as_str().unwrap().to_string())
+            .collect();
+        links.sort();
+        links
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_service_import_dedup() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let saved = setup::Submission::by(admin.id())
+        .url("https://example.com/a")
+        .insert(&ctx)
+        .await;
+    let vars = json!({ "id": saved.to_string() });
+    setup::graphql(MUTATION_SAVE, vars, &session)
+        .reply(&server)
+        .await;
+
+    let body = request_import!(&server, &session, "PINBOARD");
+    assert_eq!(
+        body,
+        json!({ "data": { "importFromService": { "status": "PENDING" } } })
+    );
+
+    let source = FakeSource::new(vec![
+        vec![
+            bookmark("https://example.com/a", &[], true),
+            bookmark("https://example.com/b", &["Rust", "later"], false),
+            bookmark("https://example.com/b#comments", &[], true),
+            bookmark("ftp://example.com/c", &[], true),
+        ],
+        vec![
+            bookmark("https://example.com/c", &["cooking"], true),
+            bookmark("https://example.com/b?utm_source=feed", &[], true),
+        ],
+    ]);
+    run_job(&ctx, 1, &source).await;
+    assert_eq!(
+        imports!(&server, &session),
+        json!([{
+            "service": "PINBOARD",
+            "status": "PENDING",
+            "imported": 1,
+            "skippedDuplicates": 2,
+            "errorCount": 1,
+            "lastError": "ftp://example.com/c: Only http and https URLs can be submitted",
+        }])
+    );
+
+    // the next page is only fetched when the service allows it
+    run_job(&ctx, 1, &source).await;
+    assert_eq!(source.cursors(), vec![None]);
+    run_job(&ctx, 2, &source).await;
+    assert_eq!(source.cursors(), vec![None, Some("1".into())]);
+
+    let imports = imports!(&server, &session);
+    assert_eq!(imports[0]["status"], "COMPLETED");
+    assert_eq!(imports[0]["imported"], 2);
+    assert_eq!(imports[0]["skippedDuplicates"], 3);
+
+    // the read status is kept
+    assert_eq!(
+        saved!(&server, &session, "UNREAD"),
+        vec!["https://example.com/a", "https://example.com/c"]
+    );
+    assert_eq!(
+        saved!(&server, &session, "READ"),
+        vec!["https://example.com/b"]
+    );
+
+    // nothing is fetched once completed
+    run_job(&ctx, 60, &source).await;
+    assert_eq!(source.cursors().len(), 2);
+    assert!(source
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .all(|(credentials, _)| credentials == "user:0123456789ABCDEF"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_service_import_resume() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    request_import!(&server, &session, "PINBOARD");
+
+    let source = FakeSource::new(vec![
+        vec![bookmark("https://example.com/1", &[], true)],
+        vec![bookmark("https://example.com/2", &[], true)],
+        vec![bookmark("https://example.com/3", &[], true)],
+    ]);
+    run_job(&ctx, 1, &source).await;
+
+    // failures are retried with a backoff, and rate limits
+    // are waited out without counting as failures
+    source.then(&[Outcome::Fail, Outcome::RateLimit]);
+    run_job(&ctx, 2, &source).await;
+    let imports = imports!(&server, &session);
+    assert_eq!(imports[0]["status"], "PENDING");
+    assert_eq!(imports[0]["lastError"], "Connection reset");
+    run_job(&ctx, 3, &source).await;
+    run_job(&ctx, 8, &source).await;
+    run_job(&ctx, 9, &source).await;
+    run_job(&ctx, 11, &source).await;
+    run_job(&ctx, 12, &source).await;
+
+    // the import continues after the last imported page
+    assert_eq!(
+        source.cursors(),
+        vec![
+            None,
+            Some("1".into()),
+            Some("1".into()),
+            Some("1".into()),
+            Some("2".into()),
+        ]
+    );
+    let imports = imports!(&server, &session);
+    assert_eq!(imports[0]["status"], "COMPLETED");
+    assert_eq!(imports[0]["imported"], 3);
+    assert_eq!(imports[0]["skippedDuplicates"], 0);
+    assert_eq!(
+        saved!(&server, &session, "ALL"),
+        vec![
+            "https://example.com/1",
+            "https://example.com/2",
+            "https://example.com/3",
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_service_import_gives_up() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    request_import!(&server, &session, "PINBOARD");
+
+    let source = FakeSource::new(vec![vec![bookmark("https://example.com/1", &[], true)]]);
+    source.then(&[Outcome::Fail; 5]);
+    for day in 1..=5 {
+        run_job(&ctx, day * 24 * 60, &source).await;
+    }
+    let imports = imports!(&server, &session);
+    assert_eq!(imports[0]["status"], "FAILED");
+    assert_eq!(imports[0]["lastError"], "Connection reset");
+    assert_eq!(imports[0]["imported"], 0);
+    run_job(&ctx, 6 * 24 * 60, &source).await;
+    assert_eq!(source.cursors().len(), 5);
+
+    // another import can be requested
+    let body = request_import!(&server, &session, "PINBOARD");
+    assert_eq!(body["data"]["importFromService"]["status"], "PENDING");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_service_import_validation() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let body = request_import!(&server, "", "PINBOARD");
+    assert!(body["data"].is_null());
+
+    // only one import per service runs at a time
+    request_import!(&server, &session, "PINBOARD");
+    let body = request_import!(&server, &session, "PINBOARD");
+    assert_eq!(
+        body["errors"][0]["message"],
+        "An import from Pinboard is already in progress, please wait for it to complete"
+    );
+
+    // Pocket requires a consumer key in the configuration
+    let body = request_import!(&server, &session, "POCKET");
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Importing from Pocket is not available"
+    );
+
+    let vars = json!({ "service": "PINBOARD", "credentials": "   " });
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let res = setup::graphql(MUTATION_IMPORT, vars, &admin_session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Invalid credentials for Pinboard"
+    );
+    assert_eq!(imports!(&server, &admin_session), json!([]));
+}
@@ -0,0 +1,265 @@
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Value};
+use server::db::models::{Login, User};
+use server::{Config, IpPrivacy};
+mod setup;
+
+const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:91.0) Gecko/20100101 Firefox/91.0";
+
+const QUERY_LOGINS: &str = "
+    query Logins {
+        viewer {
+            logins {
+                edges {
+                    node {
+                        createdAt
+                        userAgent {
+                            name
+                            operatingSystem
+                        }
+                        ipAddress
+                        lastUsed
+                        lastIpAddress
+                    }
+                }
+            }
+        }
+    }
+";
+
+/// Log in as the mock user from the given IP address,
+/// returning the session token.
+macro_rules! login {
+    ($server:expr, $ctx:expr, $ip:expr) => {{
+        let query = "
+            mutation RequestLogin($email: String!) {
+                requestLogin(email: $email) {
+                    ok
+                }
+            }
+        ";
+        let vars = json!({ "email": "test.user@urls.fyi" });
+        let res = setup::graphql(query, vars, "").reply($server).await;
+        assert_eq!(res.status(), 200);
+
+        let email = setup::last_email($ctx).await;
+        let token = email
+            .split_whitespace()
+            .find(|maybe_token| maybe_token.len() == 12)
+            .expect("Email should contain a 12 character login token");
+
+        let query = "
+            mutation Login($email: String!, $token: String!) {
+                login(email: $email, token: $token)
+            }
+        ";
+        let vars = json!({ "email": "test.user@urls.fyi", "token": token });
+        let res = setup::graphql(query, vars, "")
+            .header("User-Agent", USER_AGENT)
+            .header("X-Forwarded-For", $ip)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body["data"]["login"]
+            .as_str()
+            .expect("Login failed")
+            .to_string()
+    }};
+}
+
+fn parse_time(time: &Value) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(time.as_str().unwrap())
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_session_metadata() {
+    let (server, ctx) = setup::mock().await;
+    let session = login!(&server, &ctx, "203.0.113.42");
+
+    let res = setup::graphql(QUERY_LOGINS, json!({}), &session)
+        .header("X-Forwarded-For", "198.51.100.7")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let login = &body["data"]["viewer"]["logins"]["edges"][0]["node"];
+    assert_eq!(
+        login["userAgent"],
+        json!({ "name": "Firefox", "operatingSystem": "Linux" })
+    );
+    assert_eq!(login["ipAddress"], "203.0.113.42");
+    // the session was used within the last minutes, so the
+    // request from another address is not recorded yet
+    assert_eq!(login["lastIpAddress"], "203.0.113.42");
+
+    let created_at = parse_time(&login["createdAt"]);
+    assert!((Utc::now() - created_at) < Duration::minutes(1));
+    assert_eq!(login["lastUsed"], login["createdAt"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_session_last_used_is_throttled() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    macro_rules! last_used {
+        () => {{
+            let res = setup::graphql(QUERY_LOGINS, json!({}), &session)
+                .reply(&server)
+                .await;
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+            parse_time(&body["data"]["viewer"]["logins"]["edges"][0]["node"]["lastUsed"])
+        }};
+    }
+
+    let claimed_at = last_used!();
+    assert!((claimed_at - ctx.now()).num_milliseconds().abs() < 1000);
+
+    // uses within five minutes are not recorded
+    let mut later = ctx.clone();
+    later.set_request_time(ctx.now() + Duration::minutes(4));
+    Login::use_session(&mut later, &session).await.unwrap();
+    assert_eq!(last_used!(), claimed_at);
+
+    let mut later = ctx.clone();
+    later.set_request_time(ctx.now() + Duration::minutes(6));
+    Login::use_session(&mut later, &session).await.unwrap();
+    let touched_at = last_used!();
+    assert!((touched_at - later.now()).num_milliseconds().abs() < 1000);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_session_ip_privacy() {
+    let conf = Config::test().with_ip_privacy(IpPrivacy::Truncate);
+    let (server, ctx) = setup::mock_with_config(conf).await;
+    let session = login!(&server, &ctx, "203.0.113.42");
+
+    let res = setup::graphql(QUERY_LOGINS, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let login = &body["data"]["viewer"]["logins"]["edges"][0]["node"];
+    assert_eq!(login["ipAddress"], "203.0.113.0");
+    assert_eq!(login["lastIpAddress"], "203.0.113.0");
+
+    let conf = Config::test().with_ip_privacy(IpPrivacy::Hash);
+    let (server, ctx) = setup::mock_with_config(conf).await;
+    let session = login!(&server, &ctx, "2001:db8::1");
+
+    let res = setup::graphql(QUERY_LOGINS, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let login = &body["data"]["viewer"]["logins"]["edges"][0]["node"];
+    let stored = login["ipAddress"].as_str().unwrap();
+    assert_eq!(stored.len(), 64);
+    assert!(stored.chars().all(|c| c.is_ascii_hexdigit()));
+    assert!(!stored.contains("2001"));
+
+    // the same address always hashes to the same value
+    let session = login!(&server, &ctx, "2001:db8::1");
+    let res = setup::graphql(QUERY_LOGINS, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let edges = body["data"]["viewer"]["logins"]["edges"]
+        .as_array()
+        .unwrap();
+    assert_eq!(edges.len(), 2);
+    assert!(edges.iter().all(|edge| edge["node"]["ipAddress"] == stored));
+}
+
+const QUERY_IS_LOGGED_IN: &str = "
+    query IsLoggedIn {
+        viewer {
+            email
+        }
+    }
+";
+
+const MUTATION_LOGOUT_OTHERS: &str = "
+    mutation LogoutOtherSessions {
+        logoutOtherSessions
+    }
+";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_logout_other_sessions() {
+    let (server, ctx) = setup::mock().await;
+    let current = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let others = vec![
+        setup::session_token(&ctx, "test.user@urls.fyi").await,
+        setup::session_token(&ctx, "test.user@urls.fyi").await,
+    ];
+    let admin = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    // an outstanding login code
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let pending = Login::create(&ctx, user.id()).await.unwrap();
+    let email_token = pending.email_token().to_string();
+
+    let res = setup::graphql(MUTATION_LOGOUT_OTHERS, json!({}), &current)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, json!({ "data": { "logoutOtherSessions": 2 } }));
+
+    for session in &others {
+        let res = setup::graphql(QUERY_IS_LOGGED_IN, json!({}), session)
+            .reply(&server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["data"]["viewer"].is_null());
+    }
+    for session in &[&current, &admin] {
+        let res = setup::graphql(QUERY_IS_LOGGED_IN, json!({}), session)
+            .reply(&server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["data"]["viewer"]["email"].is_string());
+    }
+
+    let mut pending = Login::find(&ctx, pending.id()).await.unwrap();
+    assert!(pending.claim(&ctx, &email_token).await.is_err());
+
+    // nothing is left to revoke
+    let res = setup::graphql(MUTATION_LOGOUT_OTHERS, json!({}), &current)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["logoutOtherSessions"], 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_email_change_logs_out_other_sessions() {
+    let (server, ctx) = setup::mock().await;
+    let current = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let other = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let query = "
+        mutation UpdateUser($input: UpdateUserInput!) {
+            updateUser(input: $input) {
+                email
+            }
+        }
+    ";
+    let vars = json!({ "input": { "email": "test.changed@urls.fyi" } });
+    let res = setup::graphql(query, vars, &current).reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["updateUser"]["email"], "test.changed@urls.fyi");
+
+    let res = setup::graphql(QUERY_IS_LOGGED_IN, json!({}), &other)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["data"]["viewer"].is_null());
+
+    let res = setup::graphql(QUERY_IS_LOGGED_IN, json!({}), &current)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["viewer"]["email"], "test.changed@urls.fyi");
+}
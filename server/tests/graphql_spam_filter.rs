@@ -0,0 +1,285 @@
+use async_trait::async_trait;
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::spam::{SpamCheckInput, SpamFilter, SpamVerdict};
+use server::{Config, Context};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id held }
+        }
+    }
+";
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!) {
+        addComment(urlId: $url, body: $body) { id held }
+    }
+";
+
+const MUTATION_RELEASE_URL: &str = "
+    mutation ReleaseUrl($id: ID!) {
+        releaseUrl(id: $id) { id held }
+    }
+";
+
+const MUTATION_RELEASE_COMMENT: &str = "
+    mutation ReleaseComment($id: ID!) {
+        releaseComment(id: $id) { id held }
+    }
+";
+
+const QUERY_URL: &str = "
+    query Url($id: ID!) {
+        fetch__Url(id: $id) {
+            held
+            commentCount
+            comments(first: 10) {
+                edges {
+                    node { text held }
+                }
+            }
+        }
+    }
+";
+
+const QUERY_SUBMISSIONS: &str = "
+    query Submissions {
+        submissions(first: 10) {
+            edges {
+                node { id }
+            }
+        }
+    }
+";
+
+const QUERY_QUEUE: &str = "
+    query ModerationQueue {
+        moderationQueue(first: 10) {
+            edges {
+                node {
+                    url { id held }
+                    reportCount
+                    heldComments { text held }
+                }
+            }
+        }
+    }
+";
+
+/// A spam filter returning the given verdicts in order,
+/// and judging everything after them as ham.
+#[derive(Debug)]
+struct Scripted(Mutex<VecDeque<SpamVerdict>>);
+
+#[async_trait]
+impl SpamFilter for Scripted {
+    async fn check(&self, _ctx: &Context, _content: SpamCheckInput) -> SpamVerdict {
+        self.0
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or(SpamVerdict::Ham)
+    }
+}
+
+/// The test configuration, using a filter with the given verdicts.
+fn scripted(verdicts: &[SpamVerdict]) -> Config {
+    let filter = Scripted(Mutex::new(verdicts.iter().copied().collect()));
+    Config::test().with_spam_filter(Arc::new(filter))
+}
+
+/// The IDs of the submissions listed to the given session.
+macro_rules! listed {
+    ($server:expr, $session:expr) => {{
+        let body = setup::execute($server, QUERY_SUBMISSIONS, json!({}), $session).await;
+        body["data"]["submissions"]["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["node"]["id"].clone())
+            .collect::<Vec<Value>>()
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_ham_is_published() {
+    let config = scripted(&[SpamVerdict::Ham, SpamVerdict::Ham]);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "input": { "url": setup::serve_page() } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &body["data"]["submitUrl"]["url"];
+    assert_eq!(url["held"], false, "{}", body);
+    let vars = json!({ "url": url["id"], "body": "Looks fine" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    assert_eq!(body["data"]["addComment"]["held"], false, "{}", body);
+
+    let body = setup::execute(&server, QUERY_URL, json!({ "id": url["id"] }), "").await;
+    let fetched = &body["data"]["fetch__Url"];
+    assert_eq!(fetched["held"], false);
+    assert_eq!(fetched["commentCount"], 1);
+    assert_eq!(
+        fetched["comments"]["edges"][0]["node"],
+        json!({ "text": "Looks fine", "held": false })
+    );
+    assert_eq!(listed!(&server, ""), vec![url["id"].clone()]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_spam_is_rejected() {
+    let config = scripted(&[SpamVerdict::Spam, SpamVerdict::Ham, SpamVerdict::Spam]);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "input": { "url": setup::serve_page() } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars.clone(), &session).await;
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "This submission can not be accepted"
+    );
+    assert!(listed!(&server, &session).is_empty());
+
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let id = body["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "url": id, "body": "Buy now" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "This comment can not be accepted"
+    );
+    let body = setup::execute(&server, QUERY_URL, json!({ "id": id }), &session).await;
+    assert_eq!(body["data"]["fetch__Url"]["commentCount"], 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_suspect_submission_is_held() {
+    let config = scripted(&[SpamVerdict::Suspect]);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let vars = json!({ "input": { "url": setup::serve_page() } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = body["data"]["submitUrl"]["url"].clone();
+    assert_eq!(url["held"], true, "{}", body);
+    let vars = json!({ "id": url["id"] });
+
+    // only the author sees the held submission
+    let body = setup::execute(&server, QUERY_URL, vars.clone(), &session).await;
+    assert_eq!(body["data"]["fetch__Url"]["held"], true);
+    assert_eq!(listed!(&server, &session), vec![url["id"].clone()]);
+    let body = setup::execute(&server, QUERY_URL, vars.clone(), "").await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "This submission is awaiting review by a moderator"
+    );
+    assert!(listed!(&server, "").is_empty());
+
+    // moderators review it in the queue
+    let body = setup::execute(&server, QUERY_QUEUE, json!({}), &admin_session).await;
+    assert_eq!(
+        body["data"]["moderationQueue"]["edges"],
+        json!([{ "node": { "url": url, "reportCount": 0, "heldComments": [] } }])
+    );
+    let body = setup::execute(&server, MUTATION_RELEASE_URL, vars.clone(), &session).await;
+    assert!(body["data"].is_null());
+    let body = setup::execute(&server, MUTATION_RELEASE_URL, vars.clone(), &admin_session).await;
+    assert_eq!(body["data"]["releaseUrl"]["held"], false, "{}", body);
+
+    let body = setup::execute(&server, QUERY_URL, vars, "").await;
+    assert_eq!(body["data"]["fetch__Url"]["held"], false);
+    assert_eq!(listed!(&server, ""), vec![url["id"].clone()]);
+    let body = setup::execute(&server, QUERY_QUEUE, json!({}), &admin_session).await;
+    assert_eq!(body["data"]["moderationQueue"]["edges"], json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_suspect_comment_is_held() {
+    let config = scripted(&[SpamVerdict::Ham, SpamVerdict::Suspect]);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let vars = json!({ "input": { "url": setup::serve_page() } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = body["data"]["submitUrl"]["url"].clone();
+    let vars = json!({ "url": url["id"], "body": "Maybe spam" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    let comment = body["data"]["addComment"].clone();
+    assert_eq!(comment["held"], true, "{}", body);
+    let vars = json!({ "id": url["id"] });
+
+    // only the author sees the held comment, and it isn't counted
+    let body = setup::execute(&server, QUERY_URL, vars.clone(), &session).await;
+    let fetched = &body["data"]["fetch__Url"];
+    assert_eq!(fetched["commentCount"], 0);
+    assert_eq!(
+        fetched["comments"]["edges"],
+        json!([{ "node": { "text": "Maybe spam", "held": true } }])
+    );
+    let body = setup::execute(&server, QUERY_URL, vars.clone(), "").await;
+    assert_eq!(body["data"]["fetch__Url"]["comments"]["edges"], json!([]));
+
+    // moderators review it in the queue
+    let body = setup::execute(&server, QUERY_QUEUE, json!({}), &admin_session).await;
+    assert_eq!(
+        body["data"]["moderationQueue"]["edges"],
+        json!([{
+            "node": {
+                "url": url,
+                "reportCount": 0,
+                "heldComments": [{ "text": "Maybe spam", "held": true }],
+            }
+        }])
+    );
+    let release = json!({ "id": comment["id"] });
+    let body = setup::execute(&server, MUTATION_RELEASE_COMMENT, release, &admin_session).await;
+    assert_eq!(body["data"]["releaseComment"]["held"], false, "{}", body);
+
+    let body = setup::execute(&server, QUERY_URL, vars, "").await;
+    let fetched = &body["data"]["fetch__Url"];
+    assert_eq!(fetched["commentCount"], 1);
+    assert_eq!(
+        fetched["comments"]["edges"],
+        json!([{ "node": { "text": "Maybe spam", "held": false } }])
+    );
+    let body = setup::execute(&server, QUERY_QUEUE, json!({}), &admin_session).await;
+    assert_eq!(body["data"]["moderationQueue"]["edges"], json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_heuristic_holds_links_from_new_accounts() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "input": { "url": setup::serve_page() } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let id = body["data"]["submitUrl"]["url"]["id"].clone();
+
+    let vars = json!({ "url": id, "body": "See https://example.com/a" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    assert_eq!(body["data"]["addComment"]["held"], false, "{}", body);
+    let text = "See https://example.com/b and https://example.org/c";
+    let vars = json!({ "url": id, "body": text });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    assert_eq!(body["data"]["addComment"]["held"], true, "{}", body);
+
+    // older accounts may post more links
+    let config = Config::test().with_spam_new_account_age(Duration::zero());
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let vars = json!({ "input": { "url": setup::serve_page() } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let id = body["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "url": id, "body": text });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    assert_eq!(body["data"]["addComment"]["held"], false, "{}", body);
+}
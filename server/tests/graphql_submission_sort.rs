@@ -0,0 +1,309 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::{TopRange, Url, UrlFilter, UrlSort, User};
+mod setup;
+
+const QUERY_SUBMISSIONS: &str = "
+    query Submissions($sort: UrlSort!, $range: TopRange, $after: String) {
+        submissions(first: 2, after: $after, sort: $sort, range: $range) {
+            edges {
+                node { title }
+            }
+            pageInfo {
+                hasNextPage
+                endCursor
+            }
+        }
+    }
+";
+
+/// Fetch a page of submissions in the given order, returning
+/// the GraphQL response.
+macro_rules! submissions {
+    ($server:expr, $sort:expr, $after:expr) => {
+        submissions!($server, $sort, Value::Null, $after)
+    };
+    ($server:expr, $sort:expr, $range:expr, $after:expr) => {{
+        let vars = json!({ "sort": $sort, "range": $range, "after": $after });
+        let res = setup::graphql(QUERY_SUBMISSIONS, vars, "")
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Fetch all submissions in the given order, page by page.
+macro_rules! all_titles {
+    ($server:expr, $sort:expr) => {
+        all_titles!($server, $sort, Value::Null)
+    };
+    ($server:expr, $sort:expr, $range:expr) => {{
+        let mut titles = vec![];
+        let mut after = Value::Null;
+        loop {
+            let body = submissions!($server, $sort, $range, after);
+            let submissions = &body["data"]["submissions"];
+            titles.extend(
+                submissions["edges"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|edge| edge["node"]["title"].as_str().unwrap().to_string()),
+            );
+            if submissions["pageInfo"]["hasNextPage"] != true {
+                break;
+            }
+            after = submissions["pageInfo"]["endCursor"].clone();
+        }
+        titles
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sort_orders() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    setup::Submission::by(user.id())
+        .title("Old and popular")
+        .score(40)
+        .created_at(ctx.now() - Duration::hours(48))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(user.id())
+        .title("Recent")
+        .score(5)
+        .created_at(ctx.now() - Duration::hours(1))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(user.id())
+        .title("Older")
+        .score(2)
+        .created_at(ctx.now() - Duration::hours(5))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(user.id())
+        .title("Tied with older")
+        .score(2)
+        .created_at(ctx.now() - Duration::hours(3))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(user.id())
+        .title("Unpopular")
+        .score(-1)
+        .created_at(ctx.now() - Duration::hours(2))
+        .insert(&ctx)
+        .await;
+    Url::refresh_hot_ranks(&ctx).await.unwrap();
+
+    assert_eq!(
+        all_titles!(&server, "NEWEST"),
+        vec![
+            "Recent",
+            "Unpopular",
+            "Tied with older",
+            "Older",
+            "Old and popular"
+        ]
+    );
+    assert_eq!(
+        all_titles!(&server, "OLDEST"),
+        vec![
+            "Old and popular",
+            "Older",
+            "Tied with older",
+            "Unpopular",
+            "Recent"
+        ]
+    );
+    // ties on the score are broken by recency
+    assert_eq!(
+        all_titles!(&server, "TOP", "ALL"),
+        vec![
+            "Old and popular",
+            "Recent",
+            "Tied with older",
+            "Older",
+            "Unpopular"
+        ]
+    );
+    // only submissions from the last day by default
+    assert_eq!(
+        all_titles!(&server, "TOP"),
+        vec!["Recent", "Tied with older", "Older", "Unpopular"]
+    );
+    assert_eq!(
+        all_titles!(&server, "TRENDING"),
+        vec![
+            "Recent",
+            "Tied with older",
+            "Older",
+            "Old and popular",
+            "Unpopular"
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cursor_rejected_across_sorts() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    for (i, title) in ["First", "Second", "Third"].iter().enumerate() {
+        setup::Submission::by(user.id())
+            .title(title)
+            .score(i as i64)
+            .created_at(ctx.now() - Duration::hours(i as i64))
+            .insert(&ctx)
+            .await;
+    }
+
+    let body = submissions!(&server, "NEWEST", Value::Null);
+    let cursor = body["data"]["submissions"]["pageInfo"]["endCursor"].clone();
+    assert!(cursor.is_string());
+
+    for sort in ["OLDEST", "TOP", "TRENDING"] {
+        let body = submissions!(&server, sort, cursor.clone());
+        assert!(body["data"].is_null(), "{}", sort);
+        assert!(
+            body["errors"][0]["message"]
+                .as_str()
+                .unwrap()
+                .contains("This cursor was issued for the Newest order"),
+            "{}",
+            sort
+        );
+    }
+
+    // the cursor still works with the order it was issued for
+    let body = submissions!(&server, "NEWEST", cursor);
+    assert_eq!(
+        body["data"]["submissions"]["edges"],
+        json!([{ "node": { "title": "Third" } }])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_top_range_boundaries() {
+    let (_, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    let ranges = [
+        (TopRange::Day, Duration::days(1)),
+        (TopRange::Week, Duration::weeks(1)),
+        (TopRange::Month, Duration::days(30)),
+        (TopRange::Year, Duration::days(365)),
+    ];
+    let mut edges = vec![];
+    for (_, age) in ranges {
+        let at_edge = setup::Submission::by(user.id())
+            .title("At the edge")
+            .score(1)
+            .created_at(ctx.now() - age)
+            .insert(&ctx)
+            .await;
+        let outside = setup::Submission::by(user.id())
+            .title("Outside")
+            .score(1)
+            .created_at(ctx.now() - age + Duration::seconds(1))
+            .insert(&ctx)
+            .await;
+        edges.push((at_edge, outside));
+    }
+
+    for (i, (range, _)) in ranges.iter().enumerate() {
+        let filter = UrlFilter {
+            range: Some(*range),
+            ..Default::default()
+        };
+        let ids: Vec<UrlID> = Url::all_submissions(&ctx, filter, UrlSort::Top, None, None, None)
+            .await
+            .unwrap()
+            .iter()
+            .map(Url::id)
+            .collect();
+        // submissions exactly at the edge of the range are included
+        let expected: Vec<UrlID> = edges[..=i]
+            .iter()
+            .flat_map(|&(at_edge, outside)| [at_edge, outside])
+            .filter(|&id| id != edges[i].1)
+            .collect();
+        assert_eq!(ids, expected, "{:?}", range);
+    }
+
+    let filter = UrlFilter {
+        range: Some(TopRange::All),
+        ..Default::default()
+    };
+    let all = Url::all_submissions(&ctx, filter, UrlSort::Top, None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(all.len(), 2 * ranges.len());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_range_requires_top_sort() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    setup::Submission::by(user.id())
+        .title("Submission")
+        .score(1)
+        .created_at(ctx.now() - Duration::hours(1))
+        .insert(&ctx)
+        .await;
+
+    for sort in ["NEWEST", "OLDEST", "TRENDING"] {
+        let body = submissions!(&server, sort, "WEEK", Value::Null);
+        assert!(body["data"].is_null(), "{}", sort);
+        assert_eq!(
+            body["errors"][0]["message"],
+            "A range can only be given for the top order"
+        );
+    }
+    let body = submissions!(&server, "TOP", "WEEK", Value::Null);
+    assert_eq!(
+        body["data"]["submissions"]["edges"],
+        json!([{ "node": { "title": "Submission" } }])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_top_pagination_with_ties() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    // equal scores and submission times are ordered by id
+    let mut ids = vec![];
+    for i in 0..7 {
+        let title = format!("Submission {}", i);
+        ids.push(
+            setup::Submission::by(user.id())
+                .title(&title)
+                .score(3)
+                .created_at(ctx.now() - Duration::hours(2))
+                .insert(&ctx)
+                .await,
+        );
+    }
+    ids.sort_by(|a, b| b.as_str().cmp(a.as_str()));
+    let mut expected = vec![];
+    for id in ids {
+        let url = Url::find(&ctx, id).await.unwrap();
+        expected.push(url.title().unwrap().to_string());
+    }
+
+    for _ in 0..2 {
+        assert_eq!(all_titles!(&server, "TOP", "WEEK"), expected);
+    }
+}
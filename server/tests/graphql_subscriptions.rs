@@ -0,0 +1,269 @@
+use serde_json::{json, Value};
+use server::db::id::UserID;
+use server::db::models::{NewUserInput, User};
+use server::Context;
+use std::time::Duration;
+use warp::test::WsClient;
+use warp::ws::Message;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id }
+        }
+    }
+";
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!) {
+        addComment(urlId: $url, body: $body) { id }
+    }
+";
+
+const MUTATION_BLOCK: &str = "
+    mutation BlockUser($id: ID!) {
+        blockUser(userId: $id) { ok }
+    }
+";
+
+const SUBSCRIPTION_URLS: &str = "
+    subscription Urls {
+        urlSubmitted(tag: \"rust\") { title }
+    }
+";
+
+const SUBSCRIPTION_COMMENTS: &str = "
+    subscription Comments($url: ID!) {
+        commentAdded(urlId: $url) { body }
+    }
+";
+
+/// Create another verified user, returning their ID
+/// and a session token for them.
+async fn other_user(ctx: &Context, name: &str) -> (UserID, String) {
+    let email = format!("test.{}@urls.fyi", name.to_lowercase());
+    let input = NewUserInput {
+        name: name.into(),
+        email: email.clone(),
+    };
+    let mut user = User::create(ctx, input).await.unwrap();
+    user.mark_email_verified(ctx).await.unwrap();
+    (user.id(), setup::session_token(ctx, &email).await)
+}
+
+/// The input of a text post with the given title, tag and visibility.
+fn text_post(title: &str, tag: &str, visibility: &str) -> Value {
+    json!({
+        "input": {
+            "title": title,
+            "text": "Some text",
+            "tags": [tag],
+            "visibility": visibility,
+        },
+    })
+}
+
+/// Open a websocket connection to the GraphQL API.
+macro_rules! connect {
+    ($server:expr) => {{
+        warp::test::ws()
+            .path("/graphql")
+            .header("Sec-WebSocket-Protocol", "graphql-transport-ws")
+            .handshake($server.clone())
+            .await
+            .expect("handshake")
+    }};
+}
+
+async fn send(client: &mut WsClient, message: Value) {
+    client.send_text(message.to_string()).await;
+}
+
+async fn recv(client: &mut WsClient) -> Value {
+    let message = client.recv().await.unwrap();
+    serde_json::from_str(message.to_str().unwrap()).unwrap()
+}
+
+/// The code the connection was closed with.
+async fn recv_close(client: &mut WsClient) -> u16 {
+    let message: Message = client.recv().await.unwrap();
+    message.close_frame().expect("close frame").0
+}
+
+/// Initialize the connection with the given session token, if any,
+/// and subscribe to the given subscription. Once the server replied to
+/// a following ping, the subscription receives all new events.
+async fn subscribe(client: &mut WsClient, session: &str, query: &str, variables: Value) {
+    let mut init = json!({ "type": "connection_init" });
+    if !session.is_empty() {
+        init["payload"] = json!({ "sessionToken": session });
+    }
+    send(client, init).await;
+    assert_eq!(recv(client).await, json!({ "type": "connection_ack" }));
+    let payload = json!({ "query": query, "variables": variables });
+    send(
+        client,
+        json!({ "type": "subscribe", "id": "1", "payload": payload }),
+    )
+    .await;
+    send(client, json!({ "type": "ping" })).await;
+    assert_eq!(recv(client).await, json!({ "type": "pong" }));
+}
+
+/// Assert the client receives no further messages.
+async fn assert_no_message(client: &mut WsClient) {
+    let message = tokio::time::timeout(Duration::from_millis(200), client.recv()).await;
+    assert!(message.is_err(), "unexpected message {:?}", message);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_url_submitted() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let (blocked, blocked_session) = other_user(&ctx, "Blocked").await;
+    let vars = json!({ "id": blocked.to_string() });
+    setup::execute_ok(&server, MUTATION_BLOCK, vars, &session).await;
+
+    let mut client = connect!(server);
+    subscribe(&mut client, &session, SUBSCRIPTION_URLS, json!({})).await;
+
+    // only submissions listed to the subscriber are received
+    let vars = text_post("Unlisted", "rust", "UNLISTED");
+    setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let vars = text_post("Other tag", "go", "PUBLIC");
+    setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let vars = text_post("Blocked", "rust", "PUBLIC");
+    setup::execute_ok(&server, MUTATION_SUBMIT, vars, &blocked_session).await;
+    let vars = text_post("Listed", "rust", "PUBLIC");
+    setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+
+    let message = recv(&mut client).await;
+    assert_eq!(
+        message,
+        json!({
+            "type": "next",
+            "id": "1",
+            "payload": { "data": { "urlSubmitted": { "title": "Listed" } } },
+        })
+    );
+    assert_no_message(&mut client).await;
+
+    // logged out subscribers don't see the blocks of others
+    let mut client = connect!(server);
+    subscribe(&mut client, "", SUBSCRIPTION_URLS, json!({})).await;
+    let vars = text_post("Not blocked", "rust", "PUBLIC");
+    setup::execute_ok(&server, MUTATION_SUBMIT, vars, &blocked_session).await;
+    let message = recv(&mut client).await;
+    assert_eq!(
+        message["payload"]["data"]["urlSubmitted"]["title"],
+        "Not blocked"
+    );
+    assert_no_message(&mut client).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_comment_added() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let (blocked, blocked_session) = other_user(&ctx, "Blocked").await;
+    let vars = json!({ "id": blocked.to_string() });
+    setup::execute_ok(&server, MUTATION_BLOCK, vars, &session).await;
+    let vars = text_post("Discussed", "rust", "PUBLIC");
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = data["submitUrl"]["url"]["id"].clone();
+    let vars = text_post("Elsewhere", "rust", "PUBLIC");
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let other = data["submitUrl"]["url"]["id"].clone();
+
+    let mut client = connect!(server);
+    let vars = json!({ "url": url });
+    subscribe(&mut client, &session, SUBSCRIPTION_COMMENTS, vars).await;
+
+    // only comments on the given submission by unblocked users are received
+    let vars = json!({ "url": other, "body": "Elsewhere" });
+    setup::execute_ok(&server, MUTATION_ADD_COMMENT, vars, &admin_session).await;
+    let vars = json!({ "url": url, "body": "Blocked" });
+    setup::execute_ok(&server, MUTATION_ADD_COMMENT, vars, &blocked_session).await;
+    let vars = json!({ "url": url, "body": "Hello" });
+    setup::execute_ok(&server, MUTATION_ADD_COMMENT, vars, &admin_session).await;
+
+    let message = recv(&mut client).await;
+    assert_eq!(
+        message,
+        json!({
+            "type": "next",
+            "id": "1",
+            "payload": { "data": { "commentAdded": { "body": "Hello" } } },
+        })
+    );
+    assert_no_message(&mut client).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connection_init() {
+    let (server, _) = setup::mock().await;
+
+    // subscriptions require an initialized connection
+    let mut client = connect!(server);
+    let payload = json!({ "query": SUBSCRIPTION_URLS });
+    send(
+        &mut client,
+        json!({ "type": "subscribe", "id": "1", "payload": payload }),
+    )
+    .await;
+    assert_eq!(recv_close(&mut client).await, 4401);
+
+    // which is rejected with an invalid session token
+    let mut client = connect!(server);
+    let init = json!({ "type": "connection_init", "payload": { "sessionToken": "invalid" } });
+    send(&mut client, init).await;
+    assert_eq!(recv_close(&mut client).await, 4403);
+
+    // and can only be initialized once
+    let mut client = connect!(server);
+    send(&mut client, json!({ "type": "connection_init" })).await;
+    assert_eq!(recv(&mut client).await, json!({ "type": "connection_ack" }));
+    send(&mut client, json!({ "type": "connection_init" })).await;
+    assert_eq!(recv_close(&mut client).await, 4429);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_subscription_limit() {
+    let (server, _) = setup::mock().await;
+    let mut client = connect!(server);
+    send(&mut client, json!({ "type": "connection_init" })).await;
+    assert_eq!(recv(&mut client).await, json!({ "type": "connection_ack" }));
+
+    // connections may only run a limited number of subscriptions
+    let payload = json!({ "query": SUBSCRIPTION_URLS });
+    for id in 1..=16 {
+        let subscribe = json!({ "type": "subscribe", "id": id.to_string(), "payload": payload });
+        send(&mut client, subscribe).await;
+    }
+    send(&mut client, json!({ "type": "ping" })).await;
+    assert_eq!(recv(&mut client).await, json!({ "type": "pong" }));
+    send(
+        &mut client,
+        json!({ "type": "subscribe", "id": "17", "payload": payload }),
+    )
+    .await;
+    let message = recv(&mut client).await;
+    assert_eq!(message["type"], "error");
+    assert_eq!(message["id"], "17");
+    assert_eq!(
+        message["payload"][0]["extensions"]["code"],
+        "TOO_MANY_SUBSCRIPTIONS"
+    );
+
+    // until other subscriptions are completed
+    send(&mut client, json!({ "type": "complete", "id": "1" })).await;
+    send(
+        &mut client,
+        json!({ "type": "subscribe", "id": "17", "payload": payload }),
+    )
+    .await;
+    send(&mut client, json!({ "type": "ping" })).await;
+    assert_eq!(recv(&mut client).await, json!({ "type": "pong" }));
+}
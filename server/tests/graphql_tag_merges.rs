@@ -0,0 +1,239 @@
+use serde_json::{json, Value};
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url {
+                id
+                tags { name }
+            }
+        }
+    }
+";
+
+const MUTATION_RENAME: &str = "
+    mutation RenameTag($from: String!, $to: String!) {
+        renameTag(from: $from, to: $to) { name count }
+    }
+";
+
+const MUTATION_MERGE: &str = "
+    mutation MergeTags($sources: [String!]!, $target: String!) {
+        mergeTags(sources: $sources, target: $target) { name count }
+    }
+";
+
+const MUTATION_FOLLOW: &str = "
+    mutation FollowTag($name: String!) {
+        followTag(name: $name) { name }
+    }
+";
+
+const QUERY_URL_TAGS: &str = "
+    query Url($id: ID!) {
+        fetch__Url(id: $id) {
+            tags { name }
+        }
+    }
+";
+
+const QUERY_TAGS: &str = "
+    query Tags {
+        tags(first: 10) {
+            edges {
+                node { name count }
+            }
+        }
+        viewer {
+            followedTags { name }
+        }
+    }
+";
+
+const QUERY_TAGGED: &str = "
+    query Tagged($tag: String!) {
+        submissions(first: 10, tag: $tag) {
+            edges {
+                node { id }
+            }
+        }
+    }
+";
+
+const QUERY_TAG_LOG: &str = "
+    query TagLog {
+        tagLog(first: 10) {
+            edges {
+                node {
+                    admin { username }
+                    sources
+                    target
+                    urlCount
+                }
+            }
+        }
+    }
+";
+
+/// The names of the tags of the given submission.
+macro_rules! url_tags {
+    ($server:expr, $id:expr) => {{
+        let body = setup::execute($server, QUERY_URL_TAGS, json!({ "id": $id }), "").await;
+        body["data"]["fetch__Url"]["tags"].clone()
+    }};
+}
+
+/// The IDs of the submissions listed for the given tag.
+macro_rules! tagged {
+    ($server:expr, $tag:expr) => {{
+        let body = setup::execute($server, QUERY_TAGGED, json!({ "tag": $tag }), "").await;
+        body["data"]["submissions"]["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["node"]["id"].clone())
+            .collect::<Vec<Value>>()
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_merge_combines_counts() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let vars = json!({
+        "input": { "title": "Tagged", "text": "Some text", "tags": ["rust", "rustlang"] },
+    });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let both = data["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "input": { "title": "Tagged", "text": "Some text", "tags": ["rustlang"] } });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let single = data["submitUrl"]["url"]["id"].clone();
+    let vars = json!({
+        "input": { "title": "Tagged", "text": "Some text", "tags": ["rust-lang", "web"] },
+    });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let other = data["submitUrl"]["url"]["id"].clone();
+    setup::execute(
+        &server,
+        MUTATION_FOLLOW,
+        json!({ "name": "rustlang" }),
+        &session,
+    )
+    .await;
+    setup::execute(
+        &server,
+        MUTATION_FOLLOW,
+        json!({ "name": "rust" }),
+        &session,
+    )
+    .await;
+
+    let vars = json!({ "sources": ["rustlang", "Rust Lang"], "target": "rust" });
+    let body = setup::execute(&server, MUTATION_MERGE, vars.clone(), &session).await;
+    assert!(body["data"].is_null());
+    let body = setup::execute(&server, MUTATION_MERGE, vars, &admin_session).await;
+    assert_eq!(
+        body["data"]["mergeTags"],
+        json!({ "name": "rust", "count": 3 }),
+        "{}",
+        body
+    );
+
+    // the submission which had several of the tags has the target once
+    assert_eq!(url_tags!(&server, both), json!([{ "name": "rust" }]));
+    assert_eq!(url_tags!(&server, single), json!([{ "name": "rust" }]));
+    assert_eq!(
+        url_tags!(&server, other),
+        json!([{ "name": "rust" }, { "name": "web" }])
+    );
+    let body = setup::execute(&server, QUERY_TAGS, json!({}), &session).await;
+    assert_eq!(
+        body["data"]["tags"]["edges"],
+        json!([
+            { "node": { "name": "rust", "count": 3 } },
+            { "node": { "name": "web", "count": 1 } },
+        ])
+    );
+    assert_eq!(
+        body["data"]["viewer"]["followedTags"],
+        json!([{ "name": "rust" }])
+    );
+
+    let body = setup::execute(&server, QUERY_TAG_LOG, json!({}), &session).await;
+    assert!(body["data"].is_null());
+    let body = setup::execute(&server, QUERY_TAG_LOG, json!({}), &admin_session).await;
+    assert_eq!(
+        body["data"]["tagLog"]["edges"],
+        json!([{
+            "node": {
+                "admin": { "username": "test-administrator" },
+                "sources": ["rustlang", "rust-lang"],
+                "target": "rust",
+                "urlCount": 2,
+            }
+        }])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_renamed_tags_redirect() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let vars = json!({ "input": { "title": "Tagged", "text": "Some text", "tags": ["rustlang"] } });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = data["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "input": { "title": "Tagged", "text": "Some text", "tags": ["web"] } });
+    setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let vars = json!({ "from": "rustlang", "to": "rust" });
+    let body = setup::execute(&server, MUTATION_RENAME, vars, &admin_session).await;
+    assert_eq!(
+        body["data"]["renameTag"],
+        json!({ "name": "rust", "count": 1 }),
+        "{}",
+        body
+    );
+
+    // the old name lists the submissions of the new one
+    assert_eq!(tagged!(&server, "rust"), vec![url.clone()]);
+    assert_eq!(tagged!(&server, "rustlang"), vec![url.clone()]);
+    let vars = json!({
+        "input": { "title": "Tagged", "text": "Some text", "tags": ["RustLang", "rust"] },
+    });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let new = data["submitUrl"]["url"]["id"].clone();
+    assert_eq!(url_tags!(&server, new), json!([{ "name": "rust" }]));
+
+    // existing tags are merged rather than renamed onto
+    let vars = json!({ "from": "rust", "to": "web" });
+    let body = setup::execute(&server, MUTATION_RENAME, vars, &admin_session).await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "The tag \"web\" already exists, merge the tags instead"
+    );
+
+    // renaming again updates the earlier redirect
+    let vars = json!({ "from": "rust", "to": "rust-lang" });
+    let body = setup::execute(&server, MUTATION_RENAME, vars, &admin_session).await;
+    assert_eq!(body["data"]["renameTag"]["count"], 2, "{}", body);
+    for tag in ["rustlang", "rust", "rust-lang"] {
+        assert_eq!(
+            tagged!(&server, tag),
+            vec![new.clone(), url.clone()],
+            "{}",
+            tag
+        );
+    }
+
+    // renaming back to an old name replaces its redirect
+    let vars = json!({ "from": "rust-lang", "to": "rustlang" });
+    let body = setup::execute(&server, MUTATION_RENAME, vars, &admin_session).await;
+    assert_eq!(body["data"]["renameTag"]["name"], "rustlang", "{}", body);
+    for tag in ["rustlang", "rust", "rust-lang"] {
+        assert_eq!(tagged!(&server, tag).len(), 2, "{}", tag);
+    }
+}
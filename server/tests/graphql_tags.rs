@@ -0,0 +1,472 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::models::User;
+use server::schema::{tags, url_tags};
+use server::Context;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url {
+                tags { name }
+            }
+        }
+    }
+";
+
+const QUERY_TAGS: &str = "
+    query Tags($after: String, $sort: TagSort) {
+        tags(first: 4, after: $after, sort: $sort) {
+            edges {
+                node { name count }
+            }
+            pageInfo {
+                hasNextPage
+                endCursor
+            }
+        }
+    }
+";
+
+const MUTATION_SET_TAGS: &str = "
+    mutation SetUrlTags($id: ID!, $tags: [String!]!) {
+        setUrlTags(id: $id, tags: $tags) {
+            tags { name }
+        }
+    }
+";
+
+/// Insert the given tags with the given usage counts, without
+/// any submissions using them.
+async fn seed_tags(ctx: &Context, tags: &[(String, i64)]) {
+    let new_tags: Vec<_> = tags
+        .iter()
+        .enumerate()
+        .map(|(i, (name, count))| {
+            let created_at = ctx.now() + Duration::seconds(i as i64);
+            (
+                tags::dsl::name.eq(name),
+                tags::dsl::created_at.eq(created_at.naive_utc()),
+                tags::dsl::url_count.eq(count),
+            )
+        })
+        .collect();
+    diesel::insert_into(tags::table)
+        .values(&new_tags)
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+}
+
+/// Set the tags of the given submission, returning
+/// the GraphQL response.
+macro_rules! set_tags {
+    ($server:expr, $session:expr, $id:expr, $tags:expr) => {{
+        let vars = json!({ "id": $id.to_string(), "tags": $tags });
+        let res = setup::graphql(MUTATION_SET_TAGS, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Fetch a page of tags, returning the names and counts
+/// of the tags and the end cursor.
+macro_rules! tags_page {
+    ($server:expr, $sort:expr, $after:expr) => {{
+        let vars = json!({ "after": $after, "sort": $sort });
+        let res = setup::graphql(QUERY_TAGS, vars, "").reply($server).await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let tags = &body["data"]["tags"];
+        let nodes: Vec<(String, i64)> = tags["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| {
+                let node = &edge["node"];
+                (
+                    node["name"].as_str().unwrap().to_string(),
+                    node["count"].as_i64().unwrap(),
+                )
+            })
+            .collect();
+        (nodes, tags["pageInfo"]["endCursor"].clone())
+    }};
+}
+
+/// Count the rows of the given table.
+macro_rules! count {
+    ($ctx:expr, $table:expr) => {{
+        let count: i64 = $table
+            .select(diesel::dsl::count_star())
+            .get_result(&*$ctx.conn().await.unwrap())
+            .unwrap();
+        count
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_submit_with_tags() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({
+        "input": {
+            "url": setup::serve_page(),
+            "tags": ["Rust Lang", "rust-lang", "  WEB_dev "],
+        },
+    });
+    let res = setup::graphql(MUTATION_SUBMIT, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["submitUrl"]["url"]["tags"],
+        json!([{ "name": "rust-lang" }, { "name": "web-dev" }])
+    );
+    assert_eq!(count!(ctx, tags::table), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_url_tags() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let first = setup::submit(&ctx, user.id()).await;
+    let second = setup::submit(&ctx, user.id()).await;
+
+    // tags are created on first use
+    let body = set_tags!(&server, &session, first, ["rust", "Databases"]);
+    assert_eq!(
+        body["data"]["setUrlTags"]["tags"],
+        json!([{ "name": "databases" }, { "name": "rust" }])
+    );
+    assert_eq!(count!(ctx, tags::table), 2);
+
+    // and reused afterwards
+    let body = set_tags!(&server, &session, second, ["rust", "web"]);
+    assert_eq!(
+        body["data"]["setUrlTags"]["tags"],
+        json!([{ "name": "rust" }, { "name": "web" }])
+    );
+    assert_eq!(count!(ctx, tags::table), 3);
+
+    // re-tagging is idempotent
+    for _ in 0..2 {
+        let body = set_tags!(&server, &session, first, ["databases"]);
+        assert_eq!(
+            body["data"]["setUrlTags"]["tags"],
+            json!([{ "name": "databases" }])
+        );
+    }
+    assert_eq!(count!(ctx, tags::table), 3);
+    assert_eq!(count!(ctx, url_tags::table), 3);
+
+    // tags can be removed
+    let body = set_tags!(&server, &session, first, Vec::<String>::new());
+    assert_eq!(body["data"]["setUrlTags"]["tags"], json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_url_tags_validation() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let id = setup::submit(&ctx, user.id()).await;
+
+    let body = set_tags!(&server, &session, id, ["rust", "x"]);
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Invalid tag \"x\", tags must have between 2 and 30 characters"
+    );
+
+    let long = "a".repeat(31);
+    let body = set_tags!(&server, &session, id, [long.as_str()]);
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains(&long));
+
+    let six: Vec<String> = (0..6).map(|i| format!("tag-{}", i)).collect();
+    let body = set_tags!(&server, &session, id, six);
+    assert_eq!(
+        body["errors"][0]["message"],
+        "Submissions can have at most 5 tags"
+    );
+
+    // duplicates count once towards the cap
+    let body = set_tags!(
+        &server,
+        &session,
+        id,
+        ["one-tag", "One Tag", "two", "three", "four", "five"]
+    );
+    assert_eq!(
+        body["data"]["setUrlTags"]["tags"].as_array().unwrap().len(),
+        5
+    );
+    assert_eq!(count!(ctx, tags::table), 5);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_url_tags_permissions() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let id = setup::submit(&ctx, admin.id()).await;
+
+    let body = set_tags!(&server, &session, id, ["rust"]);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "EDIT_NOT_ALLOWED");
+    assert_eq!(count!(ctx, tags::table), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tag_suggestions() {
+    let (server, ctx) = setup::mock().await;
+
+    // topic-i is used by i % 6 submissions
+    let tags: Vec<(String, i64)> = (0..300)
+        .map(|i| (format!("topic-{:03}", i), i % 6))
+        .chain((0..100).map(|i| (format!("other-{:03}", i), i % 6)))
+        .collect();
+    seed_tags(&ctx, &tags).await;
+
+    let query = "
+        query Suggestions($prefix: String!, $limit: Int) {
+            tagSuggestions(prefix: $prefix, limit: $limit) {
+                name
+                count
+            }
+        }
+    ";
+    let suggest = |prefix: &str, limit: Option<i32>| {
+        let vars = json!({ "prefix": prefix, "limit": limit });
+        setup::graphql(query, vars, "").reply(&server)
+    };
+
+    // most used first, then by name
+    let res = suggest("Topic", None).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let suggestions = body["data"]["tagSuggestions"].as_array().unwrap();
+    assert_eq!(suggestions.len(), 10);
+    assert_eq!(suggestions[0], json!({ "name": "topic-005", "count": 5 }));
+    assert_eq!(suggestions[1], json!({ "name": "topic-011", "count": 5 }));
+    for pair in suggestions.windows(2) {
+        assert!(pair[0]["count"].as_i64() >= pair[1]["count"].as_i64());
+    }
+
+    // the prefix is normalized like tags
+    let res = suggest("topic 29", None).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let names: Vec<&str> = body["data"]["tagSuggestions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tag| tag["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "topic-293",
+            "topic-299",
+            "topic-292",
+            "topic-298",
+            "topic-291",
+            "topic-297",
+            "topic-290",
+            "topic-296",
+            "topic-295"
+        ]
+    );
+
+    // the limit is capped
+    let res = suggest("to", Some(1000)).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let suggestions = body["data"]["tagSuggestions"].as_array().unwrap();
+    assert_eq!(suggestions.len(), 25);
+    assert!(suggestions
+        .iter()
+        .all(|tag| tag["name"].as_str().unwrap().starts_with("topic-")));
+
+    // short prefixes return nothing
+    for prefix in ["", "t", " t ", "-"] {
+        let res = suggest(prefix, Some(25)).await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["data"]["tagSuggestions"], json!([]), "{}", prefix);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tags_connection_sorts() {
+    let (server, ctx) = setup::mock().await;
+    let tags: Vec<(String, i64)> = [
+        ("oldest", 2),
+        ("unused", 0),
+        ("popular", 9),
+        ("also-popular", 9),
+        ("newest", 1),
+    ]
+    .iter()
+    .map(|(name, count)| (name.to_string(), *count))
+    .collect();
+    seed_tags(&ctx, &tags).await;
+
+    let (nodes, _) = tags_page!(&server, Value::Null, Value::Null);
+    let names: Vec<&str> = nodes.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["also-popular", "popular", "oldest", "newest"]);
+    assert_eq!(nodes[0].1, 9);
+
+    let (nodes, _) = tags_page!(&server, "NEWEST", Value::Null);
+    let names: Vec<&str> = nodes.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["newest", "also-popular", "popular", "oldest"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tags_connection_counts() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let first = setup::submit(&ctx, user.id()).await;
+    let second = setup::submit(&ctx, user.id()).await;
+
+    set_tags!(&server, &session, first, ["rust", "web"]);
+    set_tags!(&server, &session, second, ["rust"]);
+    let (nodes, _) = tags_page!(&server, "POPULAR", Value::Null);
+    assert_eq!(nodes, vec![("rust".to_string(), 2), ("web".to_string(), 1)]);
+
+    // re-tagging with the same tags does not change the counts
+    set_tags!(&server, &session, second, ["rust", "Rust"]);
+    let (nodes, _) = tags_page!(&server, "POPULAR", Value::Null);
+    assert_eq!(nodes, vec![("rust".to_string(), 2), ("web".to_string(), 1)]);
+
+    // unused tags are no longer listed
+    set_tags!(&server, &session, first, ["databases"]);
+    let (nodes, _) = tags_page!(&server, "POPULAR", Value::Null);
+    assert_eq!(
+        nodes,
+        vec![("databases".to_string(), 1), ("rust".to_string(), 1)]
+    );
+    set_tags!(&server, &session, second, Vec::<String>::new());
+    let (nodes, _) = tags_page!(&server, "POPULAR", Value::Null);
+    assert_eq!(nodes, vec![("databases".to_string(), 1)]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tags_connection_pagination() {
+    let (server, ctx) = setup::mock().await;
+    let tags: Vec<(String, i64)> = (0..10).map(|i| (format!("tag-{}", i), 1 + i / 3)).collect();
+    seed_tags(&ctx, &tags).await;
+
+    for sort in ["POPULAR", "NEWEST"] {
+        let mut seen = vec![];
+        let mut after = Value::Null;
+        loop {
+            let (nodes, cursor) = tags_page!(&server, sort, after);
+            if nodes.is_empty() {
+                break;
+            }
+            seen.extend(nodes.into_iter().map(|(name, _)| name));
+            after = cursor;
+        }
+        seen.sort();
+        let mut expected: Vec<String> = tags.iter().map(|(name, _)| name.clone()).collect();
+        expected.sort();
+        assert_eq!(seen, expected, "{}", sort);
+    }
+
+    // the next page stays the same when the tag at the cursor changes
+    let (nodes, cursor) = tags_page!(&server, "POPULAR", Value::Null);
+    let (name, _) = nodes.last().unwrap();
+    let (expected, _) = tags_page!(&server, "POPULAR", cursor.clone());
+    diesel::update(tags::table.filter(tags::dsl::name.eq(name)))
+        .set(tags::dsl::url_count.eq(100))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let (next, _) = tags_page!(&server, "POPULAR", cursor);
+    assert_eq!(next, expected);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tag_feed() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // every other submission is tagged
+    let mut tagged = vec![];
+    for minutes in 0..9 {
+        let id = setup::Submission::by(user.id())
+            .created_at(ctx.now() - Duration::minutes(minutes))
+            .insert(&ctx)
+            .await;
+        if minutes % 2 == 0 {
+            set_tags!(&server, &session, id, ["rust"]);
+            tagged.push(id.to_string());
+        } else {
+            set_tags!(&server, &session, id, ["web"]);
+        }
+    }
+    setup::Submission::by(user.id())
+        .created_at(ctx.now() - Duration::minutes(10))
+        .insert(&ctx)
+        .await;
+
+    let query = "
+        query TagFeed($tag: String, $after: String) {
+            submissions(first: 2, after: $after, tag: $tag) {
+                edges {
+                    node { id }
+                }
+                pageInfo {
+                    hasNextPage
+                    endCursor
+                }
+            }
+        }
+    ";
+    let page = |tag: &str, after: Value| {
+        let vars = json!({ "tag": tag, "after": after });
+        setup::graphql(query, vars, "").reply(&server)
+    };
+
+    let mut ids = vec![];
+    let mut after = Value::Null;
+    loop {
+        let res = page("Rust", after).await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let submissions = &body["data"]["submissions"];
+        let edges = submissions["edges"].as_array().unwrap();
+        assert!(edges.len() <= 2);
+        ids.extend(
+            edges
+                .iter()
+                .map(|edge| edge["node"]["id"].as_str().unwrap().to_string()),
+        );
+        if !submissions["pageInfo"]["hasNextPage"].as_bool().unwrap() {
+            break;
+        }
+        after = submissions["pageInfo"]["endCursor"].clone();
+    }
+    assert_eq!(ids, tagged);
+
+    // unknown and invalid tags yield an empty connection
+    for tag in ["unknown", "x"] {
+        let res = page(tag, Value::Null).await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["errors"].is_null());
+        assert_eq!(body["data"]["submissions"]["edges"], json!([]));
+    }
+}
@@ -0,0 +1,171 @@
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::{MetadataStatus, Url};
+use warp::http::StatusCode;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url {
+                id
+                kind
+                url
+                title
+                text
+                textHtml
+                domain
+                domainSubmissionCount
+                metadataStatus
+            }
+            duplicate
+        }
+    }
+";
+
+const QUERY_SUBMISSIONS: &str = "
+    query Submissions {
+        submissions(first: 10) {
+            edges {
+                node { id kind }
+            }
+        }
+    }
+";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_submit_text_post() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let input = json!({ "title": "Ask: favourite editors?", "text": "Which one and *why*?" });
+    let body = setup::execute(
+        &server,
+        MUTATION_SUBMIT,
+        json!({ "input": input }),
+        &session,
+    )
+    .await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let result = &body["data"]["submitUrl"];
+    assert_eq!(result["duplicate"], false);
+    let url = &result["url"];
+    assert_eq!(url["kind"], "TEXT");
+    assert_eq!(url["url"], Value::Null);
+    assert_eq!(url["title"], "Ask: favourite editors?");
+    assert_eq!(url["text"], "Which one and *why*?");
+    assert!(url["textHtml"].as_str().unwrap().contains("<em>why</em>"));
+
+    // domain features do not apply to text posts
+    assert_eq!(url["domain"], Value::Null);
+    assert_eq!(url["domainSubmissionCount"], 0);
+    assert_eq!(url["metadataStatus"], "PENDING");
+
+    // the same text can be posted twice
+    let body = setup::execute(
+        &server,
+        MUTATION_SUBMIT,
+        json!({ "input": input }),
+        &session,
+    )
+    .await;
+    assert_eq!(body["data"]["submitUrl"]["duplicate"], false);
+    assert_ne!(body["data"]["submitUrl"]["url"]["id"], url["id"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_text_post_validation() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let cases = vec![
+        (
+            json!({ "title": "Both", "url": setup::serve_page(), "text": "Some text" }),
+            "Please submit either a URL or a text",
+        ),
+        (
+            json!({ "title": "Neither" }),
+            "Please submit either a URL or a text",
+        ),
+        (json!({ "text": "No title" }), "Text posts need a title"),
+        (
+            json!({ "title": "Long", "text": "a".repeat(10001) }),
+            "The text is too long",
+        ),
+    ];
+    for (input, message) in cases {
+        let body = setup::execute(
+            &server,
+            MUTATION_SUBMIT,
+            json!({ "input": input }),
+            &session,
+        )
+        .await;
+        assert!(body["data"].is_null(), "{}", input);
+        let error = body["errors"][0]["message"].as_str().unwrap();
+        assert!(error.contains(message), "{}: {}", input, error);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_text_posts_in_listings() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "input": { "url": setup::serve_page() } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let link = body["data"]["submitUrl"]["url"]["id"].clone();
+    let vars = json!({ "input": { "title": "Show: my project", "text": "It works" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let text = body["data"]["submitUrl"]["url"]["id"].clone();
+
+    // both kinds are listed together
+    let body = setup::execute(&server, QUERY_SUBMISSIONS, json!({}), "").await;
+    let nodes: Vec<Value> = body["data"]["submissions"]["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| edge["node"].clone())
+        .collect();
+    assert_eq!(nodes.len(), 2, "{}", body);
+    assert!(nodes.contains(&json!({ "id": link, "kind": "LINK" })));
+    assert!(nodes.contains(&json!({ "id": text, "kind": "TEXT" })));
+
+    // the discussion page shows the text, with nowhere to click out to
+    let id: UrlID = text.as_str().unwrap().parse().unwrap();
+    let res = warp::test::request()
+        .path(&format!("/comments/{}", id))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(String::from_utf8_lossy(res.body()).contains("It works"));
+    let res = warp::test::request()
+        .path(&format!("/out/{}", id))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_text_posts_skip_link_pipelines() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "input": { "title": "No link here", "text": "Just text" } });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let id: UrlID = body["data"]["submitUrl"]["url"]["id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    let mut url = Url::find(&ctx, id).await.unwrap();
+    url.fetch_metadata(&ctx).await.unwrap();
+    url.check_link(&ctx).await.unwrap();
+    url.archive(&ctx).await.unwrap();
+    let url = Url::find(&ctx, id).await.unwrap();
+    assert_eq!(url.metadata_status(), MetadataStatus::Pending);
+    assert!(url.last_checked_at().is_none());
+    assert!(url.archive_status().is_none());
+    assert!(url.outbound_link(&ctx).is_err());
+}
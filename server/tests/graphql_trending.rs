@@ -0,0 +1,158 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::{Url, User};
+use server::schema::urls;
+use server::Context;
+mod setup;
+
+const QUERY_TRENDING: &str = "
+    query Trending($after: String) {
+        submissions(first: 2, after: $after, sort: TRENDING) {
+            edges {
+                node { title }
+            }
+            pageInfo {
+                hasNextPage
+                endCursor
+            }
+        }
+    }
+";
+
+/// Set the score of the given submission.
+async fn set_score(ctx: &Context, id: UrlID, score: i64) {
+    diesel::update(urls::table.find(id))
+        .set(urls::dsl::score.eq(score))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+}
+
+/// Fetch a page of trending submissions, returning the titles
+/// and the end cursor, if there is a next page.
+macro_rules! trending {
+    ($server:expr, $after:expr) => {{
+        let res = setup::graphql(QUERY_TRENDING, json!({ "after": $after }), "")
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let submissions = &body["data"]["submissions"];
+        let titles: Vec<String> = submissions["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["node"]["title"].as_str().unwrap().to_string())
+            .collect();
+        let next = if submissions["pageInfo"]["hasNextPage"] == true {
+            submissions["pageInfo"]["endCursor"].clone()
+        } else {
+            Value::Null
+        };
+        (titles, next)
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_trending_order() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    setup::Submission::by(user.id())
+        .title("Old and popular")
+        .score(100)
+        .created_at(ctx.now() - Duration::hours(48))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(user.id())
+        .title("Fresh")
+        .score(1)
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(user.id())
+        .title("Rising")
+        .score(10)
+        .created_at(ctx.now() - Duration::hours(1))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(user.id())
+        .title("Unvoted")
+        .score(0)
+        .created_at(ctx.now() - Duration::hours(2))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(user.id())
+        .title("Popular")
+        .score(50)
+        .created_at(ctx.now() - Duration::hours(10))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(user.id())
+        .title("Expired")
+        .score(1000)
+        .created_at(ctx.now() - Duration::hours(31 * 24))
+        .insert(&ctx)
+        .await;
+    Url::refresh_hot_ranks(&ctx).await.unwrap();
+
+    let mut titles = vec![];
+    let mut after = Value::Null;
+    loop {
+        let (page, next) = trending!(&server, after);
+        titles.extend(page);
+        if next.is_null() {
+            break;
+        }
+        after = next;
+    }
+    assert_eq!(
+        titles,
+        vec![
+            "Rising",
+            "Popular",
+            "Fresh",
+            "Old and popular",
+            "Unvoted",
+            "Expired"
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_trending_pagination_while_voting() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    let mut ids = vec![];
+    for (i, title) in ["A", "B", "C", "D", "E", "F"].iter().enumerate() {
+        ids.push(
+            setup::Submission::by(user.id())
+                .title(title)
+                .score(60 - 10 * i as i64)
+                .created_at(ctx.now() - Duration::hours(1))
+                .insert(&ctx)
+                .await,
+        );
+    }
+    Url::refresh_hot_ranks(&ctx).await.unwrap();
+
+    let (first, after) = trending!(&server, Value::Null);
+    assert_eq!(first, vec!["A", "B"]);
+
+    // scores change between pages, ranks only change on refresh
+    set_score(&ctx, ids[4], 1000).await;
+    set_score(&ctx, ids[2], 0).await;
+    let (second, after) = trending!(&server, after);
+    let (third, after) = trending!(&server, after);
+    assert!(after.is_null());
+    assert_eq!(second, vec!["C", "D"]);
+    assert_eq!(third, vec!["E", "F"]);
+
+    Url::refresh_hot_ranks(&ctx).await.unwrap();
+    let (first, _) = trending!(&server, Value::Null);
+    assert_eq!(first, vec!["E", "A"]);
+}
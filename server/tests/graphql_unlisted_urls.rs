@@ -0,0 +1,239 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::Url;
+use server::schema::urls;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id visibility }
+        }
+    }
+";
+
+const MUTATION_UPDATE: &str = "
+    mutation UpdateUrl($id: ID!, $input: UpdateUrlInput!) {
+        updateUrl(id: $id, input: $input) {
+            visibility
+            publishedAt
+        }
+    }
+";
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!) {
+        addComment(urlId: $url, body: $body) { id }
+    }
+";
+
+const MUTATION_VOTE: &str = "
+    mutation VoteUrl($id: ID!) {
+        voteUrl(id: $id, direction: UP) { score }
+    }
+";
+
+const QUERY_URL: &str = "
+    query Url($id: ID!) {
+        fetch__Url(id: $id) {
+            title
+            visibility
+            commentCount
+        }
+    }
+";
+
+const QUERY_LISTINGS: &str = "
+    query Listings {
+        submissions(first: 10) {
+            edges {
+                node { id }
+            }
+        }
+        tagged: submissions(first: 10, tag: \"rust\") {
+            edges {
+                node { id }
+            }
+        }
+        tags(first: 10) {
+            edges {
+                node { name count }
+            }
+        }
+        search(query: \"Listed\") {
+            results(first: 10) {
+                edges {
+                    node { id }
+                }
+            }
+        }
+        user(username: \"test-user\") {
+            urlCount
+            urls(first: 10) {
+                edges {
+                    node { id }
+                }
+            }
+        }
+    }
+";
+
+/// The IDs of the nodes of the given connection.
+fn ids(connection: &Value) -> Vec<Value> {
+    connection["edges"]
+        .as_array()
+        .expect(&connection.to_string())
+        .iter()
+        .map(|edge| edge["node"]["id"].clone())
+        .collect()
+}
+
+/// Add the given submission to the search index.
+async fn index(ctx: &server::Context, id: &Value) {
+    let id: UrlID = id.as_str().unwrap().parse().unwrap();
+    let url = Url::find(ctx, id).await.unwrap();
+    ctx.search().index_url(&url).unwrap();
+}
+
+/// The input of a text post with the given title and visibility.
+fn text_post(title: &str, visibility: &str) -> Value {
+    json!({
+        "input": {
+            "title": title,
+            "text": "Some text",
+            "tags": ["rust"],
+            "visibility": visibility,
+        },
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unlisted_urls_are_not_listed() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let vars = text_post("Listed", "PUBLIC");
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    assert_eq!(data["submitUrl"]["url"]["visibility"], "PUBLIC");
+    let public = data["submitUrl"]["url"]["id"].clone();
+    let vars = text_post("Listed by link", "UNLISTED");
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    assert_eq!(data["submitUrl"]["url"]["visibility"], "UNLISTED");
+    let unlisted = data["submitUrl"]["url"]["id"].clone();
+    index(&ctx, &public).await;
+    index(&ctx, &unlisted).await;
+
+    // unlisted submissions are left out of every listing
+    for session in [session.as_str(), &admin_session, ""] {
+        let body = setup::execute(&server, QUERY_LISTINGS, json!({}), session).await;
+        let data = &body["data"];
+        assert_eq!(ids(&data["submissions"]), vec![public.clone()], "{}", body);
+        assert_eq!(ids(&data["tagged"]), vec![public.clone()]);
+        assert_eq!(
+            data["tags"]["edges"],
+            json!([{ "node": { "name": "rust", "count": 1 } }])
+        );
+        assert_eq!(ids(&data["search"]["results"]), vec![public.clone()]);
+        assert_eq!(data["user"]["urlCount"], 1);
+        assert_eq!(ids(&data["user"]["urls"]), vec![public.clone()]);
+    }
+    let res = warp::test::request()
+        .path("/sitemap.xml")
+        .reply(&server)
+        .await;
+    let sitemap = String::from_utf8(res.body().to_vec()).unwrap();
+    assert!(sitemap.contains(public.as_str().unwrap()));
+    assert!(!sitemap.contains(unlisted.as_str().unwrap()));
+
+    // but they resolve by their link, and discussions work as usual
+    let body = setup::execute(&server, QUERY_URL, json!({ "id": unlisted }), "").await;
+    assert_eq!(
+        body["data"]["fetch__Url"],
+        json!({ "title": "Listed by link", "visibility": "UNLISTED", "commentCount": 0 })
+    );
+    let vars = json!({ "url": unlisted, "body": "Thanks for the link" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &admin_session).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let vars = json!({ "id": unlisted });
+    let body = setup::execute(&server, MUTATION_VOTE, vars, &admin_session).await;
+    assert_eq!(body["data"]["voteUrl"]["score"], 1, "{}", body);
+    let body = setup::execute(&server, QUERY_URL, json!({ "id": unlisted }), "").await;
+    assert_eq!(body["data"]["fetch__Url"]["commentCount"], 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_publishing_unlisted_urls() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = text_post("Listed later", "UNLISTED");
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    assert_eq!(data["submitUrl"]["url"]["visibility"], "UNLISTED");
+    let unlisted = data["submitUrl"]["url"]["id"].clone();
+    let id: UrlID = unlisted.as_str().unwrap().parse().unwrap();
+    let submitted_at = (ctx.now() - Duration::hours(3)).naive_utc();
+    diesel::update(urls::table.find(id))
+        .set((
+            urls::dsl::created_at.eq(submitted_at),
+            urls::dsl::published_at.eq(submitted_at),
+        ))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let vars = text_post("Listed", "PUBLIC");
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    assert_eq!(data["submitUrl"]["url"]["visibility"], "PUBLIC");
+    let public = data["submitUrl"]["url"]["id"].clone();
+
+    // only the submitter may list it, even after the edit window
+    let vars = json!({ "id": unlisted, "input": { "visibility": "PUBLIC" } });
+    let body = setup::execute(&server, MUTATION_UPDATE, vars.clone(), "").await;
+    assert!(body["data"].is_null(), "{}", body);
+    let body = setup::execute(&server, MUTATION_UPDATE, vars, &session).await;
+    let updated = &body["data"]["updateUrl"];
+    assert_eq!(updated["visibility"], "PUBLIC", "{}", body);
+    let published_at = updated["publishedAt"].clone();
+
+    // publishing dates it to now, listing it among the newest submissions
+    let body = setup::execute(&server, QUERY_LISTINGS, json!({}), "").await;
+    let data = &body["data"];
+    assert_eq!(
+        ids(&data["submissions"]),
+        vec![unlisted.clone(), public.clone()]
+    );
+    assert_eq!(ids(&data["tagged"]), vec![unlisted.clone(), public.clone()]);
+    assert_eq!(
+        data["tags"]["edges"],
+        json!([{ "node": { "name": "rust", "count": 2 } }])
+    );
+    let found = ids(&data["search"]["results"]);
+    assert!(found.contains(&unlisted) && found.len() == 2, "{:?}", found);
+    assert_eq!(data["user"]["urlCount"], 2);
+    let query = QUERY_LISTINGS.replace(
+        "submissions(first: 10)",
+        "submissions(first: 10, sort: OLDEST)",
+    );
+    let body = setup::execute(&server, &query, json!({}), "").await;
+    assert_eq!(
+        ids(&body["data"]["submissions"]),
+        vec![public.clone(), unlisted.clone()]
+    );
+
+    // unlisting it again keeps the time it was published
+    let vars = json!({ "id": unlisted, "input": { "visibility": "UNLISTED" } });
+    let body = setup::execute(&server, MUTATION_UPDATE, vars, &session).await;
+    assert_eq!(
+        body["data"]["updateUrl"],
+        json!({ "visibility": "UNLISTED", "publishedAt": published_at })
+    );
+    let body = setup::execute(&server, QUERY_LISTINGS, json!({}), "").await;
+    let data = &body["data"];
+    assert_eq!(ids(&data["submissions"]), vec![public.clone()]);
+    assert_eq!(
+        data["tags"]["edges"],
+        json!([{ "node": { "name": "rust", "count": 1 } }])
+    );
+    assert_eq!(data["user"]["urlCount"], 1);
+}
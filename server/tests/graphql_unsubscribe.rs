@@ -0,0 +1,114 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::models::{EmailCategory, UnsubscribeToken, User, UserPreferences};
+mod setup;
+
+const QUERY_UNSUBSCRIBE: &str = "
+    mutation Unsubscribe($token: String!) {
+        unsubscribe(token: $token) {
+            ok
+        }
+    }
+";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unsubscribe() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let token = UnsubscribeToken::new(&ctx, user.id(), EmailCategory::NewLogin).encode(&ctx);
+
+    // repeated clicks are fine
+    for _ in 0..2 {
+        let vars = json!({ "token": token });
+        let res = setup::graphql(QUERY_UNSUBSCRIBE, vars, "")
+            .reply(&server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body, json!({ "data": { "unsubscribe": { "ok": true } } }));
+    }
+
+    // only the targeted user and category are affected
+    let prefs = UserPreferences::find(&ctx, user.id()).await.unwrap();
+    assert!(!prefs.is_subscribed(EmailCategory::NewLogin));
+    assert_eq!(prefs.timezone_name(), "UTC");
+    let prefs = UserPreferences::find(&ctx, admin.id()).await.unwrap();
+    assert!(prefs.is_subscribed(EmailCategory::NewLogin));
+
+    // mail clients can unsubscribe using a plain link
+    let token = UnsubscribeToken::new(&ctx, admin.id(), EmailCategory::NewLogin).encode(&ctx);
+    for _ in 0..2 {
+        let res = warp::test::request()
+            .path(&format!("/unsubscribe/{}", token))
+            .reply(&server)
+            .await;
+        assert_eq!(res.status(), 200);
+    }
+    let prefs = UserPreferences::find(&ctx, admin.id()).await.unwrap();
+    assert!(!prefs.is_subscribed(EmailCategory::NewLogin));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unsubscribe_invalid_tokens() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let token = UnsubscribeToken::new(&ctx, user.id(), EmailCategory::NewLogin).encode(&ctx);
+
+    let decoded = UnsubscribeToken::decode(&ctx, &token).unwrap();
+    assert_eq!(decoded.user_id(), user.id());
+    assert_eq!(decoded.category(), EmailCategory::NewLogin);
+
+    let (payload, signature) = token.split_once('.').unwrap();
+    let mut tampered_signature = signature.to_string();
+    let last = if tampered_signature.pop() == Some('A') {
+        'B'
+    } else {
+        'A'
+    };
+    tampered_signature.push(last);
+
+    let mut past = ctx.clone();
+    past.set_request_time(ctx.now() - Duration::days(365));
+    let expired = UnsubscribeToken::new(&past, user.id(), EmailCategory::NewLogin).encode(&past);
+
+    let invalid = vec![
+        format!("{}.{}", payload, tampered_signature),
+        format!(
+            "{}.{}",
+            base64::encode_config("other:new_login:0", base64::URL_SAFE_NO_PAD),
+            signature
+        ),
+        payload.to_string(),
+        "not-a-token".to_string(),
+        expired,
+    ];
+    for token in invalid {
+        assert!(UnsubscribeToken::decode(&ctx, &token).is_err());
+
+        let vars = json!({ "token": token });
+        let res = setup::graphql(QUERY_UNSUBSCRIBE, vars, "")
+            .reply(&server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["data"].is_null());
+        assert_eq!(
+            body["errors"][0]["message"],
+            "Invalid or expired unsubscribe token"
+        );
+
+        let res = warp::test::request()
+            .path(&format!("/unsubscribe/{}", token))
+            .reply(&server)
+            .await;
+        assert_eq!(res.status(), 400);
+    }
+
+    let prefs = UserPreferences::find(&ctx, user.id()).await.unwrap();
+    assert!(prefs.is_subscribed(EmailCategory::NewLogin));
+}
@@ -0,0 +1,142 @@
+use chrono::Duration;
+use serde_json::json;
+use server::db::models::User;
+use server::Config;
+mod setup;
+
+const MUTATION_UPDATE: &str = "
+    mutation UpdateComment($id: ID!, $body: String!) {
+        updateComment(id: $id, body: $body) {
+            text
+            bodyHtml
+            editedAt
+        }
+    }
+";
+
+/// Attempt to edit the given comment, returning
+/// the GraphQL response.
+macro_rules! update {
+    ($server:expr, $session:expr, $id:expr, $body:expr) => {{
+        let vars = json!({ "id": $id.to_string(), "body": $body });
+        setup::execute($server, MUTATION_UPDATE, vars, $session).await
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_comment_within_window() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let url = setup::submit(&ctx, user.id()).await;
+    let id = setup::Comment::on(url, user.id())
+        .created_at(ctx.now() - Duration::minutes(5))
+        .insert(&ctx)
+        .await;
+    let body = update!(&server, &session, id, "  Edited text  ");
+    let comment = &body["data"]["updateComment"];
+    assert_eq!(comment["text"], "Edited text");
+    assert!(comment["editedAt"].is_string());
+
+    let body = update!(&server, &session, id, "");
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("Comments must have between 1 and 5000 characters"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_comment_after_window() {
+    let (server, ctx) =
+        setup::mock_with_config(Config::test().with_comment_edit_window(Duration::minutes(60)))
+            .await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let url = setup::submit(&ctx, user.id()).await;
+    let id = setup::Comment::on(url, user.id())
+        .created_at(ctx.now() - Duration::minutes(30))
+        .insert(&ctx)
+        .await;
+    let body = update!(&server, &session, id, "Edited text");
+    assert_eq!(body["data"]["updateComment"]["text"], "Edited text");
+
+    let url = setup::submit(&ctx, user.id()).await;
+    let id = setup::Comment::on(url, user.id())
+        .created_at(ctx.now() - Duration::minutes(90))
+        .insert(&ctx)
+        .await;
+    let body = update!(&server, &session, id, "Edited text");
+    assert!(body["data"].is_null());
+    assert_eq!(body["errors"][0]["extensions"]["code"], "EDIT_NOT_ALLOWED");
+    assert_eq!(body["errors"][0]["extensions"]["reason"], "WINDOW_CLOSED");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_comment_by_other_user() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let url = setup::submit(&ctx, admin.id()).await;
+    let id = setup::Comment::on(url, admin.id())
+        .created_at(ctx.now() - Duration::minutes(1))
+        .insert(&ctx)
+        .await;
+    let body = update!(&server, &session, id, "Edited text");
+    assert!(body["data"].is_null());
+    assert_eq!(body["errors"][0]["extensions"]["code"], "EDIT_NOT_ALLOWED");
+    assert_eq!(body["errors"][0]["extensions"]["reason"], "NOT_AUTHOR");
+
+    // administrators may edit any comment at any time
+    let url = setup::submit(&ctx, user.id()).await;
+    let id = setup::Comment::on(url, user.id())
+        .created_at(ctx.now() - Duration::minutes(24 * 60))
+        .insert(&ctx)
+        .await;
+    let body = update!(&server, &admin_session, id, "Edited text");
+    assert_eq!(body["data"]["updateComment"]["text"], "Edited text");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_comment_renders_markdown() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let url = setup::submit(&ctx, user.id()).await;
+    let id = setup::Comment::on(url, user.id())
+        .created_at(ctx.now() - Duration::minutes(1))
+        .insert(&ctx)
+        .await;
+    let body = update!(&server, &session, id, "*First* edit");
+    assert_eq!(
+        body["data"]["updateComment"]["bodyHtml"],
+        "<p><em>First</em> edit</p>\n"
+    );
+
+    let body = update!(
+        &server,
+        &session,
+        id,
+        "<script>alert(1)</script> see https://example.com"
+    );
+    assert_eq!(
+        body["data"]["updateComment"]["bodyHtml"],
+        "<p>&lt;script&gt;alert(1)&lt;/script&gt; see \
+         <a href=\"https://example.com\" rel=\"nofollow ugc\">https://example.com</a></p>\n"
+    );
+}
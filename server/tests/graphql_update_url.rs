@@ -0,0 +1,110 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::models::User;
+mod setup;
+
+const MUTATION_UPDATE: &str = "
+    mutation UpdateUrl($id: ID!, $input: UpdateUrlInput!) {
+        updateUrl(id: $id, input: $input) {
+            url
+            title
+            description
+            descriptionHtml
+            editedAt
+        }
+    }
+";
+
+/// Attempt to edit the given submission, returning
+/// the GraphQL response.
+macro_rules! update {
+    ($server:expr, $session:expr, $id:expr) => {{
+        let vars = json!({
+            "id": $id.to_string(),
+            "input": { "title": "Edited title", "description": "Edited description" },
+        });
+        let res = setup::graphql(MUTATION_UPDATE, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_url_within_window() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let id = setup::Submission::by(user.id())
+        .created_at(ctx.now() - Duration::minutes(30))
+        .title("Original title")
+        .insert(&ctx)
+        .await;
+    let body = update!(&server, &session, id);
+    let url = &body["data"]["updateUrl"];
+    assert_eq!(url["url"], format!("https://example.com/{}", id));
+    assert_eq!(url["title"], "Edited title");
+    assert_eq!(url["description"], "Edited description");
+    assert_eq!(url["descriptionHtml"], "<p>Edited description</p>\n");
+    assert!(url["editedAt"].is_string());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_url_after_window() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let id = setup::Submission::by(user.id())
+        .created_at(ctx.now() - Duration::minutes(3 * 60))
+        .title("Original title")
+        .insert(&ctx)
+        .await;
+    let body = update!(&server, &session, id);
+    assert!(body["data"].is_null());
+    assert_eq!(body["errors"][0]["extensions"]["code"], "EDIT_NOT_ALLOWED");
+    assert_eq!(body["errors"][0]["extensions"]["reason"], "WINDOW_CLOSED");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_url_by_other_user() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let id = setup::Submission::by(admin.id())
+        .created_at(ctx.now() - Duration::minutes(5))
+        .title("Original title")
+        .insert(&ctx)
+        .await;
+    let body = update!(&server, &session, id);
+    assert!(body["data"].is_null());
+    assert_eq!(body["errors"][0]["extensions"]["code"], "EDIT_NOT_ALLOWED");
+    assert_eq!(body["errors"][0]["extensions"]["reason"], "NOT_AUTHOR");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_url_admin_bypasses_window() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let id = setup::Submission::by(user.id())
+        .created_at(ctx.now() - Duration::minutes(24 * 60))
+        .title("Original title")
+        .insert(&ctx)
+        .await;
+    let body = update!(&server, &admin_session, id);
+    assert_eq!(body["data"]["updateUrl"]["title"], "Edited title");
+    assert!(body["data"]["updateUrl"]["editedAt"].is_string());
+}
@@ -0,0 +1,193 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::{ArchiveStatus, LinkStatus, Url, User};
+use server::Config;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use warp::http::StatusCode;
+use warp::Filter;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($url: String!) {
+        submitUrl(input: { url: $url }) {
+            url { id archiveStatus archivedUrl }
+        }
+    }
+";
+
+const QUERY_URL: &str = "
+    query Url($id: ID!) {
+        fetch__Url(id: $id) { archiveStatus archivedUrl }
+    }
+";
+
+/// Serve a working and a missing page on an ephemeral
+/// local port, returning the address of the server.
+fn serve_pages() -> SocketAddr {
+    let ok = warp::path("ok").map(|| "ok");
+    let missing = warp::path("missing").map(|| StatusCode::NOT_FOUND);
+    setup::serve(ok.or(missing))
+}
+
+/// Serve a mock of the Wayback Machine save API, which fails
+/// with the given status if any, returning the address of the
+/// server and the paths it was asked for.
+fn serve_archive(fail: Option<StatusCode>) -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+    let requests = Arc::new(Mutex::new(vec![]));
+    let log = requests.clone();
+    let save = warp::path("save")
+        .and(warp::path::full())
+        .map(move |path: warp::path::FullPath| {
+            log.lock().unwrap().push(path.as_str().to_string());
+            match fail {
+                Some(status) => warp::http::Response::builder()
+                    .status(status)
+                    .body(String::new()),
+                None => warp::http::Response::builder()
+                    .header("Content-Location", "/web/20211009120000/snapshot")
+                    .body(String::new()),
+            }
+        });
+    let addr = setup::serve(save);
+    (addr, requests)
+}
+
+/// Fetch the archive fields of the given submission.
+macro_rules! archive {
+    ($server:expr, $id:expr) => {{
+        let vars = json!({ "id": $id });
+        let body = setup::execute($server, QUERY_URL, vars, "").await;
+        body["data"]["fetch__Url"].clone()
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_archive_new_submission() {
+    let (archive, requests) = serve_archive(None);
+    let config = Config::test().with_archive_url(Some(format!("http://{}", archive)));
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let pages = serve_pages();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let page = format!("http://{}/ok", pages);
+    let vars = json!({ "url": page });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &body["data"]["submitUrl"]["url"];
+    assert_eq!(url["archiveStatus"], "PENDING");
+    assert_eq!(url["archivedUrl"], Value::Null);
+
+    // archiving happens in the background
+    assert_eq!(Url::archive_pending(&ctx).await.unwrap(), 1);
+    assert_eq!(*requests.lock().unwrap(), vec![format!("/save/{}", page)]);
+    assert_eq!(
+        archive!(&server, url["id"]),
+        json!({
+            "archiveStatus": "ARCHIVED",
+            "archivedUrl": format!("http://{}/web/20211009120000/snapshot", archive),
+        })
+    );
+    assert_eq!(Url::archive_pending(&ctx).await.unwrap(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_archive_failures_are_retried() {
+    let (archive, requests) = serve_archive(Some(StatusCode::SERVICE_UNAVAILABLE));
+    let config = Config::test().with_archive_url(Some(format!("http://{}", archive)));
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let pages = serve_pages();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "url": format!("http://{}/ok", pages) });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let id = body["data"]["submitUrl"]["url"]["id"].clone();
+
+    assert_eq!(Url::archive_pending(&ctx).await.unwrap(), 1);
+    assert_eq!(archive!(&server, id)["archiveStatus"], "PENDING");
+
+    // the next attempt is only made after a while
+    let mut later = ctx.clone();
+    later.set_request_time(ctx.now() + Duration::minutes(5));
+    assert_eq!(Url::archive_pending(&later).await.unwrap(), 0);
+    later.set_request_time(ctx.now() + Duration::minutes(11));
+    assert_eq!(Url::archive_pending(&later).await.unwrap(), 1);
+
+    // and archiving is given up eventually
+    for day in 1..=3 {
+        later.set_request_time(ctx.now() + Duration::days(day));
+        assert_eq!(Url::archive_pending(&later).await.unwrap(), 1);
+    }
+    assert_eq!(
+        archive!(&server, id),
+        json!({ "archiveStatus": "FAILED", "archivedUrl": null })
+    );
+    later.set_request_time(ctx.now() + Duration::days(30));
+    assert_eq!(Url::archive_pending(&later).await.unwrap(), 0);
+    assert_eq!(requests.lock().unwrap().len(), 5);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_archive_broken_links() {
+    let (archive, _) = serve_archive(None);
+    let config = Config::test().with_archive_url(Some(format!("http://{}", archive)));
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let pages = serve_pages();
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    let ok = setup::Submission::by(user.id())
+        .url(&format!("http://{}/ok", pages))
+        .insert(&ctx)
+        .await;
+    let missing = setup::Submission::by(user.id())
+        .url(&format!("http://{}/missing", pages))
+        .insert(&ctx)
+        .await;
+    for id in [ok, missing] {
+        Url::find(&ctx, id)
+            .await
+            .unwrap()
+            .check_link(&ctx)
+            .await
+            .unwrap();
+    }
+
+    // only the broken link is archived
+    assert_eq!(Url::find(&ctx, ok).await.unwrap().archive_status(), None);
+    let url = Url::find(&ctx, missing).await.unwrap();
+    assert_eq!(url.link_status(), LinkStatus::Broken);
+    assert_eq!(url.archive_status(), Some(ArchiveStatus::Pending));
+    assert_eq!(Url::archive_pending(&ctx).await.unwrap(), 1);
+
+    // readers are sent to the snapshot
+    let snapshot = format!("http://{}/web/20211009120000/snapshot", archive);
+    let url = Url::find(&ctx, missing).await.unwrap();
+    assert_eq!(url.link_str(), Some(snapshot.as_str()));
+    let res = warp::test::request()
+        .path(&format!("/comments/{}", missing))
+        .reply(&server)
+        .await;
+    assert!(String::from_utf8_lossy(res.body()).contains("(archived copy)"));
+    let url = Url::find(&ctx, ok).await.unwrap();
+    assert_eq!(url.link_str(), url.url_str());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_archive_disabled() {
+    let (server, ctx) = setup::mock().await;
+    let pages = serve_pages();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "url": format!("http://{}/missing", pages) });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &body["data"]["submitUrl"]["url"];
+    assert_eq!(url["archiveStatus"], Value::Null);
+
+    let id: UrlID = url["id"].as_str().unwrap().parse().unwrap();
+    let mut url = Url::find(&ctx, id).await.unwrap();
+    url.check_link(&ctx).await.unwrap();
+    assert_eq!(url.archive_status(), None);
+    assert_eq!(Url::archive_pending(&ctx).await.unwrap(), 0);
+}
@@ -0,0 +1,165 @@
+use serde_json::{json, Value};
+use server::embed::Provider;
+use server::Config;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use warp::{Filter, Reply};
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($url: String!) {
+        submitUrl(input: { url: $url }) {
+            url {
+                id
+                embed { provider html width height }
+            }
+        }
+    }
+";
+
+/// The oEmbed response of the mock provider for the given
+/// page, or `None` if the page is not known.
+fn oembed_response(url: &str) -> Option<Value> {
+    let page = url.rsplit('/').next()?;
+    let response = match page {
+        "ok" => json!({
+            "type": "video",
+            "title": "A clip",
+            "width": 560,
+            "height": "315",
+            "html": "<iframe width=\"560\" height=\"315\" \
+                src=\"https://player.example/embed/1?feature=oembed\" \
+                allowfullscreen></iframe>",
+        }),
+        "hostile" => json!({
+            "type": "rich",
+            "title": "<img src=x onerror=alert(1)>",
+            "author_name": "Mallory\" onmouseover=\"alert(1)",
+            "html": "<script>alert(1)</script>\
+                <iframe src=\"https://evil.example/embed\" onload=\"alert(1)\"></iframe>",
+        }),
+        "smuggled" => json!({
+            "type": "video",
+            "html": "<iframe src=\"https://player.example/embed/2\" \
+                onload=\"alert(1)\"></iframe><script>alert(1)</script>",
+        }),
+        "large" => json!({
+            "type": "video",
+            "title": "x".repeat(100 * 1024),
+        }),
+        _ => return None,
+    };
+    Some(response)
+}
+
+/// Serve a mock oEmbed provider on an ephemeral local
+/// port, returning the address of the server.
+fn serve_provider() -> SocketAddr {
+    let oembed = warp::path("oembed")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            let url = query.get("url").cloned().unwrap_or_default();
+            assert_eq!(query.get("format").map(String::as_str), Some("json"));
+            match oembed_response(&url) {
+                Some(response) => warp::reply::json(&response).into_response(),
+                None => warp::http::StatusCode::NOT_FOUND.into_response(),
+            }
+        });
+    setup::serve(oembed)
+}
+
+/// Set up a server which unfurls links to the given mock provider.
+async fn mock_with_provider(
+    addr: SocketAddr,
+) -> (
+    impl Filter<Extract = (impl Reply,), Error = std::convert::Infallible> + Clone,
+    server::Context,
+) {
+    let provider = Provider::new(
+        "Mock",
+        &[&format!("http://{}/video/*", addr)],
+        &format!("http://{}/oembed", addr),
+        &["player.example"],
+    );
+    let conf = Config::test().with_oembed_providers(vec![provider]);
+    setup::mock_with_config(conf).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_embed_video() {
+    let addr = serve_provider();
+    let (server, ctx) = mock_with_provider(addr).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "url": format!("http://{}/video/ok", addr) });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &data["submitUrl"]["url"];
+    assert_eq!(
+        url["embed"],
+        json!({
+            "provider": "Mock",
+            "html": "<iframe src=\"https://player.example/embed/1?feature=oembed\" \
+                width=\"560\" height=\"315\" frameborder=\"0\" \
+                allow=\"encrypted-media; fullscreen; picture-in-picture\" \
+                allowfullscreen loading=\"lazy\"></iframe>",
+            "width": 560,
+            "height": 315,
+        })
+    );
+
+    // the player is shown on the discussion page
+    let res = warp::test::request()
+        .path(&format!("/comments/{}", url["id"].as_str().unwrap()))
+        .reply(&server)
+        .await;
+    let page = std::str::from_utf8(res.body()).unwrap();
+    assert!(page.contains("<iframe src=\"https://player.example/embed/1?feature=oembed\""));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_embed_hostile_response() {
+    let addr = serve_provider();
+    let (server, ctx) = mock_with_provider(addr).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // players from other hosts fall back to an escaped card
+    let vars = json!({ "url": format!("http://{}/video/hostile", addr) });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &data["submitUrl"]["url"];
+    let html = url["embed"]["html"].as_str().unwrap();
+    assert_eq!(
+        html,
+        format!(
+            "<blockquote class=\"embed\"><p>&lt;img src=x onerror=alert(1)&gt;</p>&mdash; \
+            <a href=\"http://{}/video/hostile\">Mallory&quot; onmouseover=&quot;alert(1)</a>\
+            </blockquote>",
+            addr
+        )
+    );
+
+    // only the source of allowed players is kept
+    let vars = json!({ "url": format!("http://{}/video/smuggled", addr) });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &data["submitUrl"]["url"];
+    let html = url["embed"]["html"].as_str().unwrap();
+    assert!(html.starts_with("<iframe src=\"https://player.example/embed/2\""));
+    for forbidden in ["onload", "<script", "alert"] {
+        assert!(!html.contains(forbidden), "{}", forbidden);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_embed_skipped() {
+    let addr = serve_provider();
+    let (server, ctx) = mock_with_provider(addr).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // unknown providers, unknown pages, and oversized
+    // responses are skipped silently
+    for path in ["page/ok", "video/missing", "video/large"] {
+        let vars = json!({ "url": format!("http://{}/{}", addr, path) });
+        let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+        let url = &data["submitUrl"]["url"];
+        assert!(url["embed"].is_null(), "{}", path);
+    }
+}
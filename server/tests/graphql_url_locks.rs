@@ -0,0 +1,185 @@
+use serde_json::{json, Value};
+use server::db::models::User;
+mod setup;
+
+const MUTATION_LOCK: &str = "
+    mutation LockUrl($id: ID!, $reason: String) {
+        lockUrl(id: $id, reason: $reason) {
+            locked
+            lockedAt
+            lockReason
+        }
+    }
+";
+
+const MUTATION_UNLOCK: &str = "
+    mutation UnlockUrl($id: ID!) {
+        unlockUrl(id: $id) {
+            locked
+            lockedAt
+            lockReason
+        }
+    }
+";
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($urlId: ID!, $body: String!) {
+        addComment(urlId: $urlId, body: $body) { body }
+    }
+";
+
+const MUTATION_UPDATE_COMMENT: &str = "
+    mutation UpdateComment($id: ID!, $body: String!) {
+        updateComment(id: $id, body: $body) { body }
+    }
+";
+
+const MUTATION_VOTE_COMMENT: &str = "
+    mutation VoteComment($id: ID!) {
+        voteComment(id: $id) { score }
+    }
+";
+
+const MUTATION_UNVOTE_COMMENT: &str = "
+    mutation UnvoteComment($id: ID!) {
+        unvoteComment(id: $id) { score }
+    }
+";
+
+const QUERY_COMMENT: &str = "
+    query Comment($id: ID!) {
+        fetch__Comment(id: $id) { body }
+    }
+";
+
+/// Assert that the given response was rejected because
+/// the discussion is locked.
+fn assert_locked(body: &Value) {
+    assert!(body["data"].is_null());
+    assert_eq!(body["errors"][0]["extensions"]["code"], "LOCKED");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_locked_discussions() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let url = setup::submit(&ctx, user.id()).await;
+    let own_comment = setup::Comment::on(url, user.id())
+        .text("Original text")
+        .insert(&ctx)
+        .await;
+    let admin_comment = setup::Comment::on(url, admin.id())
+        .text("Original text")
+        .insert(&ctx)
+        .await;
+    let vars = json!({ "id": admin_comment });
+    let body = setup::execute(&server, MUTATION_VOTE_COMMENT, vars, &session).await;
+    assert_eq!(body["data"]["voteComment"]["score"], 1);
+
+    // only moderators may lock discussions
+    let vars = json!({ "id": url, "reason": "Off topic" });
+    let body = setup::execute(&server, MUTATION_LOCK, vars.clone(), &session).await;
+    assert!(body["data"].is_null());
+    let body = setup::execute(&server, MUTATION_LOCK, vars, &admin_session).await;
+    let locked = &body["data"]["lockUrl"];
+    assert_eq!(locked["locked"], true);
+    assert!(locked["lockedAt"].is_string());
+    assert_eq!(locked["lockReason"], "Off topic");
+
+    let vars = json!({ "urlId": url, "body": "Hello" });
+    assert_locked(&setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await);
+    let vars = json!({ "id": own_comment, "body": "Edited" });
+    assert_locked(&setup::execute(&server, MUTATION_UPDATE_COMMENT, vars, &session).await);
+    let vars = json!({ "id": admin_comment });
+    assert_locked(&setup::execute(&server, MUTATION_VOTE_COMMENT, vars.clone(), &session).await);
+    assert_locked(&setup::execute(&server, MUTATION_UNVOTE_COMMENT, vars, &session).await);
+
+    // existing comments stay visible
+    let vars = json!({ "id": own_comment });
+    let body = setup::execute(&server, QUERY_COMMENT, vars, "").await;
+    assert_eq!(body["data"]["fetch__Comment"]["body"], "Original text");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_administrators_bypass_locks() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let url = setup::submit(&ctx, user.id()).await;
+    let user_comment = setup::Comment::on(url, user.id())
+        .text("Original text")
+        .insert(&ctx)
+        .await;
+    let vars = json!({ "id": url });
+    setup::execute(&server, MUTATION_LOCK, vars, &admin_session).await;
+
+    let vars = json!({ "urlId": url, "body": "Hello" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &admin_session).await;
+    assert_eq!(body["data"]["addComment"]["body"], "Hello");
+    let vars = json!({ "id": user_comment, "body": "Edited" });
+    let body = setup::execute(&server, MUTATION_UPDATE_COMMENT, vars, &admin_session).await;
+    assert_eq!(body["data"]["updateComment"]["body"], "Edited");
+    let vars = json!({ "id": user_comment });
+    let body = setup::execute(&server, MUTATION_VOTE_COMMENT, vars.clone(), &admin_session).await;
+    assert_eq!(body["data"]["voteComment"]["score"], 1);
+    let body = setup::execute(&server, MUTATION_UNVOTE_COMMENT, vars, &admin_session).await;
+    assert_eq!(body["data"]["unvoteComment"]["score"], 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unlock_url() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let url = setup::submit(&ctx, user.id()).await;
+    let own_comment = setup::Comment::on(url, user.id())
+        .text("Original text")
+        .insert(&ctx)
+        .await;
+    let admin_comment = setup::Comment::on(url, admin.id())
+        .text("Original text")
+        .insert(&ctx)
+        .await;
+    let vars = json!({ "id": url, "reason": "Heated" });
+    setup::execute(&server, MUTATION_LOCK, vars, &admin_session).await;
+
+    // only moderators may unlock discussions
+    let body = setup::execute(&server, MUTATION_UNLOCK, json!({ "id": url }), &session).await;
+    assert!(body["data"].is_null());
+    let vars = json!({ "id": url });
+    let body = setup::execute(&server, MUTATION_UNLOCK, vars, &admin_session).await;
+    assert_eq!(
+        body["data"]["unlockUrl"],
+        json!({ "locked": false, "lockedAt": null, "lockReason": null })
+    );
+
+    let vars = json!({ "urlId": url, "body": "Hello" });
+    let body = setup::execute(&server, MUTATION_ADD_COMMENT, vars, &session).await;
+    assert_eq!(body["data"]["addComment"]["body"], "Hello");
+    let vars = json!({ "id": own_comment, "body": "Edited" });
+    let body = setup::execute(&server, MUTATION_UPDATE_COMMENT, vars, &session).await;
+    assert_eq!(body["data"]["updateComment"]["body"], "Edited");
+    let vars = json!({ "id": admin_comment });
+    let body = setup::execute(&server, MUTATION_VOTE_COMMENT, vars.clone(), &session).await;
+    assert_eq!(body["data"]["voteComment"]["score"], 1);
+    let body = setup::execute(&server, MUTATION_UNVOTE_COMMENT, vars, &session).await;
+    assert_eq!(body["data"]["unvoteComment"]["score"], 0);
+}
@@ -0,0 +1,134 @@
+use futures_util::StreamExt;
+use serde_json::json;
+use server::Config;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Duration;
+use warp::hyper::body::{Body, Bytes};
+use warp::Filter;
+mod setup;
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($url: String!) {
+        submitUrl(input: { url: $url }) {
+            url {
+                title
+                fetchedTitle
+                fetchedDescription
+                previewImageUrl
+                metadataStatus
+            }
+        }
+    }
+";
+
+const PAGE: &str = "<html><head>\
+    <title>A Page</title>\
+    <meta name=\"description\" content=\"Something interesting\">\
+    <meta property=\"og:image\" content=\"http://127.0.0.1:9/preview.png\">\
+    </head><body></body></html>";
+
+/// Serve a few test pages on an ephemeral local port,
+/// returning the address of the server.
+fn serve_pages() -> SocketAddr {
+    let page = warp::path("page").map(|| warp::reply::html(PAGE));
+    let redirect = warp::path("redirect")
+        .map(|| warp::redirect::temporary(warp::http::Uri::from_static("/page")));
+    let redirect_loop =
+        warp::path("loop").map(|| warp::redirect::temporary(warp::http::Uri::from_static("/loop")));
+    let slow = warp::path("slow").and_then(|| async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        Ok::<_, Infallible>(warp::reply::html(PAGE))
+    });
+    let endless = warp::path("endless").map(|| {
+        let head = futures_util::stream::once(async { Ok::<_, Infallible>(Bytes::from(PAGE)) });
+        let padding = futures_util::stream::repeat(Bytes::from(vec![b' '; 64 * 1024]))
+            .map(Ok::<_, Infallible>);
+        warp::reply::Response::new(Body::wrap_stream(head.chain(padding)))
+    });
+
+    let routes = page.or(redirect).or(redirect_loop).or(slow).or(endless);
+    setup::serve(routes)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_metadata_fetched_on_submission() {
+    let (server, ctx) = setup::mock().await;
+    let addr = serve_pages();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let vars = json!({ "url": format!("http://{}/page", addr) });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &body["data"]["submitUrl"]["url"];
+    assert_eq!(
+        *url,
+        json!({
+            "title": "A Page",
+            "fetchedTitle": "A Page",
+            "fetchedDescription": "Something interesting",
+            // the image can not be fetched, and the remote
+            // image is never exposed instead
+            "previewImageUrl": null,
+            "metadataStatus": "OK",
+        })
+    );
+
+    // redirects are followed, but not forever
+    let vars = json!({ "url": format!("http://{}/redirect", addr) });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &body["data"]["submitUrl"]["url"];
+    assert_eq!(url["fetchedTitle"], "A Page");
+    let vars = json!({ "url": format!("http://{}/loop", addr) });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &body["data"]["submitUrl"]["url"];
+    assert_eq!(url["metadataStatus"], "FAILED");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_metadata_fetch_timeout() {
+    let conf = Config::test().with_fetch_timeout(Duration::from_millis(500));
+    let (server, ctx) = setup::mock_with_config(conf).await;
+    let addr = serve_pages();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // the submission is stored, even though the page timed out
+    let vars = json!({ "url": format!("http://{}/slow", addr) });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &body["data"]["submitUrl"]["url"];
+    assert_eq!(url["metadataStatus"], "FAILED");
+    assert!(url["title"].is_null());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_metadata_fetch_caps_body_size() {
+    let conf = Config::test().with_fetch_timeout(Duration::from_secs(5));
+    let (server, ctx) = setup::mock_with_config(conf).await;
+    let addr = serve_pages();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // the endless body is cut off, rather than read until the timeout
+    let vars = json!({ "url": format!("http://{}/endless", addr) });
+    let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url = &body["data"]["submitUrl"]["url"];
+    assert_eq!(url["metadataStatus"], "OK");
+    assert_eq!(url["fetchedTitle"], "A Page");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_metadata_fetch_refuses_private_addresses() {
+    let conf = Config::test().with_fetch_private_addresses(false);
+    let (server, ctx) = setup::mock_with_config(conf).await;
+    let addr = serve_pages();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    for url in &[
+        format!("http://{}/page", addr),
+        format!("http://localhost:{}/page", addr.port()),
+    ] {
+        let vars = json!({ "url": url });
+        let body = setup::execute(&server, MUTATION_SUBMIT, vars, &session).await;
+        let url = &body["data"]["submitUrl"]["url"];
+        assert_eq!(url["metadataStatus"], "FAILED");
+        assert!(url["fetchedTitle"].is_null());
+    }
+}
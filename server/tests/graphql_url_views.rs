@@ -0,0 +1,123 @@
+use chrono::Duration;
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::{Url, UrlView, User};
+use server::Context;
+mod setup;
+
+const BROWSER: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:93.0) Gecko/20100101 Firefox/93.0";
+
+const MUTATION_VIEW: &str = "
+    mutation RecordUrlView($id: ID!) {
+        recordUrlView(id: $id) { ok }
+    }
+";
+
+const QUERY_VIEWS: &str = "
+    query Views($id: ID!) {
+        fetchUrl(id: $id) { views }
+    }
+";
+
+/// Anonymously record a view of the given submission from
+/// the given address and user agent.
+macro_rules! view {
+    ($server:expr, $id:expr, $ip:expr, $user_agent:expr) => {{
+        let vars = json!({ "id": $id.to_string() });
+        let res = setup::graphql(MUTATION_VIEW, vars, "")
+            .header("X-Forwarded-For", $ip)
+            .header("User-Agent", $user_agent)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["errors"].is_null(), "{}", body);
+    }};
+}
+
+/// Wait for the views of the given submission, which are
+/// stored in the background, to reach the expected count.
+async fn wait_for_views(ctx: &Context, id: UrlID, expected: i64) -> i64 {
+    let mut views = 0;
+    for _ in 0..20 {
+        views = Url::find(ctx, id).await.unwrap().views();
+        if views >= expected {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    views
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_view_dedup_within_day() {
+    let (_, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let id = setup::submit(&ctx, admin.id()).await;
+
+    let as_user = ctx.acting_as(user.id());
+    assert!(UrlView::store(&as_user, id).await.unwrap());
+    assert!(!UrlView::store(&as_user, id).await.unwrap());
+    let mut later = as_user.clone();
+    later.set_request_time(ctx.now().date().and_hms(23, 59, 59));
+    assert!(!UrlView::store(&later, id).await.unwrap());
+
+    let as_admin = ctx.acting_as(admin.id());
+    assert!(UrlView::store(&as_admin, id).await.unwrap());
+    assert_eq!(Url::find(&ctx, id).await.unwrap().views(), 2);
+
+    // anonymous views without an address are not counted
+    assert!(!UrlView::store(&ctx, id).await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_view_rollover_across_days() {
+    let (_, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let id = setup::submit(&ctx, user.id()).await;
+
+    let mut today = ctx.acting_as(user.id());
+    today.set_request_time(ctx.now().date().and_hms(23, 59, 0));
+    assert!(UrlView::store(&today, id).await.unwrap());
+
+    let mut tomorrow = today.clone();
+    tomorrow.set_request_time(today.now() + Duration::minutes(2));
+    assert!(UrlView::store(&tomorrow, id).await.unwrap());
+    assert!(!UrlView::store(&tomorrow, id).await.unwrap());
+    assert_eq!(Url::find(&ctx, id).await.unwrap().views(), 2);
+
+    // views of past days are pruned, keeping the count
+    assert_eq!(UrlView::prune(&tomorrow).await.unwrap(), 1);
+    assert_eq!(UrlView::prune(&tomorrow).await.unwrap(), 0);
+    assert!(!UrlView::store(&tomorrow, id).await.unwrap());
+    assert_eq!(Url::find(&ctx, id).await.unwrap().views(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_anonymous_views() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let id = setup::submit(&ctx, user.id()).await;
+
+    view!(&server, id, "203.0.113.7", BROWSER);
+    assert_eq!(wait_for_views(&ctx, id, 1).await, 1);
+    view!(&server, id, "203.0.113.7", BROWSER);
+    view!(&server, id, "203.0.113.7", "Googlebot/2.1");
+    view!(&server, id, "203.0.113.8", BROWSER);
+    assert_eq!(wait_for_views(&ctx, id, 2).await, 2);
+
+    // the view count is exposed once stored
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let vars = json!({ "id": id.to_string() });
+    let res = setup::graphql(QUERY_VIEWS, vars, "").reply(&server).await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["fetchUrl"]["views"], 2);
+}
@@ -0,0 +1,161 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::id::{UrlID, UserID};
+use server::db::models::{NewUserInput, User};
+use server::schema::url_upvotes;
+use server::{Config, Context};
+mod setup;
+
+const QUERY_VOTERS: &str = "
+    query Voters($id: ID!, $after: String) {
+        fetch__Url(id: $id) {
+            upvotes
+            voters(first: 10, after: $after) {
+                edges {
+                    cursor
+                    node { username }
+                }
+            }
+        }
+    }
+";
+
+const MUTATION_HIDE_VOTES: &str = "
+    mutation UpdatePreferences {
+        updatePreferences(input: { hideVotes: true }) {
+            preferences { hideVotes }
+        }
+    }
+";
+
+/// Insert a vote of the given user in the given direction,
+/// cast `minutes` after the mock context was created.
+async fn vote(ctx: &Context, url: UrlID, user: UserID, direction: &str, minutes: i64) {
+    let created_at = (ctx.now() + Duration::minutes(minutes)).naive_utc();
+    diesel::insert_into(url_upvotes::table)
+        .values((
+            url_upvotes::dsl::url_id.eq(url),
+            url_upvotes::dsl::user_id.eq(user),
+            url_upvotes::dsl::created_at.eq(created_at),
+            url_upvotes::dsl::direction.eq(direction),
+        ))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+}
+
+/// Create a user with the given name, returning them.
+async fn user(ctx: &Context, name: &str) -> User {
+    let input = NewUserInput {
+        name: name.into(),
+        email: format!("test.{}@urls.fyi", name),
+    };
+    User::create(ctx, input).await.unwrap()
+}
+
+/// Set up a submission by `test.user@urls.fyi`, upvoted by the
+/// administrator and two other users, and downvoted by a third.
+async fn voted_url(ctx: &Context) -> UrlID {
+    let author = User::find_by_email(ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin = User::find_by_email(ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let url = setup::Submission::by(author.id())
+        .upvotes(3)
+        .insert(ctx)
+        .await;
+    vote(ctx, url, admin.id(), "up", 0).await;
+    vote(ctx, url, user(ctx, "first").await.id(), "up", 1).await;
+    vote(ctx, url, user(ctx, "second").await.id(), "up", 2).await;
+    vote(ctx, url, user(ctx, "critic").await.id(), "down", 3).await;
+    url
+}
+
+/// Fetch the voters of the given submission, returning the
+/// response.
+macro_rules! voters {
+    ($server:expr, $session:expr, $vars:expr) => {{
+        let res = setup::graphql(QUERY_VOTERS, $vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// The usernames listed in the given voters response.
+fn usernames(body: &Value) -> Vec<String> {
+    body["data"]["fetch__Url"]["voters"]["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| edge["node"]["username"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_voters_visibility() {
+    let (server, ctx) = setup::mock().await;
+    let url = voted_url(&ctx).await;
+    let vars = json!({ "id": url });
+    let expected = vec!["second", "first", "test-administrator"];
+
+    // the submitter and administrators see the voters
+    for email in &["test.user@urls.fyi", "test.admin@urls.fyi"] {
+        let session = setup::session_token(&ctx, email).await;
+        let body = voters!(&server, &session, vars.clone());
+        assert_eq!(usernames(&body), expected, "{}", email);
+    }
+
+    // everyone else gets an error
+    let session = setup::session_token(&ctx, "test.first@urls.fyi").await;
+    for session in &[session.as_str(), ""] {
+        let body = voters!(&server, session, vars.clone());
+        assert!(body["data"].is_null());
+        assert!(body["errors"].is_array());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_public_voters() {
+    let (server, ctx) = setup::mock_with_config(Config::test().with_public_voters(true)).await;
+    let url = voted_url(&ctx).await;
+    let session = setup::session_token(&ctx, "test.first@urls.fyi").await;
+
+    for session in &[session.as_str(), ""] {
+        let body = voters!(&server, session, json!({ "id": url }));
+        assert_eq!(
+            usernames(&body),
+            vec!["second", "first", "test-administrator"]
+        );
+    }
+
+    // voters are paginated by vote time
+    let body = voters!(&server, "", json!({ "id": url }));
+    let cursor = body["data"]["fetch__Url"]["voters"]["edges"][0]["cursor"].clone();
+    let body = voters!(&server, "", json!({ "id": url, "after": cursor }));
+    assert_eq!(usernames(&body), vec!["first", "test-administrator"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_hidden_voters() {
+    let (server, ctx) = setup::mock().await;
+    let url = voted_url(&ctx).await;
+    let session = setup::session_token(&ctx, "test.second@urls.fyi").await;
+    let res = setup::graphql(MUTATION_HIDE_VOTES, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["data"]["updatePreferences"]["preferences"]["hideVotes"],
+        true
+    );
+
+    // users who opted out are counted, but not listed
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let body = voters!(&server, &session, json!({ "id": url }));
+    assert_eq!(body["data"]["fetch__Url"]["upvotes"], 3);
+    assert_eq!(usernames(&body), vec!["first", "test-administrator"]);
+}
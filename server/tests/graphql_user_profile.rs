@@ -0,0 +1,275 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::{NewUserInput, User};
+use server::schema::urls;
+use server::Context;
+mod setup;
+
+const QUERY_PROFILE: &str = "
+    query Profile($username: String!) {
+        user(username: $username) {
+            name
+            username
+            urlCount
+            commentCount
+        }
+    }
+";
+
+const QUERY_PROFILE_URLS: &str = "
+    query ProfileUrls($username: String!, $sort: UrlSort, $after: String) {
+        user(username: $username) {
+            urls(first: 2, after: $after, sort: $sort) {
+                edges {
+                    node { title }
+                }
+                pageInfo {
+                    hasNextPage
+                    endCursor
+                }
+            }
+        }
+    }
+";
+
+const MUTATION_UPDATE_USER: &str = "
+    mutation UpdateUser($input: UpdateUserInput!) {
+        updateUser(input: $input) {
+            email
+        }
+    }
+";
+
+/// Mark the given submission as deleted.
+async fn delete(ctx: &Context, id: UrlID) {
+    diesel::update(urls::table.find(id))
+        .set(urls::dsl::deleted_at.eq(ctx.now().naive_utc()))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+}
+
+/// Fetch the profile of the given user, returning
+/// the GraphQL response.
+macro_rules! profile {
+    ($server:expr, $session:expr, $username:expr) => {{
+        let vars = json!({ "username": $username });
+        setup::execute($server, QUERY_PROFILE, vars, $session).await
+    }};
+}
+
+/// Fetch all submissions of the given user in the
+/// given order, page by page.
+macro_rules! profile_urls {
+    ($server:expr, $username:expr, $sort:expr) => {{
+        let mut titles = vec![];
+        let mut after = Value::Null;
+        loop {
+            let vars = json!({ "username": $username, "sort": $sort, "after": after });
+            let body = setup::execute($server, QUERY_PROFILE_URLS, vars, "").await;
+            let urls = &body["data"]["user"]["urls"];
+            titles.extend(
+                urls["edges"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|edge| edge["node"]["title"].as_str().unwrap().to_string()),
+            );
+            if urls["pageInfo"]["hasNextPage"] != true {
+                break;
+            }
+            after = urls["pageInfo"]["endCursor"].clone();
+        }
+        titles
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_user_profile() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    assert_eq!(user.username(), "test-user");
+
+    let live = setup::Submission::by(user.id())
+        .title("Live")
+        .score(0)
+        .created_at(ctx.now() - Duration::hours(1))
+        .insert(&ctx)
+        .await;
+    setup::Submission::by(user.id())
+        .title("Also live")
+        .score(0)
+        .created_at(ctx.now() - Duration::hours(2))
+        .insert(&ctx)
+        .await;
+    let deleted = setup::Submission::by(user.id())
+        .title("Deleted")
+        .score(0)
+        .created_at(ctx.now() - Duration::hours(3))
+        .insert(&ctx)
+        .await;
+    delete(&ctx, deleted).await;
+    setup::Comment::on(live, user.id()).insert(&ctx).await;
+    setup::Comment::on(live, user.id()).insert(&ctx).await;
+    setup::Comment::on(live, user.id())
+        .deleted()
+        .insert(&ctx)
+        .await;
+
+    // usernames are case insensitive
+    let body = profile!(&server, "", " Test-User ");
+    assert_eq!(
+        body,
+        json!({
+            "data": {
+                "user": {
+                    "name": "Test User",
+                    "username": "test-user",
+                    "urlCount": 2,
+                    "commentCount": 2,
+                }
+            }
+        })
+    );
+
+    let body = profile!(&server, "", "nobody");
+    assert_eq!(body, json!({ "data": { "user": null } }));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_user_profile_privacy() {
+    let (server, ctx) = setup::mock().await;
+    let mut user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    // the email address is not part of the public profile
+    let query = "{ user(username: \"test-user\") { email } }";
+    let res = setup::graphql(query, json!({}), &admin_session)
+        .reply(&server)
+        .await;
+    let body = String::from_utf8_lossy(res.body()).to_string();
+    assert!(body.contains("errors"));
+    assert!(!body.contains("test.user@urls.fyi"));
+
+    let query = "{ user(username: \"test-user\") { id name username joined bannedAt } }";
+    let res = setup::graphql(query, json!({}), &admin_session)
+        .reply(&server)
+        .await;
+    let body = String::from_utf8_lossy(res.body()).to_string();
+    assert!(body.contains("\"username\":\"test-user\""));
+    assert!(!body.contains("test.user@urls.fyi"));
+
+    // banned users are only visible to moderators
+    user.ban(&ctx).await.unwrap();
+    let body = profile!(&server, "", "test-user");
+    assert!(body["data"]["user"].is_null());
+    let body = profile!(&server, &session, "test-user");
+    assert!(body["data"]["user"].is_null());
+    let body = profile!(&server, &admin_session, "test-user");
+    assert_eq!(body["data"]["user"]["username"], "test-user");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_user_profile_urls() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+
+    let cases = [
+        ("First", 2, 1, false),
+        ("Deleted", 9, 2, true),
+        ("Second", 5, 3, false),
+        ("Also deleted", 1, 4, true),
+        ("Third", 1, 4, false),
+        ("Old", 3, 72, false),
+    ];
+    for (title, score, hours, deleted) in cases {
+        let id = setup::Submission::by(user.id())
+            .title(title)
+            .score(score)
+            .created_at(ctx.now() - Duration::hours(hours))
+            .insert(&ctx)
+            .await;
+        if deleted {
+            delete(&ctx, id).await;
+        }
+    }
+    setup::Submission::by(admin.id())
+        .title("By someone else")
+        .score(7)
+        .created_at(ctx.now() - Duration::hours(1))
+        .insert(&ctx)
+        .await;
+
+    assert_eq!(
+        profile_urls!(&server, "test-user", "NEWEST"),
+        vec!["First", "Second", "Third", "Old"]
+    );
+    // the top order includes all submissions of the user
+    assert_eq!(
+        profile_urls!(&server, "test-user", "TOP"),
+        vec!["Second", "Old", "First", "Third"]
+    );
+    assert_eq!(
+        profile_urls!(&server, "test-administrator", "OLDEST"),
+        vec!["By someone else"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_username() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    // derived usernames are unique
+    let input = NewUserInput {
+        name: "Test User".into(),
+        email: "test.other@urls.fyi".into(),
+    };
+    let other = User::create(&ctx, input).await.unwrap();
+    assert_eq!(other.username(), "test-user-2");
+
+    let update = |username: &str| {
+        let vars = json!({ "input": { "username": username } });
+        setup::graphql(MUTATION_UPDATE_USER, vars, &session).reply(&server)
+    };
+
+    let body: Value = serde_json::from_slice(update("test-user-2").await.body()).unwrap();
+    assert_eq!(
+        body["errors"][0]["message"],
+        "This username is already taken"
+    );
+    for username in ["a", "with space", "dots.are.invalid", "ünicode"] {
+        let body: Value = serde_json::from_slice(update(username).await.body()).unwrap();
+        assert!(
+            body["errors"][0]["message"]
+                .as_str()
+                .unwrap()
+                .contains("Usernames must have between 2 and 30"),
+            "{}",
+            username
+        );
+    }
+
+    let body: Value = serde_json::from_slice(update(" New_Name ").await.body()).unwrap();
+    assert!(body["errors"].is_null());
+    assert_eq!(
+        profile!(&server, "", "new_name")["data"]["user"]["name"],
+        "Test User"
+    );
+    assert!(profile!(&server, "", "test-user")["data"]["user"].is_null());
+
+    // keeping the current username is fine
+    let body: Value = serde_json::from_slice(update("NEW_NAME").await.body()).unwrap();
+    assert!(body["errors"].is_null());
+}
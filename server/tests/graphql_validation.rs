@@ -0,0 +1,137 @@
+use serde_json::{json, Value};
+mod setup;
+
+const MUTATION_REGISTER: &str = "
+    mutation RegisterUser($input: NewUserInput!) {
+        registerUser(input: $input) { name }
+    }
+";
+
+const MUTATION_UPDATE_USER: &str = "
+    mutation UpdateUser($input: UpdateUserInput!) {
+        updateUser(input: $input) { email }
+    }
+";
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id }
+        }
+    }
+";
+
+const MUTATION_UPDATE_PREFERENCES: &str = "
+    mutation UpdatePreferences($input: PreferencesInput!) {
+        updatePreferences(input: $input) {
+            preferences { timezone }
+        }
+    }
+";
+
+/// Run the given GraphQL request, which must fail validation, and
+/// return the paths and codes of the reported field errors.
+macro_rules! field_errors {
+    ($server:expr, $session:expr, $query:expr, $input:expr) => {{
+        let vars = json!({ "input": $input });
+        let res = setup::graphql($query, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["data"].is_null(), "{}", body);
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1, "{}", body);
+        let extensions = &body["errors"][0]["extensions"];
+        assert_eq!(extensions["code"], "VALIDATION_FAILED", "{}", body);
+        let errors: Vec<Value> = extensions["fieldErrors"]
+            .as_array()
+            .expect(&body.to_string())
+            .iter()
+            .map(|error| {
+                let mut error = error.clone();
+                error.as_object_mut().unwrap().remove("message");
+                error
+            })
+            .collect();
+        errors
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_register_user_violations() {
+    let (server, _) = setup::mock().await;
+    let input = json!({ "name": "a".repeat(257), "email": "not an email" });
+    let errors = field_errors!(&server, "", MUTATION_REGISTER, input);
+    assert_eq!(
+        errors,
+        vec![
+            json!({ "path": ["input", "email"], "code": "INVALID_EMAIL" }),
+            json!({ "path": ["input", "name"], "code": "TOO_LONG", "limit": 256 }),
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_user_violations() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let input = json!({ "name": " ", "username": "with space", "email": "test.user@" });
+    let errors = field_errors!(&server, &session, MUTATION_UPDATE_USER, input);
+    assert_eq!(
+        errors,
+        vec![
+            json!({ "path": ["input", "email"], "code": "INVALID_EMAIL" }),
+            json!({ "path": ["input", "name"], "code": "TOO_SHORT", "limit": 1 }),
+            json!({ "path": ["input", "username"], "code": "INVALID_USERNAME" }),
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_submit_url_violations() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let input = json!({
+        "title": "a".repeat(257),
+        "text": "a".repeat(10001),
+        "description": "a".repeat(2049),
+    });
+    let errors = field_errors!(&server, &session, MUTATION_SUBMIT, input);
+    assert_eq!(
+        errors,
+        vec![
+            json!({ "path": ["input", "description"], "code": "TOO_LONG", "limit": 2048 }),
+            json!({ "path": ["input", "text"], "code": "TOO_LONG", "limit": 10000 }),
+            json!({ "path": ["input", "title"], "code": "TOO_LONG", "limit": 256 }),
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_update_preferences_violations() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let input = json!({ "timezone": "Mars/Olympus_Mons", "locale": "xx" });
+    let errors = field_errors!(&server, &session, MUTATION_UPDATE_PREFERENCES, input);
+    assert_eq!(
+        errors,
+        vec![
+            json!({ "path": ["input", "locale"], "code": "UNSUPPORTED_LOCALE" }),
+            json!({ "path": ["input", "timezone"], "code": "UNKNOWN_TIMEZONE" }),
+        ]
+    );
+
+    // the messages of all violations are reported
+    let vars = json!({ "input": { "timezone": "Mars/Olympus_Mons", "locale": "xx" } });
+    let res = setup::graphql(MUTATION_UPDATE_PREFERENCES, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body["errors"][0]["message"],
+        "The locale is not supported; The timezone must be a valid tz database name"
+    );
+    assert_eq!(
+        body["errors"][0]["extensions"]["fieldErrors"][0]["message"],
+        "The locale is not supported"
+    );
+}
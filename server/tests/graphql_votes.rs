@@ -0,0 +1,238 @@
+use futures_util::future::join_all;
+use serde_json::{json, Value};
+use server::db::models::User;
+use server::Config;
+mod setup;
+
+const MUTATION_VOTE: &str = "
+    mutation VoteUrl($id: ID!) {
+        voteUrl(id: $id) {
+            score
+            viewerHasVoted
+        }
+    }
+";
+
+const MUTATION_VOTE_DIRECTION: &str = "
+    mutation VoteUrl($id: ID!, $direction: VoteDirection!) {
+        voteUrl(id: $id, direction: $direction) {
+            score
+            upvotes
+            downvotes
+            viewerVote
+        }
+    }
+";
+
+const MUTATION_UNVOTE: &str = "
+    mutation UnvoteUrl($id: ID!) {
+        unvoteUrl(id: $id) {
+            score
+            viewerHasVoted
+        }
+    }
+";
+
+const QUERY_SUBMISSIONS: &str = "
+    query Submissions {
+        submissions(first: 10) {
+            edges {
+                node {
+                    id
+                    score
+                    viewerHasVoted
+                }
+            }
+        }
+    }
+";
+
+/// Vote on the given submission in the given direction,
+/// returning the GraphQL response.
+macro_rules! vote {
+    ($server:expr, $session:expr, $id:expr, $direction:expr) => {{
+        let vars = json!({ "id": $id.to_string(), "direction": $direction });
+        let res = setup::graphql(MUTATION_VOTE_DIRECTION, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+/// Run the given mutation for the given submission,
+/// returning the GraphQL response.
+macro_rules! mutate {
+    ($server:expr, $session:expr, $query:expr, $id:expr) => {{
+        let vars = json!({ "id": $id.to_string() });
+        let res = setup::graphql($query, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_vote_is_idempotent() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let id = setup::submit(&ctx, admin.id()).await;
+
+    // concurrent duplicate votes count once
+    let votes = (0..5).map(|_| async { mutate!(&server, &session, MUTATION_VOTE, id) });
+    for body in join_all(votes).await {
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+    let body = mutate!(&server, &session, MUTATION_VOTE, id);
+    assert_eq!(
+        body,
+        json!({ "data": { "voteUrl": { "score": 1, "viewerHasVoted": true } } })
+    );
+
+    let body = mutate!(&server, &admin_session, MUTATION_VOTE, id);
+    assert_eq!(body["data"]["voteUrl"]["score"], 2);
+
+    // rescinding twice only counts once as well
+    for _ in 0..2 {
+        let body = mutate!(&server, &session, MUTATION_UNVOTE, id);
+        assert_eq!(
+            body,
+            json!({ "data": { "unvoteUrl": { "score": 1, "viewerHasVoted": false } } })
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_viewer_has_voted() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let voted = setup::submit(&ctx, admin.id()).await;
+    let other = setup::submit(&ctx, admin.id()).await;
+    mutate!(&server, &session, MUTATION_VOTE, voted);
+
+    let submissions =
+        |session| setup::graphql(QUERY_SUBMISSIONS, json!({}), session).reply(&server);
+    let body: Value = serde_json::from_slice(submissions(&session).await.body()).unwrap();
+    let mut edges: Vec<(String, i64, bool)> = body["data"]["submissions"]["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| {
+            let node = &edge["node"];
+            (
+                node["id"].as_str().unwrap().to_string(),
+                node["score"].as_i64().unwrap(),
+                node["viewerHasVoted"].as_bool().unwrap(),
+            )
+        })
+        .collect();
+    edges.sort();
+    let mut expected = vec![(voted.to_string(), 1, true), (other.to_string(), 0, false)];
+    expected.sort();
+    assert_eq!(edges, expected);
+
+    // anonymous viewers never voted
+    let body: Value = serde_json::from_slice(submissions("").await.body()).unwrap();
+    for edge in body["data"]["submissions"]["edges"].as_array().unwrap() {
+        assert_eq!(edge["node"]["viewerHasVoted"], false);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_self_votes() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let id = setup::submit(&ctx, user.id()).await;
+    let body = mutate!(&server, &session, MUTATION_VOTE, id);
+    assert_eq!(body["data"]["voteUrl"]["score"], 1);
+
+    let (server, ctx) = setup::mock_with_config(Config::test().with_allow_self_votes(false)).await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let id = setup::submit(&ctx, user.id()).await;
+    let body = mutate!(&server, &session, MUTATION_VOTE, id);
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "You can not vote on your own submission"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_vote_transitions() {
+    let (server, ctx) = setup::mock_with_config(Config::test().with_downvotes_enabled(true)).await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let id = setup::submit(&ctx, admin.id()).await;
+    vote!(&server, &admin_session, id, "UP");
+
+    let cases = [
+        (
+            "UP",
+            json!({ "score": 2, "upvotes": 2, "downvotes": 0, "viewerVote": "UP" }),
+        ),
+        (
+            "UP",
+            json!({ "score": 2, "upvotes": 2, "downvotes": 0, "viewerVote": "UP" }),
+        ),
+        (
+            "DOWN",
+            json!({ "score": 0, "upvotes": 1, "downvotes": 1, "viewerVote": "DOWN" }),
+        ),
+        (
+            "DOWN",
+            json!({ "score": 0, "upvotes": 1, "downvotes": 1, "viewerVote": "DOWN" }),
+        ),
+        (
+            "UP",
+            json!({ "score": 2, "upvotes": 2, "downvotes": 0, "viewerVote": "UP" }),
+        ),
+    ];
+    for (direction, expected) in cases {
+        let body = vote!(&server, &session, id, direction);
+        assert_eq!(body["data"]["voteUrl"], expected, "{}", direction);
+    }
+
+    vote!(&server, &session, id, "DOWN");
+    let body = mutate!(&server, &session, MUTATION_UNVOTE, id);
+    assert_eq!(
+        body["data"]["unvoteUrl"],
+        json!({ "score": 1, "viewerHasVoted": false })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_downvotes_disabled() {
+    let (server, ctx) = setup::mock().await;
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let id = setup::submit(&ctx, admin.id()).await;
+
+    let body = vote!(&server, &session, id, "DOWN");
+    assert!(body["data"].is_null());
+    assert_eq!(body["errors"][0]["message"], "Downvotes are not enabled");
+
+    let body = vote!(&server, &session, id, "UP");
+    assert_eq!(
+        body["data"]["voteUrl"],
+        json!({ "score": 1, "upvotes": 1, "downvotes": 0, "viewerVote": "UP" })
+    );
+}
@@ -0,0 +1,385 @@
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use serde_json::{json, Value};
+use server::db::models::WebhookDelivery;
+use server::{Config, Context};
+use sha2::Sha256;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use warp::http::{HeaderMap, StatusCode};
+use warp::hyper::body::Bytes;
+use warp::Filter;
+mod setup;
+
+const SECRET: &str = "0123456789abcdef";
+
+const MUTATION_CREATE: &str = "
+    mutation CreateWebhook($url: String!, $events: [WebhookEvent!]!, $secret: String!, $siteWide: Boolean) {
+        createWebhook(url: $url, events: $events, secret: $secret, siteWide: $siteWide) {
+            id
+            events
+            siteWide
+        }
+    }
+";
+
+const MUTATION_DELETE: &str = "
+    mutation DeleteWebhook($id: ID!) {
+        deleteWebhook(id: $id) { ok }
+    }
+";
+
+const MUTATION_SUBMIT: &str = "
+    mutation SubmitUrl($input: NewUrlInput!) {
+        submitUrl(input: $input) {
+            url { id }
+        }
+    }
+";
+
+const MUTATION_ADD_COMMENT: &str = "
+    mutation AddComment($url: ID!, $body: String!) {
+        addComment(urlId: $url, body: $body) { id }
+    }
+";
+
+const QUERY_DELIVERIES: &str = "
+    query Deliveries {
+        viewer {
+            webhooks {
+                url
+                deliveries {
+                    event
+                    status
+                    attempts
+                    responseStatus
+                    lastError
+                    nextAttemptAt
+                    deliveredAt
+                }
+            }
+        }
+    }
+";
+
+/// A request received by the local webhook receiver.
+struct Received {
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// A local webhook receiver, which responds with the queued
+/// statuses in order, and with 200 once they ran out.
+#[derive(Clone, Default)]
+struct Receiver {
+    received: Arc<Mutex<Vec<Received>>>,
+    statuses: Arc<Mutex<VecDeque<StatusCode>>>,
+}
+
+impl Receiver {
+    /// Serve the receiver on an ephemeral local port, returning its
+    /// URL. `GET` requests are answered with a small html page, such
+    /// that the URL can be submitted as well.
+    fn serve(&self, statuses: &[StatusCode]) -> String {
+        self.statuses.lock().unwrap().extend(statuses);
+        let receiver = self.clone();
+        let hook = warp::post()
+            .and(warp::header::headers_cloned())
+            .and(warp::body::bytes())
+            .map(move |headers, body| {
+                receiver
+                    .received
+                    .lock()
+                    .unwrap()
+                    .push(Received { headers, body });
+                let status = receiver
+                    .statuses
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+                    .unwrap_or(StatusCode::OK);
+                warp::reply::with_status("", status)
+            });
+        let page = warp::get().map(|| warp::reply::html("<html><head></head></html>"));
+        let addr = setup::serve(hook.or(page));
+        format!("http://{}/hook", addr)
+    }
+
+    fn count(&self) -> usize {
+        self.received.lock().unwrap().len()
+    }
+}
+
+/// Run the given mutation, returning the
+/// message of the error it failed with.
+macro_rules! error {
+    ($server:expr, $session:expr, $query:expr, $vars:expr) => {{
+        let body = setup::execute($server, $query, $vars, $session).await;
+        assert!(body["data"].is_null(), "{}", body);
+        body["errors"][0]["message"].as_str().unwrap().to_string()
+    }};
+}
+
+/// Attempt the deliveries which are due the given
+/// number of minutes from now.
+async fn deliver(ctx: &Context, minutes: i64) {
+    let mut ctx = ctx.clone();
+    ctx.set_request_time(Utc::now() + Duration::minutes(minutes));
+    WebhookDelivery::deliver_pending(&ctx).await.unwrap();
+}
+
+fn sign(body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+    mac.update(body);
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", digest)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_webhook_delivery() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let receiver = Receiver::default();
+    let hook_url = receiver.serve(&[]);
+    let vars = json!({
+        "url": hook_url,
+        "events": ["URL_SUBMITTED", "COMMENT_ADDED", "URL_SUBMITTED"],
+        "secret": SECRET,
+    });
+    let data = setup::execute_ok(&server, MUTATION_CREATE, vars, &session).await;
+    assert_eq!(
+        data["createWebhook"]["events"],
+        json!(["URL_SUBMITTED", "COMMENT_ADDED"])
+    );
+    assert_eq!(data["createWebhook"]["siteWide"], false);
+
+    let page = Receiver::default().serve(&[]);
+    let vars = json!({ "input": { "url": page } });
+    let data = setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+    let url_id = data["submitUrl"]["url"]["id"].clone();
+    assert_eq!(receiver.count(), 0);
+
+    deliver(&ctx, 0).await;
+    assert_eq!(receiver.count(), 1);
+    {
+        let received = receiver.received.lock().unwrap();
+        let request = &received[0];
+        assert_eq!(request.headers["Content-Type"], "application/json");
+        assert_eq!(request.headers["X-Urls-Event"], "url_submitted");
+        assert_eq!(
+            request.headers["X-Urls-Signature"].to_str().unwrap(),
+            sign(&request.body)
+        );
+        let payload: Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(payload["event"], "url_submitted");
+        assert_eq!(payload["url"]["id"], url_id);
+        assert_eq!(payload["url"]["submitted_by"], "test-user");
+    }
+
+    // comments by others on submissions of the owner are delivered
+    let vars = json!({ "url": url_id, "body": "Nice find!" });
+    setup::execute_ok(&server, MUTATION_ADD_COMMENT, vars, &admin_session).await;
+    deliver(&ctx, 0).await;
+    assert_eq!(receiver.count(), 2);
+    {
+        let received = receiver.received.lock().unwrap();
+        let payload: Value = serde_json::from_slice(&received[1].body).unwrap();
+        assert_eq!(payload["event"], "comment_added");
+        assert_eq!(payload["comment"]["comment"], "Nice find!");
+        assert_eq!(payload["comment"]["created_by"], "test-administrator");
+        assert_eq!(payload["url"]["id"], url_id);
+    }
+
+    // deliveries are only sent once
+    deliver(&ctx, 60).await;
+    assert_eq!(receiver.count(), 2);
+
+    let data = setup::execute_ok(&server, QUERY_DELIVERIES, json!({}), &session).await;
+    let deliveries = &data["viewer"]["webhooks"][0]["deliveries"];
+    assert_eq!(deliveries[0]["event"], "COMMENT_ADDED");
+    assert_eq!(deliveries[1]["event"], "URL_SUBMITTED");
+    assert_eq!(deliveries[1]["status"], "DELIVERED");
+    assert_eq!(deliveries[1]["responseStatus"], 200);
+    assert!(deliveries[1]["deliveredAt"].is_string());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_webhook_scope() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+
+    let user_receiver = Receiver::default();
+    let vars = json!({
+        "url": user_receiver.serve(&[]),
+        "events": ["URL_SUBMITTED"],
+        "secret": SECRET,
+    });
+    setup::execute_ok(&server, MUTATION_CREATE, vars, &session).await;
+
+    // only administrators may create site wide webhooks
+    let site_receiver = Receiver::default();
+    let vars = json!({
+        "url": site_receiver.serve(&[]),
+        "events": ["URL_SUBMITTED"],
+        "secret": SECRET,
+        "siteWide": true,
+    });
+    let message = error!(&server, &session, MUTATION_CREATE, vars.clone());
+    assert_eq!(message, "Not authorized");
+    let data = setup::execute_ok(&server, MUTATION_CREATE, vars, &admin_session).await;
+    assert_eq!(data["createWebhook"]["siteWide"], true);
+    let site_webhook = data["createWebhook"]["id"].clone();
+
+    // submissions of others only reach site wide webhooks
+    let page = Receiver::default().serve(&[]);
+    let vars = json!({ "input": { "url": page } });
+    setup::execute_ok(&server, MUTATION_SUBMIT, vars, &admin_session).await;
+    deliver(&ctx, 0).await;
+    assert_eq!(user_receiver.count(), 0);
+    assert_eq!(site_receiver.count(), 1);
+
+    // webhooks can only be deleted by their owner
+    let vars = json!({ "id": site_webhook });
+    let message = error!(&server, &session, MUTATION_DELETE, vars.clone());
+    assert_eq!(message, "Not authorized");
+    setup::execute_ok(&server, MUTATION_DELETE, vars, &admin_session).await;
+    let data = setup::execute_ok(&server, QUERY_DELIVERIES, json!({}), &admin_session).await;
+    assert_eq!(data["viewer"]["webhooks"], json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_webhook_retries() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let receiver = Receiver::default();
+    let statuses = [
+        StatusCode::INTERNAL_SERVER_ERROR,
+        StatusCode::NOT_FOUND,
+        StatusCode::FOUND,
+    ];
+    let vars = json!({
+        "url": receiver.serve(&statuses),
+        "events": ["URL_SUBMITTED"],
+        "secret": SECRET,
+    });
+    setup::execute_ok(&server, MUTATION_CREATE, vars, &session).await;
+    let page = Receiver::default().serve(&[]);
+    let vars = json!({ "input": { "url": page } });
+    setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+
+    deliver(&ctx, 0).await;
+    assert_eq!(receiver.count(), 1);
+    let data = setup::execute_ok(&server, QUERY_DELIVERIES, json!({}), &session).await;
+    let delivery = &data["viewer"]["webhooks"][0]["deliveries"][0];
+    assert_eq!(delivery["status"], "PENDING");
+    assert_eq!(delivery["attempts"], 1);
+    assert_eq!(delivery["responseStatus"], 500);
+    assert!(delivery["lastError"].as_str().unwrap().contains("500"));
+    assert!(delivery["nextAttemptAt"].is_string());
+
+    // retries back off exponentially
+    deliver(&ctx, 0).await;
+    assert_eq!(receiver.count(), 1);
+    deliver(&ctx, 1).await;
+    assert_eq!(receiver.count(), 2);
+    deliver(&ctx, 2).await;
+    assert_eq!(receiver.count(), 2);
+    deliver(&ctx, 3).await;
+    // redirects are not followed, and count as failures
+    assert_eq!(receiver.count(), 3);
+    deliver(&ctx, 7).await;
+    assert_eq!(receiver.count(), 4);
+
+    let data = setup::execute_ok(&server, QUERY_DELIVERIES, json!({}), &session).await;
+    let delivery = &data["viewer"]["webhooks"][0]["deliveries"][0];
+    assert_eq!(delivery["status"], "DELIVERED");
+    assert_eq!(delivery["attempts"], 3);
+    assert_eq!(delivery["lastError"], Value::Null);
+    assert_eq!(delivery["nextAttemptAt"], Value::Null);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_webhook_gives_up() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let receiver = Receiver::default();
+    let vars = json!({
+        "url": receiver.serve(&[StatusCode::SERVICE_UNAVAILABLE; 10]),
+        "events": ["URL_SUBMITTED"],
+        "secret": SECRET,
+    });
+    setup::execute_ok(&server, MUTATION_CREATE, vars, &session).await;
+    let page = Receiver::default().serve(&[]);
+    let vars = json!({ "input": { "url": page } });
+    setup::execute_ok(&server, MUTATION_SUBMIT, vars, &session).await;
+
+    for minutes in [0, 1, 3, 7, 15, 31, 63, 127] {
+        deliver(&ctx, minutes).await;
+    }
+    assert_eq!(receiver.count(), 6);
+    let data = setup::execute_ok(&server, QUERY_DELIVERIES, json!({}), &session).await;
+    let delivery = &data["viewer"]["webhooks"][0]["deliveries"][0];
+    assert_eq!(delivery["status"], "FAILED");
+    assert_eq!(delivery["attempts"], 6);
+    assert_eq!(delivery["nextAttemptAt"], Value::Null);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_webhook_validation() {
+    let config = Config::test().with_fetch_private_addresses(false);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let create = |url: &str, events: Value, secret: &str| json!({ "url": url, "events": events, "secret": secret });
+    let cases = [
+        (
+            create(
+                "http://127.0.0.1:8080/hook",
+                json!(["URL_SUBMITTED"]),
+                SECRET,
+            ),
+            "Refusing to fetch private address",
+        ),
+        (
+            create("http://169.254.169.254/", json!(["URL_SUBMITTED"]), SECRET),
+            "Refusing to fetch private address",
+        ),
+        (
+            create("http://[::1]/hook", json!(["URL_SUBMITTED"]), SECRET),
+            "Refusing to fetch private address",
+        ),
+        (
+            create("file:///etc/passwd", json!(["URL_SUBMITTED"]), SECRET),
+            "Refusing to fetch file URL",
+        ),
+        (
+            create("https://93.184.216.34/hook", json!([]), SECRET),
+            "at least one event",
+        ),
+        (
+            create(
+                "https://93.184.216.34/hook",
+                json!(["URL_SUBMITTED"]),
+                "short",
+            ),
+            "secrets must be between",
+        ),
+    ];
+    for (vars, expected) in cases {
+        let message = error!(&server, &session, MUTATION_CREATE, vars);
+        assert!(message.contains(expected), "{}", message);
+    }
+
+    let data = setup::execute_ok(&server, QUERY_DELIVERIES, json!({}), &session).await;
+    assert_eq!(data["viewer"]["webhooks"], json!([]));
+}
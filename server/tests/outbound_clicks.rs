@@ -0,0 +1,208 @@
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::{Url, User};
+use server::Config;
+use warp::http::StatusCode;
+mod setup;
+
+const BROWSER: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:93.0) Gecko/20100101 Firefox/93.0";
+
+const QUERY_CLICKS: &str = "
+    query Clicks($id: ID!) {
+        fetchUrl(id: $id) { clicks }
+    }
+";
+
+/// Follow the outbound link of the given submission
+/// with the given user agent.
+macro_rules! click {
+    ($server:expr, $id:expr, $user_agent:expr) => {{
+        warp::test::request()
+            .path(&format!("/out/{}", $id))
+            .header("User-Agent", $user_agent)
+            .reply($server)
+            .await
+    }};
+}
+
+/// The click count of the given submission as
+/// seen by the given session.
+macro_rules! clicks {
+    ($server:expr, $session:expr, $id:expr) => {{
+        let vars = json!({ "id": $id.to_string() });
+        let res = setup::graphql(QUERY_CLICKS, vars, $session)
+            .reply($server)
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body["errors"].is_null(), "{}", body);
+        body["data"]["fetchUrl"]["clicks"].as_i64().unwrap()
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_click_counting() {
+    let (server, ctx) = setup::mock().await;
+    let admin = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let author = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let id = setup::Submission::by(author.id())
+        .url("https://example.com/article")
+        .insert(&ctx)
+        .await;
+
+    let res = click!(&server, id, BROWSER);
+    assert_eq!(res.status(), StatusCode::FOUND);
+    assert_eq!(res.headers()["Location"], "https://example.com/article");
+    assert_eq!(res.headers()["Referrer-Policy"], "no-referrer");
+    assert_eq!(res.headers()["Cache-Control"], "no-store");
+    for _ in 0..2 {
+        assert_eq!(click!(&server, id, BROWSER).status(), StatusCode::FOUND);
+    }
+
+    // clicks are only visible once flushed
+    assert_eq!(clicks!(&server, &admin, id), 0);
+    assert_eq!(Url::flush_clicks(&ctx).await.unwrap(), 1);
+    assert_eq!(clicks!(&server, &admin, id), 3);
+
+    // others see rounded counts
+    assert_eq!(clicks!(&server, &user, id), 0);
+    for _ in 0..10 {
+        click!(&server, id, BROWSER);
+    }
+    Url::flush_clicks(&ctx).await.unwrap();
+    assert_eq!(clicks!(&server, &admin, id), 13);
+    assert_eq!(clicks!(&server, &user, id), 10);
+    assert_eq!(clicks!(&server, "", id), 10);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_click_batching() {
+    let (server, ctx) = setup::mock().await;
+    let author = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let first = setup::Submission::by(author.id())
+        .url("https://example.com/first")
+        .insert(&ctx)
+        .await;
+    let second = setup::Submission::by(author.id())
+        .url("https://example.com/second")
+        .insert(&ctx)
+        .await;
+
+    for _ in 0..25 {
+        click!(&server, first, BROWSER);
+    }
+    click!(&server, second, BROWSER);
+    assert_eq!(ctx.click_counter().pending(first), 25);
+    assert_eq!(ctx.click_counter().pending(second), 1);
+    assert_eq!(Url::find(&ctx, first).await.unwrap().clicks(), 0);
+
+    // every submission is updated once per flush
+    assert_eq!(Url::flush_clicks(&ctx).await.unwrap(), 2);
+    assert_eq!(ctx.click_counter().pending(first), 0);
+    assert_eq!(Url::find(&ctx, first).await.unwrap().clicks(), 25);
+    assert_eq!(Url::find(&ctx, second).await.unwrap().clicks(), 1);
+
+    // flushing again adds nothing
+    assert_eq!(Url::flush_clicks(&ctx).await.unwrap(), 0);
+    click!(&server, first, BROWSER);
+    assert_eq!(Url::flush_clicks(&ctx).await.unwrap(), 1);
+    assert_eq!(Url::find(&ctx, first).await.unwrap().clicks(), 26);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_click_scheme_allowlist() {
+    let (server, ctx) = setup::mock().await;
+    let author = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    for link in [
+        "javascript:alert(1)",
+        "data:text/html,hi",
+        "ftp://example.com/",
+    ] {
+        let id = setup::Submission::by(author.id())
+            .url(link)
+            .insert(&ctx)
+            .await;
+        let res = click!(&server, id, BROWSER);
+        assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", link);
+        assert!(res.headers().get("Location").is_none());
+        assert_eq!(ctx.click_counter().pending(id), 0);
+    }
+
+    let res = click!(&server, UrlID::new(), BROWSER);
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_click_private_hosts() {
+    let config = Config::test().with_allow_private_urls(false);
+    let (server, ctx) = setup::mock_with_config(config).await;
+    let author = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    // legacy submissions which would not be accepted today
+    for link in [
+        "http://localhost/admin",
+        "http://169.254.169.254/latest/meta-data/",
+        "https://google.com@evil.example/",
+    ] {
+        let id = setup::Submission::by(author.id())
+            .url(link)
+            .insert(&ctx)
+            .await;
+        let res = click!(&server, id, BROWSER);
+        assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", link);
+        assert!(res.headers().get("Location").is_none());
+    }
+
+    let id = setup::Submission::by(author.id())
+        .url("https://example.com/")
+        .insert(&ctx)
+        .await;
+    let res = click!(&server, id, BROWSER);
+    assert_eq!(res.status(), StatusCode::FOUND);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_click_bot_exclusion() {
+    let (server, ctx) = setup::mock().await;
+    let author = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let id = setup::Submission::by(author.id())
+        .url("https://example.com/article")
+        .insert(&ctx)
+        .await;
+
+    for user_agent in [
+        "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+        "facebookexternalhit/1.1",
+        "curl/7.79.1",
+    ] {
+        let res = click!(&server, id, user_agent);
+        assert_eq!(res.status(), StatusCode::FOUND, "{}", user_agent);
+    }
+    let res = warp::test::request()
+        .path(&format!("/out/{}", id))
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), StatusCode::FOUND);
+    let res = warp::test::request()
+        .path(&format!("/out/{}", id))
+        .header("User-Agent", BROWSER)
+        .header("Sec-Purpose", "prefetch")
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), StatusCode::FOUND);
+    assert_eq!(ctx.click_counter().pending(id), 0);
+
+    click!(&server, id, BROWSER);
+    assert_eq!(ctx.click_counter().pending(id), 1);
+}
@@ -0,0 +1,219 @@
+use serde_json::{json, Value};
+use server::db::id::UrlID;
+use server::db::models::User;
+use server::schema::urls;
+mod setup;
+
+const QUERY_FEED_URLS: &str = "
+    query FeedUrls {
+        viewer {
+            feedUrls { frontPage saved }
+        }
+    }
+";
+
+const MUTATION_REGENERATE: &str = "
+    mutation RegenerateFeedToken {
+        regenerateFeedToken {
+            feedUrls { frontPage saved }
+        }
+    }
+";
+
+const MUTATION_SAVE: &str = "
+    mutation SaveUrl($id: ID!) {
+        saveUrl(id: $id) { id }
+    }
+";
+
+const MUTATION_BLOCK: &str = "
+    mutation BlockUser($id: ID!) {
+        blockUser(userId: $id) { ok }
+    }
+";
+
+/// Extract the paths of the feed urls from a GraphQL response,
+/// such that they can be requested from the test server.
+fn feed_paths(body: &Value, field: &str) -> (String, String) {
+    let urls = &body["data"][field]["feedUrls"];
+    let path = |url: &Value| {
+        let url = url.as_str().unwrap();
+        url.strip_prefix("https://localhost").unwrap().to_string()
+    };
+    (path(&urls["frontPage"]), path(&urls["saved"]))
+}
+
+/// Request the feed at the given path, returning the
+/// status and the titles of the entries.
+macro_rules! feed {
+    ($server:expr, $path:expr) => {{
+        let res = warp::test::request().path($path).reply($server).await;
+        let titles: Vec<String> = match res.status().as_u16() {
+            200 => feed_rs::parser::parse(res.body().as_ref())
+                .unwrap()
+                .entries
+                .into_iter()
+                .map(|entry| entry.title.unwrap().content)
+                .collect(),
+            _ => vec![],
+        };
+        (res.status().as_u16(), titles)
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_private_feed_auth() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    let res = setup::graphql(QUERY_FEED_URLS, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let (front, saved) = feed_paths(&body, "viewer");
+    assert!(front.starts_with(&format!("/u/{}/feed.atom?token=", user.username())));
+    assert!(saved.starts_with(&format!("/u/{}/saved.atom?token=", user.username())));
+    assert_eq!(feed!(&server, &front).0, 200);
+    assert_eq!(feed!(&server, &saved).0, 200);
+
+    // the token is not part of the feed itself
+    let res = warp::test::request().path(&front).reply(&server).await;
+    let token = front.split_once("token=").unwrap().1;
+    assert!(!String::from_utf8_lossy(res.body()).contains(token));
+
+    // missing, tampered, and other users tokens are rejected
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let res = setup::graphql(QUERY_FEED_URLS, json!({}), &admin_session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let (admin_front, _) = feed_paths(&body, "viewer");
+    let admin_token = admin_front.split_once("token=").unwrap().1;
+    let paths = [
+        format!("/u/{}/feed.atom", user.username()),
+        format!("/u/{}/feed.atom?token=", user.username()),
+        format!("{}x", front),
+        format!("/u/{}/saved.atom?token={}", user.username(), admin_token),
+        format!("/u/unknown/saved.atom?token={}", token),
+    ];
+    for path in paths {
+        assert_eq!(feed!(&server, &path).0, 403, "{}", path);
+    }
+
+    // anonymous users have no feeds
+    let res = setup::graphql(QUERY_FEED_URLS, json!({}), "")
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["data"]["viewer"]["feedUrls"], Value::Null);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_private_feed_rotation() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+
+    let res = setup::graphql(QUERY_FEED_URLS, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let (old_front, old_saved) = feed_paths(&body, "viewer");
+
+    let res = setup::graphql(MUTATION_REGENERATE, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let (front, saved) = feed_paths(&body, "regenerateFeedToken");
+    assert_ne!(front, old_front);
+    assert_ne!(saved, old_saved);
+
+    assert_eq!(feed!(&server, &old_front).0, 403);
+    assert_eq!(feed!(&server, &old_saved).0, 403);
+    assert_eq!(feed!(&server, &front).0, 200);
+    assert_eq!(feed!(&server, &saved).0, 200);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_private_feed_content() {
+    let (server, ctx) = setup::mock().await;
+    let session = setup::session_token(&ctx, "test.user@urls.fyi").await;
+    let admin_session = setup::session_token(&ctx, "test.admin@urls.fyi").await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+    let admin = User::find_by_email(&ctx, "test.admin@urls.fyi")
+        .await
+        .unwrap();
+    let own = setup::Submission::by(user.id())
+        .title("Own submission")
+        .insert(&ctx)
+        .await;
+    let other = setup::Submission::by(admin.id())
+        .title("Admin submission")
+        .insert(&ctx)
+        .await;
+
+    // both users save something, and the user blocks the admin
+    let save = |session: String, id: UrlID| {
+        let vars = json!({ "id": id.to_string() });
+        async move {
+            let res = setup::graphql(MUTATION_SAVE, vars, &session)
+                .reply(&server)
+                .await;
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+            assert!(body["errors"].is_null(), "{}", body);
+        }
+    };
+    save(session.clone(), own).await;
+    save(admin_session.clone(), other).await;
+    let vars = json!({ "id": admin.id().to_string() });
+    let res = setup::graphql(MUTATION_BLOCK, vars, &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    assert!(body["errors"].is_null(), "{}", body);
+
+    let res = setup::graphql(QUERY_FEED_URLS, json!({}), &session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let (front, saved) = feed_paths(&body, "viewer");
+
+    // only the saves of the user are listed
+    assert_eq!(feed!(&server, &saved), (200, vec!["Own submission".into()]));
+
+    // the front page is the one the user sees
+    assert_eq!(feed!(&server, &front), (200, vec!["Own submission".into()]));
+    assert_eq!(feed!(&server, "/feed.atom").1.len(), 2);
+    let res = setup::graphql(QUERY_FEED_URLS, json!({}), &admin_session)
+        .reply(&server)
+        .await;
+    let body: Value = serde_json::from_slice(res.body()).unwrap();
+    let (admin_front, admin_saved) = feed_paths(&body, "viewer");
+    assert_eq!(feed!(&server, &admin_front).1.len(), 2);
+    assert_eq!(
+        feed!(&server, &admin_saved),
+        (200, vec!["Admin submission".into()])
+    );
+
+    // a session cookie sent along doesn't change whose feed it is
+    let res = warp::test::request()
+        .path(&saved)
+        .header("Cookie", format!("session={}", admin_session))
+        .reply(&server)
+        .await;
+    let feed = feed_rs::parser::parse(res.body().as_ref()).unwrap();
+    assert_eq!(feed.entries.len(), 1);
+    assert_eq!(
+        feed.entries[0].title.as_ref().unwrap().content,
+        "Own submission"
+    );
+    assert!(res
+        .headers()
+        .get_all("Set-Cookie")
+        .iter()
+        .all(|cookie| !cookie.to_str().unwrap().starts_with("session=")));
+}
@@ -1,8 +1,14 @@
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
 use serde_json::{json, Value};
+use server::db::id::{CommentID, UrlID, UserID};
+use server::schema::{comments, tags, url_tags, urls};
 use server::*;
 use std::convert::Infallible;
 use std::env;
-use warp::{test::RequestBuilder, Filter, Reply};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use warp::{test::RequestBuilder, Filter, Rejection, Reply};
 
 fn set_work_dir() {
     let mut dir = env::current_dir().unwrap();
@@ -15,7 +21,7 @@ fn set_work_dir() {
 async fn generate_mock_users(ctx: &Context) {
     use db::models::{NewUserInput, Permission, Role, User};
 
-    let admin = User::create(
+    let mut admin = User::create(
         ctx,
         NewUserInput {
             name: "Test Administrator".into(),
@@ -24,11 +30,12 @@ async fn generate_mock_users(ctx: &Context) {
     )
     .await
     .unwrap();
+    admin.mark_email_verified(ctx).await.unwrap();
     Role::create(ctx, admin.id(), Permission::Administrator)
         .await
         .unwrap();
 
-    User::create(
+    let mut user = User::create(
         ctx,
         NewUserInput {
             name: "Test User".into(),
@@ -37,6 +44,7 @@ async fn generate_mock_users(ctx: &Context) {
     )
     .await
     .unwrap();
+    user.mark_email_verified(ctx).await.unwrap();
 }
 
 /// Setup an isolated test environment with mock
@@ -45,10 +53,22 @@ async fn generate_mock_users(ctx: &Context) {
 pub async fn mock() -> (
     impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone,
     Context,
+) {
+    mock_with_config(Config::test()).await
+}
+
+/// Setup an isolated test environment with mock
+/// data, using a customized test configuration.
+#[allow(dead_code)]
+pub async fn mock_with_config(
+    conf: Config,
+) -> (
+    impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone,
+    Context,
 ) {
     set_work_dir();
 
-    let test_conf = Config::test();
+    let test_conf = Arc::new(conf);
     let pool = db::connect(&test_conf)
         .await
         .expect("Failed to connect to test database");
@@ -56,7 +76,7 @@ pub async fn mock() -> (
         .await
         .expect("Failed to connect to test mailer");
 
-    let ctx = Context::for_server(&pool, &mailer);
+    let ctx = Context::for_server(&test_conf, &pool, &mailer);
     generate_mock_users(&ctx).await;
 
     (global_routes(&test_conf, pool, mailer.clone()), ctx)
@@ -78,6 +98,31 @@ pub fn graphql(query: &str, variables: Value, session: &str) -> RequestBuilder {
         .body(body.to_string())
 }
 
+/// Run a GraphQL request against the given server,
+/// returning the body of the response.
+#[allow(dead_code)]
+pub async fn execute<F>(server: &F, query: &str, variables: Value, session: &str) -> Value
+where
+    F: Filter + 'static,
+    F::Extract: Reply + Send,
+{
+    let res = graphql(query, variables, session).reply(server).await;
+    serde_json::from_slice(res.body()).unwrap()
+}
+
+/// Run a GraphQL request against the given server, asserting
+/// that it succeeds, and return the data of the response.
+#[allow(dead_code)]
+pub async fn execute_ok<F>(server: &F, query: &str, variables: Value, session: &str) -> Value
+where
+    F: Filter + 'static,
+    F::Extract: Reply + Send,
+{
+    let body = execute(server, query, variables, session).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    body["data"].clone()
+}
+
 /// Return the last sent email message.
 #[allow(dead_code)]
 pub async fn last_email(ctx: &Context) -> String {
@@ -98,3 +143,322 @@ pub async fn session_token(ctx: &Context, email: &str) -> String {
     let email_token = login.email_token().to_string();
     login.claim(ctx, &email_token).await.unwrap()
 }
+
+/// Wait for an email containing the given text to be sent. Some
+/// emails are sent in the background, so this polls for a short
+/// while before giving up and returning `false`.
+#[allow(dead_code)]
+pub async fn wait_for_email(ctx: &Context, contains: &str) -> bool {
+    for _ in 0..20 {
+        let path = match ctx.mailer().clone() {
+            email::Mailer::File { last_message, .. } => last_message.lock().await.clone(),
+            _ => panic!("No file mailer"),
+        };
+        if let Some(path) = path {
+            if tokio::fs::read_to_string(path)
+                .await
+                .unwrap()
+                .contains(contains)
+            {
+                return true;
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    false
+}
+
+/// A submission which is inserted directly into the database, without
+/// validating it or fetching the linked page. Unless configured
+/// otherwise, it links to a unique page on `example.com` and is
+/// created at the time of the mock context.
+#[allow(dead_code)]
+pub struct Submission<'a> {
+    created_by: UserID,
+    url: Option<String>,
+    query: Option<&'a str>,
+    title: Option<&'a str>,
+    description: Option<&'a str>,
+    created_at: Option<DateTime<Utc>>,
+    domain: Option<&'a str>,
+    score: i64,
+    upvotes: i64,
+    nsfw: bool,
+    language: Option<&'a str>,
+    tags: &'a [&'a str],
+    indexed: bool,
+}
+
+#[allow(dead_code)]
+impl<'a> Submission<'a> {
+    /// A submission by the given user.
+    pub fn by(created_by: UserID) -> Self {
+        Submission {
+            created_by,
+            url: None,
+            query: None,
+            title: None,
+            description: None,
+            created_at: None,
+            domain: None,
+            score: 0,
+            upvotes: 0,
+            nsfw: false,
+            language: None,
+            tags: &[],
+            indexed: false,
+        }
+    }
+
+    /// Link to the given url, which is also used as its canonical url.
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    /// Append the given query string to the linked url,
+    /// but not to its canonical url.
+    pub fn query(mut self, query: &'a str) -> Self {
+        self.query = Some(query);
+        self
+    }
+
+    pub fn title(mut self, title: &'a str) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    pub fn description(mut self, description: &'a str) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    /// Create (and publish) the submission at the given time.
+    pub fn created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    /// Store the given domain. Unless a url is given, the submission
+    /// links to a unique page on this domain. Without a domain, it is
+    /// left for [`Url::backfill_domains`](db::models::Url::backfill_domains).
+    pub fn domain(mut self, domain: &'a str) -> Self {
+        self.domain = Some(domain);
+        self
+    }
+
+    pub fn score(mut self, score: i64) -> Self {
+        self.score = score;
+        self
+    }
+
+    pub fn upvotes(mut self, upvotes: i64) -> Self {
+        self.upvotes = upvotes;
+        self
+    }
+
+    pub fn nsfw(mut self, nsfw: bool) -> Self {
+        self.nsfw = nsfw;
+        self
+    }
+
+    pub fn language(mut self, language: Option<&'a str>) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Tag the submission, creating the tags if needed.
+    pub fn tags(mut self, tags: &'a [&'a str]) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Add the submission to the search index.
+    pub fn indexed(mut self) -> Self {
+        self.indexed = true;
+        self
+    }
+
+    /// Insert the submission, and return its ID.
+    pub async fn insert(self, ctx: &Context) -> UrlID {
+        let id = UrlID::new();
+        let domain = self.domain;
+        let url = self
+            .url
+            .unwrap_or_else(|| format!("https://{}/{}", domain.unwrap_or("example.com"), id));
+        let created_at = self.created_at.unwrap_or_else(|| ctx.now()).naive_utc();
+        let conn = ctx.conn().await.unwrap();
+        diesel::insert_into(urls::table)
+            .values((
+                urls::dsl::id.eq(id),
+                urls::dsl::created_at.eq(created_at),
+                urls::dsl::published_at.eq(created_at),
+                urls::dsl::updated_at.eq(created_at),
+                urls::dsl::url.eq(match self.query {
+                    Some(query) => format!("{}?{}", url, query),
+                    None => url.clone(),
+                }),
+                urls::dsl::canonical_url.eq(&url),
+                urls::dsl::status_code.eq(200),
+                urls::dsl::title.eq(self.title),
+                urls::dsl::description.eq(self.description),
+                urls::dsl::created_by.eq(self.created_by),
+                urls::dsl::domain.eq(domain.unwrap_or_default()),
+                urls::dsl::score.eq(self.score),
+                urls::dsl::upvotes.eq(self.upvotes),
+                urls::dsl::nsfw.eq(self.nsfw),
+                urls::dsl::language.eq(self.language),
+            ))
+            .execute(&*conn)
+            .unwrap();
+        for tag in self.tags {
+            diesel::insert_or_ignore_into(tags::table)
+                .values((
+                    tags::dsl::name.eq(*tag),
+                    tags::dsl::created_at.eq(ctx.now().naive_utc()),
+                ))
+                .execute(&*conn)
+                .unwrap();
+            diesel::insert_into(url_tags::table)
+                .values((
+                    url_tags::dsl::url_id.eq(id),
+                    url_tags::dsl::tag_name.eq(*tag),
+                ))
+                .execute(&*conn)
+                .unwrap();
+        }
+        drop(conn);
+
+        if self.indexed {
+            let url = db::models::Url::find(ctx, id).await.unwrap();
+            ctx.search().index_url(&url).unwrap();
+        }
+        id
+    }
+}
+
+/// Insert a submission by the given user, see [`Submission`]
+/// to customize it.
+#[allow(dead_code)]
+pub async fn submit(ctx: &Context, created_by: UserID) -> UrlID {
+    Submission::by(created_by).insert(ctx).await
+}
+
+/// A comment which is inserted directly into the database, without
+/// rendering it or updating any counts. Unless configured otherwise,
+/// it is a top level comment reading "A comment", created at the time
+/// of the mock context.
+#[allow(dead_code)]
+pub struct Comment<'a> {
+    url_id: UrlID,
+    created_by: UserID,
+    text: &'a str,
+    replies_to: Option<CommentID>,
+    created_at: Option<DateTime<Utc>>,
+    score: i64,
+    deleted: bool,
+}
+
+#[allow(dead_code)]
+impl<'a> Comment<'a> {
+    /// A comment by the given user on the given submission.
+    pub fn on(url_id: UrlID, created_by: UserID) -> Self {
+        Comment {
+            url_id,
+            created_by,
+            text: "A comment",
+            replies_to: None,
+            created_at: None,
+            score: 0,
+            deleted: false,
+        }
+    }
+
+    pub fn text(mut self, text: &'a str) -> Self {
+        self.text = text;
+        self
+    }
+
+    /// Reply to the given comment, which must be on the same submission.
+    pub fn replies_to(mut self, parent: CommentID) -> Self {
+        self.replies_to = Some(parent);
+        self
+    }
+
+    pub fn created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    pub fn score(mut self, score: i64) -> Self {
+        self.score = score;
+        self
+    }
+
+    /// Delete the comment at the time it was created.
+    pub fn deleted(mut self) -> Self {
+        self.deleted = true;
+        self
+    }
+
+    /// Insert the comment, and return its ID.
+    pub async fn insert(self, ctx: &Context) -> CommentID {
+        let id = CommentID::new();
+        let created_at = self.created_at.unwrap_or_else(|| ctx.now()).naive_utc();
+        let conn = ctx.conn().await.unwrap();
+        let depth = match self.replies_to {
+            Some(parent) => {
+                let depth: i32 = comments::table
+                    .find(parent)
+                    .select(comments::dsl::depth)
+                    .get_result(&*conn)
+                    .unwrap();
+                depth + 1
+            }
+            None => 0,
+        };
+        diesel::insert_into(comments::table)
+            .values((
+                comments::dsl::id.eq(id),
+                comments::dsl::created_at.eq(created_at),
+                comments::dsl::updated_at.eq(created_at),
+                comments::dsl::comment.eq(self.text),
+                comments::dsl::url_id.eq(self.url_id),
+                comments::dsl::created_by.eq(self.created_by),
+                comments::dsl::replies_to.eq(self.replies_to),
+                comments::dsl::depth.eq(depth),
+                comments::dsl::score.eq(self.score),
+                comments::dsl::deleted_at.eq(Some(created_at).filter(|_| self.deleted)),
+            ))
+            .execute(&*conn)
+            .unwrap();
+        id
+    }
+}
+
+/// Serve the given routes on an ephemeral local port in
+/// the background, returning the address they are served on.
+#[allow(dead_code)]
+pub fn serve<F>(routes: F) -> SocketAddr
+where
+    F: Filter<Error = Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+{
+    let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+/// Serve the given html on every path of an ephemeral
+/// local port, returning the address of a page.
+#[allow(dead_code)]
+pub fn serve_html(html: &'static str) -> String {
+    let page = warp::any().map(move || warp::reply::html(html)).boxed();
+    format!("http://{}/page", serve(page))
+}
+
+/// Serve an empty html page, see [`serve_html`].
+#[allow(dead_code)]
+pub fn serve_page() -> String {
+    serve_html("<html><head></head><body></body></html>")
+}
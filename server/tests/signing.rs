@@ -0,0 +1,48 @@
+use server::{signing, Config};
+mod setup;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_session_key_rotation() {
+    let (_, before) = setup::mock_with_config(Config::test().with_session_keys(&["key-b"])).await;
+    let old_token = signing::sign(&before, "test", "signed with b");
+
+    // key a becomes primary, key b remains valid
+    let (_, during) =
+        setup::mock_with_config(Config::test().with_session_keys(&["key-a", "key-b"])).await;
+    let new_token = signing::sign(&during, "test", "signed with a");
+    assert_ne!(
+        signing::sign(&during, "test", "signed with b"),
+        old_token,
+        "New tokens are signed with the primary key"
+    );
+
+    let hits = signing::non_primary_key_verifications();
+    assert_eq!(
+        signing::verify(&during, "test", &old_token).as_deref(),
+        Some("signed with b")
+    );
+    assert!(signing::non_primary_key_verifications() > hits);
+    assert_eq!(
+        signing::verify(&during, "test", &new_token).as_deref(),
+        Some("signed with a")
+    );
+    assert_eq!(signing::verify(&during, "other", &old_token), None);
+
+    // once key b is dropped, its tokens are no longer valid
+    let (_, after) = setup::mock_with_config(Config::test().with_session_keys(&["key-a"])).await;
+    assert_eq!(signing::verify(&after, "test", &old_token), None);
+    assert_eq!(
+        signing::verify(&after, "test", &new_token).as_deref(),
+        Some("signed with a")
+    );
+    assert_eq!(signing::verify(&before, "test", &new_token), None);
+}
+
+#[test]
+fn test_key_fingerprints() {
+    let fingerprint = signing::fingerprint(b"key-a");
+    assert_eq!(fingerprint.len(), 8);
+    assert_eq!(fingerprint, signing::fingerprint(b"key-a"));
+    assert_ne!(fingerprint, signing::fingerprint(b"key-b"));
+    assert!(!fingerprint.contains("key"));
+}
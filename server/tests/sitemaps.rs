@@ -0,0 +1,206 @@
+use chrono::{Duration, SecondsFormat};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use server::db::id::UrlID;
+use server::db::models::User;
+use server::schema::urls;
+use server::{Config, Context};
+mod setup;
+
+/// The time `minutes` after the mock context was created, as
+/// listed in sitemaps.
+fn lastmod(ctx: &Context, minutes: i64) -> String {
+    (ctx.now() + Duration::minutes(minutes)).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// The text of all elements with the given name, in order,
+/// with entities decoded.
+fn elements(body: &str, name: &str) -> Vec<String> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    body.split(&open)
+        .skip(1)
+        .map(|rest| {
+            rest.split(&close)
+                .next()
+                .unwrap()
+                .replace("&#x2f;", "/")
+                .replace("&amp;", "&")
+        })
+        .collect()
+}
+
+/// Fetch the sitemap at the given path, checking that it was
+/// served as a cacheable XML document with the given root.
+macro_rules! sitemap {
+    ($server:expr, $path:expr, $root:expr) => {{
+        let res = warp::test::request().path($path).reply($server).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(
+            res.headers()["Content-Type"],
+            "application/xml; charset=utf-8"
+        );
+        assert_eq!(res.headers()["Cache-Control"], "public, max-age=3600");
+        let body = String::from_utf8(res.body().to_vec()).unwrap();
+        assert!(body.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+        let root = format!(
+            "<{} xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">",
+            $root
+        );
+        assert!(body.contains(&root));
+        assert!(body.trim_end().ends_with(&format!("</{}>", $root)));
+        body
+    }};
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sitemap() {
+    let (server, ctx) = setup::mock().await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    let commented = setup::submit(&ctx, user.id()).await;
+    setup::Comment::on(commented, user.id())
+        .created_at(ctx.now() + Duration::minutes(30))
+        .insert(&ctx)
+        .await;
+    setup::Comment::on(commented, user.id())
+        .created_at(ctx.now() + Duration::minutes(20))
+        .insert(&ctx)
+        .await;
+    let edited = setup::Submission::by(user.id())
+        .created_at(ctx.now() + Duration::minutes(1))
+        .insert(&ctx)
+        .await;
+    let edited_at = (ctx.now() + Duration::minutes(10)).naive_utc();
+    diesel::update(urls::table.find(edited))
+        .set(urls::dsl::edited_at.eq(edited_at))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let plain = setup::Submission::by(user.id())
+        .created_at(ctx.now() + Duration::minutes(2))
+        .insert(&ctx)
+        .await;
+
+    let deleted = setup::Submission::by(user.id())
+        .created_at(ctx.now() + Duration::minutes(3))
+        .insert(&ctx)
+        .await;
+    diesel::update(urls::table.find(deleted))
+        .set(urls::dsl::deleted_at.eq(ctx.now().naive_utc()))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let removed = setup::Submission::by(user.id())
+        .created_at(ctx.now() + Duration::minutes(4))
+        .insert(&ctx)
+        .await;
+    diesel::update(urls::table.find(removed))
+        .set(urls::dsl::removed_at.eq(ctx.now().naive_utc()))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let shadow_removed = setup::Submission::by(user.id())
+        .created_at(ctx.now() + Duration::minutes(5))
+        .insert(&ctx)
+        .await;
+    diesel::update(urls::table.find(shadow_removed))
+        .set((
+            urls::dsl::removed_at.eq(ctx.now().naive_utc()),
+            urls::dsl::shadow_removed.eq(true),
+        ))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let draft = setup::Submission::by(user.id())
+        .created_at(ctx.now() + Duration::minutes(6))
+        .insert(&ctx)
+        .await;
+    diesel::update(urls::table.find(draft))
+        .set(urls::dsl::draft.eq(true))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+    let nsfw = setup::Submission::by(user.id())
+        .created_at(ctx.now() + Duration::minutes(7))
+        .insert(&ctx)
+        .await;
+    diesel::update(urls::table.find(nsfw))
+        .set(urls::dsl::nsfw.eq(true))
+        .execute(&*ctx.conn().await.unwrap())
+        .unwrap();
+
+    // oldest first, dated by the latest comment or edit
+    let body = sitemap!(&server, "/sitemap.xml", "urlset");
+    assert_eq!(
+        elements(&body, "loc"),
+        [commented, edited, plain]
+            .iter()
+            .map(|id| format!("https://localhost/comments/{}", id))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        elements(&body, "lastmod"),
+        vec![lastmod(&ctx, 30), lastmod(&ctx, 10), lastmod(&ctx, 2)]
+    );
+
+    let body = sitemap!(&server, "/sitemaps/1.xml", "urlset");
+    assert_eq!(elements(&body, "loc").len(), 3);
+    let res = warp::test::request()
+        .path("/sitemaps/2.xml")
+        .reply(&server)
+        .await;
+    assert_eq!(res.status(), 404);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sitemap_index() {
+    let (server, ctx) = setup::mock_with_config(Config::test().with_sitemap_size(2)).await;
+    let user = User::find_by_email(&ctx, "test.user@urls.fyi")
+        .await
+        .unwrap();
+
+    // a single sitemap while the submissions fit
+    let mut ids = vec![];
+    for minutes in 0..2 {
+        ids.push(
+            setup::Submission::by(user.id())
+                .created_at(ctx.now() + Duration::minutes(minutes))
+                .insert(&ctx)
+                .await,
+        );
+    }
+    let body = sitemap!(&server, "/sitemap.xml", "urlset");
+    assert_eq!(elements(&body, "loc").len(), 2);
+
+    for minutes in 2..5 {
+        ids.push(
+            setup::Submission::by(user.id())
+                .created_at(ctx.now() + Duration::minutes(minutes))
+                .insert(&ctx)
+                .await,
+        );
+    }
+    let body = sitemap!(&server, "/sitemap.xml", "sitemapindex");
+    assert_eq!(
+        elements(&body, "loc"),
+        vec![
+            "https://localhost/sitemaps/1.xml",
+            "https://localhost/sitemaps/2.xml",
+            "https://localhost/sitemaps/3.xml",
+        ]
+    );
+
+    let locs = |ids: &[UrlID]| -> Vec<String> {
+        ids.iter()
+            .map(|id| format!("https://localhost/comments/{}", id))
+            .collect()
+    };
+    let body = sitemap!(&server, "/sitemaps/1.xml", "urlset");
+    assert_eq!(elements(&body, "loc"), locs(&ids[0..2]));
+    let body = sitemap!(&server, "/sitemaps/2.xml", "urlset");
+    assert_eq!(elements(&body, "loc"), locs(&ids[2..4]));
+    let body = sitemap!(&server, "/sitemaps/3.xml", "urlset");
+    assert_eq!(elements(&body, "loc"), locs(&ids[4..5]));
+
+    for path in ["/sitemaps/0.xml", "/sitemaps/4.xml", "/sitemaps/one.xml"] {
+        let res = warp::test::request().path(path).reply(&server).await;
+        assert_eq!(res.status(), 404);
+    }
+}
@@ -14,12 +14,20 @@ export default function SubmitUrl() {
   const { commit, inFlight } = useMutation(graphql`
     mutation SubmitUrl($url: String!) {
       submitUrl(input: { url: $url }) {
-        id
-        title
+        url {
+          id
+          title
+          slug
+        }
+        duplicate
       }
     }
   `, {
-    onCommit: ({ submitUrl: { id, title } }) => {
+    onCommit: ({ submitUrl: { url: { id, title, slug }, duplicate } }) => {
+      if (duplicate) {
+        window.location.href = `/comments/${id}/${slug}`;
+        return;
+      }
       setUrl("");
       setError(null);
       if (title) {
@@ -39,12 +39,11 @@ function Login() {
   const login = useMutation(
     graphql`
     mutation Login($email: String!, $code: String!) {
-      login(email: $email, token: $code)
+      login(email: $email, token: $code, setCookie: true)
     }
   `,
     {
-      onCommit: ({ login }) => {
-        document.cookie = `session=${login};path=/;max-age=7776000`;
+      onCommit: () => {
         window.location.href = "/";
       },
       onError: (errors) => {